@@ -0,0 +1,130 @@
+//! Auto view: each received line is shown as text when enough of it is
+//! printable and as an inline hex dump otherwise, behind a badge saying
+//! which. Lines are judged whole, once reassembled, so where reads happened
+//! to split the data does not change how a line is shown.
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PRINTABLE_PERCENT: u8 = 90;
+pub const MIN_PRINTABLE_PERCENT: u8 = 50;
+pub const TEXT_BADGE: &str = "TXT│ ";
+pub const HEX_BADGE: &str = "HEX│ ";
+/// Starts the further rows of a hex dump, as wide as a badge.
+const HEX_CONTINUATION: &str = "   │ ";
+/// Bytes per row of an inline hex dump.
+pub const HEX_ROW_BYTES: usize = 16;
+
+/// Auto view settings, saved with the global settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoViewConfig {
+    /// Share of a line's bytes, without its line ending, that must be
+    /// printable for it to be shown as text.
+    pub printable_percent: u8,
+}
+
+impl Default for AutoViewConfig {
+    fn default() -> Self {
+        Self {
+            printable_percent: DEFAULT_PRINTABLE_PERCENT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rendering {
+    Text,
+    Hex,
+}
+
+impl Rendering {
+    pub fn badge(&self) -> &'static str {
+        match self {
+            Rendering::Text => TEXT_BADGE,
+            Rendering::Hex => HEX_BADGE,
+        }
+    }
+}
+
+/// Percentage of `line`'s bytes that are printable, not counting a trailing
+/// `\n` or `\r\n`. Tabs, CR, ESC (for ANSI colors) and valid UTF-8 beyond
+/// ASCII count as printable; other control characters and bytes that are not
+/// UTF-8 do not. An empty line is all printable.
+pub fn printable_percent(line: &[u8]) -> f32 {
+    let content = line.strip_suffix(b"\n").unwrap_or(line);
+    let content = content.strip_suffix(b"\r").unwrap_or(content);
+    if content.is_empty() {
+        return 100.0;
+    }
+    let printable: usize = content
+        .utf8_chunks()
+        .flat_map(|chunk| chunk.valid().chars())
+        .filter(|&c| !c.is_control() || matches!(c, '\t' | '\r' | '\x1b'))
+        .map(char::len_utf8)
+        .sum();
+    printable as f32 * 100.0 / content.len() as f32
+}
+
+/// How the Auto view shows `line`.
+pub fn classify(line: &[u8], config: &AutoViewConfig) -> Rendering {
+    if printable_percent(line) >= f32::from(config.printable_percent) {
+        Rendering::Text
+    } else {
+        Rendering::Hex
+    }
+}
+
+/// `line` as badged rows of hex bytes, its line ending included. The last
+/// row ends with a newline only when the line did, so a line still arriving
+/// continues on the same row once the rest is rendered.
+pub fn hex_rows(line: &[u8]) -> String {
+    let mut out = String::with_capacity(line.len() * 3 + HEX_BADGE.len());
+    for (index, row) in line.chunks(HEX_ROW_BYTES).enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        out.push_str(if index == 0 { HEX_BADGE } else { HEX_CONTINUATION });
+        let bytes: Vec<String> = row.iter().map(|b| format!("{:02X}", b)).collect();
+        out.push_str(&bytes.join(" "));
+    }
+    if line.ends_with(b"\n") {
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn judges_lines_by_their_printable_share() {
+        let config = AutoViewConfig::default();
+        assert_eq!(classify(b"boot ok\r\n", &config), Rendering::Text);
+        assert_eq!(classify(b"\r\n", &config), Rendering::Text);
+        assert_eq!(classify("T=21 °C\tstatus → ok\n".as_bytes(), &config), Rendering::Text);
+        assert_eq!(classify(b"\x1b[31mERR\x1b[0m\n", &config), Rendering::Text);
+        // Modbus reply without a 0x0A, which would end the line: control bytes and invalid UTF-8
+        assert_eq!(classify(&[0x01, 0x03, 0x02, 0x00, 0x07, 0xF9, 0x86], &config), Rendering::Hex);
+
+        // One NUL in ten bytes is 90% printable: text at 90, hex above
+        let line = b"ABCD\0EFGHI\r\n";
+        assert_eq!(printable_percent(line), 90.0);
+        assert_eq!(classify(line, &config), Rendering::Text);
+        let strict = AutoViewConfig { printable_percent: 95 };
+        assert_eq!(classify(line, &strict), Rendering::Hex);
+        assert_eq!(Rendering::Hex.badge(), HEX_BADGE);
+    }
+
+    #[test]
+    fn hex_rows_break_every_sixteen_bytes() {
+        let line: Vec<u8> = (0x00..0x12).chain([b'\n']).collect();
+        assert_eq!(
+            hex_rows(&line),
+            "HEX│ 00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F\n   │ 10 11 0A\n"
+        );
+        // Without its line ending yet, the last row stays open
+        assert_eq!(hex_rows(&[0xFF, 0xFE]), "HEX│ FF FE");
+        assert_eq!(hex_rows(&[]), "");
+    }
+}
@@ -0,0 +1,140 @@
+//! Groups RX chunks into bursts separated by idle gaps
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+
+/// Idle time that ends a burst unless configured otherwise.
+pub const DEFAULT_BURST_GAP_MS: u64 = 20;
+
+/// A run of chunks with no gap longer than the threshold between them.
+#[derive(Debug, Clone)]
+pub struct Burst {
+    pub started_at: DateTime<Local>,
+    pub size: usize,
+    pub duration: Duration,
+    /// Idle time since the previous burst ended; `None` for the first burst.
+    pub gap_before: Option<Duration>,
+}
+
+impl Burst {
+    /// Short description, e.g. "64 B in 5.2 ms, after 120.0 ms idle".
+    pub fn label(&self) -> String {
+        let mut label = format!("{} B in {:.1} ms", self.size, ms(self.duration));
+        if let Some(gap) = self.gap_before {
+            label.push_str(&format!(", after {:.1} ms idle", ms(gap)));
+        }
+        label
+    }
+}
+
+/// A duration in fractional milliseconds, for display.
+pub fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+struct OpenBurst {
+    started_at: DateTime<Local>,
+    first: Instant,
+    last: Instant,
+    size: usize,
+    gap_before: Option<Duration>,
+}
+
+/// Splits a stream of RX chunks into bursts by the idle time between them.
+#[derive(Default)]
+pub struct BurstTracker {
+    current: Option<OpenBurst>,
+    last_end: Option<Instant>,
+}
+
+impl BurstTracker {
+    /// Record a chunk of `len` bytes. Returns the previous burst if this chunk
+    /// arrived after a gap longer than `gap` and so starts a new one.
+    pub fn feed(&mut self, len: usize, now: Instant, gap: Duration) -> Option<Burst> {
+        let closed = self.poll(now, gap);
+
+        match self.current.as_mut() {
+            Some(open) => {
+                open.last = now;
+                open.size += len;
+            }
+            None => {
+                self.current = Some(OpenBurst {
+                    started_at: Local::now(),
+                    first: now,
+                    last: now,
+                    size: len,
+                    gap_before: self.last_end.map(|end| now.saturating_duration_since(end)),
+                });
+            }
+        }
+        closed
+    }
+
+    /// Close the open burst once the line has been idle for longer than `gap`.
+    pub fn poll(&mut self, now: Instant, gap: Duration) -> Option<Burst> {
+        let idle = self
+            .current
+            .as_ref()
+            .is_some_and(|open| now.saturating_duration_since(open.last) > gap);
+        if idle {
+            self.close()
+        } else {
+            None
+        }
+    }
+
+    fn close(&mut self) -> Option<Burst> {
+        let open = self.current.take()?;
+        self.last_end = Some(open.last);
+        Some(Burst {
+            started_at: open.started_at,
+            size: open.size,
+            duration: open.last.saturating_duration_since(open.first),
+            gap_before: open.gap_before,
+        })
+    }
+
+    /// Forget the open burst and the time of the last one.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAP: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn chunks_within_gap_form_one_burst() {
+        let mut tracker = BurstTracker::default();
+        let t0 = Instant::now();
+        assert!(tracker.feed(10, t0, GAP).is_none());
+        assert!(tracker.feed(20, t0 + Duration::from_millis(5), GAP).is_none());
+        assert!(tracker.poll(t0 + Duration::from_millis(15), GAP).is_none());
+
+        let burst = tracker.poll(t0 + Duration::from_millis(30), GAP).unwrap();
+        assert_eq!(burst.size, 30);
+        assert_eq!(burst.duration, Duration::from_millis(5));
+        assert_eq!(burst.gap_before, None);
+        assert_eq!(burst.label(), "30 B in 5.0 ms");
+    }
+
+    #[test]
+    fn late_chunk_closes_previous_burst_and_records_gap() {
+        let mut tracker = BurstTracker::default();
+        let t0 = Instant::now();
+        tracker.feed(4, t0, GAP);
+        let first = tracker.feed(8, t0 + Duration::from_millis(100), GAP).unwrap();
+        assert_eq!(first.size, 4);
+
+        let second = tracker.poll(t0 + Duration::from_millis(200), GAP).unwrap();
+        assert_eq!(second.size, 8);
+        assert_eq!(second.gap_before, Some(Duration::from_millis(100)));
+        assert_eq!(second.label(), "8 B in 0.0 ms, after 100.0 ms idle");
+        assert!(tracker.poll(t0 + Duration::from_millis(300), GAP).is_none());
+    }
+}
@@ -0,0 +1,450 @@
+//! Cable and adapter self-test: known patterns sent through a loopback plug,
+//! or across a null-modem cable between two ports, at several baud rates and
+//! checked byte for byte, plus a check that the handshake lines are wired.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use serialport::{ClearBuffer, SerialPort};
+
+use crate::port_config::{char_time, DataBits, Parity, StopBits};
+
+/// Baud rates offered for testing.
+pub const TEST_BAUD_RATES: [u32; 8] = [
+    9600, 19_200, 38_400, 57_600, 115_200, 230_400, 460_800, 921_600,
+];
+
+pub const DEFAULT_PATTERN_LEN: usize = 1024;
+
+const WRITE_CHUNK: usize = 64;
+
+/// Bytes written ahead of what has come back, so a port without flow control
+/// cannot overrun its own receive buffer during the test.
+const IN_FLIGHT_LIMIT: usize = 512;
+
+/// Extra time allowed beyond the pattern's wire time for it to come back.
+const RETURN_MARGIN: Duration = Duration::from_millis(500);
+
+/// Time for a handshake line change to reach the other side.
+const LINE_SETTLE: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestPattern {
+    /// 00, 01, 02 ... FF, repeating.
+    Incrementing,
+    /// A single set bit moving through the byte: 01, 02, 04 ... 80.
+    WalkingOnes,
+    /// Reproducible pseudo-random bytes.
+    Random,
+}
+
+impl TestPattern {
+    pub const ALL: [TestPattern; 3] = [
+        TestPattern::Incrementing,
+        TestPattern::WalkingOnes,
+        TestPattern::Random,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            TestPattern::Incrementing => "Incrementing",
+            TestPattern::WalkingOnes => "Walking ones",
+            TestPattern::Random => "Pseudo-random",
+        }
+    }
+
+    pub fn generate(&self, len: usize) -> Vec<u8> {
+        match self {
+            TestPattern::Incrementing => (0..len).map(|i| i as u8).collect(),
+            TestPattern::WalkingOnes => (0..len).map(|i| 1u8 << (i % 8)).collect(),
+            TestPattern::Random => {
+                // xorshift32 with a fixed seed, so a failure can be reproduced
+                let mut state: u32 = 0x2545_F491;
+                (0..len)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 17;
+                        state ^= state << 5;
+                        (state >> 24) as u8
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CableTestConfig {
+    pub baud_rates: Vec<u32>,
+    pub patterns: Vec<TestPattern>,
+    pub pattern_len: usize,
+    /// Toggle RTS and DTR and check they arrive as CTS and DSR.
+    pub check_handshake: bool,
+}
+
+impl Default for CableTestConfig {
+    fn default() -> Self {
+        Self {
+            baud_rates: vec![9600, 115_200],
+            patterns: TestPattern::ALL.to_vec(),
+            pattern_len: DEFAULT_PATTERN_LEN,
+            check_handshake: true,
+        }
+    }
+}
+
+/// Result of one test.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestOutcome {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+    /// Bytes per second, for pattern tests that came back clean.
+    pub throughput: Option<f64>,
+}
+
+impl TestOutcome {
+    fn fail(name: String, detail: String) -> Self {
+        Self {
+            name,
+            passed: false,
+            detail,
+            throughput: None,
+        }
+    }
+
+    /// One line of the summary, e.g. "PASS  Incrementing @ 9600  1024/1024 bytes ...".
+    pub fn line(&self) -> String {
+        format!(
+            "{}  {}  {}",
+            if self.passed { "PASS" } else { "FAIL" },
+            self.name,
+            self.detail
+        )
+    }
+}
+
+/// Wrong or missing bytes, plus any extra ones, comparing position by position.
+pub fn count_errors(sent: &[u8], received: &[u8]) -> usize {
+    let mismatched = sent.iter().zip(received).filter(|(a, b)| a != b).count();
+    mismatched + sent.len().abs_diff(received.len())
+}
+
+/// "12.3 KB/s" or "937 B/s".
+pub fn format_rate(bytes_per_second: f64) -> String {
+    if bytes_per_second >= 1024.0 {
+        format!("{:.1} KB/s", bytes_per_second / 1024.0)
+    } else {
+        format!("{:.0} B/s", bytes_per_second)
+    }
+}
+
+/// The port data comes back on: the second port of a null-modem pair, or the
+/// sending port itself with a loopback plug.
+fn receiver<'a, 'p>(
+    tx: &'a mut (dyn SerialPort + 'p),
+    rx: Option<&'a mut (dyn SerialPort + 'p)>,
+) -> &'a mut (dyn SerialPort + 'p) {
+    match rx {
+        Some(rx) => rx,
+        None => tx,
+    }
+}
+
+fn is_timeout(e: &std::io::Error) -> bool {
+    e.kind() == std::io::ErrorKind::TimedOut
+}
+
+/// Send one pattern and check what comes back. `None` when cancelled.
+pub fn run_pattern<'p>(
+    tx: &mut (dyn SerialPort + 'p),
+    mut rx: Option<&mut (dyn SerialPort + 'p)>,
+    pattern: TestPattern,
+    len: usize,
+    baud: u32,
+    cancel: &AtomicBool,
+) -> Option<TestOutcome> {
+    let name = format!("{} @ {}", pattern.as_str(), baud);
+    let mut set_baud = tx.set_baud_rate(baud);
+    if let Some(rx) = rx.as_deref_mut() {
+        set_baud = set_baud.and_then(|_| rx.set_baud_rate(baud));
+    }
+    if let Err(e) = set_baud {
+        return Some(TestOutcome::fail(
+            name,
+            format!("cannot set baud rate: {}", e),
+        ));
+    }
+    let _ = receiver(tx, rx.as_deref_mut()).clear(ClearBuffer::All);
+
+    let data = pattern.generate(len);
+    let wire_time = char_time(baud, DataBits::Eight, Parity::None, StopBits::One) * len as u32;
+    let start = Instant::now();
+    let deadline = start + wire_time * 2 + RETURN_MARGIN;
+    let mut last_byte_at = start;
+    let mut written = 0;
+    let mut received = Vec::with_capacity(len);
+    let mut buf = [0u8; 1024];
+
+    while received.len() < len && Instant::now() < deadline {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        if written < len && written - received.len().min(written) < IN_FLIGHT_LIMIT {
+            let end = (written + WRITE_CHUNK).min(len);
+            match tx.write(&data[written..end]) {
+                Ok(count) => written += count,
+                Err(e) if is_timeout(&e) => {}
+                Err(e) => return Some(TestOutcome::fail(name, format!("write failed: {}", e))),
+            }
+        }
+
+        let port = receiver(tx, rx.as_deref_mut());
+        if port.bytes_to_read().unwrap_or(1) == 0 {
+            std::thread::sleep(Duration::from_millis(1));
+            continue;
+        }
+        match port.read(&mut buf) {
+            Ok(count) if count > 0 => {
+                received.extend_from_slice(&buf[..count]);
+                last_byte_at = Instant::now();
+            }
+            Ok(_) => {}
+            Err(e) if is_timeout(&e) => {}
+            Err(e) => return Some(TestOutcome::fail(name, format!("read failed: {}", e))),
+        }
+    }
+
+    let errors = count_errors(&data, &received);
+    let elapsed = last_byte_at.duration_since(start).as_secs_f64();
+    let throughput = (errors == 0 && elapsed > 0.0).then(|| received.len() as f64 / elapsed);
+    let mut detail = format!("{}/{} bytes, {} errors", received.len(), len, errors);
+    if errors > 0 {
+        detail.push_str(&format!(
+            " ({:.2}%)",
+            errors as f64 * 100.0 / len.max(1) as f64
+        ));
+    }
+    if let Some(rate) = throughput {
+        detail.push_str(&format!(", {}", format_rate(rate)));
+    }
+    Some(TestOutcome {
+        name,
+        passed: errors == 0,
+        detail,
+        throughput,
+    })
+}
+
+/// Drive an output line high, low and high again and check the input follows.
+fn check_line<'p>(
+    name: &str,
+    tx: &mut (dyn SerialPort + 'p),
+    mut rx: Option<&mut (dyn SerialPort + 'p)>,
+    set: fn(&mut dyn SerialPort, bool) -> serialport::Result<()>,
+    read: fn(&mut dyn SerialPort) -> serialport::Result<bool>,
+) -> TestOutcome {
+    for level in [true, false, true] {
+        if let Err(e) = set(tx, level) {
+            return TestOutcome::fail(name.to_string(), format!("cannot set line: {}", e));
+        }
+        std::thread::sleep(LINE_SETTLE);
+        match read(receiver(tx, rx.as_deref_mut())) {
+            Ok(seen) if seen == level => {}
+            Ok(seen) => {
+                return TestOutcome::fail(
+                    name.to_string(),
+                    format!("set {}, read {}: not wired", level, seen),
+                )
+            }
+            Err(e) => {
+                return TestOutcome::fail(name.to_string(), format!("cannot read line: {}", e))
+            }
+        }
+    }
+    TestOutcome {
+        name: name.to_string(),
+        passed: true,
+        detail: "follows high and low".to_string(),
+        throughput: None,
+    }
+}
+
+/// Run every configured test, reporting each result as it completes. Stops
+/// early when `cancel` is set.
+pub fn run<'p>(
+    tx: &mut (dyn SerialPort + 'p),
+    mut rx: Option<&mut (dyn SerialPort + 'p)>,
+    config: &CableTestConfig,
+    cancel: &AtomicBool,
+    mut report: impl FnMut(TestOutcome),
+) {
+    for &baud in &config.baud_rates {
+        for &pattern in &config.patterns {
+            match run_pattern(
+                tx,
+                rx.as_deref_mut(),
+                pattern,
+                config.pattern_len,
+                baud,
+                cancel,
+            ) {
+                Some(outcome) => report(outcome),
+                None => return,
+            }
+        }
+    }
+
+    if config.check_handshake && !cancel.load(Ordering::Relaxed) {
+        report(check_line(
+            "RTS -> CTS",
+            tx,
+            rx.as_deref_mut(),
+            |port, level| port.write_request_to_send(level),
+            |port| port.read_clear_to_send(),
+        ));
+        report(check_line(
+            "DTR -> DSR",
+            tx,
+            rx,
+            |port, level| port.write_data_terminal_ready(level),
+            |port| port.read_data_set_ready(),
+        ));
+    }
+}
+
+/// Plain-text report: one line per test, then the overall verdict and the best
+/// clean throughput.
+pub fn summary(outcomes: &[TestOutcome]) -> String {
+    let mut text: String = outcomes.iter().map(|o| o.line() + "\n").collect();
+    let passed = outcomes.iter().filter(|o| o.passed).count();
+    let verdict = if passed == outcomes.len() && !outcomes.is_empty() {
+        "PASS"
+    } else {
+        "FAIL"
+    };
+    text.push_str(&format!(
+        "Overall: {} ({}/{} passed)",
+        verdict,
+        passed,
+        outcomes.len()
+    ));
+    let best = outcomes
+        .iter()
+        .filter_map(|o| o.throughput.map(|rate| (rate, &o.name)))
+        .max_by(|a, b| a.0.total_cmp(&b.0));
+    if let Some((rate, name)) = best {
+        text.push_str(&format!(
+            ", max clean throughput {} ({})",
+            format_rate(rate),
+            name
+        ));
+    }
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_port::MockPort;
+
+    #[test]
+    fn patterns_have_their_shapes() {
+        assert_eq!(
+            TestPattern::Incrementing.generate(258)[255..],
+            [0xFF, 0x00, 0x01]
+        );
+        assert_eq!(
+            TestPattern::WalkingOnes.generate(9),
+            [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x01]
+        );
+        let random = TestPattern::Random.generate(4096);
+        assert_eq!(random, TestPattern::Random.generate(4096));
+        let distinct: std::collections::HashSet<u8> = random.iter().copied().collect();
+        assert!(distinct.len() > 250);
+    }
+
+    #[test]
+    fn errors_count_wrong_missing_and_extra_bytes() {
+        assert_eq!(count_errors(b"abcd", b"abcd"), 0);
+        assert_eq!(count_errors(b"abcd", b"abXd"), 1);
+        assert_eq!(count_errors(b"abcd", b"ab"), 2);
+        assert_eq!(count_errors(b"ab", b"abcd"), 2);
+    }
+
+    #[test]
+    fn loopback_plug_passes_everything() {
+        let port = MockPort::default();
+        port.set_loopback(true);
+        let mut tx: Box<dyn SerialPort> = Box::new(port.clone());
+        let config = CableTestConfig::default();
+        let mut outcomes = Vec::new();
+        run(tx.as_mut(), None, &config, &AtomicBool::new(false), |o| {
+            outcomes.push(o)
+        });
+
+        assert_eq!(outcomes.len(), 2 * 3 + 2);
+        assert!(outcomes.iter().all(|o| o.passed), "{}", summary(&outcomes));
+        assert_eq!(outcomes[0].name, "Incrementing @ 9600");
+        assert!(outcomes[0].detail.starts_with("1024/1024 bytes, 0 errors"));
+        assert!(summary(&outcomes).contains("Overall: PASS (8/8 passed)"));
+    }
+
+    #[test]
+    fn missing_wiring_fails_with_details() {
+        // Nothing comes back and no lines are connected
+        let mut tx: Box<dyn SerialPort> = Box::new(MockPort::default());
+        let config = CableTestConfig {
+            baud_rates: vec![921_600],
+            patterns: vec![TestPattern::Incrementing],
+            pattern_len: 16,
+            check_handshake: true,
+        };
+        let mut outcomes = Vec::new();
+        run(tx.as_mut(), None, &config, &AtomicBool::new(false), |o| {
+            outcomes.push(o)
+        });
+
+        assert_eq!(outcomes.len(), 3);
+        assert!(outcomes.iter().all(|o| !o.passed));
+        assert_eq!(outcomes[0].detail, "0/16 bytes, 16 errors (100.00%)");
+        assert_eq!(outcomes[1].detail, "set true, read false: not wired");
+        assert!(summary(&outcomes).contains("Overall: FAIL (0/3 passed)"));
+    }
+
+    #[test]
+    fn null_modem_reads_from_the_second_port() {
+        // Two ports sharing one loopback state stand in for a crossed cable
+        let port = MockPort::default();
+        port.set_loopback(true);
+        let mut a: Box<dyn SerialPort> = Box::new(port.clone());
+        let mut b: Box<dyn SerialPort> = Box::new(port.clone());
+        let outcome = run_pattern(
+            a.as_mut(),
+            Some(b.as_mut()),
+            TestPattern::Random,
+            300,
+            115_200,
+            &AtomicBool::new(false),
+        );
+        assert!(outcome.unwrap().passed);
+    }
+
+    #[test]
+    fn cancel_stops_before_the_next_test() {
+        let port = MockPort::default();
+        port.set_loopback(true);
+        let mut tx: Box<dyn SerialPort> = Box::new(port);
+        let mut outcomes = Vec::new();
+        run(
+            tx.as_mut(),
+            None,
+            &CableTestConfig::default(),
+            &AtomicBool::new(true),
+            |o| outcomes.push(o),
+        );
+        assert!(outcomes.is_empty());
+    }
+}
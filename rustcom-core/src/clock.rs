@@ -0,0 +1,114 @@
+//! The wall clock checked against the monotonic clock between RX chunks.
+//! While the machine sleeps the monotonic clock stands still and the wall
+//! clock runs on, and setting the clock moves only the wall clock, so a
+//! disagreement splits the capture into segments whose timing cannot be
+//! compared across the split. Timestamps stay wall-clock; deltas are only
+//! meaningful within a segment.
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, TimeDelta};
+
+/// Disagreement tolerated before a discontinuity is reported, well above
+/// scheduling jitter and NTP slewing.
+pub const CLOCK_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// Start of the event text logged for a discontinuity.
+pub const CLOCK_JUMP_PREFIX: &str = "Clock discontinuity";
+
+/// The wall clock moved `offset` more than the monotonic clock (negative when
+/// it went back) between two observations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockJump {
+    pub offset: TimeDelta,
+    pub at: DateTime<Local>,
+}
+
+impl ClockJump {
+    pub fn label(&self) -> String {
+        let cause = if self.offset > TimeDelta::zero() { "sleep or clock change" } else { "clock set back" };
+        format!("{}: wall clock {} ({})", CLOCK_JUMP_PREFIX, format_offset(self.offset), cause)
+    }
+}
+
+/// Whether an event's text is a discontinuity logged by `ClockJump::label`.
+pub fn is_jump_marker(text: &[u8]) -> bool {
+    text.starts_with(CLOCK_JUMP_PREFIX.as_bytes())
+}
+
+/// `+8h 02m 10s`, `-1m 05s` or `+3.2 s`.
+fn format_offset(offset: TimeDelta) -> String {
+    let sign = if offset < TimeDelta::zero() { '-' } else { '+' };
+    let millis = offset.num_milliseconds().unsigned_abs();
+    let secs = millis / 1000;
+    if secs >= 3600 {
+        format!("{}{}h {:02}m {:02}s", sign, secs / 3600, secs % 3600 / 60, secs % 60)
+    } else if secs >= 60 {
+        format!("{}{}m {:02}s", sign, secs / 60, secs % 60)
+    } else {
+        format!("{}{:.1} s", sign, millis as f64 / 1000.0)
+    }
+}
+
+/// The time between two instants, negative when `later` is the earlier one.
+fn signed_between(earlier: Instant, later: Instant) -> TimeDelta {
+    let delta = |d: Duration| TimeDelta::from_std(d).unwrap_or(TimeDelta::MAX);
+    match later.checked_duration_since(earlier) {
+        Some(forward) => delta(forward),
+        None => -delta(earlier.duration_since(later)),
+    }
+}
+
+/// The last pair of clock readings, to compare the next pair against.
+#[derive(Debug, Default)]
+pub struct ClockWatch {
+    last: Option<(Instant, DateTime<Local>)>,
+}
+
+impl ClockWatch {
+    /// Record a pair of readings taken together; returns the discontinuity
+    /// since the previous pair, if the clocks disagree by more than `CLOCK_TOLERANCE`.
+    pub fn observe(&mut self, instant: Instant, wall: DateTime<Local>) -> Option<ClockJump> {
+        let (last_instant, last_wall) = self.last.replace((instant, wall))?;
+        let offset = (wall - last_wall) - signed_between(last_instant, instant);
+        let tolerance = TimeDelta::from_std(CLOCK_TOLERANCE).unwrap_or(TimeDelta::MAX);
+        (offset.abs() > tolerance).then_some(ClockJump { offset, at: wall })
+    }
+
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_sleep_and_clock_changes_only() {
+        let mut watch = ClockWatch::default();
+        let instant = Instant::now();
+        let wall = Local::now();
+        assert_eq!(watch.observe(instant, wall), None);
+
+        // Jitter between the two readings is ignored
+        let ms = |ms: u64| Duration::from_millis(ms);
+        assert_eq!(watch.observe(instant + ms(1000), wall + TimeDelta::milliseconds(1500)), None);
+
+        // Overnight sleep: one monotonic second, eight hours of wall clock
+        let base = wall + TimeDelta::hours(8) + TimeDelta::milliseconds(132_500);
+        let jump = watch.observe(instant + ms(2000), base).unwrap();
+        assert_eq!(jump.offset.num_seconds(), 8 * 3600 + 130);
+        assert_eq!(jump.label(), "Clock discontinuity: wall clock +8h 02m 10s (sleep or clock change)");
+        assert!(is_jump_marker(jump.label().as_bytes()));
+
+        // The clock set back a minute
+        let jump = watch.observe(instant + ms(3000), base - TimeDelta::seconds(64)).unwrap();
+        assert_eq!(jump.label(), "Clock discontinuity: wall clock -1m 05s (clock set back)");
+
+        // Readings handed over out of order are compared with their sign
+        assert_eq!(watch.observe(instant + ms(2500), base - TimeDelta::milliseconds(64_500)), None);
+        watch.reset();
+        assert_eq!(watch.observe(instant, wall), None);
+    }
+}
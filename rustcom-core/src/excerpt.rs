@@ -0,0 +1,367 @@
+//! Printable capture excerpts for protocol documentation: a byte range with
+//! offsets, hex and ASCII, and named, coloured field annotations, rendered as
+//! HTML or Markdown with inline styles so the snippet pastes anywhere.
+
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+/// Bytes per excerpt row, as in the hex view.
+pub const EXCERPT_ROW_BYTES: usize = 16;
+
+/// Colours handed to new annotations in turn.
+pub const ANNOTATION_COLORS: [[u8; 3]; 6] = [
+    [0xFF, 0xD5, 0x4F],
+    [0x81, 0xD4, 0xFA],
+    [0xA5, 0xD6, 0xA7],
+    [0xF4, 0x8F, 0xB1],
+    [0xCE, 0x93, 0xD8],
+    [0xFF, 0xAB, 0x91],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExcerptFormat {
+    Html,
+    /// Markdown with the dump as inline HTML, for renderers that allow it.
+    Markdown,
+}
+
+impl ExcerptFormat {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ExcerptFormat::Html => "HTML",
+            ExcerptFormat::Markdown => "Markdown",
+        }
+    }
+
+    /// File extension, without the dot.
+    pub fn extension(&self) -> &str {
+        match self {
+            ExcerptFormat::Html => "html",
+            ExcerptFormat::Markdown => "md",
+        }
+    }
+}
+
+/// A named field covering `len` bytes from `start`, relative to the excerpt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub name: String,
+    pub start: usize,
+    pub len: usize,
+    pub color: [u8; 3],
+}
+
+impl Annotation {
+    pub fn contains(&self, index: usize) -> bool {
+        index >= self.start && index < self.start + self.len
+    }
+}
+
+/// The bytes of a selection, where they sit in the capture, and their annotations.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Excerpt {
+    /// Capture offset of the first byte.
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+}
+
+fn css_color(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+/// Black or white, whichever reads better on `background`.
+fn text_color(background: [u8; 3]) -> &'static str {
+    let [r, g, b] = background.map(f64::from);
+    if 0.299 * r + 0.587 * g + 0.114 * b > 140.0 {
+        "#000"
+    } else {
+        "#fff"
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn ascii_char(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+impl Excerpt {
+    pub fn new(offset: u64, bytes: Vec<u8>) -> Self {
+        Self {
+            offset,
+            bytes,
+            annotations: Vec::new(),
+        }
+    }
+
+    /// Add a field over `start..start + len`, clamped to the excerpt, in the
+    /// next colour of the palette.
+    pub fn annotate(&mut self, name: &str, start: usize, len: usize) {
+        if self.bytes.is_empty() {
+            return;
+        }
+        let start = start.min(self.bytes.len().saturating_sub(1));
+        let len = len.clamp(1, self.bytes.len() - start);
+        let color = ANNOTATION_COLORS[self.annotations.len() % ANNOTATION_COLORS.len()];
+        self.annotations.push(Annotation {
+            name: name.to_string(),
+            start,
+            len,
+            color,
+        });
+    }
+
+    /// Problems that would make the rendered excerpt misleading.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.bytes.is_empty() {
+            return Err("The excerpt has no bytes".to_string());
+        }
+        for annotation in &self.annotations {
+            if annotation.name.trim().is_empty() {
+                return Err("Every annotation needs a name".to_string());
+            }
+            if annotation.len == 0 || annotation.start + annotation.len > self.bytes.len() {
+                return Err(format!(
+                    "Annotation '{}' runs past the {} bytes of the excerpt",
+                    annotation.name,
+                    self.bytes.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Which annotation colours byte `index`; the first one listed wins
+    /// where fields overlap.
+    pub fn annotation_index(&self, index: usize) -> Option<usize> {
+        self.annotations.iter().position(|a| a.contains(index))
+    }
+
+    pub fn render(&self, format: ExcerptFormat) -> String {
+        match format {
+            ExcerptFormat::Html => self.to_html(),
+            ExcerptFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    /// A standalone HTML page: the dump followed by a legend of the fields.
+    pub fn to_html(&self) -> String {
+        let mut out = String::from("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>");
+        out.push_str(&escape_html(&self.title()));
+        out.push_str("</title></head>\n<body>\n");
+        let _ = writeln!(out, "<p><strong>{}</strong></p>", escape_html(&self.title()));
+        out.push_str(&self.dump_html());
+        if !self.annotations.is_empty() {
+            out.push_str("<table style=\"border-collapse:collapse;font-family:sans-serif;font-size:13px\">\n");
+            out.push_str("<tr><th style=\"text-align:left;padding:2px 8px\">Field</th><th style=\"text-align:left;padding:2px 8px\">Bytes</th><th style=\"text-align:left;padding:2px 8px\">Value</th></tr>\n");
+            for annotation in &self.annotations {
+                let _ = writeln!(
+                    out,
+                    "<tr><td style=\"padding:2px 8px\"><span style=\"background:{};color:{};padding:0 4px\">{}</span></td><td style=\"padding:2px 8px\">{}</td><td style=\"padding:2px 8px;font-family:monospace\">{}</td></tr>",
+                    css_color(annotation.color),
+                    text_color(annotation.color),
+                    escape_html(&annotation.name),
+                    self.range_label(annotation),
+                    self.field_hex(annotation)
+                );
+            }
+            out.push_str("</table>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    /// Markdown: a heading line, the coloured dump as inline HTML and the
+    /// fields as a Markdown table.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("**{}**\n\n", self.title());
+        out.push_str(&self.dump_html());
+        if !self.annotations.is_empty() {
+            out.push_str("\n| Field | Bytes | Value |\n|---|---|---|\n");
+            for annotation in &self.annotations {
+                let _ = writeln!(
+                    out,
+                    "| <span style=\"background:{};color:{}\">{}</span> | {} | `{}` |",
+                    css_color(annotation.color),
+                    text_color(annotation.color),
+                    escape_html(&annotation.name).replace('|', "\\|"),
+                    self.range_label(annotation),
+                    self.field_hex(annotation)
+                );
+            }
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Cannot encode excerpt: {}", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Not an excerpt: {}", e))
+    }
+
+    fn title(&self) -> String {
+        format!(
+            "Offset 0x{:08X}, {} byte{}",
+            self.offset,
+            self.bytes.len(),
+            if self.bytes.len() == 1 { "" } else { "s" }
+        )
+    }
+
+    /// Capture offsets of a field, e.g. `0x00000010–0x00000013`.
+    fn range_label(&self, annotation: &Annotation) -> String {
+        let first = self.offset + annotation.start as u64;
+        let last = first + annotation.len.saturating_sub(1) as u64;
+        if first == last {
+            format!("0x{:08X}", first)
+        } else {
+            format!("0x{:08X}–0x{:08X}", first, last)
+        }
+    }
+
+    fn field_hex(&self, annotation: &Annotation) -> String {
+        let end = (annotation.start + annotation.len).min(self.bytes.len());
+        let hex: Vec<String> = self.bytes[annotation.start.min(end)..end]
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        hex.join(" ")
+    }
+
+    /// The offset/hex/ASCII rows in a `<pre>`, with each run of bytes that
+    /// belongs to one field wrapped in a coloured span in both columns.
+    fn dump_html(&self) -> String {
+        let mut out = String::from(
+            "<pre style=\"font-family:Consolas,Menlo,monospace;font-size:13px;line-height:1.4;background:#f6f8fa;color:#24292f;padding:8px\">",
+        );
+        for (row, chunk) in self.bytes.chunks(EXCERPT_ROW_BYTES).enumerate() {
+            let base = row * EXCERPT_ROW_BYTES;
+            let _ = write!(
+                out,
+                "<span style=\"color:#8c959f\">{:08X}</span>  ",
+                self.offset + base as u64
+            );
+            self.write_runs(&mut out, base, chunk, |byte| format!("{:02X}", byte), " ");
+            // Pad a short last row so the ASCII column lines up
+            out.push_str(&" ".repeat((EXCERPT_ROW_BYTES - chunk.len()) * 3));
+            out.push_str("  ");
+            self.write_runs(&mut out, base, chunk, |byte| escape_html(&ascii_char(byte).to_string()), "");
+            out.push('\n');
+        }
+        out.push_str("</pre>\n");
+        out
+    }
+
+    /// Write `chunk` with `cell` per byte joined by `gap`, grouping consecutive
+    /// bytes of the same field into one span so the gaps inside it are coloured too.
+    fn write_runs(&self, out: &mut String, base: usize, chunk: &[u8], cell: impl Fn(u8) -> String, gap: &str) {
+        let mut i = 0;
+        while i < chunk.len() {
+            let field = self.annotation_index(base + i);
+            let mut end = i + 1;
+            while end < chunk.len() && self.annotation_index(base + end) == field {
+                end += 1;
+            }
+            if i > 0 {
+                out.push_str(gap);
+            }
+            let cells: Vec<String> = chunk[i..end].iter().map(|&b| cell(b)).collect();
+            match field.map(|index| &self.annotations[index]) {
+                Some(annotation) => {
+                    let _ = write!(
+                        out,
+                        "<span title=\"{}\" style=\"background:{};color:{}\">{}</span>",
+                        escape_html(&annotation.name),
+                        css_color(annotation.color),
+                        text_color(annotation.color),
+                        cells.join(gap)
+                    );
+                }
+                None => out.push_str(&cells.join(gap)),
+            }
+            i = end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Excerpt {
+        let mut excerpt = Excerpt::new(0x20, b"\x02R01<x>\x03\x50".to_vec());
+        excerpt.annotate("STX", 0, 1);
+        excerpt.annotate("Command", 1, 3);
+        excerpt
+    }
+
+    #[test]
+    fn html_colours_fields_in_both_columns() {
+        let html = sample().to_html();
+        assert!(html.contains("<strong>Offset 0x00000020, 9 bytes</strong>"));
+        assert!(html.contains("<span style=\"color:#8c959f\">00000020</span>  "));
+        // The command run is one span in the hex column and one in the ASCII column
+        assert!(html.contains("<span title=\"Command\" style=\"background:#81d4fa;color:#000\">52 30 31</span> 3C"));
+        assert!(html.contains("<span title=\"Command\" style=\"background:#81d4fa;color:#000\">R01</span>&lt;x&gt;.P"));
+        assert!(html.contains("<span title=\"STX\" style=\"background:#ffd54f;color:#000\">.</span>"));
+        assert!(html.contains("<td style=\"padding:2px 8px\">0x00000021–0x00000023</td><td style=\"padding:2px 8px;font-family:monospace\">52 30 31</td>"));
+    }
+
+    #[test]
+    fn markdown_has_dump_and_field_table() {
+        let markdown = sample().to_markdown();
+        assert!(markdown.starts_with("**Offset 0x00000020, 9 bytes**\n\n<pre "));
+        assert!(markdown.contains("| Field | Bytes | Value |\n|---|---|---|\n"));
+        assert!(markdown.contains("| 0x00000020 | `02` |"));
+        assert!(markdown.contains("| 0x00000021–0x00000023 | `52 30 31` |"));
+    }
+
+    #[test]
+    fn rows_wrap_and_pad() {
+        let excerpt = Excerpt::new(0, (0u8..20).collect());
+        let html = excerpt.to_markdown();
+        let rows: Vec<&str> = html.lines().filter(|l| l.contains("color:#8c959f")).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[1].contains(">00000010</span>  10 11 12 13"));
+        // 12 missing bytes of padding, then the column gap
+        assert!(rows[1].ends_with(&format!("13{}  ....", " ".repeat(36))));
+    }
+
+    #[test]
+    fn annotations_are_clamped_and_validated() {
+        let mut excerpt = Excerpt::new(0, vec![1, 2, 3]);
+        excerpt.annotate("Tail", 2, 10);
+        assert_eq!(excerpt.annotations[0].len, 1);
+        assert_eq!(excerpt.annotations[0].color, ANNOTATION_COLORS[0]);
+        assert!(excerpt.validate().is_ok());
+
+        excerpt.annotations[0].len = 5;
+        assert!(excerpt.validate().unwrap_err().contains("runs past"));
+        excerpt.annotations[0].len = 1;
+        excerpt.annotations[0].name.clear();
+        assert!(excerpt.validate().is_err());
+        assert!(Excerpt::new(0, Vec::new()).validate().is_err());
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let excerpt = sample();
+        let restored = Excerpt::from_json(&excerpt.to_json().unwrap()).unwrap();
+        assert_eq!(restored, excerpt);
+        assert!(Excerpt::from_json("{}").is_err());
+    }
+}
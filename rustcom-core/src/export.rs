@@ -0,0 +1,387 @@
+//! Export of the logged TX/RX/event entries that fall inside a time window.
+
+use std::io::Write;
+
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+
+use crate::clock;
+use crate::logging::{DataLogEntry, Direction, LogBuffer, TIMESTAMP_FORMAT};
+
+/// pcapng link type for the packets: LINKTYPE_USER0, since serial data has no
+/// standard one. Wireshark shows the bytes as data.
+pub const PCAPNG_LINKTYPE: u16 = 147;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// `[timestamp] RX: text` lines, as written by Save Log.
+    Text,
+    /// The received bytes only, concatenated.
+    Raw,
+    /// `timestamp,direction,hex,text,discontinuity` rows.
+    Csv,
+    /// pcapng with one packet per entry; direction goes in the packet flags.
+    Pcapng,
+}
+
+impl ExportFormat {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ExportFormat::Text => "Text",
+            ExportFormat::Raw => "Raw RX bytes",
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Pcapng => "pcapng",
+        }
+    }
+
+    /// File extension, without the dot.
+    pub fn extension(&self) -> &str {
+        match self {
+            ExportFormat::Text => "txt",
+            ExportFormat::Raw => "bin",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Pcapng => "pcapng",
+        }
+    }
+}
+
+/// How the export window is chosen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportRange {
+    /// Between two typed times.
+    Timestamps,
+    /// From one event marker through another, by index into the event list.
+    Markers(usize, usize),
+    /// The last few minutes up to now.
+    LastMinutes,
+}
+
+impl ExportRange {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ExportRange::Timestamps => "Between times",
+            ExportRange::Markers(..) => "Between markers",
+            ExportRange::LastMinutes => "Last N minutes",
+        }
+    }
+}
+
+/// Inclusive time range, in local time like the log timestamps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeWindow {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+impl TimeWindow {
+    /// The window between two times given in either order.
+    pub fn between(a: NaiveDateTime, b: NaiveDateTime) -> Self {
+        Self {
+            start: a.min(b),
+            end: a.max(b),
+        }
+    }
+
+    /// The last `minutes` before `now`.
+    pub fn last_minutes(minutes: u32, now: NaiveDateTime) -> Self {
+        Self {
+            start: now - chrono::Duration::minutes(i64::from(minutes)),
+            end: now,
+        }
+    }
+
+    pub fn contains(&self, time: NaiveDateTime) -> bool {
+        self.start <= time && time <= self.end
+    }
+}
+
+/// Parse a log entry timestamp.
+pub fn parse_timestamp(text: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(text.trim(), TIMESTAMP_FORMAT).ok()
+}
+
+/// Parse a time typed by the user: a full `YYYY-MM-DD HH:MM:SS[.fff]`, or just
+/// the time of day on `date`.
+pub fn parse_user_time(text: &str, date: NaiveDate) -> Result<NaiveDateTime, String> {
+    let text = text.trim();
+    NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M"))
+        .or_else(|_| {
+            NaiveTime::parse_from_str(text, "%H:%M:%S%.f")
+                .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+                .map(|time| date.and_time(time))
+        })
+        .map_err(|_| format!("Cannot read '{}' as a time (YYYY-MM-DD HH:MM:SS or HH:MM:SS)", text))
+}
+
+/// Quote a CSV field when it needs it.
+pub fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Comment on the first packet after a clock discontinuity.
+const AFTER_JUMP_NOTE: &str = "after clock discontinuity";
+
+fn csv_row(entry: &DataLogEntry, after_jump: bool) -> String {
+    let hex: Vec<String> = entry.data.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(
+        "{},{},{},{},{}\n",
+        entry.timestamp,
+        entry.direction.as_str(),
+        hex.join(" "),
+        csv_field(&String::from_utf8_lossy(&entry.data)),
+        if after_jump { "1" } else { "" }
+    )
+}
+
+fn pcapng_block(out: &mut impl Write, block_type: u32, body: &[u8]) -> std::io::Result<()> {
+    let padded = body.len().div_ceil(4) * 4;
+    let total = (12 + padded) as u32;
+    out.write_all(&block_type.to_le_bytes())?;
+    out.write_all(&total.to_le_bytes())?;
+    out.write_all(body)?;
+    out.write_all(&vec![0u8; padded - body.len()])?;
+    out.write_all(&total.to_le_bytes())
+}
+
+fn pcapng_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    body.resize(body.len().div_ceil(4) * 4, 0);
+}
+
+/// Section header and the single interface description.
+fn pcapng_header(out: &mut impl Write) -> std::io::Result<()> {
+    let mut shb = Vec::new();
+    shb.extend_from_slice(&0x1A2B_3C4Du32.to_le_bytes());
+    shb.extend_from_slice(&1u16.to_le_bytes());
+    shb.extend_from_slice(&0u16.to_le_bytes());
+    shb.extend_from_slice(&(-1i64).to_le_bytes());
+    pcapng_block(out, 0x0A0D_0D0A, &shb)?;
+
+    let mut idb = Vec::new();
+    idb.extend_from_slice(&PCAPNG_LINKTYPE.to_le_bytes());
+    idb.extend_from_slice(&0u16.to_le_bytes());
+    idb.extend_from_slice(&0u32.to_le_bytes());
+    pcapng_block(out, 1, &idb)
+}
+
+/// An enhanced packet block. Timestamps use the default microsecond resolution;
+/// RX is flagged inbound, TX outbound, and events carry their text as a comment.
+fn pcapng_packet(out: &mut impl Write, entry: &DataLogEntry, time: NaiveDateTime, after_jump: bool) -> std::io::Result<()> {
+    let micros = Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|t| t.timestamp_micros() as u64)
+        .unwrap_or(0);
+
+    let mut epb = Vec::new();
+    epb.extend_from_slice(&0u32.to_le_bytes());
+    epb.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+    epb.extend_from_slice(&(micros as u32).to_le_bytes());
+    epb.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
+    epb.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
+    epb.extend_from_slice(&entry.data);
+    epb.resize(epb.len().div_ceil(4) * 4, 0);
+
+    match entry.direction {
+        Direction::Received => pcapng_option(&mut epb, 2, &1u32.to_le_bytes()),
+        Direction::Sent => pcapng_option(&mut epb, 2, &2u32.to_le_bytes()),
+        Direction::Event => pcapng_option(&mut epb, 1, &entry.data),
+    }
+    if after_jump {
+        pcapng_option(&mut epb, 1, AFTER_JUMP_NOTE.as_bytes());
+    }
+    pcapng_option(&mut epb, 0, &[]);
+    pcapng_block(out, 6, &epb)
+}
+
+/// Write the entries inside `window` to `out`. Returns how many were written.
+/// The first RX/TX entry after a clock discontinuity event is flagged in CSV
+/// and pcapng, since its time cannot be compared with the entries before.
+pub fn export_window(
+    log: &mut LogBuffer,
+    window: TimeWindow,
+    format: ExportFormat,
+    out: &mut impl Write,
+) -> std::io::Result<usize> {
+    let mut result = match format {
+        ExportFormat::Csv => out.write_all(b"timestamp,direction,hex,text,discontinuity\n"),
+        ExportFormat::Pcapng => pcapng_header(out),
+        ExportFormat::Text | ExportFormat::Raw => Ok(()),
+    };
+    let mut count = 0;
+    let mut jumped = false;
+
+    log.for_each(|entry| {
+        if result.is_err() {
+            return;
+        }
+        let after_jump = match entry.direction {
+            Direction::Event => {
+                jumped |= clock::is_jump_marker(&entry.data);
+                false
+            }
+            _ => std::mem::take(&mut jumped),
+        };
+        let Some(time) = parse_timestamp(&entry.timestamp).filter(|t| window.contains(*t)) else {
+            return;
+        };
+        result = match format {
+            ExportFormat::Text => out.write_all(entry.text_line().as_bytes()),
+            ExportFormat::Raw if entry.direction == Direction::Received => out.write_all(&entry.data),
+            ExportFormat::Raw => return,
+            ExportFormat::Csv => out.write_all(csv_row(entry, after_jump).as_bytes()),
+            ExportFormat::Pcapng => pcapng_packet(out, entry, time, after_jump),
+        };
+        count += 1;
+    })?;
+
+    result?;
+    out.flush()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(text: &str) -> NaiveDateTime {
+        parse_timestamp(text).unwrap()
+    }
+
+    fn entry(timestamp: &str, direction: Direction, data: &[u8]) -> DataLogEntry {
+        DataLogEntry {
+            timestamp: timestamp.to_string(),
+            direction,
+            data: data.to_vec(),
+        }
+    }
+
+    fn sample_log() -> LogBuffer {
+        // Small limit so part of the window comes from the journal
+        let mut log = LogBuffer::with_limits(2, 1 << 20);
+        log.push(entry("2024-03-01 10:00:00.000", Direction::Received, b"boot"));
+        log.push(entry("2024-03-01 10:00:05.000", Direction::Sent, b"AT"));
+        log.push(entry("2024-03-01 10:00:06.000", Direction::Event, b"FAULT"));
+        log.push(entry("2024-03-01 10:00:07.500", Direction::Received, b"ERR,\"x\""));
+        log.push(entry("2024-03-01 10:01:00.000", Direction::Received, b"later"));
+        log
+    }
+
+    fn window() -> TimeWindow {
+        TimeWindow::between(time("2024-03-01 10:00:08.000"), time("2024-03-01 10:00:05.000"))
+    }
+
+    fn export(format: ExportFormat) -> (usize, Vec<u8>) {
+        let mut out = Vec::new();
+        let count = export_window(&mut sample_log(), window(), format, &mut out).unwrap();
+        (count, out)
+    }
+
+    #[test]
+    fn parses_full_and_time_only_input() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        assert_eq!(
+            parse_user_time(" 10:00:06.5 ", date).unwrap(),
+            time("2024-03-01 10:00:06.500")
+        );
+        assert_eq!(
+            parse_user_time("2024-02-29 23:59", date).unwrap(),
+            time("2024-02-29 23:59:00.000")
+        );
+        assert!(parse_user_time("soon", date).is_err());
+
+        let last = TimeWindow::last_minutes(2, time("2024-03-01 10:01:00.000"));
+        assert!(last.contains(time("2024-03-01 09:59:00.000")));
+        assert!(!last.contains(time("2024-03-01 09:58:59.999")));
+    }
+
+    #[test]
+    fn text_and_csv_keep_only_the_window_in_order() {
+        let (count, text) = export(ExportFormat::Text);
+        assert_eq!(count, 3);
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "[2024-03-01 10:00:05.000] TX: AT\n\
+             [2024-03-01 10:00:06.000] EV: FAULT\n\
+             [2024-03-01 10:00:07.500] RX: ERR,\"x\"\n"
+        );
+
+        let (_, csv) = export(ExportFormat::Csv);
+        let csv = String::from_utf8(csv).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows[0], "timestamp,direction,hex,text,discontinuity");
+        assert_eq!(rows[1], "2024-03-01 10:00:05.000,TX,41 54,AT,");
+        assert_eq!(rows[3], "2024-03-01 10:00:07.500,RX,45 52 52 2C 22 78 22,\"ERR,\"\"x\"\"\",");
+    }
+
+    #[test]
+    fn first_entry_after_a_clock_jump_is_flagged() {
+        let jump = b"Clock discontinuity: wall clock +8h 00m 00s (sleep or clock change)";
+        let mut log = LogBuffer::with_limits(100, 1 << 20);
+        log.push(entry("2024-03-01 22:00:00.000", Direction::Received, b"before"));
+        log.push(entry("2024-03-02 06:00:00.000", Direction::Event, jump));
+        log.push(entry("2024-03-02 06:00:00.100", Direction::Received, b"after"));
+        log.push(entry("2024-03-02 06:00:00.200", Direction::Received, b"next"));
+        let window = TimeWindow::between(time("2024-03-01 00:00:00.000"), time("2024-03-03 00:00:00.000"));
+
+        let mut csv = Vec::new();
+        export_window(&mut log, window, ExportFormat::Csv, &mut csv).unwrap();
+        let flags: Vec<String> = String::from_utf8(csv)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|row| row.rsplit(',').next().unwrap().to_string())
+            .collect();
+        assert_eq!(flags, ["", "", "1", ""]);
+
+        let mut pcapng = Vec::new();
+        export_window(&mut log, window, ExportFormat::Pcapng, &mut pcapng).unwrap();
+        let notes = pcapng.windows(AFTER_JUMP_NOTE.len()).filter(|w| *w == AFTER_JUMP_NOTE.as_bytes()).count();
+        assert_eq!(notes, 1);
+    }
+
+    #[test]
+    fn raw_is_received_bytes_only() {
+        let (count, raw) = export(ExportFormat::Raw);
+        assert_eq!(count, 1);
+        assert_eq!(raw, b"ERR,\"x\"");
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn pcapng_blocks_are_well_formed() {
+        let (count, file) = export(ExportFormat::Pcapng);
+        assert_eq!(count, 3);
+
+        let mut blocks = Vec::new();
+        let mut at = 0;
+        while at < file.len() {
+            let kind = u32_at(&file, at);
+            let len = u32_at(&file, at + 4) as usize;
+            assert_eq!(len % 4, 0);
+            assert_eq!(u32_at(&file, at + len - 4) as usize, len, "trailing length");
+            blocks.push((kind, at));
+            at += len;
+        }
+        assert_eq!(at, file.len());
+        let kinds: Vec<u32> = blocks.iter().map(|b| b.0).collect();
+        assert_eq!(kinds, [0x0A0D_0D0A, 1, 6, 6, 6]);
+        assert_eq!(u32_at(&file, 8), 0x1A2B_3C4D);
+
+        // TX packet: 2 data bytes, padded, then the outbound flag
+        let tx = blocks[2].1;
+        assert_eq!(u32_at(&file, tx + 20), 2);
+        assert_eq!(&file[tx + 28..tx + 30], b"AT");
+        assert_eq!(u32_at(&file, tx + 32), 0x0004_0002);
+        assert_eq!(u32_at(&file, tx + 36), 2);
+    }
+}
@@ -0,0 +1,257 @@
+//! Failover port lists: an ordered set of acceptable ports, by name or by USB
+//! identity, tried in turn until one opens. For a device that can show up on
+//! either of several adapters depending on which enumerates first.
+
+use serde::{Deserialize, Serialize};
+use serialport::{SerialPortInfo, SerialPortType};
+
+/// One acceptable port.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PortCandidate {
+    /// A port name such as `COM3` or `/dev/ttyUSB0`.
+    Name(String),
+    /// Whichever port a USB adapter enumerates as. The serial number, when
+    /// given, tells identical adapters apart.
+    Usb {
+        vid: u16,
+        pid: u16,
+        serial: Option<String>,
+    },
+}
+
+impl PortCandidate {
+    /// `usb:VID:PID` or `usb:VID:PID:SERIAL` with hex IDs, anything else is a
+    /// port name.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("Enter a port name or usb:VID:PID".to_string());
+        }
+        let Some(usb) = text.strip_prefix("usb:").or_else(|| text.strip_prefix("USB:")) else {
+            return Ok(PortCandidate::Name(text.to_string()));
+        };
+        let mut parts = usb.splitn(3, ':');
+        let id = |part: Option<&str>| {
+            part.and_then(|p| u16::from_str_radix(p.trim(), 16).ok())
+                .ok_or_else(|| format!("'{}' is not usb:VID:PID with hex IDs, e.g. usb:0403:6001", text))
+        };
+        let vid = id(parts.next())?;
+        let pid = id(parts.next())?;
+        let serial = parts.next().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string);
+        Ok(PortCandidate::Usb { vid, pid, serial })
+    }
+
+    /// The candidate for `port`: its USB identity when it has one, otherwise its name.
+    pub fn for_port(port: &SerialPortInfo) -> Self {
+        match &port.port_type {
+            SerialPortType::UsbPort(info) => PortCandidate::Usb {
+                vid: info.vid,
+                pid: info.pid,
+                serial: info.serial_number.clone(),
+            },
+            _ => PortCandidate::Name(port.port_name.clone()),
+        }
+    }
+
+    /// The text form accepted by `parse`.
+    pub fn label(&self) -> String {
+        match self {
+            PortCandidate::Name(name) => name.clone(),
+            PortCandidate::Usb { vid, pid, serial: None } => format!("usb:{:04x}:{:04x}", vid, pid),
+            PortCandidate::Usb {
+                vid,
+                pid,
+                serial: Some(serial),
+            } => format!("usb:{:04x}:{:04x}:{}", vid, pid, serial),
+        }
+    }
+
+    fn matches(&self, port: &SerialPortInfo) -> bool {
+        match (self, &port.port_type) {
+            (PortCandidate::Name(name), _) => *name == port.port_name,
+            (PortCandidate::Usb { vid, pid, serial }, SerialPortType::UsbPort(info)) => {
+                info.vid == *vid
+                    && info.pid == *pid
+                    && serial.as_ref().is_none_or(|s| info.serial_number.as_ref() == Some(s))
+            }
+            _ => false,
+        }
+    }
+
+    /// The port name to open. Names are used as given, since a port can be
+    /// openable without being listed; USB identities must be among `ports`.
+    pub fn resolve(&self, ports: &[SerialPortInfo]) -> Result<String, String> {
+        match self {
+            PortCandidate::Name(name) => Ok(name.clone()),
+            PortCandidate::Usb { .. } => ports
+                .iter()
+                .find(|port| self.matches(port))
+                .map(|port| port.port_name.clone())
+                .ok_or_else(|| "no such USB device present".to_string()),
+        }
+    }
+}
+
+/// Failover settings, saved per A/B configuration.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FailoverConfig {
+    pub enabled: bool,
+    /// Tried first to last.
+    pub candidates: Vec<PortCandidate>,
+}
+
+impl FailoverConfig {
+    /// Whether Connect goes through the list instead of the selected port.
+    pub fn active(&self) -> bool {
+        self.enabled && !self.candidates.is_empty()
+    }
+
+    /// Whether any candidate needs the port list to resolve.
+    pub fn needs_port_list(&self) -> bool {
+        self.candidates.iter().any(|c| matches!(c, PortCandidate::Usb { .. }))
+    }
+}
+
+/// A candidate that did not open, and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedCandidate {
+    pub candidate: String,
+    pub error: String,
+}
+
+/// `COM3: Access denied; usb:0403:6001: no such USB device present`.
+pub fn describe_failures(failures: &[FailedCandidate]) -> String {
+    let parts: Vec<String> = failures
+        .iter()
+        .map(|f| format!("{}: {}", f.candidate, f.error))
+        .collect();
+    parts.join("; ")
+}
+
+/// The candidate that opened.
+pub struct Opened<T> {
+    /// Position in the list, from 0.
+    pub index: usize,
+    pub port_name: String,
+    pub port: T,
+    /// The candidates before it and why each failed.
+    pub failures: Vec<FailedCandidate>,
+}
+
+/// Try `candidates` in order with `open` until one succeeds. Returns every
+/// failure when none does.
+pub fn open_first<T>(
+    candidates: &[PortCandidate],
+    ports: &[SerialPortInfo],
+    mut open: impl FnMut(&str) -> Result<T, String>,
+) -> Result<Opened<T>, Vec<FailedCandidate>> {
+    let mut failures = Vec::new();
+    for (index, candidate) in candidates.iter().enumerate() {
+        let label = candidate.label();
+        let opened = candidate
+            .resolve(ports)
+            .and_then(|port_name| open(&port_name).map(|port| (port_name, port)));
+        match opened {
+            Ok((port_name, port)) => {
+                return Ok(Opened {
+                    index,
+                    port_name,
+                    port,
+                    failures,
+                })
+            }
+            Err(error) => failures.push(FailedCandidate { candidate: label, error }),
+        }
+    }
+    Err(failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serialport::UsbPortInfo;
+
+    fn usb_port(name: &str, vid: u16, pid: u16, serial: &str) -> SerialPortInfo {
+        SerialPortInfo {
+            port_name: name.to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid,
+                pid,
+                serial_number: Some(serial.to_string()),
+                manufacturer: None,
+                product: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn parses_names_and_usb_identities() {
+        assert_eq!(PortCandidate::parse(" COM3 ").unwrap(), PortCandidate::Name("COM3".to_string()));
+        let usb = PortCandidate::parse("usb:0403:6001:A10K").unwrap();
+        assert_eq!(
+            usb,
+            PortCandidate::Usb {
+                vid: 0x0403,
+                pid: 0x6001,
+                serial: Some("A10K".to_string())
+            }
+        );
+        assert_eq!(usb.label(), "usb:0403:6001:A10K");
+        assert_eq!(PortCandidate::parse("USB:10c4:EA60").unwrap().label(), "usb:10c4:ea60");
+        assert!(PortCandidate::parse("usb:0403").is_err());
+        assert!(PortCandidate::parse("usb:xyz:6001").is_err());
+        assert!(PortCandidate::parse("  ").is_err());
+    }
+
+    #[test]
+    fn usb_identities_resolve_to_the_enumerated_port() {
+        let ports = [
+            usb_port("/dev/ttyUSB0", 0x0403, 0x6001, "A10K"),
+            usb_port("/dev/ttyUSB1", 0x0403, 0x6001, "B20X"),
+        ];
+        let second = PortCandidate::parse("usb:0403:6001:B20X").unwrap();
+        assert_eq!(second.resolve(&ports).unwrap(), "/dev/ttyUSB1");
+        // Without a serial number the first matching adapter is used
+        assert_eq!(PortCandidate::parse("usb:0403:6001").unwrap().resolve(&ports).unwrap(), "/dev/ttyUSB0");
+        assert!(PortCandidate::parse("usb:10c4:ea60").unwrap().resolve(&ports).is_err());
+        assert_eq!(PortCandidate::for_port(&ports[1]), second);
+    }
+
+    #[test]
+    fn second_candidate_opens_after_the_first_fails() {
+        let candidates = [
+            PortCandidate::Name("COM3".to_string()),
+            PortCandidate::Name("COM4".to_string()),
+            PortCandidate::Name("COM5".to_string()),
+        ];
+        let mut tried = Vec::new();
+        let opened = open_first(&candidates, &[], |name| {
+            tried.push(name.to_string());
+            if name == "COM3" {
+                Err("Access denied".to_string())
+            } else {
+                Ok(name.len())
+            }
+        })
+        .unwrap();
+        assert_eq!(tried, ["COM3", "COM4"]);
+        assert_eq!(opened.index, 1);
+        assert_eq!(opened.port_name, "COM4");
+        assert_eq!(describe_failures(&opened.failures), "COM3: Access denied");
+    }
+
+    #[test]
+    fn every_failure_is_reported_when_none_opens() {
+        let candidates = [
+            PortCandidate::parse("usb:0403:6001").unwrap(),
+            PortCandidate::Name("COM4".to_string()),
+        ];
+        let failures = open_first(&candidates, &[], |_| Err::<(), _>("The system cannot find the file specified".to_string()))
+            .err()
+            .unwrap();
+        assert_eq!(
+            describe_failures(&failures),
+            "usb:0403:6001: no such USB device present; COM4: The system cannot find the file specified"
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! Capture filter rules
+
+use std::time::{Duration, Instant};
+
+use regex::bytes::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Compiled-size cap per pattern, so a pathological rule fails to compile instead
+/// of eating memory.
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+/// One evaluation in this many also times each rule separately.
+const TIMING_SAMPLE_INTERVAL: u64 = 64;
+
+/// Rules averaging more than this per chunk are flagged in the UI.
+pub const SLOW_RULE_THRESHOLD: Duration = Duration::from_micros(50);
+
+/// Sampled per-rule match times.
+#[derive(Debug, Clone, Default)]
+pub struct RuleTiming {
+    pub samples: u64,
+    pub total: Duration,
+}
+
+impl RuleTiming {
+    /// Mean time per sampled evaluation.
+    pub fn average(&self) -> Option<Duration> {
+        (self.samples > 0).then(|| self.total / self.samples as u32)
+    }
+}
+
+/// One filter rule. Patterns are matched against the raw bytes, so hex rules built
+/// with `(?-u)\xNN` escapes match binary data exactly. Only the pattern, enabled
+/// flag and highlight colour are saved; call `compile` after loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterRule {
+    pub pattern: String,
+    pub enabled: bool,
+    /// sRGB colour for terminal lines the rule matches.
+    #[serde(default)]
+    pub highlight: Option<[u8; 3]>,
+    #[serde(skip)]
+    pub regex: Option<Regex>,
+    #[serde(skip)]
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub timing: RuleTiming,
+}
+
+impl FilterRule {
+    /// An enabled rule, compiled straight away; check `error` for a bad pattern.
+    pub fn new(pattern: &str) -> Self {
+        let mut rule = Self {
+            pattern: pattern.to_string(),
+            enabled: true,
+            highlight: None,
+            regex: None,
+            error: None,
+            timing: RuleTiming::default(),
+        };
+        rule.compile();
+        rule
+    }
+
+    /// Recompile after the pattern was edited.
+    pub fn compile(&mut self) {
+        self.timing = RuleTiming::default();
+        if self.pattern.is_empty() {
+            self.regex = None;
+            self.error = None;
+            return;
+        }
+
+        match RegexBuilder::new(&self.pattern)
+            .size_limit(MAX_REGEX_SIZE)
+            .build()
+        {
+            Ok(regex) => {
+                self.regex = Some(regex);
+                self.error = None;
+            }
+            Err(e) => {
+                self.regex = None;
+                self.error = Some(format!("Invalid regex: {}", e));
+            }
+        }
+    }
+
+    /// Enabled and compiled without error.
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.regex.is_some()
+    }
+}
+
+/// Highlight colour of the first active highlighting rule matching `line`.
+pub fn highlight_for(rules: &[FilterRule], line: &[u8]) -> Option<[u8; 3]> {
+    rules.iter().filter(|rule| rule.is_active()).find_map(|rule| {
+        let color = rule.highlight?;
+        rule.regex.as_ref().is_some_and(|re| re.is_match(line)).then_some(color)
+    })
+}
+
+/// True when no rule is active, or when any active rule matches.
+pub fn matches_any(rules: &[FilterRule], data: &[u8]) -> bool {
+    let mut active = rules.iter().filter(|r| r.is_active()).peekable();
+    if active.peek().is_none() {
+        return true;
+    }
+    active.any(|rule| rule.regex.as_ref().is_some_and(|re| re.is_match(data)))
+}
+
+/// The active rules combined into one `RegexSet`, so a chunk is scanned once no
+/// matter how many rules there are. Rebuilt whenever the active patterns change.
+#[derive(Default)]
+pub struct FilterSet {
+    patterns: Vec<String>,
+    set: Option<RegexSet>,
+    evaluations: u64,
+}
+
+impl FilterSet {
+    fn sync(&mut self, rules: &[FilterRule]) {
+        let active = rules.iter().filter(|r| r.is_active());
+        if active.clone().map(|r| &r.pattern).eq(self.patterns.iter()) {
+            return;
+        }
+        self.patterns = active.map(|r| r.pattern.clone()).collect();
+        self.set = RegexSetBuilder::new(&self.patterns)
+            .size_limit(MAX_REGEX_SIZE * self.patterns.len().max(1))
+            .build()
+            .ok();
+    }
+
+    /// Same result as `matches_any`. Every `TIMING_SAMPLE_INTERVAL`th call also
+    /// runs each rule on its own to update its timing.
+    pub fn matches(&mut self, rules: &mut [FilterRule], data: &[u8]) -> bool {
+        self.sync(rules);
+        if self.patterns.is_empty() {
+            return true;
+        }
+
+        if self.evaluations.is_multiple_of(TIMING_SAMPLE_INTERVAL) {
+            for rule in rules.iter_mut().filter(|r| r.is_active()) {
+                if let Some(regex) = &rule.regex {
+                    let start = Instant::now();
+                    let _ = regex.is_match(data);
+                    rule.timing.total += start.elapsed();
+                    rule.timing.samples += 1;
+                }
+            }
+        }
+        self.evaluations += 1;
+
+        match &self.set {
+            Some(set) => set.is_match(data),
+            None => matches_any(rules, data),
+        }
+    }
+}
+
+/// A pattern matching `text` literally.
+pub fn text_pattern(text: &str) -> String {
+    regex::escape(text)
+}
+
+/// A pattern matching exactly these bytes, independent of UTF-8 validity.
+pub fn hex_pattern(bytes: &[u8]) -> String {
+    let mut pattern = String::from("(?-u)");
+    for byte in bytes {
+        pattern.push_str(&format!("\\x{:02X}", byte));
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_active_rules_matches_everything() {
+        assert!(matches_any(&[], b"anything"));
+
+        let mut disabled = FilterRule::new("ERROR");
+        disabled.enabled = false;
+        assert!(matches_any(&[disabled, FilterRule::new("")], b"anything"));
+    }
+
+    #[test]
+    fn any_rule_matching_is_enough() {
+        let rules = vec![FilterRule::new("ERROR"), FilterRule::new("WARN")];
+        assert!(matches_any(&rules, b"WARN: low battery"));
+        assert!(!matches_any(&rules, b"INFO: ok"));
+    }
+
+    #[test]
+    fn escaped_text_matches_literally() {
+        let rule = FilterRule::new(&text_pattern("temp=(41.5)*"));
+        assert!(rule.error.is_none());
+        assert!(matches_any(std::slice::from_ref(&rule), b"x temp=(41.5)* y"));
+        assert!(!matches_any(&[rule], b"temp=41.5"));
+    }
+
+    #[test]
+    fn hex_pattern_matches_binary_bytes() {
+        let rule = FilterRule::new(&hex_pattern(&[0xFF, 0x00, 0xC0]));
+        assert!(rule.error.is_none());
+        assert!(matches_any(std::slice::from_ref(&rule), &[0x01, 0xFF, 0x00, 0xC0, 0x02]));
+        assert!(!matches_any(&[rule], &[0xFF, 0xC0]));
+    }
+
+    #[test]
+    fn set_tracks_rule_changes() {
+        let mut set = FilterSet::default();
+        let mut rules = vec![FilterRule::new("ERROR")];
+        assert!(!set.matches(&mut rules, b"WARN"));
+
+        rules.push(FilterRule::new("WARN"));
+        assert!(set.matches(&mut rules, b"WARN"));
+
+        rules[1].enabled = false;
+        assert!(!set.matches(&mut rules, b"WARN"));
+
+        rules.clear();
+        assert!(set.matches(&mut rules, b"anything"));
+    }
+
+    #[test]
+    fn set_samples_per_rule_timing() {
+        let mut set = FilterSet::default();
+        let mut rules = vec![FilterRule::new("a+b"), FilterRule::new("c")];
+        for _ in 0..=TIMING_SAMPLE_INTERVAL {
+            set.matches(&mut rules, b"xxaab");
+        }
+        assert_eq!(rules[0].timing.samples, 2);
+        assert_eq!(rules[1].timing.samples, 2);
+        assert!(rules[0].timing.average().is_some());
+
+        rules[0].compile();
+        assert_eq!(rules[0].timing.samples, 0);
+    }
+
+    #[test]
+    fn oversized_pattern_is_rejected() {
+        let rule = FilterRule::new(r"\w{1000}\w{1000}\w{1000}");
+        assert!(rule.error.is_some());
+    }
+
+    #[test]
+    fn set_matches_naive_loop_benchmark() {
+        let mut rules: Vec<FilterRule> = (0..20)
+            .map(|i| FilterRule::new(&format!("SENSOR{:02} temp=[0-9]+\\.[0-9]", i)))
+            .collect();
+        let chunks: Vec<Vec<u8>> = (0..2000)
+            .map(|i| format!("{} SENSOR{:02} temp={}.{} ok\r\n", i, i % 40, i % 90, i % 10).into_bytes())
+            .collect();
+
+        let naive: Vec<bool> = chunks.iter().map(|c| matches_any(&rules, c)).collect();
+        let mut set = FilterSet::default();
+        let combined: Vec<bool> = chunks.iter().map(|c| set.matches(&mut rules, c)).collect();
+
+        assert_eq!(naive, combined);
+        assert_eq!(combined.iter().filter(|&&m| m).count(), 1000);
+    }
+
+    #[test]
+    fn first_matching_highlight_wins() {
+        let mut error = FilterRule::new("ERR");
+        error.highlight = Some([255, 0, 0]);
+        let mut disabled = FilterRule::new("WARN");
+        disabled.highlight = Some([255, 255, 0]);
+        disabled.enabled = false;
+        let mut any = FilterRule::new(".");
+        any.highlight = Some([0, 0, 255]);
+        let rules = [FilterRule::new("INFO"), error, disabled, any];
+
+        assert_eq!(highlight_for(&rules, b"ERR: bus off"), Some([255, 0, 0]));
+        assert_eq!(highlight_for(&rules, b"WARN: low"), Some([0, 0, 255]));
+        assert_eq!(highlight_for(&rules[..3], b"INFO ok"), None);
+    }
+
+    #[test]
+    fn invalid_pattern_reports_error() {
+        let rule = FilterRule::new("(unclosed");
+        assert!(rule.error.is_some());
+        assert!(!rule.is_active());
+    }
+}
@@ -0,0 +1,589 @@
+//! Hex formatting and parsing, escaped text, and software parity for
+//! transmitted bytes.
+
+/// Classic hex dump: offset, 16 hex bytes and their ASCII, one row per line.
+pub fn format_hex(data: &[u8]) -> String {
+    format_hex_at(data, 0)
+}
+
+/// The rows of `format_hex` for `data` found at byte `offset` of the dump,
+/// which should start a row. Appending these after the whole rows already
+/// shown extends a dump without formatting it again.
+pub fn format_hex_at(data: &[u8], offset: usize) -> String {
+    let mut result = String::new();
+    for (i, chunk) in data.chunks(16).enumerate() {
+        result.push_str(&format!("{:04X}  ", offset + i * 16));
+
+        // Hex bytes
+        for (j, byte) in chunk.iter().enumerate() {
+            result.push_str(&format!("{:02X} ", byte));
+            if j == 7 {
+                result.push(' ');
+            }
+        }
+
+        // Padding for incomplete lines
+        if chunk.len() < 16 {
+            for _ in 0..(16 - chunk.len()) {
+                result.push_str("   ");
+            }
+            // Add the column-8 separator space if the line didn't reach it
+            if chunk.len() <= 8 {
+                result.push(' ');
+            }
+        }
+
+        result.push_str("  ");
+
+        // ASCII representation
+        for byte in chunk {
+            let ch = if byte.is_ascii_graphic() || *byte == b' ' {
+                *byte as char
+            } else {
+                '.'
+            };
+            result.push(ch);
+        }
+
+        result.push('\n');
+    }
+    result
+}
+
+/// Width of one full `format_hex` line, including the trailing newline, while
+/// the offset has four digits. Each further digit, from 64 KiB on, widens the
+/// row by one.
+pub const HEX_DUMP_LINE_WIDTH: usize = 74;
+/// Columns below are those of a row with a four-digit offset.
+const HEX_COLUMN_START: usize = 6;
+const HEX_COLUMN_END: usize = 55;
+const ASCII_COLUMN_START: usize = 57;
+
+/// Runs of dump rows sharing an offset width, from the top: the first row, the
+/// row after the last, and the width of each.
+fn hex_dump_bands() -> impl Iterator<Item = (usize, usize, usize)> {
+    (4u32..).map_while(|digits| {
+        let first = if digits == 4 { 0 } else { 16usize.checked_pow(digits - 1)? / 16 };
+        let end = 16usize.checked_pow(digits).map_or(usize::MAX, |max| max / 16);
+        Some((first, end, HEX_DUMP_LINE_WIDTH - 4 + digits as usize))
+    })
+}
+
+/// Length of `format_hex` for `len` bytes.
+pub fn hex_dump_len(len: usize) -> usize {
+    let rows = len.div_ceil(16);
+    let full: usize = hex_dump_bands()
+        .take_while(|&(first, _, _)| first < rows)
+        .map(|(first, end, width)| (end.min(rows) - first) * width)
+        .sum();
+    // The ASCII column of a short last row stops at its last byte
+    full - (16 - len % 16) % 16
+}
+
+/// The row of `format_hex` that character `index` falls in, and its column as
+/// in a row with a four-digit offset.
+fn hex_dump_position(index: usize) -> (usize, usize) {
+    let mut start = 0;
+    for (first, end, width) in hex_dump_bands() {
+        let band_len = (end - first).saturating_mul(width);
+        if index - start < band_len {
+            let within = index - start;
+            let column = (within % width).saturating_sub(width - HEX_DUMP_LINE_WIDTH);
+            return (first + within / width, column);
+        }
+        start += band_len;
+    }
+    unreachable!("the last band runs to usize::MAX")
+}
+
+/// Map a character range of `format_hex(data)` back to the bytes it covers.
+/// Offsets and separators are ignored. The column the selection starts in (hex or
+/// ASCII) decides which one is mapped, so a selection running through the ASCII
+/// column of one row into the hex column of the next stays contiguous.
+pub fn hex_dump_selection(data: &[u8], chars: std::ops::Range<usize>) -> Vec<u8> {
+    hex_dump_selection_range(data, chars).map_or_else(Vec::new, |bytes| data[bytes].to_vec())
+}
+
+/// The byte offsets behind a character range of `format_hex(data)`, as for
+/// `hex_dump_selection`. `None` when no byte is covered.
+pub fn hex_dump_selection_range(data: &[u8], chars: std::ops::Range<usize>) -> Option<std::ops::Range<usize>> {
+    let mut first: Option<usize> = None;
+    let mut last: Option<usize> = None;
+    let mut in_ascii_column: Option<bool> = None;
+
+    for index in chars {
+        let (line, column) = hex_dump_position(index);
+
+        let mapped = if (HEX_COLUMN_START..HEX_COLUMN_END).contains(&column) {
+            // Each byte is "XX " with an extra gap space after the eighth;
+            // only the digits map to a byte
+            let c = column - HEX_COLUMN_START;
+            match c {
+                0..=23 if c % 3 != 2 => Some((c / 3, false)),
+                25.. if (c - 25) % 3 != 2 => Some((8 + (c - 25) / 3, false)),
+                _ => None,
+            }
+        } else if (ASCII_COLUMN_START..ASCII_COLUMN_START + 16).contains(&column) {
+            Some((column - ASCII_COLUMN_START, true))
+        } else {
+            None
+        };
+
+        let Some((offset, ascii)) = mapped else {
+            continue;
+        };
+        if *in_ascii_column.get_or_insert(ascii) != ascii {
+            continue;
+        }
+
+        let byte = line * 16 + offset;
+        if byte < data.len() {
+            first = Some(first.map_or(byte, |f| f.min(byte)));
+            last = Some(last.map_or(byte, |l| l.max(byte)));
+        }
+    }
+
+    match (first, last) {
+        (Some(first), Some(last)) => Some(first..last + 1),
+        _ => None,
+    }
+}
+
+/// Length of `bytes` without an unfinished UTF-8 sequence at the end, so data
+/// cut mid-character is decoded once the rest arrives rather than as U+FFFD.
+pub fn complete_utf8_len(bytes: &[u8]) -> usize {
+    for start in (bytes.len().saturating_sub(3)..bytes.len()).rev() {
+        if let Err(e) = std::str::from_utf8(&bytes[start..]) {
+            if e.valid_up_to() == 0 && e.error_len().is_none() {
+                return start;
+            }
+        }
+    }
+    bytes.len()
+}
+
+/// Remove ANSI CSI escape sequences (`ESC [ ... letter`) from text.
+pub fn strip_ansi_codes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            if chars.peek() == Some(&'[') {
+                chars.next(); // consume '['
+                // Skip everything until we find a letter (command character)
+                while let Some(&next_ch) = chars.peek() {
+                    chars.next();
+                    if next_ch.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            } else {
+                result.push(ch);
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+
+    result
+}
+
+/// Clear the high bit of every byte, for 7-bit links where it arrives set.
+pub fn mask_7bit(data: &[u8]) -> Vec<u8> {
+    data.iter().map(|b| b & 0x7F).collect()
+}
+
+/// Parity bit computed in software and placed in bit 7, for adapters limited to 8N1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoftParity {
+    None,
+    Even,
+    Odd,
+    Mark,
+    Space,
+}
+
+impl SoftParity {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SoftParity::None => "None",
+            SoftParity::Even => "Even",
+            SoftParity::Odd => "Odd",
+            SoftParity::Mark => "Mark",
+            SoftParity::Space => "Space",
+        }
+    }
+}
+
+/// Replace bit 7 of each byte with the chosen parity over bits 0-6.
+/// `SoftParity::None` leaves the data untouched.
+pub fn apply_soft_parity(data: &[u8], parity: SoftParity) -> Vec<u8> {
+    data.iter()
+        .map(|&byte| {
+            let low = byte & 0x7F;
+            let odd_ones = low.count_ones() % 2 == 1;
+            let high = match parity {
+                SoftParity::None => return byte,
+                SoftParity::Even => odd_ones,
+                SoftParity::Odd => !odd_ones,
+                SoftParity::Mark => true,
+                SoftParity::Space => false,
+            };
+            if high {
+                low | 0x80
+            } else {
+                low
+            }
+        })
+        .collect()
+}
+
+/// Parse hex bytes into raw bytes, as typed or pasted from source code:
+/// `AA BB 0D 0A`, `0xAA, 0xBB;`, `AABB0D0A`. Bytes are separated by
+/// whitespace, commas or semicolons and may carry a `0x`/`0X` prefix; a run of
+/// more than two digits is read as pairs in the order written. A `//` comment
+/// runs to the end of its line. Returns Err naming the first invalid token
+/// and where it starts.
+pub fn parse_hex_input(input: &str) -> Result<Vec<u8>, String> {
+    let lines: Vec<&str> = input.lines().map(|line| line.split("//").next().unwrap_or("")).collect();
+    let multiline = lines.iter().filter(|line| !line.trim().is_empty()).count() > 1;
+
+    let mut bytes = Vec::new();
+    for (line_index, line) in lines.iter().enumerate() {
+        for (column, token) in hex_tokens(line) {
+            parse_hex_token(token, &mut bytes).map_err(|problem| {
+                let at = if multiline {
+                    format!("line {}, column {}", line_index + 1, column)
+                } else {
+                    format!("column {}", column)
+                };
+                format!("Invalid hex '{}' at {}: {}", token, at, problem)
+            })?;
+        }
+    }
+    if bytes.is_empty() {
+        return Err("Empty input".to_string());
+    }
+    Ok(bytes)
+}
+
+/// The tokens of one line between separators, with the 1-based column each
+/// starts at.
+fn hex_tokens(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    let is_separator = |c: char| c.is_whitespace() || c == ',' || c == ';';
+    let mut rest = line;
+    let mut column = 1;
+    std::iter::from_fn(move || {
+        let skipped = rest.len() - rest.trim_start_matches(is_separator).len();
+        column += rest[..skipped].chars().count();
+        rest = &rest[skipped..];
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.find(is_separator).unwrap_or(rest.len());
+        let (token, after) = rest.split_at(end);
+        let at = column;
+        column += token.chars().count();
+        rest = after;
+        Some((at, token))
+    })
+}
+
+/// Append the bytes of one token: one or two digits, or an even run of them,
+/// after an optional `0x`.
+fn parse_hex_token(token: &str, bytes: &mut Vec<u8>) -> Result<(), &'static str> {
+    let digits = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")).unwrap_or(token);
+    if digits.is_empty() {
+        return Err("no digits after 0x");
+    }
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("not a hex digit");
+    }
+    match digits.len() {
+        1 | 2 => bytes.push(u8::from_str_radix(digits, 16).unwrap_or(0)),
+        len if len % 2 == 1 => return Err("odd number of digits"),
+        _ => {
+            for pair in digits.as_bytes().chunks(2) {
+                let pair = std::str::from_utf8(pair).unwrap_or("");
+                bytes.push(u8::from_str_radix(pair, 16).unwrap_or(0));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Text with C-style escapes as bytes: `\r`, `\n`, `\t`, `\0`, `\e` (ESC),
+/// `\\` and `\xHH`. Anything else after a backslash is an error.
+pub fn parse_escapes(input: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let byte = match chars.next() {
+            Some('r') => b'\r',
+            Some('n') => b'\n',
+            Some('t') => b'\t',
+            Some('0') => 0x00,
+            Some('e') => 0x1B,
+            Some('\\') => b'\\',
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                if digits.len() != 2 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(format!("Invalid escape '\\x{}': expected two hex digits", digits));
+                }
+                u8::from_str_radix(&digits, 16).unwrap_or(0)
+            }
+            Some(other) => return Err(format!("Unknown escape '\\{}'", other)),
+            None => return Err("Backslash at the end of the text".to_string()),
+        };
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_become_control_bytes() {
+        assert_eq!(parse_escapes(r"AT\r\n").unwrap(), b"AT\r\n");
+        assert_eq!(parse_escapes(r"\x02Ä\x03\\\e\0").unwrap(), b"\x02\xC3\x84\x03\\\x1B\x00");
+        assert_eq!(parse_escapes(r"\q").unwrap_err(), r"Unknown escape '\q'");
+        assert_eq!(parse_escapes(r"\x4").unwrap_err(), r"Invalid escape '\x4': expected two hex digits");
+        assert!(parse_escapes("\\").is_err());
+    }
+
+    #[test]
+    fn every_escape_and_its_errors() {
+        for (escape, byte) in [
+            (r"\r", b'\r'),
+            (r"\n", b'\n'),
+            (r"\t", b'\t'),
+            (r"\0", 0x00),
+            (r"\e", 0x1B),
+            (r"\\", b'\\'),
+            (r"\x7f", 0x7F),
+            (r"\xA5", 0xA5),
+        ] {
+            assert_eq!(parse_escapes(escape).unwrap(), [byte], "{}", escape);
+        }
+        assert_eq!(parse_escapes("").unwrap(), b"");
+        assert_eq!(parse_escapes(r"C:\\dir\\").unwrap(), br"C:\dir\");
+        assert_eq!(parse_escapes(r"\xZZ").unwrap_err(), r"Invalid escape '\xZZ': expected two hex digits");
+        assert_eq!(parse_escapes(r"\x+1").unwrap_err(), r"Invalid escape '\x+1': expected two hex digits");
+        assert_eq!(parse_escapes(r"\x").unwrap_err(), r"Invalid escape '\x': expected two hex digits");
+        assert_eq!(parse_escapes(r"AT\").unwrap_err(), "Backslash at the end of the text");
+        assert_eq!(parse_escapes(r"AT\\\").unwrap_err(), "Backslash at the end of the text");
+    }
+
+    #[test]
+    fn hex_input_takes_spaced_prefixed_and_packed_bytes() {
+        assert_eq!(parse_hex_input("AA BB 0D 0A").unwrap(), [0xAA, 0xBB, 0x0D, 0x0A]);
+        assert_eq!(parse_hex_input("0xAA,0xBB").unwrap(), [0xAA, 0xBB]);
+        assert_eq!(parse_hex_input("0XaA; 0xbb;").unwrap(), [0xAA, 0xBB]);
+        assert_eq!(parse_hex_input("AABB0D0A").unwrap(), [0xAA, 0xBB, 0x0D, 0x0A]);
+        assert_eq!(parse_hex_input("0x1234").unwrap(), [0x12, 0x34], "pairs in the order written");
+        assert_eq!(parse_hex_input("5 0x7 f").unwrap(), [0x05, 0x07, 0x0F], "single digits are bytes");
+        assert_eq!(parse_hex_input(" \t01,\t02 ,03 ;; ,04\r\n").unwrap(), [1, 2, 3, 4]);
+        assert_eq!(parse_hex_input("AABB, 0xCC dd;0D0A").unwrap(), [0xAA, 0xBB, 0xCC, 0xDD, 0x0D, 0x0A]);
+    }
+
+    #[test]
+    fn hex_input_skips_line_comments() {
+        let array = "0x01, 0x03, // slave, function\n\
+                     0x00, 0x00, // address\n\
+                     // quantity follows\n\
+                     0x00, 0x02,\n";
+        assert_eq!(parse_hex_input(array).unwrap(), [0x01, 0x03, 0x00, 0x00, 0x00, 0x02]);
+        assert_eq!(parse_hex_input("AA BB // 0xZZ is not parsed").unwrap(), [0xAA, 0xBB]);
+        assert_eq!(parse_hex_input("AA//BB").unwrap(), [0xAA]);
+    }
+
+    #[test]
+    fn hex_input_errors_point_at_the_token() {
+        assert_eq!(parse_hex_input("AA BB GG").unwrap_err(), "Invalid hex 'GG' at column 7: not a hex digit");
+        assert_eq!(parse_hex_input("AABB0").unwrap_err(), "Invalid hex 'AABB0' at column 1: odd number of digits");
+        assert_eq!(parse_hex_input("0xAA,0x").unwrap_err(), "Invalid hex '0x' at column 6: no digits after 0x");
+        assert_eq!(parse_hex_input("0xAA,0x0xBB").unwrap_err(), "Invalid hex '0x0xBB' at column 6: not a hex digit");
+        assert_eq!(parse_hex_input("AA -1").unwrap_err(), "Invalid hex '-1' at column 4: not a hex digit");
+        assert_eq!(parse_hex_input("AA, Ä1, BB").unwrap_err(), "Invalid hex 'Ä1' at column 5: not a hex digit");
+        assert_eq!(
+            parse_hex_input("0x01, 0x02,\n\t0x03, 0xG4 // bad\n").unwrap_err(),
+            "Invalid hex '0xG4' at line 2, column 8: not a hex digit"
+        );
+        assert_eq!(parse_hex_input("").unwrap_err(), "Empty input");
+        assert_eq!(parse_hex_input(" , ;\n// only a comment").unwrap_err(), "Empty input");
+    }
+
+    #[test]
+    fn mask_clears_high_bit() {
+        assert_eq!(mask_7bit(&[0xC1, 0x41, 0xFF, 0x00]), vec![0x41, 0x41, 0x7F, 0x00]);
+    }
+
+    #[test]
+    fn even_parity() {
+        // 'A' = 0x41 has two ones -> parity bit clear; 'C' = 0x43 has three -> set
+        assert_eq!(apply_soft_parity(b"AC", SoftParity::Even), vec![0x41, 0xC3]);
+        // Existing high bits are replaced, not preserved
+        assert_eq!(apply_soft_parity(&[0xC1], SoftParity::Even), vec![0x41]);
+        for byte in apply_soft_parity(&(0..=255).collect::<Vec<u8>>(), SoftParity::Even) {
+            assert_eq!(byte.count_ones() % 2, 0);
+        }
+    }
+
+    #[test]
+    fn odd_parity() {
+        assert_eq!(apply_soft_parity(b"AC", SoftParity::Odd), vec![0xC1, 0x43]);
+        for byte in apply_soft_parity(&(0..=255).collect::<Vec<u8>>(), SoftParity::Odd) {
+            assert_eq!(byte.count_ones() % 2, 1);
+        }
+    }
+
+    #[test]
+    fn mark_and_space_parity() {
+        assert_eq!(apply_soft_parity(&[0x00, 0x41, 0xC1], SoftParity::Mark), vec![0x80, 0xC1, 0xC1]);
+        assert_eq!(apply_soft_parity(&[0x80, 0x41, 0xC1], SoftParity::Space), vec![0x00, 0x41, 0x41]);
+    }
+
+    #[test]
+    fn no_parity_is_identity() {
+        let data: Vec<u8> = (0..=255).collect();
+        assert_eq!(apply_soft_parity(&data, SoftParity::None), data);
+    }
+
+    #[test]
+    fn hex_dump_lines_have_fixed_width() {
+        let data: Vec<u8> = (0..40).collect();
+        let dump = format_hex(&data);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines[0].len() + 1, HEX_DUMP_LINE_WIDTH);
+        assert_eq!(lines[1].len() + 1, HEX_DUMP_LINE_WIDTH);
+    }
+
+    #[test]
+    fn rows_past_64_kib_are_mapped_by_their_wider_offset() {
+        let data: Vec<u8> = (0..0x11010u32).map(|i| (i % 251) as u8).collect();
+        let dump = format_hex(&data);
+        assert_eq!(hex_dump_len(data.len()), dump.len());
+        for len in [0, 1, 15, 16, 17, 0x10000, 0x10001] {
+            assert_eq!(hex_dump_len(len), format_hex(&data[..len]).len(), "{} bytes", len);
+        }
+
+        for at in [0xFFF0, 0x10000, 0x10FF0, 0x11000] {
+            let row = dump.find(&format!("\n{:04X}  ", at)).unwrap() + 1;
+            let digits = if at < 0x10000 { 4 } else { 5 };
+            // The third byte of the row, in the hex and in the ASCII column
+            let hex = row + digits + 2 + 6;
+            assert_eq!(hex_dump_selection_range(&data, hex..hex + 2), Some(at + 2..at + 3), "0x{:X}", at);
+            let ascii = row + digits + 53 + 2;
+            assert_eq!(hex_dump_selection_range(&data, ascii..ascii + 1), Some(at + 2..at + 3), "0x{:X}", at);
+        }
+        // A selection across the 64 KiB row boundary
+        let start = dump.find("\nFFF0  ").unwrap() + 1 + 6 + 45;
+        let end = dump.find("\n10000  ").unwrap() + 1 + 7 + 5;
+        assert_eq!(hex_dump_selection_range(&data, start..end), Some(0xFFFF..0x10002));
+    }
+
+    #[test]
+    fn selection_in_hex_columns_maps_to_bytes() {
+        let data: Vec<u8> = (0..40).collect();
+        let dump = format_hex(&data);
+        // "02 03" on the first line
+        let start = dump.find("02 03").unwrap();
+        assert_eq!(hex_dump_selection(&data, start..start + 5), vec![0x02, 0x03]);
+        // Across the mid-row gap
+        let start = dump.find("07  08").unwrap();
+        assert_eq!(hex_dump_selection(&data, start..start + 6), vec![0x07, 0x08]);
+    }
+
+    #[test]
+    fn selection_across_lines_and_ascii_column() {
+        let data: Vec<u8> = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ".to_vec();
+        let dump = format_hex(&data);
+        let start = dump.find("4F 50").unwrap();
+        let end = dump.find("52 53").unwrap() + 5;
+        assert_eq!(hex_dump_selection(&data, start..end), b"OPQRS".to_vec());
+
+        let ascii = dump.find("QRST").unwrap();
+        assert_eq!(hex_dump_selection(&data, ascii..ascii + 4), b"QRST".to_vec());
+    }
+
+    #[test]
+    fn selection_of_offsets_only_is_empty() {
+        let data = vec![0xAA; 16];
+        assert!(hex_dump_selection(&data, 0..4).is_empty());
+    }
+
+    /// Chunk lengths from 1 to `max`, from a fixed xorshift seed.
+    fn chunk_lengths(seed: u64, max: u64) -> impl Iterator<Item = usize> {
+        let mut state = seed;
+        std::iter::repeat_with(move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % max + 1) as usize
+        })
+    }
+
+    #[test]
+    fn hex_dump_grows_row_by_row_like_a_full_format() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(1500).collect();
+        for seed in 1..20 {
+            // As the terminal does it: keep the whole rows, format the rest again
+            let mut dump = String::new();
+            let mut kept = (0, 0);
+            let mut received = 0;
+            for len in chunk_lengths(seed, 40) {
+                received = (received + len).min(data.len());
+                let (bytes, chars) = kept;
+                dump.truncate(chars);
+                dump.push_str(&format_hex_at(&data[bytes..received], bytes));
+                assert_eq!(dump, format_hex(&data[..received]), "seed {} at {}", seed, received);
+
+                let whole = received - received % 16;
+                let last_row = if whole < received { dump.trim_end().rfind('\n').map_or(0, |i| i + 1) } else { dump.len() };
+                kept = (whole, last_row);
+                if received == data.len() {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn text_split_mid_character_decodes_like_the_whole() {
+        let mut data = "température 23 °C → ok ✓ 🙂\n".repeat(20).into_bytes();
+        data.extend_from_slice(&[0xE2, 0x41, 0xFF, b'\n']);
+        let whole = String::from_utf8_lossy(&data).to_string();
+        for seed in 1..20 {
+            let mut text = String::new();
+            let mut pending = Vec::new();
+            let mut received = 0;
+            for len in chunk_lengths(seed, 7) {
+                let end = (received + len).min(data.len());
+                pending.extend_from_slice(&data[received..end]);
+                received = end;
+                let complete = complete_utf8_len(&pending);
+                text.push_str(&String::from_utf8_lossy(&pending[..complete]));
+                pending.drain(..complete);
+                // Only the invalid bytes at the very end are replaced
+                assert!(!text.ends_with('\u{FFFD}') || received > data.len() - 4);
+                if received == data.len() {
+                    break;
+                }
+            }
+            assert!(pending.is_empty());
+            assert_eq!(text, whole, "seed {}", seed);
+        }
+        assert_eq!(complete_utf8_len("a→".as_bytes()), 4);
+        assert_eq!(complete_utf8_len(&"a→".as_bytes()[..3]), 1);
+        assert_eq!(complete_utf8_len(&[b'a', 0xF0, 0x9F, 0x99]), 1);
+        assert_eq!(complete_utf8_len(&[b'a', 0xFF]), 2);
+    }
+}
@@ -0,0 +1,146 @@
+//! Data inspector: the integer, float and ASCII readings of a few bytes, for
+//! checking a value picked out of the terminal without leaving the app.
+
+/// Longest selection that is interpreted; a double is the widest reading.
+pub const MAX_INSPECT_BYTES: usize = 8;
+
+/// One reading of the bytes, e.g. `float BE` = `31`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interpretation {
+    pub label: &'static str,
+    pub value: String,
+}
+
+impl Interpretation {
+    fn new(label: &'static str, value: String) -> Self {
+        Self { label, value }
+    }
+}
+
+/// Bytes written as hex text, e.g. `0x41F8 0000`, `41 F8 00 00` or
+/// `0x41,0xF8`. Tokens without a `0x` prefix need an even number of digits, so
+/// ordinary words are not mistaken for hex. `None` if any token is not hex.
+pub fn parse_hex_text(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for token in text.split(|c: char| c.is_whitespace() || c == ',' || c == ':').filter(|t| !t.is_empty()) {
+        let (digits, prefixed) = match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+            Some(digits) => (digits, true),
+            None => (token, false),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        if digits.len() % 2 == 1 && !prefixed {
+            return None;
+        }
+        let padded = if digits.len() % 2 == 1 {
+            format!("0{}", digits)
+        } else {
+            digits.to_string()
+        };
+        for pair in padded.as_bytes().chunks(2) {
+            let pair = std::str::from_utf8(pair).ok()?;
+            bytes.push(u8::from_str_radix(pair, 16).ok()?);
+        }
+    }
+    (!bytes.is_empty()).then_some(bytes)
+}
+
+fn array<const N: usize>(bytes: &[u8]) -> Option<[u8; N]> {
+    bytes.get(..N)?.try_into().ok()
+}
+
+/// Every reading that fits in `bytes`, taken from the first byte: unsigned and
+/// signed integers of each width and floats, in both byte orders, plus ASCII
+/// when every byte is printable.
+pub fn interpret(bytes: &[u8]) -> Vec<Interpretation> {
+    let mut readings = Vec::new();
+    if bytes.len() == 1 {
+        readings.push(Interpretation::new("8-bit", format!("{} / {}", bytes[0], bytes[0] as i8)));
+    }
+    if let Some(b) = array::<2>(bytes) {
+        readings.push(Interpretation::new(
+            "16-bit BE",
+            format!("{} / {}", u16::from_be_bytes(b), i16::from_be_bytes(b)),
+        ));
+        readings.push(Interpretation::new(
+            "16-bit LE",
+            format!("{} / {}", u16::from_le_bytes(b), i16::from_le_bytes(b)),
+        ));
+    }
+    if let Some(b) = array::<4>(bytes) {
+        readings.push(Interpretation::new(
+            "32-bit BE",
+            format!("{} / {}", u32::from_be_bytes(b), i32::from_be_bytes(b)),
+        ));
+        readings.push(Interpretation::new(
+            "32-bit LE",
+            format!("{} / {}", u32::from_le_bytes(b), i32::from_le_bytes(b)),
+        ));
+        readings.push(Interpretation::new("float BE", f32::from_be_bytes(b).to_string()));
+        readings.push(Interpretation::new("float LE", f32::from_le_bytes(b).to_string()));
+    }
+    if let Some(b) = array::<8>(bytes) {
+        readings.push(Interpretation::new(
+            "64-bit BE",
+            format!("{} / {}", u64::from_be_bytes(b), i64::from_be_bytes(b)),
+        ));
+        readings.push(Interpretation::new(
+            "64-bit LE",
+            format!("{} / {}", u64::from_le_bytes(b), i64::from_le_bytes(b)),
+        ));
+        readings.push(Interpretation::new("double BE", f64::from_be_bytes(b).to_string()));
+        readings.push(Interpretation::new("double LE", f64::from_le_bytes(b).to_string()));
+    }
+    if bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        readings.push(Interpretation::new("ASCII", String::from_utf8_lossy(bytes).into_owned()));
+    }
+    readings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value<'a>(readings: &'a [Interpretation], label: &str) -> Option<&'a str> {
+        readings.iter().find(|r| r.label == label).map(|r| r.value.as_str())
+    }
+
+    #[test]
+    fn parses_the_usual_ways_of_writing_hex() {
+        assert_eq!(parse_hex_text("0x41F8 0000"), Some(vec![0x41, 0xF8, 0x00, 0x00]));
+        assert_eq!(parse_hex_text("41 f8 00 00\n"), Some(vec![0x41, 0xF8, 0x00, 0x00]));
+        assert_eq!(parse_hex_text("0x41,0xF8"), Some(vec![0x41, 0xF8]));
+        assert_eq!(parse_hex_text("0x5"), Some(vec![0x05]));
+        assert_eq!(parse_hex_text("DE:AD"), Some(vec![0xDE, 0xAD]));
+        // Words and odd digit counts without a prefix are not hex
+        assert_eq!(parse_hex_text("add"), None);
+        assert_eq!(parse_hex_text("temp=41"), None);
+        assert_eq!(parse_hex_text("0x"), None);
+        assert_eq!(parse_hex_text("  "), None);
+    }
+
+    #[test]
+    fn reads_a_float_in_both_byte_orders() {
+        let readings = interpret(&[0x41, 0xF8, 0x00, 0x00]);
+        assert_eq!(value(&readings, "float BE"), Some("31"));
+        assert_eq!(value(&readings, "32-bit BE"), Some("1106771968 / 1106771968"));
+        assert_eq!(value(&readings, "32-bit LE"), Some("63553 / 63553"));
+        assert_eq!(value(&readings, "16-bit BE"), Some("16888 / 16888"));
+        assert_eq!(value(&readings, "8-bit"), None);
+        assert_eq!(value(&readings, "double BE"), None);
+        assert_eq!(value(&readings, "ASCII"), None);
+    }
+
+    #[test]
+    fn signed_values_and_ascii() {
+        let readings = interpret(&[0xFF]);
+        assert_eq!(value(&readings, "8-bit"), Some("255 / -1"));
+        assert_eq!(value(&interpret(b"OK"), "ASCII"), Some("OK"));
+        assert_eq!(value(&interpret(&[0xFF, 0xFE]), "16-bit LE"), Some("65279 / -257"));
+
+        let pi = std::f64::consts::PI.to_le_bytes();
+        assert_eq!(value(&interpret(&pi), "double LE"), Some("3.141592653589793"));
+        assert_eq!(value(&interpret(&pi), "64-bit LE"), Some("4614256656552045848 / 4614256656552045848"));
+    }
+}
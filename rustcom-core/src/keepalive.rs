@@ -0,0 +1,124 @@
+//! Keepalive sends for radio links and device watchdogs that drop a quiet
+//! session. The keepalive goes out only after a whole interval without any
+//! other TX, so real traffic keeps the link up by itself.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::payloads::SendMode;
+
+pub const MIN_KEEPALIVE_SECS: u64 = 1;
+pub const DEFAULT_KEEPALIVE_SECS: u64 = 30;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeepaliveConfig {
+    pub enabled: bool,
+    /// Text sent as typed, without the line ending, or `AA BB` hex.
+    pub text: String,
+    pub mode: SendMode,
+    pub interval_secs: u64,
+    /// Leave keepalives out of the TX log, echo and byte counters.
+    pub quiet: bool,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            text: String::new(),
+            mode: SendMode::Ascii,
+            interval_secs: DEFAULT_KEEPALIVE_SECS,
+            quiet: true,
+        }
+    }
+}
+
+impl KeepaliveConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs.max(MIN_KEEPALIVE_SECS))
+    }
+
+    /// The bytes to send.
+    pub fn payload(&self) -> Result<Vec<u8>, String> {
+        let payload = match self.mode {
+            SendMode::Ascii => self.text.as_bytes().to_vec(),
+            SendMode::Hex => crate::hex::parse_hex_input(&self.text)?,
+        };
+        if payload.is_empty() {
+            return Err("Keepalive payload is empty".to_string());
+        }
+        Ok(payload)
+    }
+}
+
+/// When the link last carried TX, to tell when a keepalive is due.
+#[derive(Debug, Default)]
+pub struct KeepaliveTimer {
+    last_tx: Option<Instant>,
+}
+
+impl KeepaliveTimer {
+    /// Any write, keepalive or not, starts the window again.
+    pub fn note_tx(&mut self, now: Instant) {
+        if self.last_tx.is_some() {
+            self.last_tx = Some(now);
+        }
+    }
+
+    /// How long until a keepalive is due at `interval`; zero when it is. The
+    /// first call after `stop` starts the window at `now`.
+    pub fn wait(&mut self, interval: Duration, now: Instant) -> Duration {
+        let last = *self.last_tx.get_or_insert(now);
+        (last + interval).saturating_duration_since(now)
+    }
+
+    /// Stop counting, e.g. while disconnected.
+    pub fn stop(&mut self) {
+        self.last_tx = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.last_tx.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn other_traffic_postpones_the_keepalive() {
+        let secs = Duration::from_secs;
+        let start = Instant::now();
+        let mut timer = KeepaliveTimer::default();
+        timer.note_tx(start);
+        assert!(!timer.is_running(), "TX before the start is ignored");
+
+        assert_eq!(timer.wait(secs(30), start), secs(30));
+        assert_eq!(timer.wait(secs(30), start + secs(10)), secs(20));
+        timer.note_tx(start + secs(25));
+        assert_eq!(timer.wait(secs(30), start + secs(50)), secs(5));
+        assert_eq!(timer.wait(secs(30), start + secs(60)), Duration::ZERO);
+
+        timer.stop();
+        assert_eq!(timer.wait(secs(30), start + secs(100)), secs(30));
+    }
+
+    #[test]
+    fn payload_is_text_or_hex() {
+        let mut config = KeepaliveConfig {
+            text: "AT\r".to_string(),
+            ..KeepaliveConfig::default()
+        };
+        assert_eq!(config.payload().unwrap(), b"AT\r");
+        config.mode = SendMode::Hex;
+        config.text = "00".to_string();
+        assert_eq!(config.payload().unwrap(), [0x00]);
+        config.text = " ".to_string();
+        assert!(config.payload().is_err());
+        config.interval_secs = 0;
+        assert_eq!(config.interval(), Duration::from_secs(MIN_KEEPALIVE_SECS));
+    }
+}
@@ -0,0 +1,56 @@
+//! Serial analysis logic from RustCOM with no GUI dependency: protocol
+//! decoders and framers, the per-line text or hex choice of the Auto view,
+//! checksums, hex parsing and value inspection, log formats and importing them
+//! back, UTF-16 text detection and decoding, per-boot capture files, clock
+//! discontinuities, capture filters and the data they reject, send guard rules,
+//! send history, macro buttons and send templates, TX rate limits, keepalives,
+//! repeat and scheduled sends, session names, failover port lists, statistics
+//! snapshots, key=value variables, watch expressions, RX rate alarms, Prometheus
+//! metrics, the browser live view, the TCP bridge, TCP client and RFC 2217
+//! transports, documentation excerpts, XMODEM transfers and settings serialization.
+
+pub mod auto_view;
+pub mod burst;
+pub mod cable_test;
+pub mod clock;
+pub mod excerpt;
+pub mod export;
+pub mod failover;
+pub mod filter;
+pub mod hex;
+pub mod inspect;
+pub mod keepalive;
+pub mod line_editor;
+pub mod line_offsets;
+pub mod lines;
+pub mod live_view;
+pub mod log_import;
+pub mod logging;
+pub mod macros;
+pub mod metrics;
+pub mod mock_port;
+pub mod payloads;
+pub mod pinned;
+pub mod port_config;
+pub mod protocol;
+pub mod rate_alarm;
+pub mod rate_limit;
+pub mod rejected;
+pub mod repeat;
+pub mod rfc2217;
+pub mod rule_set;
+pub mod rx_queue;
+pub mod schedule;
+pub mod send_guard;
+pub mod send_history;
+pub mod session;
+pub mod settings;
+pub mod split_log;
+pub mod stats_snapshot;
+pub mod tcp_bridge;
+pub mod tcp_port;
+pub mod template;
+pub mod text_encoding;
+pub mod variables;
+pub mod watch;
+pub mod xmodem;
@@ -0,0 +1,157 @@
+//! Keyboard typing straight into the port: per keystroke, or a line at a time
+//! with local editing for devices that cannot cope with single bytes.
+
+use serde::{Deserialize, Serialize};
+
+/// When typed text leaves for the port.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum SendDiscipline {
+    /// Compose in the send box and press Send.
+    #[default]
+    SendBox,
+    /// Every keystroke is written as it is typed.
+    Character,
+    /// Keystrokes are edited locally and the line is sent on Enter.
+    Line,
+}
+
+impl SendDiscipline {
+    pub const ALL: [SendDiscipline; 3] = [SendDiscipline::SendBox, SendDiscipline::Character, SendDiscipline::Line];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            SendDiscipline::SendBox => "Send box",
+            SendDiscipline::Character => "Per character",
+            SendDiscipline::Line => "Per line",
+        }
+    }
+}
+
+/// The local line in line mode, with a cursor counted in characters.
+#[derive(Debug, Clone, Default)]
+pub struct LineEditor {
+    line: String,
+    cursor: usize,
+}
+
+impl LineEditor {
+    pub fn text(&self) -> &str {
+        &self.line
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.line.is_empty()
+    }
+
+    fn byte_index(&self, chars: usize) -> usize {
+        self.line.char_indices().nth(chars).map_or(self.line.len(), |(index, _)| index)
+    }
+
+    /// Insert typed or pasted text at the cursor. Line breaks in pasted text
+    /// are dropped; Enter is what ends a line.
+    pub fn insert(&mut self, text: &str) {
+        let text: String = text.chars().filter(|c| *c != '\r' && *c != '\n').collect();
+        let at = self.byte_index(self.cursor);
+        self.line.insert_str(at, &text);
+        self.cursor += text.chars().count();
+    }
+
+    /// Remove the character before the cursor.
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let at = self.byte_index(self.cursor);
+            self.line.remove(at);
+        }
+    }
+
+    /// Remove the character under the cursor.
+    pub fn delete(&mut self) {
+        if self.cursor < self.line.chars().count() {
+            let at = self.byte_index(self.cursor);
+            self.line.remove(at);
+        }
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.line.chars().count());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.line.chars().count();
+    }
+
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.cursor = 0;
+    }
+
+    /// The finished line, leaving the editor empty.
+    pub fn take_line(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_happen_at_the_cursor() {
+        let mut editor = LineEditor::default();
+        editor.insert("AT+CGM");
+        editor.backspace();
+        editor.insert("MR");
+        assert_eq!(editor.text(), "AT+CGMR");
+
+        editor.home();
+        editor.right();
+        editor.right();
+        editor.delete();
+        assert_eq!(editor.text(), "ATCGMR");
+        editor.insert("+");
+        editor.end();
+        editor.insert("?");
+        assert_eq!(editor.text(), "AT+CGMR?");
+        assert_eq!(editor.cursor(), 8);
+
+        editor.home();
+        editor.backspace();
+        editor.left();
+        assert_eq!(editor.cursor(), 0);
+        assert_eq!(editor.take_line(), "AT+CGMR?");
+        assert!(editor.is_empty());
+        assert_eq!(editor.cursor(), 0);
+    }
+
+    #[test]
+    fn multibyte_characters_and_pasted_newlines() {
+        let mut editor = LineEditor::default();
+        editor.insert("température\r\n");
+        assert_eq!(editor.text(), "température");
+        assert_eq!(editor.cursor(), 11);
+        for _ in 0..8 {
+            editor.left();
+        }
+        editor.backspace();
+        editor.insert("m");
+        editor.delete();
+        assert_eq!(editor.text(), "temérature");
+        editor.clear();
+        assert!(editor.is_empty());
+    }
+
+}
@@ -0,0 +1,161 @@
+//! Absolute positions of terminal lines. The capture buffer drops its oldest
+//! bytes as it fills; offsets and line numbers here count from the start of the
+//! session, so they stay the same after old data is gone.
+
+/// Where a line begins: its byte offset and 1-based line number in the session.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineStart {
+    pub offset: u64,
+    pub line: u64,
+}
+
+/// What has been dropped from the front of the capture so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Evicted {
+    pub bytes: u64,
+    pub lines: u64,
+}
+
+impl Evicted {
+    /// Account for `dropped` leaving the front of the buffer.
+    pub fn drop_front(&mut self, dropped: &[u8]) {
+        self.bytes += dropped.len() as u64;
+        self.lines += dropped.iter().filter(|&&b| b == b'\n').count() as u64;
+    }
+
+    /// Position of the first byte still in the buffer.
+    pub fn start(&self) -> LineStart {
+        LineStart {
+            offset: self.bytes,
+            line: self.lines + 1,
+        }
+    }
+}
+
+impl LineStart {
+    /// The position `bytes` further on.
+    pub fn advance(self, bytes: &[u8]) -> LineStart {
+        LineStart {
+            offset: self.offset + bytes.len() as u64,
+            line: self.line + bytes.iter().filter(|&&b| b == b'\n').count() as u64,
+        }
+    }
+}
+
+/// Split `data`, which begins at `start`, into lines that each end after a
+/// `\n` (the last one may not), with the position of each.
+pub fn split_lines(data: &[u8], start: LineStart) -> Vec<(LineStart, &[u8])> {
+    let mut lines = Vec::new();
+    let mut position = start;
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        lines.push((position, line));
+        position = position.advance(line);
+    }
+    lines
+}
+
+/// What "Go to" looks for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GotoTarget {
+    Offset(u64),
+    Line(u64),
+}
+
+/// A byte offset typed as decimal or `0x` hex.
+pub fn parse_offset(text: &str) -> Result<u64, String> {
+    let text = text.trim().replace('_', "");
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("Not an offset: {}", text))
+}
+
+/// Index of the display line holding `target`. `lines` has one entry per
+/// display line, `None` for lines with no data position (dividers, headers,
+/// continuations); `end` is the offset just past the last byte held.
+pub fn find_line(lines: &[Option<LineStart>], target: GotoTarget, end: u64) -> Result<usize, String> {
+    let positioned = || lines.iter().enumerate().filter_map(|(index, line)| line.map(|line| (index, line)));
+    let Some((_, first)) = positioned().next() else {
+        return Err("This view has no byte offsets".to_string());
+    };
+
+    match target {
+        GotoTarget::Offset(offset) => {
+            if offset < first.offset {
+                return Err(format!(
+                    "Offset {} is no longer held; the oldest kept is {}",
+                    offset, first.offset
+                ));
+            }
+            if offset >= end {
+                return Err(format!("Offset {} has not been received (end is {})", offset, end));
+            }
+            Ok(positioned()
+                .take_while(|(_, line)| line.offset <= offset)
+                .last()
+                .map_or(0, |(index, _)| index))
+        }
+        GotoTarget::Line(number) => {
+            if number < first.line {
+                return Err(format!(
+                    "Line {} is no longer held; the oldest kept is {}",
+                    number, first.line
+                ));
+            }
+            positioned()
+                .find(|(_, line)| line.line == number)
+                .map(|(index, _)| index)
+                .ok_or_else(|| format!("Line {} has not been received", number))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_survive_eviction() {
+        let mut buffer = b"boot\nok\nready\n> ".to_vec();
+        let mut evicted = Evicted::default();
+
+        // Drop the first line and part of the second
+        let dropped: Vec<u8> = buffer.drain(..6).collect();
+        evicted.drop_front(&dropped);
+        assert_eq!(evicted.start(), LineStart { offset: 6, line: 2 });
+
+        let lines = split_lines(&buffer, evicted.start());
+        let starts: Vec<(u64, u64)> = lines.iter().map(|(s, _)| (s.offset, s.line)).collect();
+        assert_eq!(starts, [(6, 2), (8, 3), (14, 4)]);
+        assert_eq!(lines[1].1, b"ready\n");
+        assert_eq!(lines[2].1, b"> ");
+    }
+
+    #[test]
+    fn offsets_parse_as_decimal_or_hex() {
+        assert_eq!(parse_offset("4096"), Ok(4096));
+        assert_eq!(parse_offset(" 0x1F00 "), Ok(0x1F00));
+        assert_eq!(parse_offset("1_000_000"), Ok(1_000_000));
+        assert!(parse_offset("0xZZ").is_err());
+        assert!(parse_offset("").is_err());
+    }
+
+    #[test]
+    fn finds_the_line_holding_a_target() {
+        let at = |offset, line| Some(LineStart { offset, line });
+        // A divider line and a continuation with no position of their own
+        let lines = [at(100, 7), None, at(110, 8), None, at(150, 9)];
+
+        assert_eq!(find_line(&lines, GotoTarget::Offset(100), 160), Ok(0));
+        assert_eq!(find_line(&lines, GotoTarget::Offset(149), 160), Ok(2));
+        assert_eq!(find_line(&lines, GotoTarget::Offset(159), 160), Ok(4));
+        assert!(find_line(&lines, GotoTarget::Offset(99), 160).unwrap_err().contains("oldest kept is 100"));
+        assert!(find_line(&lines, GotoTarget::Offset(160), 160).is_err());
+
+        assert_eq!(find_line(&lines, GotoTarget::Line(8), 160), Ok(2));
+        assert!(find_line(&lines, GotoTarget::Line(3), 160).is_err());
+        assert!(find_line(&lines, GotoTarget::Line(10), 160).is_err());
+        assert!(find_line(&[None, None], GotoTarget::Line(1), 0).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+//! Reassembles complete text lines from arbitrarily split RX chunks
+
+/// Partial lines longer than this are flushed as-is so a stream without
+/// newlines cannot grow the buffer forever.
+pub const MAX_LINE_LENGTH: usize = 1024;
+
+/// How long RX must be quiet before a partial line counts as complete.
+pub const DEFAULT_IDLE_FLUSH_MS: u64 = 200;
+
+/// Added where a line completed by `flush` is logged, since it had no newline.
+pub const NO_TERMINATOR_NOTE: &str = " [no terminator]";
+
+/// Collects bytes until a newline; feed it every RX chunk in order.
+#[derive(Default)]
+pub struct LineAssembler {
+    partial: Vec<u8>,
+    /// The last line was completed by `flush`, so a late `\r\n` ends that
+    /// line instead of starting an empty one.
+    flushed: bool,
+}
+
+impl LineAssembler {
+    /// Feed a chunk and return the lines it completed, without their `\r\n` / `\n`.
+    pub fn push(&mut self, data: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+        for &byte in data {
+            if self.flushed {
+                match byte {
+                    b'\r' => continue,
+                    b'\n' => {
+                        self.flushed = false;
+                        continue;
+                    }
+                    _ => self.flushed = false,
+                }
+            }
+            if byte == b'\n' {
+                lines.push(self.take_line());
+            } else {
+                self.partial.push(byte);
+                if self.partial.len() >= MAX_LINE_LENGTH {
+                    lines.push(self.take_line());
+                }
+            }
+        }
+        lines
+    }
+
+    /// Complete the partial line, e.g. after the line went quiet without a
+    /// newline. Whatever arrives next starts a new line, so nothing is returned twice.
+    pub fn flush(&mut self) -> Option<String> {
+        if self.partial.is_empty() {
+            return None;
+        }
+        self.flushed = true;
+        Some(self.take_line())
+    }
+
+    fn take_line(&mut self) -> String {
+        if self.partial.last() == Some(&b'\r') {
+            self.partial.pop();
+        }
+        let line = String::from_utf8_lossy(&self.partial).into_owned();
+        self.partial.clear();
+        line
+    }
+}
+
+/// Like `LineAssembler`, but hands back the raw bytes of each line with its
+/// terminator, for paths that pass the data on as received.
+#[derive(Default)]
+pub struct LineBuffer {
+    partial: Vec<u8>,
+    /// Set while lines are UTF-16: the newline code unit, which only ends a
+    /// line on a unit boundary, and pieces are handed back whole units.
+    newline_unit: Option<[u8; 2]>,
+    /// The held line starts on the second byte of a code unit.
+    mid_unit: bool,
+}
+
+impl LineBuffer {
+    /// Split on `newline_unit` from now on, or on `\n` bytes for `None`.
+    /// `mid_unit` says the held line, or the next one, starts on the second
+    /// byte of a code unit, as after switching encodings mid-stream.
+    pub fn set_newline_unit(&mut self, newline_unit: Option<[u8; 2]>, mid_unit: bool) {
+        self.newline_unit = newline_unit;
+        self.mid_unit = newline_unit.is_some() && mid_unit;
+    }
+
+    /// Feed a chunk and return the lines it completed, `\n` included. A partial
+    /// line that reaches `MAX_LINE_LENGTH` is returned as it is, so data without
+    /// newlines passes through in pieces instead of piling up.
+    pub fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        if let Some(newline) = self.newline_unit {
+            return self.push_units(data, newline);
+        }
+        let mut pieces = Vec::new();
+        let mut rest = data;
+        while let Some(newline) = rest.iter().position(|&b| b == b'\n') {
+            self.partial.extend_from_slice(&rest[..=newline]);
+            pieces.push(std::mem::take(&mut self.partial));
+            rest = &rest[newline + 1..];
+        }
+        self.partial.extend_from_slice(rest);
+        if self.partial.len() >= MAX_LINE_LENGTH {
+            pieces.push(std::mem::take(&mut self.partial));
+        }
+        pieces
+    }
+
+    /// `push` for UTF-16, counting units from the start of the held line.
+    fn push_units(&mut self, data: &[u8], newline: [u8; 2]) -> Vec<Vec<u8>> {
+        let mut pieces = Vec::new();
+        let mut at = self.whole_units();
+        self.partial.extend_from_slice(data);
+        let mut start = 0;
+        while at + 1 < self.partial.len() {
+            at += 2;
+            if self.partial[at - 2..at] == newline {
+                pieces.push(self.partial[start..at].to_vec());
+                start = at;
+                self.mid_unit = false;
+            }
+        }
+        self.partial.drain(..start);
+        if self.partial.len() >= MAX_LINE_LENGTH {
+            pieces.extend(self.take_units());
+        }
+        pieces
+    }
+
+    /// The held partial line, e.g. once nothing has completed it for a while.
+    /// For UTF-16 a trailing half unit stays held.
+    pub fn flush(&mut self) -> Option<Vec<u8>> {
+        if self.newline_unit.is_some() {
+            return self.take_units();
+        }
+        (!self.partial.is_empty()).then(|| std::mem::take(&mut self.partial))
+    }
+
+    /// Length of the held line up to a half unit at its end.
+    fn whole_units(&self) -> usize {
+        let skew = usize::from(self.mid_unit).min(self.partial.len());
+        skew + ((self.partial.len() - skew) & !1)
+    }
+
+    fn take_units(&mut self) -> Option<Vec<u8>> {
+        let whole = self.whole_units();
+        self.mid_unit = false;
+        (whole > 0).then(|| self.partial.drain(..whole).collect())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.partial.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.partial.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_lines_split_across_chunks() {
+        let mut lines = LineAssembler::default();
+        assert!(lines.push(b"RELAY").is_empty());
+        assert_eq!(lines.push(b" ON\r\nPOWER CY"), vec!["RELAY ON"]);
+        assert_eq!(lines.push(b"CLE\n\n"), vec!["POWER CYCLE", ""]);
+    }
+
+    #[test]
+    fn idle_flush_then_continuation_is_a_new_line() {
+        let mut lines = LineAssembler::default();
+        assert!(lines.push(b"READY> ").is_empty());
+        assert_eq!(lines.flush().as_deref(), Some("READY> "));
+        assert_eq!(lines.flush(), None);
+        assert_eq!(lines.push(b"cmd ok\nnext"), vec!["cmd ok"]);
+        assert_eq!(lines.push(b"\n"), vec!["next"]);
+    }
+
+    #[test]
+    fn terminator_after_idle_flush_ends_the_flushed_line() {
+        let mut lines = LineAssembler::default();
+        lines.push(b"T=21.5");
+        assert_eq!(lines.flush().as_deref(), Some("T=21.5"));
+        // The late CR LF belongs to the flushed line, not an empty one
+        assert!(lines.push(b"\r").is_empty());
+        assert_eq!(lines.push(b"\nT=21.6\n\n"), vec!["T=21.6", ""]);
+
+        // A CR that arrived before the flush is dropped with it
+        lines.push(b"T=21.7\r");
+        assert_eq!(lines.flush().as_deref(), Some("T=21.7"));
+        assert_eq!(lines.push(b"\n"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn flushes_overlong_lines() {
+        let mut lines = LineAssembler::default();
+        let out = lines.push(&vec![b'x'; MAX_LINE_LENGTH + 5]);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].len(), MAX_LINE_LENGTH);
+        assert_eq!(lines.push(b"\n"), vec!["xxxxx"]);
+    }
+
+    #[test]
+    fn line_buffer_keeps_terminators_and_passes_binary_through() {
+        let mut lines = LineBuffer::default();
+        assert!(lines.push(b"ERROR: sensor").is_empty());
+        assert_eq!(lines.push(b" timeout\r\nok\nT="), [b"ERROR: sensor timeout\r\n".to_vec(), b"ok\n".to_vec()]);
+        assert_eq!(lines.flush(), Some(b"T=".to_vec()));
+        assert!(lines.is_empty());
+
+        // No newline in sight: handed on once the line limit is reached
+        assert!(lines.push(&[0xAA; MAX_LINE_LENGTH - 1]).is_empty());
+        let pieces = lines.push(&[0x55; 10]);
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(pieces[0].len(), MAX_LINE_LENGTH + 9);
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn utf16_lines_end_on_newline_units() {
+        let mut lines = LineBuffer::default();
+        lines.set_newline_unit(Some([b'\n', 0]), false);
+        // "ok\n" then U+010A, whose low byte is 0x0A, in reads of odd length
+        assert!(lines.push(&[b'o', 0, b'k', 0, b'\n']).is_empty());
+        assert_eq!(lines.push(&[0, 0x0A, 0x01, b'x']), [vec![b'o', 0, b'k', 0, b'\n', 0]]);
+        assert_eq!(lines.flush(), Some(vec![0x0A, 0x01]));
+        assert!(!lines.is_empty(), "the half unit waits for its other byte");
+        assert_eq!(lines.push(&[0, b'\n', 0]), [vec![b'x', 0, b'\n', 0]]);
+
+        // Switched to mid-stream, with the held line starting on a unit's second byte
+        let mut lines = LineBuffer::default();
+        assert_eq!(lines.push(&[b'A', 0, b'\n', 0]), [vec![b'A', 0, b'\n']]);
+        lines.set_newline_unit(Some([b'\n', 0]), true);
+        assert_eq!(lines.push(&[b'B', 0, b'\n', 0, b'C']), [vec![0, b'B', 0, b'\n', 0]]);
+        assert_eq!(lines.flush(), None, "C waits for its other byte");
+    }
+}
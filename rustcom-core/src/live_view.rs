@@ -0,0 +1,330 @@
+//! Read-only live view in a browser: a single HTML page that polls a small
+//! JSON state endpoint for the latest received lines, the connection status
+//! and the capture counters. Listens on localhost unless LAN access is turned
+//! on, which requires a token in the page's query string.
+
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::Metrics;
+
+pub const DEFAULT_LIVE_VIEW_PORT: u16 = 8787;
+pub const DEFAULT_LIVE_VIEW_LINES: usize = 200;
+pub const MAX_LIVE_VIEW_LINES: usize = 5000;
+
+const PAGE: &str = include_str!("live_view.html");
+
+/// Live view settings, saved with the global settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LiveViewConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// How many of the latest lines the page shows.
+    pub lines: usize,
+    /// Listen on every interface instead of 127.0.0.1 only.
+    pub expose_lan: bool,
+    /// Required as `?token=` when not empty; always required with `expose_lan`.
+    pub token: String,
+}
+
+impl Default for LiveViewConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_LIVE_VIEW_PORT,
+            lines: DEFAULT_LIVE_VIEW_LINES,
+            expose_lan: false,
+            token: String::new(),
+        }
+    }
+}
+
+impl LiveViewConfig {
+    pub fn bind_address(&self) -> String {
+        let host = if self.expose_lan { "0.0.0.0" } else { "127.0.0.1" };
+        format!("{}:{}", host, self.port)
+    }
+}
+
+/// 32 random hex digits.
+pub fn new_token() -> String {
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    format!(
+        "{:016x}{:016x}",
+        RandomState::new().hash_one(seed),
+        RandomState::new().hash_one(seed)
+    )
+}
+
+/// The latest received lines and the port they came from. Shared between the
+/// app and the server thread.
+#[derive(Debug)]
+pub struct LiveFeed {
+    lines: Mutex<VecDeque<String>>,
+    capacity: AtomicUsize,
+    /// Lines pushed since the start, so the page can tell when to redraw.
+    total: AtomicU64,
+    port: Mutex<String>,
+}
+
+impl Default for LiveFeed {
+    fn default() -> Self {
+        Self::new(DEFAULT_LIVE_VIEW_LINES)
+    }
+}
+
+impl LiveFeed {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Mutex::new(VecDeque::new()),
+            capacity: AtomicUsize::new(capacity.clamp(1, MAX_LIVE_VIEW_LINES)),
+            total: AtomicU64::new(0),
+            port: Mutex::new(String::new()),
+        }
+    }
+
+    pub fn push_line(&self, line: String) {
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        let mut lines = self.lines.lock().unwrap();
+        lines.push_back(line);
+        while lines.len() > capacity {
+            lines.pop_front();
+        }
+        self.total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Keep at most `capacity` lines from now on, dropping the oldest.
+    pub fn set_capacity(&self, capacity: usize) {
+        let capacity = capacity.clamp(1, MAX_LIVE_VIEW_LINES);
+        self.capacity.store(capacity, Ordering::Relaxed);
+        let mut lines = self.lines.lock().unwrap();
+        while lines.len() > capacity {
+            lines.pop_front();
+        }
+    }
+
+    pub fn set_port(&self, port: &str) {
+        *self.port.lock().unwrap() = port.to_string();
+    }
+
+    pub fn clear(&self) {
+        self.lines.lock().unwrap().clear();
+        self.total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Everything the page shows, read from the feed and the counters.
+    pub fn state(&self, metrics: &Metrics) -> LiveState {
+        LiveState {
+            port: self.port.lock().unwrap().clone(),
+            connected: metrics.connected.load(Ordering::Relaxed),
+            bytes_received: metrics.bytes_received.load(Ordering::Relaxed),
+            bytes_sent: metrics.bytes_sent.load(Ordering::Relaxed),
+            frames_decoded: metrics.frames_decoded.load(Ordering::Relaxed),
+            frame_errors: metrics.frame_errors.load(Ordering::Relaxed),
+            reconnects: metrics.reconnects.load(Ordering::Relaxed),
+            total_lines: self.total.load(Ordering::Relaxed),
+            lines: self.lines.lock().unwrap().iter().cloned().collect(),
+        }
+    }
+}
+
+/// The `/state` document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveState {
+    pub port: String,
+    pub connected: bool,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    pub frames_decoded: u64,
+    pub frame_errors: u64,
+    pub reconnects: u64,
+    pub total_lines: u64,
+    pub lines: Vec<String>,
+}
+
+/// The value of `token` in a request URL's query string.
+fn query_token(url: &str) -> Option<&str> {
+    let (_, query) = url.split_once('?')?;
+    query.split('&').find_map(|pair| pair.strip_prefix("token="))
+}
+
+fn respond(request: tiny_http::Request, body: String, content_type: &str, status: u16) {
+    let header = tiny_http::Header::from_bytes("Content-Type", content_type).unwrap();
+    let no_cache = tiny_http::Header::from_bytes("Cache-Control", "no-store").unwrap();
+    let response = tiny_http::Response::from_string(body)
+        .with_header(header)
+        .with_header(no_cache)
+        .with_status_code(status);
+    let _ = request.respond(response);
+}
+
+/// Serves the page at `/` and its state at `/state` on its own thread until dropped.
+pub struct LiveViewServer {
+    server: Arc<tiny_http::Server>,
+    port: u16,
+    token: String,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl LiveViewServer {
+    pub fn start(config: &LiveViewConfig, feed: Arc<LiveFeed>, metrics: Arc<Metrics>) -> Result<Self, String> {
+        let token = config.token.trim().to_string();
+        if config.expose_lan && token.is_empty() {
+            return Err("Set a token before opening the live view to the LAN".to_string());
+        }
+        if !token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err("The live view token may only use letters, digits, '-' and '_'".to_string());
+        }
+        let address = config.bind_address();
+        let server = tiny_http::Server::http(&address)
+            .map(Arc::new)
+            .map_err(|e| format!("Cannot listen on {}: {}", address, e))?;
+        let port = server.server_addr().to_ip().map_or(config.port, |addr| addr.port());
+        let worker = {
+            let server = server.clone();
+            let token = token.clone();
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    if *request.method() != tiny_http::Method::Get {
+                        respond(request, "Read-only\n".to_string(), "text/plain; charset=utf-8", 405);
+                        continue;
+                    }
+                    if !token.is_empty() && query_token(request.url()) != Some(token.as_str()) {
+                        respond(request, "Add ?token= to the URL\n".to_string(), "text/plain; charset=utf-8", 403);
+                        continue;
+                    }
+                    let path = request.url().split('?').next().unwrap_or_default().to_string();
+                    match path.as_str() {
+                        "/" | "/index.html" => respond(request, PAGE.to_string(), "text/html; charset=utf-8", 200),
+                        "/state" => {
+                            let body = serde_json::to_string(&feed.state(&metrics)).unwrap_or_default();
+                            respond(request, body, "application/json", 200)
+                        }
+                        _ => respond(request, "Not found\n".to_string(), "text/plain; charset=utf-8", 404),
+                    }
+                }
+            })
+        };
+        Ok(Self {
+            server,
+            port,
+            token,
+            worker: Some(worker),
+        })
+    }
+
+    /// The port actually bound, e.g. when configured as 0.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The page as opened on this machine, with the token if one is set.
+    pub fn url(&self) -> String {
+        if self.token.is_empty() {
+            format!("http://127.0.0.1:{}/", self.port)
+        } else {
+            format!("http://127.0.0.1:{}/?token={}", self.port, self.token)
+        }
+    }
+}
+
+impl Drop for LiveViewServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    fn get(port: u16, path: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn config(token: &str) -> LiveViewConfig {
+        LiveViewConfig {
+            port: 0,
+            token: token.to_string(),
+            ..LiveViewConfig::default()
+        }
+    }
+
+    #[test]
+    fn feed_keeps_the_latest_lines() {
+        let feed = LiveFeed::new(3);
+        for i in 0..5 {
+            feed.push_line(format!("line {}", i));
+        }
+        let metrics = Metrics::default();
+        Metrics::add(&metrics.bytes_received, 42);
+        let state = feed.state(&metrics);
+        assert_eq!(state.lines, ["line 2", "line 3", "line 4"]);
+        assert_eq!(state.total_lines, 5);
+        assert_eq!(state.bytes_received, 42);
+
+        feed.set_capacity(1);
+        assert_eq!(feed.state(&metrics).lines, ["line 4"]);
+    }
+
+    #[test]
+    fn serves_the_page_and_state() {
+        let feed = Arc::new(LiveFeed::default());
+        let metrics = Arc::new(Metrics::default());
+        feed.set_port("COM3");
+        feed.push_line("T=21.5".to_string());
+        metrics.connected.store(true, Ordering::Relaxed);
+        let server = LiveViewServer::start(&config(""), feed, metrics).unwrap();
+        assert_eq!(server.url(), format!("http://127.0.0.1:{}/", server.port()));
+
+        let page = get(server.port(), "/");
+        assert!(page.starts_with("HTTP/1.1 200"));
+        assert!(page.contains("text/html"));
+        assert!(page.contains("fetch(\"state\""));
+
+        let response = get(server.port(), "/state");
+        assert!(response.contains("application/json"));
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let state: LiveState = serde_json::from_str(body).unwrap();
+        assert_eq!(state.port, "COM3");
+        assert!(state.connected);
+        assert_eq!(state.lines, ["T=21.5"]);
+
+        assert!(get(server.port(), "/other").starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn token_is_required_when_set() {
+        let server = LiveViewServer::start(&config("s3cret"), Arc::default(), Arc::default()).unwrap();
+        assert!(get(server.port(), "/state").starts_with("HTTP/1.1 403"));
+        assert!(get(server.port(), "/state?token=wrong").starts_with("HTTP/1.1 403"));
+        assert!(get(server.port(), "/state?x=1&token=s3cret").starts_with("HTTP/1.1 200"));
+        assert!(server.url().ends_with("/?token=s3cret"));
+
+        let lan = LiveViewConfig {
+            expose_lan: true,
+            ..config("")
+        };
+        assert!(LiveViewServer::start(&lan, Arc::default(), Arc::default()).is_err());
+        assert!(LiveViewServer::start(&config("a b"), Arc::default(), Arc::default()).is_err());
+        assert_eq!(lan.bind_address(), "0.0.0.0:0");
+        assert_eq!(new_token().len(), 32);
+    }
+}
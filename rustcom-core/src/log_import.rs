@@ -0,0 +1,362 @@
+//! Reading RustCOM's own logs back in: Save Log text, CSV and pcapng exports.
+//! Each becomes the timestamped, direction-tagged entries it was written from,
+//! so an old capture can be replayed as an offline session. Lines or packets
+//! that cannot be read are skipped and counted rather than failing the import.
+
+use chrono::{Local, TimeZone};
+
+use crate::export::parse_timestamp;
+use crate::hex::parse_hex_input;
+use crate::logging::{DataLogEntry, Direction, TIMESTAMP_FORMAT};
+
+/// First line of a CSV export.
+pub const CSV_HEADER: &str = "timestamp,direction,hex,text,discontinuity";
+/// pcapng section header block type, the first four bytes of the file.
+const PCAPNG_MAGIC: [u8; 4] = [0x0A, 0x0D, 0x0D, 0x0A];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// `[timestamp] RX: text` lines. Bytes that were not UTF-8 were already
+    /// replaced when the log was written.
+    Text,
+    /// Exact bytes from the hex column.
+    Csv,
+    Pcapng,
+}
+
+impl LogFormat {
+    pub fn as_str(&self) -> &str {
+        match self {
+            LogFormat::Text => "Text",
+            LogFormat::Csv => "CSV",
+            LogFormat::Pcapng => "pcapng",
+        }
+    }
+
+    /// The format of a file from its first bytes.
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(&PCAPNG_MAGIC) {
+            LogFormat::Pcapng
+        } else if data.starts_with(CSV_HEADER.as_bytes()) {
+            LogFormat::Csv
+        } else {
+            LogFormat::Text
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportedLog {
+    pub entries: Vec<DataLogEntry>,
+    /// Lines, rows or packets that could not be read.
+    pub skipped: usize,
+}
+
+/// Read a log in whichever format it is in.
+pub fn import(data: &[u8]) -> Result<(LogFormat, ImportedLog), String> {
+    let format = LogFormat::detect(data);
+    let log = match format {
+        LogFormat::Text => import_text(&String::from_utf8_lossy(data)),
+        LogFormat::Csv => import_csv(&String::from_utf8_lossy(data)),
+        LogFormat::Pcapng => import_pcapng(data)?,
+    };
+    if log.entries.is_empty() {
+        return Err(match log.skipped {
+            0 => "The file has no log entries".to_string(),
+            skipped => format!("No log entries could be read ({} skipped)", skipped),
+        });
+    }
+    Ok((format, log))
+}
+
+/// The timestamp and direction of a `[timestamp] RX: ` line, and the rest of
+/// it. `Err` for a line shaped like one whose timestamp does not parse.
+fn text_header(line: &str) -> Option<Result<(String, Direction, &str), ()>> {
+    let (timestamp, rest) = line.strip_prefix('[')?.split_once("] ")?;
+    let (direction, data) = rest.split_once(": ")?;
+    let direction = Direction::parse(direction)?;
+    if parse_timestamp(timestamp).is_none() {
+        return Some(Err(()));
+    }
+    Some(Ok((timestamp.to_string(), direction, data)))
+}
+
+/// Save Log text. An entry runs until the next line that starts like one, so
+/// data with line breaks in it comes back whole; the newline every entry ends
+/// with is dropped. Lines before the first entry, and those after a header
+/// with a bad timestamp, are skipped.
+pub fn import_text(text: &str) -> ImportedLog {
+    let mut log = ImportedLog::default();
+    let mut current: Option<DataLogEntry> = None;
+    let finish = |entry: Option<DataLogEntry>, log: &mut ImportedLog| {
+        if let Some(mut entry) = entry {
+            if entry.data.last() == Some(&b'\n') {
+                entry.data.pop();
+            }
+            log.entries.push(entry);
+        }
+    };
+    // Whether lines are skipped until the next good header
+    let mut skipping = false;
+    for line in text.split_inclusive('\n') {
+        match text_header(line) {
+            Some(Ok((timestamp, direction, data))) => {
+                finish(current.take(), &mut log);
+                skipping = false;
+                current = Some(DataLogEntry {
+                    timestamp,
+                    direction,
+                    data: data.as_bytes().to_vec(),
+                });
+            }
+            Some(Err(())) => {
+                finish(current.take(), &mut log);
+                skipping = true;
+                log.skipped += 1;
+            }
+            None => match current.as_mut() {
+                Some(entry) if !skipping => entry.data.extend_from_slice(line.as_bytes()),
+                _ if line.trim().is_empty() => {}
+                _ => log.skipped += 1,
+            },
+        }
+    }
+    finish(current, &mut log);
+    log
+}
+
+/// Split CSV into records of fields. Quoted fields may hold commas, quotes
+/// and line breaks. `None` for a record cut off inside a quoted field.
+fn csv_records(text: &str) -> Vec<Option<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                if field.ends_with('\r') {
+                    field.pop();
+                }
+                fields.push(std::mem::take(&mut field));
+                records.push(Some(std::mem::take(&mut fields)));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        records.push(None);
+    } else if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push(Some(fields));
+    }
+    records
+}
+
+fn csv_entry(fields: &[String]) -> Option<DataLogEntry> {
+    let [timestamp, direction, hex, ..] = fields else {
+        return None;
+    };
+    parse_timestamp(timestamp)?;
+    let data = if hex.trim().is_empty() {
+        Vec::new()
+    } else {
+        parse_hex_input(hex).ok()?
+    };
+    Some(DataLogEntry {
+        timestamp: timestamp.trim().to_string(),
+        direction: Direction::parse(direction.trim())?,
+        data,
+    })
+}
+
+/// A CSV export. The data comes from the hex column, so it is exact; rows
+/// with a bad timestamp, direction or hex are skipped.
+pub fn import_csv(text: &str) -> ImportedLog {
+    let mut log = ImportedLog::default();
+    for record in csv_records(text).into_iter().skip(1) {
+        match record.as_deref().and_then(csv_entry) {
+            Some(entry) => log.entries.push(entry),
+            None if record.as_ref().is_some_and(|fields| fields.concat().trim().is_empty()) => {}
+            None => log.skipped += 1,
+        }
+    }
+    log
+}
+
+fn le_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+/// An enhanced packet block body as written by the export: RX and TX by the
+/// flags option, events as packets without one.
+fn pcapng_entry(body: &[u8]) -> Option<DataLogEntry> {
+    let micros = (u64::from(le_u32(body, 4)?) << 32) | u64::from(le_u32(body, 8)?);
+    let length = le_u32(body, 12)? as usize;
+    let data = body.get(20..20 + length)?.to_vec();
+    let mut at = 20 + length.div_ceil(4) * 4;
+    let mut direction = Direction::Event;
+    while let (Some(code), Some(len)) = (le_u16(body, at), le_u16(body, at + 2)) {
+        let value = body.get(at + 4..at + 4 + len as usize)?;
+        match (code, le_u32(value, 0)) {
+            (0, _) => break,
+            (2, Some(1)) => direction = Direction::Received,
+            (2, Some(2)) => direction = Direction::Sent,
+            _ => {}
+        }
+        at += 4 + (len as usize).div_ceil(4) * 4;
+    }
+    let time = Local.timestamp_micros(i64::try_from(micros).ok()?).single()?;
+    Some(DataLogEntry {
+        timestamp: time.format(TIMESTAMP_FORMAT).to_string(),
+        direction,
+        data,
+    })
+}
+
+/// A pcapng export. Only little-endian files, as the export writes, are read;
+/// unreadable packets are skipped, and a block cut short ends the import.
+pub fn import_pcapng(data: &[u8]) -> Result<ImportedLog, String> {
+    if le_u32(data, 8) != Some(0x1A2B_3C4D) {
+        return Err("Not a little-endian pcapng file".to_string());
+    }
+    let mut log = ImportedLog::default();
+    let mut at = 0;
+    while at < data.len() {
+        let (Some(block_type), Some(total)) = (le_u32(data, at), le_u32(data, at + 4)) else {
+            log.skipped += 1;
+            break;
+        };
+        let total = total as usize;
+        let Some(body) = data.get(at + 8..at + total.saturating_sub(4)).filter(|_| total >= 12) else {
+            log.skipped += 1;
+            break;
+        };
+        if block_type == 6 {
+            match pcapng_entry(body) {
+                Some(entry) => log.entries.push(entry),
+                None => log.skipped += 1,
+            }
+        }
+        at += total;
+    }
+    Ok(log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{export_window, ExportFormat, TimeWindow};
+    use crate::logging::LogBuffer;
+
+    fn entry(timestamp: &str, direction: Direction, data: &[u8]) -> DataLogEntry {
+        DataLogEntry {
+            timestamp: timestamp.to_string(),
+            direction,
+            data: data.to_vec(),
+        }
+    }
+
+    fn sample() -> Vec<DataLogEntry> {
+        vec![
+            entry("2024-03-01 10:00:00.000", Direction::Received, b"boot\r\nready\r\n"),
+            entry("2024-03-01 10:00:05.120", Direction::Sent, b"AT,\"x\"\r"),
+            entry("2024-03-01 10:00:06.000", Direction::Event, b"FAULT"),
+            entry("2024-03-01 10:00:07.500", Direction::Received, b"[not a header] RX: \n\nOK"),
+        ]
+    }
+
+    fn export(format: ExportFormat, entries: &[DataLogEntry]) -> Vec<u8> {
+        let mut log = LogBuffer::default();
+        for entry in entries {
+            log.push(entry.clone());
+        }
+        let window = TimeWindow::between(
+            parse_timestamp("2024-01-01 00:00:00.000").unwrap(),
+            parse_timestamp("2025-01-01 00:00:00.000").unwrap(),
+        );
+        let mut out = Vec::new();
+        export_window(&mut log, window, format, &mut out).unwrap();
+        out
+    }
+
+    fn summary(entries: &[DataLogEntry]) -> Vec<(String, &str, Vec<u8>)> {
+        entries
+            .iter()
+            .map(|e| (e.timestamp.clone(), e.direction.as_str(), e.data.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn every_export_format_round_trips() {
+        let mut binary = sample();
+        binary.push(entry("2024-03-01 10:00:08.001", Direction::Received, &[0x00, 0xFF, 0x0A]));
+        for (format, expected, entries) in [
+            (ExportFormat::Text, LogFormat::Text, sample()),
+            (ExportFormat::Csv, LogFormat::Csv, binary.clone()),
+            (ExportFormat::Pcapng, LogFormat::Pcapng, binary),
+        ] {
+            let (detected, log) = import(&export(format, &entries)).unwrap();
+            assert_eq!(detected, expected);
+            assert_eq!(log.skipped, 0, "{:?}", format);
+            assert_eq!(summary(&log.entries), summary(&entries), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn unreadable_lines_and_rows_are_counted_and_skipped() {
+        let text = "\
+preamble from an editor
+[2024-03-01 10:00:00.000] RX: one
+[2024-03-01 10:00:01.000] XX: unknown direction stays data
+[yesterday] RX: bad stamp
+continuation of the bad entry
+
+[2024-03-01 10:00:02.000] TX: two
+";
+        let log = import_text(text);
+        assert_eq!(log.skipped, 3);
+        assert_eq!(
+            summary(&log.entries),
+            [
+                (
+                    "2024-03-01 10:00:00.000".to_string(),
+                    "RX",
+                    b"one\n[2024-03-01 10:00:01.000] XX: unknown direction stays data".to_vec()
+                ),
+                ("2024-03-01 10:00:02.000".to_string(), "TX", b"two".to_vec()),
+            ]
+        );
+
+        let csv = format!(
+            "{}\r\n2024-03-01 10:00:00.000,RX,41 42,AB,\r\nnot-a-time,RX,41,A,\n\
+             2024-03-01 10:00:01.000,ZZ,41,A,\n2024-03-01 10:00:02.000,TX,4G,?,\n\
+             2024-03-01 10:00:03.000,EV,,,\n2024-03-01 10:00:04.000,RX,41,\"cut off",
+            CSV_HEADER
+        );
+        let log = import_csv(&csv);
+        assert_eq!(log.skipped, 4);
+        assert_eq!(log.entries.len(), 2);
+        assert_eq!(log.entries[0].data, b"AB");
+        assert_eq!(log.entries[1].direction, Direction::Event);
+
+        assert_eq!(import(b"\n\n").err().unwrap(), "The file has no log entries");
+        assert_eq!(import(b"junk\n").err().unwrap(), "No log entries could be read (1 skipped)");
+        let mut cut = export(ExportFormat::Pcapng, &sample());
+        cut.truncate(cut.len() - 10);
+        let (_, log) = import(&cut).unwrap();
+        assert_eq!((log.entries.len(), log.skipped), (3, 1));
+    }
+}
@@ -0,0 +1,491 @@
+//! Captured RX/TX entries, their text and binary log formats, and a
+//! memory-bounded log buffer that spills to a temporary journal.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write as IoWrite};
+use std::path::PathBuf;
+use chrono::{DateTime, Local};
+
+pub mod database;
+pub mod stream;
+pub mod syslog;
+
+/// Default in-memory entry limit for `LogBuffer`.
+pub const DEFAULT_LOG_MAX_ENTRIES: usize = 100_000;
+/// Default in-memory data limit for `LogBuffer`.
+pub const DEFAULT_LOG_MAX_BYTES: usize = 16 * 1024 * 1024;
+/// Entries held after a failed spill before spilling is tried again, so a full
+/// disk does not cost a rewrite of everything in memory on every push.
+pub const SPILL_RETRY_ENTRIES: usize = 1_000;
+
+/// Which way the data went, from the analyzer's point of view.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Received,
+    Sent,
+    /// An event marker; the data is the marker text.
+    Event,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Direction::Received => "RX",
+            Direction::Sent => "TX",
+            Direction::Event => "EV",
+        }
+    }
+    /// The direction written as `as_str` gives it.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "RX" => Some(Direction::Received),
+            "TX" => Some(Direction::Sent),
+            "EV" => Some(Direction::Event),
+            _ => None,
+        }
+    }
+}
+
+/// One chunk of captured data.
+#[derive(Debug, Clone)]
+pub struct DataLogEntry {
+    pub timestamp: String,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+/// Format of `DataLogEntry::timestamp`.
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+impl DataLogEntry {
+    /// The entry as a text log line, `[timestamp] RX: text`, with its newline.
+    pub fn text_line(&self) -> String {
+        format!(
+            "[{}] {}: {}\n",
+            self.timestamp,
+            self.direction.as_str(),
+            String::from_utf8_lossy(&self.data)
+        )
+    }
+}
+
+/// An entry stamped with the current local time.
+pub fn create_log_entry(direction: Direction, data: &[u8]) -> DataLogEntry {
+    create_log_entry_at(direction, data, Local::now())
+}
+
+/// An entry stamped with `at`, e.g. when the data was read rather than processed.
+pub fn create_log_entry_at(direction: Direction, data: &[u8], at: DateTime<Local>) -> DataLogEntry {
+    DataLogEntry {
+        timestamp: at.format(TIMESTAMP_FORMAT).to_string(),
+        direction,
+        data: data.to_vec(),
+    }
+}
+
+/// Binary log record: direction byte (0 = RX, 1 = TX, 2 = event), u16 timestamp length,
+/// timestamp, u32 data length, data. Lengths are little-endian.
+pub fn write_record(out: &mut impl IoWrite, entry: &DataLogEntry) -> std::io::Result<()> {
+    let direction = match entry.direction {
+        Direction::Received => 0u8,
+        Direction::Sent => 1u8,
+        Direction::Event => 2u8,
+    };
+    out.write_all(&[direction])?;
+    out.write_all(&(entry.timestamp.len() as u16).to_le_bytes())?;
+    out.write_all(entry.timestamp.as_bytes())?;
+    out.write_all(&(entry.data.len() as u32).to_le_bytes())?;
+    out.write_all(&entry.data)
+}
+
+/// Read the next record written by `write_record`, or `None` at end of input.
+pub fn read_record(input: &mut impl Read) -> std::io::Result<Option<DataLogEntry>> {
+    let mut direction = [0u8; 1];
+    if input.read(&mut direction)? == 0 {
+        return Ok(None);
+    }
+    let direction = match direction[0] {
+        0 => Direction::Received,
+        1 => Direction::Sent,
+        2 => Direction::Event,
+        other => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("bad direction byte {}", other),
+            ))
+        }
+    };
+
+    let mut len = [0u8; 2];
+    input.read_exact(&mut len)?;
+    let mut timestamp = vec![0u8; u16::from_le_bytes(len) as usize];
+    input.read_exact(&mut timestamp)?;
+
+    let mut len = [0u8; 4];
+    input.read_exact(&mut len)?;
+    let mut data = vec![0u8; u32::from_le_bytes(len) as usize];
+    input.read_exact(&mut data)?;
+
+    Ok(Some(DataLogEntry {
+        timestamp: String::from_utf8_lossy(&timestamp).to_string(),
+        direction,
+        data,
+    }))
+}
+
+/// Log entries with bounded memory. Once the in-memory entries pass either
+/// limit they are appended to a temporary journal file, which is read back in
+/// order when the log is saved. The journal is deleted on `clear` and on drop.
+pub struct LogBuffer {
+    entries: VecDeque<DataLogEntry>,
+    bytes: usize,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+    /// Keep only the newest this many entries, dropping older ones instead of
+    /// spilling them, e.g. while they are streamed to a log file anyway.
+    pub keep_last: Option<usize>,
+    journal: Option<(PathBuf, BufWriter<File>)>,
+    /// Bytes of the journal holding whole spills.
+    journal_len: u64,
+    spilled: usize,
+    /// `(timestamp, text)` of every event entry, kept in memory so markers can
+    /// be listed without reading the journal.
+    events: Vec<(String, String)>,
+    /// Set when spilling failed; entries then stay in memory.
+    pub spill_error: Option<String>,
+    /// After a failed spill, the number of entries in memory at which to try again.
+    spill_retry_at: Option<usize>,
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::with_limits(DEFAULT_LOG_MAX_ENTRIES, DEFAULT_LOG_MAX_BYTES)
+    }
+}
+
+fn journal_path() -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "rustcom-journal-{}-{}.bin",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+impl LogBuffer {
+    /// An empty buffer that spills once it holds more than either limit.
+    pub fn with_limits(max_entries: usize, max_bytes: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            bytes: 0,
+            max_entries,
+            max_bytes,
+            keep_last: None,
+            journal: None,
+            journal_len: 0,
+            spilled: 0,
+            events: Vec::new(),
+            spill_error: None,
+            spill_retry_at: None,
+        }
+    }
+
+    /// Append an entry, spilling everything in memory if a limit is exceeded,
+    /// or with `keep_last` set, dropping the oldest entries past it.
+    pub fn push(&mut self, entry: DataLogEntry) {
+        if entry.direction == Direction::Event {
+            self.events.push((
+                entry.timestamp.clone(),
+                String::from_utf8_lossy(&entry.data).into_owned(),
+            ));
+        }
+        self.bytes += entry.data.len();
+        self.entries.push_back(entry);
+        if let Some(keep) = self.keep_last {
+            while self.entries.len() > keep {
+                if let Some(dropped) = self.entries.pop_front() {
+                    self.bytes -= dropped.data.len();
+                }
+            }
+            return;
+        }
+        let over = self.entries.len() > self.max_entries || self.bytes > self.max_bytes;
+        if over && self.spill_retry_at.is_none_or(|at| self.entries.len() >= at) {
+            if let Err(e) = self.spill() {
+                self.spill_retry_at = Some(self.entries.len() + SPILL_RETRY_ENTRIES);
+                self.spill_error.get_or_insert_with(|| format!("Cannot write log journal: {}", e));
+            }
+        }
+    }
+
+    fn spill(&mut self) -> std::io::Result<()> {
+        if self.journal.is_none() {
+            let path = journal_path();
+            let file = File::create(&path)?;
+            self.journal = Some((path, BufWriter::new(file)));
+            self.journal_len = 0;
+        }
+        let Some((_, writer)) = self.journal.as_mut() else {
+            return Ok(());
+        };
+        let written = self
+            .entries
+            .iter()
+            .try_for_each(|entry| write_record(writer, entry))
+            .and_then(|()| writer.flush());
+        if let Err(e) = written {
+            self.rewind_journal();
+            return Err(e);
+        }
+        self.journal_len = writer.get_ref().stream_position()?;
+        self.spilled += self.entries.len();
+        self.entries.clear();
+        self.bytes = 0;
+        self.spill_error = None;
+        self.spill_retry_at = None;
+        Ok(())
+    }
+
+    /// Cut off a spill that failed part way, so the entries it left in memory
+    /// are not in the journal twice once a later spill succeeds.
+    fn rewind_journal(&mut self) {
+        let Some((path, writer)) = self.journal.take() else {
+            return;
+        };
+        let (mut file, _unwritten) = writer.into_parts();
+        let _ = file.set_len(self.journal_len);
+        let _ = file.seek(SeekFrom::Start(self.journal_len));
+        self.journal = Some((path, BufWriter::new(file)));
+    }
+
+    /// Total entries, in memory and on disk.
+    pub fn len(&self) -> usize {
+        self.spilled + self.entries.len()
+    }
+
+    /// No entries in memory or on disk.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Entries moved to the journal so far.
+    pub fn spilled(&self) -> usize {
+        self.spilled
+    }
+
+    /// `(timestamp, text)` of the event entries, oldest first.
+    pub fn events(&self) -> &[(String, String)] {
+        &self.events
+    }
+
+    /// Drop every entry and delete the journal.
+    pub fn clear(&mut self) {
+        if let Some((path, _)) = self.journal.take() {
+            let _ = std::fs::remove_file(path);
+        }
+        self.entries.clear();
+        self.bytes = 0;
+        self.spilled = 0;
+        self.events.clear();
+        self.spill_error = None;
+        self.spill_retry_at = None;
+    }
+
+    /// Visit every entry, oldest first: the journal, then memory.
+    pub fn for_each(&mut self, mut visit: impl FnMut(&DataLogEntry)) -> std::io::Result<()> {
+        if let Some((path, writer)) = self.journal.as_mut() {
+            writer.flush()?;
+            let mut reader = BufReader::new(File::open(&*path)?);
+            while let Some(entry) = read_record(&mut reader)? {
+                visit(&entry);
+            }
+        }
+        self.entries.iter().for_each(visit);
+        Ok(())
+    }
+}
+
+impl Drop for LogBuffer {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Write every entry as `[timestamp] RX: text` lines, oldest first.
+pub fn save_log(log: &mut LogBuffer, path: &str) -> Result<String, String> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(path)
+        .map_err(|_| "Failed to save log".to_string())?;
+    let mut file = BufWriter::new(file);
+
+    log.for_each(|entry| {
+        let _ = file.write_all(entry.text_line().as_bytes());
+    })
+    .map_err(|e| format!("Failed to read log journal: {}", e))?;
+    file.flush().map_err(|_| "Failed to save log".to_string())?;
+    Ok(format!("Log saved to {}", path))
+}
+
+/// Write the terminal text to `<stem>.txt` in the working directory.
+pub fn save_buffer(display: &str, stem: &str) {
+    let filename = format!("{}.txt", stem);
+    if let Ok(mut file) = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&filename)
+    {
+        let _ = file.write_all(display.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(i: usize) -> DataLogEntry {
+        DataLogEntry {
+            timestamp: format!("t{}", i),
+            direction: if i.is_multiple_of(2) { Direction::Received } else { Direction::Sent },
+            data: format!("data {}", i).into_bytes(),
+        }
+    }
+
+    fn collect(log: &mut LogBuffer) -> Vec<String> {
+        let mut seen = Vec::new();
+        log.for_each(|e| seen.push(e.timestamp.clone())).unwrap();
+        seen
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let mut bytes = Vec::new();
+        write_record(&mut bytes, &entry(1)).unwrap();
+        write_record(&mut bytes, &entry(2)).unwrap();
+        let mut input = bytes.as_slice();
+        let first = read_record(&mut input).unwrap().unwrap();
+        assert_eq!(first.timestamp, "t1");
+        assert_eq!(first.direction, Direction::Sent);
+        assert_eq!(first.data, b"data 1");
+        assert_eq!(read_record(&mut input).unwrap().unwrap().timestamp, "t2");
+        assert!(read_record(&mut input).unwrap().is_none());
+    }
+
+    #[test]
+    fn order_is_kept_across_the_memory_disk_boundary() {
+        let mut log = LogBuffer::with_limits(4, DEFAULT_LOG_MAX_BYTES);
+        for i in 0..11 {
+            log.push(entry(i));
+        }
+        assert_eq!(log.len(), 11);
+        assert_eq!(log.spilled(), 10);
+        let expected: Vec<String> = (0..11).map(|i| format!("t{}", i)).collect();
+        assert_eq!(collect(&mut log), expected);
+
+        // Reading does not disturb later spills
+        for i in 11..20 {
+            log.push(entry(i));
+        }
+        let expected: Vec<String> = (0..20).map(|i| format!("t{}", i)).collect();
+        assert_eq!(collect(&mut log), expected);
+    }
+
+    #[test]
+    fn byte_limit_spills_and_clear_removes_the_journal() {
+        let mut log = LogBuffer::with_limits(DEFAULT_LOG_MAX_ENTRIES, 10);
+        log.push(entry(0));
+        assert_eq!(log.spilled(), 0);
+        log.push(entry(1));
+        assert_eq!(log.spilled(), 2);
+        let path = log.journal.as_ref().unwrap().0.clone();
+        assert!(path.exists());
+
+        log.clear();
+        assert!(log.is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn a_failed_spill_leaves_nothing_behind_in_the_journal() {
+        let mut log = LogBuffer::with_limits(2, DEFAULT_LOG_MAX_BYTES);
+        for i in 0..3 {
+            log.push(entry(i));
+        }
+        assert_eq!(log.spilled(), 3);
+        // A spill that got part way: records on disk, entries still in memory
+        log.push(entry(3));
+        let (_, writer) = log.journal.as_mut().unwrap();
+        write_record(writer, &entry(3)).unwrap();
+        writer.flush().unwrap();
+        log.rewind_journal();
+
+        for i in 4..6 {
+            log.push(entry(i));
+        }
+        assert_eq!(log.spilled(), 6);
+        let expected: Vec<String> = (0..6).map(|i| format!("t{}", i)).collect();
+        assert_eq!(collect(&mut log), expected);
+    }
+
+    #[test]
+    fn a_failed_spill_is_retried_only_after_more_entries() {
+        let mut log = LogBuffer::with_limits(2, DEFAULT_LOG_MAX_BYTES);
+        // A journal that cannot be written, like one on a full disk
+        let path = journal_path();
+        File::create(&path).unwrap();
+        log.journal = Some((path.clone(), BufWriter::new(File::open(&path).unwrap())));
+        for i in 0..3 {
+            log.push(entry(i));
+        }
+        let error = log.spill_error.clone().unwrap();
+        assert!(error.starts_with("Cannot write log journal: "), "{}", error);
+        assert_eq!(log.spilled(), 0);
+
+        // Writable again, but left alone until enough entries have come in
+        log.journal = Some((path.clone(), BufWriter::new(File::create(&path).unwrap())));
+        for i in 3..3 + SPILL_RETRY_ENTRIES - 1 {
+            log.push(entry(i));
+        }
+        assert_eq!(log.spilled(), 0);
+        assert_eq!(log.spill_error.as_deref(), Some(error.as_str()));
+        log.push(entry(SPILL_RETRY_ENTRIES + 2));
+        assert_eq!(log.spilled(), SPILL_RETRY_ENTRIES + 3);
+        assert!(log.spill_error.is_none());
+        log.clear();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn keep_last_drops_the_oldest_instead_of_spilling() {
+        let mut log = LogBuffer::with_limits(2, DEFAULT_LOG_MAX_BYTES);
+        log.keep_last = Some(3);
+        for i in 0..6 {
+            log.push(entry(i));
+        }
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.spilled(), 0);
+        assert!(log.journal.is_none());
+        assert_eq!(collect(&mut log), ["t3", "t4", "t5"]);
+    }
+
+    #[test]
+    fn saved_log_stitches_disk_and_memory() {
+        let mut log = LogBuffer::with_limits(2, DEFAULT_LOG_MAX_BYTES);
+        for i in 0..5 {
+            log.push(entry(i));
+        }
+        let path = std::env::temp_dir().join(format!("rustcom-log-test-{}.log", std::process::id()));
+        save_log(&mut log, path.to_str().unwrap()).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "[t0] RX: data 0");
+        assert_eq!(lines[4], "[t4] RX: data 4");
+    }
+}
@@ -0,0 +1,415 @@
+//! Database logging: sessions, frames, extracted values and events written to
+//! a SQLite file by a background thread, so long captures can be queried with
+//! SQL afterwards.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, Transaction};
+
+use super::{Direction, TIMESTAMP_FORMAT};
+
+/// Stored in `PRAGMA user_version`; bump it and add a migration when the
+/// schema changes.
+pub const SCHEMA_VERSION: i64 = 1;
+
+/// Statements taking the schema from version `index` to `index + 1`.
+const MIGRATIONS: [&str; 1] = ["
+    CREATE TABLE sessions (
+        id INTEGER PRIMARY KEY,
+        port TEXT NOT NULL,
+        baud_rate INTEGER NOT NULL,
+        started_at TEXT NOT NULL,
+        ended_at TEXT
+    );
+    CREATE TABLE frames (
+        id INTEGER PRIMARY KEY,
+        session_id INTEGER REFERENCES sessions(id),
+        time TEXT NOT NULL,
+        direction TEXT NOT NULL,
+        data BLOB NOT NULL,
+        summary TEXT,
+        error TEXT
+    );
+    CREATE TABLE extracted_values (
+        id INTEGER PRIMARY KEY,
+        session_id INTEGER REFERENCES sessions(id),
+        time TEXT NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        number REAL
+    );
+    CREATE TABLE events (
+        id INTEGER PRIMARY KEY,
+        session_id INTEGER REFERENCES sessions(id),
+        time TEXT NOT NULL,
+        text TEXT NOT NULL
+    );
+    CREATE INDEX frames_time ON frames(time);
+    CREATE INDEX extracted_values_key_time ON extracted_values(key, time);
+"];
+
+/// Rows written in one transaction at most.
+const BATCH_SIZE: usize = 500;
+/// How long the writer collects records before committing what it has.
+const BATCH_WAIT: Duration = Duration::from_millis(250);
+
+/// One thing to store. Records after a `SessionStart` belong to that session.
+#[derive(Debug, Clone)]
+pub enum DbRecord {
+    SessionStart {
+        at: DateTime<Local>,
+        port: String,
+        baud_rate: u32,
+    },
+    SessionEnd {
+        at: DateTime<Local>,
+    },
+    /// A raw chunk, or a decoded frame when `summary` is set.
+    Frame {
+        at: DateTime<Local>,
+        direction: Direction,
+        data: Vec<u8>,
+        summary: Option<String>,
+        error: Option<String>,
+    },
+    Value {
+        at: DateTime<Local>,
+        key: String,
+        value: String,
+        number: Option<f64>,
+    },
+    Event {
+        at: DateTime<Local>,
+        text: String,
+    },
+}
+
+impl DbRecord {
+    /// A raw chunk stamped now.
+    pub fn chunk(direction: Direction, data: &[u8]) -> Self {
+        DbRecord::Frame {
+            at: Local::now(),
+            direction,
+            data: data.to_vec(),
+            summary: None,
+            error: None,
+        }
+    }
+}
+
+fn time(at: &DateTime<Local>) -> String {
+    at.format(TIMESTAMP_FORMAT).to_string()
+}
+
+/// Open (or create) a capture database and bring its schema up to date.
+/// Fails for a file written by a newer version.
+pub fn open_database(path: &Path) -> Result<Connection, String> {
+    let mut conn = Connection::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+    // Lets queries run against the file while a capture is still writing it
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|e| format!("Cannot set up {}: {}", path.display(), e))?;
+
+    let version: i64 = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "{} uses schema version {}; this version of RustCOM knows up to {}",
+            path.display(),
+            version,
+            SCHEMA_VERSION
+        ));
+    }
+
+    // A damaged or foreign file can hold a negative version
+    let Ok(applied) = usize::try_from(version) else {
+        return Err(format!("{} has an invalid schema version {}", path.display(), version));
+    };
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for migration in &MIGRATIONS[applied..] {
+        tx.execute_batch(migration).map_err(|e| format!("Schema update failed: {}", e))?;
+    }
+    tx.pragma_update(None, "user_version", SCHEMA_VERSION)
+        .and_then(|_| tx.commit())
+        .map_err(|e| format!("Schema update failed: {}", e))?;
+    Ok(conn)
+}
+
+/// Store `records` in one transaction. Returns the number of rows inserted.
+fn write_batch(conn: &mut Connection, records: &[DbRecord], session: &mut Option<i64>) -> rusqlite::Result<u64> {
+    let tx = conn.transaction()?;
+    let mut rows = 0;
+    for record in records {
+        rows += insert(&tx, record, session)? as u64;
+    }
+    tx.commit()?;
+    Ok(rows)
+}
+
+fn insert(tx: &Transaction, record: &DbRecord, session: &mut Option<i64>) -> rusqlite::Result<usize> {
+    match record {
+        DbRecord::SessionStart { at, port, baud_rate } => {
+            let rows = tx.execute(
+                "INSERT INTO sessions (port, baud_rate, started_at) VALUES (?1, ?2, ?3)",
+                params![port, baud_rate, time(at)],
+            )?;
+            *session = Some(tx.last_insert_rowid());
+            Ok(rows)
+        }
+        DbRecord::SessionEnd { at } => {
+            let Some(id) = session.take() else {
+                return Ok(0);
+            };
+            tx.execute("UPDATE sessions SET ended_at = ?1 WHERE id = ?2", params![time(at), id])?;
+            Ok(0)
+        }
+        DbRecord::Frame {
+            at,
+            direction,
+            data,
+            summary,
+            error,
+        } => tx.execute(
+            "INSERT INTO frames (session_id, time, direction, data, summary, error) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![*session, time(at), direction.as_str(), data, summary, error],
+        ),
+        DbRecord::Value { at, key, value, number } => tx.execute(
+            "INSERT INTO extracted_values (session_id, time, key, value, number) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![*session, time(at), key, value, number],
+        ),
+        DbRecord::Event { at, text } => tx.execute(
+            "INSERT INTO events (session_id, time, text) VALUES (?1, ?2, ?3)",
+            params![*session, time(at), text],
+        ),
+    }
+}
+
+#[derive(Default)]
+struct DbStatus {
+    rows: AtomicU64,
+    error: Mutex<Option<String>>,
+}
+
+/// Writes records to a capture database on its own thread. `log` never
+/// blocks; records are committed in batches.
+pub struct DbLogger {
+    path: PathBuf,
+    sender: Option<Sender<DbRecord>>,
+    status: Arc<DbStatus>,
+    worker: Option<JoinHandle<()>>,
+}
+
+fn run_writer(mut conn: Connection, records: Receiver<DbRecord>, status: Arc<DbStatus>) {
+    let mut session = None;
+    while let Ok(first) = records.recv() {
+        let mut batch = vec![first];
+        let deadline = Instant::now() + BATCH_WAIT;
+        while batch.len() < BATCH_SIZE {
+            match records.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(record) => batch.push(record),
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        match write_batch(&mut conn, &batch, &mut session) {
+            Ok(rows) => {
+                status.rows.fetch_add(rows, Ordering::Relaxed);
+            }
+            Err(e) => *status.error.lock().unwrap() = Some(format!("Database write failed: {}", e)),
+        }
+    }
+}
+
+impl DbLogger {
+    /// Open the database at `path` (checking its schema here, so problems are
+    /// reported straight away) and start the writer thread.
+    pub fn start(path: &Path) -> Result<Self, String> {
+        let conn = open_database(path)?;
+        let (sender, records) = mpsc::channel();
+        let status = Arc::new(DbStatus::default());
+        let worker = {
+            let status = status.clone();
+            std::thread::spawn(move || run_writer(conn, records, status))
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            sender: Some(sender),
+            status,
+            worker: Some(worker),
+        })
+    }
+
+    pub fn log(&self, record: DbRecord) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(record);
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Rows committed so far.
+    pub fn rows_written(&self) -> u64 {
+        self.status.rows.load(Ordering::Relaxed)
+    }
+
+    /// The latest write failure, if any.
+    pub fn last_error(&self) -> Option<String> {
+        self.status.error.lock().unwrap().clone()
+    }
+
+    /// Size of the database file and its write-ahead log, in bytes.
+    pub fn file_size(&self) -> u64 {
+        let mut wal = self.path.clone().into_os_string();
+        wal.push("-wal");
+        [self.path.as_os_str(), wal.as_os_str()]
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+}
+
+impl Drop for DbLogger {
+    /// Commits whatever is still queued before returning.
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rustcom_{}_{}.sqlite", name, std::process::id()));
+        remove_db(&path);
+        path
+    }
+
+    fn remove_db(path: &Path) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.as_os_str().to_owned();
+            file.push(suffix);
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[test]
+    fn creates_the_schema() {
+        let path = temp_db("schema");
+        let conn = open_database(&path).unwrap();
+        let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+
+        let mut statement = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .unwrap();
+        let tables: Vec<String> = statement.query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect();
+        assert_eq!(tables, ["events", "extracted_values", "frames", "sessions"]);
+        drop(statement);
+        drop(conn);
+
+        // Reopening leaves an up-to-date file alone; a newer one is refused
+        open_database(&path).unwrap().pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+        assert!(open_database(&path).unwrap_err().contains("schema version 2"));
+        let conn = Connection::open(&path).unwrap();
+        conn.pragma_update(None, "user_version", -1).unwrap();
+        drop(conn);
+        assert!(open_database(&path).unwrap_err().ends_with("has an invalid schema version -1"));
+        remove_db(&path);
+    }
+
+    #[test]
+    fn writes_records_from_the_background_thread() {
+        let path = temp_db("rows");
+        let at = Local::now();
+        let logger = DbLogger::start(&path).unwrap();
+        logger.log(DbRecord::Frame {
+            at,
+            direction: Direction::Received,
+            data: b"boot".to_vec(),
+            summary: None,
+            error: None,
+        });
+        logger.log(DbRecord::SessionStart {
+            at,
+            port: "COM3".to_string(),
+            baud_rate: 115_200,
+        });
+        logger.log(DbRecord::Frame {
+            at,
+            direction: Direction::Received,
+            data: vec![0x01, 0x03, 0x00],
+            summary: Some("Read Holding Registers".to_string()),
+            error: Some("CRC mismatch".to_string()),
+        });
+        logger.log(DbRecord::Value {
+            at,
+            key: "vbat".to_string(),
+            value: "3.72V".to_string(),
+            number: Some(3.72),
+        });
+        logger.log(DbRecord::Event {
+            at,
+            text: "RELAY ON".to_string(),
+        });
+        logger.log(DbRecord::SessionEnd { at });
+        drop(logger);
+
+        let conn = Connection::open(&path).unwrap();
+        let (port, baud, ended): (String, u32, Option<String>) = conn
+            .query_row("SELECT port, baud_rate, ended_at FROM sessions", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap();
+        assert_eq!((port.as_str(), baud), ("COM3", 115_200));
+        assert_eq!(ended, Some(time(&at)));
+
+        type FrameRow = (Option<i64>, String, Vec<u8>, Option<String>);
+        let frames: Vec<FrameRow> = conn
+            .prepare("SELECT session_id, direction, data, error FROM frames ORDER BY id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(frames[0], (None, "RX".to_string(), b"boot".to_vec(), None));
+        assert_eq!(frames[1], (Some(1), "RX".to_string(), vec![1, 3, 0], Some("CRC mismatch".to_string())));
+
+        let number: f64 = conn
+            .query_row("SELECT number FROM extracted_values WHERE key = 'vbat'", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(number, 3.72);
+        let event: (i64, String) = conn.query_row("SELECT session_id, text FROM events", [], |r| Ok((r.get(0)?, r.get(1)?))).unwrap();
+        assert_eq!(event, (1, "RELAY ON".to_string()));
+        drop(conn);
+        remove_db(&path);
+    }
+
+    #[test]
+    fn reports_rows_and_file_size() {
+        let path = temp_db("status");
+        let logger = DbLogger::start(&path).unwrap();
+        for i in 0..1200u32 {
+            logger.log(DbRecord::chunk(Direction::Sent, &i.to_le_bytes()));
+        }
+        let start = Instant::now();
+        while logger.rows_written() < 1200 && start.elapsed() < Duration::from_secs(10) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(logger.rows_written(), 1200);
+        assert!(logger.file_size() > 0);
+        assert!(logger.last_error().is_none());
+        drop(logger);
+        remove_db(&path);
+    }
+}
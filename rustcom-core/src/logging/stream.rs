@@ -0,0 +1,195 @@
+//! Streaming log file: entries appended as text log lines while they are
+//! captured and flushed at least every `FLUSH_INTERVAL`, so a crash or power
+//! loss during a long capture costs at most the last moment of it. Writing and
+//! flushing happen on a writer thread so a slow disk never holds up the UI.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::DataLogEntry;
+
+/// Longest that written entries wait in memory before reaching the file.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A log file open for appending.
+pub struct LogStream {
+    path: PathBuf,
+    sender: Option<Sender<String>>,
+    error: Arc<Mutex<Option<String>>>,
+    worker: Option<JoinHandle<std::io::Result<()>>>,
+    /// File size including what the writer has not written yet.
+    size: u64,
+    entries: u64,
+}
+
+fn run_writer(file: File, lines: Receiver<String>) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(file);
+    let mut last_flush = Instant::now();
+    let mut unflushed = false;
+    loop {
+        let line = if unflushed {
+            lines.recv_timeout(FLUSH_INTERVAL.saturating_sub(last_flush.elapsed()))
+        } else {
+            lines.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        };
+        match line {
+            Ok(line) => {
+                writer.write_all(line.as_bytes())?;
+                unflushed = true;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if unflushed && last_flush.elapsed() >= FLUSH_INTERVAL {
+            writer.flush()?;
+            last_flush = Instant::now();
+            unflushed = false;
+        }
+    }
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
+
+impl LogStream {
+    /// Open `path` for appending, creating it if needed, so a bad path or
+    /// missing permission is reported before anything is captured, and start
+    /// the writer thread.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if path.as_os_str().is_empty() {
+            return Err("Enter a log file path".to_string());
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Cannot open log file {}: {}", path.display(), e))?;
+        let size = file.metadata().map_or(0, |metadata| metadata.len());
+        let (sender, lines) = mpsc::channel();
+        let error = Arc::new(Mutex::new(None));
+        let worker = {
+            let error = error.clone();
+            std::thread::spawn(move || {
+                let result = run_writer(file, lines);
+                if let Err(e) = &result {
+                    *error.lock().unwrap() = Some(e.to_string());
+                }
+                result
+            })
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            sender: Some(sender),
+            error,
+            worker: Some(worker),
+            size,
+            entries: 0,
+        })
+    }
+
+    /// Queue `entry` as a text log line. Never blocks; a write that fails on
+    /// the writer thread shows up in `last_error`.
+    pub fn write(&mut self, entry: &DataLogEntry) {
+        let line = entry.text_line();
+        self.size += line.len() as u64;
+        self.entries += 1;
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(line);
+        }
+    }
+
+    /// Why the writer thread stopped, if a write or flush failed.
+    pub fn last_error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Size of the file in bytes, counting what is not written yet.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Entries written since the file was opened.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Write everything queued and sync it to disk before closing the file.
+    pub fn close(mut self) -> Result<(), String> {
+        self.sender = None;
+        match self.worker.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result.map_err(|e| e.to_string()),
+            Some(Err(_)) => Err("the log writer stopped".to_string()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for LogStream {
+    /// Writes whatever is still queued before returning.
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::Direction;
+
+    fn entry(direction: Direction, data: &[u8]) -> DataLogEntry {
+        DataLogEntry {
+            timestamp: "2024-03-01 10:00:00.000".to_string(),
+            direction,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn appends_and_flushes_on_the_interval() {
+        let path = std::env::temp_dir().join(format!("rustcom-stream-{}.log", std::process::id()));
+        std::fs::write(&path, "[2024-03-01 09:00:00.000] EV: earlier\n").unwrap();
+        let mut stream = LogStream::open(&path).unwrap();
+        assert_eq!(stream.size(), 38);
+
+        // The writer flushes on its own, without a close
+        stream.write(&entry(Direction::Received, b"boot ok"));
+        stream.write(&entry(Direction::Sent, b"AT"));
+        let deadline = Instant::now() + FLUSH_INTERVAL * 5;
+        let mut text = String::new();
+        while Instant::now() < deadline && !text.ends_with("TX: AT\n") {
+            std::thread::sleep(Duration::from_millis(20));
+            text = std::fs::read_to_string(&path).unwrap();
+        }
+        assert!(text.ends_with("RX: boot ok\n[2024-03-01 10:00:00.000] TX: AT\n"), "{}", text);
+        assert_eq!(stream.size(), text.len() as u64);
+        assert_eq!(stream.entries(), 2);
+
+        // Closing writes what is left
+        stream.write(&entry(Direction::Event, b"done"));
+        assert_eq!(stream.last_error(), None);
+        stream.close().unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(text.ends_with("EV: done\n"));
+        assert!(text.starts_with("[2024-03-01 09:00:00.000] EV: earlier\n"));
+    }
+
+    #[test]
+    fn reports_paths_that_cannot_be_opened() {
+        assert_eq!(LogStream::open(Path::new("")).err().unwrap(), "Enter a log file path");
+        let missing = std::env::temp_dir().join("rustcom-no-such-dir").join("capture.log");
+        let error = LogStream::open(&missing).err().unwrap();
+        assert!(error.starts_with(&format!("Cannot open log file {}", missing.display())), "{}", error);
+    }
+}
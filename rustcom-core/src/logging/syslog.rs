@@ -0,0 +1,506 @@
+//! Syslog forwarding: RX lines, and optionally TX data and event markers, sent
+//! as RFC 5424 messages over UDP or TCP to a collector. Messages go through a
+//! bounded queue to a sender thread, so an unreachable collector never holds
+//! up the capture; what cannot be sent is counted instead.
+
+use std::io::Write as IoWrite;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::Direction;
+
+/// Same compiled-size cap as filter rules.
+const MAX_REGEX_SIZE: usize = 1 << 20;
+/// Messages waiting for the sender thread; more than this are dropped.
+const QUEUE_LIMIT: usize = 4096;
+/// Connect and write timeout for TCP.
+const TCP_TIMEOUT: Duration = Duration::from_secs(2);
+/// After a failed lookup or connect, messages are counted as failed without
+/// trying again for this long.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Facility names by code (RFC 5424 section 6.2.1).
+pub const FACILITY_NAMES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron", "authpriv", "ftp",
+    "ntp", "audit", "alert", "clock", "local0", "local1", "local2", "local3", "local4", "local5",
+    "local6", "local7",
+];
+
+/// Severity names by code, 0 (most severe) to 7.
+pub const SEVERITY_NAMES: [&str; 8] = [
+    "Emergency",
+    "Alert",
+    "Critical",
+    "Error",
+    "Warning",
+    "Notice",
+    "Informational",
+    "Debug",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+impl Transport {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Transport::Udp => "UDP",
+            Transport::Tcp => "TCP",
+        }
+    }
+}
+
+/// Lines matching `pattern` are sent with `severity`. Only the definition is
+/// saved; call `compile` after loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityRule {
+    pub pattern: String,
+    pub severity: u8,
+    #[serde(skip)]
+    pub regex: Option<Regex>,
+    #[serde(skip)]
+    pub error: Option<String>,
+}
+
+impl SeverityRule {
+    pub fn new(pattern: &str, severity: u8) -> Self {
+        let mut rule = Self {
+            pattern: pattern.to_string(),
+            severity,
+            regex: None,
+            error: None,
+        };
+        rule.compile();
+        rule
+    }
+
+    /// Recompile after the pattern was edited.
+    pub fn compile(&mut self) {
+        self.regex = None;
+        self.error = None;
+        if self.pattern.is_empty() {
+            return;
+        }
+        match RegexBuilder::new(&self.pattern).size_limit(MAX_REGEX_SIZE).build() {
+            Ok(regex) => self.regex = Some(regex),
+            Err(e) => self.error = Some(format!("Invalid regex: {}", e)),
+        }
+    }
+}
+
+/// Where and how to forward, saved with each profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyslogConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub transport: Transport,
+    pub facility: u8,
+    /// HOSTNAME field; empty uses this machine's name.
+    pub hostname: String,
+    /// APP-NAME field.
+    pub app_name: String,
+    pub forward_tx: bool,
+    pub forward_events: bool,
+    /// Severity of messages no rule matches.
+    pub default_severity: u8,
+    /// Checked in order; the first match sets the severity.
+    pub rules: Vec<SeverityRule>,
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: 514,
+            transport: Transport::Udp,
+            facility: 16,
+            hostname: String::new(),
+            app_name: "rustcom".to_string(),
+            forward_tx: false,
+            forward_events: true,
+            default_severity: 6,
+            rules: vec![SeverityRule::new("ERROR", 3), SeverityRule::new("WARN", 4)],
+        }
+    }
+}
+
+impl SyslogConfig {
+    pub fn compile(&mut self) {
+        for rule in &mut self.rules {
+            rule.compile();
+        }
+    }
+
+    /// Severity for `text`: the first matching rule's, or the default.
+    pub fn severity_for(&self, text: &str) -> u8 {
+        self.rules
+            .iter()
+            .find(|rule| rule.regex.as_ref().is_some_and(|re| re.is_match(text)))
+            .map_or(self.default_severity, |rule| rule.severity)
+    }
+
+    /// Whether data going `direction` is forwarded at all.
+    pub fn forwards(&self, direction: Direction) -> bool {
+        match direction {
+            Direction::Received => true,
+            Direction::Sent => self.forward_tx,
+            Direction::Event => self.forward_events,
+        }
+    }
+
+    /// The full message for `text`, with its severity looked up. `local_hostname`
+    /// stands in for an empty `hostname`.
+    pub fn message(&self, direction: Direction, text: &str, at: DateTime<Local>, local_hostname: &str) -> String {
+        let hostname = if self.hostname.trim().is_empty() {
+            local_hostname
+        } else {
+            &self.hostname
+        };
+        let header = Header {
+            facility: self.facility,
+            severity: self.severity_for(text),
+            at,
+            hostname,
+            app_name: &self.app_name,
+            msg_id: direction.as_str(),
+        };
+        format_message(&header, text)
+    }
+}
+
+/// This machine's name from the environment, or empty when it is not set.
+pub fn local_hostname() -> String {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// The header fields of one message.
+pub struct Header<'a> {
+    pub facility: u8,
+    pub severity: u8,
+    pub at: DateTime<Local>,
+    pub hostname: &'a str,
+    pub app_name: &'a str,
+    pub msg_id: &'a str,
+}
+
+/// A header field: printable ASCII without spaces, at most `max` characters,
+/// or `-` when nothing is left.
+fn header_field(value: &str, max: usize) -> String {
+    let field: String = value.chars().filter(|c| c.is_ascii_graphic()).take(max).collect();
+    if field.is_empty() {
+        "-".to_string()
+    } else {
+        field
+    }
+}
+
+/// `<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID - MSG`, with no structured
+/// data. Control characters in `text` are replaced so a message is one line.
+pub fn format_message(header: &Header, text: &str) -> String {
+    let priority = u16::from(header.facility.min(23)) * 8 + u16::from(header.severity.min(7));
+    let text: String = text
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    format!(
+        "<{}>1 {} {} {} {} {} - {}",
+        priority,
+        header.at.format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+        header_field(header.hostname, 255),
+        header_field(header.app_name, 48),
+        std::process::id(),
+        header_field(header.msg_id, 32),
+        text.trim_end()
+    )
+}
+
+#[derive(Default)]
+struct ForwardStatus {
+    sent: AtomicU64,
+    failed: AtomicU64,
+    error: Mutex<Option<String>>,
+}
+
+impl ForwardStatus {
+    fn fail(&self, error: String) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        *self.error.lock().unwrap() = Some(error);
+    }
+}
+
+/// An open connection to the collector.
+enum Link {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+impl Link {
+    fn open(transport: Transport, host: &str, port: u16) -> Result<Self, String> {
+        let addr: SocketAddr = (host, port)
+            .to_socket_addrs()
+            .map_err(|e| format!("Cannot resolve {}: {}", host, e))?
+            .next()
+            .ok_or_else(|| format!("No address for {}", host))?;
+        match transport {
+            Transport::Udp => {
+                let local: SocketAddr = if addr.is_ipv4() {
+                    "0.0.0.0:0".parse().unwrap()
+                } else {
+                    "[::]:0".parse().unwrap()
+                };
+                let socket = UdpSocket::bind(local).map_err(|e| e.to_string())?;
+                // Connected, so an ICMP "port unreachable" shows up as a send error
+                socket.connect(addr).map_err(|e| e.to_string())?;
+                Ok(Link::Udp(socket))
+            }
+            Transport::Tcp => {
+                let stream = TcpStream::connect_timeout(&addr, TCP_TIMEOUT)
+                    .map_err(|e| format!("Cannot connect to {}: {}", addr, e))?;
+                stream.set_write_timeout(Some(TCP_TIMEOUT)).map_err(|e| e.to_string())?;
+                Ok(Link::Tcp(stream))
+            }
+        }
+    }
+
+    /// UDP sends one message per datagram; TCP uses octet counting (RFC 6587).
+    fn send(&mut self, message: &str) -> std::io::Result<()> {
+        match self {
+            Link::Udp(socket) => socket.send(message.as_bytes()).map(|_| ()),
+            Link::Tcp(stream) => stream.write_all(format!("{} {}", message.len(), message).as_bytes()),
+        }
+    }
+}
+
+fn run_sender(transport: Transport, host: String, port: u16, messages: Receiver<String>, status: Arc<ForwardStatus>) {
+    let mut link: Option<Link> = None;
+    let mut retry_at = Instant::now();
+    for message in messages {
+        if link.is_none() {
+            if Instant::now() < retry_at {
+                status.failed.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            match Link::open(transport, &host, port) {
+                Ok(opened) => link = Some(opened),
+                Err(e) => {
+                    retry_at = Instant::now() + RETRY_DELAY;
+                    status.fail(e);
+                    continue;
+                }
+            }
+        }
+        if let Some(open) = link.as_mut() {
+            match open.send(&message) {
+                Ok(()) => {
+                    status.sent.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    link = None;
+                    status.fail(format!("Send to {}:{} failed: {}", host, port, e));
+                }
+            }
+        }
+    }
+}
+
+/// Sends messages to one collector on its own thread. `send` never blocks;
+/// dropping the forwarder lets the thread finish what is queued and exit.
+pub struct SyslogForwarder {
+    target: String,
+    local_hostname: String,
+    sender: SyncSender<String>,
+    status: Arc<ForwardStatus>,
+}
+
+impl SyslogForwarder {
+    /// Start the sender thread. The host is looked up there, so a slow or
+    /// failing lookup is reported through `last_error`, not here.
+    pub fn start(config: &SyslogConfig) -> Result<Self, String> {
+        let host = config.host.trim().to_string();
+        if host.is_empty() {
+            return Err("Enter a syslog host".to_string());
+        }
+        let (sender, messages) = mpsc::sync_channel(QUEUE_LIMIT);
+        let status = Arc::new(ForwardStatus::default());
+        let target = format!("{}:{} ({})", host, config.port, config.transport.as_str());
+        {
+            let status = status.clone();
+            let (transport, port) = (config.transport, config.port);
+            std::thread::spawn(move || run_sender(transport, host, port, messages, status));
+        }
+        Ok(Self {
+            target,
+            local_hostname: local_hostname(),
+            sender,
+            status,
+        })
+    }
+
+    /// Queue a formatted message. Counted as failed if the queue is full.
+    pub fn send(&self, message: String) {
+        match self.sender.try_send(message) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => self.status.fail("Queue full, collector too slow".to_string()),
+            Err(TrySendError::Disconnected(_)) => self.status.fail("Sender thread stopped".to_string()),
+        }
+    }
+
+    /// `host:port (UDP)`
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// This machine's name, looked up once when forwarding started.
+    pub fn local_hostname(&self) -> &str {
+        &self.local_hostname
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.status.sent.load(Ordering::Relaxed)
+    }
+
+    /// Messages not delivered: send errors, dropped while the collector was
+    /// unreachable, or dropped from a full queue.
+    pub fn failed(&self) -> u64 {
+        self.status.failed.load(Ordering::Relaxed)
+    }
+
+    /// The latest failure, if any.
+    pub fn last_error(&self) -> Option<String> {
+        self.status.error.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    fn wait_for(done: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !done() && start.elapsed() < Duration::from_secs(10) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn formats_rfc5424_messages() {
+        let at = DateTime::parse_from_rfc3339("2024-03-05T14:07:09.250+01:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let header = Header {
+            facility: 16,
+            severity: 3,
+            at,
+            hostname: "bench 4",
+            app_name: "",
+            msg_id: "RX",
+        };
+        let message = format_message(&header, "ERROR\tflash\r");
+        let local = at.format("%Y-%m-%dT%H:%M:%S%.3f%:z");
+        assert_eq!(
+            message,
+            format!("<131>1 {} bench4 - {} RX - ERROR flash", local, std::process::id())
+        );
+    }
+
+    #[test]
+    fn severity_follows_the_first_matching_rule() {
+        let mut config = SyslogConfig::default();
+        config.rules.insert(0, SeverityRule::new("^FATAL", 2));
+        config.rules.push(SeverityRule::new("(bad", 0));
+        assert!(config.rules[3].error.is_some());
+
+        assert_eq!(config.severity_for("FATAL ERROR in task"), 2);
+        assert_eq!(config.severity_for("I2C ERROR 5"), 3);
+        assert_eq!(config.severity_for("WARN low battery"), 4);
+        assert_eq!(config.severity_for("vbat=3.72"), 6);
+        assert!(config.message(Direction::Received, "ok", Local::now(), "bench").contains(" bench rustcom "));
+        config.hostname = "rig2".to_string();
+        assert!(config.message(Direction::Received, "ok", Local::now(), "bench").contains(" rig2 rustcom "));
+        assert!(config.forwards(Direction::Received));
+        assert!(!config.forwards(Direction::Sent));
+    }
+
+    #[test]
+    fn forwards_over_udp() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        collector.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let config = SyslogConfig {
+            host: "127.0.0.1".to_string(),
+            port: collector.local_addr().unwrap().port(),
+            hostname: "bench".to_string(),
+            ..SyslogConfig::default()
+        };
+        let forwarder = SyslogForwarder::start(&config).unwrap();
+        forwarder.send(config.message(Direction::Received, "I2C ERROR 5", Local::now(), forwarder.local_hostname()));
+
+        let mut buf = [0; 512];
+        let len = collector.recv(&mut buf).unwrap();
+        let received = String::from_utf8_lossy(&buf[..len]);
+        assert!(received.starts_with("<131>1 "));
+        let tail = format!(" bench rustcom {} RX - I2C ERROR 5", std::process::id());
+        assert!(received.ends_with(&tail));
+        wait_for(|| forwarder.sent() == 1);
+        assert_eq!((forwarder.sent(), forwarder.failed()), (1, 0));
+    }
+
+    #[test]
+    fn frames_tcp_messages_by_length() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = SyslogConfig {
+            host: "localhost".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            transport: Transport::Tcp,
+            ..SyslogConfig::default()
+        };
+        let forwarder = SyslogForwarder::start(&config).unwrap();
+        forwarder.send("<134>1 a".to_string());
+        forwarder.send("<134>1 bc".to_string());
+
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut received = vec![0; 21];
+        stream.read_exact(&mut received).unwrap();
+        assert_eq!(received, b"8 <134>1 a9 <134>1 bc");
+        wait_for(|| forwarder.sent() == 2);
+    }
+
+    #[test]
+    fn counts_failures_when_the_collector_is_down() {
+        // A port that was free a moment ago, so the connect is refused
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let config = SyslogConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            transport: Transport::Tcp,
+            ..SyslogConfig::default()
+        };
+        let forwarder = SyslogForwarder::start(&config).unwrap();
+        for _ in 0..3 {
+            forwarder.send("<134>1 lost".to_string());
+        }
+        wait_for(|| forwarder.failed() == 3);
+        assert_eq!((forwarder.sent(), forwarder.failed()), (0, 3));
+        assert!(forwarder.last_error().unwrap().starts_with("Cannot connect"));
+        assert!(SyslogForwarder::start(&SyslogConfig::default()).is_err());
+    }
+}
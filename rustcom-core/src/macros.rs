@@ -0,0 +1,161 @@
+//! Macro buttons: named canned payloads sent with one click or a function key,
+//! for the commands a device session repeats all day. A template macro asks
+//! for its `{placeholders}` before sending.
+
+use serde::{Deserialize, Serialize};
+
+use crate::hex::{parse_escapes, parse_hex_input};
+use crate::payloads::{LineEnding, SendMode};
+use crate::template::Template;
+
+pub const MAX_MACROS: usize = 32;
+/// Macros can be bound to F1 to F12.
+pub const MACRO_KEY_COUNT: u8 = 12;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Macro {
+    pub name: String,
+    /// Text with `\r`, `\n`, `\t`, `\0`, `\e`, `\\` and `\xHH` escapes, or
+    /// `AA BB` hex.
+    pub text: String,
+    pub mode: SendMode,
+    /// Added after text; `None` uses the send box's line ending.
+    pub line_ending: Option<LineEnding>,
+    /// The function key that sends it, 1 for F1.
+    pub key: Option<u8>,
+    /// Text has `{placeholders}` to fill in when sent.
+    pub template: bool,
+}
+
+impl Default for Macro {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            text: String::new(),
+            mode: SendMode::Ascii,
+            line_ending: None,
+            key: None,
+            template: false,
+        }
+    }
+}
+
+impl Macro {
+    /// The bytes to send, with `line_ending` after text unless the macro has
+    /// its own.
+    pub fn payload(&self, line_ending: LineEnding) -> Result<Vec<u8>, String> {
+        let payload = match self.mode {
+            SendMode::Ascii => {
+                let mut bytes = parse_escapes(&self.text)?;
+                bytes.extend_from_slice(self.line_ending.unwrap_or(line_ending).as_bytes());
+                bytes
+            }
+            SendMode::Hex => parse_hex_input(&self.text)?,
+        };
+        if payload.is_empty() {
+            return Err("Macro payload is empty".to_string());
+        }
+        Ok(payload)
+    }
+
+    /// The button caption: the name, or the text for an unnamed macro.
+    pub fn label(&self) -> &str {
+        match self.name.trim() {
+            "" => &self.text,
+            name => name,
+        }
+    }
+
+    /// The placeholders of a template macro; `None` for a plain one.
+    pub fn parse_template(&self) -> Result<Option<Template>, String> {
+        match self.template {
+            true => Template::parse(&self.text).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// A plain macro sending this template with `values` filled in; a plain
+    /// macro as it is.
+    pub fn filled(&self, values: &[String]) -> Result<Macro, String> {
+        let mut filled = self.clone();
+        if let Some(template) = self.parse_template()? {
+            filled.text = template.resolve(values, self.mode)?;
+            filled.template = false;
+        }
+        Ok(filled)
+    }
+}
+
+/// The macro function key `key` sends.
+pub fn for_key(macros: &[Macro], key: u8) -> Option<usize> {
+    macros.iter().position(|m| m.key == Some(key))
+}
+
+/// Bind `key` to macro `index`, taking it from any other macro.
+pub fn assign_key(macros: &mut [Macro], index: usize, key: Option<u8>) {
+    if key.is_some() {
+        for m in macros.iter_mut().filter(|m| m.key == key) {
+            m.key = None;
+        }
+    }
+    if let Some(m) = macros.get_mut(index) {
+        m.key = key;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_is_escaped_text_with_a_line_ending_or_hex() {
+        let mut m = Macro {
+            name: "Reset".to_string(),
+            text: r"AT+RST\x00".to_string(),
+            ..Macro::default()
+        };
+        assert_eq!(m.payload(LineEnding::Cr).unwrap(), b"AT+RST\x00\r");
+        m.line_ending = Some(LineEnding::None);
+        assert_eq!(m.payload(LineEnding::Cr).unwrap(), b"AT+RST\x00");
+        assert_eq!(m.label(), "Reset");
+
+        m.mode = SendMode::Hex;
+        m.text = "01 03".to_string();
+        assert_eq!(m.payload(LineEnding::CrLf).unwrap(), [0x01, 0x03]);
+        m.text = r"\q".to_string();
+        assert!(m.payload(LineEnding::CrLf).is_err());
+
+        let empty = Macro::default();
+        assert_eq!(empty.payload(LineEnding::None).unwrap_err(), "Macro payload is empty");
+        assert_eq!(empty.payload(LineEnding::Lf).unwrap(), b"\n", "a bare line ending is a payload");
+    }
+
+    #[test]
+    fn templates_fill_in_before_the_payload() {
+        let m = Macro {
+            text: r"SET CHAN {n:int(1..16)}\r".to_string(),
+            line_ending: Some(LineEnding::None),
+            template: true,
+            ..Macro::default()
+        };
+        let filled = m.filled(&["5".to_string()]).unwrap();
+        assert_eq!(filled.payload(LineEnding::Lf).unwrap(), b"SET CHAN 5\r");
+        assert!(!filled.template);
+        assert_eq!(m.filled(&[]).unwrap_err(), "No value for {n}");
+
+        let plain = Macro { template: false, ..m };
+        assert_eq!(plain.filled(&[]).unwrap().text, plain.text, "braces in a plain macro are text");
+    }
+
+    #[test]
+    fn each_key_sends_one_macro() {
+        let mut macros = vec![Macro::default(), Macro::default(), Macro::default()];
+        assign_key(&mut macros, 0, Some(1));
+        assign_key(&mut macros, 2, Some(1));
+        assert_eq!(for_key(&macros, 1), Some(2));
+        assert_eq!(macros[0].key, None);
+        assign_key(&mut macros, 2, None);
+        assert_eq!(for_key(&macros, 1), None);
+    }
+}
@@ -0,0 +1,306 @@
+//! Prometheus metrics: counters and extracted-value gauges updated with
+//! atomics from the capture path, and a small HTTP endpoint that renders them
+//! in the Prometheus text format. A scrape only reads the atomics, so it never
+//! waits on the serial port or the UI.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use serde::{Deserialize, Serialize};
+
+/// Where the endpoint listens unless configured otherwise.
+pub const DEFAULT_METRICS_ADDRESS: &str = "127.0.0.1:9898";
+
+/// Whether a decoder error is a failed CRC, checksum, BCC or LRC, as opposed to a
+/// truncated or unrecognised frame.
+pub fn is_check_failure(error: &str) -> bool {
+    error.contains("CRC")
+        || error.contains("checksum mismatch")
+        || error.contains("BCC mismatch")
+        || error.contains("LRC mismatch")
+}
+
+/// Which extraction a gauge comes from; each is its own metric family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ValueSource {
+    /// A pinned value, labelled with its name.
+    Pinned,
+    /// A key=value variable, labelled with its key.
+    Variable,
+}
+
+impl ValueSource {
+    /// Metric family name.
+    pub fn as_str(&self) -> &str {
+        match self {
+            ValueSource::Pinned => "rustcom_pinned_value",
+            ValueSource::Variable => "rustcom_variable",
+        }
+    }
+
+    fn label(&self) -> &str {
+        match self {
+            ValueSource::Pinned => "name",
+            ValueSource::Variable => "key",
+        }
+    }
+
+    fn help(&self) -> &str {
+        match self {
+            ValueSource::Pinned => "Latest numeric value of a pinned value",
+            ValueSource::Variable => "Latest numeric value of an extracted key=value variable",
+        }
+    }
+}
+
+/// A handle to one gauge; setting it is a single atomic store.
+#[derive(Debug, Clone, Default)]
+pub struct Gauge(Arc<AtomicU64>);
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// Everything exported. Shared between the app and the endpoint thread.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub bytes_received: AtomicU64,
+    pub bytes_sent: AtomicU64,
+    pub reconnects: AtomicU64,
+    pub frames_decoded: AtomicU64,
+    /// Frames the decoder marked malformed for any reason.
+    pub frame_errors: AtomicU64,
+    /// Frames that failed a CRC, checksum, BCC or LRC.
+    pub crc_errors: AtomicU64,
+    /// Times a framer found its way back after losing sync, and the bytes it skipped.
+    pub resyncs: AtomicU64,
+    pub resync_bytes: AtomicU64,
+    pub connected: AtomicBool,
+    /// Registered gauges by family and label value. Locked only to register a
+    /// gauge or render a scrape; updates go through `Gauge` handles.
+    gauges: Mutex<BTreeMap<(ValueSource, String), Gauge>>,
+}
+
+/// `\`, `"` and newlines escaped for a label value.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// A float in Prometheus text form.
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+impl Metrics {
+    pub fn add(counter: &AtomicU64, amount: usize) {
+        counter.fetch_add(amount as u64, Ordering::Relaxed);
+    }
+
+    /// The gauge for `name`, registering it on first use. Keep the handle to
+    /// update it without locking.
+    pub fn gauge(&self, source: ValueSource, name: &str) -> Gauge {
+        self.gauges
+            .lock()
+            .unwrap()
+            .entry((source, name.to_string()))
+            .or_default()
+            .clone()
+    }
+
+    /// Drop every gauge, e.g. after the extraction config changed.
+    pub fn clear_gauges(&self) {
+        self.gauges.lock().unwrap().clear();
+    }
+
+    /// The Prometheus text exposition of every metric.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let counters = [
+            ("rustcom_bytes_received_total", "Bytes received from the serial port", &self.bytes_received),
+            ("rustcom_bytes_sent_total", "Bytes written to the serial port", &self.bytes_sent),
+            ("rustcom_reconnects_total", "Successful automatic reconnects", &self.reconnects),
+            ("rustcom_frames_decoded_total", "Frames recovered by the protocol decoder", &self.frames_decoded),
+            ("rustcom_frame_errors_total", "Decoded frames marked malformed", &self.frame_errors),
+            ("rustcom_crc_errors_total", "Decoded frames that failed a CRC, checksum, BCC or LRC", &self.crc_errors),
+            ("rustcom_resyncs_total", "Times the framer resynchronised after losing sync", &self.resyncs),
+            ("rustcom_resync_skipped_bytes_total", "Bytes skipped while resynchronising", &self.resync_bytes),
+        ];
+        for (name, help, counter) in counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+        }
+        let _ = writeln!(out, "# HELP rustcom_connected Whether the serial port is open");
+        let _ = writeln!(out, "# TYPE rustcom_connected gauge");
+        let _ = writeln!(out, "rustcom_connected {}", u8::from(self.connected.load(Ordering::Relaxed)));
+
+        let gauges = self.gauges.lock().unwrap();
+        for source in [ValueSource::Pinned, ValueSource::Variable] {
+            let mut family = gauges.iter().filter(|((s, _), _)| *s == source).peekable();
+            if family.peek().is_none() {
+                continue;
+            }
+            let _ = writeln!(out, "# HELP {} {}", source.as_str(), source.help());
+            let _ = writeln!(out, "# TYPE {} gauge", source.as_str());
+            for ((_, name), gauge) in family {
+                let _ = writeln!(
+                    out,
+                    "{}{{{}=\"{}\"}} {}",
+                    source.as_str(),
+                    source.label(),
+                    escape_label(name),
+                    format_value(gauge.get())
+                );
+            }
+        }
+        out
+    }
+}
+
+/// Endpoint settings, saved with the global settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    /// `host:port` to listen on.
+    pub address: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: DEFAULT_METRICS_ADDRESS.to_string(),
+        }
+    }
+}
+
+/// Serves `GET /metrics` on its own thread until dropped.
+pub struct MetricsServer {
+    server: Arc<tiny_http::Server>,
+    address: String,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    pub fn start(address: &str, metrics: Arc<Metrics>) -> Result<Self, String> {
+        let server = tiny_http::Server::http(address.trim())
+            .map(Arc::new)
+            .map_err(|e| format!("Cannot listen on {}: {}", address.trim(), e))?;
+        let address = server
+            .server_addr()
+            .to_ip()
+            .map_or_else(|| address.trim().to_string(), |addr| addr.to_string());
+        let worker = {
+            let server = server.clone();
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let response = if request.url() == "/metrics" {
+                        let content_type = tiny_http::Header::from_bytes(
+                            "Content-Type",
+                            "text/plain; version=0.0.4; charset=utf-8",
+                        )
+                        .unwrap();
+                        tiny_http::Response::from_string(metrics.render()).with_header(content_type)
+                    } else {
+                        tiny_http::Response::from_string("Not found; metrics are at /metrics\n").with_status_code(404)
+                    };
+                    let _ = request.respond(response);
+                }
+            })
+        };
+        Ok(Self {
+            server,
+            address,
+            worker: Some(worker),
+        })
+    }
+
+    /// The address actually bound, e.g. with the port filled in for `:0`.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write as IoWrite};
+    use std::net::TcpStream;
+
+    #[test]
+    fn renders_counters_and_gauges() {
+        let metrics = Metrics::default();
+        Metrics::add(&metrics.bytes_received, 1500);
+        Metrics::add(&metrics.crc_errors, 2);
+        metrics.connected.store(true, Ordering::Relaxed);
+        let vbat = metrics.gauge(ValueSource::Variable, "vbat");
+        vbat.set(3.72);
+        metrics.gauge(ValueSource::Pinned, "Board \"A\" temp").set(f64::NAN);
+        metrics.gauge(ValueSource::Variable, "vbat").set(3.7);
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE rustcom_bytes_received_total counter\nrustcom_bytes_received_total 1500\n"));
+        assert!(text.contains("\nrustcom_crc_errors_total 2\n"));
+        assert!(text.contains("\nrustcom_connected 1\n"));
+        assert!(text.contains("# TYPE rustcom_variable gauge\nrustcom_variable{key=\"vbat\"} 3.7\n"));
+        assert!(text.contains("rustcom_pinned_value{name=\"Board \\\"A\\\" temp\"} NaN\n"));
+        assert_eq!(vbat.get(), 3.7);
+
+        metrics.clear_gauges();
+        assert!(!metrics.render().contains("rustcom_variable"));
+    }
+
+    #[test]
+    fn classifies_check_failures() {
+        assert!(is_check_failure("CRC mismatch"));
+        assert!(is_check_failure("UBX 01 07 checksum mismatch: got 00 00, expected 12 34"));
+        assert!(is_check_failure("BCC mismatch: got AF, expected 50"));
+        assert!(!is_check_failure("NMEA sentence truncated"));
+    }
+
+    #[test]
+    fn serves_the_metrics_page() {
+        let metrics = Arc::new(Metrics::default());
+        Metrics::add(&metrics.frames_decoded, 7);
+        let server = MetricsServer::start("127.0.0.1:0", metrics).unwrap();
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(server.address()).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let page = get("/metrics");
+        assert!(page.starts_with("HTTP/1.1 200"));
+        assert!(page.contains("text/plain; version=0.0.4"));
+        assert!(page.contains("\nrustcom_frames_decoded_total 7\n"));
+        assert!(get("/").starts_with("HTTP/1.1 404"));
+
+        assert!(MetricsServer::start("not an address", Arc::new(Metrics::default())).is_err());
+    }
+}
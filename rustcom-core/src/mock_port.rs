@@ -0,0 +1,293 @@
+//! An in-memory `SerialPort` for tests: records everything that would have
+//! reached the wire and serves queued bytes as received data.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serialport::{ClearBuffer, SerialPort};
+
+#[derive(Default)]
+struct MockState {
+    activity: Vec<String>,
+    written: Vec<u8>,
+    rx: VecDeque<u8>,
+    /// Looped-back writes and when they become readable.
+    delayed: VecDeque<(Instant, Vec<u8>)>,
+    eof: bool,
+    loopback: bool,
+    loopback_delay: Duration,
+    timeout: Duration,
+    rts: bool,
+    dtr: bool,
+    baud_rate: u32,
+    write_limit: Option<usize>,
+    write_error: Option<std::io::ErrorKind>,
+    read_error: Option<std::io::ErrorKind>,
+}
+
+/// Clones share state, so a test can keep one handle while the code under test
+/// owns another as a `Box<dyn SerialPort>`.
+#[derive(Clone, Default)]
+pub struct MockPort {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockPort {
+    fn record(&self, what: String) {
+        self.state.lock().unwrap().activity.push(what);
+    }
+
+    /// Writes and control line changes so far, e.g. `"write [41, 42]"`,
+    /// `"dtr true"` or `"break on"`.
+    pub fn activity(&self) -> Vec<String> {
+        self.state.lock().unwrap().activity.clone()
+    }
+
+    /// The bytes written since the last call, for a test playing the device.
+    pub fn take_written(&self) -> Vec<u8> {
+        std::mem::take(&mut self.state.lock().unwrap().written)
+    }
+
+    /// Queue bytes to be returned by later reads.
+    pub fn push_rx(&self, data: &[u8]) {
+        self.state.lock().unwrap().rx.extend(data);
+    }
+
+    /// Bytes queued by `push_rx` or a delayed loopback and not yet read.
+    pub fn pending_rx(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.rx.len() + state.delayed.iter().map(|(_, data)| data.len()).sum::<usize>()
+    }
+
+    /// Behave like a loopback plug: written bytes come back as received data,
+    /// RTS is wired to CTS and DTR to DSR.
+    pub fn set_loopback(&self, loopback: bool) {
+        self.state.lock().unwrap().loopback = loopback;
+    }
+
+    /// Hold looped-back bytes for `delay` before they can be read, like a
+    /// device that takes that long to echo.
+    pub fn set_loopback_delay(&self, delay: Duration) {
+        self.state.lock().unwrap().loopback_delay = delay;
+    }
+
+    /// Accept at most `limit` bytes per write, like a port held off by flow
+    /// control; with `Some(0)` every write times out.
+    pub fn set_write_limit(&self, limit: Option<usize>) {
+        self.state.lock().unwrap().write_limit = limit;
+    }
+
+    /// Fail every write with `kind` from now on.
+    pub fn set_write_error(&self, kind: Option<std::io::ErrorKind>) {
+        self.state.lock().unwrap().write_error = kind;
+    }
+
+    /// Fail every read with `kind` once the queued bytes are gone, like an
+    /// adapter that was unplugged.
+    pub fn set_read_error(&self, kind: Option<std::io::ErrorKind>) {
+        self.state.lock().unwrap().read_error = kind;
+    }
+
+    /// Once the queued bytes are gone, return `Ok(0)` like a closed PTY instead
+    /// of timing out.
+    pub fn set_eof(&self, eof: bool) {
+        self.state.lock().unwrap().eof = eof;
+    }
+}
+
+impl std::io::Read for MockPort {
+    /// Returns queued bytes, or `TimedOut` like a real port with nothing to read
+    /// (`Ok(0)` after `set_eof`, the error after `set_read_error`).
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        while state.delayed.front().is_some_and(|(due, _)| *due <= now) {
+            let (_, data) = state.delayed.pop_front().unwrap();
+            state.rx.extend(data);
+        }
+        if state.rx.is_empty() {
+            if let Some(kind) = state.read_error {
+                return Err(kind.into());
+            }
+            return if state.eof {
+                Ok(0)
+            } else {
+                Err(std::io::ErrorKind::TimedOut.into())
+            };
+        }
+        let count = buf.len().min(state.rx.len());
+        for (slot, byte) in buf.iter_mut().zip(state.rx.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+}
+
+impl std::io::Write for MockPort {
+    /// Records what was accepted, which `set_write_limit` may cut short.
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(kind) = state.write_error {
+            return Err(kind.into());
+        }
+        let buf = &buf[..buf.len().min(state.write_limit.unwrap_or(usize::MAX))];
+        if buf.is_empty() {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+        if state.loopback && state.loopback_delay.is_zero() {
+            state.rx.extend(buf);
+        } else if state.loopback {
+            let due = Instant::now() + state.loopback_delay;
+            state.delayed.push_back((due, buf.to_vec()));
+        } else {
+            state.activity.push(format!("write {:02X?}", buf));
+            state.written.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SerialPort for MockPort {
+    fn name(&self) -> Option<String> {
+        Some("TEST".to_string())
+    }
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(match self.state.lock().unwrap().baud_rate {
+            0 => 9600,
+            baud => baud,
+        })
+    }
+    fn data_bits(&self) -> serialport::Result<serialport::DataBits> {
+        Ok(serialport::DataBits::Eight)
+    }
+    fn flow_control(&self) -> serialport::Result<serialport::FlowControl> {
+        Ok(serialport::FlowControl::None)
+    }
+    fn parity(&self) -> serialport::Result<serialport::Parity> {
+        Ok(serialport::Parity::None)
+    }
+    fn stop_bits(&self) -> serialport::Result<serialport::StopBits> {
+        Ok(serialport::StopBits::One)
+    }
+    fn timeout(&self) -> Duration {
+        self.state.lock().unwrap().timeout
+    }
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.state.lock().unwrap().baud_rate = baud_rate;
+        Ok(())
+    }
+    fn set_data_bits(&mut self, _: serialport::DataBits) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_flow_control(&mut self, _: serialport::FlowControl) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_parity(&mut self, _: serialport::Parity) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_stop_bits(&mut self, _: serialport::StopBits) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.state.lock().unwrap().timeout = timeout;
+        Ok(())
+    }
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.state.lock().unwrap().rts = level;
+        self.record(format!("rts {}", level));
+        Ok(())
+    }
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.state.lock().unwrap().dtr = level;
+        self.record(format!("dtr {}", level));
+        Ok(())
+    }
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        let state = self.state.lock().unwrap();
+        Ok(state.loopback && state.rts)
+    }
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        let state = self.state.lock().unwrap();
+        Ok(state.loopback && state.dtr)
+    }
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self.state.lock().unwrap().rx.len() as u32)
+    }
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+    fn clear(&self, buffer: ClearBuffer) -> serialport::Result<()> {
+        if matches!(buffer, ClearBuffer::Input | ClearBuffer::All) {
+            self.state.lock().unwrap().rx.clear();
+        }
+        Ok(())
+    }
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(self.clone()))
+    }
+    fn set_break(&self) -> serialport::Result<()> {
+        self.record("break on".to_string());
+        Ok(())
+    }
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.record("break off".to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn records_writes_and_serves_queued_reads() {
+        let port = MockPort::default();
+        let mut boxed: Box<dyn SerialPort> = Box::new(port.clone());
+
+        boxed.write_all(b"AB").unwrap();
+        boxed.write_data_terminal_ready(true).unwrap();
+        assert_eq!(port.activity(), ["write [41, 42]", "dtr true"]);
+
+        let mut buf = [0u8; 4];
+        assert_eq!(
+            boxed.read(&mut buf).unwrap_err().kind(),
+            std::io::ErrorKind::TimedOut
+        );
+        port.push_rx(b"hello");
+        assert_eq!(boxed.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"hell");
+        assert_eq!(boxed.bytes_to_read().unwrap(), 1);
+
+        port.set_eof(true);
+        assert_eq!(boxed.read(&mut buf).unwrap(), 1);
+        assert_eq!(boxed.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn loopback_echoes_data_and_handshake_lines() {
+        let port = MockPort::default();
+        port.set_loopback(true);
+        let mut boxed: Box<dyn SerialPort> = Box::new(port.clone());
+
+        boxed.write_all(b"ping").unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(boxed.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"ping");
+
+        boxed.write_request_to_send(true).unwrap();
+        assert!(boxed.read_clear_to_send().unwrap());
+        assert!(!boxed.read_data_set_ready().unwrap());
+    }
+}
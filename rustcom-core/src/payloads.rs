@@ -0,0 +1,219 @@
+//! Send modes and line endings, and the recently sent or copied payloads, kept
+//! app-wide so they can be re-inserted into the send box with one click.
+
+use std::convert::Infallible;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hex::parse_escapes;
+
+/// Entries kept; the oldest drops off when a new one arrives.
+pub const MAX_RECENT_PAYLOADS: usize = 20;
+
+/// How the send box interprets its text.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SendMode {
+    Ascii,
+    Hex,
+}
+
+impl SendMode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SendMode::Ascii => "ASCII",
+            SendMode::Hex => "Hex",
+        }
+    }
+}
+
+/// What goes after each line of text sent.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LineEnding {
+    None,
+    Cr,
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::None => "None",
+            LineEnding::Cr => "\\r",
+            LineEnding::Lf => "\\n",
+            LineEnding::CrLf => "\\r\\n",
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            LineEnding::None => b"",
+            LineEnding::Cr => b"\r",
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+        }
+    }
+
+    /// `text` as sent: each line break in it and the end become this line
+    /// ending. With no line ending, breaks are sent as `\n`.
+    pub fn terminate(&self, text: &str) -> Vec<u8> {
+        let Ok(bytes) = self.join_lines(text, |line| Ok::<_, Infallible>(line.as_bytes().to_vec()));
+        bytes
+    }
+
+    /// Like `terminate`, with the escapes of `parse_escapes` in each line. An
+    /// escaped `\n` is sent as that byte, not as the line ending.
+    pub fn terminate_escaped(&self, text: &str) -> Result<Vec<u8>, String> {
+        self.join_lines(text, parse_escapes)
+    }
+
+    fn join_lines<E>(&self, text: &str, mut line_bytes: impl FnMut(&str) -> Result<Vec<u8>, E>) -> Result<Vec<u8>, E> {
+        let mut bytes = Vec::with_capacity(text.len() + 2);
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                bytes.extend_from_slice(if *self == LineEnding::None { b"\n" } else { self.as_bytes() });
+            }
+            bytes.extend(line_bytes(line)?);
+        }
+        bytes.extend_from_slice(self.as_bytes());
+        Ok(bytes)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentPayload {
+    /// Send box text: plain text for ASCII, `AA BB` style for hex.
+    pub text: String,
+    pub mode: SendMode,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Most recent first, without duplicates.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PayloadRing {
+    entries: Vec<RecentPayload>,
+}
+
+impl PayloadRing {
+    pub fn entries(&self) -> &[RecentPayload] {
+        &self.entries
+    }
+
+    pub fn entries_mut(&mut self) -> &mut [RecentPayload] {
+        &mut self.entries
+    }
+
+    /// Put a payload at the top. An identical payload (same text and mode) is
+    /// moved up instead of repeated, keeping its description unless a new one
+    /// is given.
+    pub fn add(&mut self, text: &str, mode: SendMode, description: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let existing = self
+            .entries
+            .iter()
+            .position(|entry| entry.text == text && entry.mode == mode)
+            .map(|index| self.entries.remove(index));
+        let mut entry = existing.unwrap_or_else(|| RecentPayload {
+            text: text.to_string(),
+            mode,
+            description: String::new(),
+        });
+        if !description.is_empty() {
+            entry.description = description.to_string();
+        }
+        self.entries.insert(0, entry);
+        self.entries.truncate(MAX_RECENT_PAYLOADS);
+    }
+
+    /// Move an entry to the top after it was reused, returning it.
+    pub fn reuse(&mut self, index: usize) -> Option<RecentPayload> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        let entry = self.entries.remove(index);
+        self.entries.insert(0, entry.clone());
+        Some(entry)
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.entries.len() {
+            self.entries.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(ring: &PayloadRing) -> Vec<&str> {
+        ring.entries().iter().map(|e| e.text.as_str()).collect()
+    }
+
+    #[test]
+    fn line_endings_replace_each_break() {
+        assert_eq!(LineEnding::CrLf.terminate("a\nb"), b"a\r\nb\r\n");
+        assert_eq!(LineEnding::None.terminate("a\nb"), b"a\nb");
+    }
+
+    #[test]
+    fn escaped_lines_keep_escaped_breaks_as_bytes() {
+        assert_eq!(LineEnding::Cr.terminate_escaped("\\x02A\\n\nB").unwrap(), b"\x02A\n\rB\r");
+        assert_eq!(LineEnding::Cr.terminate("\\x02"), b"\\x02\r");
+        assert!(LineEnding::Cr.terminate_escaped("ok\n\\xZZ").is_err());
+    }
+
+    #[test]
+    fn duplicates_move_to_the_top() {
+        let mut ring = PayloadRing::default();
+        ring.add("AT", SendMode::Ascii, "");
+        ring.add("AA BB", SendMode::Hex, "ping");
+        ring.add("AT", SendMode::Ascii, "");
+        assert_eq!(texts(&ring), ["AT", "AA BB"]);
+
+        // Same text in another mode is a different payload
+        ring.add("AT", SendMode::Hex, "");
+        assert_eq!(ring.entries().len(), 3);
+
+        ring.add("AA BB", SendMode::Hex, "");
+        assert_eq!(ring.entries()[0].description, "ping");
+        ring.add("", SendMode::Ascii, "");
+        assert_eq!(ring.entries().len(), 3);
+    }
+
+    #[test]
+    fn keeps_only_the_newest() {
+        let mut ring = PayloadRing::default();
+        for i in 0..MAX_RECENT_PAYLOADS + 5 {
+            ring.add(&format!("cmd {}", i), SendMode::Ascii, "");
+        }
+        assert_eq!(ring.entries().len(), MAX_RECENT_PAYLOADS);
+        assert_eq!(ring.entries()[0].text, format!("cmd {}", MAX_RECENT_PAYLOADS + 4));
+        assert_eq!(ring.entries().last().unwrap().text, "cmd 5");
+    }
+
+    #[test]
+    fn reuse_bumps_the_entry() {
+        let mut ring = PayloadRing::default();
+        ring.add("one", SendMode::Ascii, "");
+        ring.add("two", SendMode::Ascii, "");
+        ring.add("three", SendMode::Ascii, "");
+        assert_eq!(ring.reuse(2).unwrap().text, "one");
+        assert_eq!(texts(&ring), ["one", "three", "two"]);
+        assert!(ring.reuse(9).is_none());
+    }
+
+    #[test]
+    fn saves_as_a_plain_list() {
+        let mut ring = PayloadRing::default();
+        ring.add("01 03", SendMode::Hex, "read");
+        let json = serde_json::to_string(&ring).unwrap();
+        assert!(json.starts_with('['));
+        let restored: PayloadRing = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.entries(), ring.entries());
+    }
+}
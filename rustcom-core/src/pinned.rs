@@ -0,0 +1,244 @@
+//! Pinned values: fields picked out of fixed-format status lines and kept on
+//! screen with their latest value, coloured by threshold.
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Same compiled-size cap as filter rules.
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+/// Where a pinned value is found in each status line.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PinSource {
+    /// The first capture group of a regex, or the whole match without groups.
+    Regex,
+    /// A fixed byte range of the line.
+    Bytes,
+}
+
+impl PinSource {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PinSource::Regex => "Regex",
+            PinSource::Bytes => "Byte range",
+        }
+    }
+}
+
+/// How a value compares to its thresholds.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PinLevel {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+/// One pinned value. Only the definition is saved; call `compile` after
+/// loading, as with filter rules.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinnedValue {
+    pub name: String,
+    pub source: PinSource,
+    pub pattern: String,
+    pub offset: usize,
+    pub length: usize,
+    pub units: String,
+    pub warning: Option<f64>,
+    pub critical: Option<f64>,
+    /// Thresholds are lower limits (e.g. a supply voltage) instead of upper ones.
+    pub below: bool,
+    /// Raise an alert when the value becomes critical.
+    pub alert: bool,
+    #[serde(skip)]
+    pub regex: Option<Regex>,
+    #[serde(skip)]
+    pub error: Option<String>,
+    /// Latest extracted text, trimmed.
+    #[serde(skip)]
+    pub latest: Option<String>,
+    #[serde(skip)]
+    pub level: PinLevel,
+}
+
+impl PinnedValue {
+    /// A regex-based value, compiled straight away; check `error` for a bad pattern.
+    pub fn new(name: &str, pattern: &str) -> Self {
+        let mut value = Self {
+            name: name.to_string(),
+            source: PinSource::Regex,
+            pattern: pattern.to_string(),
+            offset: 0,
+            length: 1,
+            units: String::new(),
+            warning: None,
+            critical: None,
+            below: false,
+            alert: false,
+            regex: None,
+            error: None,
+            latest: None,
+            level: PinLevel::Normal,
+        };
+        value.compile();
+        value
+    }
+
+    /// Recompile after the pattern was edited.
+    pub fn compile(&mut self) {
+        self.regex = None;
+        self.error = None;
+        if self.pattern.is_empty() {
+            return;
+        }
+        match RegexBuilder::new(&self.pattern)
+            .size_limit(MAX_REGEX_SIZE)
+            .build()
+        {
+            Ok(regex) => self.regex = Some(regex),
+            Err(e) => self.error = Some(format!("Invalid regex: {}", e)),
+        }
+    }
+
+    /// The field's text in a line, if the line contains it. A regex searches
+    /// the line's `text`; a byte range indexes `record`, the line's bytes, so
+    /// invalid UTF-8 replaced in `text` does not shift the offsets.
+    pub fn extract(&self, text: &str, record: &[u8]) -> Option<String> {
+        let text = match self.source {
+            PinSource::Regex => {
+                let captures = self.regex.as_ref()?.captures(text)?;
+                captures.get(1).or_else(|| captures.get(0))?.as_str().to_string()
+            }
+            PinSource::Bytes => {
+                let end = self.offset.checked_add(self.length)?;
+                String::from_utf8_lossy(record.get(self.offset..end)?).into_owned()
+            }
+        };
+        Some(text.trim().to_string())
+    }
+
+    /// Threshold level for a numeric value.
+    pub fn level_for(&self, value: f64) -> PinLevel {
+        let beyond = |limit: Option<f64>| {
+            limit.is_some_and(|limit| if self.below { value <= limit } else { value >= limit })
+        };
+        if beyond(self.critical) {
+            PinLevel::Critical
+        } else if beyond(self.warning) {
+            PinLevel::Warning
+        } else {
+            PinLevel::Normal
+        }
+    }
+
+    /// Take the value from `line` if it is there. Returns true when this made
+    /// the value critical, so an alert fires once per excursion.
+    pub fn update(&mut self, text: &str, record: &[u8]) -> bool {
+        let Some(text) = self.extract(text, record) else {
+            return false;
+        };
+        let level = text
+            .parse::<f64>()
+            .map(|value| self.level_for(value))
+            .unwrap_or_default();
+        let became_critical = level == PinLevel::Critical && self.level != PinLevel::Critical;
+        self.latest = Some(text);
+        self.level = level;
+        became_critical
+    }
+
+    /// "23.5 °C", or "—" before the first value arrived.
+    pub fn display(&self) -> String {
+        match &self.latest {
+            Some(text) if self.units.is_empty() => text.clone(),
+            Some(text) => format!("{} {}", text, self.units),
+            None => "—".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS: &str = "STAT T=23.5C V=11.9 MODE=RUN";
+
+    #[test]
+    fn regex_takes_the_first_group_or_the_whole_match() {
+        let temp = PinnedValue::new("Temp", r"T=([\d.]+)");
+        assert_eq!(temp.extract(STATUS, STATUS.as_bytes()).as_deref(), Some("23.5"));
+        let mode = PinnedValue::new("Mode", r"RUN|STOP");
+        assert_eq!(mode.extract(STATUS, STATUS.as_bytes()).as_deref(), Some("RUN"));
+        assert_eq!(temp.extract("boot ok", b"boot ok"), None);
+
+        let bad = PinnedValue::new("Bad", "(");
+        assert!(bad.error.is_some());
+        assert_eq!(bad.extract(STATUS, STATUS.as_bytes()), None);
+    }
+
+    #[test]
+    fn byte_range_reads_fixed_records() {
+        let mut volts = PinnedValue::new("Volts", "");
+        volts.source = PinSource::Bytes;
+        volts.offset = 15;
+        volts.length = 5;
+        assert_eq!(volts.extract(STATUS, STATUS.as_bytes()).as_deref(), Some("11.9"));
+        assert_eq!(volts.extract("short", b"short"), None);
+
+        // Offsets count the bytes as received, not their text with U+FFFD for invalid ones
+        let record = b"\xAA\xBB\xCC\xDD\xEE\xFF\x00\x01\x02\x03\x04\x05\x06\x07\x0812.25";
+        assert_eq!(volts.extract(&String::from_utf8_lossy(record), record).as_deref(), Some("12.25"));
+    }
+
+    #[test]
+    fn thresholds_work_in_either_direction() {
+        let mut temp = PinnedValue::new("Temp", r"T=([\d.]+)");
+        temp.warning = Some(60.0);
+        temp.critical = Some(80.0);
+        assert_eq!(temp.level_for(20.0), PinLevel::Normal);
+        assert_eq!(temp.level_for(60.0), PinLevel::Warning);
+        assert_eq!(temp.level_for(95.0), PinLevel::Critical);
+
+        let mut supply = PinnedValue::new("Supply", r"V=([\d.]+)");
+        supply.below = true;
+        supply.warning = Some(11.5);
+        supply.critical = Some(10.5);
+        assert_eq!(supply.level_for(12.0), PinLevel::Normal);
+        assert_eq!(supply.level_for(11.0), PinLevel::Warning);
+        assert_eq!(supply.level_for(10.0), PinLevel::Critical);
+    }
+
+    #[test]
+    fn alert_fires_once_per_critical_excursion() {
+        let mut temp = PinnedValue::new("Temp", r"T=([\d.]+)");
+        temp.critical = Some(80.0);
+        temp.units = "°C".to_string();
+        assert_eq!(temp.display(), "—");
+
+        assert!(!temp.update("T=25", b"T=25"));
+        assert!(temp.update("T=85", b"T=85"));
+        assert!(!temp.update("T=90", b"T=90"));
+        assert!(!temp.update("unrelated line", b"unrelated line"));
+        assert_eq!(temp.display(), "90 °C");
+        assert!(!temp.update("T=70", b"T=70"));
+        assert!(temp.update("T=81", b"T=81"));
+
+        // Non-numeric values are shown but never alert
+        let mut mode = PinnedValue::new("Mode", r"MODE=(\w+)");
+        mode.critical = Some(1.0);
+        assert!(!mode.update(STATUS, STATUS.as_bytes()));
+        assert_eq!(mode.level, PinLevel::Normal);
+    }
+
+    #[test]
+    fn only_the_definition_is_saved() {
+        let mut temp = PinnedValue::new("Temp", r"T=([\d.]+)");
+        temp.update(STATUS, STATUS.as_bytes());
+        let json = serde_json::to_string(&temp).unwrap();
+        assert!(!json.contains("latest"));
+        let mut restored: PinnedValue = serde_json::from_str(&json).unwrap();
+        assert!(restored.regex.is_none());
+        restored.compile();
+        assert_eq!(restored.extract(STATUS, STATUS.as_bytes()).as_deref(), Some("23.5"));
+    }
+}
@@ -0,0 +1,159 @@
+//! Serial line settings shared by the analyzer and the settings file.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Data bits per character.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DataBits {
+    Five,
+    Six,
+    Seven,
+    Eight,
+}
+
+impl DataBits {
+    pub fn to_serial(self) -> serialport::DataBits {
+        match self {
+            DataBits::Five => serialport::DataBits::Five,
+            DataBits::Six => serialport::DataBits::Six,
+            DataBits::Seven => serialport::DataBits::Seven,
+            DataBits::Eight => serialport::DataBits::Eight,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            DataBits::Five => "5",
+            DataBits::Six => "6",
+            DataBits::Seven => "7",
+            DataBits::Eight => "8",
+        }
+    }
+}
+
+/// Stop bits per character.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+impl StopBits {
+    pub fn to_serial(self) -> serialport::StopBits {
+        match self {
+            StopBits::One => serialport::StopBits::One,
+            StopBits::Two => serialport::StopBits::Two,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            StopBits::One => "1",
+            StopBits::Two => "2",
+        }
+    }
+}
+
+/// Hardware parity bit.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+impl Parity {
+    pub fn to_serial(self) -> serialport::Parity {
+        match self {
+            Parity::None => serialport::Parity::None,
+            Parity::Even => serialport::Parity::Even,
+            Parity::Odd => serialport::Parity::Odd,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Parity::None => "None",
+            Parity::Even => "Even",
+            Parity::Odd => "Odd",
+        }
+    }
+}
+
+/// Flow control handled by the driver.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FlowControl {
+    None,
+    Software,
+    Hardware,
+}
+
+impl FlowControl {
+    pub fn to_serial(self) -> serialport::FlowControl {
+        match self {
+            FlowControl::None => serialport::FlowControl::None,
+            FlowControl::Software => serialport::FlowControl::Software,
+            FlowControl::Hardware => serialport::FlowControl::Hardware,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            FlowControl::None => "None",
+            FlowControl::Software => "Software",
+            FlowControl::Hardware => "Hardware",
+        }
+    }
+}
+
+/// Time on the wire for one character: start bit, data bits, parity bit if any,
+/// and stop bits.
+pub fn char_time(baud: u32, data_bits: DataBits, parity: Parity, stop_bits: StopBits) -> Duration {
+    let data = match data_bits {
+        DataBits::Five => 5,
+        DataBits::Six => 6,
+        DataBits::Seven => 7,
+        DataBits::Eight => 8,
+    };
+    let parity = match parity {
+        Parity::None => 0,
+        Parity::Even | Parity::Odd => 1,
+    };
+    let stop = match stop_bits {
+        StopBits::One => 1,
+        StopBits::Two => 2,
+    };
+    let bits: u64 = 1 + data + parity + stop;
+    Duration::from_nanos(bits * 1_000_000_000 / u64::from(baud.max(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn char_time_counts_every_bit_on_the_wire() {
+        // 8N1: 10 bits
+        assert_eq!(
+            char_time(9600, DataBits::Eight, Parity::None, StopBits::One),
+            Duration::from_nanos(1_041_666)
+        );
+        // 8E1: 11 bits
+        assert_eq!(
+            char_time(19200, DataBits::Eight, Parity::Even, StopBits::One),
+            Duration::from_nanos(572_916)
+        );
+        // 7O2: 11 bits
+        assert_eq!(
+            char_time(115200, DataBits::Seven, Parity::Odd, StopBits::Two),
+            Duration::from_nanos(95_486)
+        );
+        // 5N1: 7 bits
+        assert_eq!(
+            char_time(300, DataBits::Five, Parity::None, StopBits::One),
+            Duration::from_nanos(23_333_333)
+        );
+    }
+}
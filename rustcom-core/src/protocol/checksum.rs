@@ -0,0 +1,56 @@
+//! Checksums shared by the decoders
+
+/// CRC-16/MODBUS (poly 0xA001 reflected, init 0xFFFF). Transmitted low byte first.
+pub fn crc16_modbus(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// True when the last two bytes are a valid little-endian Modbus CRC of the rest.
+pub fn has_valid_modbus_crc(frame: &[u8]) -> bool {
+    if frame.len() < 3 {
+        return false;
+    }
+    let (body, crc) = frame.split_at(frame.len() - 2);
+    crc16_modbus(body) == u16::from_le_bytes([crc[0], crc[1]])
+}
+
+/// Sum of the bytes, modulo 256.
+pub fn sum8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+/// XOR of the bytes.
+pub fn xor8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |acc, &b| acc ^ b)
+}
+
+/// Modbus ASCII LRC: the two's complement of the byte sum.
+pub fn lrc(data: &[u8]) -> u8 {
+    sum8(data).wrapping_neg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modbus_crc_reference() {
+        assert_eq!(crc16_modbus(b"123456789"), 0x4B37);
+        assert!(has_valid_modbus_crc(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]));
+        assert!(!has_valid_modbus_crc(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCE]));
+        assert_eq!(lrc(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]), 0xF2);
+        assert_eq!(sum8(&[0x80, 0x81, 0x02]), 0x03);
+        assert_eq!(xor8(&[0x0F, 0xF0, 0x01]), 0xFE);
+    }
+}
@@ -0,0 +1,225 @@
+//! COBS (Consistent Overhead Byte Stuffing) framing, 0x00-delimited
+
+use super::{Decoder, Frame, MAX_FRAME_SIZE};
+
+/// COBS-encode a payload. The trailing 0x00 delimiter is not included.
+pub fn encode(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + payload.len() / 254 + 1);
+    let mut code_index = 0;
+    let mut code: u8 = 1;
+    out.push(0);
+
+    for &byte in payload {
+        // A full block only gets a follow-up block when more data exists
+        if code == 0xFF {
+            out[code_index] = code;
+            code_index = out.len();
+            out.push(0);
+            code = 1;
+        }
+
+        if byte == 0 {
+            out[code_index] = code;
+            code_index = out.len();
+            out.push(0);
+            code = 1;
+        } else {
+            out.push(byte);
+            code += 1;
+        }
+    }
+
+    out[code_index] = code;
+    out
+}
+
+/// Decode a single COBS block sequence (without the 0x00 delimiter).
+pub fn decode(encoded: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+
+    while i < encoded.len() {
+        let code = encoded[i];
+        if code == 0 {
+            return Err(format!("unexpected 0x00 code byte at offset {}", i));
+        }
+        i += 1;
+
+        let end = i + code as usize - 1;
+        if end > encoded.len() {
+            return Err(format!(
+                "block at offset {} needs {} bytes, only {} left",
+                i - 1,
+                code - 1,
+                encoded.len() - i
+            ));
+        }
+        out.extend_from_slice(&encoded[i..end]);
+        i = end;
+
+        if code != 0xFF && i < encoded.len() {
+            out.push(0);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Splits the stream on 0x00 delimiters and decodes each COBS frame.
+#[derive(Default)]
+pub struct CobsDecoder {
+    buffer: Vec<u8>,
+    overflowed: bool,
+}
+
+impl Decoder for CobsDecoder {
+    fn feed(&mut self, data: &[u8]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+
+        for &byte in data {
+            if byte != 0 {
+                if self.overflowed {
+                    continue;
+                }
+                self.buffer.push(byte);
+                if self.buffer.len() > MAX_FRAME_SIZE {
+                    let raw = std::mem::take(&mut self.buffer);
+                    self.overflowed = true;
+                    frames.push(Frame::malformed(
+                        raw,
+                        format!("no 0x00 delimiter within {} bytes", MAX_FRAME_SIZE),
+                    ));
+                }
+                continue;
+            }
+
+            if self.overflowed {
+                self.overflowed = false;
+                continue;
+            }
+
+            let raw = std::mem::take(&mut self.buffer);
+            if raw.is_empty() {
+                continue;
+            }
+
+            match decode(&raw) {
+                Ok(payload) => {
+                    let summary = format!("{} bytes", payload.len());
+                    frames.push(Frame::ok(payload, summary));
+                }
+                Err(e) => frames.push(Frame::malformed(raw, e)),
+            }
+        }
+
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.buffer.clear();
+        self.overflowed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_reference_vectors() {
+        assert_eq!(encode(&[]), vec![0x01]);
+        assert_eq!(encode(&[0x00]), vec![0x01, 0x01]);
+        assert_eq!(encode(&[0x00, 0x00]), vec![0x01, 0x01, 0x01]);
+        assert_eq!(encode(&[0x11, 0x22, 0x00, 0x33]), vec![0x03, 0x11, 0x22, 0x02, 0x33]);
+        assert_eq!(encode(&[0x11, 0x00, 0x00, 0x00]), vec![0x02, 0x11, 0x01, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn full_254_byte_block_has_no_trailing_code() {
+        let payload: Vec<u8> = (1..=254).collect();
+        let encoded = encode(&payload);
+        assert_eq!(encoded.len(), 255);
+        assert_eq!(encoded[0], 0xFF);
+        assert_eq!(decode(&encoded).unwrap(), payload);
+    }
+
+    #[test]
+    fn block_boundary_after_254_bytes() {
+        // 00 01..FE: leading zero then a full block
+        let mut payload = vec![0x00];
+        payload.extend(1..=254u8);
+        let encoded = encode(&payload);
+        assert_eq!(&encoded[..2], &[0x01, 0xFF]);
+        assert_eq!(decode(&encoded).unwrap(), payload);
+
+        // 01..FF: a full block followed by a one-byte block
+        let payload: Vec<u8> = (1..=255).collect();
+        let encoded = encode(&payload);
+        assert_eq!(encoded[0], 0xFF);
+        assert_eq!(&encoded[255..], &[0x02, 0xFF]);
+        assert_eq!(decode(&encoded).unwrap(), payload);
+    }
+
+    #[test]
+    fn zero_length_payload_round_trips() {
+        assert_eq!(decode(&encode(&[])).unwrap(), Vec::<u8>::new());
+
+        let mut decoder = CobsDecoder::default();
+        let frames = decoder.feed(&[0x01, 0x00]);
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].payload.is_empty());
+        assert!(frames[0].error.is_none());
+    }
+
+    #[test]
+    fn consecutive_delimiters_are_ignored() {
+        let mut decoder = CobsDecoder::default();
+        let frames = decoder.feed(&[0x00, 0x00, 0x02, 0x41, 0x00, 0x00]);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, vec![0x41]);
+    }
+
+    #[test]
+    fn truncated_block_is_malformed() {
+        assert!(decode(&[0x05, 0x01, 0x02]).is_err());
+
+        let mut decoder = CobsDecoder::default();
+        let frames = decoder.feed(&[0x05, 0x01, 0x02, 0x00]);
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].error.is_some());
+        assert_eq!(frames[0].payload, vec![0x05, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn frame_split_across_chunks() {
+        let encoded = encode(b"hello\0world");
+        let (first, second) = encoded.split_at(4);
+        let mut decoder = CobsDecoder::default();
+        assert!(decoder.feed(first).is_empty());
+        let mut rest = second.to_vec();
+        rest.push(0x00);
+        let frames = decoder.feed(&rest);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, b"hello\0world".to_vec());
+    }
+
+    #[test]
+    fn oversized_frame_is_dropped_until_next_delimiter() {
+        let mut decoder = CobsDecoder::default();
+        let mut data = vec![0x01; MAX_FRAME_SIZE + 10];
+        data.push(0x00);
+        data.extend_from_slice(&[0x02, 0x41, 0x00]);
+        let frames = decoder.feed(&data);
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].error.is_some());
+        assert_eq!(frames[1].payload, vec![0x41]);
+    }
+
+    #[test]
+    fn round_trip_random_lengths() {
+        for len in [0usize, 1, 253, 254, 255, 508, 509, 1000] {
+            let payload: Vec<u8> = (0..len).map(|i| (i % 7) as u8).collect();
+            assert_eq!(decode(&encode(&payload)).unwrap(), payload, "len {}", len);
+        }
+    }
+}
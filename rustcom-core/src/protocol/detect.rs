@@ -0,0 +1,230 @@
+//! Lightweight protocol detection over the first few KB of a session
+
+use super::{checksum, nmea, slip};
+
+/// Bytes sampled before the first evaluation, and between later ones.
+pub const DETECT_STEP: usize = 2048;
+/// Detection gives up once this much data has been seen without a verdict.
+pub const DETECT_MAX_SAMPLE: usize = 8192;
+/// Minimum score for a protocol to be suggested.
+pub const SUGGEST_THRESHOLD: f32 = 0.8;
+/// Minimum number of messages a score must be based on.
+const MIN_MESSAGES: usize = 3;
+
+/// A protocol recognised in the opening bytes of a session.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DetectedProtocol {
+    Nmea,
+    ModbusRtu,
+    Slip,
+}
+
+impl DetectedProtocol {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DetectedProtocol::Nmea => "NMEA 0183",
+            DetectedProtocol::ModbusRtu => "Modbus RTU",
+            DetectedProtocol::Slip => "SLIP",
+        }
+    }
+}
+
+/// Fraction of CR/LF-terminated lines that are checksum-valid NMEA sentences.
+pub fn score_nmea(data: &[u8]) -> (f32, usize) {
+    let mut lines = 0;
+    let mut valid = 0;
+    for line in data.split(|&b| b == b'\n').filter(|l| l.len() > 1) {
+        lines += 1;
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if nmea::verify_sentence(line).is_ok() {
+            valid += 1;
+        }
+    }
+    ratio(valid, lines)
+}
+
+/// Fraction of gap-delimited frames (one per received burst) ending in a valid Modbus CRC.
+pub fn score_modbus_rtu(bursts: &[Vec<u8>]) -> (f32, usize) {
+    let frames: Vec<&Vec<u8>> = bursts.iter().filter(|b| b.len() >= 4).collect();
+    let valid = frames
+        .iter()
+        .filter(|f| checksum::has_valid_modbus_crc(f))
+        .count();
+    ratio(valid, frames.len())
+}
+
+/// Fraction of END-delimited segments that unstuff cleanly, provided END bytes
+/// are frequent enough to be delimiters rather than incidental data.
+pub fn score_slip(data: &[u8]) -> (f32, usize) {
+    let delimiters = data.iter().filter(|&&b| b == slip::END).count();
+    if delimiters < 2 || data.len() / delimiters > 512 {
+        return (0.0, 0);
+    }
+
+    let mut frames = 0;
+    let mut valid = 0;
+    for segment in data.split(|&b| b == slip::END).filter(|s| !s.is_empty()) {
+        frames += 1;
+        let mut escaping = false;
+        let clean = segment.iter().all(|&b| {
+            let ok = !escaping || b == slip::ESC_END || b == slip::ESC_ESC;
+            escaping = !escaping && b == slip::ESC;
+            ok
+        });
+        if clean && !escaping {
+            valid += 1;
+        }
+    }
+    ratio(valid, frames)
+}
+
+/// Fraction of bytes that are printable ASCII or common whitespace.
+pub fn printable_ratio(data: &[u8]) -> f32 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let printable = data
+        .iter()
+        .filter(|b| b.is_ascii_graphic() || matches!(b, b' ' | b'\r' | b'\n' | b'\t'))
+        .count();
+    printable as f32 / data.len() as f32
+}
+
+fn ratio(hits: usize, total: usize) -> (f32, usize) {
+    if total == 0 {
+        (0.0, 0)
+    } else {
+        ((hits as f32 / total as f32).min(1.0), total)
+    }
+}
+
+/// Collects RX bursts until enough data is available, then scores each detector.
+#[derive(Default)]
+pub struct ProtocolDetector {
+    bursts: Vec<Vec<u8>>,
+    sampled: usize,
+    next_evaluation: usize,
+    finished: bool,
+}
+
+impl ProtocolDetector {
+    /// Feed one received chunk. Returns a suggestion at most once.
+    pub fn feed(&mut self, data: &[u8]) -> Option<DetectedProtocol> {
+        if self.finished || data.is_empty() {
+            return None;
+        }
+
+        let take = data.len().min(DETECT_MAX_SAMPLE - self.sampled);
+        self.bursts.push(data[..take].to_vec());
+        self.sampled += take;
+
+        if self.next_evaluation == 0 {
+            self.next_evaluation = DETECT_STEP;
+        }
+        if self.sampled < self.next_evaluation && self.sampled < DETECT_MAX_SAMPLE {
+            return None;
+        }
+        self.next_evaluation += DETECT_STEP;
+
+        let verdict = self.evaluate();
+        if verdict.is_some() || self.sampled >= DETECT_MAX_SAMPLE {
+            self.finished = true;
+            self.bursts = Vec::new();
+        }
+        verdict
+    }
+
+    fn evaluate(&self) -> Option<DetectedProtocol> {
+        let data: Vec<u8> = self.bursts.concat();
+
+        let mut candidates = vec![(DetectedProtocol::ModbusRtu, score_modbus_rtu(&self.bursts))];
+        if printable_ratio(&data) > 0.9 {
+            // Text streams: only a line protocol can be a match
+            candidates = vec![(DetectedProtocol::Nmea, score_nmea(&data))];
+        } else {
+            candidates.push((DetectedProtocol::Slip, score_slip(&data)));
+            candidates.push((DetectedProtocol::Nmea, score_nmea(&data)));
+        }
+
+        candidates
+            .into_iter()
+            .filter(|(_, (score, messages))| *score >= SUGGEST_THRESHOLD && *messages >= MIN_MESSAGES)
+            .max_by(|a, b| a.1 .0.total_cmp(&b.1 .0))
+            .map(|(protocol, _)| protocol)
+    }
+
+    /// Start over, e.g. after reconnecting to a possibly different device.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GGA: &[u8] = b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n";
+
+    fn run(chunks: impl Iterator<Item = Vec<u8>>) -> Option<DetectedProtocol> {
+        let mut detector = ProtocolDetector::default();
+        for chunk in chunks {
+            if let Some(found) = detector.feed(&chunk) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn detects_nmea() {
+        assert_eq!(run((0..40).map(|_| GGA.to_vec())), Some(DetectedProtocol::Nmea));
+    }
+
+    #[test]
+    fn plain_text_is_not_suggested() {
+        let line = b"temperature is fine, nothing to report here\r\n".to_vec();
+        assert_eq!(run((0..200).map(|_| line.clone())), None);
+    }
+
+    #[test]
+    fn detects_modbus_rtu_bursts() {
+        let request = vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD];
+        let mut response = vec![0x01, 0x03, 0x14];
+        response.extend((0..20).map(|i| i as u8));
+        let crc = checksum::crc16_modbus(&response);
+        response.extend_from_slice(&crc.to_le_bytes());
+
+        let chunks = (0..200).map(|i| if i % 2 == 0 { request.clone() } else { response.clone() });
+        assert_eq!(run(chunks), Some(DetectedProtocol::ModbusRtu));
+    }
+
+    #[test]
+    fn detects_slip() {
+        let frame = slip::encode(&[0x10, 0xC0, 0x20, 0x00, 0xDB, 0x99, 0x42, 0x17]);
+        assert_eq!(run((0..400).map(|_| frame.clone())), Some(DetectedProtocol::Slip));
+    }
+
+    #[test]
+    fn random_binary_is_not_suggested() {
+        let mut state: u32 = 12345;
+        let chunks = (0..100).map(move |_| {
+            (0..64)
+                .map(|_| {
+                    state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    (state >> 16) as u8
+                })
+                .collect::<Vec<u8>>()
+        });
+        assert_eq!(run(chunks), None);
+    }
+
+    #[test]
+    fn stops_after_max_sample() {
+        let mut detector = ProtocolDetector::default();
+        for _ in 0..(DETECT_MAX_SAMPLE / 64 + 10) {
+            detector.feed(&[0x55; 64]);
+        }
+        assert!(detector.finished);
+        assert!(detector.bursts.is_empty());
+    }
+}
@@ -0,0 +1,447 @@
+//! User-defined framing for `CustomFraming::Rule`: frames end at a delimiter,
+//! are a fixed number of bytes, or sit between STX and ETX (optionally with a
+//! length byte after the STX), and may carry a trailing checksum. Each frame is
+//! shown as one line with its payload in hex and a PASS/FAIL column.
+
+use serde::{Deserialize, Serialize};
+
+use super::stx_etx::{ETX, STX};
+use super::{checksum, Decoder, Frame, MAX_FRAME_SIZE};
+
+pub const DEFAULT_MAX_FRAME_LEN: usize = 1024;
+
+/// Where one frame ends and the next begins.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FrameBoundary {
+    Delimiter,
+    FixedLength,
+    StxEtx,
+}
+
+impl FrameBoundary {
+    pub const ALL: [FrameBoundary; 3] = [FrameBoundary::Delimiter, FrameBoundary::FixedLength, FrameBoundary::StxEtx];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            FrameBoundary::Delimiter => "Delimiter",
+            FrameBoundary::FixedLength => "Fixed length",
+            FrameBoundary::StxEtx => "STX/ETX",
+        }
+    }
+}
+
+/// Check bytes at the end of each frame.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TrailingChecksum {
+    None,
+    Sum8,
+    Xor,
+    /// CRC-16/MODBUS, low byte first.
+    Crc16,
+}
+
+impl TrailingChecksum {
+    pub const ALL: [TrailingChecksum; 4] =
+        [TrailingChecksum::None, TrailingChecksum::Sum8, TrailingChecksum::Xor, TrailingChecksum::Crc16];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            TrailingChecksum::None => "None",
+            TrailingChecksum::Sum8 => "8-bit sum",
+            TrailingChecksum::Xor => "XOR",
+            TrailingChecksum::Crc16 => "CRC-16 (Modbus)",
+        }
+    }
+
+    /// Number of check bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            TrailingChecksum::None => 0,
+            TrailingChecksum::Sum8 | TrailingChecksum::Xor => 1,
+            TrailingChecksum::Crc16 => 2,
+        }
+    }
+
+    /// The check bytes for `data`, in the order they are sent.
+    pub fn compute(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            TrailingChecksum::None => Vec::new(),
+            TrailingChecksum::Sum8 => vec![checksum::sum8(data)],
+            TrailingChecksum::Xor => vec![checksum::xor8(data)],
+            TrailingChecksum::Crc16 => checksum::crc16_modbus(data).to_le_bytes().to_vec(),
+        }
+    }
+}
+
+/// How to cut the RX stream into frames. The checksum covers the bytes before
+/// it, leaving out the STX, the ETX and the delimiter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FramingRule {
+    pub boundary: FrameBoundary,
+    pub delimiter: Vec<u8>,
+    /// Whole frame length for `FixedLength`, checksum included.
+    pub fixed_len: usize,
+    /// For `StxEtx`: the byte after STX is the payload length, so the payload may contain ETX.
+    pub length_byte: bool,
+    pub checksum: TrailingChecksum,
+    /// Frames longer than this are reported as malformed and dropped.
+    pub max_len: usize,
+}
+
+impl Default for FramingRule {
+    fn default() -> Self {
+        Self {
+            boundary: FrameBoundary::Delimiter,
+            delimiter: vec![b'\n'],
+            fixed_len: 8,
+            length_byte: false,
+            checksum: TrailingChecksum::None,
+            max_len: DEFAULT_MAX_FRAME_LEN,
+        }
+    }
+}
+
+impl FramingRule {
+    /// `max_len` within `1..=MAX_FRAME_SIZE`.
+    pub fn frame_limit(&self) -> usize {
+        self.max_len.clamp(1, MAX_FRAME_SIZE)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// Waiting for STX; only used by `StxEtx`.
+    Idle,
+    /// The next byte is the length.
+    Length,
+    Body,
+    /// Length-prefixed payload complete, ETX expected.
+    Etx,
+    /// After ETX, collecting the check bytes.
+    Check,
+    /// An oversized frame was dropped; skipping to the next delimiter.
+    Skip,
+}
+
+pub struct RuleDecoder {
+    rule: FramingRule,
+    state: State,
+    buffer: Vec<u8>,
+    /// The STX/ETX length byte, kept for the checksum.
+    length: Option<u8>,
+    check: Vec<u8>,
+}
+
+impl RuleDecoder {
+    /// Fails for a delimiter rule without delimiter bytes, which could never end a frame.
+    pub fn new(rule: FramingRule) -> Result<Self, String> {
+        if rule.boundary == FrameBoundary::Delimiter && rule.delimiter.is_empty() {
+            return Err("The framing rule needs at least one delimiter byte".to_string());
+        }
+        let mut decoder = Self {
+            rule,
+            state: State::Idle,
+            buffer: Vec::new(),
+            length: None,
+            check: Vec::new(),
+        };
+        decoder.reset();
+        Ok(decoder)
+    }
+
+    /// The start state for the rule's boundary.
+    fn start(&self) -> State {
+        match self.rule.boundary {
+            FrameBoundary::StxEtx => State::Idle,
+            _ => State::Body,
+        }
+    }
+
+    /// The state after an STX.
+    fn after_stx(&self) -> State {
+        if self.rule.length_byte {
+            State::Length
+        } else {
+            State::Body
+        }
+    }
+
+    /// A frame that failed framing or its checksum, under the FAIL column.
+    fn failed(payload: Vec<u8>, reason: String) -> Frame {
+        let len = payload.len();
+        let mut frame = Frame::malformed(payload, reason);
+        frame.summary = format!("FAIL {} bytes", len);
+        frame.hex = true;
+        frame
+    }
+
+    /// Check `body` (the frame with its check bytes split off into `check`)
+    /// and build its line.
+    fn checked(&self, body: Vec<u8>, check: &[u8]) -> Frame {
+        let len = body.len();
+        let mut frame = match self.rule.checksum {
+            TrailingChecksum::None => Frame::ok(body, format!("--   {} bytes", len)),
+            kind => {
+                let covered: Vec<u8> = self.length.iter().copied().chain(body.iter().copied()).collect();
+                let expected = kind.compute(&covered);
+                if expected == check {
+                    Frame::ok(body, format!("PASS {} bytes", len))
+                } else {
+                    let error = format!(
+                        "checksum mismatch: expected {}, got {}",
+                        super::format_payload_hex(&expected),
+                        super::format_payload_hex(check)
+                    );
+                    Self::failed(body, error)
+                }
+            }
+        };
+        frame.hex = true;
+        frame
+    }
+
+    /// A delimited or fixed-length frame, with the check bytes still at its end.
+    fn split_frame(&self, mut body: Vec<u8>) -> Frame {
+        let check_len = self.rule.checksum.size();
+        if body.len() < check_len {
+            return Self::failed(body, format!("shorter than the {}-byte checksum", check_len));
+        }
+        let check = body.split_off(body.len() - check_len);
+        self.checked(body, &check)
+    }
+
+    /// Give up on the frame so far, going to `next`.
+    fn drop_frame(&mut self, reason: String, next: State) -> Frame {
+        self.state = next;
+        self.length = None;
+        self.check.clear();
+        Self::failed(std::mem::take(&mut self.buffer), reason)
+    }
+
+    fn feed_delimited(&mut self, byte: u8, frames: &mut Vec<Frame>) {
+        let delimiter = &self.rule.delimiter;
+        self.buffer.push(byte);
+        let ended = self.buffer.ends_with(delimiter);
+        if self.state == State::Skip {
+            if ended {
+                self.buffer.clear();
+                self.state = State::Body;
+            } else if self.buffer.len() >= delimiter.len() {
+                // Keep only what may be the start of a delimiter
+                self.buffer.drain(..self.buffer.len() + 1 - delimiter.len());
+            }
+        } else if ended {
+            self.buffer.truncate(self.buffer.len() - delimiter.len());
+            if !self.buffer.is_empty() {
+                let body = std::mem::take(&mut self.buffer);
+                frames.push(self.split_frame(body));
+            }
+        } else if self.buffer.len() > self.rule.frame_limit() {
+            let reason = format!("no delimiter within {} bytes", self.rule.frame_limit());
+            frames.push(self.drop_frame(reason, State::Skip));
+        }
+    }
+
+    fn feed_fixed(&mut self, byte: u8, frames: &mut Vec<Frame>) {
+        self.buffer.push(byte);
+        if self.buffer.len() >= self.rule.fixed_len.clamp(1, self.rule.frame_limit()) {
+            let body = std::mem::take(&mut self.buffer);
+            frames.push(self.split_frame(body));
+        }
+    }
+
+    /// The payload is complete: collect the check bytes, or finish now without any.
+    fn payload_done(&mut self, frames: &mut Vec<Frame>) {
+        if self.rule.checksum == TrailingChecksum::None {
+            let body = std::mem::take(&mut self.buffer);
+            frames.push(self.checked(body, &[]));
+            self.state = State::Idle;
+            self.length = None;
+        } else {
+            self.state = State::Check;
+        }
+    }
+
+    fn feed_stx_etx(&mut self, byte: u8, frames: &mut Vec<Frame>) {
+        let limit = self.rule.frame_limit();
+        match self.state {
+            State::Idle | State::Skip => {
+                if byte == STX {
+                    self.state = self.after_stx();
+                }
+            }
+            State::Length if byte as usize > limit => {
+                let reason = format!("length byte {} over the {}-byte limit", byte, limit);
+                frames.push(self.drop_frame(reason, State::Idle));
+            }
+            State::Length => {
+                self.length = Some(byte);
+                self.state = if byte == 0 { State::Etx } else { State::Body };
+            }
+            State::Body => match self.length {
+                Some(length) => {
+                    self.buffer.push(byte);
+                    if self.buffer.len() == length as usize {
+                        self.state = State::Etx;
+                    }
+                }
+                None if byte == ETX => self.payload_done(frames),
+                None if byte == STX => {
+                    let reason = format!("STX again after {} bytes, no ETX", self.buffer.len());
+                    frames.push(self.drop_frame(reason, self.after_stx()));
+                }
+                None if self.buffer.len() >= limit => {
+                    let reason = format!("no ETX within {} bytes", limit);
+                    frames.push(self.drop_frame(reason, State::Idle));
+                }
+                None => self.buffer.push(byte),
+            },
+            State::Etx if byte == ETX => self.payload_done(frames),
+            State::Etx => {
+                let reason = format!("expected ETX after {} bytes, got {:02X}", self.buffer.len(), byte);
+                let next = if byte == STX { self.after_stx() } else { State::Idle };
+                frames.push(self.drop_frame(reason, next));
+            }
+            State::Check => {
+                self.check.push(byte);
+                if self.check.len() == self.rule.checksum.size() {
+                    let body = std::mem::take(&mut self.buffer);
+                    let check = std::mem::take(&mut self.check);
+                    frames.push(self.checked(body, &check));
+                    self.state = State::Idle;
+                    self.length = None;
+                }
+            }
+        }
+    }
+}
+
+impl Decoder for RuleDecoder {
+    fn feed(&mut self, data: &[u8]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        for &byte in data {
+            match self.rule.boundary {
+                FrameBoundary::Delimiter => self.feed_delimited(byte, &mut frames),
+                FrameBoundary::FixedLength => self.feed_fixed(byte, &mut frames),
+                FrameBoundary::StxEtx => self.feed_stx_etx(byte, &mut frames),
+            }
+        }
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.state = self.start();
+        self.buffer.clear();
+        self.length = None;
+        self.check.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(rule: FramingRule, chunks: &[&[u8]]) -> Vec<Frame> {
+        let mut decoder = RuleDecoder::new(rule).unwrap();
+        chunks.iter().flat_map(|chunk| decoder.feed(chunk)).collect()
+    }
+
+    fn summaries(frames: &[Frame]) -> Vec<String> {
+        frames
+            .iter()
+            .map(|f| match &f.error {
+                Some(error) => format!("{} ({})", f.summary, error),
+                None => f.summary.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn delimited_frames_with_a_trailing_checksum() {
+        let rule = FramingRule {
+            delimiter: b"\r\n".to_vec(),
+            checksum: TrailingChecksum::Xor,
+            ..FramingRule::default()
+        };
+        // 'A' ^ 'B' = 0x03; a split delimiter, blank lines and a bad check byte
+        let frames = decode(rule.clone(), &[b"AB\x03\r", b"\n\r\nAB\x04\r\n\r\n", b"Z\x5A\r\n"]);
+        assert_eq!(
+            summaries(&frames),
+            ["PASS 2 bytes", "FAIL 2 bytes (checksum mismatch: expected 03, got 04)", "PASS 1 bytes"]
+        );
+        assert_eq!(frames[0].payload, b"AB");
+        assert!(frames.iter().all(|f| f.hex));
+
+        // Oversized frames are dropped up to the next delimiter
+        let rule = FramingRule {
+            max_len: 4,
+            checksum: TrailingChecksum::None,
+            ..rule
+        };
+        let frames = decode(rule, &[b"0123456789\r", b"\nOK\r\n"]);
+        assert_eq!(summaries(&frames), ["FAIL 5 bytes (no delimiter within 4 bytes)", "--   2 bytes"]);
+    }
+
+    #[test]
+    fn fixed_length_frames_with_crc() {
+        let mut frame = vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x0A];
+        frame.extend(TrailingChecksum::Crc16.compute(&frame));
+        assert_eq!(frame[6..], [0xC5, 0xCD]);
+        let rule = FramingRule {
+            boundary: FrameBoundary::FixedLength,
+            fixed_len: 8,
+            checksum: TrailingChecksum::Crc16,
+            ..FramingRule::default()
+        };
+        let mut data = frame.clone();
+        data.extend_from_slice(&frame[..7]);
+        data.push(0x00);
+        let frames = decode(rule, &[&data[..5], &data[5..]]);
+        assert_eq!(
+            summaries(&frames),
+            ["PASS 6 bytes", "FAIL 6 bytes (checksum mismatch: expected C5 CD, got C5 00)"]
+        );
+    }
+
+    #[test]
+    fn stx_etx_with_and_without_a_length_byte() {
+        let rule = FramingRule {
+            boundary: FrameBoundary::StxEtx,
+            checksum: TrailingChecksum::Sum8,
+            ..FramingRule::default()
+        };
+        let frames = decode(rule.clone(), &[b"noise\x02AB\x03\x83\x02A", b"\x02AB\x03\x00"]);
+        assert_eq!(
+            summaries(&frames),
+            ["PASS 2 bytes", "FAIL 1 bytes (STX again after 1 bytes, no ETX)", "FAIL 2 bytes (checksum mismatch: expected 83, got 00)"]
+        );
+
+        // With a length byte the payload may hold ETX; the sum covers the length
+        let rule = FramingRule { length_byte: true, max_len: 8, ..rule };
+        let frames = decode(rule, &[b"\x02\x02\x03\x03\x03\x08", b"\x02\x09", b"\x02\x01AB\x03"]);
+        assert_eq!(
+            summaries(&frames),
+            [
+                "PASS 2 bytes",
+                "FAIL 0 bytes (length byte 9 over the 8-byte limit)",
+                "FAIL 1 bytes (expected ETX after 1 bytes, got 42)",
+            ]
+        );
+        assert_eq!(frames[0].payload, [0x03, 0x03]);
+    }
+
+    #[test]
+    fn a_delimiter_rule_needs_a_delimiter() {
+        let rule = FramingRule {
+            delimiter: Vec::new(),
+            ..FramingRule::default()
+        };
+        assert!(RuleDecoder::new(rule.clone()).is_err());
+        let fixed = FramingRule {
+            boundary: FrameBoundary::FixedLength,
+            ..rule
+        };
+        assert!(RuleDecoder::new(fixed).is_ok());
+    }
+}
@@ -0,0 +1,358 @@
+//! Protocol decoders
+//! Each decoder consumes raw RX chunks and emits framed, decoded messages for the frame pane.
+
+pub mod checksum;
+pub mod cobs;
+pub mod detect;
+pub mod framing_rule;
+pub mod modbus;
+pub mod modbus_ascii;
+pub mod nmea;
+pub mod slip;
+pub mod stx_etx;
+pub mod ubx;
+
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+/// Frames longer than this are reported as malformed and the framer resynchronises.
+pub const MAX_FRAME_SIZE: usize = 4096;
+
+/// Which decoder runs over the RX stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ProtocolMode {
+    None,
+    Nmea,
+    Ubx,
+    ModbusRtu,
+    ModbusAscii,
+    Custom,
+}
+
+impl ProtocolMode {
+    /// Every mode, in the order the protocol hotkey steps through them.
+    pub const ALL: [ProtocolMode; 6] = [
+        ProtocolMode::None,
+        ProtocolMode::Nmea,
+        ProtocolMode::Ubx,
+        ProtocolMode::ModbusRtu,
+        ProtocolMode::ModbusAscii,
+        ProtocolMode::Custom,
+    ];
+
+    /// The mode after this one, wrapping round to `None`.
+    pub fn next(&self) -> ProtocolMode {
+        let index = Self::ALL.iter().position(|mode| mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ProtocolMode::None => "None",
+            ProtocolMode::Nmea => "NMEA 0183",
+            ProtocolMode::Ubx => "UBX + NMEA",
+            ProtocolMode::ModbusRtu => "Modbus RTU",
+            ProtocolMode::ModbusAscii => "Modbus ASCII",
+            ProtocolMode::Custom => "Custom",
+        }
+    }
+
+    /// RTU or ASCII: frames carry a slave address and pair up as transactions.
+    pub fn is_modbus(&self) -> bool {
+        matches!(self, ProtocolMode::ModbusRtu | ProtocolMode::ModbusAscii)
+    }
+}
+
+/// How the terminal presents data while a decoder is active. The raw bytes are
+/// captured in every case; this only changes what is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum DecodedView {
+    /// Raw terminal, with decoded frames in their own pane.
+    #[default]
+    Raw,
+    /// Only the decoded frame lines.
+    Decoded,
+    /// Raw data with each frame's line inserted where the frame ended.
+    Interleaved,
+}
+
+impl DecodedView {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DecodedView::Raw => "Raw only",
+            DecodedView::Decoded => "Decoded only",
+            DecodedView::Interleaved => "Interleaved",
+        }
+    }
+}
+
+/// Framing used by `ProtocolMode::Custom`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CustomFraming {
+    Slip,
+    Cobs,
+    /// `STX payload ETX BCC`
+    StxEtx,
+    /// The user's `framing_rule::FramingRule`.
+    Rule,
+}
+
+impl CustomFraming {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CustomFraming::Slip => "SLIP",
+            CustomFraming::Cobs => "COBS",
+            CustomFraming::StxEtx => "STX/ETX + BCC",
+            CustomFraming::Rule => "Framing rule",
+        }
+    }
+}
+
+/// Optional encoding applied to outgoing payloads before they are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SendEncoding {
+    None,
+    Slip,
+    Cobs,
+    StxEtx,
+}
+
+impl SendEncoding {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SendEncoding::None => "Raw",
+            SendEncoding::Slip => "SLIP",
+            SendEncoding::Cobs => "COBS",
+            SendEncoding::StxEtx => "STX/ETX",
+        }
+    }
+
+    /// The bytes to write for `payload`. COBS frames get their 0x00 delimiter.
+    pub fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        match self {
+            SendEncoding::None => payload.to_vec(),
+            SendEncoding::Slip => slip::encode(payload),
+            SendEncoding::StxEtx => stx_etx::encode(payload),
+            SendEncoding::Cobs => {
+                let mut out = cobs::encode(payload);
+                out.push(0x00);
+                out
+            }
+        }
+    }
+}
+
+/// A single message recovered by a decoder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub payload: Vec<u8>,
+    pub summary: String,
+    /// Set when the frame was malformed; the payload holds whatever could be recovered.
+    pub error: Option<String>,
+    /// Framing-timing rules the frame broke, for decoders that check them.
+    pub violations: Vec<TimingViolation>,
+    /// Set on the notice a framer emits when it finds its way back to a frame
+    /// start after losing sync; such a notice carries no payload.
+    pub resync: Option<Resync>,
+    /// Show the payload as hex even when it is printable.
+    pub hex: bool,
+}
+
+impl Frame {
+    /// A well-formed frame.
+    pub fn ok(payload: Vec<u8>, summary: String) -> Self {
+        Self {
+            payload,
+            summary,
+            error: None,
+            violations: Vec::new(),
+            resync: None,
+            hex: false,
+        }
+    }
+
+    /// A frame that failed validation, summarised by its length.
+    pub fn malformed(payload: Vec<u8>, error: String) -> Self {
+        Self {
+            summary: format!("{} bytes", payload.len()),
+            payload,
+            error: Some(error),
+            violations: Vec::new(),
+            resync: None,
+            hex: false,
+        }
+    }
+
+    /// The notice for bytes skipped while resynchronising.
+    pub fn resync(resync: Resync) -> Self {
+        Self {
+            payload: Vec::new(),
+            summary: resync.label(),
+            error: None,
+            violations: Vec::new(),
+            resync: Some(resync),
+            hex: false,
+        }
+    }
+}
+
+/// Bytes a framer discarded between losing sync and the next good frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resync {
+    pub skipped: usize,
+    /// Stream offset of the first skipped byte, counted from the last reset.
+    pub offset: u64,
+}
+
+impl Resync {
+    pub fn label(&self) -> String {
+        format!("resync: skipped {} bytes at offset {}", self.skipped, self.offset)
+    }
+}
+
+/// A silence rule broken by a frame, with the measured gap in character times.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimingViolation {
+    /// Less than 3.5 characters of silence before the frame.
+    ShortFrameGap(f64),
+    /// More than 1.5 characters of silence between two bytes of the frame.
+    LongCharGap(f64),
+}
+
+impl TimingViolation {
+    /// Human-readable form for the frame line.
+    pub fn label(&self) -> String {
+        match self {
+            TimingViolation::ShortFrameGap(chars) => {
+                format!("{:.1} char gap before frame, needs 3.5", chars)
+            }
+            TimingViolation::LongCharGap(chars) => {
+                format!("{:.1} char gap inside frame, max 1.5", chars)
+            }
+        }
+    }
+}
+
+/// A frame with the time it was received, as kept for display.
+#[derive(Debug, Clone)]
+pub struct DecodedFrame {
+    pub timestamp: String,
+    pub frame: Frame,
+    /// Modbus slave address, for colouring and filtering.
+    pub slave: Option<u8>,
+}
+
+impl DecodedFrame {
+    /// Stamp `frame` with the current local time.
+    pub fn new(frame: Frame) -> Self {
+        Self {
+            timestamp: Local::now().format("%H:%M:%S%.3f").to_string(),
+            frame,
+            slave: None,
+        }
+    }
+
+    /// One-line rendering used by the frame pane and the terminal.
+    pub fn line(&self) -> String {
+        let mut line = format!(
+            "[{}] {}  {}",
+            self.timestamp,
+            self.frame.summary,
+            if self.frame.hex {
+                format_payload_hex(&self.frame.payload)
+            } else {
+                format_payload(&self.frame.payload)
+            }
+        );
+        if let Some(error) = &self.frame.error {
+            line.push_str(&format!("  ({})", error));
+        }
+        for violation in &self.frame.violations {
+            line.push_str(&format!("  [{}]", violation.label()));
+        }
+        line
+    }
+}
+
+/// Turns raw RX chunks into frames. Decoders keep partial frames between calls.
+pub trait Decoder {
+    /// Feed a chunk of received bytes, returning any frames completed by it.
+    fn feed(&mut self, data: &[u8]) -> Vec<Frame>;
+
+    /// Feed a chunk whose last byte arrived at `arrived`. Decoders that end
+    /// frames on silence time gaps from this rather than from the clock, so
+    /// data worked off later than it was read still frames as it arrived.
+    fn feed_at(&mut self, data: &[u8], _arrived: Instant) -> Vec<Frame> {
+        self.feed(data)
+    }
+
+    /// Called while no data is arriving, for decoders that end frames on silence.
+    fn poll(&mut self, _now: Instant) -> Vec<Frame> {
+        Vec::new()
+    }
+
+    /// Drop any partially assembled frame.
+    fn reset(&mut self);
+
+    /// The line settings changed; only timing-based decoders care.
+    fn set_line_timing(&mut self, _baud: u32, _char_time: Duration) {}
+}
+
+impl detect::DetectedProtocol {
+    /// The protocol settings that decode this protocol, if a decoder exists for it.
+    pub fn settings(&self) -> Option<(ProtocolMode, CustomFraming)> {
+        match self {
+            detect::DetectedProtocol::Nmea => Some((ProtocolMode::Nmea, CustomFraming::Slip)),
+            detect::DetectedProtocol::Slip => Some((ProtocolMode::Custom, CustomFraming::Slip)),
+            detect::DetectedProtocol::ModbusRtu => {
+                Some((ProtocolMode::ModbusRtu, CustomFraming::Slip))
+            }
+        }
+    }
+}
+
+/// The decoder for `mode`, or `None` when decoding is off. `rule` is used by
+/// `CustomFraming::Rule`, and fails when it cannot frame anything. `baud` and
+/// `char_time` describe the line for decoders that frame on timing;
+/// `rtu_frame_gap` replaces the Modbus RTU 3.5 character gap when set.
+pub fn create_decoder(
+    mode: ProtocolMode,
+    framing: CustomFraming,
+    rule: &framing_rule::FramingRule,
+    baud: u32,
+    char_time: Duration,
+    rtu_frame_gap: Option<Duration>,
+) -> Result<Option<Box<dyn Decoder>>, String> {
+    Ok(match mode {
+        ProtocolMode::None => None,
+        ProtocolMode::Nmea => Some(Box::new(nmea::NmeaDecoder::default())),
+        ProtocolMode::Ubx => Some(Box::new(ubx::GnssDecoder::default())),
+        ProtocolMode::ModbusRtu => Some(Box::new(
+            modbus::RtuDecoder::new(modbus::RtuTiming::new(baud, char_time)).with_frame_gap(rtu_frame_gap),
+        )),
+        ProtocolMode::ModbusAscii => Some(Box::new(modbus_ascii::AsciiDecoder::default())),
+        ProtocolMode::Custom => match framing {
+            CustomFraming::Slip => Some(Box::new(slip::SlipDecoder::default())),
+            CustomFraming::Cobs => Some(Box::new(cobs::CobsDecoder::default())),
+            CustomFraming::StxEtx => Some(Box::new(stx_etx::StxEtxDecoder::default())),
+            CustomFraming::Rule => Some(Box::new(framing_rule::RuleDecoder::new(rule.clone())?)),
+        },
+    })
+}
+
+/// Space-separated uppercase hex bytes.
+pub fn format_payload_hex(payload: &[u8]) -> String {
+    let hex: Vec<String> = payload.iter().map(|b| format!("{:02X}", b)).collect();
+    hex.join(" ")
+}
+
+/// Printable payloads (e.g. NMEA sentences) are shown as text, everything else as hex.
+pub fn format_payload(payload: &[u8]) -> String {
+    if !payload.is_empty() && payload.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        String::from_utf8_lossy(payload).to_string()
+    } else {
+        format_payload_hex(payload)
+    }
+}
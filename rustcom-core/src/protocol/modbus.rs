@@ -0,0 +1,752 @@
+//! Modbus RTU framing on line silence, with frame timing compliance checks
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use super::{checksum, Decoder, Frame, TimingViolation};
+
+/// Largest RTU frame: address, a PDU of up to 253 bytes, and the CRC.
+pub const MAX_ADU_SIZE: usize = 256;
+
+/// How long a request may go unanswered before it counts as a timeout.
+pub const DEFAULT_RESPONSE_TIMEOUT_MS: u64 = 1000;
+
+/// Above this baud rate the spec fixes t1.5 and t3.5 instead of scaling them.
+const FIXED_TIMING_BAUD: u32 = 19200;
+
+/// Register values listed in a read response summary before it is cut short.
+const MAX_LISTED_REGISTERS: usize = 8;
+
+/// Silence limits for one line configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RtuTiming {
+    pub char_time: Duration,
+    /// Longest silence allowed between characters of one frame.
+    pub t1_5: Duration,
+    /// Shortest silence required between frames.
+    pub t3_5: Duration,
+}
+
+impl RtuTiming {
+    /// Limits for `baud`, with `char_time` as the length of one character.
+    pub fn new(baud: u32, char_time: Duration) -> Self {
+        if baud > FIXED_TIMING_BAUD {
+            Self {
+                char_time,
+                t1_5: Duration::from_micros(750),
+                t3_5: Duration::from_micros(1750),
+            }
+        } else {
+            Self {
+                char_time,
+                t1_5: char_time * 3 / 2,
+                t3_5: char_time * 7 / 2,
+            }
+        }
+    }
+
+    /// These limits with the frame gap set by hand, for adapters that batch
+    /// bytes and blur the 3.5 character silence.
+    pub fn with_frame_gap(self, gap: Duration) -> Self {
+        Self {
+            t1_5: self.t1_5.min(gap),
+            t3_5: gap,
+            ..self
+        }
+    }
+
+    fn in_chars(&self, gap: Duration) -> f64 {
+        gap.as_secs_f64() / self.char_time.as_secs_f64()
+    }
+}
+
+fn function_name(code: u8) -> Option<&'static str> {
+    match code {
+        0x01 => Some("Read Coils"),
+        0x02 => Some("Read Discrete Inputs"),
+        0x03 => Some("Read Holding Registers"),
+        0x04 => Some("Read Input Registers"),
+        0x05 => Some("Write Single Coil"),
+        0x06 => Some("Write Single Register"),
+        0x0F => Some("Write Multiple Coils"),
+        0x10 => Some("Write Multiple Registers"),
+        0x17 => Some("Read/Write Multiple Registers"),
+        _ => None,
+    }
+}
+
+fn exception_name(code: u8) -> Option<&'static str> {
+    match code {
+        0x01 => Some("Illegal Function"),
+        0x02 => Some("Illegal Data Address"),
+        0x03 => Some("Illegal Data Value"),
+        0x04 => Some("Server Device Failure"),
+        0x05 => Some("Acknowledge"),
+        0x06 => Some("Server Device Busy"),
+        0x0B => Some("Gateway Target Failed to Respond"),
+        _ => None,
+    }
+}
+
+fn describe_function(code: u8) -> String {
+    match function_name(code) {
+        Some(name) => name.to_string(),
+        None => format!("Function 0x{:02X}", code),
+    }
+}
+
+fn word(bytes: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([bytes[at], bytes[at + 1]])
+}
+
+/// The fields of a common function's request or response. `response` is the
+/// role when the transaction tracker knows it; otherwise the two are told
+/// apart by length. `None` when `data` fits neither.
+fn describe_fields(function: u8, data: &[u8], response: Option<bool>) -> Option<String> {
+    // A response body is its byte count and that many bytes
+    let counted = data.first().is_some_and(|&count| count as usize + 1 == data.len());
+    let request = response != Some(true);
+    let reply = response != Some(false);
+    match function {
+        // Without a role a 4-byte body is read as a request; a 3-byte coil response is rarer
+        0x01..=0x04 if request && data.len() == 4 => {
+            Some(format!("addr=0x{:04X} qty={}", word(data, 0), word(data, 2)))
+        }
+        0x01 | 0x02 if reply && counted => Some(format!("bytes={}", data.len() - 1)),
+        0x03 | 0x04 if reply && counted && data.len() % 2 == 1 => {
+            let values: Vec<String> = data[1..]
+                .chunks(2)
+                .take(MAX_LISTED_REGISTERS)
+                .map(|pair| word(pair, 0).to_string())
+                .collect();
+            let more = if data.len() / 2 > MAX_LISTED_REGISTERS { ",…" } else { "" };
+            Some(format!("bytes={} values={}{}", data.len() - 1, values.join(","), more))
+        }
+        0x05 if data.len() == 4 => {
+            let value = match word(data, 2) {
+                0xFF00 => "ON".to_string(),
+                0x0000 => "OFF".to_string(),
+                other => format!("0x{:04X}", other),
+            };
+            Some(format!("addr=0x{:04X} value={}", word(data, 0), value))
+        }
+        0x06 if data.len() == 4 => Some(format!("addr=0x{:04X} value={}", word(data, 0), word(data, 2))),
+        0x0F | 0x10 if data.len() == 4 => Some(format!("addr=0x{:04X} qty={}", word(data, 0), word(data, 2))),
+        0x0F | 0x10 if data.len() >= 5 && data[4] as usize == data.len() - 5 => Some(format!(
+            "addr=0x{:04X} qty={} bytes={}",
+            word(data, 0),
+            word(data, 2),
+            data[4]
+        )),
+        _ => None,
+    }
+}
+
+/// One-line summary of a CRC-checked frame, e.g. "Slave 1 Read Holding
+/// Registers addr=0x0000 qty=10".
+pub fn summarize(adu: &[u8]) -> String {
+    summarize_message(&adu[..adu.len() - 2])
+}
+
+/// The summary of a checked message: slave address and PDU, with the CRC or
+/// LRC already removed. Needs at least the address and function code.
+pub fn summarize_message(message: &[u8]) -> String {
+    describe_message(message, None)
+}
+
+/// The summary of a checked message once the tracker has given it a role, so
+/// a request and a response of the same length are not confused.
+pub fn summarize_with_role(message: &[u8], role: Role) -> String {
+    describe_message(message, Some(!matches!(role, Role::Request)))
+}
+
+fn describe_message(message: &[u8], response: Option<bool>) -> String {
+    let slave = message[0];
+    let function = message[1];
+    if function & 0x80 != 0 {
+        let exception = message.get(2).copied().unwrap_or(0);
+        format!(
+            "Slave {} exception to {}: {}",
+            slave,
+            describe_function(function & 0x7F),
+            exception_name(exception)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("code 0x{:02X}", exception))
+        )
+    } else {
+        let mut summary = format!("Slave {} {}", slave, describe_function(function));
+        if let Some(fields) = describe_fields(function, &message[2..], response) {
+            summary.push(' ');
+            summary.push_str(&fields);
+        }
+        summary
+    }
+}
+
+/// Splits the RX stream into frames wherever the line was silent for 3.5
+/// characters. Silence is measured from chunk arrival times, assuming the bytes
+/// of one chunk arrived back to back, so adapters that batch data (USB latency
+/// timers) can report gaps that were not on the wire.
+pub struct RtuDecoder {
+    timing: RtuTiming,
+    /// Replaces the 3.5 character frame gap when set.
+    frame_gap: Option<Duration>,
+    buffer: Vec<u8>,
+    violations: Vec<TimingViolation>,
+    last_byte_at: Option<Instant>,
+}
+
+impl RtuDecoder {
+    /// A decoder for a line with the given timing.
+    pub fn new(timing: RtuTiming) -> Self {
+        Self {
+            timing,
+            frame_gap: None,
+            buffer: Vec::new(),
+            violations: Vec::new(),
+            last_byte_at: None,
+        }
+    }
+
+    /// End frames after `gap` of silence instead of 3.5 characters.
+    pub fn with_frame_gap(mut self, gap: Option<Duration>) -> Self {
+        self.frame_gap = gap;
+        self.set_timing(self.timing);
+        self
+    }
+
+    fn set_timing(&mut self, timing: RtuTiming) {
+        self.timing = match self.frame_gap {
+            Some(gap) => timing.with_frame_gap(gap),
+            None => timing,
+        };
+    }
+
+    fn finish_frame(&mut self) -> Option<Frame> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        let adu = std::mem::take(&mut self.buffer);
+        let mut frame = if adu.len() < 4 {
+            Frame::malformed(adu, "shorter than the 4-byte minimum".to_string())
+        } else if !checksum::has_valid_modbus_crc(&adu) {
+            Frame::malformed(adu, "CRC mismatch".to_string())
+        } else {
+            let summary = summarize(&adu);
+            Frame::ok(adu, summary)
+        };
+        frame.violations = std::mem::take(&mut self.violations);
+        Some(frame)
+    }
+}
+
+impl Decoder for RtuDecoder {
+    fn feed(&mut self, data: &[u8]) -> Vec<Frame> {
+        self.feed_at(data, Instant::now())
+    }
+
+    fn feed_at(&mut self, data: &[u8], now: Instant) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        if data.is_empty() {
+            return frames;
+        }
+
+        let first_byte_at = now
+            .checked_sub(self.timing.char_time * (data.len() as u32 - 1))
+            .unwrap_or(now);
+        if let Some(last) = self.last_byte_at {
+            let silence = first_byte_at
+                .saturating_duration_since(last)
+                .saturating_sub(self.timing.char_time);
+            // A gap between t1.5 and t3.5 is ambiguous: if what we have so far
+            // checks out as a frame, treat it as a too-short frame gap.
+            let ends_frame = silence >= self.timing.t3_5
+                || (silence > self.timing.t1_5 && checksum::has_valid_modbus_crc(&self.buffer));
+
+            if !self.buffer.is_empty() && !ends_frame {
+                if silence > self.timing.t1_5 {
+                    self.violations
+                        .push(TimingViolation::LongCharGap(self.timing.in_chars(silence)));
+                }
+            } else {
+                frames.extend(self.finish_frame());
+                if silence < self.timing.t3_5 {
+                    self.violations
+                        .push(TimingViolation::ShortFrameGap(self.timing.in_chars(silence)));
+                }
+            }
+        }
+
+        self.buffer.extend_from_slice(data);
+        self.last_byte_at = Some(now);
+
+        if self.buffer.len() > MAX_ADU_SIZE {
+            let adu = std::mem::take(&mut self.buffer);
+            let mut frame = Frame::malformed(
+                adu,
+                format!("no frame gap within {} bytes", MAX_ADU_SIZE),
+            );
+            frame.violations = std::mem::take(&mut self.violations);
+            frames.push(frame);
+        }
+
+        frames
+    }
+
+    fn poll(&mut self, now: Instant) -> Vec<Frame> {
+        let silent = self
+            .last_byte_at
+            .is_some_and(|last| now.saturating_duration_since(last) >= self.timing.t3_5);
+        if silent {
+            self.finish_frame().into_iter().collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn reset(&mut self) {
+        self.buffer.clear();
+        self.violations.clear();
+        self.last_byte_at = None;
+    }
+
+    fn set_line_timing(&mut self, baud: u32, char_time: Duration) {
+        self.set_timing(RtuTiming::new(baud, char_time));
+    }
+}
+
+/// Frame and violation counts for the statistics panel.
+#[derive(Debug, Default)]
+pub struct TimingStats {
+    pub frames: u64,
+    pub short_frame_gaps: u64,
+    pub long_char_gaps: u64,
+}
+
+impl TimingStats {
+    /// Count a finished frame and its violations.
+    pub fn record(&mut self, frame: &Frame) {
+        self.frames += 1;
+        for violation in &frame.violations {
+            match violation {
+                TimingViolation::ShortFrameGap(_) => self.short_frame_gaps += 1,
+                TimingViolation::LongCharGap(_) => self.long_char_gaps += 1,
+            }
+        }
+    }
+
+    /// All violations of either kind.
+    pub fn violations(&self) -> u64 {
+        self.short_frame_gaps + self.long_char_gaps
+    }
+}
+
+/// Where a frame sits in a request/response exchange.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Request,
+    /// A normal response, with the time since its request.
+    Response(Duration),
+    /// An exception response; the latency is known when the request was seen.
+    Exception(Option<Duration>),
+}
+
+impl Role {
+    /// Prefix for the frame summary, e.g. "→" or "← 12.5 ms".
+    pub fn label(&self) -> String {
+        match self {
+            Role::Request => "→".to_string(),
+            Role::Response(latency) | Role::Exception(Some(latency)) => {
+                format!("← {:.1} ms", crate::burst::ms(*latency))
+            }
+            Role::Exception(None) => "←".to_string(),
+        }
+    }
+}
+
+/// Request/response counts for one slave address.
+#[derive(Debug, Clone, Default)]
+pub struct SlaveStats {
+    pub requests: u64,
+    pub responses: u64,
+    pub exceptions: u64,
+    pub timeouts: u64,
+    latency_total: Duration,
+    latency_samples: u32,
+}
+
+impl SlaveStats {
+    fn add_latency(&mut self, latency: Duration) {
+        self.latency_total += latency;
+        self.latency_samples += 1;
+    }
+
+    /// Mean response time over the responses matched to a request.
+    pub fn mean_latency(&self) -> Option<Duration> {
+        (self.latency_samples > 0).then(|| self.latency_total / self.latency_samples)
+    }
+}
+
+/// Pairs requests with responses by (slave, function) and keeps per-slave
+/// counts. A frame for a (slave, function) with a request outstanding is the
+/// response; anything else is a new request. Exception responses are always
+/// responses, matched against the request when there is one.
+pub struct TransactionTracker {
+    pub timeout: Duration,
+    pending: HashMap<(u8, u8), Instant>,
+    pub slaves: BTreeMap<u8, SlaveStats>,
+}
+
+impl TransactionTracker {
+    /// A tracker that counts requests unanswered after `timeout` as timeouts.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            pending: HashMap::new(),
+            slaves: BTreeMap::new(),
+        }
+    }
+
+    /// Classify a CRC-valid frame received at `at`.
+    pub fn record(&mut self, adu: &[u8], at: Instant) -> Option<Role> {
+        let (&slave, &function) = (adu.first()?, adu.get(1)?);
+        self.expire(at);
+
+        let request = self.pending.remove(&(slave, function & 0x7F));
+        let stats = self.slaves.entry(slave).or_default();
+        let latency = request.map(|sent| at.saturating_duration_since(sent));
+        if let Some(latency) = latency {
+            stats.add_latency(latency);
+        }
+
+        let role = if function & 0x80 != 0 {
+            stats.exceptions += 1;
+            Role::Exception(latency)
+        } else if let Some(latency) = latency {
+            stats.responses += 1;
+            Role::Response(latency)
+        } else {
+            stats.requests += 1;
+            self.pending.insert((slave, function), at);
+            Role::Request
+        };
+        Some(role)
+    }
+
+    /// Count requests older than the timeout as unanswered.
+    pub fn expire(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        let slaves = &mut self.slaves;
+        self.pending.retain(|(slave, _), sent| {
+            let expired = now.saturating_duration_since(*sent) > timeout;
+            if expired {
+                slaves.entry(*slave).or_default().timeouts += 1;
+            }
+            !expired
+        });
+    }
+
+    /// Forget outstanding requests and all statistics.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.slaves.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 9600 8N1: 10 bits per character.
+    const CHAR: Duration = Duration::from_nanos(1_041_667);
+
+    fn with_crc(body: &[u8]) -> Vec<u8> {
+        let mut adu = body.to_vec();
+        adu.extend_from_slice(&checksum::crc16_modbus(body).to_le_bytes());
+        adu
+    }
+
+    fn request() -> Vec<u8> {
+        with_crc(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A])
+    }
+
+    fn decoder() -> RtuDecoder {
+        RtuDecoder::new(RtuTiming::new(9600, CHAR))
+    }
+
+    /// Arrival time of a chunk sent `silence` after the previous chunk ended.
+    fn after(previous: Instant, silence: Duration, len: usize) -> Instant {
+        previous + silence + CHAR * len as u32
+    }
+
+    #[test]
+    fn timing_scales_with_char_time_up_to_19200() {
+        let slow = RtuTiming::new(9600, CHAR);
+        assert_eq!(slow.t1_5, CHAR * 3 / 2);
+        assert_eq!(slow.t3_5, CHAR * 7 / 2);
+
+        let fast = RtuTiming::new(115200, Duration::from_nanos(86_806));
+        assert_eq!(fast.t1_5, Duration::from_micros(750));
+        assert_eq!(fast.t3_5, Duration::from_micros(1750));
+    }
+
+    #[test]
+    fn well_spaced_frames_have_no_violations() {
+        let mut decoder = decoder();
+        let start = Instant::now();
+        let frame = request();
+
+        assert!(decoder.feed_at(&frame, start).is_empty());
+        let second = after(start, CHAR * 10, frame.len());
+        let frames = decoder.feed_at(&frame, second);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].summary, "Slave 1 Read Holding Registers addr=0x0000 qty=10");
+        assert!(frames[0].error.is_none());
+        assert!(frames[0].violations.is_empty());
+
+        let frames = decoder.poll(second + CHAR * 4);
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].violations.is_empty());
+    }
+
+    #[test]
+    fn a_boxed_decoder_times_frames_by_arrival() {
+        // As the app holds it: data worked off at once still frames as it arrived
+        let mut decoder: Box<dyn Decoder> = Box::new(decoder());
+        let start = Instant::now();
+        let frame = request();
+        assert!(decoder.feed_at(&frame, start).is_empty());
+        assert_eq!(decoder.feed_at(&frame, after(start, CHAR * 10, frame.len())).len(), 1);
+    }
+
+    #[test]
+    fn poll_waits_for_three_and_a_half_characters() {
+        let mut decoder = decoder();
+        let start = Instant::now();
+        decoder.feed_at(&request(), start);
+        assert!(decoder.poll(start + CHAR * 3).is_empty());
+        assert_eq!(decoder.poll(start + CHAR * 4).len(), 1);
+    }
+
+    #[test]
+    fn short_silence_between_frames_is_flagged() {
+        let mut decoder = decoder();
+        let start = Instant::now();
+        let frame = request();
+
+        decoder.feed_at(&frame, start);
+        let second = after(start, CHAR * 2, frame.len());
+        let frames = decoder.feed_at(&frame, second);
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].violations.is_empty());
+
+        let frames = decoder.poll(second + CHAR * 4);
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].error.is_none());
+        match frames[0].violations[..] {
+            [TimingViolation::ShortFrameGap(chars)] => assert!((chars - 2.0).abs() < 0.01),
+            ref other => panic!("unexpected violations {:?}", other),
+        }
+    }
+
+    #[test]
+    fn long_gap_inside_frame_is_flagged() {
+        let mut decoder = decoder();
+        let start = Instant::now();
+        let frame = request();
+
+        decoder.feed_at(&frame[..3], start);
+        let rest = after(start, CHAR * 5 / 2, frame.len() - 3);
+        assert!(decoder.feed_at(&frame[3..], rest).is_empty());
+
+        let frames = decoder.poll(rest + CHAR * 4);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, frame);
+        assert!(frames[0].error.is_none());
+        match frames[0].violations[..] {
+            [TimingViolation::LongCharGap(chars)] => assert!((chars - 2.5).abs() < 0.01),
+            ref other => panic!("unexpected violations {:?}", other),
+        }
+    }
+
+    #[test]
+    fn small_gap_inside_frame_is_fine() {
+        let mut decoder = decoder();
+        let start = Instant::now();
+        let frame = request();
+
+        decoder.feed_at(&frame[..3], start);
+        let rest = after(start, CHAR, frame.len() - 3);
+        decoder.feed_at(&frame[3..], rest);
+        let frames = decoder.poll(rest + CHAR * 4);
+        assert!(frames[0].violations.is_empty());
+    }
+
+    #[test]
+    fn bad_crc_and_exceptions() {
+        let mut decoder = decoder();
+        let start = Instant::now();
+        let mut corrupt = request();
+        corrupt[7] ^= 0xFF;
+        decoder.feed_at(&corrupt, start);
+        let frames = decoder.poll(start + CHAR * 4);
+        assert_eq!(frames[0].error.as_deref(), Some("CRC mismatch"));
+
+        let exception = with_crc(&[0x11, 0x83, 0x02]);
+        assert_eq!(
+            summarize(&exception),
+            "Slave 17 exception to Read Holding Registers: Illegal Data Address"
+        );
+    }
+
+    #[test]
+    fn summaries_decode_the_common_functions() {
+        let cases: [(&[u8], &str); 12] = [
+            (&[1, 0x01, 0x00, 0x13, 0x00, 0x25], "Slave 1 Read Coils addr=0x0013 qty=37"),
+            (&[1, 0x01, 0x05, 0xCD, 0x6B, 0xB2, 0x0E, 0x1B], "Slave 1 Read Coils bytes=5"),
+            (&[2, 0x04, 0x00, 0x08, 0x00, 0x01], "Slave 2 Read Input Registers addr=0x0008 qty=1"),
+            (&[1, 0x03, 0x04, 0x00, 0x01, 0x01, 0x00], "Slave 1 Read Holding Registers bytes=4 values=1,256"),
+            (&[1, 0x05, 0x00, 0xAC, 0xFF, 0x00], "Slave 1 Write Single Coil addr=0x00AC value=ON"),
+            (&[1, 0x06, 0x00, 0x01, 0x00, 0x03], "Slave 1 Write Single Register addr=0x0001 value=3"),
+            (
+                &[1, 0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01],
+                "Slave 1 Write Multiple Coils addr=0x0013 qty=10 bytes=2",
+            ),
+            (&[1, 0x0F, 0x00, 0x13, 0x00, 0x0A], "Slave 1 Write Multiple Coils addr=0x0013 qty=10"),
+            (
+                &[1, 0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02],
+                "Slave 1 Write Multiple Registers addr=0x0001 qty=2 bytes=4",
+            ),
+            // A body that fits no layout keeps just the name
+            (&[1, 0x03, 0x09, 0x00], "Slave 1 Read Holding Registers"),
+            (&[1, 0x41, 0x00], "Slave 1 Function 0x41"),
+            (&[1, 0x03], "Slave 1 Read Holding Registers"),
+        ];
+        for (body, expected) in cases {
+            assert_eq!(summarize(&with_crc(body)), expected);
+        }
+
+        let mut long = vec![1, 0x03, 20];
+        long.extend((0..10u16).flat_map(|v| v.to_be_bytes()));
+        assert!(summarize(&with_crc(&long)).ends_with("values=0,1,2,3,4,5,6,7,…"));
+    }
+
+    #[test]
+    fn a_known_role_settles_a_four_byte_body() {
+        // Three coil bytes after the count look like a request by length alone
+        let body = [1, 0x01, 0x03, 0xCD, 0x6B, 0x05];
+        assert_eq!(summarize_message(&body), "Slave 1 Read Coils addr=0x03CD qty=27397");
+        let response = Role::Response(Duration::from_millis(5));
+        assert_eq!(summarize_with_role(&body, response), "Slave 1 Read Coils bytes=3");
+
+        let request = [1, 0x02, 0x00, 0x00, 0x00, 0x03];
+        assert_eq!(summarize_with_role(&request, Role::Request), "Slave 1 Read Discrete Inputs addr=0x0000 qty=3");
+        // A counted body sent as a request fits no request layout
+        assert_eq!(summarize_with_role(&body[..5], Role::Request), "Slave 1 Read Coils");
+    }
+
+    #[test]
+    fn frame_gap_can_be_set_by_hand() {
+        let gap = Duration::from_millis(20);
+        let mut decoder = decoder().with_frame_gap(Some(gap));
+        let start = Instant::now();
+        // An adapter that holds bytes for 16 ms would split frames at 3.5 characters
+        decoder.feed_at(&request()[..3], start);
+        assert!(decoder.feed_at(&request()[3..], start + Duration::from_millis(16)).is_empty());
+        assert!(decoder.poll(start + Duration::from_millis(30)).is_empty());
+        let frames = decoder.poll(start + Duration::from_millis(36));
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].error.is_none());
+
+        // The override outlives a change of line settings
+        decoder.set_line_timing(9600, CHAR);
+        assert_eq!(decoder.timing.t3_5, gap);
+        assert_eq!(decoder.timing.t1_5, CHAR * 3 / 2);
+    }
+
+    #[test]
+    fn stats_count_violations_by_kind() {
+        let mut stats = TimingStats::default();
+        let mut frame = Frame::ok(request(), "x".to_string());
+        stats.record(&frame);
+        frame.violations = vec![
+            TimingViolation::ShortFrameGap(1.0),
+            TimingViolation::LongCharGap(2.0),
+            TimingViolation::LongCharGap(3.0),
+        ];
+        stats.record(&frame);
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.short_frame_gaps, 1);
+        assert_eq!(stats.long_char_gaps, 2);
+        assert_eq!(stats.violations(), 3);
+    }
+
+    fn read_holding(slave: u8) -> Vec<u8> {
+        with_crc(&[slave, 0x03, 0x00, 0x00, 0x00, 0x02])
+    }
+
+    fn holding_reply(slave: u8) -> Vec<u8> {
+        with_crc(&[slave, 0x03, 0x04, 0x00, 0x01, 0x00, 0x02])
+    }
+
+    #[test]
+    fn pairs_interleaved_traffic_to_several_slaves() {
+        let mut tracker = TransactionTracker::new(Duration::from_millis(100));
+        let t = Instant::now();
+        let ms = Duration::from_millis;
+
+        assert_eq!(tracker.record(&read_holding(1), t), Some(Role::Request));
+        assert_eq!(tracker.record(&read_holding(2), t + ms(5)), Some(Role::Request));
+        let write = with_crc(&[1, 0x06, 0x00, 0x10, 0x00, 0x01]);
+        assert_eq!(tracker.record(&write, t + ms(8)), Some(Role::Request));
+
+        // Replies arrive out of order and are matched by slave and function
+        assert_eq!(
+            tracker.record(&holding_reply(2), t + ms(20)),
+            Some(Role::Response(ms(15)))
+        );
+        assert_eq!(tracker.record(&write, t + ms(30)), Some(Role::Response(ms(22))));
+        let exception = with_crc(&[1, 0x83, 0x02]);
+        assert_eq!(
+            tracker.record(&exception, t + ms(40)),
+            Some(Role::Exception(Some(ms(40))))
+        );
+
+        let one = &tracker.slaves[&1];
+        assert_eq!((one.requests, one.responses, one.exceptions, one.timeouts), (2, 1, 1, 0));
+        assert_eq!(one.mean_latency(), Some(ms(31)));
+        let two = &tracker.slaves[&2];
+        assert_eq!((two.requests, two.responses, two.exceptions, two.timeouts), (1, 1, 0, 0));
+    }
+
+    #[test]
+    fn unanswered_requests_time_out() {
+        let mut tracker = TransactionTracker::new(Duration::from_millis(100));
+        let t = Instant::now();
+        let ms = Duration::from_millis;
+
+        tracker.record(&read_holding(3), t);
+        tracker.record(&read_holding(4), t + ms(10));
+        tracker.record(&holding_reply(4), t + ms(50));
+        tracker.expire(t + ms(150));
+        assert_eq!(tracker.slaves[&3].timeouts, 1);
+        assert_eq!(tracker.slaves[&4].timeouts, 0);
+
+        // A reply after the window is a new request, not a late response
+        assert_eq!(tracker.record(&holding_reply(3), t + ms(160)), Some(Role::Request));
+        tracker.expire(t + ms(300));
+        assert_eq!(tracker.slaves[&3].timeouts, 2);
+    }
+
+    #[test]
+    fn exception_without_request_is_still_counted() {
+        let mut tracker = TransactionTracker::new(Duration::from_millis(100));
+        let exception = with_crc(&[9, 0x81, 0x01]);
+        assert_eq!(
+            tracker.record(&exception, Instant::now()),
+            Some(Role::Exception(None))
+        );
+        assert_eq!(tracker.slaves[&9].exceptions, 1);
+        assert_eq!(tracker.slaves[&9].requests, 0);
+    }
+}
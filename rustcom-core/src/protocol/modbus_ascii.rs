@@ -0,0 +1,184 @@
+//! Modbus ASCII framing: `:`, the message as pairs of hex digits, an LRC pair
+//! and CRLF. Frames are summarised the same way as Modbus RTU.
+
+use super::{checksum, modbus, Decoder, Frame};
+
+/// Hex digits between `:` and CRLF in the largest frame: address, a PDU of up
+/// to 253 bytes and the LRC, two digits each.
+pub const MAX_HEX_DIGITS: usize = 2 * (1 + 253 + 1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// Waiting for a `:`; anything else is ignored.
+    Idle,
+    /// Collecting hex digits.
+    Frame,
+    /// Got the CR, expecting the LF.
+    Cr,
+}
+
+/// The frame text with every digit pair as a byte, or why it is not hex.
+fn parse_hex(digits: &[u8]) -> Result<Vec<u8>, String> {
+    if let Some(at) = digits.iter().position(|b| !b.is_ascii_hexdigit()) {
+        return Err(format!(
+            "invalid hex character {:?} at position {}",
+            char::from(digits[at]),
+            at + 1
+        ));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits ({})", digits.len()));
+    }
+    let nibble = |b: u8| (b as char).to_digit(16).unwrap_or(0) as u8;
+    Ok(digits.chunks(2).map(|pair| (nibble(pair[0]) << 4) | nibble(pair[1])).collect())
+}
+
+/// Splits the RX stream on `:` and CRLF and checks each frame's LRC. A frame
+/// that is cut short (a new `:`, a lone CR or LF) or fails to parse is
+/// reported as malformed with its text as the payload.
+pub struct AsciiDecoder {
+    state: State,
+    digits: Vec<u8>,
+}
+
+impl Default for AsciiDecoder {
+    fn default() -> Self {
+        Self {
+            state: State::Idle,
+            digits: Vec::new(),
+        }
+    }
+}
+
+impl AsciiDecoder {
+    /// Drop the frame so far as malformed and go back to waiting for a `:`.
+    fn abandon(&mut self, reason: &str) -> Frame {
+        self.state = State::Idle;
+        Frame::malformed(std::mem::take(&mut self.digits), reason.to_string())
+    }
+
+    fn finish_frame(&mut self) -> Frame {
+        self.state = State::Idle;
+        let digits = std::mem::take(&mut self.digits);
+        let bytes = match parse_hex(&digits) {
+            Ok(bytes) => bytes,
+            Err(reason) => return Frame::malformed(digits, reason),
+        };
+        if bytes.len() < 3 {
+            return Frame::malformed(digits, "shorter than the 3-byte minimum".to_string());
+        }
+        let (message, lrc) = bytes.split_at(bytes.len() - 1);
+        let expected = checksum::lrc(message);
+        if lrc[0] != expected {
+            let error = format!("LRC mismatch: expected {:02X}, got {:02X}", expected, lrc[0]);
+            return Frame::malformed(bytes, error);
+        }
+        let summary = modbus::summarize_message(message);
+        Frame::ok(bytes, summary)
+    }
+}
+
+impl Decoder for AsciiDecoder {
+    fn feed(&mut self, data: &[u8]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        for &byte in data {
+            match (self.state, byte) {
+                (_, b':') => {
+                    if self.state != State::Idle {
+                        frames.push(self.abandon("missing CRLF before the next ':'"));
+                    }
+                    self.state = State::Frame;
+                }
+                (State::Idle, _) => {}
+                (State::Frame, b'\r') => self.state = State::Cr,
+                (State::Frame, b'\n') => frames.push(self.abandon("LF without CR")),
+                (State::Frame, _) if self.digits.len() >= MAX_HEX_DIGITS => {
+                    let reason = format!("no CRLF within {} characters", MAX_HEX_DIGITS);
+                    frames.push(self.abandon(&reason));
+                }
+                (State::Frame, _) => self.digits.push(byte),
+                (State::Cr, b'\n') => frames.push(self.finish_frame()),
+                (State::Cr, _) => frames.push(self.abandon("CR without LF")),
+            }
+        }
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.state = State::Idle;
+        self.digits.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(chunks: &[&[u8]]) -> Vec<Frame> {
+        let mut decoder = AsciiDecoder::default();
+        chunks.iter().flat_map(|chunk| decoder.feed(chunk)).collect()
+    }
+
+    #[test]
+    fn decodes_captured_frames_split_across_reads() {
+        // A read of two holding registers and its answer, then a coil write
+        let frames = feed_all(&[
+            b":0103000000",
+            b"02FA\r\n:0103040064003262\r",
+            b"\n\r\n:01050000FF00FB\r\n",
+        ]);
+        let summaries: Vec<&str> = frames.iter().map(|f| f.summary.as_str()).collect();
+        assert_eq!(
+            summaries,
+            [
+                "Slave 1 Read Holding Registers addr=0x0000 qty=2",
+                "Slave 1 Read Holding Registers bytes=4 values=100,50",
+                "Slave 1 Write Single Coil addr=0x0000 value=ON",
+            ]
+        );
+        assert!(frames.iter().all(|f| f.error.is_none()));
+        assert_eq!(frames[0].payload, [0x01, 0x03, 0x00, 0x00, 0x00, 0x02, 0xFA]);
+
+        // Lowercase digits and an exception response
+        let frames = feed_all(&[b":0183027a\r\n"]);
+        assert_eq!(frames[0].summary, "Slave 1 exception to Read Holding Registers: Illegal Data Address");
+    }
+
+    #[test]
+    fn malformed_frames_say_why() {
+        let errors: Vec<String> = feed_all(&[
+            b":010300000002FB\r\n",
+            b":01030000002FA\r\n",
+            b":0103G0000002FA\r\n",
+            b":0103000000",
+            b":010300000002FA\n",
+            b":010300000002FA\rx",
+            b":01FE\r\n",
+        ])
+        .into_iter()
+        .map(|f| f.error.unwrap())
+        .collect();
+        assert_eq!(
+            errors,
+            [
+                "LRC mismatch: expected FA, got FB",
+                "odd number of hex digits (13)",
+                "invalid hex character 'G' at position 5",
+                "missing CRLF before the next ':'",
+                "LF without CR",
+                "CR without LF",
+                "shorter than the 3-byte minimum",
+            ]
+        );
+    }
+
+    #[test]
+    fn reset_drops_a_partial_frame() {
+        let mut decoder = AsciiDecoder::default();
+        assert!(decoder.feed(b"noise :0103").is_empty());
+        decoder.reset();
+        let frames = decoder.feed(b"00000002FA\r\n:010300000002FA\r\n");
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].error.is_none());
+    }
+}
@@ -0,0 +1,282 @@
+//! NMEA 0183 sentence validation, field breakdown of common sentences and a
+//! framer for NMEA-only streams
+
+use super::{Decoder, Frame};
+use crate::metrics;
+
+/// Longest sentence accepted before the framer gives up waiting for CR/LF.
+/// The standard allows 82 characters; some receivers emit longer proprietary ones.
+pub const MAX_SENTENCE_LEN: usize = 128;
+
+/// XOR of the bytes between `$` and `*`.
+pub fn checksum(body: &[u8]) -> u8 {
+    body.iter().fold(0, |acc, b| acc ^ b)
+}
+
+/// Validate a sentence of the form `$BODY*hh` (line terminator already stripped).
+/// Returns the sentence body (talker + type + fields) on success.
+pub fn verify_sentence(sentence: &[u8]) -> Result<&[u8], String> {
+    if sentence.first() != Some(&b'$') && sentence.first() != Some(&b'!') {
+        return Err("missing '$' start".to_string());
+    }
+
+    let Some(star) = sentence.iter().rposition(|&b| b == b'*') else {
+        return Err("missing '*' checksum delimiter".to_string());
+    };
+
+    let body = &sentence[1..star];
+    let hex = &sentence[star + 1..];
+    if hex.len() != 2 {
+        return Err("checksum must be two hex digits".to_string());
+    }
+
+    let expected = std::str::from_utf8(hex)
+        .ok()
+        .and_then(|h| u8::from_str_radix(h, 16).ok())
+        .ok_or_else(|| "checksum is not hex".to_string())?;
+
+    let actual = checksum(body);
+    if actual != expected {
+        return Err(format!(
+            "checksum mismatch: got {:02X}, expected {:02X}",
+            actual, expected
+        ));
+    }
+
+    Ok(body)
+}
+
+/// Talker + sentence type, e.g. "GPGGA" from "GPGGA,123519,...".
+pub fn sentence_id(body: &[u8]) -> String {
+    let end = body.iter().position(|&b| b == b',').unwrap_or(body.len());
+    String::from_utf8_lossy(&body[..end]).to_string()
+}
+
+/// `ddmm.mmmm` (or `dddmm.mmmm`) and its hemisphere as signed decimal degrees.
+fn coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    let raw: f64 = value.parse().ok()?;
+    let degrees = (raw / 100.0).trunc() + (raw % 100.0) / 60.0;
+    Some(if matches!(hemisphere, "S" | "W") { -degrees } else { degrees })
+}
+
+/// `lat=48.11730 lon=11.51667`, or nothing while there is no position.
+fn position(fields: &[&str], at: usize) -> Option<String> {
+    let lat = coordinate(fields.get(at)?, fields.get(at + 1)?)?;
+    let lon = coordinate(fields.get(at + 2)?, fields.get(at + 3)?)?;
+    Some(format!("lat={:.5} lon={:.5}", lat, lon))
+}
+
+/// A satellite count without its leading zeros.
+fn count(value: &str) -> String {
+    value.parse::<u32>().map(|n| n.to_string()).unwrap_or_else(|_| value.to_string())
+}
+
+fn fix_quality(code: &str) -> &str {
+    match code {
+        "0" => "none",
+        "1" => "GPS",
+        "2" => "DGPS",
+        "3" => "PPS",
+        "4" => "RTK fixed",
+        "5" => "RTK float",
+        "6" => "estimated",
+        "7" => "manual",
+        "8" => "simulated",
+        other => other,
+    }
+}
+
+/// The talker (`GP`, `GN`, or `P` for proprietary) and the sentence type.
+fn split_id(id: &str) -> (&str, &str) {
+    let at = if id.starts_with('P') { 1 } else { 2.min(id.len()) };
+    id.split_at(at)
+}
+
+/// A one-line summary of a checked sentence body: the fix, position, speed and
+/// satellites for GGA, RMC, GSV and VTG, or the talker, type and raw fields.
+pub fn describe(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(body);
+    let fields: Vec<&str> = text.split(',').collect();
+    let id = fields[0];
+    let (talker, kind) = split_id(id);
+    let field = |at: usize| fields.get(at).copied().unwrap_or("");
+    let mut parts = vec![id.to_string()];
+    match kind {
+        "GGA" => {
+            parts.push(format!("fix={}", fix_quality(field(6))));
+            if !field(7).is_empty() {
+                parts.push(format!("sats={}", count(field(7))));
+            }
+            parts.extend(position(&fields, 2));
+            if !field(9).is_empty() {
+                parts.push(format!("alt={} m", field(9)));
+            }
+        }
+        "RMC" => {
+            parts.push(if field(2) == "A" { "status=valid" } else { "status=void" }.to_string());
+            parts.extend(position(&fields, 3));
+            if !field(7).is_empty() {
+                parts.push(format!("speed={} kn", field(7)));
+            }
+        }
+        "GSV" => {
+            parts.push(format!("{}/{}", field(2), field(1)));
+            parts.push(format!("sats in view={}", count(field(3))));
+        }
+        "VTG" => {
+            if !field(1).is_empty() {
+                parts.push(format!("course={}", field(1)));
+            }
+            if !field(7).is_empty() {
+                parts.push(format!("speed={} km/h", field(7)));
+            }
+        }
+        _ => {
+            parts[0] = format!("talker={} type={}", talker, kind);
+            parts.push(format!("fields={}", fields[1..].join(",")));
+        }
+    }
+    parts.join(" ")
+}
+
+/// A frame for a sentence with its line terminator stripped.
+pub fn sentence_frame(line: &[u8]) -> Frame {
+    match verify_sentence(line) {
+        Ok(body) => Frame::ok(line.to_vec(), format!("NMEA {}", describe(body))),
+        Err(e) => Frame::malformed(line.to_vec(), format!("NMEA {}", e)),
+    }
+}
+
+/// Splits an NMEA-only stream into sentences. One cut short by the next `$`
+/// or `!`, or longer than `MAX_SENTENCE_LEN`, is reported as truncated;
+/// bytes between sentences are skipped.
+#[derive(Default)]
+pub struct NmeaDecoder {
+    line: Vec<u8>,
+}
+
+impl Decoder for NmeaDecoder {
+    fn feed(&mut self, data: &[u8]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        for &byte in data {
+            match byte {
+                b'$' | b'!' => {
+                    if !self.line.is_empty() {
+                        let partial = std::mem::take(&mut self.line);
+                        frames.push(Frame::malformed(partial, "NMEA sentence truncated".to_string()));
+                    }
+                    self.line.push(byte);
+                }
+                _ if self.line.is_empty() => {}
+                b'\r' | b'\n' => frames.push(sentence_frame(&std::mem::take(&mut self.line))),
+                _ if self.line.len() >= MAX_SENTENCE_LEN => {
+                    let partial = std::mem::take(&mut self.line);
+                    frames.push(Frame::malformed(partial, "NMEA sentence truncated".to_string()));
+                }
+                _ => self.line.push(byte),
+            }
+        }
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+    }
+}
+
+/// Sentence counts for the protocol panel.
+#[derive(Debug, Default)]
+pub struct NmeaStats {
+    pub sentences: u64,
+    pub checksum_errors: u64,
+    /// Truncated or otherwise unreadable sentences.
+    pub malformed: u64,
+}
+
+impl NmeaStats {
+    pub fn record(&mut self, frame: &Frame) {
+        self.sentences += 1;
+        match &frame.error {
+            Some(error) if metrics::is_check_failure(error) => self.checksum_errors += 1,
+            Some(_) => self.malformed += 1,
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_reference_sentence() {
+        let sentence = b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+        let body = verify_sentence(sentence).unwrap();
+        assert_eq!(sentence_id(body), "GPGGA");
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        let sentence = b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*48";
+        assert!(verify_sentence(sentence).unwrap_err().contains("mismatch"));
+    }
+
+    #[test]
+    fn rejects_missing_checksum() {
+        assert!(verify_sentence(b"$GPGGA,123519").is_err());
+        assert!(verify_sentence(b"$GPGGA,123519*4").is_err());
+        assert!(verify_sentence(b"GPGGA,123519*47").is_err());
+    }
+
+    fn summaries(frames: &[Frame]) -> Vec<String> {
+        frames.iter().map(|f| f.summary.clone()).collect()
+    }
+
+    const STREAM: &[u8] = b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n\
+        $GPRMC,123519,A,4807.038,N,01131.000,W,022.4,084.4,230394,003.1,W*78\r\n\
+        $GPGSV,2,1,08,01,40,083,46,02,17,308,41,12,07,344,39,14,22,228,45*75\r\n\
+        $GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48\r\n\
+        $GPZDA,201530.00,04,07,2002,00,00*60\r\n";
+
+    #[test]
+    fn common_sentences_are_broken_down() {
+        let frames = NmeaDecoder::default().feed(STREAM);
+        assert!(frames.iter().all(|f| f.error.is_none()), "{:?}", frames);
+        assert_eq!(
+            summaries(&frames),
+            [
+                "NMEA GPGGA fix=GPS sats=8 lat=48.11730 lon=11.51667 alt=545.4 m",
+                "NMEA GPRMC status=valid lat=48.11730 lon=-11.51667 speed=022.4 kn",
+                "NMEA GPGSV 1/2 sats in view=8",
+                "NMEA GPVTG course=054.7 speed=010.2 km/h",
+                "NMEA talker=GP type=ZDA fields=201530.00,04,07,2002,00,00",
+            ]
+        );
+        // No fix yet: empty position fields are left out
+        let frames = NmeaDecoder::default().feed(b"$GPGGA,,,,,,0,00,,,M,,M,,*66\r\n");
+        assert_eq!(frames[0].summary, "NMEA GPGGA fix=none sats=0");
+    }
+
+    #[test]
+    fn sentences_split_across_reads_and_truncated_ones() {
+        let expected = summaries(&NmeaDecoder::default().feed(STREAM));
+        for chunk_size in [1, 3, 17, 64] {
+            let mut decoder = NmeaDecoder::default();
+            let frames: Vec<Frame> = STREAM.chunks(chunk_size).flat_map(|chunk| decoder.feed(chunk)).collect();
+            assert_eq!(summaries(&frames), expected, "chunk size {}", chunk_size);
+        }
+
+        let mut decoder = NmeaDecoder::default();
+        let mut stats = NmeaStats::default();
+        let mut frames = decoder.feed(b"$GPGGA,1235$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*49\r\n");
+        frames.extend(decoder.feed(b"$"));
+        frames.extend(decoder.feed(&[b'A'; MAX_SENTENCE_LEN]));
+        let errors: Vec<&str> = frames.iter().map(|f| f.error.as_deref().unwrap()).collect();
+        assert_eq!(
+            errors,
+            ["NMEA sentence truncated", "NMEA checksum mismatch: got 48, expected 49", "NMEA sentence truncated"]
+        );
+        frames.iter().for_each(|f| stats.record(f));
+        assert_eq!((stats.sentences, stats.checksum_errors, stats.malformed), (3, 1, 2));
+    }
+}
@@ -0,0 +1,199 @@
+//! SLIP (RFC 1055) framing
+
+use super::{Decoder, Frame, MAX_FRAME_SIZE};
+
+/// Frame delimiter.
+pub const END: u8 = 0xC0;
+/// Escape byte.
+pub const ESC: u8 = 0xDB;
+/// Escaped END: `ESC ESC_END` stands for an END byte in the payload.
+pub const ESC_END: u8 = 0xDC;
+/// Escaped ESC: `ESC ESC_ESC` stands for an ESC byte in the payload.
+pub const ESC_ESC: u8 = 0xDD;
+
+/// Wrap a payload in SLIP delimiters, escaping END and ESC bytes.
+pub fn encode(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 2);
+    out.push(END);
+    for &byte in payload {
+        match byte {
+            END => out.extend_from_slice(&[ESC, ESC_END]),
+            ESC => out.extend_from_slice(&[ESC, ESC_ESC]),
+            _ => out.push(byte),
+        }
+    }
+    out.push(END);
+    out
+}
+
+/// Splits the stream on END bytes and unescapes each SLIP frame.
+#[derive(Default)]
+pub struct SlipDecoder {
+    buffer: Vec<u8>,
+    escaping: bool,
+    error: Option<String>,
+}
+
+impl SlipDecoder {
+    fn finish_frame(&mut self) -> Option<Frame> {
+        let payload = std::mem::take(&mut self.buffer);
+        let error = self.error.take();
+        if self.escaping {
+            self.escaping = false;
+            let payload_len = payload.len();
+            return Some(Frame::malformed(
+                payload,
+                format!("frame ends with dangling ESC after {} bytes", payload_len),
+            ));
+        }
+        match error {
+            Some(error) => Some(Frame::malformed(payload, error)),
+            None if payload.is_empty() => None,
+            None => {
+                let summary = format!("{} bytes", payload.len());
+                Some(Frame::ok(payload, summary))
+            }
+        }
+    }
+}
+
+impl Decoder for SlipDecoder {
+    fn feed(&mut self, data: &[u8]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+
+        for &byte in data {
+            if byte == END {
+                if let Some(frame) = self.finish_frame() {
+                    frames.push(frame);
+                }
+                continue;
+            }
+
+            if self.escaping {
+                self.escaping = false;
+                match byte {
+                    ESC_END => self.buffer.push(END),
+                    ESC_ESC => self.buffer.push(ESC),
+                    other => {
+                        // Keep the byte so nothing is hidden, but flag the frame
+                        if self.error.is_none() {
+                            self.error = Some(format!(
+                                "invalid escape 0xDB 0x{:02X} at offset {}",
+                                other,
+                                self.buffer.len()
+                            ));
+                        }
+                        self.buffer.push(other);
+                    }
+                }
+            } else if byte == ESC {
+                self.escaping = true;
+            } else {
+                self.buffer.push(byte);
+            }
+
+            if self.buffer.len() > MAX_FRAME_SIZE {
+                let payload = std::mem::take(&mut self.buffer);
+                self.escaping = false;
+                self.error = None;
+                frames.push(Frame::malformed(
+                    payload,
+                    format!("no END delimiter within {} bytes", MAX_FRAME_SIZE),
+                ));
+            }
+        }
+
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.buffer.clear();
+        self.escaping = false;
+        self.error = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(data: &[u8]) -> Vec<Frame> {
+        SlipDecoder::default().feed(data)
+    }
+
+    #[test]
+    fn encode_escapes_end_and_esc() {
+        assert_eq!(encode(&[0x01, END, 0x02]), vec![END, 0x01, ESC, ESC_END, 0x02, END]);
+        assert_eq!(encode(&[ESC]), vec![END, ESC, ESC_ESC, END]);
+        assert_eq!(encode(&[]), vec![END, END]);
+    }
+
+    #[test]
+    fn decodes_plain_frame() {
+        let frames = decode_all(&[END, 0x01, 0x02, 0x03, END]);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, vec![0x01, 0x02, 0x03]);
+        assert!(frames[0].error.is_none());
+    }
+
+    #[test]
+    fn decodes_escaped_end_and_esc() {
+        let frames = decode_all(&[ESC, ESC_END, ESC, ESC_ESC, 0x7E, END]);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, vec![END, ESC, 0x7E]);
+    }
+
+    #[test]
+    fn back_to_back_ends_produce_no_empty_frames() {
+        let frames = decode_all(&[END, END, END, 0x41, END, END]);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, vec![0x41]);
+    }
+
+    #[test]
+    fn invalid_escape_is_flagged() {
+        let frames = decode_all(&[0x01, ESC, 0x42, 0x03, END]);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, vec![0x01, 0x42, 0x03]);
+        assert!(frames[0].error.as_deref().unwrap().contains("0x42"));
+    }
+
+    #[test]
+    fn dangling_escape_before_end_is_flagged() {
+        let frames = decode_all(&[0x01, ESC, END]);
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].error.is_some());
+
+        // The decoder recovers for the following frame
+        let mut decoder = SlipDecoder::default();
+        decoder.feed(&[0x01, ESC, END]);
+        let frames = decoder.feed(&[0x02, END]);
+        assert_eq!(frames[0].payload, vec![0x02]);
+        assert!(frames[0].error.is_none());
+    }
+
+    #[test]
+    fn escape_split_across_chunks() {
+        let mut decoder = SlipDecoder::default();
+        assert!(decoder.feed(&[END, 0x10, ESC]).is_empty());
+        let frames = decoder.feed(&[ESC_END, 0x11, END]);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, vec![0x10, END, 0x11]);
+    }
+
+    #[test]
+    fn oversized_frame_is_cut() {
+        let data = vec![0x55; MAX_FRAME_SIZE + 1];
+        let frames = decode_all(&data);
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].error.is_some());
+    }
+
+    #[test]
+    fn round_trip() {
+        let payload: Vec<u8> = (0..=255).collect();
+        let frames = decode_all(&encode(&payload));
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, payload);
+    }
+}
@@ -0,0 +1,260 @@
+//! STX/ETX framing with a block check character, as used by many instruments:
+//! `STX payload ETX BCC`, where BCC is the XOR of the payload bytes and ETX.
+//! Single ACK and NAK bytes between frames are reported as frames of their own.
+//! After a bad frame the decoder skips to the next STX, and reports how much it
+//! skipped once a good frame arrives instead of a malformed frame per attempt.
+
+use super::{Decoder, Frame, Resync, MAX_FRAME_SIZE};
+
+pub const STX: u8 = 0x02;
+pub const ETX: u8 = 0x03;
+pub const ACK: u8 = 0x06;
+pub const NAK: u8 = 0x15;
+
+/// XOR of `payload` and the ETX that ends it.
+pub fn bcc(payload: &[u8]) -> u8 {
+    payload.iter().fold(ETX, |acc, &byte| acc ^ byte)
+}
+
+/// `STX payload ETX BCC`.
+pub fn encode(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 3);
+    out.push(STX);
+    out.extend_from_slice(payload);
+    out.push(ETX);
+    out.push(bcc(payload));
+    out
+}
+
+#[derive(Default)]
+enum State {
+    /// Between frames; only STX, ACK and NAK mean anything here.
+    #[default]
+    Idle,
+    Payload,
+    /// ETX seen, the next byte is the BCC.
+    Bcc,
+    /// Sync lost; everything up to the next STX is skipped.
+    Resync,
+}
+
+#[derive(Default)]
+pub struct StxEtxDecoder {
+    state: State,
+    buffer: Vec<u8>,
+    /// Bytes fed since the last reset.
+    offset: u64,
+    /// Bytes skipped since a bad frame, while no good frame has followed.
+    lost: Option<Resync>,
+}
+
+impl StxEtxDecoder {
+    /// A frame of `len` bytes failed, ending before stream offset `next`. The
+    /// first failure is reported; later ones are counted as skipped bytes.
+    fn failed(&mut self, frames: &mut Vec<Frame>, frame: Frame, len: usize, next: u64) {
+        match &mut self.lost {
+            Some(lost) => lost.skipped += len,
+            None => {
+                frames.push(frame);
+                self.lost = Some(Resync { skipped: 0, offset: next });
+            }
+        }
+    }
+
+    fn succeeded(&mut self, frames: &mut Vec<Frame>, frame: Frame) {
+        if let Some(lost) = self.lost.take().filter(|lost| lost.skipped > 0) {
+            frames.push(Frame::resync(lost));
+        }
+        frames.push(frame);
+    }
+}
+
+impl Decoder for StxEtxDecoder {
+    fn feed(&mut self, data: &[u8]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+
+        for &byte in data {
+            let at = self.offset;
+            self.offset += 1;
+            match self.state {
+                State::Idle => match byte {
+                    STX => self.state = State::Payload,
+                    ACK => frames.push(Frame::ok(vec![ACK], "ACK".to_string())),
+                    NAK => frames.push(Frame::ok(vec![NAK], "NAK".to_string())),
+                    // Line noise and trailing CR/LF between frames
+                    _ => {}
+                },
+                State::Resync => match byte {
+                    STX => self.state = State::Payload,
+                    _ => {
+                        if let Some(lost) = &mut self.lost {
+                            lost.skipped += 1;
+                        }
+                    }
+                },
+                State::Payload => match byte {
+                    ETX => self.state = State::Bcc,
+                    STX => {
+                        let payload = std::mem::take(&mut self.buffer);
+                        let len = payload.len();
+                        let frame = Frame::malformed(payload, format!("STX again after {} bytes, no ETX", len));
+                        self.failed(&mut frames, frame, len + 1, at);
+                    }
+                    _ => {
+                        self.buffer.push(byte);
+                        if self.buffer.len() > MAX_FRAME_SIZE {
+                            self.state = State::Resync;
+                            let payload = std::mem::take(&mut self.buffer);
+                            let len = payload.len();
+                            let frame = Frame::malformed(payload, format!("no ETX within {} bytes", MAX_FRAME_SIZE));
+                            self.failed(&mut frames, frame, len + 1, at + 1);
+                        }
+                    }
+                },
+                State::Bcc => {
+                    let payload = std::mem::take(&mut self.buffer);
+                    let expected = bcc(&payload);
+                    if byte == expected {
+                        self.state = State::Idle;
+                        let summary = format!("STX/ETX {} bytes", payload.len());
+                        self.succeeded(&mut frames, Frame::ok(payload, summary));
+                    } else {
+                        let len = payload.len() + 2;
+                        let frame = Frame::malformed(
+                            payload,
+                            format!("BCC mismatch: got {:02X}, expected {:02X}", byte, expected),
+                        );
+                        // A bad check byte that is an STX may be the next frame's start
+                        if byte == STX {
+                            self.state = State::Payload;
+                            self.failed(&mut frames, frame, len, at);
+                        } else {
+                            self.state = State::Resync;
+                            self.failed(&mut frames, frame, len + 1, at + 1);
+                        }
+                    }
+                }
+            }
+        }
+
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.state = State::Idle;
+        self.buffer.clear();
+        self.offset = 0;
+        self.lost = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(data: &[u8]) -> Vec<Frame> {
+        StxEtxDecoder::default().feed(data)
+    }
+
+    #[test]
+    fn encodes_with_bcc() {
+        // 'A' ^ 'B' ^ ETX = 0x41 ^ 0x42 ^ 0x03
+        assert_eq!(encode(b"AB"), vec![STX, b'A', b'B', ETX, 0x00]);
+        assert_eq!(encode(b"R01"), vec![STX, b'R', b'0', b'1', ETX, 0x50]);
+        assert_eq!(encode(&[]), vec![STX, ETX, ETX]);
+    }
+
+    #[test]
+    fn decodes_valid_frames_split_across_chunks() {
+        let wire = encode(b"T=21.5");
+        let mut decoder = StxEtxDecoder::default();
+        assert!(decoder.feed(&wire[..4]).is_empty());
+        let frames = decoder.feed(&wire[4..]);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, b"T=21.5");
+        assert_eq!(frames[0].summary, "STX/ETX 6 bytes");
+        assert!(frames[0].error.is_none());
+    }
+
+    #[test]
+    fn corrupted_bcc_is_flagged() {
+        let mut wire = encode(b"R01");
+        *wire.last_mut().unwrap() ^= 0xFF;
+        let frames = decode_all(&wire);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, b"R01");
+        assert_eq!(frames[0].error.as_deref(), Some("BCC mismatch: got AF, expected 50"));
+
+        // A flipped payload bit is caught as well, and the next frame decodes
+        let mut wire = encode(b"R01");
+        wire[2] ^= 0x01;
+        wire.extend_from_slice(&encode(b"R02"));
+        let frames = decode_all(&wire);
+        assert!(frames[0].error.is_some());
+        assert!(frames[1].error.is_none());
+    }
+
+    #[test]
+    fn ack_and_nak_stand_alone() {
+        let mut wire = vec![ACK, b'\r', b'\n'];
+        wire.extend_from_slice(&encode(&[ACK, NAK]));
+        wire.push(NAK);
+        let frames = decode_all(&wire);
+        let summaries: Vec<&str> = frames.iter().map(|f| f.summary.as_str()).collect();
+        assert_eq!(summaries, ["ACK", "STX/ETX 2 bytes", "NAK"]);
+        // Inside a frame they are payload
+        assert_eq!(frames[1].payload, [ACK, NAK]);
+    }
+
+    #[test]
+    fn restarts_on_stx_without_etx() {
+        let mut wire = vec![STX, b'x', b'y'];
+        wire.extend_from_slice(&encode(b"ok"));
+        let frames = decode_all(&wire);
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].error.as_deref().unwrap().contains("no ETX"));
+        assert_eq!(frames[1].payload, b"ok");
+    }
+
+    #[test]
+    fn garbage_costs_one_resync_notice() {
+        let mut wire = encode(b"A");
+        // A bad BCC loses sync at offset 4; the rest is skipped, false STX included
+        wire.extend_from_slice(&[STX, b'x', ETX, 0x00, 0xFF, 0x13, STX, 0x41, ETX, 0x77, 0x10, 0x20]);
+        wire.extend_from_slice(&encode(b"R01"));
+        wire.extend_from_slice(&encode(b"R02"));
+
+        for chunk_size in [1, 5, wire.len()] {
+            let mut decoder = StxEtxDecoder::default();
+            let frames: Vec<Frame> = wire.chunks(chunk_size).flat_map(|chunk| decoder.feed(chunk)).collect();
+            let summaries: Vec<&str> = frames.iter().map(|f| f.summary.as_str()).collect();
+            assert_eq!(
+                summaries,
+                ["STX/ETX 1 bytes", "1 bytes", "resync: skipped 8 bytes at offset 8", "STX/ETX 3 bytes", "STX/ETX 3 bytes"],
+                "chunk size {}",
+                chunk_size
+            );
+            assert!(frames[1].error.as_deref().unwrap().starts_with("BCC mismatch"));
+            assert_eq!(frames[2].resync, Some(Resync { skipped: 8, offset: 8 }));
+            assert!(frames.iter().filter(|f| f.resync.is_some()).all(|f| f.error.is_none()));
+            assert_eq!(frames[3].payload, b"R01");
+            assert_eq!(frames[4].payload, b"R02");
+        }
+
+        // A bad check byte that turns out to be an STX starts the next frame
+        let mut wire = vec![STX, b'x', ETX];
+        wire.extend_from_slice(&encode(b"ok"));
+        let frames = decode_all(&wire);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].payload, b"ok");
+    }
+
+    #[test]
+    fn oversized_frame_is_cut() {
+        let mut wire = vec![STX];
+        wire.extend(std::iter::repeat_n(b'a', MAX_FRAME_SIZE + 1));
+        let frames = decode_all(&wire);
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].error.is_some());
+    }
+}
@@ -0,0 +1,354 @@
+//! u-blox UBX binary protocol, framed alongside interleaved NMEA sentences
+
+use super::{nmea, Decoder, Frame, MAX_FRAME_SIZE};
+
+/// First UBX sync character.
+pub const SYNC_1: u8 = 0xB5;
+/// Second UBX sync character.
+pub const SYNC_2: u8 = 0x62;
+const HEADER_LEN: usize = 6;
+const CHECKSUM_LEN: usize = 2;
+
+/// 8-bit Fletcher checksum over class, id, length and payload.
+pub fn checksum(data: &[u8]) -> (u8, u8) {
+    let mut ck_a: u8 = 0;
+    let mut ck_b: u8 = 0;
+    for &byte in data {
+        ck_a = ck_a.wrapping_add(byte);
+        ck_b = ck_b.wrapping_add(ck_a);
+    }
+    (ck_a, ck_b)
+}
+
+/// Name of a UBX message class, e.g. "NAV".
+pub fn class_name(class: u8) -> Option<&'static str> {
+    match class {
+        0x01 => Some("NAV"),
+        0x02 => Some("RXM"),
+        0x04 => Some("INF"),
+        0x05 => Some("ACK"),
+        0x06 => Some("CFG"),
+        0x0A => Some("MON"),
+        0x0D => Some("TIM"),
+        0x10 => Some("ESF"),
+        0x13 => Some("MGA"),
+        0x27 => Some("SEC"),
+        _ => None,
+    }
+}
+
+/// Name of a UBX message, e.g. "NAV-PVT". Unknown ids fall back to hex.
+pub fn message_name(class: u8, id: u8) -> String {
+    let name = match (class, id) {
+        (0x01, 0x02) => Some("NAV-POSLLH"),
+        (0x01, 0x03) => Some("NAV-STATUS"),
+        (0x01, 0x06) => Some("NAV-SOL"),
+        (0x01, 0x07) => Some("NAV-PVT"),
+        (0x01, 0x12) => Some("NAV-VELNED"),
+        (0x01, 0x21) => Some("NAV-TIMEUTC"),
+        (0x01, 0x35) => Some("NAV-SAT"),
+        (0x05, 0x00) => Some("ACK-NAK"),
+        (0x05, 0x01) => Some("ACK-ACK"),
+        (0x06, 0x00) => Some("CFG-PRT"),
+        (0x06, 0x01) => Some("CFG-MSG"),
+        (0x06, 0x08) => Some("CFG-RATE"),
+        (0x06, 0x8A) => Some("CFG-VALSET"),
+        (0x06, 0x8B) => Some("CFG-VALGET"),
+        (0x0A, 0x04) => Some("MON-VER"),
+        (0x0A, 0x09) => Some("MON-HW"),
+        _ => None,
+    };
+
+    match (name, class_name(class)) {
+        (Some(name), _) => name.to_string(),
+        (None, Some(class)) => format!("{}-0x{:02X}", class, id),
+        (None, None) => format!("0x{:02X}-0x{:02X}", class, id),
+    }
+}
+
+fn describe_payload(class: u8, id: u8, payload: &[u8]) -> Option<String> {
+    match (class, id) {
+        (0x05, 0x00) | (0x05, 0x01) if payload.len() >= 2 => {
+            Some(format!("for {}", message_name(payload[0], payload[1])))
+        }
+        (0x01, 0x07) if payload.len() >= 32 => {
+            let fix = match payload[20] {
+                0 => "no fix",
+                1 => "DR",
+                2 => "2D",
+                3 => "3D",
+                4 => "GNSS+DR",
+                5 => "time only",
+                _ => "?",
+            };
+            let lon = i32::from_le_bytes([payload[24], payload[25], payload[26], payload[27]]);
+            let lat = i32::from_le_bytes([payload[28], payload[29], payload[30], payload[31]]);
+            Some(format!(
+                "fix={} sats={} lat={:.7} lon={:.7}",
+                fix,
+                payload[23],
+                lat as f64 * 1e-7,
+                lon as f64 * 1e-7
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn ubx_frame(message: &[u8]) -> Frame {
+    let class = message[2];
+    let id = message[3];
+    let payload = &message[HEADER_LEN..message.len() - CHECKSUM_LEN];
+    let (ck_a, ck_b) = checksum(&message[2..message.len() - CHECKSUM_LEN]);
+    let received = (message[message.len() - 2], message[message.len() - 1]);
+    let name = message_name(class, id);
+
+    if (ck_a, ck_b) != received {
+        return Frame::malformed(
+            payload.to_vec(),
+            format!(
+                "UBX {} checksum mismatch: got {:02X} {:02X}, expected {:02X} {:02X}",
+                name, received.0, received.1, ck_a, ck_b
+            ),
+        );
+    }
+
+    let mut summary = format!("UBX {} len={}", name, payload.len());
+    if let Some(details) = describe_payload(class, id, payload) {
+        summary.push(' ');
+        summary.push_str(&details);
+    }
+    Frame::ok(payload.to_vec(), summary)
+}
+
+fn nmea_frame(line: &[u8]) -> Frame {
+    match nmea::verify_sentence(line) {
+        Ok(body) => Frame::ok(line.to_vec(), format!("NMEA {}", nmea::sentence_id(body))),
+        Err(e) => Frame::malformed(line.to_vec(), format!("NMEA {}", e)),
+    }
+}
+
+fn find_start(data: &[u8]) -> Option<usize> {
+    data.iter().enumerate().position(|(i, &b)| {
+        b == b'$' || (b == SYNC_1 && data.get(i + 1).is_none_or(|&next| next == SYNC_2))
+    })
+}
+
+/// Splits a byte stream into UBX messages and NMEA sentences, deciding per message
+/// which protocol applies. Bytes belonging to neither are reported as unrecognised.
+#[derive(Default)]
+pub struct GnssDecoder {
+    buffer: Vec<u8>,
+}
+
+impl GnssDecoder {
+    fn next_frame(&mut self) -> Option<Frame> {
+        // Discard anything before the next plausible message start
+        let start = find_start(&self.buffer).unwrap_or(self.buffer.len());
+        if start > 0 {
+            let junk: Vec<u8> = self.buffer.drain(..start).collect();
+            if junk.iter().any(|b| !b.is_ascii_whitespace()) {
+                return Some(Frame::malformed(junk, "unrecognised bytes".to_string()));
+            }
+        }
+
+        match self.buffer.first() {
+            None => None,
+            Some(&SYNC_1) => self.next_ubx(),
+            Some(_) => self.next_nmea(),
+        }
+    }
+
+    fn next_ubx(&mut self) -> Option<Frame> {
+        if self.buffer.len() < HEADER_LEN {
+            return None;
+        }
+
+        let len = u16::from_le_bytes([self.buffer[4], self.buffer[5]]) as usize;
+        if len > MAX_FRAME_SIZE {
+            // Not a real header; skip the sync byte and rescan
+            let skipped: Vec<u8> = self.buffer.drain(..1).collect();
+            return Some(Frame::malformed(
+                skipped,
+                format!("UBX length {} exceeds limit, resyncing", len),
+            ));
+        }
+
+        let total = HEADER_LEN + len + CHECKSUM_LEN;
+        if self.buffer.len() < total {
+            return None;
+        }
+
+        let message: Vec<u8> = self.buffer.drain(..total).collect();
+        Some(ubx_frame(&message))
+    }
+
+    fn next_nmea(&mut self) -> Option<Frame> {
+        let limit = self.buffer.len().min(nmea::MAX_SENTENCE_LEN);
+        let newline = self.buffer[..limit].iter().position(|&b| b == b'\n');
+        // A UBX sync inside an unterminated sentence means the sentence was cut short
+        let ubx_start = self.buffer[1..limit]
+            .windows(2)
+            .position(|w| w == [SYNC_1, SYNC_2])
+            .map(|p| p + 1);
+
+        match (newline, ubx_start) {
+            (Some(nl), Some(ubx)) if ubx < nl => Some(self.truncated_sentence(ubx)),
+            (Some(nl), _) => {
+                let mut line: Vec<u8> = self.buffer.drain(..=nl).collect();
+                while matches!(line.last(), Some(b'\n') | Some(b'\r')) {
+                    line.pop();
+                }
+                Some(nmea_frame(&line))
+            }
+            (None, Some(ubx)) => Some(self.truncated_sentence(ubx)),
+            (None, None) if self.buffer.len() >= nmea::MAX_SENTENCE_LEN => {
+                Some(self.truncated_sentence(nmea::MAX_SENTENCE_LEN))
+            }
+            (None, None) => None,
+        }
+    }
+
+    fn truncated_sentence(&mut self, end: usize) -> Frame {
+        let partial: Vec<u8> = self.buffer.drain(..end).collect();
+        Frame::malformed(partial, "NMEA sentence truncated".to_string())
+    }
+}
+
+impl Decoder for GnssDecoder {
+    fn feed(&mut self, data: &[u8]) -> Vec<Frame> {
+        self.buffer.extend_from_slice(data);
+        let mut frames = Vec::new();
+        while let Some(frame) = self.next_frame() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.buffer.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(class: u8, id: u8, payload: &[u8]) -> Vec<u8> {
+        let len = payload.len() as u16;
+        let mut out = vec![SYNC_1, SYNC_2, class, id];
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(payload);
+        let (ck_a, ck_b) = checksum(&out[2..]);
+        out.push(ck_a);
+        out.push(ck_b);
+        out
+    }
+
+    const GGA: &[u8] = b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n";
+
+    fn ack_ack() -> Vec<u8> {
+        vec![0xB5, 0x62, 0x05, 0x01, 0x02, 0x00, 0x06, 0x01, 0x0F, 0x38]
+    }
+
+    fn nav_pvt() -> Vec<u8> {
+        let mut payload = vec![0u8; 92];
+        payload[20] = 3;
+        payload[23] = 12;
+        payload[24..28].copy_from_slice(&115_000_000i32.to_le_bytes());
+        payload[28..32].copy_from_slice(&480_117_300i32.to_le_bytes());
+        encode(0x01, 0x07, &payload)
+    }
+
+    fn interleaved() -> Vec<u8> {
+        let mut stream = Vec::new();
+        stream.extend_from_slice(GGA);
+        stream.extend_from_slice(&nav_pvt());
+        stream.extend_from_slice(&ack_ack());
+        stream.extend_from_slice(GGA);
+        stream
+    }
+
+    fn summaries(frames: &[Frame]) -> Vec<String> {
+        frames.iter().map(|f| f.summary.clone()).collect()
+    }
+
+    #[test]
+    fn checksum_matches_known_message() {
+        assert_eq!(encode(0x05, 0x01, &[0x06, 0x01]), ack_ack());
+    }
+
+    #[test]
+    fn decodes_interleaved_stream() {
+        let frames = GnssDecoder::default().feed(&interleaved());
+        assert_eq!(frames.len(), 4);
+        assert!(frames.iter().all(|f| f.error.is_none()));
+        assert_eq!(frames[0].summary, "NMEA GPGGA");
+        assert!(frames[1].summary.starts_with("UBX NAV-PVT len=92 fix=3D sats=12"));
+        assert_eq!(frames[2].summary, "UBX ACK-ACK len=2 for CFG-MSG");
+        assert_eq!(frames[3].summary, "NMEA GPGGA");
+    }
+
+    #[test]
+    fn messages_split_across_every_chunk_boundary() {
+        let stream = interleaved();
+        let expected = summaries(&GnssDecoder::default().feed(&stream));
+
+        for chunk_size in 1..=stream.len() {
+            let mut decoder = GnssDecoder::default();
+            let mut frames = Vec::new();
+            for chunk in stream.chunks(chunk_size) {
+                frames.extend(decoder.feed(chunk));
+            }
+            assert_eq!(summaries(&frames), expected, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn ubx_payload_containing_dollar_and_newline() {
+        let message = encode(0x02, 0x15, b"$\n$\n");
+        let mut stream = message.clone();
+        stream.extend_from_slice(GGA);
+        let frames = GnssDecoder::default().feed(&stream);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].payload, b"$\n$\n".to_vec());
+        assert_eq!(frames[1].summary, "NMEA GPGGA");
+    }
+
+    #[test]
+    fn bad_ubx_checksum_is_flagged() {
+        let mut message = ack_ack();
+        message[9] ^= 0xFF;
+        let frames = GnssDecoder::default().feed(&message);
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].error.as_deref().unwrap().contains("checksum"));
+    }
+
+    #[test]
+    fn truncated_sentence_followed_by_ubx() {
+        let mut stream = b"$GPGGA,1235".to_vec();
+        stream.extend_from_slice(&ack_ack());
+        let frames = GnssDecoder::default().feed(&stream);
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].error.as_deref().unwrap().contains("truncated"));
+        assert_eq!(frames[1].summary, "UBX ACK-ACK len=2 for CFG-MSG");
+    }
+
+    #[test]
+    fn junk_between_messages_is_reported_and_skipped() {
+        let mut stream = vec![0x01, 0x02, 0x03];
+        stream.extend_from_slice(&ack_ack());
+        let frames = GnssDecoder::default().feed(&stream);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].payload, vec![0x01, 0x02, 0x03]);
+        assert!(frames[1].error.is_none());
+    }
+
+    #[test]
+    fn implausible_length_resyncs() {
+        let mut stream = vec![0xB5, 0x62, 0x01, 0x07, 0xFF, 0xFF];
+        stream.extend_from_slice(&ack_ack());
+        let frames = GnssDecoder::default().feed(&stream);
+        assert_eq!(frames.last().unwrap().summary, "UBX ACK-ACK len=2 for CFG-MSG");
+    }
+}
@@ -0,0 +1,187 @@
+//! RX rate alarm for runaway devices: raised once the receive rate has stayed
+//! above a threshold for a while, and cleared only once it has stayed below a
+//! lower level as long, so a rate hovering at the threshold does not flap.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// The rate is bytes over about this long.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateAlarmConfig {
+    pub enabled: bool,
+    /// KB/s (1024 bytes) the rate has to stay above.
+    pub threshold_kb: f64,
+    /// How long the rate has to stay above the threshold to raise the alarm,
+    /// and below the clear level to end it.
+    pub sustain_secs: f64,
+    /// The alarm clears below this percentage of the threshold.
+    pub clear_percent: f64,
+    /// Switch to raw recording when the alarm is raised.
+    pub start_recorder: bool,
+    /// RTS level set while the alarm is raised, and undone when it clears;
+    /// `None` leaves RTS alone.
+    pub rts: Option<bool>,
+}
+
+impl Default for RateAlarmConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_kb: 50.0,
+            sustain_secs: 5.0,
+            clear_percent: 50.0,
+            start_recorder: false,
+            rts: None,
+        }
+    }
+}
+
+impl RateAlarmConfig {
+    fn threshold(&self) -> f64 {
+        self.threshold_kb.max(0.0) * 1024.0
+    }
+
+    fn sustain(&self) -> Duration {
+        Duration::from_secs_f64(self.sustain_secs.clamp(0.0, 3600.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateEvent {
+    /// With the rate, in bytes per second, when raised.
+    Raised(f64),
+    Cleared(f64),
+}
+
+/// Tracks the rate from a running RX byte count sampled every frame or so.
+#[derive(Debug, Default)]
+pub struct RateAlarm {
+    samples: VecDeque<(Instant, u64)>,
+    pub bytes_per_second: f64,
+    pub alarm: bool,
+    /// Since when the rate has been past the level that changes the state.
+    past_since: Option<Instant>,
+}
+
+impl RateAlarm {
+    /// Note the RX byte count `total` at `now`. Returns an event when the
+    /// alarm is raised or cleared.
+    pub fn sample(&mut self, config: &RateAlarmConfig, total: u64, now: Instant) -> Option<RateEvent> {
+        // A count that went backwards was reset; start measuring again
+        if self.samples.back().is_some_and(|&(_, last)| total < last) {
+            self.samples.clear();
+        }
+        self.samples.push_back((now, total));
+        while self.samples.len() > 2 && now.saturating_duration_since(self.samples[1].0) >= RATE_WINDOW {
+            self.samples.pop_front();
+        }
+        let (since, from) = self.samples[0];
+        let elapsed = now.saturating_duration_since(since);
+        if elapsed < RATE_WINDOW / 4 {
+            return None;
+        }
+        self.bytes_per_second = (total - from) as f64 / elapsed.as_secs_f64();
+
+        let past = if self.alarm {
+            self.bytes_per_second < config.threshold() * config.clear_percent.clamp(0.0, 100.0) / 100.0
+        } else {
+            self.bytes_per_second > config.threshold()
+        };
+        if !past {
+            self.past_since = None;
+            return None;
+        }
+        let past_since = *self.past_since.get_or_insert(now);
+        if now.saturating_duration_since(past_since) < config.sustain() {
+            return None;
+        }
+        self.past_since = None;
+        self.alarm = !self.alarm;
+        Some(if self.alarm {
+            RateEvent::Raised(self.bytes_per_second)
+        } else {
+            RateEvent::Cleared(self.bytes_per_second)
+        })
+    }
+
+    /// Forget the measurements, e.g. on a new connection; the alarm ends
+    /// without an event.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `rate` bytes per second for `secs`, sampled every 100 ms, from
+    /// `total` at `at`; returns the events.
+    fn run(
+        alarm: &mut RateAlarm,
+        config: &RateAlarmConfig,
+        at: &mut Instant,
+        total: &mut u64,
+        rate: u64,
+        secs: u64,
+    ) -> Vec<RateEvent> {
+        let mut events = Vec::new();
+        for _ in 0..secs * 10 {
+            *at += Duration::from_millis(100);
+            *total += rate / 10;
+            events.extend(alarm.sample(config, *total, *at));
+        }
+        events
+    }
+
+    #[test]
+    fn raised_after_the_sustain_time_and_cleared_below_the_lower_level() {
+        let config = RateAlarmConfig {
+            enabled: true,
+            threshold_kb: 10.0,
+            sustain_secs: 3.0,
+            ..RateAlarmConfig::default()
+        };
+        let mut alarm = RateAlarm::default();
+        let (mut at, mut total) = (Instant::now(), 0);
+        assert!(run(&mut alarm, &config, &mut at, &mut total, 5 * 1024, 10).is_empty());
+
+        // A two-second spike is not enough
+        assert!(run(&mut alarm, &config, &mut at, &mut total, 20 * 1024, 2).is_empty());
+        assert!(run(&mut alarm, &config, &mut at, &mut total, 1024, 3).is_empty());
+
+        let events = run(&mut alarm, &config, &mut at, &mut total, 20 * 1024, 6);
+        assert!(matches!(events[..], [RateEvent::Raised(rate)] if rate > 19.0 * 1024.0));
+        assert!(alarm.alarm);
+
+        // Between the clear level (5 KB/s) and the threshold it stays raised
+        assert!(run(&mut alarm, &config, &mut at, &mut total, 8 * 1024, 10).is_empty());
+        assert!(alarm.alarm);
+        let events = run(&mut alarm, &config, &mut at, &mut total, 1024, 6);
+        assert!(matches!(events[..], [RateEvent::Cleared(_)]));
+        assert!(!alarm.alarm);
+    }
+
+    #[test]
+    fn a_reset_counter_starts_over() {
+        let config = RateAlarmConfig {
+            threshold_kb: 1.0,
+            sustain_secs: 0.0,
+            ..RateAlarmConfig::default()
+        };
+        let mut alarm = RateAlarm::default();
+        let start = Instant::now();
+        alarm.sample(&config, 1_000_000, start);
+        assert_eq!(alarm.sample(&config, 0, start + Duration::from_millis(500)), None);
+        assert_eq!(alarm.bytes_per_second, 0.0);
+        let event = alarm.sample(&config, 2048, start + Duration::from_millis(1500));
+        assert_eq!(event, Some(RateEvent::Raised(2048.0)));
+        alarm.reset();
+        assert!(!alarm.alarm);
+    }
+}
@@ -0,0 +1,94 @@
+//! TX rate limit for devices documented to drop input sent too fast, e.g.
+//! "max 10 commands per second". A token bucket one payload deep: each write
+//! pays for itself in payload and byte time before the next may go, so sends
+//! are spaced out rather than bunched at the start of each second.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Payloads and bytes per second allowed; 0 leaves that side unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimit {
+    pub payloads_per_sec: u32,
+    pub bytes_per_sec: u32,
+}
+
+impl RateLimit {
+    pub fn is_unlimited(&self) -> bool {
+        self.payloads_per_sec == 0 && self.bytes_per_sec == 0
+    }
+
+    /// The time a write of `len` bytes occupies: the longer of its payload and
+    /// byte allowance.
+    pub fn cost(&self, len: usize) -> Duration {
+        let per = |count: f64, rate: u32| {
+            if rate == 0 {
+                Duration::ZERO
+            } else {
+                Duration::from_secs_f64(count / rate as f64)
+            }
+        };
+        per(1.0, self.payloads_per_sec).max(per(len as f64, self.bytes_per_sec))
+    }
+}
+
+/// When the next write may go under a `RateLimit`.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    pub limit: RateLimit,
+    free_at: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// How long a write has to wait at `now`; zero when it may go.
+    pub fn wait(&self, now: Instant) -> Duration {
+        match self.free_at {
+            Some(at) if !self.limit.is_unlimited() => at.saturating_duration_since(now),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Record `len` bytes written at `now`.
+    pub fn sent(&mut self, len: usize, now: Instant) {
+        self.free_at = Some(now + self.limit.cost(len));
+    }
+
+    /// Forget the last write, e.g. on a new connection.
+    pub fn reset(&mut self) {
+        self.free_at = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_are_spaced_by_the_tighter_limit() {
+        let ms = Duration::from_millis;
+        let now = Instant::now();
+        let mut limiter = RateLimiter::default();
+        limiter.sent(100, now);
+        assert_eq!(limiter.wait(now), Duration::ZERO, "unlimited by default");
+
+        limiter.limit = RateLimit {
+            payloads_per_sec: 10,
+            bytes_per_sec: 0,
+        };
+        assert_eq!(limiter.wait(now), Duration::ZERO, "nothing sent yet");
+        limiter.sent(5, now);
+        assert_eq!(limiter.wait(now + ms(40)), ms(60));
+        assert_eq!(limiter.wait(now + ms(100)), Duration::ZERO);
+
+        // A long payload takes longer in bytes than in payloads
+        limiter.limit.bytes_per_sec = 100;
+        limiter.sent(50, now);
+        assert_eq!(limiter.wait(now), ms(500));
+        assert_eq!(limiter.limit.cost(5), ms(100));
+
+        limiter.reset();
+        assert_eq!(limiter.wait(now), Duration::ZERO);
+    }
+}
@@ -0,0 +1,170 @@
+//! Received data the capture filter discarded, kept in a bounded ring so the
+//! filter can be audited before it is trusted for a long run.
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use chrono::{DateTime, Local};
+
+use crate::hex;
+
+pub const DEFAULT_REJECTED_KB: usize = 64;
+pub const MAX_REJECTED_KB: usize = 4096;
+
+/// One filtered-out chunk, as it was read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedChunk {
+    pub received_at: DateTime<Local>,
+    pub data: Vec<u8>,
+}
+
+/// The latest rejected bytes, at most `capacity` of them. The oldest chunks
+/// go first; a chunk bigger than the whole ring keeps only its tail.
+#[derive(Debug, Clone)]
+pub struct RejectedRing {
+    chunks: VecDeque<RejectedChunk>,
+    bytes: usize,
+    capacity: usize,
+    /// Bytes pushed out to make room, since the last clear.
+    evicted: u64,
+}
+
+impl Default for RejectedRing {
+    fn default() -> Self {
+        Self::new(DEFAULT_REJECTED_KB * 1024)
+    }
+}
+
+impl RejectedRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            chunks: VecDeque::new(),
+            bytes: 0,
+            capacity: capacity.max(1),
+            evicted: 0,
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8], received_at: DateTime<Local>) {
+        if data.is_empty() {
+            return;
+        }
+        let kept = &data[data.len().saturating_sub(self.capacity)..];
+        self.evicted += (data.len() - kept.len()) as u64;
+        self.bytes += kept.len();
+        self.chunks.push_back(RejectedChunk {
+            received_at,
+            data: kept.to_vec(),
+        });
+        self.evict();
+    }
+
+    /// Hold at most `capacity` bytes from now on, dropping the oldest.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.bytes > self.capacity {
+            let Some(front) = self.chunks.front_mut() else {
+                break;
+            };
+            let excess = self.bytes - self.capacity;
+            if front.data.len() <= excess {
+                self.bytes -= front.data.len();
+                self.evicted += front.data.len() as u64;
+                self.chunks.pop_front();
+            } else {
+                front.data.drain(..excess);
+                self.bytes -= excess;
+                self.evicted += excess as u64;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.bytes = 0;
+        self.evicted = 0;
+    }
+
+    pub fn chunks(&self) -> impl DoubleEndedIterator<Item = &RejectedChunk> {
+        self.chunks.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Bytes held.
+    pub fn len(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    /// Every chunk as a timestamped heading and a hex dump, for saving.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        if self.evicted > 0 {
+            let _ = writeln!(out, "({} older bytes no longer held)\n", self.evicted);
+        }
+        for chunk in &self.chunks {
+            let _ = writeln!(
+                out,
+                "[{}] {} bytes",
+                chunk.received_at.format("%Y-%m-%d %H:%M:%S%.3f"),
+                chunk.data.len()
+            );
+            out.push_str(&hex::format_hex(&chunk.data));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_bytes_within_capacity() {
+        let now = Local::now();
+        let mut ring = RejectedRing::new(8);
+        ring.push(b"abcd", now);
+        ring.push(b"efgh", now);
+        assert_eq!(ring.len(), 8);
+        ring.push(b"ij", now);
+        // The oldest chunk is trimmed from the front, not dropped whole
+        let held: Vec<&[u8]> = ring.chunks().map(|c| c.data.as_slice()).collect();
+        assert_eq!(held, [b"cd".as_slice(), b"efgh", b"ij"]);
+        assert_eq!(ring.evicted(), 2);
+
+        // A chunk bigger than the ring keeps its tail
+        ring.push(b"0123456789", now);
+        assert_eq!(ring.chunks().count(), 1);
+        assert_eq!(ring.chunks().next().unwrap().data, b"23456789");
+        assert_eq!(ring.evicted(), 12);
+
+        ring.set_capacity(3);
+        assert_eq!(ring.chunks().next().unwrap().data, b"789");
+        ring.clear();
+        assert!(ring.is_empty());
+        assert_eq!(ring.evicted(), 0);
+    }
+
+    #[test]
+    fn text_dump_has_a_timestamped_hex_dump_per_chunk() {
+        let mut ring = RejectedRing::new(4);
+        let at = Local::now();
+        ring.push(b"DBG:", at);
+        ring.push(b"x", at);
+        let text = ring.to_text();
+        assert!(text.starts_with("(1 older bytes no longer held)\n\n["));
+        assert!(text.contains(" 3 bytes\n0000  42 47 3A"));
+        assert!(text.contains(" 1 bytes\n0000  78 "));
+    }
+}
@@ -0,0 +1,128 @@
+//! Repeat send: the send box sent again every interval, for polling a device
+//! with the same command.
+
+use std::time::{Duration, Instant};
+
+pub const MIN_REPEAT_MS: u64 = 10;
+pub const MAX_REPEAT_MS: u64 = 60_000;
+pub const DEFAULT_REPEAT_MS: u64 = 1000;
+
+/// Paces the repeats. The first send is due at once; after that a send is
+/// due each whole interval since the last one.
+#[derive(Debug, Default)]
+pub struct RepeatTimer {
+    running: bool,
+    last_send: Option<Instant>,
+    /// Sends made since the repeat started.
+    pub fired: u64,
+    /// Set by a failed send, so the error is reported once and not on every
+    /// later one.
+    failed: bool,
+}
+
+impl RepeatTimer {
+    pub fn start(&mut self) {
+        *self = Self {
+            running: true,
+            ..Self::default()
+        };
+    }
+
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Whether a send is due at `now`. The next one is then timed from when
+    /// this one was due, not from `now`, so a late frame does not push back
+    /// every later send; sends missed while the UI was busy are skipped.
+    pub fn due(&mut self, interval: Duration, now: Instant) -> bool {
+        if !self.running {
+            return false;
+        }
+        let Some(last) = self.last_send else {
+            self.last_send = Some(now);
+            return true;
+        };
+        let elapsed = now.duration_since(last);
+        if elapsed < interval {
+            return false;
+        }
+        let whole = (elapsed.as_nanos() / interval.as_nanos().max(1)) as u32;
+        self.last_send = Some(last + interval * whole);
+        true
+    }
+
+    /// How long until the next send; `None` when stopped.
+    pub fn wait(&self, interval: Duration, now: Instant) -> Option<Duration> {
+        if !self.running {
+            return None;
+        }
+        Some(match self.last_send {
+            Some(last) => interval.saturating_sub(now.duration_since(last)),
+            None => Duration::ZERO,
+        })
+    }
+
+    /// Count a send. Returns its error if the previous send went through, so
+    /// a failure that repeats is shown once.
+    pub fn sent(&mut self, result: Result<(), String>) -> Option<String> {
+        self.fired += 1;
+        match result {
+            Ok(()) => {
+                self.failed = false;
+                None
+            }
+            Err(e) => (!std::mem::replace(&mut self.failed, true)).then_some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_stay_on_the_interval_grid() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let interval = Duration::from_millis(100);
+        let mut timer = RepeatTimer::default();
+        assert!(!timer.due(interval, at(0)));
+        assert_eq!(timer.wait(interval, at(0)), None);
+
+        timer.start();
+        assert!(timer.due(interval, at(0)), "the first send goes at once");
+        assert!(!timer.due(interval, at(99)));
+        assert_eq!(timer.wait(interval, at(40)), Some(Duration::from_millis(60)));
+        // A late frame does not shift the sends after it
+        assert!(timer.due(interval, at(130)));
+        assert_eq!(timer.wait(interval, at(130)), Some(Duration::from_millis(70)));
+        assert!(timer.due(interval, at(200)));
+        // Missed sends are skipped, not made up in a burst
+        assert!(timer.due(interval, at(560)));
+        assert!(!timer.due(interval, at(590)));
+        assert!(timer.due(interval, at(600)));
+
+        timer.stop();
+        assert!(!timer.due(interval, at(10_000)));
+    }
+
+    #[test]
+    fn a_repeated_failure_is_reported_once() {
+        let mut timer = RepeatTimer::default();
+        timer.start();
+        let failure = || Err("Send failed: not connected".to_string());
+        assert_eq!(timer.sent(failure()).as_deref(), Some("Send failed: not connected"));
+        assert_eq!(timer.sent(failure()), None);
+        assert_eq!(timer.sent(Ok(())), None);
+        assert!(timer.sent(failure()).is_some(), "a new failure after a good send");
+        assert_eq!(timer.fired, 4);
+
+        timer.start();
+        assert_eq!(timer.fired, 0);
+    }
+}
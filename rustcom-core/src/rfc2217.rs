@@ -0,0 +1,833 @@
+//! RFC 2217 client: a Telnet connection to a port server that also carries
+//! the port settings and control lines (COM-PORT-OPTION), behind the
+//! `SerialPort` trait like a raw socket. [`TelnetCodec`] does the protocol
+//! without any I/O; [`Rfc2217Port`] runs it over a socket. A server that
+//! refuses the option, or never answers, leaves a plain data connection.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+use crate::tcp_port::{io_error, open_stream, port_error, set_socket_timeout};
+
+pub const IAC: u8 = 255;
+pub const DONT: u8 = 254;
+pub const DO: u8 = 253;
+pub const WONT: u8 = 252;
+pub const WILL: u8 = 251;
+pub const SB: u8 = 250;
+pub const SE: u8 = 240;
+pub const BINARY: u8 = 0;
+pub const SUPPRESS_GO_AHEAD: u8 = 3;
+pub const COM_PORT_OPTION: u8 = 44;
+
+const SET_BAUDRATE: u8 = 1;
+const SET_DATASIZE: u8 = 2;
+const SET_PARITY: u8 = 3;
+const SET_STOPSIZE: u8 = 4;
+const SET_CONTROL: u8 = 5;
+const PURGE_DATA: u8 = 12;
+/// Servers answer a command with its code plus this.
+const SERVER_OFFSET: u8 = 100;
+const NOTIFY_MODEMSTATE: u8 = 7;
+
+/// How long `connect` waits for the server to take up COM-PORT-OPTION.
+pub const NEGOTIATION_TIMEOUT: Duration = Duration::from_secs(1);
+/// Read timeout while waiting for the negotiation.
+const NEGOTIATION_POLL: Duration = Duration::from_millis(20);
+/// Longest subnegotiation kept; the COM port ones are a few bytes.
+const MAX_SUBNEGOTIATION: usize = 64;
+
+/// Options we offer to do, and ones we ask the server to do.
+const LOCAL_OPTIONS: [u8; 2] = [COM_PORT_OPTION, BINARY];
+const REMOTE_OPTIONS: [u8; 2] = [BINARY, SUPPRESS_GO_AHEAD];
+
+/// Where COM-PORT-OPTION stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Negotiation {
+    Pending,
+    Accepted,
+    Refused,
+}
+
+/// Port settings and control lines to configure on the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemoteSettings {
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+    pub dtr: bool,
+    pub rts: bool,
+}
+
+/// Modem lines as last reported by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ModemState {
+    pub cts: bool,
+    pub dsr: bool,
+    pub ring: bool,
+    pub carrier: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OptionState {
+    Off,
+    Requested,
+    On,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseState {
+    Data,
+    Iac,
+    Option(u8),
+    Sub,
+    SubIac,
+}
+
+/// The Telnet side of a connection: takes received bytes apart into data and
+/// commands, answers option negotiation, and queues the COM port commands for
+/// whatever does the I/O to send.
+#[derive(Debug, Clone)]
+pub struct TelnetCodec {
+    state: ParseState,
+    sub: Vec<u8>,
+    outgoing: Vec<u8>,
+    /// Options on our side (WILL/WONT) and the server's (DO/DONT).
+    local: [OptionState; 256],
+    remote: [OptionState; 256],
+    com_port: Negotiation,
+    settings: RemoteSettings,
+    modem: ModemState,
+    /// A Telnet command has arrived, so the server speaks Telnet.
+    heard_telnet: bool,
+    /// Passing bytes through unchanged, as to a server that is not Telnet.
+    raw: bool,
+}
+
+impl TelnetCodec {
+    pub fn new(settings: RemoteSettings) -> Self {
+        Self {
+            state: ParseState::Data,
+            sub: Vec::new(),
+            outgoing: Vec::new(),
+            local: [OptionState::Off; 256],
+            remote: [OptionState::Off; 256],
+            com_port: Negotiation::Pending,
+            settings,
+            modem: ModemState::default(),
+            heard_telnet: false,
+            raw: false,
+        }
+    }
+
+    /// Queue the opening negotiation: COM-PORT-OPTION and binary mode from
+    /// us, binary mode and no go-aheads from the server.
+    pub fn start(&mut self) {
+        for option in LOCAL_OPTIONS {
+            self.local[option as usize] = OptionState::Requested;
+            self.outgoing.extend_from_slice(&[IAC, WILL, option]);
+        }
+        for option in REMOTE_OPTIONS {
+            self.remote[option as usize] = OptionState::Requested;
+            self.outgoing.extend_from_slice(&[IAC, DO, option]);
+        }
+    }
+
+    /// Stop waiting for COM-PORT-OPTION. A server that sent no Telnet
+    /// command at all is taken for a raw socket from now on.
+    pub fn give_up(&mut self) {
+        if self.com_port == Negotiation::Pending {
+            self.com_port = Negotiation::Refused;
+        }
+        if !self.heard_telnet {
+            self.raw = true;
+        }
+    }
+
+    pub fn negotiation(&self) -> Negotiation {
+        self.com_port
+    }
+
+    /// Whether bytes pass through without Telnet framing.
+    pub fn is_raw(&self) -> bool {
+        self.raw
+    }
+
+    pub fn settings(&self) -> &RemoteSettings {
+        &self.settings
+    }
+
+    pub fn modem_state(&self) -> ModemState {
+        self.modem
+    }
+
+    /// Bytes to send: negotiation replies and COM port commands.
+    pub fn take_outgoing(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    /// The data in `received`, with commands handled and escaped IACs
+    /// undone. A command cut off at the end is finished by the next call.
+    pub fn feed(&mut self, received: &[u8]) -> Vec<u8> {
+        if self.raw {
+            return received.to_vec();
+        }
+        let mut data = Vec::with_capacity(received.len());
+        for &byte in received {
+            self.state = match self.state {
+                ParseState::Data if byte == IAC => ParseState::Iac,
+                ParseState::Data => {
+                    data.push(byte);
+                    ParseState::Data
+                }
+                ParseState::Iac if byte == IAC => {
+                    data.push(IAC);
+                    ParseState::Data
+                }
+                ParseState::Iac => {
+                    self.heard_telnet = true;
+                    match byte {
+                        WILL | WONT | DO | DONT => ParseState::Option(byte),
+                        SB => {
+                            self.sub.clear();
+                            ParseState::Sub
+                        }
+                        // NOP, go-ahead and the like
+                        _ => ParseState::Data,
+                    }
+                }
+                ParseState::Option(verb) => {
+                    self.negotiate(verb, byte);
+                    ParseState::Data
+                }
+                ParseState::Sub if byte == IAC => ParseState::SubIac,
+                ParseState::Sub => {
+                    if self.sub.len() < MAX_SUBNEGOTIATION {
+                        self.sub.push(byte);
+                    }
+                    ParseState::Sub
+                }
+                ParseState::SubIac => match byte {
+                    IAC => {
+                        if self.sub.len() < MAX_SUBNEGOTIATION {
+                            self.sub.push(IAC);
+                        }
+                        ParseState::Sub
+                    }
+                    SE => {
+                        self.subnegotiation();
+                        ParseState::Data
+                    }
+                    // Malformed; drop what was collected
+                    _ => ParseState::Data,
+                },
+            };
+        }
+        data
+    }
+
+    /// `data` ready to send, with IAC bytes doubled unless the connection
+    /// is raw.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        if self.raw {
+            data.to_vec()
+        } else {
+            escape(data)
+        }
+    }
+
+    pub fn set_baud_rate(&mut self, baud_rate: u32) {
+        self.settings.baud_rate = baud_rate;
+        self.command(SET_BAUDRATE, &baud_rate.to_be_bytes());
+    }
+
+    pub fn set_data_bits(&mut self, data_bits: DataBits) {
+        self.settings.data_bits = data_bits;
+        self.command(SET_DATASIZE, &[data_size(data_bits)]);
+    }
+
+    pub fn set_parity(&mut self, parity: Parity) {
+        self.settings.parity = parity;
+        self.command(SET_PARITY, &[parity_code(parity)]);
+    }
+
+    pub fn set_stop_bits(&mut self, stop_bits: StopBits) {
+        self.settings.stop_bits = stop_bits;
+        self.command(SET_STOPSIZE, &[stop_size(stop_bits)]);
+    }
+
+    pub fn set_flow_control(&mut self, flow_control: FlowControl) {
+        self.settings.flow_control = flow_control;
+        self.command(SET_CONTROL, &[flow_code(flow_control)]);
+    }
+
+    pub fn set_dtr(&mut self, on: bool) {
+        self.settings.dtr = on;
+        self.command(SET_CONTROL, &[if on { 8 } else { 9 }]);
+    }
+
+    pub fn set_rts(&mut self, on: bool) {
+        self.settings.rts = on;
+        self.command(SET_CONTROL, &[if on { 11 } else { 12 }]);
+    }
+
+    pub fn set_break(&mut self, on: bool) {
+        self.command(SET_CONTROL, &[if on { 5 } else { 6 }]);
+    }
+
+    /// Have the server drop the buffered data of its port.
+    pub fn purge(&mut self, buffer: ClearBuffer) {
+        let code = match buffer {
+            ClearBuffer::Input => 1,
+            ClearBuffer::Output => 2,
+            ClearBuffer::All => 3,
+        };
+        self.command(PURGE_DATA, &[code]);
+    }
+
+    /// Queue a COM port command, once the server has accepted the option.
+    /// Until then the settings are only stored and go out on acceptance.
+    fn command(&mut self, code: u8, value: &[u8]) {
+        if self.com_port != Negotiation::Accepted {
+            return;
+        }
+        self.outgoing.extend_from_slice(&[IAC, SB, COM_PORT_OPTION, code]);
+        self.outgoing.extend(escape(value));
+        self.outgoing.extend_from_slice(&[IAC, SE]);
+    }
+
+    fn send_settings(&mut self) {
+        let settings = self.settings;
+        self.set_baud_rate(settings.baud_rate);
+        self.set_data_bits(settings.data_bits);
+        self.set_parity(settings.parity);
+        self.set_stop_bits(settings.stop_bits);
+        self.set_flow_control(settings.flow_control);
+        self.set_dtr(settings.dtr);
+        self.set_rts(settings.rts);
+    }
+
+    /// Answer a WILL, WONT, DO or DONT. Requests we made are only recorded
+    /// when answered, and a state already in place gets no reply, so the two
+    /// sides never loop.
+    fn negotiate(&mut self, verb: u8, option: u8) {
+        let (states, supported, yes, no) = match verb {
+            DO | DONT => (&mut self.local, LOCAL_OPTIONS.contains(&option), WILL, WONT),
+            _ => (&mut self.remote, REMOTE_OPTIONS.contains(&option), DO, DONT),
+        };
+        let state = &mut states[option as usize];
+        let enable = matches!(verb, DO | WILL);
+        let reply = match (enable, *state) {
+            (true, OptionState::On) | (false, OptionState::Off) => None,
+            (true, OptionState::Requested) => {
+                *state = OptionState::On;
+                None
+            }
+            (true, OptionState::Off) if supported => {
+                *state = OptionState::On;
+                Some(yes)
+            }
+            (true, OptionState::Off) => Some(no),
+            (false, OptionState::Requested) => {
+                *state = OptionState::Off;
+                None
+            }
+            (false, OptionState::On) => {
+                *state = OptionState::Off;
+                Some(no)
+            }
+        };
+        if let Some(reply) = reply {
+            self.outgoing.extend_from_slice(&[IAC, reply, option]);
+        }
+
+        if option == COM_PORT_OPTION && matches!(verb, DO | DONT) {
+            match self.local[COM_PORT_OPTION as usize] {
+                OptionState::On if self.com_port != Negotiation::Accepted => {
+                    self.com_port = Negotiation::Accepted;
+                    self.send_settings();
+                }
+                OptionState::Off => self.com_port = Negotiation::Refused,
+                _ => {}
+            }
+        }
+    }
+
+    fn subnegotiation(&mut self) {
+        if let [COM_PORT_OPTION, code, value, ..] = self.sub[..] {
+            if code == SERVER_OFFSET + NOTIFY_MODEMSTATE {
+                self.modem = ModemState {
+                    cts: value & 0x10 != 0,
+                    dsr: value & 0x20 != 0,
+                    ring: value & 0x40 != 0,
+                    carrier: value & 0x80 != 0,
+                };
+            }
+        }
+    }
+}
+
+/// `data` with every IAC byte doubled.
+pub fn escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for &byte in data {
+        escaped.push(byte);
+        if byte == IAC {
+            escaped.push(IAC);
+        }
+    }
+    escaped
+}
+
+fn data_size(data_bits: DataBits) -> u8 {
+    match data_bits {
+        DataBits::Five => 5,
+        DataBits::Six => 6,
+        DataBits::Seven => 7,
+        DataBits::Eight => 8,
+    }
+}
+
+fn parity_code(parity: Parity) -> u8 {
+    match parity {
+        Parity::None => 1,
+        Parity::Odd => 2,
+        Parity::Even => 3,
+    }
+}
+
+fn stop_size(stop_bits: StopBits) -> u8 {
+    match stop_bits {
+        StopBits::One => 1,
+        StopBits::Two => 2,
+    }
+}
+
+fn flow_code(flow_control: FlowControl) -> u8 {
+    match flow_control {
+        FlowControl::None => 1,
+        FlowControl::Software => 2,
+        FlowControl::Hardware => 3,
+    }
+}
+
+/// What the handles on one connection share: the codec, and data decoded
+/// but not yet read.
+struct Shared {
+    codec: TelnetCodec,
+    pending: Vec<u8>,
+}
+
+/// A Telnet connection to an RFC 2217 port server. Setting the line settings
+/// and control lines configures the server's port, and the modem lines read
+/// as the server last reported them. Clones share the codec, so commands and
+/// data never interleave mid-sequence.
+pub struct Rfc2217Port {
+    stream: TcpStream,
+    address: String,
+    shared: Arc<Mutex<Shared>>,
+    timeout: Duration,
+}
+
+impl Rfc2217Port {
+    /// Connect to `address` as `host:port` and negotiate COM-PORT-OPTION,
+    /// waiting up to `NEGOTIATION_TIMEOUT` for the server to take it up.
+    /// Reads and writes then time out after `timeout`.
+    pub fn connect(address: &str, settings: RemoteSettings, timeout: Duration) -> Result<Self, String> {
+        let address = address.trim();
+        let mut codec = TelnetCodec::new(settings);
+        codec.start();
+        let mut port = Self {
+            stream: open_stream(address)?,
+            address: address.to_string(),
+            shared: Arc::new(Mutex::new(Shared {
+                codec,
+                pending: Vec::new(),
+            })),
+            timeout: Duration::ZERO,
+        };
+        let setup_error = |e: &dyn std::fmt::Display| format!("Cannot set up {}: {}", address, e);
+        set_socket_timeout(&port.stream, NEGOTIATION_POLL).map_err(|e| setup_error(&e))?;
+        port.send(&mut port.shared.lock().unwrap()).map_err(|e| setup_error(&e))?;
+        let deadline = Instant::now() + NEGOTIATION_TIMEOUT;
+        while port.negotiation() == Negotiation::Pending && Instant::now() < deadline {
+            port.receive().map_err(|e| setup_error(&e))?;
+        }
+        port.shared.lock().unwrap().codec.give_up();
+        port.set_timeout(timeout).map_err(|e| setup_error(&e))?;
+        Ok(port)
+    }
+
+    pub fn negotiation(&self) -> Negotiation {
+        self.shared.lock().unwrap().codec.negotiation()
+    }
+
+    /// Why the port settings do not reach the server, if they do not.
+    pub fn fallback_reason(&self) -> Option<&'static str> {
+        let shared = self.shared.lock().unwrap();
+        match shared.codec.negotiation() {
+            Negotiation::Accepted => None,
+            _ if shared.codec.is_raw() => Some("the server did not answer Telnet negotiation"),
+            _ => Some("the server refused COM port control"),
+        }
+    }
+
+    /// Send what the codec has queued. Called with the lock held, so nothing
+    /// else writes in between.
+    fn send(&self, shared: &mut Shared) -> std::io::Result<()> {
+        let outgoing = shared.codec.take_outgoing();
+        if outgoing.is_empty() {
+            return Ok(());
+        }
+        (&self.stream).write_all(&outgoing).map_err(port_error)
+    }
+
+    /// Read the socket once and decode what came into `pending`. A timeout is
+    /// not an error here.
+    fn receive(&self) -> std::io::Result<()> {
+        let mut buf = [0u8; 4096];
+        let n = match (&self.stream).read(&mut buf).map_err(port_error) {
+            Ok(0) => return Err(std::io::Error::new(ErrorKind::UnexpectedEof, "connection closed")),
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::Interrupted => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let mut shared = self.shared.lock().unwrap();
+        let data = shared.codec.feed(&buf[..n]);
+        shared.pending.extend_from_slice(&data);
+        self.send(&mut shared)
+    }
+
+    /// Apply a codec setter and send the command it queued.
+    fn control(&self, apply: impl FnOnce(&mut TelnetCodec)) -> serialport::Result<()> {
+        let mut shared = self.shared.lock().unwrap();
+        apply(&mut shared.codec);
+        self.send(&mut shared).map_err(io_error)
+    }
+
+    fn take_pending(&self, buf: &mut [u8]) -> Option<usize> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.pending.is_empty() {
+            return None;
+        }
+        let n = buf.len().min(shared.pending.len());
+        buf[..n].copy_from_slice(&shared.pending[..n]);
+        shared.pending.drain(..n);
+        Some(n)
+    }
+}
+
+impl Read for Rfc2217Port {
+    /// Reads that bring only commands time out like an idle port.
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(n) = self.take_pending(buf) {
+            return Ok(n);
+        }
+        self.receive()?;
+        self.take_pending(buf).ok_or_else(|| ErrorKind::TimedOut.into())
+    }
+}
+
+impl Write for Rfc2217Port {
+    /// Writes all of `buf` or nothing, so a timeout never leaves half an
+    /// escaped IAC on the wire.
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let shared = self.shared.lock().unwrap();
+        let encoded = shared.codec.encode(buf);
+        let mut written = 0;
+        while written < encoded.len() {
+            match (&self.stream).write(&encoded[written..]).map_err(port_error) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) if written == 0 => return Err(e),
+                Err(e) => return Err(std::io::Error::other(format!("write cut off: {}", e))),
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        (&self.stream).flush().map_err(port_error)
+    }
+}
+
+impl SerialPort for Rfc2217Port {
+    fn name(&self) -> Option<String> {
+        Some(self.address.clone())
+    }
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(self.shared.lock().unwrap().codec.settings().baud_rate)
+    }
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(self.shared.lock().unwrap().codec.settings().data_bits)
+    }
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(self.shared.lock().unwrap().codec.settings().flow_control)
+    }
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(self.shared.lock().unwrap().codec.settings().parity)
+    }
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(self.shared.lock().unwrap().codec.settings().stop_bits)
+    }
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.control(|codec| codec.set_baud_rate(baud_rate))
+    }
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        self.control(|codec| codec.set_data_bits(data_bits))
+    }
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.control(|codec| codec.set_flow_control(flow_control))
+    }
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        self.control(|codec| codec.set_parity(parity))
+    }
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        self.control(|codec| codec.set_stop_bits(stop_bits))
+    }
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        set_socket_timeout(&self.stream, timeout)?;
+        self.timeout = timeout;
+        Ok(())
+    }
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.control(|codec| codec.set_rts(level))
+    }
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.control(|codec| codec.set_dtr(level))
+    }
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Ok(self.shared.lock().unwrap().codec.modem_state().cts)
+    }
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Ok(self.shared.lock().unwrap().codec.modem_state().dsr)
+    }
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(self.shared.lock().unwrap().codec.modem_state().ring)
+    }
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(self.shared.lock().unwrap().codec.modem_state().carrier)
+    }
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self.shared.lock().unwrap().pending.len() as u32)
+    }
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+    /// Purges the server's buffers, and for input what is decoded but unread.
+    fn clear(&self, buffer: ClearBuffer) -> serialport::Result<()> {
+        let mut shared = self.shared.lock().unwrap();
+        if matches!(buffer, ClearBuffer::Input | ClearBuffer::All) {
+            shared.pending.clear();
+        }
+        shared.codec.purge(buffer);
+        self.send(&mut shared).map_err(io_error)
+    }
+    /// A handle on the same connection and codec, as for the reader thread.
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(Self {
+            stream: self.stream.try_clone().map_err(io_error)?,
+            address: self.address.clone(),
+            shared: self.shared.clone(),
+            timeout: self.timeout,
+        }))
+    }
+    fn set_break(&self) -> serialport::Result<()> {
+        self.control(|codec| codec.set_break(true))
+    }
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.control(|codec| codec.set_break(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn settings() -> RemoteSettings {
+        RemoteSettings {
+            baud_rate: 115_200,
+            data_bits: DataBits::Eight,
+            parity: Parity::Even,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            dtr: true,
+            rts: false,
+        }
+    }
+
+    /// The commands `settings()` configures, as a server receives them.
+    const SETTINGS_COMMANDS: [u8; 52] = [
+        IAC, SB, 44, 1, 0x00, 0x01, 0xC2, 0x00, IAC, SE, // 115200 baud
+        IAC, SB, 44, 2, 8, IAC, SE, // 8 data bits
+        IAC, SB, 44, 3, 3, IAC, SE, // even parity
+        IAC, SB, 44, 4, 1, IAC, SE, // 1 stop bit
+        IAC, SB, 44, 5, 1, IAC, SE, // no flow control
+        IAC, SB, 44, 5, 8, IAC, SE, // DTR on
+        IAC, SB, 44, 5, 12, IAC, SE, // RTS off
+    ];
+
+    #[test]
+    fn negotiates_the_option_then_sends_the_settings() {
+        let mut codec = TelnetCodec::new(settings());
+        codec.start();
+        assert_eq!(
+            codec.take_outgoing(),
+            [IAC, WILL, 44, IAC, WILL, BINARY, IAC, DO, BINARY, IAC, DO, SUPPRESS_GO_AHEAD]
+        );
+        // Settings wait for the server
+        codec.set_baud_rate(115_200);
+        assert!(codec.take_outgoing().is_empty());
+        assert_eq!(codec.negotiation(), Negotiation::Pending);
+
+        // Answers to our own requests need no reply; data around them passes
+        let data = codec.feed(&[b'>', IAC, DO, 44, IAC, DO, BINARY, IAC, WILL, BINARY, IAC, WILL, 3, b'\r']);
+        assert_eq!(data, b">\r");
+        assert_eq!(codec.negotiation(), Negotiation::Accepted);
+        assert_eq!(codec.take_outgoing(), SETTINGS_COMMANDS);
+
+        // Once accepted, setters go out at once; a repeated DO is not answered
+        codec.set_data_bits(DataBits::Seven);
+        codec.set_break(true);
+        codec.purge(ClearBuffer::All);
+        codec.feed(&[IAC, DO, 44]);
+        assert_eq!(
+            codec.take_outgoing(),
+            [IAC, SB, 44, 2, 7, IAC, SE, IAC, SB, 44, 5, 5, IAC, SE, IAC, SB, 44, 12, 3, IAC, SE]
+        );
+        assert_eq!(codec.settings().data_bits, DataBits::Seven);
+    }
+
+    #[test]
+    fn escapes_iac_both_ways_and_across_reads() {
+        let mut codec = TelnetCodec::new(settings());
+        assert_eq!(codec.encode(&[0x01, IAC, 0x02]), [0x01, IAC, IAC, 0x02]);
+        // A doubled IAC, and one split over two reads
+        assert_eq!(codec.feed(&[0x10, IAC, IAC, 0x11, IAC]), [0x10, IAC, 0x11]);
+        assert_eq!(codec.feed(&[IAC, 0x12]), [IAC, 0x12]);
+        // A command split over reads is not data
+        assert!(codec.feed(&[IAC, SB, 44]).is_empty());
+        assert!(codec.feed(&[107, 0x30, IAC]).is_empty());
+        assert_eq!(codec.feed(&[SE, b'A']), b"A");
+        assert_eq!(
+            codec.modem_state(),
+            ModemState {
+                cts: true,
+                dsr: true,
+                ring: false,
+                carrier: false
+            }
+        );
+
+        // A baud rate with an IAC byte in it is escaped inside the command
+        codec.start();
+        codec.feed(&[IAC, DO, 44]);
+        codec.take_outgoing();
+        codec.set_baud_rate(0xFF00);
+        assert_eq!(codec.take_outgoing(), [IAC, SB, 44, 1, 0x00, 0x00, IAC, IAC, 0x00, IAC, SE]);
+    }
+
+    #[test]
+    fn refusals_and_unknown_options() {
+        let mut codec = TelnetCodec::new(settings());
+        codec.start();
+        codec.take_outgoing();
+        // Terminal type and echo are declined; a refused request gets no reply
+        codec.feed(&[IAC, DO, 24, IAC, WILL, 1, IAC, DONT, 44, IAC, WONT, 3]);
+        assert_eq!(codec.take_outgoing(), [IAC, WONT, 24, IAC, DONT, 1]);
+        assert_eq!(codec.negotiation(), Negotiation::Refused);
+        codec.set_dtr(false);
+        assert!(codec.take_outgoing().is_empty(), "no commands once refused");
+
+        // Still Telnet, so IACs stay escaped after giving up
+        codec.give_up();
+        assert!(!codec.is_raw());
+        assert_eq!(codec.encode(&[IAC]), [IAC, IAC]);
+
+        // A server that never spoke Telnet is raw from then on
+        let mut silent = TelnetCodec::new(settings());
+        silent.start();
+        assert_eq!(silent.feed(b"login: "), b"login: ");
+        silent.give_up();
+        assert!(silent.is_raw());
+        assert_eq!(silent.negotiation(), Negotiation::Refused);
+        assert_eq!(silent.feed(&[IAC, IAC]), [IAC, IAC]);
+        assert_eq!(silent.encode(&[IAC]), [IAC]);
+    }
+
+    /// Read from `server` until `count` bytes arrived.
+    fn read_exactly(server: &mut TcpStream, count: usize) -> Vec<u8> {
+        let mut data = vec![0u8; count];
+        server.read_exact(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn configures_the_server_and_carries_data_over_a_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (mut server, _) = listener.accept().unwrap();
+            server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            assert_eq!(read_exactly(&mut server, 12)[..3], [IAC, WILL, 44]);
+            server.write_all(&[IAC, DO, 44, IAC, SB, 44, 107, 0x10, IAC, SE]).unwrap();
+            assert_eq!(read_exactly(&mut server, SETTINGS_COMMANDS.len()), SETTINGS_COMMANDS);
+            server
+        });
+        let mut port = Rfc2217Port::connect(&address, settings(), Duration::from_millis(20)).unwrap();
+        let mut server = server.join().unwrap();
+        assert_eq!(port.negotiation(), Negotiation::Accepted);
+        assert_eq!(port.fallback_reason(), None);
+        assert!(port.read_clear_to_send().unwrap());
+
+        port.write_all(&[b'A', IAC]).unwrap();
+        assert_eq!(read_exactly(&mut server, 3), [b'A', IAC, IAC]);
+        port.write_request_to_send(true).unwrap();
+        assert_eq!(read_exactly(&mut server, 7), [IAC, SB, 44, 5, 11, IAC, SE]);
+
+        // The reader's clone sees data with the escaping undone
+        let mut reader = port.try_clone().unwrap();
+        server.write_all(&[b'O', IAC, IAC, b'K']).unwrap();
+        let mut data = Vec::new();
+        let mut buf = [0u8; 16];
+        for _ in 0..500 {
+            match reader.read(&mut buf) {
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                Err(e) => assert_eq!(e.kind(), ErrorKind::TimedOut),
+            }
+            if data.len() >= 3 {
+                break;
+            }
+        }
+        assert_eq!(data, [b'O', IAC, b'K']);
+    }
+
+    #[test]
+    fn a_silent_server_leaves_a_raw_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut port = Rfc2217Port::connect(&address, settings(), Duration::from_millis(20)).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(port.negotiation(), Negotiation::Refused);
+        assert_eq!(port.fallback_reason(), Some("the server did not answer Telnet negotiation"));
+        read_exactly(&mut server, 12);
+        port.write_all(&[IAC]).unwrap();
+        port.set_baud_rate(9600).unwrap();
+        assert_eq!(read_exactly(&mut server, 1), [IAC]);
+    }
+}
@@ -0,0 +1,175 @@
+//! Named filter rule sets (patterns with their highlight colours), exported and
+//! imported as JSON files on their own so a team can share them per product.
+
+use serde::{Deserialize, Serialize};
+
+use crate::filter::FilterRule;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub name: String,
+    pub rules: Vec<FilterRule>,
+}
+
+/// What importing a set changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    pub added: usize,
+    /// Rules already in the set whose enabled flag or colour changed.
+    pub updated: usize,
+    /// One message per rule that could not be imported.
+    pub errors: Vec<String>,
+}
+
+impl ImportReport {
+    /// "3 rules added, 1 updated, 2 skipped"
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} rules added, {} updated", self.added, self.updated);
+        if !self.errors.is_empty() {
+            summary.push_str(&format!(", {} skipped", self.errors.len()));
+        }
+        summary
+    }
+}
+
+/// A rule set file as read, before each rule is checked on its own.
+#[derive(Deserialize)]
+struct RuleSetFile {
+    name: String,
+    rules: Vec<serde_json::Value>,
+}
+
+impl RuleSet {
+    pub fn new(name: &str, rules: Vec<FilterRule>) -> Self {
+        Self {
+            name: name.to_string(),
+            rules,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Cannot encode rule set: {}", e))
+    }
+
+    /// Parse a rule set file. Rules that are malformed or have a bad regex are
+    /// left out and described in the returned messages; the rest are compiled.
+    pub fn from_json(text: &str) -> Result<(Self, Vec<String>), String> {
+        let file: RuleSetFile =
+            serde_json::from_str(text).map_err(|e| format!("Not a rule set: {}", e))?;
+
+        let mut rules = Vec::new();
+        let mut errors = Vec::new();
+        for (index, value) in file.rules.into_iter().enumerate() {
+            match serde_json::from_value::<FilterRule>(value) {
+                Ok(mut rule) => {
+                    rule.compile();
+                    match rule.error.take() {
+                        Some(error) => errors.push(format!("Rule {} ({}): {}", index + 1, rule.pattern, error)),
+                        None => rules.push(rule),
+                    }
+                }
+                Err(e) => errors.push(format!("Rule {}: {}", index + 1, e)),
+            }
+        }
+        Ok((Self::new(&file.name, rules), errors))
+    }
+
+    /// Merge `incoming` into this set: rules with a pattern already present take
+    /// the incoming enabled flag and colour, new patterns are appended.
+    pub fn merge(&mut self, incoming: Vec<FilterRule>) -> ImportReport {
+        let mut report = ImportReport::default();
+        for rule in incoming {
+            match self.rules.iter_mut().find(|existing| existing.pattern == rule.pattern) {
+                Some(existing) => {
+                    if existing.enabled != rule.enabled || existing.highlight != rule.highlight {
+                        existing.enabled = rule.enabled;
+                        existing.highlight = rule.highlight;
+                        report.updated += 1;
+                    }
+                }
+                None => {
+                    self.rules.push(rule);
+                    report.added += 1;
+                }
+            }
+        }
+        report
+    }
+}
+
+/// Import `text` into `sets`, merging into the set of the same name if there is
+/// one. Returns the index of the set and what changed.
+pub fn import(sets: &mut Vec<RuleSet>, text: &str) -> Result<(usize, ImportReport), String> {
+    let (incoming, errors) = RuleSet::from_json(text)?;
+    let (index, mut report) = match sets.iter().position(|set| set.name == incoming.name) {
+        Some(index) => (index, sets[index].merge(incoming.rules)),
+        None => {
+            let report = ImportReport {
+                added: incoming.rules.len(),
+                ..ImportReport::default()
+            };
+            sets.push(incoming);
+            (sets.len() - 1, report)
+        }
+    };
+    report.errors = errors;
+    Ok((index, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, highlight: Option<[u8; 3]>) -> FilterRule {
+        let mut rule = FilterRule::new(pattern);
+        rule.highlight = highlight;
+        rule
+    }
+
+    #[test]
+    fn round_trips_with_colours() {
+        let set = RuleSet::new("Gateway", vec![rule("ERR", Some([255, 80, 80])), rule("^\\$GP", None)]);
+        let (restored, errors) = RuleSet::from_json(&set.to_json().unwrap()).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(restored.name, "Gateway");
+        assert_eq!(restored.rules[0].highlight, Some([255, 80, 80]));
+        assert!(restored.rules[1].is_active());
+        assert!(RuleSet::from_json("[]").is_err());
+    }
+
+    #[test]
+    fn bad_rules_are_reported_and_the_rest_imported() {
+        let json = r#"{
+            "name": "Sensor",
+            "rules": [
+                {"pattern": "TEMP", "enabled": true, "highlight": [0, 200, 0]},
+                {"pattern": "(unclosed", "enabled": true},
+                {"enabled": true},
+                {"pattern": "HUM", "enabled": false}
+            ]
+        }"#;
+        let (set, errors) = RuleSet::from_json(json).unwrap();
+        let patterns: Vec<&str> = set.rules.iter().map(|r| r.pattern.as_str()).collect();
+        assert_eq!(patterns, ["TEMP", "HUM"]);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("Rule 2 ((unclosed): Invalid regex"));
+        assert!(errors[1].starts_with("Rule 3: missing field `pattern`"));
+    }
+
+    #[test]
+    fn importing_merges_into_a_set_of_the_same_name() {
+        let mut sets = vec![RuleSet::new("Sensor", vec![rule("TEMP", None), rule("HUM", None)])];
+        let incoming = RuleSet::new("Sensor", vec![rule("TEMP", Some([0, 200, 0])), rule("HUM", None), rule("PRES", None)]);
+
+        let (index, report) = import(&mut sets, &incoming.to_json().unwrap()).unwrap();
+        assert_eq!(index, 0);
+        assert_eq!((report.added, report.updated), (1, 1));
+        assert_eq!(report.summary(), "1 rules added, 1 updated");
+        assert_eq!(sets[0].rules.len(), 3);
+        assert_eq!(sets[0].rules[0].highlight, Some([0, 200, 0]));
+
+        let other = RuleSet::new("Modem", vec![rule("^AT", None)]);
+        let (index, report) = import(&mut sets, &other.to_json().unwrap()).unwrap();
+        assert_eq!((index, report.added), (1, 1));
+    }
+}
@@ -0,0 +1,132 @@
+//! Received data waiting to be processed. Large reads are split into bounded
+//! sub-chunks and handed out a frame's budget at a time, so one huge read from
+//! a USB adapter cannot stall the display.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+
+/// Largest piece of a read that goes through the pipeline at once.
+pub const MAX_RX_SUB_CHUNK: usize = 4096;
+
+/// Bytes processed per UI frame unless configured otherwise.
+pub const DEFAULT_RX_FRAME_BUDGET: usize = 32 * 1024;
+
+/// Reading from the port pauses while this much is still queued, leaving the
+/// rest in the driver buffer (where flow control can hold the sender off).
+pub const RX_QUEUE_LIMIT: usize = 1024 * 1024;
+
+/// A piece of one read, stamped with the read time less the wire time of the
+/// bytes after it in that read, so its last byte is when it arrived.
+#[derive(Debug, Clone)]
+pub struct RxChunk {
+    pub data: Vec<u8>,
+    pub received_at: DateTime<Local>,
+    pub arrived: Instant,
+}
+
+#[derive(Default)]
+pub struct RxQueue {
+    chunks: VecDeque<RxChunk>,
+    bytes: usize,
+}
+
+impl RxQueue {
+    /// Queue one read, split into sub-chunks of at most `MAX_RX_SUB_CHUNK`.
+    /// `byte_time` is the wire time of one character, used for the offsets.
+    /// The read finished at `received_at`, so the stamps count back from it.
+    pub fn push(&mut self, data: &[u8], received_at: DateTime<Local>, arrived: Instant, byte_time: Duration) {
+        let mut end = 0;
+        for piece in data.chunks(MAX_RX_SUB_CHUNK) {
+            end += piece.len();
+            let before_end = byte_time * (data.len() - end) as u32;
+            self.chunks.push_back(RxChunk {
+                data: piece.to_vec(),
+                received_at: received_at - before_end,
+                arrived: arrived.checked_sub(before_end).unwrap_or(arrived),
+            });
+        }
+        self.bytes += data.len();
+    }
+
+    /// The chunks to process this frame: as many as fit in `budget` bytes, but
+    /// always at least one so the queue keeps moving.
+    pub fn take_frame(&mut self, budget: usize) -> Vec<RxChunk> {
+        let mut taken = Vec::new();
+        let mut size = 0;
+        while let Some(chunk) = self.chunks.front() {
+            if !taken.is_empty() && size + chunk.data.len() > budget {
+                break;
+            }
+            size += chunk.data.len();
+            taken.extend(self.chunks.pop_front());
+        }
+        self.bytes -= size;
+        taken
+    }
+
+    /// Bytes waiting.
+    pub fn len(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.bytes = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_megabyte_read_is_spread_over_frames() {
+        let data: Vec<u8> = (0..1024 * 1024).map(|i| i as u8).collect();
+        let start = Local::now();
+        let mut queue = RxQueue::default();
+        queue.push(&data, start, Instant::now(), Duration::from_micros(10));
+        assert_eq!(queue.len(), data.len());
+
+        let mut out = Vec::new();
+        let mut frames = 0;
+        let mut last_stamp = None;
+        while !queue.is_empty() {
+            let chunks = queue.take_frame(DEFAULT_RX_FRAME_BUDGET);
+            let size: usize = chunks.iter().map(|c| c.data.len()).sum();
+            assert!(size <= DEFAULT_RX_FRAME_BUDGET);
+            for chunk in chunks {
+                assert!(chunk.data.len() <= MAX_RX_SUB_CHUNK);
+                assert!(last_stamp.is_none_or(|last| chunk.received_at >= last));
+                assert!(chunk.received_at <= start, "no byte is stamped after the read");
+                if last_stamp.is_none() {
+                    // The first sub-chunk ended 1020 KiB of wire time before the read did
+                    let before = start - chunk.received_at;
+                    assert_eq!(before.num_microseconds(), Some(10 * (1024 * 1024 - MAX_RX_SUB_CHUNK) as i64));
+                }
+                last_stamp = Some(chunk.received_at);
+                out.extend(chunk.data);
+            }
+            frames += 1;
+        }
+        assert_eq!(out, data);
+        assert_eq!(frames, data.len() / DEFAULT_RX_FRAME_BUDGET);
+        // The last byte lines up with the read
+        assert_eq!(last_stamp, Some(start));
+    }
+
+    #[test]
+    fn a_tiny_budget_still_makes_progress() {
+        let mut queue = RxQueue::default();
+        queue.push(&[1; 10_000], Local::now(), Instant::now(), Duration::ZERO);
+        assert_eq!(queue.take_frame(1).len(), 1);
+        assert_eq!(queue.len(), 10_000 - MAX_RX_SUB_CHUNK);
+        queue.clear();
+        assert!(queue.take_frame(1).is_empty());
+    }
+}
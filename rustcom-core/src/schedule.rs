@@ -0,0 +1,170 @@
+//! Scheduled sends: payloads repeated on a fixed interval while connected.
+//! Each has its own phase offset, so entries with the same interval run as a
+//! spaced sequence, e.g. three queries 200 ms apart every 10 s.
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::payloads::SendMode;
+
+/// Shortest interval accepted, so a typo cannot flood the port.
+pub const MIN_SCHEDULE_INTERVAL_MS: u64 = 50;
+pub const DEFAULT_SCHEDULE_INTERVAL_MS: u64 = 10_000;
+
+/// The outcome of the latest run of an entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleRun {
+    pub at: DateTime<Local>,
+    /// Bytes written, or why nothing was sent.
+    pub result: Result<usize, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledSend {
+    /// Send box text: plain text (sent with the line ending) or `AA BB` hex.
+    pub text: String,
+    pub mode: SendMode,
+    pub interval_ms: u64,
+    /// Delay of the first run after the schedule starts.
+    #[serde(default)]
+    pub phase_ms: u64,
+    pub enabled: bool,
+    #[serde(skip)]
+    next_due: Option<Instant>,
+    #[serde(skip)]
+    pub last_run: Option<ScheduleRun>,
+}
+
+impl ScheduledSend {
+    /// An enabled entry with no phase offset.
+    pub fn new(text: &str, mode: SendMode, interval_ms: u64) -> Self {
+        Self {
+            text: text.to_string(),
+            mode,
+            interval_ms,
+            phase_ms: 0,
+            enabled: true,
+            next_due: None,
+            last_run: None,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.max(MIN_SCHEDULE_INTERVAL_MS))
+    }
+
+    /// When the next run is due; `None` until started, and while disabled.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.next_due.filter(|_| self.enabled)
+    }
+
+    /// Count from `start`: the first run comes after the phase offset.
+    pub fn start(&mut self, start: Instant) {
+        self.next_due = Some(start + Duration::from_millis(self.phase_ms));
+    }
+
+    /// Stop until started again, e.g. while disconnected.
+    pub fn stop(&mut self) {
+        self.next_due = None;
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.next_due.is_some()
+    }
+
+    /// Record a run made at `now` and move on to the next slot after it.
+    /// Slots missed while the port was busy are skipped, not caught up.
+    pub fn ran(&mut self, now: Instant, run: ScheduleRun) {
+        let interval = self.interval();
+        let mut next = self.next_due.unwrap_or(now) + interval;
+        if next <= now {
+            let behind = now.duration_since(next).as_nanos() / interval.as_nanos() + 1;
+            next += interval * behind as u32;
+        }
+        self.next_due = Some(next);
+        self.last_run = Some(run);
+    }
+}
+
+/// Indices of the entries due at `now`, earliest first.
+pub fn due(entries: &[ScheduledSend], now: Instant) -> Vec<usize> {
+    let mut due: Vec<(Instant, usize)> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| entry.next_due().filter(|&at| at <= now).map(|at| (at, index)))
+        .collect();
+    due.sort();
+    due.into_iter().map(|(_, index)| index).collect()
+}
+
+/// How long until the next entry is due.
+pub fn next_wait(entries: &[ScheduledSend], now: Instant) -> Option<Duration> {
+    entries
+        .iter()
+        .filter_map(ScheduledSend::next_due)
+        .min()
+        .map(|at| at.saturating_duration_since(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run() -> ScheduleRun {
+        ScheduleRun {
+            at: Local::now(),
+            result: Ok(1),
+        }
+    }
+
+    #[test]
+    fn phase_offsets_space_out_a_sequence() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut entries: Vec<ScheduledSend> = ["A?", "B?", "C?"]
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let mut entry = ScheduledSend::new(text, SendMode::Ascii, 10_000);
+                entry.phase_ms = 200 * i as u64;
+                entry.start(start);
+                entry
+            })
+            .collect();
+        entries.push(ScheduledSend::new("HK", SendMode::Ascii, 300_000));
+        assert!(entries[3].next_due().is_none(), "not started");
+        entries[3].start(start + ms(50));
+
+        assert_eq!(due(&entries, start), [0]);
+        assert_eq!(next_wait(&entries, start), Some(Duration::ZERO));
+        // Late polling still sends in phase order
+        assert_eq!(due(&entries, start + ms(450)), [0, 3, 1, 2]);
+        for index in [0, 3, 1, 2] {
+            entries[index].ran(start + ms(450), run());
+        }
+        assert!(due(&entries, start + ms(9_999)).is_empty());
+        assert_eq!(due(&entries, start + ms(10_400)), [0, 1, 2]);
+        assert_eq!(next_wait(&entries, start + ms(10_000)), Some(Duration::ZERO));
+
+        entries[1].enabled = false;
+        assert_eq!(due(&entries, start + ms(10_400)), [0, 2]);
+        entries[0].stop();
+        assert_eq!(due(&entries, start + ms(10_400)), [2]);
+    }
+
+    #[test]
+    fn missed_slots_are_skipped() {
+        let start = Instant::now();
+        let mut entry = ScheduledSend::new("PING", SendMode::Ascii, 1_000);
+        entry.start(start);
+        // Busy for three and a half intervals: one run, then back on the grid
+        entry.ran(start + Duration::from_millis(3_500), run());
+        assert_eq!(entry.next_due(), Some(start + Duration::from_millis(4_000)));
+        assert!(entry.last_run.as_ref().unwrap().result.is_ok());
+
+        entry.interval_ms = 0;
+        assert_eq!(entry.interval(), Duration::from_millis(MIN_SCHEDULE_INTERVAL_MS));
+    }
+}
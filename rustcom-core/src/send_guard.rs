@@ -0,0 +1,210 @@
+//! Send guard: payloads matching a rule need confirmation before they are
+//! transmitted, so a typo in a memory-write command cannot go out unnoticed.
+
+use regex::bytes::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::filter::hex_pattern;
+use crate::hex::parse_hex_input;
+
+/// Same compiled-size cap as filter rules.
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+/// How a rule's pattern is written.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GuardKind {
+    /// A byte sequence such as `"A5 5A 01"`, matched anywhere in the payload.
+    Hex,
+    /// A regex over the payload bytes.
+    Regex,
+}
+
+impl GuardKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            GuardKind::Hex => "Hex",
+            GuardKind::Regex => "Regex",
+        }
+    }
+}
+
+/// One guard rule. Only the definition is saved; call `compile` after loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardRule {
+    /// Shown in the confirmation, e.g. "Flash write".
+    pub description: String,
+    pub kind: GuardKind,
+    pub pattern: String,
+    pub enabled: bool,
+    #[serde(skip)]
+    pub regex: Option<Regex>,
+    #[serde(skip)]
+    pub error: Option<String>,
+}
+
+impl GuardRule {
+    /// An enabled rule, compiled straight away; check `error` for a bad pattern.
+    pub fn new(description: &str, kind: GuardKind, pattern: &str) -> Self {
+        let mut rule = Self {
+            description: description.to_string(),
+            kind,
+            pattern: pattern.to_string(),
+            enabled: true,
+            regex: None,
+            error: None,
+        };
+        rule.compile();
+        rule
+    }
+
+    /// Recompile after the pattern or kind was edited.
+    pub fn compile(&mut self) {
+        self.regex = None;
+        self.error = None;
+        if self.pattern.trim().is_empty() {
+            return;
+        }
+        let pattern = match self.kind {
+            GuardKind::Regex => self.pattern.clone(),
+            GuardKind::Hex => match parse_hex_input(&self.pattern) {
+                Ok(bytes) => hex_pattern(&bytes),
+                Err(e) => {
+                    self.error = Some(e);
+                    return;
+                }
+            },
+        };
+        match RegexBuilder::new(&pattern).size_limit(MAX_REGEX_SIZE).build() {
+            Ok(regex) => self.regex = Some(regex),
+            Err(e) => self.error = Some(format!("Invalid regex: {}", e)),
+        }
+    }
+
+    /// Enabled, compiled and matching `payload`.
+    pub fn matches(&self, payload: &[u8]) -> bool {
+        self.enabled && self.regex.as_ref().is_some_and(|re| re.is_match(payload))
+    }
+}
+
+/// The guard rules of one profile. A lock code keeps them from being edited or
+/// disabled until it is entered again. It deters casual changes; it is not a
+/// security boundary, since the settings file stays editable.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SendGuard {
+    pub rules: Vec<GuardRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lock: Option<u64>,
+}
+
+impl SendGuard {
+    /// Unlocked, with these rules.
+    pub fn with_rules(rules: Vec<GuardRule>) -> Self {
+        Self { rules, lock: None }
+    }
+
+    pub fn compile(&mut self) {
+        for rule in &mut self.rules {
+            rule.compile();
+        }
+    }
+
+    /// The first rule matching `payload`, if any.
+    pub fn matching_rule(&self, payload: &[u8]) -> Option<&GuardRule> {
+        self.rules.iter().find(|rule| rule.matches(payload))
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_some()
+    }
+
+    /// Lock the rules with `code`. An empty code is refused.
+    pub fn lock(&mut self, code: &str) -> Result<(), String> {
+        if code.is_empty() {
+            return Err("Enter a code to lock the send guard with".to_string());
+        }
+        self.lock = Some(fnv1a(code));
+        Ok(())
+    }
+
+    /// Unlock if `code` is the one the rules were locked with.
+    pub fn unlock(&mut self, code: &str) -> Result<(), String> {
+        match self.lock {
+            Some(hash) if hash != fnv1a(code) => Err("Wrong unlock code".to_string()),
+            _ => {
+                self.lock = None;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// 64-bit FNV-1a. Stable across builds, unlike `DefaultHasher`.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_rules_match_anywhere_in_the_payload() {
+        let rule = GuardRule::new("Memory write", GuardKind::Hex, "A5 5A 01");
+        assert!(rule.error.is_none());
+        assert!(rule.matches(&[0x00, 0xA5, 0x5A, 0x01, 0xFF]));
+        assert!(!rule.matches(&[0xA5, 0x5A, 0x02]));
+
+        let bad = GuardRule::new("Typo", GuardKind::Hex, "A5 ZZ");
+        assert!(bad.error.is_some());
+        assert!(!bad.matches(&[0xA5]));
+    }
+
+    #[test]
+    fn regex_rules_match_text_commands() {
+        let rule = GuardRule::new("Erase", GuardKind::Regex, r"(?i)^erase\s+\d+");
+        assert!(rule.matches(b"ERASE 4\r\n"));
+        assert!(!rule.matches(b"read 4\r\n"));
+        assert!(GuardRule::new("Bad", GuardKind::Regex, "(").error.is_some());
+    }
+
+    #[test]
+    fn disabled_rules_are_skipped() {
+        let mut guard = SendGuard::with_rules(vec![
+            GuardRule::new("Write", GuardKind::Hex, "57"),
+            GuardRule::new("Any W", GuardKind::Regex, "W"),
+        ]);
+        assert_eq!(guard.matching_rule(b"W").unwrap().description, "Write");
+        guard.rules[0].enabled = false;
+        assert_eq!(guard.matching_rule(b"W").unwrap().description, "Any W");
+        guard.rules[1].enabled = false;
+        assert!(guard.matching_rule(b"W").is_none());
+    }
+
+    #[test]
+    fn lock_needs_the_same_code_to_open() {
+        let mut guard = SendGuard::default();
+        assert!(guard.lock("").is_err());
+        guard.lock("1234").unwrap();
+        assert!(guard.is_locked());
+        assert_eq!(guard.unlock("4321"), Err("Wrong unlock code".to_string()));
+        assert!(guard.is_locked());
+        guard.unlock("1234").unwrap();
+        assert!(!guard.is_locked());
+    }
+
+    #[test]
+    fn lock_and_rules_survive_a_round_trip() {
+        let mut guard = SendGuard::with_rules(vec![GuardRule::new("Write", GuardKind::Hex, "A5 5A")]);
+        guard.lock("secret").unwrap();
+        let json = serde_json::to_string(&guard).unwrap();
+        assert!(!json.contains("secret"));
+
+        let mut restored: SendGuard = serde_json::from_str(&json).unwrap();
+        restored.compile();
+        assert!(restored.is_locked());
+        assert!(restored.matching_rule(&[0xA5, 0x5A]).is_some());
+        assert!(restored.unlock("secret").is_ok());
+    }
+}
@@ -0,0 +1,142 @@
+//! Shell-style history of what was sent from the send box, one list per send
+//! mode, recalled with Up/Down. The text being typed when browsing starts is
+//! kept as the newest entry, so stepping back down restores it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::payloads::SendMode;
+
+/// Entries kept per mode; the oldest drops off when a new one arrives.
+pub const MAX_SEND_HISTORY: usize = 100;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SendHistory {
+    /// Oldest first.
+    ascii: Vec<String>,
+    hex: Vec<String>,
+    /// The mode and entry being shown while browsing.
+    #[serde(skip)]
+    browsing: Option<(SendMode, usize)>,
+    /// The send box text from before browsing started.
+    #[serde(skip)]
+    draft: String,
+}
+
+impl SendHistory {
+    /// Oldest first.
+    pub fn entries(&self, mode: SendMode) -> &[String] {
+        match mode {
+            SendMode::Ascii => &self.ascii,
+            SendMode::Hex => &self.hex,
+        }
+    }
+
+    fn entries_mut(&mut self, mode: SendMode) -> &mut Vec<String> {
+        match mode {
+            SendMode::Ascii => &mut self.ascii,
+            SendMode::Hex => &mut self.hex,
+        }
+    }
+
+    /// Add a sent payload, unless it repeats the newest entry, and stop
+    /// browsing.
+    pub fn record(&mut self, mode: SendMode, text: &str) {
+        self.stop_browsing();
+        let entries = self.entries_mut(mode);
+        if text.is_empty() || entries.last().is_some_and(|last| last == text) {
+            return;
+        }
+        entries.push(text.to_string());
+        if entries.len() > MAX_SEND_HISTORY {
+            entries.drain(..entries.len() - MAX_SEND_HISTORY);
+        }
+    }
+
+    /// The entry before the one shown, or the newest when not browsing yet,
+    /// in which case `current` is kept as the draft. `None` at the oldest.
+    pub fn older(&mut self, mode: SendMode, current: &str) -> Option<String> {
+        let index = match self.browsing {
+            Some((browsing, index)) if browsing == mode => index.checked_sub(1)?,
+            _ => {
+                let newest = self.entries(mode).len().checked_sub(1)?;
+                self.draft = current.to_string();
+                newest
+            }
+        };
+        self.browsing = Some((mode, index));
+        Some(self.entries(mode)[index].clone())
+    }
+
+    /// The entry after the one shown; past the newest, the draft. `None`
+    /// when not browsing.
+    pub fn newer(&mut self, mode: SendMode) -> Option<String> {
+        let index = match self.browsing {
+            Some((browsing, index)) if browsing == mode => index + 1,
+            _ => return None,
+        };
+        if index < self.entries(mode).len() {
+            self.browsing = Some((mode, index));
+            Some(self.entries(mode)[index].clone())
+        } else {
+            self.browsing = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+
+    /// Forget the browsing position, e.g. after a send.
+    pub fn stop_browsing(&mut self) {
+        self.browsing = None;
+        self.draft.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn up_and_down_walk_the_history_and_restore_the_draft() {
+        let mut history = SendHistory::default();
+        assert_eq!(history.older(SendMode::Ascii, "typed"), None);
+        for text in ["AT", "ATI", "ATI", "", "AT+GMR"] {
+            history.record(SendMode::Ascii, text);
+        }
+        history.record(SendMode::Hex, "01 03");
+        assert_eq!(history.entries(SendMode::Ascii), ["AT", "ATI", "AT+GMR"]);
+
+        assert_eq!(history.newer(SendMode::Ascii), None);
+        assert_eq!(history.older(SendMode::Ascii, "AT+C").as_deref(), Some("AT+GMR"));
+        assert_eq!(history.older(SendMode::Ascii, "AT+GMR").as_deref(), Some("ATI"));
+        assert_eq!(history.older(SendMode::Ascii, "ATI").as_deref(), Some("AT"));
+        assert_eq!(history.older(SendMode::Ascii, "AT"), None);
+        assert_eq!(history.newer(SendMode::Ascii).as_deref(), Some("ATI"));
+        assert_eq!(history.newer(SendMode::Ascii).as_deref(), Some("AT+GMR"));
+        assert_eq!(history.newer(SendMode::Ascii).as_deref(), Some("AT+C"));
+        assert_eq!(history.newer(SendMode::Ascii), None);
+
+        // Switching modes browses the other list from its newest entry
+        history.older(SendMode::Ascii, "");
+        assert_eq!(history.older(SendMode::Hex, "AA").as_deref(), Some("01 03"));
+        assert_eq!(history.newer(SendMode::Hex).as_deref(), Some("AA"));
+    }
+
+    #[test]
+    fn keeps_the_newest_entries_and_saves_no_browsing_state() {
+        let mut history = SendHistory::default();
+        for i in 0..MAX_SEND_HISTORY + 5 {
+            history.record(SendMode::Hex, &format!("{:02X}", i));
+        }
+        let entries = history.entries(SendMode::Hex);
+        assert_eq!(entries.len(), MAX_SEND_HISTORY);
+        assert_eq!((entries[0].as_str(), entries[MAX_SEND_HISTORY - 1].as_str()), ("05", "68"));
+
+        history.older(SendMode::Hex, "draft");
+        let json = serde_json::to_string(&history).unwrap();
+        assert!(!json.contains("draft"));
+        let mut restored: SendHistory = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.entries(SendMode::Hex), history.entries(SendMode::Hex));
+        assert_eq!(restored.newer(SendMode::Hex), None);
+        assert!(serde_json::from_str::<SendHistory>("{}").unwrap().entries(SendMode::Ascii).is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+//! A name and colour for a RustCOM window, so several windows open on
+//! different ports can be told apart in the taskbar, the title bar and the
+//! files they export.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// Colours offered for the session chip.
+pub const SESSION_COLORS: [[u8; 3]; 8] = [
+    [220, 60, 60],
+    [230, 140, 30],
+    [210, 190, 40],
+    [60, 170, 80],
+    [40, 170, 170],
+    [60, 110, 220],
+    [150, 80, 210],
+    [200, 80, 150],
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionTag {
+    pub name: String,
+    pub color: Option<[u8; 3]>,
+}
+
+impl SessionTag {
+    /// The name with anything unsafe in a file name replaced by `-`; empty
+    /// when the session has no name.
+    pub fn file_part(&self) -> String {
+        self.name
+            .trim()
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+            .collect()
+    }
+
+    /// `prefix_<name>_<timestamp>`, or `prefix_<timestamp>` without a name.
+    pub fn file_stem(&self, prefix: &str, at: DateTime<Local>) -> String {
+        let stamp = at.format("%Y%m%d_%H%M%S");
+        match self.file_part() {
+            name if name.is_empty() => format!("{}_{}", prefix, stamp),
+            name => format!("{}_{}_{}", prefix, name, stamp),
+        }
+    }
+
+    pub fn window_title(&self) -> String {
+        match self.name.trim() {
+            "" => "RustCOM".to_string(),
+            name => format!("{} - RustCOM", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn name_goes_into_file_names_and_the_title() {
+        let at = Local.with_ymd_and_hms(2026, 3, 1, 9, 5, 7).unwrap();
+        let mut tag = SessionTag::default();
+        assert_eq!(tag.file_stem("report", at), "report_20260301_090507");
+        assert_eq!(tag.window_title(), "RustCOM");
+
+        tag.name = " Bench A/B: gateway ".to_string();
+        assert_eq!(tag.file_stem("report", at), "report_Bench-A-B--gateway_20260301_090507");
+        assert_eq!(tag.window_title(), "Bench A/B: gateway - RustCOM");
+    }
+}
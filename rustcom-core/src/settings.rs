@@ -0,0 +1,563 @@
+//! Persisted settings and configuration profiles
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::failover::FailoverConfig;
+use crate::filter::FilterRule;
+use crate::keepalive::KeepaliveConfig;
+use crate::line_editor::SendDiscipline;
+use crate::logging::syslog::SyslogConfig;
+use crate::live_view::LiveViewConfig;
+use crate::macros::Macro;
+use crate::metrics::MetricsConfig;
+use crate::payloads::PayloadRing;
+use crate::pinned::PinnedValue;
+use crate::port_config::{DataBits, FlowControl, Parity, StopBits};
+use crate::protocol::framing_rule::FramingRule;
+use crate::protocol::{CustomFraming, DecodedView, ProtocolMode};
+use crate::auto_view::AutoViewConfig;
+use crate::rate_alarm::RateAlarmConfig;
+use crate::rate_limit::RateLimit;
+use crate::rule_set::RuleSet;
+use crate::schedule::ScheduledSend;
+use crate::session::SessionTag;
+use crate::send_guard::SendGuard;
+use crate::send_history::SendHistory;
+use crate::tcp_bridge::TcpBridgeConfig;
+use crate::tcp_port::ConnectionType;
+use crate::text_encoding::{EncodingDetection, TextEncoding};
+use crate::watch::Watch;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Dark/light theme choice. The GUI resolves `System` against the OS theme.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ThemeSetting {
+    /// Follow the OS, including changes while running.
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl ThemeSetting {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ThemeSetting::System => "System",
+            ThemeSetting::Dark => "Dark",
+            ThemeSetting::Light => "Light",
+        }
+    }
+}
+
+/// A complete set of connection, filter and protocol settings. The A/B slots
+/// and the settings restored at startup are both stored this way.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ConfigSlot {
+    pub selected_port: Option<String>,
+    #[serde(default)]
+    pub connection_type: ConnectionType,
+    /// `host:port` opened in TCP client mode.
+    #[serde(default)]
+    pub tcp_address: String,
+    pub baud_rate: String,
+    pub data_bits: DataBits,
+    pub stop_bits: StopBits,
+    pub parity: Parity,
+    pub flow_control: FlowControl,
+    pub filter_enabled: bool,
+    pub filter_rules: Vec<FilterRule>,
+    pub protocol_mode: ProtocolMode,
+    pub custom_framing: CustomFraming,
+    /// Delimiter, length and checksum used by `CustomFraming::Rule`.
+    #[serde(default)]
+    pub framing_rule: FramingRule,
+    #[serde(default)]
+    pub monitor_only: bool,
+    #[serde(default)]
+    pub pinned_values: Vec<PinnedValue>,
+    #[serde(default)]
+    pub send_guard: SendGuard,
+    #[serde(default)]
+    pub send_discipline: SendDiscipline,
+    #[serde(default)]
+    pub variables_enabled: bool,
+    /// Keys left out of the variable table.
+    #[serde(default)]
+    pub hidden_variables: Vec<String>,
+    #[serde(default)]
+    pub watches: Vec<Watch>,
+    /// How RX text is decoded for the terminal.
+    #[serde(default)]
+    pub display_encoding: TextEncoding,
+    #[serde(default)]
+    pub encoding_detection: EncodingDetection,
+    #[serde(default)]
+    pub rate_alarm: RateAlarmConfig,
+    #[serde(default)]
+    pub syslog: SyslogConfig,
+    #[serde(default)]
+    pub failover: FailoverConfig,
+    #[serde(default)]
+    pub schedule: Vec<ScheduledSend>,
+    /// Limit on TX payloads and bytes per second, across every send path.
+    #[serde(default)]
+    pub tx_rate_limit: RateLimit,
+    #[serde(default)]
+    pub keepalive: KeepaliveConfig,
+    #[serde(default)]
+    pub macros: Vec<Macro>,
+    /// Name and colour of the window while this profile is active.
+    #[serde(default)]
+    pub session: SessionTag,
+}
+
+impl ConfigSlot {
+    /// True when switching between the two slots requires reopening the port.
+    pub fn port_settings_differ(&self, other: &ConfigSlot) -> bool {
+        self.selected_port != other.selected_port
+            || self.connection_type != other.connection_type
+            || self.tcp_address != other.tcp_address
+            || self.baud_rate != other.baud_rate
+            || self.data_bits != other.data_bits
+            || self.stop_bits != other.stop_bits
+            || self.parity != other.parity
+            || self.flow_control != other.flow_control
+            || self.failover != other.failover
+    }
+}
+
+/// Everything saved to the settings file between runs.
+#[derive(Serialize, Deserialize)]
+pub struct Settings {
+    pub current: ConfigSlot,
+    pub config_slots: [Option<ConfigSlot>; 2],
+    pub active_slot: usize,
+    /// Display preference for each protocol mode, independent of the A/B slots.
+    #[serde(default)]
+    pub decoded_views: HashMap<ProtocolMode, DecodedView>,
+    #[serde(default)]
+    pub theme: ThemeSetting,
+    /// Recently sent or copied payloads, shared by both A/B slots.
+    #[serde(default)]
+    pub recent_payloads: PayloadRing,
+    /// Send box history for Up/Down recall, shared by both A/B slots.
+    #[serde(default)]
+    pub send_history: SendHistory,
+    /// Named filter rule sets, shared by both A/B slots.
+    #[serde(default)]
+    pub rule_sets: Vec<RuleSet>,
+    /// The Prometheus endpoint, shared by both A/B slots.
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    /// The browser live view, shared by both A/B slots.
+    #[serde(default)]
+    pub live_view: LiveViewConfig,
+    /// The TCP bridge, shared by both A/B slots.
+    #[serde(default)]
+    pub tcp_bridge: TcpBridgeConfig,
+    /// The Auto view threshold, shared by both A/B slots.
+    #[serde(default)]
+    pub auto_view: AutoViewConfig,
+    #[serde(default = "default_terminal_font_size")]
+    pub terminal_font_size: f32,
+}
+
+/// egui's own monospace size.
+pub const DEFAULT_TERMINAL_FONT_SIZE: f32 = 12.0;
+
+fn default_terminal_font_size() -> f32 {
+    DEFAULT_TERMINAL_FONT_SIZE
+}
+
+/// `path` with `suffix` added to its file name, e.g. `settings.json.bak`.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// What loading found: the settings to use, and why the file was not used
+/// when it could not be read.
+#[derive(Default)]
+pub struct SettingsLoad {
+    /// `None` when starting from defaults.
+    pub settings: Option<Settings>,
+    pub problem: Option<String>,
+    /// Where the unreadable file was moved, so the next save cannot overwrite it.
+    pub corrupt_path: Option<PathBuf>,
+}
+
+/// `<config dir>/rustcom/settings.json`, falling back to the working directory.
+pub fn settings_path() -> PathBuf {
+    let base = std::env::var_os("APPDATA")
+        .or_else(|| std::env::var_os("XDG_CONFIG_HOME"))
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    match base {
+        Some(dir) => dir.join("rustcom").join(SETTINGS_FILE),
+        None => PathBuf::from(SETTINGS_FILE),
+    }
+}
+
+impl Settings {
+    /// Parse a settings file and compile its filter rules.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let mut settings: Settings =
+            serde_json::from_str(text).map_err(|e| format!("Invalid settings file: {}", e))?;
+        for slot in std::iter::once(&mut settings.current)
+            .chain(settings.config_slots.iter_mut().flatten())
+        {
+            for rule in &mut slot.filter_rules {
+                rule.compile();
+            }
+            for value in &mut slot.pinned_values {
+                value.compile();
+            }
+            slot.send_guard.compile();
+            for watch in &mut slot.watches {
+                watch.compile();
+            }
+            slot.syslog.compile();
+        }
+        for rule in settings.rule_sets.iter_mut().flat_map(|set| &mut set.rules) {
+            rule.compile();
+        }
+        settings.active_slot = settings.active_slot.min(1);
+        Ok(settings)
+    }
+
+    /// Pretty-printed JSON, as written by `save`.
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Cannot encode settings: {}", e))
+    }
+
+    /// Read the settings file. `Ok(None)` when there is none.
+    fn read(path: &Path) -> Result<Option<Self>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::from_json(&text).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Cannot read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn load() -> SettingsLoad {
+        Self::load_from(&settings_path())
+    }
+
+    /// Read `path`, falling back to its `.bak` and then to defaults when it
+    /// cannot be read or parsed, or is missing after a save was cut short.
+    /// The bad file is moved to `.corrupt`.
+    pub fn load_from(path: &Path) -> SettingsLoad {
+        let backup = sibling(path, ".bak");
+        let (problem, corrupt_path) = match Self::read(path) {
+            Ok(None) if backup.exists() => (format!("{} is missing", path.display()), None),
+            Ok(settings) => {
+                return SettingsLoad {
+                    settings,
+                    ..SettingsLoad::default()
+                }
+            }
+            Err(e) => {
+                let corrupt = sibling(path, ".corrupt");
+                (e, std::fs::rename(path, &corrupt).ok().map(|_| corrupt))
+            }
+        };
+        let settings = Self::read(&backup).ok().flatten();
+        let fallback = if settings.is_some() { "restored the backup" } else { "started with defaults" };
+        SettingsLoad {
+            settings,
+            problem: Some(format!("{}; {}", problem, fallback)),
+            corrupt_path,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        self.save_to(&settings_path())
+    }
+
+    /// Write `path` so that a crash part-way leaves either the old or the new
+    /// file: the JSON goes to `.tmp` and is synced, the old file becomes
+    /// `.bak`, then the new one is renamed into place.
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+        }
+        let json = self.to_json()?;
+        let tmp = sibling(path, ".tmp");
+        let written = File::create(&tmp).and_then(|mut file| {
+            file.write_all(json.as_bytes())?;
+            file.sync_all()
+        });
+        written.map_err(|e| format!("Cannot write {}: {}", tmp.display(), e))?;
+        if path.exists() {
+            let backup = sibling(path, ".bak");
+            std::fs::rename(path, &backup)
+                .map_err(|e| format!("Cannot back up {}: {}", path.display(), e))?;
+        }
+        std::fs::rename(&tmp, path).map_err(|e| format!("Cannot replace {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::failover::PortCandidate;
+    use crate::payloads::{LineEnding, SendMode};
+    use crate::send_guard::{GuardKind, GuardRule};
+
+    fn slot() -> ConfigSlot {
+        ConfigSlot {
+            selected_port: Some("COM7".to_string()),
+            connection_type: ConnectionType::TcpClient,
+            tcp_address: "10.0.0.5:4001".to_string(),
+            baud_rate: "9600".to_string(),
+            data_bits: DataBits::Seven,
+            stop_bits: StopBits::Two,
+            parity: Parity::Even,
+            flow_control: FlowControl::Software,
+            filter_enabled: true,
+            filter_rules: vec![FilterRule::new("^\\$GP")],
+            protocol_mode: ProtocolMode::Custom,
+            custom_framing: CustomFraming::Cobs,
+            framing_rule: FramingRule {
+                delimiter: vec![0x7E],
+                ..FramingRule::default()
+            },
+            monitor_only: true,
+            pinned_values: vec![PinnedValue::new("Temp", r"T=([\d.]+)")],
+            send_guard: SendGuard::with_rules(vec![GuardRule::new("Write", GuardKind::Hex, "A5 5A")]),
+            send_discipline: SendDiscipline::Line,
+            variables_enabled: true,
+            hidden_variables: vec!["seq".to_string()],
+            watches: vec![Watch::new("vbat < 3.3 for 5s")],
+            display_encoding: TextEncoding::Utf16Le,
+            encoding_detection: EncodingDetection::Apply,
+            rate_alarm: RateAlarmConfig {
+                enabled: true,
+                rts: Some(false),
+                ..RateAlarmConfig::default()
+            },
+            syslog: SyslogConfig {
+                host: "logs.lab".to_string(),
+                ..SyslogConfig::default()
+            },
+            failover: FailoverConfig {
+                enabled: true,
+                candidates: vec![PortCandidate::Name("COM7".to_string()), PortCandidate::parse("usb:0403:6001").unwrap()],
+            },
+            schedule: vec![{
+                let mut entry = ScheduledSend::new("01 03 00 00 00 02", SendMode::Hex, 10_000);
+                entry.phase_ms = 200;
+                entry
+            }],
+            tx_rate_limit: RateLimit {
+                payloads_per_sec: 10,
+                bytes_per_sec: 0,
+            },
+            keepalive: KeepaliveConfig {
+                enabled: true,
+                text: "AT\r".to_string(),
+                ..KeepaliveConfig::default()
+            },
+            macros: vec![Macro {
+                name: "Version".to_string(),
+                text: r"ATI\x00".to_string(),
+                line_ending: Some(LineEnding::Cr),
+                key: Some(5),
+                ..Macro::default()
+            }],
+            session: SessionTag {
+                name: "Gateway".to_string(),
+                color: Some([60, 110, 220]),
+            },
+        }
+    }
+
+    #[test]
+    fn round_trips_and_recompiles_filters() {
+        let mut settings = Settings {
+            current: slot(),
+            config_slots: [None, Some(slot())],
+            active_slot: 1,
+            decoded_views: HashMap::from([(ProtocolMode::Ubx, DecodedView::Interleaved)]),
+            theme: ThemeSetting::Light,
+            recent_payloads: PayloadRing::default(),
+            send_history: SendHistory::default(),
+            rule_sets: vec![RuleSet::new("Gateway", vec![FilterRule::new("ERR")])],
+            metrics: MetricsConfig {
+                enabled: true,
+                address: "0.0.0.0:9100".to_string(),
+            },
+            live_view: LiveViewConfig {
+                expose_lan: true,
+                token: "abc123".to_string(),
+                ..LiveViewConfig::default()
+            },
+            tcp_bridge: TcpBridgeConfig {
+                read_only: true,
+                ..TcpBridgeConfig::default()
+            },
+            auto_view: AutoViewConfig { printable_percent: 75 },
+            terminal_font_size: 16.5,
+        };
+        settings.recent_payloads.add("AT+RST", SendMode::Ascii, "reset");
+        settings.send_history.record(SendMode::Hex, "01 03");
+        let restored = Settings::from_json(&settings.to_json().unwrap()).unwrap();
+
+        assert_eq!(restored.active_slot, 1);
+        assert_eq!(
+            restored.decoded_views.get(&ProtocolMode::Ubx),
+            Some(&DecodedView::Interleaved)
+        );
+        assert!(!restored.decoded_views.contains_key(&ProtocolMode::Custom));
+        assert_eq!(restored.theme, ThemeSetting::Light);
+        assert_eq!(restored.recent_payloads.entries()[0].description, "reset");
+        assert_eq!(restored.send_history.entries(SendMode::Hex), ["01 03"]);
+        assert!(restored.rule_sets[0].rules[0].is_active());
+        assert!(restored.metrics.enabled);
+        assert_eq!(restored.terminal_font_size, 16.5);
+        assert_eq!(restored.metrics.address, "0.0.0.0:9100");
+        assert!(restored.live_view.expose_lan);
+        assert_eq!(restored.live_view.token, "abc123");
+        assert!(restored.tcp_bridge.read_only);
+        assert_eq!(restored.auto_view.printable_percent, 75);
+        assert!(restored.config_slots[0].is_none());
+        let current = restored.current;
+        assert_eq!(current.selected_port.as_deref(), Some("COM7"));
+        assert_eq!(current.connection_type, ConnectionType::TcpClient);
+        assert_eq!(current.tcp_address, "10.0.0.5:4001");
+        assert_eq!(current.data_bits, DataBits::Seven);
+        assert_eq!(current.custom_framing, CustomFraming::Cobs);
+        assert_eq!(current.framing_rule.delimiter, [0x7E]);
+        assert!(current.monitor_only);
+        assert!(current.filter_rules[0].is_active());
+        assert_eq!(current.pinned_values[0].extract("T=21.0", b"T=21.0").as_deref(), Some("21.0"));
+        assert!(current.send_guard.matching_rule(&[0xA5, 0x5A]).is_some());
+        assert_eq!(current.send_discipline, SendDiscipline::Line);
+        assert!(current.variables_enabled);
+        assert_eq!(current.hidden_variables, ["seq"]);
+        assert!(current.watches[0].error.is_none());
+        assert!(current.rate_alarm.enabled);
+        assert_eq!(current.display_encoding, TextEncoding::Utf16Le);
+        assert_eq!(current.encoding_detection, EncodingDetection::Apply);
+        assert_eq!(current.rate_alarm.rts, Some(false));
+        assert_eq!(current.syslog.host, "logs.lab");
+        assert_eq!(current.syslog.severity_for("ERROR 5"), 3);
+        assert!(current.failover.active());
+        assert_eq!(current.failover.candidates[1].label(), "usb:0403:6001");
+        assert_eq!(current.schedule[0].phase_ms, 200);
+        assert_eq!(current.schedule[0].mode, SendMode::Hex);
+        assert!(current.schedule[0].next_due().is_none());
+        assert_eq!(current.tx_rate_limit.payloads_per_sec, 10);
+        assert_eq!(current.keepalive.text, "AT\r");
+        assert_eq!(current.macros[0].payload(LineEnding::None).unwrap(), b"ATI\x00\r");
+        assert_eq!(current.macros[0].key, Some(5));
+        assert_eq!(current.session.name, "Gateway");
+        assert_eq!(current.session.color, Some([60, 110, 220]));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(Settings::from_json("not json").is_err());
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustcom-settings-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn settings(port: &str) -> Settings {
+        let mut current = slot();
+        current.selected_port = Some(port.to_string());
+        Settings::from_json(&format!(
+            r#"{{"current": {}, "config_slots": [null, null], "active_slot": 0}}"#,
+            serde_json::to_string(&current).unwrap()
+        ))
+        .unwrap()
+    }
+
+    fn port(load: &SettingsLoad) -> Option<&str> {
+        load.settings.as_ref()?.current.selected_port.as_deref()
+    }
+
+    #[test]
+    fn saves_keep_one_backup_and_a_bad_file_falls_back_to_it() {
+        let dir = test_dir("backup");
+        let path = dir.join(SETTINGS_FILE);
+        let load = Settings::load_from(&path);
+        assert!(load.settings.is_none() && load.problem.is_none(), "no file yet");
+
+        settings("COM1").save_to(&path).unwrap();
+        settings("COM2").save_to(&path).unwrap();
+        settings("COM3").save_to(&path).unwrap();
+        assert!(!sibling(&path, ".tmp").exists());
+        assert_eq!(port(&Settings::load_from(&path)), Some("COM3"));
+        assert_eq!(Settings::read(&sibling(&path, ".bak")).unwrap().unwrap().current.selected_port.as_deref(), Some("COM2"));
+
+        // Power lost mid-write under the old scheme: the file is cut short
+        let json = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, &json[..json.len() / 2]).unwrap();
+        let load = Settings::load_from(&path);
+        assert_eq!(port(&load), Some("COM2"));
+        assert!(load.problem.as_deref().unwrap().ends_with("restored the backup"));
+        let corrupt = load.corrupt_path.unwrap();
+        assert_eq!(std::fs::read_to_string(&corrupt).unwrap(), json[..json.len() / 2]);
+        assert!(!path.exists());
+
+        // As after a crash between the two renames of a save: only the backup is left
+        let load = Settings::load_from(&path);
+        assert_eq!(port(&load), Some("COM2"));
+        assert!(load.problem.unwrap().contains("is missing"));
+        assert!(load.corrupt_path.is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_files_start_from_defaults() {
+        let dir = test_dir("corrupt");
+        let path = dir.join(SETTINGS_FILE);
+        let full = settings("COM1").to_json().unwrap();
+        let inputs = [
+            String::new(),
+            "{".to_string(),
+            full[..full.len() - 2].to_string(),
+            full.replace("\"baud_rate\": \"9600\"", "\"baud_rate\": 9600"),
+            "[current]\nbaud_rate = \"9600\"\n".to_string(),
+            "\u{0}\u{0}\u{0}".to_string(),
+        ];
+        for input in inputs {
+            std::fs::write(&path, &input).unwrap();
+            let load = Settings::load_from(&path);
+            assert!(load.settings.is_none(), "{:?}", input);
+            assert!(load.problem.unwrap().starts_with("Invalid settings file"));
+            assert!(load.corrupt_path.is_some());
+        }
+
+        // A file from before most options existed still loads
+        std::fs::write(
+            &path,
+            r#"{"current": {"selected_port": "COM4", "baud_rate": "115200", "data_bits": "Eight",
+                "stop_bits": "One", "parity": "None", "flow_control": "None", "filter_enabled": false,
+                "filter_rules": [], "protocol_mode": "None", "custom_framing": "Slip"},
+               "config_slots": [null, null], "active_slot": 3}"#,
+        )
+        .unwrap();
+        let load = Settings::load_from(&path);
+        assert!(load.problem.is_none(), "{:?}", load.problem);
+        assert_eq!(port(&load), Some("COM4"));
+        let settings = load.settings.unwrap();
+        assert_eq!(settings.active_slot, 1);
+        assert_eq!(settings.terminal_font_size, DEFAULT_TERMINAL_FONT_SIZE);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,178 @@
+//! A streaming RX capture that starts a new file at every line matching a
+//! pattern, e.g. the banner a device prints as it boots, so each file holds
+//! one boot cycle. The matching line opens the new file.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use regex::Regex;
+
+use crate::lines::LineBuffer;
+
+pub const SPLIT_LOG_DIR: &str = "split_logs";
+pub const DEFAULT_SPLIT_TEMPLATE: &str = "boot_{n}_{time}.log";
+
+/// `template` with `{n}` replaced by the zero-padded boot number and `{time}`
+/// by `at`. Without `{n}` the number goes before the extension, so files never
+/// share a name.
+pub fn file_name(template: &str, boot: u32, at: DateTime<Local>) -> String {
+    let template = match template.trim() {
+        "" => DEFAULT_SPLIT_TEMPLATE.to_string(),
+        t if t.contains("{n}") => t.to_string(),
+        t => match t.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => format!("{}_{{n}}.{}", stem, ext),
+            _ => format!("{}_{{n}}", t),
+        },
+    };
+    template
+        .replace("{n}", &format!("{:03}", boot))
+        .replace("{time}", &at.format("%Y%m%d_%H%M%S").to_string())
+}
+
+/// Create `name` in `dir` without overwriting an earlier capture, adding a
+/// numeric suffix when it exists.
+fn create_unique(dir: &Path, name: &str) -> Result<(PathBuf, File), String> {
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+        _ => (name, String::new()),
+    };
+    for attempt in 0..100 {
+        let path = match attempt {
+            0 => dir.join(name),
+            n => dir.join(format!("{}_{}{}", stem, n, ext)),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Cannot create {}: {}", path.display(), e)),
+        }
+    }
+    Err(format!("Too many split logs named {}", name))
+}
+
+/// A split that happened in a chunk: the matching line, and where it ended in
+/// the chunk (it may have begun in an earlier one).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Split {
+    pub banner: Vec<u8>,
+    pub end: usize,
+}
+
+pub struct SplitLog {
+    dir: PathBuf,
+    template: String,
+    pattern: Regex,
+    /// Banners seen; the file before the first one is number 0.
+    pub boot: u32,
+    pub path: PathBuf,
+    file: File,
+    lines: LineBuffer,
+    /// Bytes held in `lines`.
+    held: usize,
+}
+
+impl SplitLog {
+    /// Compile `pattern` and open file number 0 in `dir`.
+    pub fn start(dir: &Path, template: &str, pattern: &str, at: DateTime<Local>) -> Result<Self, String> {
+        if pattern.trim().is_empty() {
+            return Err("Split pattern is empty".to_string());
+        }
+        let pattern = Regex::new(pattern).map_err(|e| format!("Invalid split pattern: {}", e))?;
+        std::fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+        let (path, file) = create_unique(dir, &file_name(template, 0, at))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            template: template.to_string(),
+            pattern,
+            boot: 0,
+            path,
+            file,
+            lines: LineBuffer::default(),
+            held: 0,
+        })
+    }
+
+    fn is_banner(&self, line: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(line);
+        self.pattern.is_match(text.trim_end_matches(['\r', '\n']))
+    }
+
+    /// Append received data a line at a time, moving to a new file at each
+    /// banner line. Returns the last split made, if any.
+    pub fn write(&mut self, data: &[u8], at: DateTime<Local>) -> Result<Option<Split>, String> {
+        let held_before = self.held;
+        let pieces = self.lines.push(data);
+        let mut consumed = 0;
+        let mut split = None;
+        for piece in pieces {
+            consumed += piece.len();
+            if self.is_banner(&piece) {
+                let (path, file) = create_unique(&self.dir, &file_name(&self.template, self.boot + 1, at))?;
+                self.boot += 1;
+                self.path = path;
+                self.file = file;
+                split = Some(Split {
+                    end: consumed - held_before,
+                    banner: piece.clone(),
+                });
+            }
+            self.file
+                .write_all(&piece)
+                .map_err(|e| format!("Split log write failed: {}", e))?;
+        }
+        self.held = held_before + data.len() - consumed;
+        Ok(split)
+    }
+}
+
+impl Drop for SplitLog {
+    /// Keep the unterminated last line.
+    fn drop(&mut self) {
+        if let Some(rest) = self.lines.flush() {
+            let _ = self.file.write_all(&rest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn file_names_carry_the_boot_number() {
+        let at = Local.with_ymd_and_hms(2026, 5, 2, 8, 30, 0).unwrap();
+        assert_eq!(file_name(DEFAULT_SPLIT_TEMPLATE, 3, at), "boot_003_20260502_083000.log");
+        assert_eq!(file_name("dut.txt", 12, at), "dut_012.txt");
+        assert_eq!(file_name("", 1, at), "boot_001_20260502_083000.log");
+    }
+
+    #[test]
+    fn each_banner_starts_a_file_with_the_banner_on_top() {
+        let dir = std::env::temp_dir().join(format!("rustcom-split-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let at = Local::now();
+        assert!(SplitLog::start(&dir, "b{n}.log", "(", at).is_err());
+
+        let mut log = SplitLog::start(&dir, "b{n}.log", r"^U-Boot \d", at).unwrap();
+        assert_eq!(log.write(b"tail of a run\r\nU-Bo", at).unwrap(), None);
+        let split = log.write(b"ot 2024\r\nstarting\n", at).unwrap().unwrap();
+        assert_eq!(split, Split { banner: b"U-Boot 2024\r\n".to_vec(), end: 9 });
+        assert_eq!(log.write(b"U-Boot 2025\nready\nprompt> ", at).unwrap().unwrap().end, 12);
+        assert_eq!(log.boot, 2);
+        drop(log);
+
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("b000.log"), "tail of a run\r\n");
+        assert_eq!(read("b001.log"), "U-Boot 2024\r\nstarting\n");
+        assert_eq!(read("b002.log"), "U-Boot 2025\nready\nprompt> ");
+
+        // A second capture does not overwrite the first
+        let log = SplitLog::start(&dir, "b{n}.log", "U-Boot", at).unwrap();
+        assert_eq!(log.path, dir.join("b000_1.log"));
+        drop(log);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+//! Session statistics in a saveable form, for comparing runs (e.g. before and
+//! after a firmware change) and for reports.
+
+use serde::{Deserialize, Serialize};
+
+/// Relative changes smaller than this count as unchanged.
+pub const SAME_TOLERANCE_PERCENT: f64 = 1.0;
+
+/// Which direction of change is an improvement.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Better {
+    Higher,
+    Lower,
+    /// Informational, e.g. byte counts.
+    Neither,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metric {
+    pub name: String,
+    pub value: f64,
+    /// Shown after the value, e.g. "ms"; empty for counts.
+    #[serde(default)]
+    pub unit: String,
+    pub better: Better,
+}
+
+impl Metric {
+    pub fn new(name: &str, value: f64, unit: &str, better: Better) -> Self {
+        Self {
+            name: name.to_string(),
+            value,
+            unit: unit.to_string(),
+            better,
+        }
+    }
+
+    /// Whole numbers without decimals, everything else to one decimal.
+    pub fn display(&self) -> String {
+        format_value(self.value, &self.unit)
+    }
+}
+
+fn format_value(value: f64, unit: &str) -> String {
+    let number = if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.1}", value)
+    };
+    if unit.is_empty() {
+        number
+    } else {
+        format!("{} {}", number, unit)
+    }
+}
+
+/// How a live value compares with a saved one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Same,
+    Better,
+    Worse,
+    /// Changed, for a metric without a better direction.
+    Different,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    pub delta: f64,
+    /// Relative to the saved value; `None` when that was zero.
+    pub percent: Option<f64>,
+    pub change: Change,
+}
+
+impl Comparison {
+    /// `+12.5%`, or the absolute delta when there is no percentage.
+    pub fn display(&self, unit: &str) -> String {
+        match self.percent {
+            Some(percent) => format!("{:+.1}%", percent),
+            None => {
+                let sign = if self.delta > 0.0 { "+" } else { "" };
+                format!("{}{}", sign, format_value(self.delta, unit))
+            }
+        }
+    }
+}
+
+/// Compare `current` against `baseline`, using `current`'s better direction.
+pub fn compare(current: &Metric, baseline: &Metric) -> Comparison {
+    let delta = current.value - baseline.value;
+    let percent = (baseline.value != 0.0).then(|| delta / baseline.value.abs() * 100.0);
+    let same = match percent {
+        Some(percent) => percent.abs() < SAME_TOLERANCE_PERCENT,
+        None => delta == 0.0,
+    };
+    let change = match current.better {
+        _ if same => Change::Same,
+        Better::Neither => Change::Different,
+        Better::Higher if delta > 0.0 => Change::Better,
+        Better::Lower if delta < 0.0 => Change::Better,
+        _ => Change::Worse,
+    };
+    Comparison {
+        delta,
+        percent,
+        change,
+    }
+}
+
+/// One session's statistics.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    pub port: String,
+    /// Local time the snapshot was taken, `%Y-%m-%d %H:%M:%S`.
+    pub taken_at: String,
+    pub metrics: Vec<Metric>,
+}
+
+impl StatsSnapshot {
+    pub fn get(&self, name: &str) -> Option<&Metric> {
+        self.metrics.iter().find(|metric| metric.name == name)
+    }
+
+    /// "COM3 2024-05-01 14:02:11"
+    pub fn label(&self) -> String {
+        format!("{} {}", self.port, self.taken_at)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Cannot serialize statistics: {}", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Not a statistics snapshot: {}", e))
+    }
+}
+
+/// Metric names of `live` followed by any only found in the saved snapshots,
+/// so a comparison table shows every row once.
+pub fn metric_names<'a>(live: &'a StatsSnapshot, saved: &'a [StatsSnapshot]) -> Vec<&'a str> {
+    let mut names: Vec<&str> = live.metrics.iter().map(|m| m.name.as_str()).collect();
+    for snapshot in saved {
+        for metric in &snapshot.metrics {
+            if !names.contains(&metric.name.as_str()) {
+                names.push(&metric.name);
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(latency: f64, throughput: f64) -> StatsSnapshot {
+        StatsSnapshot {
+            port: "COM3".to_string(),
+            taken_at: "2024-05-01 14:02:11".to_string(),
+            metrics: vec![
+                Metric::new("RX bytes", 4096.0, "", Better::Neither),
+                Metric::new("RX throughput", throughput, "B/s", Better::Higher),
+                Metric::new("Modbus latency", latency, "ms", Better::Lower),
+            ],
+        }
+    }
+
+    #[test]
+    fn deltas_follow_the_better_direction() {
+        let yesterday = snapshot(20.0, 1000.0);
+        let today = snapshot(15.0, 995.0);
+
+        let latency = compare(today.get("Modbus latency").unwrap(), yesterday.get("Modbus latency").unwrap());
+        assert_eq!(latency.change, Change::Better);
+        assert_eq!(latency.display("ms"), "-25.0%");
+
+        // Within the tolerance
+        let throughput = compare(today.get("RX throughput").unwrap(), yesterday.get("RX throughput").unwrap());
+        assert_eq!(throughput.change, Change::Same);
+
+        let slower = Metric::new("RX throughput", 500.0, "B/s", Better::Higher);
+        assert_eq!(compare(&slower, yesterday.get("RX throughput").unwrap()).change, Change::Worse);
+
+        let more = Metric::new("RX bytes", 8192.0, "", Better::Neither);
+        assert_eq!(compare(&more, yesterday.get("RX bytes").unwrap()).change, Change::Different);
+    }
+
+    #[test]
+    fn zero_baselines_show_absolute_deltas() {
+        let before = Metric::new("Modbus timeouts", 0.0, "", Better::Lower);
+        let after = Metric::new("Modbus timeouts", 3.0, "", Better::Lower);
+        let comparison = compare(&after, &before);
+        assert_eq!(comparison.percent, None);
+        assert_eq!(comparison.change, Change::Worse);
+        assert_eq!(comparison.display(""), "+3");
+        assert_eq!(compare(&before, &before).change, Change::Same);
+    }
+
+    #[test]
+    fn values_display_with_units() {
+        assert_eq!(Metric::new("Bursts", 12.0, "", Better::Neither).display(), "12");
+        assert_eq!(Metric::new("Gap", 2.345, "ms", Better::Neither).display(), "2.3 ms");
+    }
+
+    #[test]
+    fn round_trips_and_lists_every_metric() {
+        let live = snapshot(15.0, 990.0);
+        let mut old = snapshot(20.0, 1000.0);
+        old.metrics.push(Metric::new("XOFF pauses", 2.0, "", Better::Lower));
+        let restored = StatsSnapshot::from_json(&old.to_json().unwrap()).unwrap();
+        assert_eq!(restored, old);
+        assert_eq!(restored.label(), "COM3 2024-05-01 14:02:11");
+        assert!(StatsSnapshot::from_json("{}").is_err());
+
+        let saved = [restored];
+        let names = metric_names(&live, &saved);
+        assert_eq!(names, ["RX bytes", "RX throughput", "Modbus latency", "XOFF pauses"]);
+    }
+}
@@ -0,0 +1,314 @@
+//! TCP bridge: shares the serial port with TCP clients. Data received from the
+//! port is sent to every client, and what clients send is handed back to the
+//! app to write to the port. The listener and each client run on their own
+//! threads; the app sees connections and client data through
+//! [`TcpBridge::poll`]. Listens on localhost unless LAN access is turned on.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_TCP_BRIDGE_PORT: u16 = 7777;
+
+/// How often the listener checks whether it should stop.
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+/// Port reads queued for one client; a client this far behind is dropped
+/// rather than sent a stream with holes in it.
+const MAX_QUEUED_CHUNKS: usize = 1024;
+
+/// TCP bridge settings, saved with the global settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TcpBridgeConfig {
+    pub port: u16,
+    /// Listen on every interface instead of 127.0.0.1 only.
+    pub expose_lan: bool,
+    /// Clients only listen: what they send is read and dropped.
+    pub read_only: bool,
+}
+
+impl Default for TcpBridgeConfig {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_TCP_BRIDGE_PORT,
+            expose_lan: false,
+            read_only: false,
+        }
+    }
+}
+
+impl TcpBridgeConfig {
+    pub fn bind_address(&self) -> String {
+        let host = if self.expose_lan { "0.0.0.0" } else { "127.0.0.1" };
+        format!("{}:{}", host, self.port)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BridgeEvent {
+    Connected(SocketAddr),
+    /// Bytes a client sent, to be written to the port.
+    Data(SocketAddr, Vec<u8>),
+    /// The client closed the connection, failed, or fell too far behind.
+    Disconnected(SocketAddr),
+}
+
+struct Client {
+    id: u64,
+    stream: TcpStream,
+    outbox: SyncSender<Arc<[u8]>>,
+}
+
+/// What the listener thread shares with the client threads.
+#[derive(Clone)]
+struct Shared {
+    clients: Arc<Mutex<Vec<Client>>>,
+    events: Sender<BridgeEvent>,
+    read_only: Arc<AtomicBool>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Shared {
+    /// Start the threads for a new client: one writes what is broadcast, the
+    /// other reads what the client sends and cleans up once it goes away.
+    fn add(&self, stream: TcpStream, peer: SocketAddr) {
+        // Accepted sockets may inherit the listener's non-blocking mode
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_nodelay(true);
+        let (Ok(mut reader), Ok(mut writer)) = (stream.try_clone(), stream.try_clone()) else {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        };
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (outbox, inbox) = mpsc::sync_channel::<Arc<[u8]>>(MAX_QUEUED_CHUNKS);
+        self.clients.lock().unwrap().push(Client { id, stream, outbox });
+        let _ = self.events.send(BridgeEvent::Connected(peer));
+
+        std::thread::spawn(move || {
+            for data in inbox {
+                if writer.write_all(&data).is_err() {
+                    break;
+                }
+            }
+            // Wakes the reader, which does the cleanup
+            let _ = writer.shutdown(Shutdown::Both);
+        });
+
+        let shared = self.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if !shared.read_only.load(Ordering::Relaxed)
+                            && shared.events.send(BridgeEvent::Data(peer, buf[..n].to_vec())).is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(_) => break,
+                }
+            }
+            // Dropping the outbox ends the writer
+            shared.clients.lock().unwrap().retain(|client| client.id != id);
+            let _ = reader.shutdown(Shutdown::Both);
+            let _ = shared.events.send(BridgeEvent::Disconnected(peer));
+        });
+    }
+}
+
+/// Listens for clients on its own thread until dropped, which disconnects
+/// every client.
+pub struct TcpBridge {
+    port: u16,
+    shared: Shared,
+    events: Receiver<BridgeEvent>,
+    stop: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl TcpBridge {
+    pub fn start(config: &TcpBridgeConfig) -> Result<Self, String> {
+        let address = config.bind_address();
+        let listener = TcpListener::bind(&address).map_err(|e| format!("Cannot listen on {}: {}", address, e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Cannot listen on {}: {}", address, e))?;
+        let port = listener.local_addr().map_or(config.port, |addr| addr.port());
+        let (events, receiver) = mpsc::channel();
+        let shared = Shared {
+            clients: Arc::default(),
+            events,
+            read_only: Arc::new(AtomicBool::new(config.read_only)),
+            next_id: Arc::default(),
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let shared = shared.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, peer)) => shared.add(stream, peer),
+                        Err(_) => std::thread::sleep(ACCEPT_POLL),
+                    }
+                }
+            })
+        };
+        Ok(Self {
+            port,
+            shared,
+            events: receiver,
+            stop,
+            listener: Some(worker),
+        })
+    }
+
+    /// The port actually bound, e.g. when configured as 0.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.shared.clients.lock().unwrap().len()
+    }
+
+    /// Drop what clients send from now on, or pass it on again.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.shared.read_only.store(read_only, Ordering::Relaxed);
+    }
+
+    /// Queue `data` for every client. A client too far behind to take it is
+    /// disconnected.
+    pub fn broadcast(&self, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        let data: Arc<[u8]> = data.into();
+        for client in self.shared.clients.lock().unwrap().iter() {
+            if let Err(TrySendError::Full(_)) = client.outbox.try_send(data.clone()) {
+                let _ = client.stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+
+    /// Connections, client data and disconnections since the last call.
+    pub fn poll(&self) -> Vec<BridgeEvent> {
+        self.events.try_iter().collect()
+    }
+}
+
+impl Drop for TcpBridge {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(listener) = self.listener.take() {
+            let _ = listener.join();
+        }
+        for client in self.shared.clients.lock().unwrap().iter() {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn start(read_only: bool) -> TcpBridge {
+        TcpBridge::start(&TcpBridgeConfig {
+            port: 0,
+            read_only,
+            ..TcpBridgeConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn connect(bridge: &TcpBridge) -> TcpStream {
+        let stream = TcpStream::connect(("127.0.0.1", bridge.port())).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream
+    }
+
+    /// Poll until `count` events have arrived.
+    fn events(bridge: &TcpBridge, count: usize) -> Vec<BridgeEvent> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut events = Vec::new();
+        while events.len() < count {
+            assert!(Instant::now() < deadline, "timed out waiting for bridge events: {:?}", events);
+            events.extend(bridge.poll());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        events
+    }
+
+    #[test]
+    fn forwards_both_ways_and_notices_disconnects() {
+        let bridge = start(false);
+        let mut first = connect(&bridge);
+        let mut second = connect(&bridge);
+        let peers: Vec<SocketAddr> = events(&bridge, 2)
+            .into_iter()
+            .map(|event| match event {
+                BridgeEvent::Connected(peer) => peer,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert!(peers.contains(&first.local_addr().unwrap()));
+        assert_eq!(bridge.client_count(), 2);
+
+        bridge.broadcast(b"$GPGGA\r\n");
+        for client in [&mut first, &mut second] {
+            let mut buf = [0u8; 8];
+            client.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"$GPGGA\r\n");
+        }
+
+        second.write_all(b"AT\r").unwrap();
+        let from = second.local_addr().unwrap();
+        assert_eq!(events(&bridge, 1), [BridgeEvent::Data(from, b"AT\r".to_vec())]);
+
+        drop(second);
+        assert_eq!(events(&bridge, 1), [BridgeEvent::Disconnected(from)]);
+        assert_eq!(bridge.client_count(), 1);
+
+        // Stopping the bridge closes the remaining client
+        drop(bridge);
+        let mut rest = Vec::new();
+        assert_eq!(first.read_to_end(&mut rest).unwrap(), 0);
+    }
+
+    #[test]
+    fn read_only_drops_what_clients_send() {
+        let bridge = start(true);
+        let mut client = connect(&bridge);
+        assert!(matches!(events(&bridge, 1)[..], [BridgeEvent::Connected(_)]));
+        client.write_all(b"reboot\r").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(bridge.poll().is_empty());
+
+        bridge.set_read_only(false);
+        client.write_all(b"AT\r").unwrap();
+        assert!(matches!(&events(&bridge, 1)[..], [BridgeEvent::Data(_, data)] if data == b"AT\r"));
+    }
+
+    #[test]
+    fn reports_a_port_in_use() {
+        let bridge = start(false);
+        let taken = TcpBridgeConfig {
+            port: bridge.port(),
+            ..TcpBridgeConfig::default()
+        };
+        let error = TcpBridge::start(&taken).err().unwrap();
+        assert!(error.starts_with("Cannot listen on 127.0.0.1:"), "{}", error);
+        assert_eq!(taken.bind_address(), format!("127.0.0.1:{}", bridge.port()));
+    }
+}
@@ -0,0 +1,311 @@
+//! TCP client transport: a raw socket to a serial server such as ser2net or
+//! ESP-Link, behind the same `SerialPort` trait as a real port, so reading,
+//! writing and reconnecting need no second code path.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+/// How long connecting to one resolved address may take.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What Connect opens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ConnectionType {
+    #[default]
+    Serial,
+    TcpClient,
+    /// TCP with the port settings and control lines sent over Telnet.
+    Rfc2217,
+}
+
+impl ConnectionType {
+    pub const ALL: [ConnectionType; 3] = [ConnectionType::Serial, ConnectionType::TcpClient, ConnectionType::Rfc2217];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionType::Serial => "Serial",
+            ConnectionType::TcpClient => "TCP client",
+            ConnectionType::Rfc2217 => "RFC 2217",
+        }
+    }
+
+    /// Whether Connect opens a socket to a host:port rather than a port.
+    pub fn is_network(&self) -> bool {
+        *self != ConnectionType::Serial
+    }
+}
+
+/// Line settings as last set. A raw socket has none, but they are kept so the
+/// port reports what was asked for.
+#[derive(Debug, Clone, Copy)]
+struct LineSettings {
+    baud_rate: u32,
+    data_bits: DataBits,
+    flow_control: FlowControl,
+    parity: Parity,
+    stop_bits: StopBits,
+    timeout: Duration,
+}
+
+/// A connected socket. Reads time out like a port's, and the peer closing the
+/// connection is a read error rather than an endless run of empty reads.
+/// Control lines and break have no meaning over a raw socket and are accepted
+/// without effect.
+pub struct TcpPort {
+    stream: TcpStream,
+    address: String,
+    settings: LineSettings,
+}
+
+impl TcpPort {
+    /// Connect to `address` as `host:port`, trying each address it resolves to,
+    /// with reads and writes timing out after `timeout`.
+    pub fn connect(address: &str, baud_rate: u32, timeout: Duration) -> Result<Self, String> {
+        let address = address.trim();
+        let mut port = Self {
+            stream: open_stream(address)?,
+            address: address.to_string(),
+            settings: LineSettings {
+                baud_rate,
+                data_bits: DataBits::Eight,
+                flow_control: FlowControl::None,
+                parity: Parity::None,
+                stop_bits: StopBits::One,
+                timeout: Duration::ZERO,
+            },
+        };
+        port.set_timeout(timeout)
+            .map_err(|e| format!("Cannot set up {}: {}", address, e))?;
+        Ok(port)
+    }
+}
+
+/// A socket connected to `address`, trying each address it resolves to.
+pub(crate) fn open_stream(address: &str) -> Result<TcpStream, String> {
+    let addrs: Vec<SocketAddr> = address
+        .to_socket_addrs()
+        .map_err(|e| format!("Invalid address {}: {}", address, e))?
+        .collect();
+    let mut last_error = format!("{} did not resolve", address);
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                let _ = stream.set_nodelay(true);
+                return Ok(stream);
+            }
+            Err(e) => last_error = format!("Cannot connect to {}: {}", addr, e),
+        }
+    }
+    Err(last_error)
+}
+
+/// Set the read and write timeouts of `stream` as a port timeout. A zero
+/// timeout would mean blocking forever to a socket, so it becomes 1 ms.
+pub(crate) fn set_socket_timeout(stream: &TcpStream, timeout: Duration) -> serialport::Result<()> {
+    let socket_timeout = Some(timeout.max(Duration::from_millis(1)));
+    stream.set_read_timeout(socket_timeout).map_err(io_error)?;
+    stream.set_write_timeout(socket_timeout).map_err(io_error)
+}
+
+/// Sockets report an expired timeout as `WouldBlock` on Unix and `TimedOut`
+/// on Windows; ports always say `TimedOut`.
+pub(crate) fn port_error(e: std::io::Error) -> std::io::Error {
+    if e.kind() == ErrorKind::WouldBlock {
+        ErrorKind::TimedOut.into()
+    } else {
+        e
+    }
+}
+
+impl Read for TcpPort {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.stream.read(buf) {
+            Ok(0) if !buf.is_empty() => Err(std::io::Error::new(ErrorKind::UnexpectedEof, "connection closed")),
+            result => result.map_err(port_error),
+        }
+    }
+}
+
+impl Write for TcpPort {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stream.write(buf).map_err(port_error)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush().map_err(port_error)
+    }
+}
+
+pub(crate) fn io_error(e: std::io::Error) -> serialport::Error {
+    serialport::Error::new(serialport::ErrorKind::Io(e.kind()), e.to_string())
+}
+
+impl SerialPort for TcpPort {
+    fn name(&self) -> Option<String> {
+        Some(self.address.clone())
+    }
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(self.settings.baud_rate)
+    }
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(self.settings.data_bits)
+    }
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(self.settings.flow_control)
+    }
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(self.settings.parity)
+    }
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(self.settings.stop_bits)
+    }
+    fn timeout(&self) -> Duration {
+        self.settings.timeout
+    }
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.settings.baud_rate = baud_rate;
+        Ok(())
+    }
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        self.settings.data_bits = data_bits;
+        Ok(())
+    }
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.settings.flow_control = flow_control;
+        Ok(())
+    }
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        self.settings.parity = parity;
+        Ok(())
+    }
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        self.settings.stop_bits = stop_bits;
+        Ok(())
+    }
+    /// Applies to reads and writes alike.
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        set_socket_timeout(&self.stream, timeout)?;
+        self.settings.timeout = timeout;
+        Ok(())
+    }
+    fn write_request_to_send(&mut self, _: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn write_data_terminal_ready(&mut self, _: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+    fn clear(&self, _: ClearBuffer) -> serialport::Result<()> {
+        Ok(())
+    }
+    /// A handle on the same connection, as for the reader thread.
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(Self {
+            stream: self.stream.try_clone().map_err(io_error)?,
+            address: self.address.clone(),
+            settings: self.settings,
+        }))
+    }
+    fn set_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn clear_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// A port connected to a local listener, and the server side of it.
+    fn pair() -> (TcpPort, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let port = TcpPort::connect(&address, 115_200, Duration::from_millis(20)).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        (port, server)
+    }
+
+    /// Read until `count` bytes arrived, passing over timeouts.
+    fn read_some(port: &mut dyn SerialPort, count: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 64];
+        for _ in 0..500 {
+            match port.read(&mut buf) {
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                Err(e) => assert_eq!(e.kind(), ErrorKind::TimedOut),
+            }
+            if data.len() >= count {
+                break;
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn carries_data_both_ways_and_times_out_like_a_port() {
+        let (mut port, mut server) = pair();
+        let mut buf = [0u8; 16];
+        assert_eq!(port.read(&mut buf).unwrap_err().kind(), ErrorKind::TimedOut);
+
+        port.write_all(b"AT\r").unwrap();
+        let mut received = [0u8; 3];
+        server.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"AT\r");
+
+        // A clone reads the same connection, as the reader thread does
+        let mut reader = port.try_clone().unwrap();
+        server.write_all(b"OK\r\n").unwrap();
+        assert_eq!(read_some(&mut *reader, 4), b"OK\r\n");
+
+        assert_eq!(port.name().as_deref(), Some(server.local_addr().unwrap().to_string().as_str()));
+        assert_eq!(port.baud_rate().unwrap(), 115_200);
+        assert_eq!(reader.timeout(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn the_peer_closing_is_a_read_error() {
+        let (mut port, server) = pair();
+        drop(server);
+        let mut buf = [0u8; 16];
+        let error = (0..500)
+            .find_map(|_| port.read(&mut buf).err().filter(|e| e.kind() != ErrorKind::TimedOut))
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn reports_bad_and_refused_addresses() {
+        let error = TcpPort::connect("no-port-here", 9600, Duration::from_millis(10)).err().unwrap();
+        assert!(error.starts_with("Invalid address no-port-here"), "{}", error);
+
+        // A port that was just free again is very likely refused
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let error = TcpPort::connect(&address.to_string(), 9600, Duration::from_millis(10)).err().unwrap();
+        assert!(error.starts_with(&format!("Cannot connect to {}", address)), "{}", error);
+    }
+}
@@ -0,0 +1,320 @@
+//! Send templates: macro text with `{placeholders}` asked for at send time,
+//! for commands that differ only in a parameter, like `SET CHAN {n:int(1..16)}\r`.
+//!
+//! A placeholder is `{name}`, `{name:type}` or either with `=default` after
+//! it. Types are `text` (the default), `int`, `int(MIN..MAX)` with both ends
+//! inclusive, `hex` for bytes, and `A|B|C` for one of a fixed set of words.
+//! A name used again refers to the same value. `{{` and `}}` are literal
+//! braces.
+
+use crate::hex::parse_hex_input;
+use crate::payloads::SendMode;
+
+/// The placeholder syntax, for hover text.
+pub const SYNTAX_HELP: &str = "{name} asks for text, {name:int} or {name:int(1..16)} for a whole number \
+                               (range inclusive), {name:hex} for bytes and {name:ON|OFF} for one of a set. \
+                               Add =default after it, e.g. {n:int(1..16)=1}. {{ and }} are literal braces.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaceholderKind {
+    Text,
+    Int { min: i64, max: i64 },
+    Hex,
+    Choice(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placeholder {
+    pub name: String,
+    pub kind: PlaceholderKind,
+    pub default: Option<String>,
+}
+
+impl Placeholder {
+    /// What the prompt shows next to the field, e.g. `1..16` or `ON|OFF`.
+    pub fn hint(&self) -> String {
+        match &self.kind {
+            PlaceholderKind::Text => "text".to_string(),
+            PlaceholderKind::Int { min: i64::MIN, max: i64::MAX } => "integer".to_string(),
+            PlaceholderKind::Int { min, max } => format!("{}..{}", min, max),
+            PlaceholderKind::Hex => "hex bytes".to_string(),
+            PlaceholderKind::Choice(options) => options.join("|"),
+        }
+    }
+
+    /// `value` checked against the type and written the way the template's
+    /// `mode` reads it: hex bytes become `\xHH` escapes in ASCII text, and
+    /// integers become big-endian bytes in a hex template, as many as the
+    /// range maximum needs whatever the value, so later fields keep their
+    /// place. An empty value takes the default.
+    pub fn render(&self, value: &str, mode: SendMode) -> Result<String, String> {
+        let value = match (value.trim(), &self.default) {
+            ("", Some(default)) => default.as_str(),
+            ("", None) => return Err(format!("No value for {{{}}}", self.name)),
+            (value, _) => value,
+        };
+        match &self.kind {
+            PlaceholderKind::Text => Ok(value.to_string()),
+            PlaceholderKind::Int { min, max } => {
+                let n = parse_int(value).ok_or_else(|| format!("{} must be a whole number", self.name))?;
+                if n < *min || n > *max {
+                    return Err(format!("{} must be {}..{}", self.name, min, max));
+                }
+                match mode {
+                    SendMode::Ascii => Ok(n.to_string()),
+                    SendMode::Hex if n < 0 => Err(format!("{} cannot be negative in a hex template", self.name)),
+                    SendMode::Hex => {
+                        let width = int_width(*max);
+                        let bytes: Vec<String> = n.to_be_bytes()[8 - width..]
+                            .iter()
+                            .map(|b| format!("{:02X}", b))
+                            .collect();
+                        Ok(bytes.join(" "))
+                    }
+                }
+            }
+            PlaceholderKind::Hex => {
+                let bytes = parse_hex_input(value).map_err(|e| format!("{}: {}", self.name, e))?;
+                let sep = if mode == SendMode::Hex { " " } else { "" };
+                let escaped: Vec<String> = bytes
+                    .iter()
+                    .map(|b| match mode {
+                        SendMode::Ascii => format!("\\x{:02X}", b),
+                        SendMode::Hex => format!("{:02X}", b),
+                    })
+                    .collect();
+                Ok(escaped.join(sep))
+            }
+            PlaceholderKind::Choice(options) => options
+                .iter()
+                .find(|option| option.eq_ignore_ascii_case(value))
+                .cloned()
+                .ok_or_else(|| format!("{} must be one of {}", self.name, options.join(", "))),
+        }
+    }
+}
+
+/// Bytes needed for any value up to `max`, at least one.
+fn int_width(max: i64) -> usize {
+    let bits = 64 - max.max(1).leading_zeros() as usize;
+    bits.div_ceil(8)
+}
+
+/// Decimal, or hex after `0x`.
+fn parse_int(value: &str) -> Option<i64> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let n = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    Some(if negative { -n } else { n })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Slot(usize),
+}
+
+/// Parsed template text: literal runs and references to its placeholders.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub placeholders: Vec<Placeholder>,
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut template = Template {
+            placeholders: Vec::new(),
+            segments: Vec::new(),
+        };
+        let mut literal = String::new();
+        let mut chars = text.char_indices().peekable();
+        while let Some((at, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|&(_, c)| c == '{').is_some() => literal.push('{'),
+                '}' if chars.next_if(|&(_, c)| c == '}').is_some() => literal.push('}'),
+                '}' => return Err(format!("Unmatched }} at column {}", at + 1)),
+                '{' => {
+                    let mut spec = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((inner, '{')) => return Err(format!("Nested placeholder at column {}", inner + 1)),
+                            Some((_, c)) => spec.push(c),
+                            None => return Err(format!("Unclosed placeholder at column {}", at + 1)),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        template.segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    let slot = template.declare(&spec, at + 1)?;
+                    template.segments.push(Segment::Slot(slot));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            template.segments.push(Segment::Literal(literal));
+        }
+        Ok(template)
+    }
+
+    /// The index of the placeholder `spec` names, added if new. A later bare
+    /// `{name}` reuses the first one's type; two different types are an error.
+    fn declare(&mut self, spec: &str, column: usize) -> Result<usize, String> {
+        let (head, default) = match spec.split_once('=') {
+            Some((head, default)) => (head, Some(default.trim().to_string())),
+            None => (spec, None),
+        };
+        let (name, kind) = match head.split_once(':') {
+            Some((name, kind)) => (name.trim(), Some(parse_kind(name.trim(), kind.trim())?)),
+            None => (head.trim(), None),
+        };
+        if name.is_empty() {
+            return Err(format!("Placeholder without a name at column {}", column));
+        }
+        if let Some(index) = self.placeholders.iter().position(|p| p.name == name) {
+            let existing = &self.placeholders[index];
+            if kind.as_ref().is_some_and(|kind| *kind != existing.kind)
+                || default.as_ref().is_some_and(|default| Some(default) != existing.default.as_ref())
+            {
+                return Err(format!("{{{}}} is declared twice differently", name));
+            }
+            return Ok(index);
+        }
+        let placeholder = Placeholder {
+            name: name.to_string(),
+            kind: kind.unwrap_or(PlaceholderKind::Text),
+            default,
+        };
+        if let Some(default) = &placeholder.default {
+            placeholder
+                .render(default, SendMode::Ascii)
+                .map_err(|e| format!("Default for {{{}}}: {}", name, e))?;
+        }
+        self.placeholders.push(placeholder);
+        Ok(self.placeholders.len() - 1)
+    }
+
+    /// The text with `values` (one per placeholder, in order; missing or empty
+    /// ones take the default) filled in for `mode` to parse.
+    pub fn resolve(&self, values: &[String], mode: SendMode) -> Result<String, String> {
+        let rendered = self
+            .placeholders
+            .iter()
+            .enumerate()
+            .map(|(i, p)| p.render(values.get(i).map_or("", String::as_str), mode))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(text) => text.as_str(),
+                Segment::Slot(index) => rendered[*index].as_str(),
+            })
+            .collect())
+    }
+}
+
+fn parse_kind(name: &str, kind: &str) -> Result<PlaceholderKind, String> {
+    if kind.contains('|') {
+        let options: Vec<String> = kind.split('|').map(|option| option.trim().to_string()).collect();
+        if options.iter().any(String::is_empty) {
+            return Err(format!("Empty choice in {{{}}}", name));
+        }
+        return Ok(PlaceholderKind::Choice(options));
+    }
+    match kind {
+        "" | "text" => return Ok(PlaceholderKind::Text),
+        "hex" => return Ok(PlaceholderKind::Hex),
+        "int" => {
+            return Ok(PlaceholderKind::Int {
+                min: i64::MIN,
+                max: i64::MAX,
+            })
+        }
+        _ => {}
+    }
+    let range = kind
+        .strip_prefix("int(")
+        .and_then(|rest| rest.strip_suffix(')'))
+        .ok_or_else(|| format!("Unknown type \"{}\" for {{{}}}", kind, name))?;
+    let bounds = range
+        .split_once("..")
+        .and_then(|(min, max)| Some((parse_int(min.trim())?, parse_int(max.trim())?)));
+    match bounds {
+        Some((min, max)) if min <= max => Ok(PlaceholderKind::Int { min, max }),
+        _ => Err(format!("Bad range \"{}\" for {{{}}}: expected MIN..MAX", range, name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn placeholders_are_typed_checked_and_filled_in() {
+        let t = Template::parse(r"SET CHAN {n:int(1..16)=3} {mode:ON|OFF} {data:hex}\r").unwrap();
+        assert_eq!(t.placeholders.len(), 3);
+        assert_eq!(t.placeholders[0].hint(), "1..16");
+        assert_eq!(t.placeholders[1].hint(), "ON|OFF");
+        assert_eq!(
+            t.resolve(&values(&["12", "off", "01 0A"]), SendMode::Ascii).unwrap(),
+            r"SET CHAN 12 OFF \x01\x0A\r"
+        );
+        assert_eq!(t.resolve(&values(&["", "ON", "FF"]), SendMode::Ascii).unwrap(), r"SET CHAN 3 ON \xFF\r");
+        assert_eq!(
+            t.resolve(&values(&["17", "ON", "FF"]), SendMode::Ascii).unwrap_err(),
+            "n must be 1..16"
+        );
+        assert_eq!(
+            t.resolve(&values(&["1", "AUTO", "FF"]), SendMode::Ascii).unwrap_err(),
+            "mode must be one of ON, OFF"
+        );
+        assert!(t.resolve(&values(&["x", "ON", "FF"]), SendMode::Ascii).is_err());
+        assert!(t.resolve(&values(&["1", "ON", "GG"]), SendMode::Ascii).is_err());
+
+        // An integer takes the bytes its range needs, whatever the value
+        let hex = Template::parse("01 06 {reg:int(0..0xFFFF)} {value:hex}").unwrap();
+        assert_eq!(hex.resolve(&values(&["0x1", "00 2A"]), SendMode::Hex).unwrap(), "01 06 00 01 00 2A");
+        assert_eq!(hex.resolve(&values(&["4660", "1"]), SendMode::Hex).unwrap(), "01 06 12 34 01");
+        let narrow = Template::parse("{a:int(0..255)} {b:int(0..256)} {c:int}").unwrap();
+        assert_eq!(
+            narrow.resolve(&values(&["7", "7", "7"]), SendMode::Hex).unwrap(),
+            "07 00 07 00 00 00 00 00 00 00 07"
+        );
+        assert!(narrow.resolve(&values(&["7", "7", "-1"]), SendMode::Hex).is_err());
+    }
+
+    #[test]
+    fn missing_repeated_and_nested_placeholders() {
+        let t = Template::parse("{a}-{a}:{b=x}").unwrap();
+        assert_eq!(t.placeholders.len(), 2, "a repeated name is one value");
+        assert_eq!(t.resolve(&values(&["1"]), SendMode::Ascii).unwrap(), "1-1:x");
+        assert_eq!(t.resolve(&[], SendMode::Ascii).unwrap_err(), "No value for {a}");
+        assert_eq!(t.resolve(&values(&[" "]), SendMode::Ascii).unwrap_err(), "No value for {a}");
+
+        assert_eq!(Template::parse("{{}} {n}").unwrap().resolve(&values(&["1"]), SendMode::Ascii).unwrap(), "{} 1");
+        assert_eq!(Template::parse("plain").unwrap().resolve(&[], SendMode::Ascii).unwrap(), "plain");
+
+        assert_eq!(Template::parse("{a{b}}").unwrap_err(), "Nested placeholder at column 3");
+        assert_eq!(Template::parse("{a={b}}").unwrap_err(), "Nested placeholder at column 4");
+        assert_eq!(Template::parse("X {a").unwrap_err(), "Unclosed placeholder at column 3");
+        assert_eq!(Template::parse("a}").unwrap_err(), "Unmatched } at column 2");
+        assert_eq!(Template::parse("{:int}").unwrap_err(), "Placeholder without a name at column 1");
+        assert_eq!(Template::parse("{a:int} {a:hex}").unwrap_err(), "{a} is declared twice differently");
+        assert!(Template::parse("{a:float}").is_err());
+        assert!(Template::parse("{a:int(5..1)}").is_err());
+        assert!(Template::parse("{a:int(1..9)=10}").is_err(), "the default is checked too");
+        assert!(Template::parse("{a:ON||OFF}").is_err());
+    }
+}
@@ -0,0 +1,384 @@
+//! Text encodings for the terminal: a streaming UTF-16 decoder that carries
+//! odd bytes and split surrogate pairs over from one read to the next, UTF-16
+//! line splitting for the text views, and detection of UTF-16 at the start of
+//! a session from a byte order mark or the NUL bytes every other byte of
+//! mostly-ASCII text.
+
+use serde::{Deserialize, Serialize};
+
+/// Bytes looked at for the NUL pattern; detection gives up after this many.
+pub const DETECT_SAMPLE: usize = 16;
+/// Share of code units that must have a NUL in the high byte and a non-NUL
+/// in the low byte.
+const NUL_PATTERN_RATIO: f32 = 0.75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl TextEncoding {
+    pub const ALL: [TextEncoding; 3] = [TextEncoding::Utf8, TextEncoding::Utf16Le, TextEncoding::Utf16Be];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf16Le => "UTF-16LE",
+            TextEncoding::Utf16Be => "UTF-16BE",
+        }
+    }
+
+    /// The bytes of a newline code unit, for the UTF-16 encodings.
+    pub fn newline_unit(&self) -> Option<[u8; 2]> {
+        match self {
+            TextEncoding::Utf8 => None,
+            TextEncoding::Utf16Le => Some([b'\n', 0]),
+            TextEncoding::Utf16Be => Some([0, b'\n']),
+        }
+    }
+}
+
+/// What to do when a session looks like UTF-16 while decoding as UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EncodingDetection {
+    Off,
+    #[default]
+    Offer,
+    Apply,
+}
+
+impl EncodingDetection {
+    pub const ALL: [EncodingDetection; 3] = [EncodingDetection::Off, EncodingDetection::Offer, EncodingDetection::Apply];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EncodingDetection::Off => "Off",
+            EncodingDetection::Offer => "Offer",
+            EncodingDetection::Apply => "Apply",
+        }
+    }
+}
+
+/// Decodes UTF-16 arriving in pieces of any length.
+#[derive(Debug, Clone)]
+pub struct Utf16Decoder {
+    big_endian: bool,
+    /// The first byte of a code unit whose second byte has not arrived.
+    odd_byte: Option<u8>,
+    /// A high surrogate waiting for its low half.
+    high: Option<u16>,
+    /// Nothing decoded yet, so a byte order mark is dropped.
+    at_start: bool,
+    /// The next byte ends a code unit begun before the decoder started.
+    skip: bool,
+}
+
+impl Utf16Decoder {
+    /// `None` for UTF-8, which needs no decoder.
+    pub fn new(encoding: TextEncoding) -> Option<Self> {
+        Self::new_at(encoding, 0)
+    }
+
+    /// A decoder for a stream already `offset` bytes in. An odd offset is the
+    /// middle of a code unit, whose second byte is dropped; only at offset 0
+    /// is a byte order mark expected.
+    pub fn new_at(encoding: TextEncoding, offset: u64) -> Option<Self> {
+        let big_endian = match encoding {
+            TextEncoding::Utf8 => return None,
+            TextEncoding::Utf16Le => false,
+            TextEncoding::Utf16Be => true,
+        };
+        Some(Self {
+            big_endian,
+            odd_byte: None,
+            high: None,
+            at_start: offset == 0,
+            skip: offset % 2 == 1,
+        })
+    }
+
+    /// The text completed by `data`. Unpaired surrogates become U+FFFD; a
+    /// unit or pair cut off at the end is kept for the next call.
+    pub fn decode(&mut self, data: &[u8]) -> String {
+        let mut out = String::with_capacity(data.len() / 2 + 1);
+        let mut bytes = data.iter().copied();
+        if self.skip && bytes.next().is_some() {
+            self.skip = false;
+        }
+        loop {
+            let first = match self.odd_byte.take() {
+                Some(byte) => byte,
+                None => match bytes.next() {
+                    Some(byte) => byte,
+                    None => break,
+                },
+            };
+            let Some(second) = bytes.next() else {
+                self.odd_byte = Some(first);
+                break;
+            };
+            let unit = if self.big_endian {
+                u16::from_be_bytes([first, second])
+            } else {
+                u16::from_le_bytes([first, second])
+            };
+            self.push_unit(&mut out, unit);
+        }
+        out
+    }
+
+    fn push_unit(&mut self, out: &mut String, unit: u16) {
+        if std::mem::take(&mut self.at_start) && unit == 0xFEFF {
+            return;
+        }
+        match unit {
+            0xD800..=0xDBFF => {
+                if self.high.replace(unit).is_some() {
+                    out.push(char::REPLACEMENT_CHARACTER);
+                }
+            }
+            0xDC00..=0xDFFF => match self.high.take() {
+                Some(high) => {
+                    let code = 0x10000 + ((u32::from(high) - 0xD800) << 10) + (u32::from(unit) - 0xDC00);
+                    out.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                None => out.push(char::REPLACEMENT_CHARACTER),
+            },
+            _ => {
+                if self.high.take().is_some() {
+                    out.push(char::REPLACEMENT_CHARACTER);
+                }
+                out.push(char::from_u32(u32::from(unit)).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+        }
+    }
+}
+
+/// A line of UTF-16 data and its text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Utf16Line<'a> {
+    pub bytes: &'a [u8],
+    pub text: String,
+    /// Ends with a newline code unit; only the last line may not.
+    pub complete: bool,
+}
+
+/// Split UTF-16 `data`, which is `offset` bytes into its stream, into lines
+/// that each end after a newline code unit. A newline only counts on a code
+/// unit boundary of the stream; a unit cut off at the end is left out of the
+/// last line's text until the rest arrives. `None` for UTF-8.
+pub fn split_lines_utf16(data: &[u8], encoding: TextEncoding, offset: u64) -> Option<Vec<Utf16Line<'_>>> {
+    let newline = encoding.newline_unit()?;
+    let mut decoder = Utf16Decoder::new_at(encoding, offset)?;
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut at = (offset % 2) as usize;
+    while at + 1 < data.len() {
+        at += 2;
+        if data[at - 2..at] == newline {
+            let bytes = &data[start..at];
+            lines.push(Utf16Line {
+                bytes,
+                text: decoder.decode(bytes),
+                complete: true,
+            });
+            start = at;
+        }
+    }
+    if start < data.len() {
+        let bytes = &data[start..];
+        lines.push(Utf16Line {
+            bytes,
+            text: decoder.decode(bytes),
+            complete: false,
+        });
+    }
+    Some(lines)
+}
+
+/// Why a session was taken for UTF-16.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectedEncoding {
+    pub encoding: TextEncoding,
+    pub bom: bool,
+    /// Bytes of the session before the read that gave the verdict.
+    pub offset: usize,
+}
+
+impl DetectedEncoding {
+    pub fn reason(&self) -> &'static str {
+        if self.bom {
+            "byte order mark"
+        } else {
+            "NUL every other byte"
+        }
+    }
+}
+
+/// Looks at the first bytes of a session for UTF-16.
+#[derive(Debug, Default)]
+pub struct EncodingDetector {
+    sample: Vec<u8>,
+    done: bool,
+}
+
+impl EncodingDetector {
+    /// Start over for a new session.
+    pub fn reset(&mut self) {
+        self.sample.clear();
+        self.done = false;
+    }
+
+    /// Add received bytes. Returns a verdict once, as soon as the sample
+    /// holds one; after `DETECT_SAMPLE` bytes without one it stops looking.
+    pub fn feed(&mut self, data: &[u8]) -> Option<DetectedEncoding> {
+        if self.done || data.is_empty() {
+            return None;
+        }
+        let offset = self.sample.len();
+        self.sample.extend_from_slice(&data[..data.len().min(DETECT_SAMPLE - offset)]);
+        let detected = detect(&self.sample).map(|detected| DetectedEncoding { offset, ..detected });
+        if detected.is_some() || self.sample.len() >= DETECT_SAMPLE {
+            self.done = true;
+            self.sample = Vec::new();
+        }
+        detected
+    }
+}
+
+/// A byte order mark at the start, or a full sample in which nearly every
+/// code unit is an ASCII character with a NUL beside it.
+pub fn detect(sample: &[u8]) -> Option<DetectedEncoding> {
+    let bom = |encoding| {
+        Some(DetectedEncoding {
+            encoding,
+            bom: true,
+            offset: 0,
+        })
+    };
+    match sample {
+        [0xFF, 0xFE, ..] => return bom(TextEncoding::Utf16Le),
+        [0xFE, 0xFF, ..] => return bom(TextEncoding::Utf16Be),
+        _ if sample.len() < DETECT_SAMPLE => return None,
+        _ => {}
+    }
+    let units = sample.len() / 2;
+    let count = |nul_at: usize| {
+        sample
+            .chunks_exact(2)
+            .filter(|unit| unit[nul_at] == 0 && unit[1 - nul_at] != 0)
+            .count()
+    };
+    let needed = (units as f32 * NUL_PATTERN_RATIO).ceil() as usize;
+    let encoding = if count(1) >= needed {
+        TextEncoding::Utf16Le
+    } else if count(0) >= needed {
+        TextEncoding::Utf16Be
+    } else {
+        return None;
+    };
+    Some(DetectedEncoding {
+        encoding,
+        bom: false,
+        offset: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(text: &str, encoding: TextEncoding) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| match encoding {
+                TextEncoding::Utf16Be => unit.to_be_bytes(),
+                _ => unit.to_le_bytes(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_surrogate_pairs_and_odd_chunks_decode_whole() {
+        let text = "T=21°C 🌡 ok\r\n𝄞";
+        for encoding in [TextEncoding::Utf16Le, TextEncoding::Utf16Be] {
+            let bytes = encode(text, encoding);
+            // Every split point, including inside the 4-byte surrogate pairs
+            for split in 0..=bytes.len() {
+                let mut decoder = Utf16Decoder::new(encoding).unwrap();
+                let decoded = decoder.decode(&bytes[..split]) + &decoder.decode(&bytes[split..]);
+                assert_eq!(decoded, text, "{:?} split at {}", encoding, split);
+            }
+            // One byte at a time
+            let mut decoder = Utf16Decoder::new(encoding).unwrap();
+            let decoded: String = bytes.iter().map(|&b| decoder.decode(&[b])).collect();
+            assert_eq!(decoded, text);
+        }
+        assert!(Utf16Decoder::new(TextEncoding::Utf8).is_none());
+    }
+
+    #[test]
+    fn incomplete_pieces_wait_and_unpaired_surrogates_are_replaced() {
+        let mut decoder = Utf16Decoder::new(TextEncoding::Utf16Le).unwrap();
+        // BOM, then "A" and the high half of U+1F321
+        assert_eq!(decoder.decode(&[0xFF, 0xFE, 0x41, 0x00, 0x3C, 0xD8, 0x21]), "A");
+        assert_eq!(decoder.decode(&[0xDF]), "🌡");
+        // A lone low surrogate, and a high one followed by a letter
+        assert_eq!(decoder.decode(&[0x00, 0xDC, 0x3C, 0xD8, 0x42, 0x00]), "\u{FFFD}\u{FFFD}B");
+        // A BOM later on is text, not a mark
+        assert_eq!(decoder.decode(&[0xFF, 0xFE]), "\u{FEFF}");
+    }
+
+    #[test]
+    fn lines_split_on_newline_units_only() {
+        for encoding in [TextEncoding::Utf16Le, TextEncoding::Utf16Be] {
+            let mut bytes = encode("\u{FEFF}ok\r\nĊ\n", encoding);
+            bytes.push(b'x');
+            // U+010A holds a 0x0A byte, but not as a whole unit
+            let lines = split_lines_utf16(&bytes, encoding, 0).unwrap();
+            let texts: Vec<(&str, bool)> = lines.iter().map(|line| (line.text.as_str(), line.complete)).collect();
+            assert_eq!(texts, [("ok\r\n", true), ("Ċ\n", true), ("", false)], "{:?}", encoding);
+            assert_eq!(lines[1].bytes.len(), 4);
+
+            // Resuming mid-unit drops the half unit and keeps the alignment
+            let tail = split_lines_utf16(&bytes[9..], encoding, 9).unwrap();
+            assert_eq!((tail[0].text.as_str(), tail[0].bytes.len()), ("Ċ\n", 5));
+        }
+        assert!(split_lines_utf16(b"ok\n", TextEncoding::Utf8, 0).is_none());
+    }
+
+    #[test]
+    fn detects_a_bom_or_the_nul_pattern() {
+        let mut detector = EncodingDetector::default();
+        assert_eq!(detector.feed(&[0xFE]), None);
+        assert_eq!(
+            detector.feed(&[0xFF, 0x00, 0x41]),
+            Some(DetectedEncoding {
+                encoding: TextEncoding::Utf16Be,
+                bom: true,
+                offset: 1
+            })
+        );
+        // One verdict per session
+        assert_eq!(detector.feed(&[0xFE, 0xFF]), None);
+
+        let le = encode("Controller ready\r\n", TextEncoding::Utf16Le);
+        detector.reset();
+        assert_eq!(detector.feed(&le[..9]), None);
+        let detected = detector.feed(&le[9..]).unwrap();
+        assert_eq!(detected.encoding, TextEncoding::Utf16Le);
+        assert_eq!(detected.reason(), "NUL every other byte");
+        assert_eq!(detected.offset, 9);
+        let be = encode("Controller ready\r\n", TextEncoding::Utf16Be);
+        assert_eq!(detect(&be[..DETECT_SAMPLE]).unwrap().encoding, TextEncoding::Utf16Be);
+
+        // Plain text, and binary with scattered NULs, are left alone
+        detector.reset();
+        assert_eq!(detector.feed(b"Controller ready\r\n"), None);
+        assert_eq!(detector.feed(&le), None, "gave up after the sample");
+        let modbus = [0x01, 0x03, 0x00, 0x00, 0x00, 0x02, 0xC4, 0x0B, 0x00, 0x00, 0x10, 0x00, 0x20, 0x00, 0x30, 0x00];
+        assert_eq!(detect(&modbus), None);
+    }
+}
@@ -0,0 +1,298 @@
+//! Live variables: `key=value` and `key: value` pairs picked out of debug
+//! lines such as `vbat=3.72 temp=41 state=RUN`, with each key's latest value
+//! and a short history of its numeric values for plotting.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local};
+
+use crate::export::csv_field;
+
+/// Keys beyond this many are not added, so a binary stream that happens to
+/// contain `x=y` sequences cannot grow the table without bound.
+pub const MAX_VARIABLES: usize = 256;
+/// Numeric values kept per key for plotting.
+pub const MAX_HISTORY: usize = 600;
+/// Longer values are cut to this many characters.
+const MAX_VALUE_CHARS: usize = 64;
+
+fn is_key_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'_'
+}
+
+fn is_key_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"_.-[]".contains(&byte)
+}
+
+fn ends_value(c: char) -> bool {
+    c.is_whitespace() || c.is_control() || c == ',' || c == ';' || c == char::REPLACEMENT_CHARACTER
+}
+
+/// The `key=value` and `key: value` pairs in `line`, in order. Keys are ASCII
+/// identifiers (`vbat`, `adc[2]`, `motor.rpm`); a value runs to the next space,
+/// comma or semicolon. `key:value` without a space is not a pair, so times
+/// and URLs are left alone.
+pub fn parse_pairs(line: &str) -> Vec<(&str, &str)> {
+    let bytes = line.as_bytes();
+    let skip_spaces = |mut i: usize| {
+        while bytes.get(i) == Some(&b' ') || bytes.get(i) == Some(&b'\t') {
+            i += 1;
+        }
+        i
+    };
+
+    let mut pairs = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if !is_key_start(bytes[i]) || (i > 0 && is_key_byte(bytes[i - 1])) {
+            i += 1;
+            continue;
+        }
+        let key_start = i;
+        while i < bytes.len() && is_key_byte(bytes[i]) {
+            i += 1;
+        }
+        let key_end = i;
+
+        let after_key = skip_spaces(key_end);
+        let value_start = match bytes.get(after_key) {
+            Some(b'=') => skip_spaces(after_key + 1),
+            Some(b':') if matches!(bytes.get(after_key + 1), Some(b' ' | b'\t')) => skip_spaces(after_key + 1),
+            _ => continue,
+        };
+        let rest = &line[value_start..];
+        let value_len = rest.find(ends_value).unwrap_or(rest.len());
+        if value_len > 0 {
+            pairs.push((&line[key_start..key_end], &rest[..value_len]));
+        }
+        i = value_start + value_len;
+    }
+    pairs
+}
+
+/// The number at the start of a value, so `3.72V` and `41C` still plot.
+pub fn numeric_value(value: &str) -> Option<f64> {
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+        .unwrap_or(value.len());
+    (1..=end).rev().find_map(|len| value[..len].parse().ok())
+}
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub key: String,
+    pub value: String,
+    pub updates: u64,
+    /// When the value last differed from the one before.
+    pub changed_at: DateTime<Local>,
+    /// Numeric values with the time they arrived, oldest first.
+    pub history: VecDeque<(DateTime<Local>, f64)>,
+    /// Shown as a plot under the table.
+    pub plotted: bool,
+}
+
+impl Variable {
+    fn new(key: &str, value: String, now: DateTime<Local>) -> Self {
+        Self {
+            key: key.to_string(),
+            value,
+            updates: 0,
+            changed_at: now,
+            history: VecDeque::new(),
+            plotted: false,
+        }
+    }
+
+    fn set(&mut self, value: String, now: DateTime<Local>) {
+        if value != self.value {
+            self.changed_at = now;
+        }
+        if let Some(number) = numeric_value(&value) {
+            if self.history.len() == MAX_HISTORY {
+                self.history.pop_front();
+            }
+            self.history.push_back((now, number));
+        }
+        self.value = value;
+        self.updates += 1;
+    }
+}
+
+/// Every key seen so far, in discovery order.
+#[derive(Debug, Clone, Default)]
+pub struct VariableTable {
+    variables: Vec<Variable>,
+    /// Keys kept out of the table view and export, including ones not seen yet.
+    hidden: Vec<String>,
+    /// Pairs whose key was not added because the table was full.
+    pub dropped: u64,
+}
+
+impl VariableTable {
+    /// A table that starts with `hidden` keys hidden.
+    pub fn with_hidden(hidden: Vec<String>) -> Self {
+        Self {
+            hidden,
+            ..Self::default()
+        }
+    }
+
+    /// Take the pairs in `line`. Returns how many were found.
+    pub fn update(&mut self, line: &str, now: DateTime<Local>) -> usize {
+        let pairs = parse_pairs(line);
+        for &(key, value) in &pairs {
+            let value: String = value.chars().take(MAX_VALUE_CHARS).collect();
+            let full = self.variables.len() >= MAX_VARIABLES;
+            match self.get_mut(key) {
+                Some(variable) => variable.set(value, now),
+                None if !full => {
+                    let mut variable = Variable::new(key, String::new(), now);
+                    variable.set(value, now);
+                    self.variables.push(variable);
+                }
+                None => self.dropped += 1,
+            }
+        }
+        pairs.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.variables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty()
+    }
+
+    pub fn is_hidden(&self, key: &str) -> bool {
+        self.hidden.iter().any(|hidden| hidden == key)
+    }
+
+    pub fn set_hidden(&mut self, key: &str, hidden: bool) {
+        self.hidden.retain(|k| k != key);
+        if hidden {
+            self.hidden.push(key.to_string());
+        }
+    }
+
+    pub fn hidden_keys(&self) -> &[String] {
+        &self.hidden
+    }
+
+    /// Variables that are not hidden.
+    pub fn visible(&self) -> impl Iterator<Item = &Variable> {
+        self.variables.iter().filter(|variable| !self.is_hidden(&variable.key))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Variable> {
+        self.variables.iter().find(|variable| variable.key == key)
+    }
+
+    /// The number at the start of `key`'s latest value.
+    pub fn numeric(&self, key: &str) -> Option<f64> {
+        self.get(key).and_then(|variable| numeric_value(&variable.value))
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Variable> {
+        self.variables.iter_mut().find(|variable| variable.key == key)
+    }
+
+    /// Forget every value; hidden keys stay hidden.
+    pub fn clear(&mut self) {
+        self.variables.clear();
+        self.dropped = 0;
+    }
+
+    /// `key,value,updates,last_change`, one row per visible key.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("key,value,updates,last_change\n");
+        for variable in self.visible() {
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                csv_field(&variable.key),
+                csv_field(&variable.value),
+                variable.updates,
+                variable.changed_at.format("%Y-%m-%d %H:%M:%S%.3f")
+            ));
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_both_pair_styles() {
+        assert_eq!(
+            parse_pairs("vbat=3.72 temp=41 state=RUN"),
+            [("vbat", "3.72"), ("temp", "41"), ("state", "RUN")]
+        );
+        assert_eq!(
+            parse_pairs("[12:30:01] mode: IDLE, adc[2] = 0x3FF; rssi:-71"),
+            [("mode", "IDLE"), ("adc[2]", "0x3FF")]
+        );
+        assert_eq!(parse_pairs("see http://host/a=b"), [("a", "b")]);
+        assert!(parse_pairs("x= ,y:").is_empty());
+        assert!(parse_pairs("2abc=1").is_empty());
+    }
+
+    #[test]
+    fn binary_lines_do_not_panic() {
+        let mut seed = 0x2545_F491_4F6C_DD1Du64;
+        let mut table = VariableTable::default();
+        for _ in 0..200 {
+            let bytes: Vec<u8> = (0..300)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    // Bias towards the characters pairs are made of
+                    b"a=: \xC3\xA9\xFF"[(seed % 7) as usize]
+                })
+                .collect();
+            table.update(&String::from_utf8_lossy(&bytes), Local::now());
+        }
+        assert!(table.len() <= MAX_VARIABLES);
+        assert!(table.visible().all(|v| !v.value.contains(char::REPLACEMENT_CHARACTER)));
+    }
+
+    #[test]
+    fn tracks_updates_changes_and_history() {
+        let mut table = VariableTable::with_hidden(vec!["seq".to_string()]);
+        let start = Local::now();
+        let later = start + chrono::Duration::seconds(5);
+        table.update("vbat=3.72V seq=1 state=RUN", start);
+        table.update("vbat=3.70V seq=2 state=RUN", later);
+
+        let keys: Vec<&str> = table.visible().map(|v| v.key.as_str()).collect();
+        assert_eq!(keys, ["vbat", "state"]);
+        let state = table.get_mut("state").unwrap();
+        assert_eq!((state.updates, state.changed_at), (2, start));
+        assert!(state.history.is_empty());
+        let vbat = table.get_mut("vbat").unwrap();
+        assert_eq!(vbat.changed_at, later);
+        assert_eq!(vbat.history.iter().map(|(_, v)| *v).collect::<Vec<_>>(), [3.72, 3.70]);
+
+        table.set_hidden("seq", false);
+        table.set_hidden("state", true);
+        let csv = table.to_csv();
+        assert!(csv.starts_with("key,value,updates,last_change\nvbat,3.70V,2,"));
+        assert!(csv.contains("\nseq,2,2,"));
+        assert!(!csv.contains("state"));
+
+        table.clear();
+        assert!(table.is_empty());
+        assert!(table.is_hidden("state"));
+    }
+
+    #[test]
+    fn numbers_are_read_from_the_front() {
+        assert_eq!(numeric_value("41"), Some(41.0));
+        assert_eq!(numeric_value("-3.5dBm"), Some(-3.5));
+        assert_eq!(numeric_value("1e3"), Some(1000.0));
+        assert_eq!(numeric_value("12e"), Some(12.0));
+        assert_eq!(numeric_value("RUN"), None);
+    }
+}
@@ -0,0 +1,407 @@
+//! Watch expressions over extracted variables, e.g. `vbat < 3.3` or
+//! `temp > 60 and fan == 0 for 5s`, raising an alarm while they hold.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+impl CompareOp {
+    fn parse(text: &str) -> Option<Self> {
+        Some(match text {
+            "<" => CompareOp::Less,
+            "<=" => CompareOp::LessEqual,
+            ">" => CompareOp::Greater,
+            ">=" => CompareOp::GreaterEqual,
+            "==" | "=" => CompareOp::Equal,
+            "!=" => CompareOp::NotEqual,
+            _ => return None,
+        })
+    }
+
+    /// Compare with the threshold moved by `band` towards keeping the result
+    /// true, so an alarm needs a clear move back before it ends.
+    fn holds(self, value: f64, threshold: f64, band: f64) -> bool {
+        match self {
+            CompareOp::Less => value < threshold + band,
+            CompareOp::LessEqual => value <= threshold + band,
+            CompareOp::Greater => value > threshold - band,
+            CompareOp::GreaterEqual => value >= threshold - band,
+            CompareOp::Equal => value == threshold,
+            CompareOp::NotEqual => value != threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Compare { key: String, op: CompareOp, threshold: f64 },
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// `None` while a variable it needs has no numeric value yet, unless the
+    /// rest decides the result on its own.
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Option<f64>, band: f64) -> Option<bool> {
+        match self {
+            Expr::Compare { key, op, threshold } => lookup(key).map(|value| op.holds(value, *threshold, band)),
+            Expr::And(a, b) => match (a.eval(lookup, band), b.eval(lookup, band)) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+            Expr::Or(a, b) => match (a.eval(lookup, band), b.eval(lookup, band)) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(f64),
+    Op(CompareOp),
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_.-[]".contains(c)
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+        } else if "<>=!".contains(c) {
+            chars.next();
+            let mut op = c.to_string();
+            if let Some(&(_, '=')) = chars.peek() {
+                chars.next();
+                op.push('=');
+            }
+            let op = CompareOp::parse(&op).ok_or_else(|| format!("Unknown operator '{}'", op))?;
+            tokens.push(Token::Op(op));
+        } else if c == '&' || c == '|' {
+            chars.next();
+            if chars.next().map(|(_, next)| next) != Some(c) {
+                return Err(format!("Use {0}{0} or {1}", c, if c == '&' { "and" } else { "or" }));
+            }
+            tokens.push(if c == '&' { Token::And } else { Token::Or });
+        } else if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' {
+            let mut end = start + c.len_utf8();
+            chars.next();
+            while let Some(&(i, next)) = chars.peek() {
+                let exponent_sign = (next == '-' || next == '+') && text[..i].ends_with(['e', 'E']);
+                if !(next.is_ascii_digit() || next == '.' || next == 'e' || next == 'E' || exponent_sign) {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+            let number = &text[start..end];
+            tokens.push(Token::Number(number.parse().map_err(|_| format!("Not a number: {}", number))?));
+        } else if is_word_char(c) {
+            let mut end = start;
+            while let Some(&(i, next)) = chars.peek() {
+                if !is_word_char(next) {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+            let word = &text[start..end];
+            tokens.push(match word.to_ascii_lowercase().as_str() {
+                "and" => Token::And,
+                "or" => Token::Or,
+                _ => Token::Word(word.to_string()),
+            });
+        } else {
+            return Err(format!("Unexpected '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.comparison()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.comparison()?));
+        }
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("Missing ')'".to_string()),
+                }
+            }
+            Some(Token::Word(key)) => {
+                let Some(Token::Op(op)) = self.next() else {
+                    return Err(format!("Expected a comparison after '{}'", key));
+                };
+                let Some(Token::Number(threshold)) = self.next() else {
+                    return Err(format!("Expected a number after '{}'", key));
+                };
+                Ok(Expr::Compare { key, op, threshold })
+            }
+            _ => Err("Expected a variable name or '('".to_string()),
+        }
+    }
+}
+
+/// `5s`, `500ms`, `2m`, `1h`, or a bare number of seconds.
+fn parse_duration(text: &str) -> Result<chrono::Duration, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("Not a duration: {}", text))?;
+    let millis = match unit.trim() {
+        "ms" => number,
+        "" | "s" => number * 1000.0,
+        "m" | "min" => number * 60_000.0,
+        "h" => number * 3_600_000.0,
+        _ => return Err(format!("Unknown duration unit '{}' (use ms, s, m or h)", unit.trim())),
+    };
+    Ok(chrono::Duration::milliseconds(millis as i64))
+}
+
+/// Parse a watch expression, with an optional trailing `for <duration>` the
+/// condition must hold before it alarms.
+pub fn parse(text: &str) -> Result<(Expr, Option<chrono::Duration>), String> {
+    let lower = text.to_ascii_lowercase();
+    let (condition, hold) = match lower.rfind(" for ") {
+        Some(at) => (&text[..at], Some(parse_duration(&text[at + 5..])?)),
+        None => (text, None),
+    };
+    let mut parser = Parser {
+        tokens: tokenize(condition)?,
+        pos: 0,
+    };
+    if parser.tokens.is_empty() {
+        return Err("Empty expression".to_string());
+    }
+    let expr = parser.or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err("Unexpected text after the expression".to_string());
+    }
+    Ok((expr, hold))
+}
+
+/// A change worth telling the user about.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchEvent {
+    Raised,
+    Cleared,
+}
+
+/// One watch. Only the definition is saved; call `compile` after loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Watch {
+    pub expression: String,
+    pub enabled: bool,
+    /// Deadband: once alarmed, thresholds move by this much before it clears.
+    pub hysteresis: f64,
+    #[serde(skip)]
+    parsed: Option<(Expr, Option<chrono::Duration>)>,
+    #[serde(skip)]
+    pub error: Option<String>,
+    /// Latest evaluation; `None` while a variable has no value.
+    #[serde(skip)]
+    pub state: Option<bool>,
+    #[serde(skip)]
+    pub alarm: bool,
+    #[serde(skip)]
+    true_since: Option<DateTime<Local>>,
+}
+
+impl Watch {
+    /// An enabled watch, compiled straight away; check `error` for a bad expression.
+    pub fn new(expression: &str) -> Self {
+        let mut watch = Self {
+            expression: expression.to_string(),
+            enabled: true,
+            hysteresis: 0.0,
+            parsed: None,
+            error: None,
+            state: None,
+            alarm: false,
+            true_since: None,
+        };
+        watch.compile();
+        watch
+    }
+
+    /// Reparse after the expression was edited. Resets the alarm.
+    pub fn compile(&mut self) {
+        self.parsed = None;
+        self.error = None;
+        self.state = None;
+        self.alarm = false;
+        self.true_since = None;
+        if self.expression.trim().is_empty() {
+            return;
+        }
+        match parse(&self.expression) {
+            Ok(parsed) => self.parsed = Some(parsed),
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// Evaluate at `now`, returning whether the alarm started or ended.
+    pub fn update(&mut self, lookup: &dyn Fn(&str) -> Option<f64>, now: DateTime<Local>) -> Option<WatchEvent> {
+        let Some((expr, hold)) = self.parsed.as_ref().filter(|_| self.enabled) else {
+            self.state = None;
+            self.true_since = None;
+            return self.alarm.then(|| {
+                self.alarm = false;
+                WatchEvent::Cleared
+            });
+        };
+        let band = if self.alarm { self.hysteresis.abs() } else { 0.0 };
+        self.state = expr.eval(lookup, band);
+
+        if self.state != Some(true) {
+            self.true_since = None;
+            return self.alarm.then(|| {
+                self.alarm = false;
+                WatchEvent::Cleared
+            });
+        }
+        let since = *self.true_since.get_or_insert(now);
+        if !self.alarm && now - since >= hold.unwrap_or_default() {
+            self.alarm = true;
+            return Some(WatchEvent::Raised);
+        }
+        None
+    }
+
+    /// Time left before a condition that is already true alarms.
+    pub fn pending(&self, now: DateTime<Local>) -> Option<chrono::Duration> {
+        let (_, hold) = self.parsed.as_ref()?;
+        let since = self.true_since.filter(|_| !self.alarm)?;
+        Some(hold.unwrap_or_default() - (now - since))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn values(pairs: &[(&str, f64)]) -> HashMap<String, f64> {
+        pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    fn eval(text: &str, vars: &HashMap<String, f64>) -> Option<bool> {
+        let (expr, _) = parse(text).unwrap();
+        expr.eval(&|key| vars.get(key).copied(), 0.0)
+    }
+
+    #[test]
+    fn evaluates_comparisons_with_and_or() {
+        let vars = values(&[("vbat", 3.2), ("temp", 41.0), ("adc[2]", -5.0)]);
+        assert_eq!(eval("vbat < 3.3", &vars), Some(true));
+        assert_eq!(eval("temp>60 OR vbat<=3.2", &vars), Some(true));
+        assert_eq!(eval("temp > 60 && vbat < 3.3", &vars), Some(false));
+        assert_eq!(eval("(temp >= 41 or fan == 1) and adc[2] != -5", &vars), Some(false));
+        assert_eq!(eval("adc[2] > -1e1", &vars), Some(true));
+        // Unknown variables leave the result open unless the rest decides it
+        assert_eq!(eval("fan == 0", &vars), None);
+        assert_eq!(eval("fan == 0 or vbat < 3.3", &vars), Some(true));
+        assert_eq!(eval("fan == 0 and vbat > 3.3", &vars), Some(false));
+    }
+
+    #[test]
+    fn reports_bad_expressions() {
+        for bad in ["", "vbat <", "vbat 3.3", "< 3", "(vbat < 3", "vbat < 3 junk", "a & b", "v ~ 1", "t > 1 for 5x"] {
+            assert!(parse(bad).is_err(), "{} parsed", bad);
+        }
+        let (_, hold) = parse("temp > 60 for 5s").unwrap();
+        assert_eq!(hold, Some(chrono::Duration::seconds(5)));
+        assert_eq!(parse("x > 1 FOR 250ms").unwrap().1, Some(chrono::Duration::milliseconds(250)));
+        assert!(Watch::new("vbat << 3").error.is_some());
+    }
+
+    #[test]
+    fn duration_and_hysteresis() {
+        let mut watch = Watch::new("temp > 60 for 5s");
+        watch.hysteresis = 2.0;
+        let start = Local::now();
+        let at = |secs| start + chrono::Duration::seconds(secs);
+        let temp = |t: f64| move |_: &str| Some(t);
+
+        assert_eq!(watch.update(&temp(61.0), at(0)), None);
+        assert_eq!(watch.state, Some(true));
+        assert_eq!(watch.pending(at(2)), Some(chrono::Duration::seconds(3)));
+        assert_eq!(watch.update(&temp(61.0), at(5)), Some(WatchEvent::Raised));
+        assert!(watch.alarm);
+        // Noise around the threshold does not clear it...
+        assert_eq!(watch.update(&temp(59.5), at(6)), None);
+        assert!(watch.alarm);
+        // ...a drop below the band does
+        assert_eq!(watch.update(&temp(57.0), at(7)), Some(WatchEvent::Cleared));
+        // Back above, but not for long enough
+        assert_eq!(watch.update(&temp(65.0), at(8)), None);
+        assert_eq!(watch.update(&temp(50.0), at(9)), None);
+        assert!(!watch.alarm);
+    }
+
+    #[test]
+    fn disabling_clears_the_alarm() {
+        let mut watch = Watch::new("vbat < 3.3");
+        assert_eq!(watch.update(&|_| Some(3.0), Local::now()), Some(WatchEvent::Raised));
+        watch.enabled = false;
+        assert_eq!(watch.update(&|_| Some(3.0), Local::now()), Some(WatchEvent::Cleared));
+        assert_eq!(watch.state, None);
+    }
+}
@@ -0,0 +1,700 @@
+//! XMODEM file transfer, checksum and CRC-16, with 128-byte blocks and
+//! XMODEM-1K. The sender and receiver are state machines: they are fed what
+//! the port received and the time, and hand back the bytes to write, so a
+//! transfer runs from the UI loop without a thread of its own.
+
+use std::time::{Duration, Instant};
+
+pub const SOH: u8 = 0x01;
+pub const STX: u8 = 0x02;
+pub const EOT: u8 = 0x04;
+pub const ACK: u8 = 0x06;
+pub const NAK: u8 = 0x15;
+pub const CAN: u8 = 0x18;
+/// Pads the last block.
+pub const SUB: u8 = 0x1A;
+/// Sent instead of NAK by a receiver asking for CRC-16.
+pub const CRC_REQUEST: u8 = b'C';
+
+/// Two CANs in a row end a transfer from either side.
+const CANCEL: [u8; 2] = [CAN, CAN];
+
+/// Attempts at one block, or at the end of the transfer, before giving up.
+pub const MAX_RETRIES: u32 = 10;
+/// How long the sender waits for the receiver to start, or to answer a block,
+/// before giving up. It only sends a block again when NAKed, since a block
+/// sent twice could be taken for the next one.
+pub const ANSWER_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long the receiver waits for a block before NAKing it.
+pub const BLOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the receiver repeats its request for the first block.
+pub const REQUEST_INTERVAL: Duration = Duration::from_secs(3);
+/// Unanswered CRC requests before the receiver falls back to checksums.
+const CRC_REQUESTS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Checksum {
+    /// One byte: the sum of the data.
+    Sum,
+    Crc16,
+}
+
+impl Checksum {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Checksum::Sum => "checksum",
+            Checksum::Crc16 => "CRC-16",
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Checksum::Sum => 1,
+            Checksum::Crc16 => 2,
+        }
+    }
+
+    fn append(&self, data: &[u8], out: &mut Vec<u8>) {
+        match self {
+            Checksum::Sum => out.push(data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))),
+            Checksum::Crc16 => out.extend_from_slice(&crc16(data).to_be_bytes()),
+        }
+    }
+}
+
+/// CRC-16/XMODEM: polynomial 0x1021, starting from zero.
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Block `number` carrying `data`, padded with SUB to 128 bytes, or to 1024
+/// with an STX header when `data` is longer than 128.
+pub fn block(number: u8, data: &[u8], checksum: Checksum) -> Vec<u8> {
+    let (header, size) = if data.len() > 128 { (STX, 1024) } else { (SOH, 128) };
+    let mut payload = data.to_vec();
+    payload.resize(size, SUB);
+    let mut out = vec![header, number, !number];
+    out.extend_from_slice(&payload);
+    checksum.append(&payload, &mut out);
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Running,
+    Done,
+    Failed(String),
+}
+
+/// How far a transfer has got. `bytes` counts file data in acknowledged
+/// blocks, padding included on the last one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    pub blocks: u32,
+    pub bytes: u64,
+    /// The file size when sending.
+    pub total: Option<u64>,
+    /// Blocks NAKed, or timed out waiting for.
+    pub retries: u32,
+    /// Negotiated once the first block goes.
+    pub checksum: Option<Checksum>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SendState {
+    Start,
+    Block,
+    Eot,
+    Finished,
+}
+
+/// Sends a file to a receiver, which starts the transfer by asking for CRC
+/// ('C') or checksum (NAK) blocks.
+pub struct Sender {
+    data: Vec<u8>,
+    one_k: bool,
+    state: SendState,
+    /// Start of the block in flight, and its length.
+    offset: usize,
+    len: usize,
+    number: u8,
+    deadline: Instant,
+    attempts: u32,
+    cans: u8,
+    progress: Progress,
+    status: Status,
+}
+
+impl Sender {
+    /// With `one_k`, 1024-byte blocks are sent while at least that much is left
+    /// and the receiver asked for CRC.
+    pub fn new(data: Vec<u8>, one_k: bool, now: Instant) -> Self {
+        let progress = Progress {
+            total: Some(data.len() as u64),
+            ..Progress::default()
+        };
+        Self {
+            data,
+            one_k,
+            state: SendState::Start,
+            offset: 0,
+            len: 0,
+            number: 1,
+            deadline: now + ANSWER_TIMEOUT,
+            attempts: 0,
+            cans: 0,
+            progress,
+            status: Status::Running,
+        }
+    }
+
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// Take in received bytes; returns what to write back.
+    pub fn feed(&mut self, input: &[u8], now: Instant) -> Vec<u8> {
+        let mut out = Vec::new();
+        for &byte in input {
+            if self.state == SendState::Finished {
+                break;
+            }
+            if byte == CAN {
+                self.cans += 1;
+                if self.cans >= 2 {
+                    self.finish(Status::Failed("cancelled by the receiver".to_string()));
+                }
+                continue;
+            }
+            self.cans = 0;
+            match (self.state, byte) {
+                (SendState::Start, CRC_REQUEST | NAK) => {
+                    self.progress.checksum = Some(if byte == CRC_REQUEST { Checksum::Crc16 } else { Checksum::Sum });
+                    self.next_block(&mut out, now);
+                }
+                (SendState::Block, ACK) => {
+                    self.progress.blocks += 1;
+                    self.progress.bytes += self.len.max(128) as u64;
+                    self.offset += self.len;
+                    self.number = self.number.wrapping_add(1);
+                    self.next_block(&mut out, now);
+                }
+                (SendState::Block, NAK) => self.retry(&mut out, now),
+                (SendState::Eot, ACK) => self.finish(Status::Done),
+                // Many receivers NAK the first EOT to be sure of it
+                (SendState::Eot, NAK) => self.retry(&mut out, now),
+                // Line noise, or a start request repeated while the block was on its way
+                _ => {}
+            }
+        }
+        out
+    }
+
+    /// Give up once the receiver has been quiet too long.
+    pub fn poll(&mut self, now: Instant) -> Vec<u8> {
+        if now < self.deadline || self.state == SendState::Finished {
+            return Vec::new();
+        }
+        let reason = match self.state {
+            SendState::Start => "the receiver never asked for data".to_string(),
+            SendState::Eot => "no answer to the end of the transfer".to_string(),
+            _ => format!("no answer to block {}", self.progress.blocks + 1),
+        };
+        self.finish(Status::Failed(reason));
+        CANCEL.to_vec()
+    }
+
+    /// Stop the transfer; returns the CANs that tell the receiver.
+    pub fn cancel(&mut self) -> Vec<u8> {
+        if self.state == SendState::Finished {
+            return Vec::new();
+        }
+        self.finish(Status::Failed("cancelled".to_string()));
+        CANCEL.to_vec()
+    }
+
+    fn next_block(&mut self, out: &mut Vec<u8>, now: Instant) {
+        self.attempts = 0;
+        let remaining = self.data.len() - self.offset;
+        if remaining == 0 {
+            self.state = SendState::Eot;
+        } else {
+            let crc = self.progress.checksum == Some(Checksum::Crc16);
+            self.len = remaining.min(if self.one_k && crc && remaining > 128 { 1024 } else { 128 });
+            self.state = SendState::Block;
+        }
+        self.send_current(out, now);
+    }
+
+    fn send_current(&mut self, out: &mut Vec<u8>, now: Instant) {
+        match self.state {
+            SendState::Block => {
+                let data = &self.data[self.offset..self.offset + self.len];
+                out.extend(block(self.number, data, self.progress.checksum.unwrap_or(Checksum::Sum)));
+            }
+            SendState::Eot => out.push(EOT),
+            _ => return,
+        }
+        self.deadline = now + ANSWER_TIMEOUT;
+    }
+
+    fn retry(&mut self, out: &mut Vec<u8>, now: Instant) {
+        self.attempts += 1;
+        self.progress.retries += 1;
+        if self.attempts >= MAX_RETRIES {
+            let what = match self.state {
+                SendState::Eot => "EOT".to_string(),
+                _ => format!("block {}", self.progress.blocks + 1),
+            };
+            self.finish(Status::Failed(format!("{} NAKed {} times", what, MAX_RETRIES)));
+            out.extend_from_slice(&CANCEL);
+            return;
+        }
+        self.send_current(out, now);
+    }
+
+    fn finish(&mut self, status: Status) {
+        self.state = SendState::Finished;
+        self.status = status;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReceiveState {
+    /// Asking the sender to start; how many requests have gone out.
+    Start(u32),
+    Blocks,
+    Finished,
+}
+
+/// Receives a file, asking for CRC blocks first and falling back to checksums
+/// when the sender does not answer. SUB padding at the end of the last block
+/// is dropped, so a file that really ends in SUB bytes loses them, as with
+/// any XMODEM receiver.
+pub struct Receiver {
+    checksum: Checksum,
+    state: ReceiveState,
+    buffer: Vec<u8>,
+    expected: u8,
+    deadline: Instant,
+    attempts: u32,
+    /// The newest block, held back until it is known not to be the last.
+    held: Vec<u8>,
+    data: Vec<u8>,
+    progress: Progress,
+    status: Status,
+}
+
+impl Receiver {
+    /// The first `poll` sends the request for the first block.
+    pub fn new(crc: bool, now: Instant) -> Self {
+        Self {
+            checksum: if crc { Checksum::Crc16 } else { Checksum::Sum },
+            state: ReceiveState::Start(0),
+            buffer: Vec::new(),
+            expected: 1,
+            deadline: now,
+            attempts: 0,
+            held: Vec::new(),
+            data: Vec::new(),
+            progress: Progress::default(),
+            status: Status::Running,
+        }
+    }
+
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// File data received since the last call, in order.
+    pub fn take_data(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.data)
+    }
+
+    /// Take in received bytes; returns what to write back.
+    pub fn feed(&mut self, input: &[u8], now: Instant) -> Vec<u8> {
+        let mut out = Vec::new();
+        if self.state == ReceiveState::Finished {
+            return out;
+        }
+        self.buffer.extend_from_slice(input);
+        while let Some(&first) = self.buffer.first() {
+            match first {
+                SOH | STX => {
+                    let size = if first == STX { 1024 } else { 128 };
+                    let needed = 3 + size + self.checksum.len();
+                    if self.buffer.len() < needed {
+                        break;
+                    }
+                    let frame: Vec<u8> = self.buffer.drain(..needed).collect();
+                    self.take_block(&frame, size, &mut out, now);
+                }
+                // Straight after the request, the file is empty
+                EOT => {
+                    self.buffer.clear();
+                    out.push(ACK);
+                    let end = self.held.iter().rposition(|&b| b != SUB).map_or(0, |last| last + 1);
+                    self.held.truncate(end);
+                    self.data.append(&mut self.held);
+                    self.finish(Status::Done);
+                }
+                CAN if self.buffer.get(1) == Some(&CAN) => {
+                    self.finish(Status::Failed("cancelled by the sender".to_string()));
+                }
+                CAN if self.buffer.len() == 1 => break,
+                // Noise, e.g. the bootloader's prompt before the first block
+                _ => {
+                    self.buffer.remove(0);
+                }
+            }
+            if self.state == ReceiveState::Finished {
+                break;
+            }
+        }
+        out
+    }
+
+    fn take_block(&mut self, frame: &[u8], size: usize, out: &mut Vec<u8>, now: Instant) {
+        let number = frame[1];
+        let payload = &frame[3..3 + size];
+        let mut check = Vec::new();
+        self.checksum.append(payload, &mut check);
+        if frame[2] != !number || frame[3 + size..] != check[..] {
+            // Whatever follows a damaged block is suspect too
+            self.buffer.clear();
+            self.retry(out, now);
+            return;
+        }
+        if number == self.expected {
+            self.state = ReceiveState::Blocks;
+            self.progress.checksum = Some(self.checksum);
+            self.data.append(&mut self.held);
+            self.held = payload.to_vec();
+            self.expected = self.expected.wrapping_add(1);
+            self.progress.blocks += 1;
+            self.progress.bytes += size as u64;
+            self.attempts = 0;
+            out.push(ACK);
+        } else if self.state == ReceiveState::Blocks && number == self.expected.wrapping_sub(1) {
+            // Our ACK was lost and the sender repeated the block
+            out.push(ACK);
+        } else {
+            self.finish(Status::Failed(format!(
+                "block {} arrived while expecting block {}",
+                number, self.expected
+            )));
+            out.extend_from_slice(&CANCEL);
+            return;
+        }
+        self.deadline = now + BLOCK_TIMEOUT;
+    }
+
+    /// Ask for the first block again, or NAK once the sender has gone quiet.
+    pub fn poll(&mut self, now: Instant) -> Vec<u8> {
+        let mut out = Vec::new();
+        if now < self.deadline {
+            return out;
+        }
+        match self.state {
+            ReceiveState::Start(requests) if requests >= MAX_RETRIES => {
+                self.finish(Status::Failed("the sender never started".to_string()));
+                out.extend_from_slice(&CANCEL);
+            }
+            ReceiveState::Start(requests) => {
+                if requests == CRC_REQUESTS {
+                    self.checksum = Checksum::Sum;
+                }
+                self.buffer.clear();
+                out.push(match self.checksum {
+                    Checksum::Crc16 => CRC_REQUEST,
+                    Checksum::Sum => NAK,
+                });
+                self.state = ReceiveState::Start(requests + 1);
+                self.deadline = now + REQUEST_INTERVAL;
+            }
+            ReceiveState::Blocks => {
+                self.buffer.clear();
+                self.retry(&mut out, now);
+            }
+            ReceiveState::Finished => {}
+        }
+        out
+    }
+
+    /// Stop the transfer; returns the CANs that tell the sender.
+    pub fn cancel(&mut self) -> Vec<u8> {
+        if self.state == ReceiveState::Finished {
+            return Vec::new();
+        }
+        self.finish(Status::Failed("cancelled".to_string()));
+        CANCEL.to_vec()
+    }
+
+    fn retry(&mut self, out: &mut Vec<u8>, now: Instant) {
+        self.attempts += 1;
+        self.progress.retries += 1;
+        if self.attempts >= MAX_RETRIES {
+            self.finish(Status::Failed(format!(
+                "block {} failed {} times",
+                self.progress.blocks + 1,
+                MAX_RETRIES
+            )));
+            out.extend_from_slice(&CANCEL);
+            return;
+        }
+        out.push(NAK);
+        self.deadline = now + BLOCK_TIMEOUT;
+    }
+
+    fn finish(&mut self, status: Status) {
+        self.state = ReceiveState::Finished;
+        self.status = status;
+    }
+}
+
+/// A running transfer in either direction.
+pub enum Transfer {
+    Send(Sender),
+    Receive(Receiver),
+}
+
+impl Transfer {
+    pub fn feed(&mut self, input: &[u8], now: Instant) -> Vec<u8> {
+        match self {
+            Transfer::Send(sender) => sender.feed(input, now),
+            Transfer::Receive(receiver) => receiver.feed(input, now),
+        }
+    }
+
+    pub fn poll(&mut self, now: Instant) -> Vec<u8> {
+        match self {
+            Transfer::Send(sender) => sender.poll(now),
+            Transfer::Receive(receiver) => receiver.poll(now),
+        }
+    }
+
+    pub fn cancel(&mut self) -> Vec<u8> {
+        match self {
+            Transfer::Send(sender) => sender.cancel(),
+            Transfer::Receive(receiver) => receiver.cancel(),
+        }
+    }
+
+    pub fn status(&self) -> &Status {
+        match self {
+            Transfer::Send(sender) => sender.status(),
+            Transfer::Receive(receiver) => receiver.status(),
+        }
+    }
+
+    pub fn progress(&self) -> &Progress {
+        match self {
+            Transfer::Send(sender) => sender.progress(),
+            Transfer::Receive(receiver) => receiver.progress(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_port::MockPort;
+    use serialport::SerialPort;
+    use std::io::{Read, Write};
+
+    /// One side of the transfer on a mock port: reads what the other side
+    /// wrote, feeds it to `transfer` and writes the answer.
+    fn step(transfer: &mut Transfer, port: &mut Box<dyn SerialPort>, now: Instant) {
+        let mut buf = [0u8; 2048];
+        let input = match port.read(&mut buf) {
+            Ok(count) => buf[..count].to_vec(),
+            Err(_) => Vec::new(),
+        };
+        let mut out = transfer.feed(&input, now);
+        out.extend(transfer.poll(now));
+        port.write_all(&out).ok();
+    }
+
+    /// Run a sender and a receiver against each other over two mock ports,
+    /// passing the wire through `line` on its way; returns the file received.
+    fn run(
+        data: &[u8],
+        one_k: bool,
+        crc: bool,
+        mut line: impl FnMut(Vec<u8>) -> Vec<u8>,
+    ) -> (Transfer, Transfer, Vec<u8>) {
+        let start = Instant::now();
+        let (sender_port, receiver_port) = (MockPort::default(), MockPort::default());
+        let (mut sender_io, mut receiver_io): (Box<dyn SerialPort>, Box<dyn SerialPort>) =
+            (Box::new(sender_port.clone()), Box::new(receiver_port.clone()));
+        let mut sender = Transfer::Send(Sender::new(data.to_vec(), one_k, start));
+        let mut receiver = Transfer::Receive(Receiver::new(crc, start));
+        let mut received = Vec::new();
+        for tick in 0..2000 {
+            let now = start + Duration::from_millis(100) * tick;
+            step(&mut receiver, &mut receiver_io, now);
+            sender_port.push_rx(&receiver_port.take_written());
+            step(&mut sender, &mut sender_io, now);
+            receiver_port.push_rx(&line(sender_port.take_written()));
+            if let Transfer::Receive(r) = &mut receiver {
+                received.extend(r.take_data());
+            }
+            if *sender.status() != Status::Running && *receiver.status() != Status::Running {
+                break;
+            }
+        }
+        (sender, receiver, received)
+    }
+
+    fn file(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn crc_matches_the_xmodem_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+        let b = block(1, b"AB", Checksum::Sum);
+        assert_eq!(&b[..5], [SOH, 1, 0xFE, b'A', b'B']);
+        assert_eq!(b.len(), 3 + 128 + 1);
+        assert_eq!(b[131], (b'A' as u32 + b'B' as u32 + 126 * SUB as u32) as u8);
+        assert_eq!(block(2, &[0; 129], Checksum::Crc16).len(), 3 + 1024 + 2);
+    }
+
+    #[test]
+    fn files_arrive_whole_in_every_variant() {
+        for (one_k, crc) in [(false, false), (false, true), (true, true), (true, false)] {
+            for len in [0, 1, 128, 129, 1000, 1024, 3000] {
+                let data = file(len);
+                let (sender, receiver, received) = run(&data, one_k, crc, |wire| wire);
+                assert_eq!(*sender.status(), Status::Done, "1K {} CRC {} {} bytes", one_k, crc, len);
+                assert_eq!(*receiver.status(), Status::Done);
+                assert_eq!(received, data, "1K {} CRC {} {} bytes", one_k, crc, len);
+                assert_eq!(sender.progress().retries, 0);
+                let checksum = if crc { Checksum::Crc16 } else { Checksum::Sum };
+                assert_eq!(sender.progress().checksum, Some(checksum));
+            }
+        }
+        // 1K blocks only while more than 128 bytes are left
+        let (sender, _, _) = run(&file(2100), true, true, |wire| wire);
+        assert_eq!(sender.progress().blocks, 3);
+    }
+
+    #[test]
+    fn damaged_and_lost_blocks_are_sent_again() {
+        let data = file(1000);
+        let mut corrupted = 0;
+        let (sender, receiver, received) = run(&data, false, true, |mut wire| {
+            if wire.len() > 100 && corrupted < 3 {
+                corrupted += 1;
+                wire[50] ^= 0xFF;
+            }
+            wire
+        });
+        assert_eq!(received, data);
+        assert_eq!(*receiver.status(), Status::Done);
+        assert_eq!(sender.progress().retries, 3);
+
+        // A block that never arrives times out on both sides and goes again
+        let mut blocks = 0;
+        let (sender, _, received) = run(&data, false, true, |wire| {
+            if wire.len() > 100 {
+                blocks += 1;
+                if blocks == 2 {
+                    return Vec::new();
+                }
+            }
+            wire
+        });
+        assert_eq!(received, data);
+        assert!(sender.progress().retries >= 1);
+    }
+
+    #[test]
+    fn a_lost_ack_does_not_duplicate_data() {
+        let start = Instant::now();
+        let mut receiver = Receiver::new(true, start);
+        assert_eq!(receiver.poll(start), [CRC_REQUEST]);
+        let first = block(1, b"one", Checksum::Crc16);
+        assert_eq!(receiver.feed(&first, start), [ACK]);
+        assert_eq!(receiver.feed(&first, start), [ACK], "the repeat is acknowledged");
+        assert_eq!(receiver.feed(&block(2, b"two", Checksum::Crc16), start), [ACK]);
+        assert_eq!(receiver.feed(&[EOT], start), [ACK]);
+        assert_eq!(receiver.take_data(), [&b"one"[..], &[SUB; 125], b"two"].concat());
+        assert_eq!(receiver.progress().blocks, 2);
+
+        let mut receiver = Receiver::new(true, start);
+        receiver.poll(start);
+        receiver.feed(&block(1, b"one", Checksum::Crc16), start);
+        assert_eq!(receiver.feed(&block(3, b"three", Checksum::Crc16), start), CANCEL);
+        assert_eq!(
+            *receiver.status(),
+            Status::Failed("block 3 arrived while expecting block 2".to_string())
+        );
+    }
+
+    #[test]
+    fn timeouts_and_cancels_end_the_transfer() {
+        let start = Instant::now();
+        let mut sender = Sender::new(file(10), false, start);
+        assert!(sender.feed(b"boot> ", start).is_empty(), "noise before the start is ignored");
+        assert!(sender.poll(start + ANSWER_TIMEOUT / 2).is_empty());
+        assert_eq!(sender.poll(start + ANSWER_TIMEOUT), CANCEL);
+        assert_eq!(*sender.status(), Status::Failed("the receiver never asked for data".to_string()));
+
+        // The receiver asks for CRC three times, then checksums
+        let mut receiver = Receiver::new(true, start);
+        let mut requests = Vec::new();
+        for tick in 0..=MAX_RETRIES {
+            requests.extend(receiver.poll(start + REQUEST_INTERVAL * tick));
+        }
+        assert_eq!(&requests[..4], [CRC_REQUEST, CRC_REQUEST, CRC_REQUEST, NAK]);
+        assert_eq!(requests.len(), MAX_RETRIES as usize + CANCEL.len());
+        assert_eq!(*receiver.status(), Status::Failed("the sender never started".to_string()));
+
+        // Only a NAK sends a block again, MAX_RETRIES times at most
+        let mut sender = Sender::new(file(10), false, start);
+        let mut written = sender.feed(&[NAK], start).len();
+        assert!(sender.poll(start + BLOCK_TIMEOUT).is_empty(), "the receiver NAKs a lost block");
+        for _ in 0..MAX_RETRIES {
+            written += sender.feed(&[NAK], start).len();
+        }
+        assert_eq!(written, (3 + 128 + 1) * MAX_RETRIES as usize + CANCEL.len());
+        assert_eq!(*sender.status(), Status::Failed("block 1 NAKed 10 times".to_string()));
+        let mut sender = Sender::new(file(10), false, start);
+        sender.feed(&[NAK], start);
+        assert_eq!(sender.poll(start + ANSWER_TIMEOUT), CANCEL);
+        assert_eq!(*sender.status(), Status::Failed("no answer to block 1".to_string()));
+
+        // Either side cancelling stops the other
+        let mut sender = Sender::new(file(300), false, start);
+        sender.feed(&[NAK], start);
+        let mut receiver = Receiver::new(false, start);
+        receiver.poll(start);
+        receiver.feed(&sender.cancel(), start);
+        assert_eq!(*receiver.status(), Status::Failed("cancelled by the sender".to_string()));
+        assert!(sender.cancel().is_empty(), "cancelled once");
+
+        let mut sender = Sender::new(file(300), false, start);
+        sender.feed(&[NAK], start);
+        sender.feed(&[CAN], start);
+        assert_eq!(*sender.status(), Status::Running, "one CAN may be noise");
+        sender.feed(&[CAN], start);
+        assert_eq!(*sender.status(), Status::Failed("cancelled by the receiver".to_string()));
+    }
+}
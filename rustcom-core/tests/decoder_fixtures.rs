@@ -0,0 +1,218 @@
+//! Decoder regression tests from recorded captures. Each fixture in
+//! `tests/fixtures/` is a raw capture `<name>.bin` and a `<name>.json` naming
+//! the decoder and the frames it should emit. The capture is fed in random
+//! chunk sizes, many times over, and the frames are compared with the JSON.
+//! See `tests/fixtures/README.md` for adding one.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use rustcom_core::protocol::framing_rule::FramingRule;
+use rustcom_core::protocol::modbus::{RtuDecoder, RtuTiming};
+use rustcom_core::protocol::{self, CustomFraming, Decoder, Frame, ProtocolMode};
+
+/// Chunkings tried per fixture, besides one read per segment and one byte at a time.
+const RANDOM_RUNS: u64 = 50;
+/// Setting this rewrites each fixture's expected frames from what the decoder emits.
+const BLESS_VAR: &str = "RUSTCOM_BLESS";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Fixture {
+    mode: ProtocolMode,
+    /// For `ProtocolMode::Custom`; SLIP when left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    framing: Option<CustomFraming>,
+    /// For `CustomFraming::Rule`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rule: Option<FramingRule>,
+    #[serde(default = "default_baud")]
+    baud: u32,
+    /// Offsets in the capture after which the line went quiet, for decoders
+    /// that end frames on silence (Modbus RTU).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    idle_after: Vec<usize>,
+    frames: Vec<ExpectedFrame>,
+}
+
+fn default_baud() -> u32 {
+    9600
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ExpectedFrame {
+    payload: String,
+    summary: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resync: Option<String>,
+}
+
+impl From<Frame> for ExpectedFrame {
+    fn from(frame: Frame) -> Self {
+        Self {
+            payload: protocol::format_payload(&frame.payload),
+            summary: frame.summary,
+            error: frame.error,
+            resync: frame.resync.map(|resync| resync.label()),
+        }
+    }
+}
+
+/// 10 bits per character: start, 8 data, stop.
+fn char_time(baud: u32) -> Duration {
+    Duration::from_secs_f64(10.0 / baud as f64)
+}
+
+/// Runs a fixture's decoder on a made-up clock: bytes arrive back to back at
+/// the line rate, and the line goes quiet for a second at each idle offset.
+struct Player {
+    decoder: Played,
+    char_time: Duration,
+    now: Instant,
+}
+
+enum Played {
+    /// Fed with timestamps, as its frames end on silence.
+    Rtu(RtuDecoder),
+    Stream(Box<dyn Decoder>),
+}
+
+impl Player {
+    fn new(fixture: &Fixture) -> Self {
+        let char_time = char_time(fixture.baud);
+        let decoder = match fixture.mode {
+            ProtocolMode::ModbusRtu => Played::Rtu(RtuDecoder::new(RtuTiming::new(fixture.baud, char_time))),
+            mode => {
+                let framing = fixture.framing.unwrap_or(CustomFraming::Slip);
+                let rule = fixture.rule.clone().unwrap_or_default();
+                Played::Stream(
+                    protocol::create_decoder(mode, framing, &rule, fixture.baud, char_time, None)
+                        .expect("fixture framing rule is invalid")
+                        .expect("fixture mode has no decoder"),
+                )
+            }
+        };
+        Self {
+            decoder,
+            char_time,
+            now: Instant::now(),
+        }
+    }
+
+    fn feed(&mut self, chunk: &[u8]) -> Vec<Frame> {
+        self.now += self.char_time * chunk.len() as u32;
+        match &mut self.decoder {
+            Played::Rtu(rtu) => rtu.feed_at(chunk, self.now),
+            Played::Stream(decoder) => decoder.feed(chunk),
+        }
+    }
+
+    fn idle(&mut self) -> Vec<Frame> {
+        self.now += Duration::from_secs(1);
+        match &mut self.decoder {
+            Played::Rtu(rtu) => rtu.poll(self.now),
+            Played::Stream(decoder) => decoder.poll(self.now),
+        }
+    }
+}
+
+/// Small deterministic generator, so a failing chunking can be replayed from its seed.
+struct XorShift(u64);
+
+impl XorShift {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+/// Feed `capture` in chunks of the sizes `next_len` picks, never running a
+/// chunk across an idle offset, and collect every frame.
+fn play(fixture: &Fixture, capture: &[u8], mut next_len: impl FnMut(usize) -> usize) -> Vec<ExpectedFrame> {
+    let mut player = Player::new(fixture);
+    let mut frames = Vec::new();
+    let mut at = 0;
+    let mut segment_ends: Vec<usize> = fixture.idle_after.iter().copied().filter(|&end| end < capture.len()).collect();
+    segment_ends.push(capture.len());
+    for end in segment_ends {
+        while at < end {
+            let len = next_len(end - at).clamp(1, end - at);
+            frames.extend(player.feed(&capture[at..at + len]));
+            at += len;
+        }
+        frames.extend(player.idle());
+    }
+    frames.into_iter().map(ExpectedFrame::from).collect()
+}
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+}
+
+fn load(json_path: &Path) -> (Fixture, Vec<u8>) {
+    let text = std::fs::read_to_string(json_path).unwrap();
+    let fixture: Fixture =
+        serde_json::from_str(&text).unwrap_or_else(|e| panic!("{}: {}", json_path.display(), e));
+    let capture = std::fs::read(json_path.with_extension("bin"))
+        .unwrap_or_else(|e| panic!("{}: no capture: {}", json_path.display(), e));
+    (fixture, capture)
+}
+
+/// The first difference between two frame lists, for a readable failure.
+fn first_difference(expected: &[ExpectedFrame], actual: &[ExpectedFrame]) -> Option<String> {
+    let at = (0..expected.len().max(actual.len())).find(|&i| expected.get(i) != actual.get(i))?;
+    Some(format!(
+        "frame {} of {} expected, {} emitted\n  expected: {:?}\n  emitted:  {:?}",
+        at,
+        expected.len(),
+        actual.len(),
+        expected.get(at),
+        actual.get(at)
+    ))
+}
+
+#[test]
+fn decoders_match_recorded_fixtures() {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(fixtures_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no fixtures found");
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let (mut fixture, capture) = load(path);
+
+        if std::env::var_os(BLESS_VAR).is_some() {
+            fixture.frames = play(&fixture, &capture, |rest| rest);
+            let json = serde_json::to_string_pretty(&fixture).unwrap();
+            std::fs::write(path, json + "\n").unwrap();
+            continue;
+        }
+
+        let mut runs: Vec<(String, Vec<ExpectedFrame>)> = vec![
+            ("one read per segment".to_string(), play(&fixture, &capture, |rest| rest)),
+            ("one byte per read".to_string(), play(&fixture, &capture, |_| 1)),
+        ];
+        for seed in 1..=RANDOM_RUNS {
+            let mut rng = XorShift(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            let frames = play(&fixture, &capture, |_| 1 + rng.below(24));
+            runs.push((format!("random chunks, seed {}", seed), frames));
+        }
+        for (how, frames) in runs {
+            if let Some(diff) = first_difference(&fixture.frames, &frames) {
+                failures.push(format!("{} ({}): {}", name, how, diff));
+                break;
+            }
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
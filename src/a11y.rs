@@ -0,0 +1,70 @@
+// Accessibility: screen-reader names for widgets whose caption is an icon or a
+// separate label, announcements, and Ctrl+F6 navigation between the window's areas
+
+use eframe::egui::{self, accesskit, Key, KeyboardShortcut, Modifiers};
+
+/// Moves focus to the next side panel group, the terminal or the send box.
+/// Plain F6 is left to the macros.
+pub const NEXT_AREA: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::F6);
+pub const PREVIOUS_AREA: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL.plus(Modifiers::SHIFT), Key::F6);
+
+pub trait Spoken {
+    /// Name the widget for screen readers, in place of its icon or value.
+    fn spoken(self, name: &str) -> Self;
+    /// Have screen readers read the widget out whenever its text changes.
+    fn live(self) -> Self;
+}
+
+impl Spoken for egui::Response {
+    fn spoken(self, name: &str) -> Self {
+        self.ctx.accesskit_node_builder(self.id, |node| node.set_name(name));
+        self
+    }
+
+    fn live(self) -> Self {
+        self.ctx
+            .accesskit_node_builder(self.id, |node| node.set_live(accesskit::Live::Assertive));
+        self
+    }
+}
+
+/// The newest line of terminal text with anything on it.
+pub fn last_line(text: &str) -> Option<&str> {
+    text.lines().map(str::trim_end).rfind(|line| !line.trim().is_empty())
+}
+
+/// The area after (or before) the focused one in `order`, wrapping around.
+/// Without focus in any of them, the first (or last).
+pub fn next_area(order: &[egui::Id], focused: Option<egui::Id>, forward: bool) -> Option<egui::Id> {
+    let at = focused.and_then(|id| order.iter().position(|&area| area == id));
+    let index = match (at, forward) {
+        (Some(at), true) => (at + 1) % order.len(),
+        (Some(at), false) => (at + order.len() - 1) % order.len(),
+        (None, true) => 0,
+        (None, false) => order.len().checked_sub(1)?,
+    };
+    order.get(index).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_line_skips_the_blank_ones() {
+        assert_eq!(last_line("boot\nOK\r\n\n  \n"), Some("OK"));
+        assert_eq!(last_line("partial"), Some("partial"));
+        assert_eq!(last_line("\n\n"), None);
+    }
+
+    #[test]
+    fn areas_cycle_both_ways() {
+        let order: Vec<egui::Id> = ["Connection", "Terminal", "Send"].into_iter().map(egui::Id::new).collect();
+        assert_eq!(next_area(&order, None, true), Some(order[0]));
+        assert_eq!(next_area(&order, None, false), Some(order[2]));
+        assert_eq!(next_area(&order, Some(order[2]), true), Some(order[0]));
+        assert_eq!(next_area(&order, Some(order[0]), false), Some(order[2]));
+        assert_eq!(next_area(&order, Some(egui::Id::new("elsewhere")), true), Some(order[0]));
+        assert_eq!(next_area(&[], None, false), None);
+    }
+}
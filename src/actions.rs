@@ -0,0 +1,341 @@
+// Named user actions shared by the command palette, hotkeys and buttons, so
+// each one has a single name, shortcut and enable rule
+
+use eframe::egui::{Key, KeyboardShortcut, Modifiers};
+
+use crate::app::ComAnalyzerApp;
+use rustcom_core::macros::Macro;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Connect,
+    Disconnect,
+    CancelConnecting,
+    ClearTerminal,
+    SaveTerminal,
+    GoTo,
+    ReadLastLine,
+    SaveLog,
+    GenerateReport,
+    ResetCounters,
+    ToggleConfigSlot,
+    CycleProtocol,
+    BaudUp,
+    BaudDown,
+    ShowRejected,
+    MonitorOnly,
+    AutoScroll,
+    StripAnsi,
+    TimestampTx,
+    AutoReconnect,
+    LowLatency,
+    Filter,
+    Logging,
+    Schedule,
+    Repeat,
+    Keepalive,
+    Dtr,
+    Rts,
+}
+
+impl Action {
+    /// In palette order when nothing is typed.
+    pub const ALL: [Action; 28] = [
+        Action::Connect,
+        Action::Disconnect,
+        Action::CancelConnecting,
+        Action::ClearTerminal,
+        Action::SaveTerminal,
+        Action::GoTo,
+        Action::ReadLastLine,
+        Action::SaveLog,
+        Action::GenerateReport,
+        Action::ResetCounters,
+        Action::ToggleConfigSlot,
+        Action::CycleProtocol,
+        Action::BaudUp,
+        Action::BaudDown,
+        Action::ShowRejected,
+        Action::MonitorOnly,
+        Action::AutoScroll,
+        Action::StripAnsi,
+        Action::TimestampTx,
+        Action::AutoReconnect,
+        Action::LowLatency,
+        Action::Filter,
+        Action::Logging,
+        Action::Schedule,
+        Action::Repeat,
+        Action::Keepalive,
+        Action::Dtr,
+        Action::Rts,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Connect => "Connect",
+            Action::Disconnect => "Disconnect",
+            Action::CancelConnecting => "Cancel connecting",
+            Action::ClearTerminal => "Clear terminal",
+            Action::SaveTerminal => "Save terminal",
+            Action::GoTo => "Go to offset or line",
+            Action::ReadLastLine => "Read last line aloud",
+            Action::SaveLog => "Save log",
+            Action::GenerateReport => "Generate report",
+            Action::ResetCounters => "Reset counters",
+            Action::ToggleConfigSlot => "Switch config slot",
+            Action::CycleProtocol => "Next protocol decoder",
+            Action::BaudUp => "Next baud rate",
+            Action::BaudDown => "Previous baud rate",
+            Action::ShowRejected => "Show rejected data",
+            Action::MonitorOnly => "Monitor only",
+            Action::AutoScroll => "Auto-scroll",
+            Action::StripAnsi => "Strip ANSI codes",
+            Action::TimestampTx => "Timestamp TX lines",
+            Action::AutoReconnect => "Auto-reconnect",
+            Action::LowLatency => "Low latency mode",
+            Action::Filter => "Enable filter",
+            Action::Logging => "Enable logging",
+            Action::Schedule => "Run schedule",
+            Action::Repeat => "Repeat send",
+            Action::Keepalive => "Keepalive",
+            Action::Dtr => "DTR",
+            Action::Rts => "RTS",
+        }
+    }
+
+    /// The hotkey that runs the action anywhere in the window.
+    pub fn shortcut(&self) -> Option<KeyboardShortcut> {
+        let ctrl = |key| Some(KeyboardShortcut::new(Modifiers::CTRL, key));
+        match self {
+            Action::GoTo => ctrl(Key::G),
+            Action::ReadLastLine => Some(KeyboardShortcut::new(Modifiers::CTRL | Modifiers::SHIFT, Key::L)),
+            Action::ToggleConfigSlot => ctrl(Key::B),
+            Action::CycleProtocol => Some(KeyboardShortcut::new(Modifiers::CTRL | Modifiers::SHIFT, Key::D)),
+            Action::BaudUp => ctrl(Key::ArrowUp),
+            Action::BaudDown => ctrl(Key::ArrowDown),
+            _ => None,
+        }
+    }
+}
+
+/// Opens the command palette.
+pub const PALETTE_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::CTRL.plus(Modifiers::SHIFT), Key::P);
+
+/// How well `query` matches `text`: every query character must appear in
+/// order, ignoring case and spaces. Runs of adjacent characters and word starts
+/// score higher. `None` when it does not match.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut from = 0;
+    let mut last = None;
+    for c in query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()) {
+        let at = from + text[from..].iter().position(|&t| t == c)?;
+        score += 1;
+        if at > 0 && last == Some(at - 1) {
+            score += 4;
+        }
+        if at == 0 || !text[at - 1].is_alphanumeric() {
+            score += 3;
+        }
+        last = Some(at);
+        from = at + 1;
+    }
+    Some(score)
+}
+
+/// A palette row: an action, or a macro to send by its index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteItem {
+    Action(Action),
+    Macro(usize),
+}
+
+/// How a macro is listed in the palette; a template's name ends in an
+/// ellipsis, since it asks for values first.
+pub fn macro_palette_name(m: &Macro) -> String {
+    let ellipsis = if m.template { "…" } else { "" };
+    format!("Send macro: {}{}", m.label(), ellipsis)
+}
+
+/// The actions and macros matching `query`, best first; ties keep registry
+/// order, then macro order.
+pub fn palette_matches(query: &str, macros: &[Macro]) -> Vec<PaletteItem> {
+    let actions = Action::ALL
+        .iter()
+        .filter_map(|&action| fuzzy_score(query, action.name()).map(|score| (score, PaletteItem::Action(action))));
+    let macros = macros.iter().enumerate().filter_map(|(index, m)| {
+        fuzzy_score(query, &macro_palette_name(m)).map(|score| (score, PaletteItem::Macro(index)))
+    });
+    let mut scored: Vec<(u32, PaletteItem)> = actions.chain(macros).collect();
+    scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(_, item)| item).collect()
+}
+
+/// The open palette: what has been typed and the highlighted row.
+#[derive(Debug, Default)]
+pub struct CommandPalette {
+    pub query: String,
+    pub selected: usize,
+}
+
+impl ComAnalyzerApp {
+    /// Whether `action` can run now; the palette greys it out and buttons are
+    /// disabled otherwise.
+    pub fn action_enabled(&self, action: Action) -> bool {
+        match action {
+            Action::Connect => !self.connected && !self.connecting(),
+            Action::Disconnect => self.connected,
+            Action::CancelConnecting => self.connecting() && !self.connected,
+            Action::SaveTerminal => !self.receive_buffer_display.is_empty(),
+            Action::SaveLog => self.logging_enabled && self.log_stream.is_none(),
+            Action::ShowRejected => self.keep_rejected || !self.rejected.is_empty(),
+            Action::Dtr | Action::Rts => !self.monitor_only,
+            Action::Repeat => self.repeat.is_running() || (self.connected && !self.monitor_only),
+            _ => true,
+        }
+    }
+
+    /// The on/off state of a toggle action; `None` for one-shot actions.
+    pub fn action_checked(&self, action: Action) -> Option<bool> {
+        Some(match action {
+            Action::MonitorOnly => self.monitor_only,
+            Action::AutoScroll => self.auto_scroll,
+            Action::StripAnsi => self.strip_ansi,
+            Action::TimestampTx => self.timestamp_tx,
+            Action::AutoReconnect => self.auto_reconnect,
+            Action::LowLatency => self.low_latency,
+            Action::Filter => self.filter_enabled,
+            Action::Logging => self.logging_enabled,
+            Action::Schedule => self.schedule_running,
+            Action::Repeat => self.repeat.is_running(),
+            Action::Keepalive => self.keepalive.enabled,
+            Action::Dtr => self.dtr_state,
+            Action::Rts => self.rts_state,
+            _ => return None,
+        })
+    }
+
+    /// Run `action` if it is enabled; toggles flip.
+    pub fn run_action(&mut self, action: Action) {
+        if !self.action_enabled(action) {
+            return;
+        }
+        match action {
+            Action::Connect => self.connect_manually(),
+            Action::Disconnect => self.disconnect(),
+            Action::CancelConnecting => self.cancel_reconnect(),
+            Action::ClearTerminal => self.clear_terminal(),
+            Action::SaveTerminal => self.save_buffer(),
+            Action::GoTo => self.goto_open = true,
+            Action::ReadLastLine => self.read_last_line(),
+            Action::SaveLog => self.save_log(),
+            Action::GenerateReport => self.generate_report(),
+            Action::ResetCounters => self.reset_counters(),
+            Action::ToggleConfigSlot => self.toggle_config_slot(),
+            Action::CycleProtocol => self.cycle_protocol(),
+            Action::BaudUp => self.step_baud(true),
+            Action::BaudDown => self.step_baud(false),
+            Action::ShowRejected => self.rejected_open = true,
+            Action::MonitorOnly => self.monitor_only = !self.monitor_only,
+            Action::AutoScroll => self.auto_scroll = !self.auto_scroll,
+            Action::StripAnsi => {
+                self.strip_ansi = !self.strip_ansi;
+                self.update_display_buffer();
+            }
+            Action::TimestampTx => self.timestamp_tx = !self.timestamp_tx,
+            Action::AutoReconnect => self.auto_reconnect = !self.auto_reconnect,
+            Action::LowLatency => self.set_low_latency(!self.low_latency),
+            Action::Filter => self.filter_enabled = !self.filter_enabled,
+            Action::Logging => self.set_logging(!self.logging_enabled),
+            Action::Schedule => self.schedule_running = !self.schedule_running,
+            Action::Repeat => {
+                if self.repeat.is_running() {
+                    self.repeat.stop();
+                } else {
+                    self.repeat.start();
+                }
+            }
+            Action::Keepalive => self.keepalive.enabled = !self.keepalive.enabled,
+            Action::Dtr => {
+                if let Err(e) = self.set_dtr(!self.dtr_state) {
+                    self.error_message = Some(e);
+                } else {
+                    self.dtr_state = !self.dtr_state;
+                }
+            }
+            Action::Rts => {
+                if let Err(e) = self.set_rts(!self.rts_state) {
+                    self.error_message = Some(e);
+                } else {
+                    self.rts_state = !self.rts_state;
+                }
+            }
+        }
+    }
+
+    /// The action the connect button stands for right now.
+    pub fn connect_action(&self) -> Action {
+        if self.connected {
+            Action::Disconnect
+        } else if self.connecting() {
+            Action::CancelConnecting
+        } else {
+            Action::Connect
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_search_prefers_word_starts_and_runs() {
+        assert_eq!(fuzzy_score("", "Connect"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "Connect"), None);
+        assert_eq!(fuzzy_score("tcn", "Connect"), None, "out of order");
+        assert!(fuzzy_score("CON", "Connect").is_some());
+
+        assert_eq!(palette_matches("sl", &[])[0], PaletteItem::Action(Action::SaveLog));
+        assert_eq!(palette_matches("clear", &[])[0], PaletteItem::Action(Action::ClearTerminal));
+        assert_eq!(
+            palette_matches("baud", &[]),
+            [PaletteItem::Action(Action::BaudUp), PaletteItem::Action(Action::BaudDown)]
+        );
+        assert_eq!(palette_matches("", &[]).len(), Action::ALL.len());
+
+        let macros = [
+            Macro {
+                name: "Reset".to_string(),
+                ..Macro::default()
+            },
+            Macro {
+                name: "Set channel".to_string(),
+                template: true,
+                ..Macro::default()
+            },
+        ];
+        assert_eq!(macro_palette_name(&macros[1]), "Send macro: Set channel…");
+        assert_eq!(palette_matches("set chan", &macros)[0], PaletteItem::Macro(1));
+        assert_eq!(palette_matches("", &macros).len(), Action::ALL.len() + 2);
+    }
+
+    #[test]
+    fn names_and_shortcuts_are_unique() {
+        for (i, a) in Action::ALL.iter().enumerate() {
+            for b in &Action::ALL[i + 1..] {
+                assert_ne!(a, b);
+                assert_ne!(a.name(), b.name());
+                if let (Some(x), Some(y)) = (a.shortcut(), b.shortcut()) {
+                    assert_ne!(x, y, "{} and {}", a.name(), b.name());
+                }
+            }
+            for reserved in [PALETTE_SHORTCUT, crate::a11y::NEXT_AREA, crate::a11y::PREVIOUS_AREA] {
+                assert_ne!(a.shortcut(), Some(reserved));
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+// RX/TX activity LEDs with a short afterglow, and an optional audible tick
+
+use std::time::{Duration, Instant};
+
+/// How long an LED stays visibly lit after the last byte.
+pub const LED_DECAY: Duration = Duration::from_millis(100);
+
+/// Repaint interval while an LED is fading, so the decay is animated.
+pub const LED_REPAINT_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Default)]
+pub struct ActivityLed {
+    last_activity: Option<Instant>,
+}
+
+impl ActivityLed {
+    /// Record activity. Returns true when the LED was dark, i.e. this starts a
+    /// new blink rather than extending one.
+    pub fn trigger(&mut self, now: Instant) -> bool {
+        let was_dark = self.brightness(now) == 0.0;
+        self.last_activity = Some(now);
+        was_dark
+    }
+
+    /// 1.0 right after activity, fading linearly to 0.0 over `LED_DECAY`.
+    pub fn brightness(&self, now: Instant) -> f32 {
+        match self.last_activity {
+            Some(at) => {
+                let elapsed = now.saturating_duration_since(at);
+                (1.0 - elapsed.as_secs_f32() / LED_DECAY.as_secs_f32()).max(0.0)
+            }
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(windows)]
+#[link(name = "user32")]
+extern "system" {
+    fn MessageBeep(kind: u32) -> i32;
+}
+
+/// A short system sound. On Windows this is the default beep; elsewhere the
+/// terminal bell, which is only audible when started from a terminal.
+pub fn tick() {
+    #[cfg(windows)]
+    // SAFETY: MessageBeep takes a plain integer and has no memory preconditions.
+    unsafe {
+        MessageBeep(0xFFFF_FFFF);
+    }
+    #[cfg(not(windows))]
+    {
+        use std::io::Write;
+        let mut out = std::io::stdout();
+        let _ = out.write_all(b"\x07");
+        let _ = out.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blink_fades_out_over_the_decay() {
+        let mut led = ActivityLed::default();
+        let start = Instant::now();
+        assert_eq!(led.brightness(start), 0.0);
+
+        assert!(led.trigger(start));
+        assert_eq!(led.brightness(start), 1.0);
+        let half = led.brightness(start + LED_DECAY / 2);
+        assert!((half - 0.5).abs() < 0.01);
+        assert_eq!(led.brightness(start + LED_DECAY), 0.0);
+        assert_eq!(led.brightness(start + LED_DECAY * 10), 0.0);
+    }
+
+    #[test]
+    fn activity_while_lit_extends_the_blink() {
+        let mut led = ActivityLed::default();
+        let start = Instant::now();
+        assert!(led.trigger(start));
+        assert!(!led.trigger(start + LED_DECAY / 2));
+        assert_eq!(led.brightness(start + LED_DECAY / 2), 1.0);
+        assert!(led.brightness(start + LED_DECAY) > 0.0);
+        assert!(led.trigger(start + LED_DECAY * 3));
+    }
+}
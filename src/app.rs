@@ -1,31 +1,300 @@
 use serialport::{SerialPort, SerialPortInfo};
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
-use regex::Regex;
-use chrono::Local;
+use chrono::{DateTime, Local};
 
-use crate::hex;
-use crate::logging::{self, DataLogEntry};
+use crate::a11y;
+use crate::actions::CommandPalette;
+use crate::activity::{self, ActivityLed};
+use crate::boot_log::{self, BootCapture, DEFAULT_BOOT_LOG_KB, DEFAULT_BOOT_LOG_SECONDS};
+use crate::cable_test::CableTestRun;
+use crate::event_port::EventPort;
+use crate::fast_attach::{AttachTiming, FastAttach};
+use crate::net_connect::NetConnect;
+use crate::file_send::{FilePreview, FileTransfer, FirmwareSendMode, LineConversion, XmodemRun};
+use crate::flow::{FlowEvent, XonXoffTracker};
+use crate::instance::{self, InstanceServer, PortLock, PortOwner};
+use crate::portinfo::PortInfo;
+use crate::reader::PortReader;
+use crate::recorder::{Recording, RECORDER_DIR};
+use crate::report::{self, Excerpt, Report};
+use crate::sanitize;
 use crate::serial::*;
+use crate::sound::{SoundEvent, SoundMonitor, SoundSettings};
+use crate::stats::{BurstStats, EchoLatency};
+use crate::theme::Palette;
+use crate::translate::TranslationTable;
+
+use rustcom_core::auto_view::{self, AutoViewConfig, Rendering};
+use rustcom_core::burst::{self, Burst, BurstTracker, DEFAULT_BURST_GAP_MS};
+use rustcom_core::cable_test::CableTestConfig;
+use rustcom_core::clock::ClockWatch;
+use rustcom_core::excerpt::{self, ExcerptFormat};
+use rustcom_core::export::{self, ExportFormat, ExportRange, TimeWindow};
+use rustcom_core::failover::FailoverConfig;
+use rustcom_core::filter::{self, FilterRule, FilterSet};
+use rustcom_core::hex::{self, SoftParity};
+use rustcom_core::inspect;
+use rustcom_core::keepalive::{KeepaliveConfig, KeepaliveTimer};
+use rustcom_core::line_editor::{LineEditor, SendDiscipline};
+use rustcom_core::line_offsets::{self, Evicted, GotoTarget, LineStart};
+use rustcom_core::lines::{LineAssembler, LineBuffer, DEFAULT_IDLE_FLUSH_MS, NO_TERMINATOR_NOTE};
+use rustcom_core::live_view::{LiveFeed, LiveViewConfig, LiveViewServer};
+use rustcom_core::log_import::LogFormat;
+use rustcom_core::logging::database::{DbLogger, DbRecord};
+use rustcom_core::logging::stream::LogStream;
+use rustcom_core::logging::syslog::{SyslogConfig, SyslogForwarder};
+use rustcom_core::metrics::{self, Gauge, Metrics, MetricsConfig, MetricsServer, ValueSource};
+use rustcom_core::logging::{self, DataLogEntry, Direction, LogBuffer};
+use rustcom_core::macros::Macro;
+use rustcom_core::template::{PlaceholderKind, Template};
+use rustcom_core::payloads::PayloadRing;
+use rustcom_core::repeat::{RepeatTimer, DEFAULT_REPEAT_MS};
+use rustcom_core::pinned::PinnedValue;
+use rustcom_core::port_config::{DataBits, FlowControl, Parity, StopBits};
+use rustcom_core::protocol::detect::{DetectedProtocol, ProtocolDetector};
+use rustcom_core::protocol::modbus::{self, TimingStats, TransactionTracker, DEFAULT_RESPONSE_TIMEOUT_MS};
+use rustcom_core::protocol::framing_rule::FramingRule;
+use rustcom_core::protocol::nmea::NmeaStats;
+use rustcom_core::protocol::{
+    self, CustomFraming, DecodedFrame, DecodedView, Decoder, ProtocolMode, SendEncoding,
+};
+use rustcom_core::rate_alarm::{RateAlarm, RateAlarmConfig, RateEvent};
+use rustcom_core::rate_limit::RateLimiter;
+use rustcom_core::rejected::{RejectedRing, DEFAULT_REJECTED_KB};
+use rustcom_core::rule_set::{self, RuleSet};
+use rustcom_core::rx_queue::{RxQueue, DEFAULT_RX_FRAME_BUDGET};
+use rustcom_core::schedule::ScheduledSend;
+use rustcom_core::session::SessionTag;
+use rustcom_core::send_guard::SendGuard;
+use rustcom_core::send_history::SendHistory;
+use rustcom_core::settings::{ConfigSlot, Settings, ThemeSetting, DEFAULT_TERMINAL_FONT_SIZE};
+use rustcom_core::split_log::{SplitLog, DEFAULT_SPLIT_TEMPLATE, SPLIT_LOG_DIR};
+use rustcom_core::stats_snapshot::{Better, Metric, StatsSnapshot};
+use rustcom_core::tcp_bridge::{TcpBridge, TcpBridgeConfig};
+use rustcom_core::tcp_port::ConnectionType;
+use rustcom_core::text_encoding::{
+    split_lines_utf16, DetectedEncoding, EncodingDetection, EncodingDetector, TextEncoding, Utf16Decoder,
+};
+use rustcom_core::variables::{self, VariableTable};
+use rustcom_core::watch::{Watch, WatchEvent};
 
 pub const MAX_BUFFER_SIZE: usize = 100_000;
 pub const BUFFER_DRAIN_SIZE: usize = 10_000;
-pub const SERIAL_READ_BUFFER_SIZE: usize = 1024;
+/// Largest single read; big reads are split up again by the RX queue.
+pub const SERIAL_READ_BUFFER_SIZE: usize = 64 * 1024;
 pub const DEFAULT_REPAINT_INTERVAL_MS: u64 = 50;
 pub const DEFAULT_PORT_SCAN_INTERVAL_MS: u64 = 3000;
 pub const DEFAULT_RECONNECT_DELAY_MS: u64 = 2000;
+pub const DEFAULT_EOF_ZERO_READS: u32 = 5;
+pub const DEFAULT_WRITE_TIMEOUT_MS: u64 = 1000;
+/// How long the filter holds a partial line before matching it as it is.
+pub const DEFAULT_FILTER_HOLD_MS: u64 = 500;
 pub const SIDEBAR_WIDTH: f32 = 240.0;
+/// Ctrl+wheel zoom limits for the terminal font.
+pub const MIN_TERMINAL_FONT_SIZE: f32 = 6.0;
+pub const MAX_TERMINAL_FONT_SIZE: f32 = 36.0;
+pub const BOTH_VIEW_HEX_HEADER: &str = "=== HEX ===\n";
+/// Entries kept in memory for the UI while streaming to a log file.
+pub const DEFAULT_LOG_MEMORY_ENTRIES: usize = 10_000;
+pub const MAX_DECODED_FRAMES: usize = 5_000;
+pub const DECODED_FRAMES_DRAIN: usize = 500;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ViewMode {
     Ascii,
     Hex,
     Both,
+    /// Each line as text or as a hex dump, whichever suits it.
+    Auto,
+}
+
+/// What the terminal gutter shows next to each line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gutter {
+    Off,
+    Offset,
+    Line,
+}
+
+impl Gutter {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Gutter::Off => "Off",
+            Gutter::Offset => "Byte offset",
+            Gutter::Line => "Line number",
+        }
+    }
+}
+
+/// A line inserted into the text view at a receive-buffer offset.
+#[derive(Debug, Clone)]
+pub enum DisplayMark {
+    /// End of an RX burst, with its label.
+    Burst(String),
+    /// A decoded frame that completed at this point, with its Modbus slave.
+    Frame(Option<u8>, String),
+}
+
+/// Received data in `receive_buffer`, as opposed to the notes and markers
+/// between it. The text views decode it from the display encoding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RxSpan {
+    pub start: usize,
+    pub end: usize,
+    /// Received bytes before `start` this session, which gives UTF-16 its
+    /// code unit alignment.
+    pub stream: u64,
+}
+
+/// The settings the display was rendered with; any change renders it again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayKey {
+    view_mode: ViewMode,
+    auto_percent: u8,
+    encoding: TextEncoding,
+    strip_ansi: bool,
+    mask_7bit: bool,
+    translate: bool,
+    evicted: Evicted,
+}
+
+/// A received line rendered for the text views.
+struct TextLine<'a> {
+    start: LineStart,
+    bytes: &'a [u8],
+    piece: String,
+    /// Ends with a newline, so later data cannot change it.
+    complete: bool,
+    highlight: Option<[u8; 3]>,
+}
+
+/// A piece of the text view: where it starts in the session, unless it is
+/// not received data, its text, and its highlight colour.
+type DisplayPiece = (Option<LineStart>, String, Option<[u8; 3]>);
+
+/// The text view, with the position and highlight of each of its lines.
+type RenderedText = (String, Vec<Option<LineStart>>, Vec<Option<[u8; 3]>>);
+
+/// Collects the lines of a stretch of the buffer rendered piece by piece.
+struct TextLines<'a> {
+    data: &'a [u8],
+    lines: Vec<TextLine<'a>>,
+    next: LineStart,
+}
+
+impl<'a> TextLines<'a> {
+    /// Add `data[from..to]`, which follows what was added before. A line cut
+    /// short by a switch between received data and notes goes on with it.
+    fn add(&mut self, from: usize, to: usize, piece: String, complete: bool, highlight: Option<[u8; 3]>) {
+        let start = self.next;
+        self.next = start.advance(&self.data[from..to]);
+        match self.lines.last_mut() {
+            Some(last) if !last.complete => {
+                let at = from - last.bytes.len();
+                last.bytes = &self.data[at..to];
+                last.piece.push_str(&piece);
+                last.complete = complete;
+                last.highlight = last.highlight.or(highlight);
+            }
+            _ => self.lines.push(TextLine {
+                start,
+                bytes: &self.data[from..to],
+                piece,
+                complete,
+                highlight,
+            }),
+        }
+    }
+}
+
+/// How far `receive_buffer_display` is final, so received data can be
+/// appended instead of rendering the whole buffer again: whole hex rows, or
+/// text up to the last line end. What follows is rendered again as it grows.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayCursor {
+    key: DisplayKey,
+    bytes: usize,
+    position: LineStart,
+    /// Length of the display text and lines, and chars shown, up to `bytes`.
+    text_len: usize,
+    lines: usize,
+    chars: usize,
+}
+
+/// A macro open in the edit dialog. Saving replaces the macro at `index`, or
+/// adds the draft when `index` is `None`.
+#[derive(Debug, Clone)]
+pub struct MacroEdit {
+    pub index: Option<usize>,
+    pub draft: Macro,
+    pub error: Option<String>,
+}
+
+/// A log file replayed into the terminal while disconnected.
+#[derive(Debug, Clone)]
+pub struct OfflineSession {
+    pub path: String,
+    pub format: LogFormat,
+    pub entries: usize,
+    /// Lines, rows or packets of the file that could not be read.
+    pub skipped: usize,
+}
+
+/// A log being replayed a frame's budget at a time, each entry with its
+/// logged time.
+pub struct OfflineReplay {
+    pub entries: VecDeque<(DataLogEntry, DateTime<Local>)>,
+    /// When the first entry counts as having arrived; the rest follow with
+    /// their logged spacing.
+    pub first: DateTime<Local>,
+    pub start: std::time::Instant,
+}
+
+/// The values being typed for a template macro's placeholders before it is
+/// sent; `values` has one entry per placeholder, empty for the default.
+#[derive(Debug, Clone)]
+pub struct TemplatePrompt {
+    pub index: usize,
+    pub template: Template,
+    pub values: Vec<String>,
+    /// Focus the first field on the next frame.
+    pub focus: bool,
+}
+
+impl TemplatePrompt {
+    pub fn new(index: usize, template: Template) -> Self {
+        let values = template
+            .placeholders
+            .iter()
+            .map(|p| match &p.kind {
+                PlaceholderKind::Choice(options) => p.default.clone().unwrap_or_else(|| options[0].clone()),
+                _ => String::new(),
+            })
+            .collect();
+        Self {
+            index,
+            template,
+            values,
+            focus: true,
+        }
+    }
 }
 
 pub struct ComAnalyzerApp {
     // Connection settings
     pub available_ports: Vec<SerialPortInfo>,
     pub selected_port: Option<String>,
+    /// Whether Connect opens `selected_port` or `tcp_address`.
+    pub connection_type: ConnectionType,
+    /// `host:port` of a serial server such as ser2net, in TCP client mode.
+    pub tcp_address: String,
+    /// Ports to try in order instead of `selected_port`.
+    pub failover: FailoverConfig,
+    /// Text of the candidate being added to the failover list.
+    pub failover_input: String,
+    /// Lists and opens ports; replaced in tests.
+    pub port_opener: PortOpener,
     pub baud_rate: String,
     pub data_bits: DataBits,
     pub stop_bits: StopBits,
@@ -33,13 +302,117 @@ pub struct ComAnalyzerApp {
     pub flow_control: FlowControl,
     pub connected: bool,
     pub serial_port: Option<Arc<Mutex<Box<dyn SerialPort>>>>,
+    /// Reads the port on its own thread; `None` if the port could not be cloned.
+    pub port_reader: Option<PortReader>,
+    /// The settings and driver of the last port opened, as the OS reports them.
+    pub port_info: Option<PortInfo>,
+    /// Shorter read timeouts and echo latency timing; see `set_low_latency`.
+    pub low_latency: bool,
+    pub echo_latency: EchoLatency,
+    /// RX written straight to a file by the reader thread, bypassing the
+    /// display; see `start_recording`.
+    pub recording: Option<Recording>,
+    pub recorder_dir: String,
+    pub recorder_gzip: bool,
+    pub recorder_tail: bool,
 
     // Display
     pub receive_buffer: Vec<u8>,
     pub receive_buffer_display: String,
+    /// Session position of each line of `receive_buffer_display`; `None` for
+    /// lines that are not received data (dividers, headers, continuations).
+    pub display_lines: Vec<Option<LineStart>>,
+    /// Highlight colour of each line of `receive_buffer_display`, matched
+    /// once when the line is rendered.
+    pub display_highlights: Vec<Option<[u8; 3]>>,
+    /// The active highlighting rules the display was matched against.
+    pub display_highlight_rules: Vec<(String, [u8; 3])>,
+    /// Where received data is appended to the display; `None` after a render
+    /// that cannot be extended.
+    pub display_cursor: Option<DisplayCursor>,
+    /// Bytes and lines dropped from the front of `receive_buffer` this session.
+    pub evicted: Evicted,
+    pub gutter: Gutter,
+    pub goto_open: bool,
+    /// Open while the command palette (Ctrl+Shift+P) is showing.
+    pub command_palette: Option<CommandPalette>,
+    /// Side panel group headings, the terminal and the send box in window
+    /// order, for Ctrl+F6. Collected while rendering.
+    pub focus_areas: Vec<eframe::egui::Id>,
+    /// What Read last line last handed to the screen reader, numbered so the
+    /// same line read twice is announced twice.
+    pub announcement: Option<(u64, String)>,
+    pub goto_input: String,
+    pub goto_by_line: bool,
+    /// Display line to scroll the terminal to on the next frame.
+    pub goto_line: Option<usize>,
+    /// Character range of the current terminal selection, if any.
+    pub terminal_selection: Option<std::ops::Range<usize>>,
+    /// The excerpt being annotated; the dialog is open while this is set.
+    pub doc_excerpt: Option<excerpt::Excerpt>,
+    pub doc_excerpt_format: ExcerptFormat,
+    /// Saved excerpt file to reopen for editing.
+    pub doc_excerpt_path: String,
+    pub terminal_font_size: f32,
+    /// Where the terminal's scroll area was last frame and how far it was
+    /// scrolled, so a zoom can keep the same text in view.
+    pub terminal_rect: eframe::egui::Rect,
+    pub terminal_scroll_offset: eframe::egui::Vec2,
     pub send_buffer: String,
     pub view_mode: ViewMode,
+    pub auto_view: AutoViewConfig,
     pub strip_ansi: bool,
+    pub timestamp_tx: bool,
+    pub mask_7bit: bool,
+    pub translation_enabled: bool,
+    pub translation_table: Option<TranslationTable>,
+    pub translation_path: String,
+    /// The text views decode received data from this encoding; the buffer
+    /// keeps the bytes as they arrived.
+    pub display_encoding: TextEncoding,
+    /// Set while the display encoding is UTF-16: decodes RX for the pinned
+    /// values, variables and syslog.
+    pub utf16_decoder: Option<Utf16Decoder>,
+    /// Decodes the pieces the filter judges, in order, for matching and the
+    /// live view, while the display encoding is UTF-16.
+    pub piece_decoder: Option<Utf16Decoder>,
+    /// Where received data sits in the buffer, in order.
+    pub rx_spans: Vec<RxSpan>,
+    /// Received bytes judged by the filter this session.
+    pub rx_stream: u64,
+    pub encoding_detection: EncodingDetection,
+    pub encoding_detector: EncodingDetector,
+    pub encoding_suggestion: Option<TextEncoding>,
+    pub burst_grouping: bool,
+    pub burst_gap_ms: u64,
+    /// Treat a partial RX line as complete for pinned values, variables and
+    /// syslog once RX has been quiet for `idle_flush_ms`.
+    pub idle_flush: bool,
+    pub idle_flush_ms: u64,
+    /// When the last RX chunk arrived, until its partial line is flushed.
+    pub last_rx_at: Option<std::time::Instant>,
+    pub burst_tracker: BurstTracker,
+    /// Wall clock vs monotonic clock, to mark sleep and clock changes.
+    pub clock_watch: ClockWatch,
+    /// Lines shown between received bytes, anchored at receive-buffer offsets.
+    pub display_marks: Vec<(usize, DisplayMark)>,
+    pub burst_stats: BurstStats,
+    /// When the counters were last reset, for session duration and throughput.
+    pub stats_started: std::time::Instant,
+    /// Saved sessions shown next to the live statistics.
+    pub stats_snapshots: Vec<StatsSnapshot>,
+    pub stats_snapshot_path: String,
+    pub snapshot_on_disconnect: bool,
+
+    pub theme_setting: ThemeSetting,
+    pub rx_led: ActivityLed,
+    pub tx_led: ActivityLed,
+    pub activity_ticks: bool,
+    pub sound_settings: SoundSettings,
+    /// Open while the sound monitor is on; `None` costs nothing per byte.
+    pub sound: Option<SoundMonitor>,
+    /// Accent colours for the theme in use.
+    pub palette: Palette,
 
     // Advanced features
     pub auto_scroll: bool,
@@ -47,8 +420,27 @@ pub struct ComAnalyzerApp {
     pub rts_state: bool,
     pub auto_reconnect: bool,
     pub reconnect_delay_ms: u64,
+    /// How long a send may wait for the port to take more bytes.
+    pub write_timeout_ms: u64,
     pub reconnecting: bool,
+    pub retry_initial_connect: bool,
+    /// While reconnecting, watch for the port on a worker thread and open it
+    /// the moment it appears, to catch a device's first bytes after power-on.
+    pub fast_attach_enabled: bool,
+    /// Raise DTR straight after a fast attach opens the port.
+    pub fast_attach_dtr: bool,
+    pub fast_attach: Option<FastAttach>,
+    /// A TCP or RFC 2217 connect under way on its worker thread.
+    pub net_connect: Option<NetConnect>,
+    /// Appearance, open and first-byte times of the last fast attach.
+    pub attach_timing: Option<AttachTiming>,
+    pub reconnect_attempts: u32,
     pub last_reconnect_attempt: std::time::Instant,
+    /// Treat `eof_zero_reads` consecutive `Ok(0)` reads as the peer closing the
+    /// port. Some drivers return 0 now and then, so both are adjustable.
+    pub eof_detection: bool,
+    pub eof_zero_reads: u32,
+    pub zero_reads: u32,
 
     // Port scanning
     pub auto_scan_ports: bool,
@@ -58,23 +450,230 @@ pub struct ComAnalyzerApp {
     // Logging
     pub logging_enabled: bool,
     pub log_file_path: String,
-    pub log_entries: Vec<DataLogEntry>,
+    /// Write entries to `log_file_path` as they are captured instead of on Save Log.
+    pub log_streaming: bool,
+    /// While streaming, also keep the newest `log_memory_entries` in memory.
+    pub log_keep_in_memory: bool,
+    pub log_memory_entries: usize,
+    pub log_stream: Option<LogStream>,
+    /// Log file to open as an offline session.
+    pub log_import_path: String,
+    pub offline_session: Option<OfflineSession>,
+    /// Set while an offline log is being replayed.
+    pub offline_replay: Option<OfflineReplay>,
+    pub db_path: String,
+    pub db_logger: Option<DbLogger>,
+    pub syslog: SyslogConfig,
+    pub metrics: Arc<Metrics>,
+    pub metrics_config: MetricsConfig,
+    pub metrics_server: Option<MetricsServer>,
+    /// Gauge handles by source and name, so updates need no lock.
+    pub metric_gauges: HashMap<(ValueSource, String), Gauge>,
+    pub syslog_forwarder: Option<SyslogForwarder>,
+    pub syslog_lines: LineAssembler,
+    pub live_view: LiveViewConfig,
+    pub live_view_server: Option<LiveViewServer>,
+    /// Displayed RX lines for the browser live view.
+    pub live_feed: Arc<LiveFeed>,
+    pub tcp_bridge: TcpBridgeConfig,
+    /// Runs only while connected.
+    pub tcp_bridge_server: Option<TcpBridge>,
+    pub live_lines: LineAssembler,
+    pub log_entries: LogBuffer,
+    pub boot_log_enabled: bool,
+    pub boot_log_seconds: u64,
+    pub boot_log_kb: u64,
+    pub boot_capture: Option<BootCapture>,
+    pub boot_logs: Vec<std::path::PathBuf>,
+    /// RX capture moved to a new file at each line matching `split_pattern`.
+    pub split_log: Option<SplitLog>,
+    pub split_pattern: String,
+    pub split_template: String,
+    /// Also clear the terminal at each split, leaving the banner line on top.
+    pub split_display: bool,
 
     // Filtering
     pub filter_enabled: bool,
-    pub filter_pattern: String,
-    pub filter_regex: Option<Regex>,
+    pub filter_rules: Vec<FilterRule>,
+    pub filter_set: FilterSet,
+    /// RX bytes waiting for the rest of their line before the filter sees them.
+    pub filter_lines: LineBuffer,
+    /// When the held partial line last grew.
+    pub filter_line_at: Option<std::time::Instant>,
+    pub filter_hold_ms: u64,
+    /// Keep what the filter drops in `rejected`, for auditing the rules.
+    pub keep_rejected: bool,
+    pub rejected_kb: usize,
+    pub rejected: RejectedRing,
+    pub rejected_open: bool,
+    pub rule_sets: Vec<RuleSet>,
+    pub selected_rule_set: Option<usize>,
+    pub rule_set_name: String,
+    pub rule_set_path: String,
+    /// Rules the last import left out, one message each.
+    pub rule_set_errors: Vec<String>,
+
+    // Pinned values
+    pub pinned_values: Vec<PinnedValue>,
+    /// Whole lines with their bytes kept, for byte ranges.
+    pub pinned_lines: LineBuffer,
+
+    // key=value variables
+    pub variables_enabled: bool,
+    pub variables: VariableTable,
+    pub variable_lines: LineAssembler,
+    pub watches: Vec<Watch>,
+    /// Alarm on a sustained RX rate, saved with the watches.
+    pub rate_alarm: RateAlarmConfig,
+    pub rate_tracker: RateAlarm,
+    /// The RTS level to go back to when the rate alarm clears.
+    pub rate_alarm_rts: Option<bool>,
+    /// Payloads sent on a timer, saved with the profile.
+    pub schedule: Vec<ScheduledSend>,
+    /// Whether the schedule runs; off at startup so nothing is sent unasked.
+    pub schedule_running: bool,
+
+    // Session name and colour, shown in the top panel and the window title
+    pub session_tag: SessionTag,
+    pub renaming_session: bool,
+    /// The window title last sent to the viewport.
+    pub window_title: String,
 
     // Virtual COM
     pub virtual_com_port: Option<String>,
 
+    // Cable/adapter test
+    pub cable_test: Option<CableTestRun>,
+    pub cable_test_config: CableTestConfig,
+    /// Second port of a null-modem cable; `None` for a loopback plug.
+    pub cable_test_port_b: Option<String>,
+
+    // Protocol analyzer
+    pub protocol_mode: ProtocolMode,
+    pub custom_framing: CustomFraming,
+    pub framing_rule: FramingRule,
+    /// Delimiter being typed as hex, applied to `framing_rule` once it parses.
+    pub framing_delimiter_text: String,
+    pub decoder: Option<Box<dyn Decoder>>,
+    pub decoded_frames: Vec<DecodedFrame>,
+    pub decoded_views: HashMap<ProtocolMode, DecodedView>,
+    pub rtu_timing_stats: TimingStats,
+    pub nmea_stats: NmeaStats,
+    pub modbus_tracker: TransactionTracker,
+    pub modbus_timeout_ms: u64,
+    /// Silence that ends an RTU frame; 0 uses 3.5 characters at the baud rate.
+    pub rtu_frame_gap_ms: u64,
+    /// Slave addresses to show; empty shows all.
+    pub slave_filter: BTreeSet<u8>,
+
+    // Report
+    pub report_title: String,
+    pub report_excerpt: Excerpt,
+
+    // Time-window export
+    pub export_range: ExportRange,
+    pub export_format: ExportFormat,
+    pub export_start: String,
+    pub export_end: String,
+    pub export_minutes: u32,
+    pub protocol_detector: ProtocolDetector,
+    pub protocol_suggestion: Option<DetectedProtocol>,
+    /// Once a suggestion is dismissed, none is offered again this session.
+    pub suggestion_dismissed: bool,
+
+    // File send
+    pub file_send_path: String,
+    pub file_preview: Option<FilePreview>,
+    pub firmware_send_mode: FirmwareSendMode,
+    pub file_line_conversion: LineConversion,
+    pub file_transfer: Option<FileTransfer>,
+    pub xmodem: Option<XmodemRun>,
+    /// XMODEM-1K blocks when sending.
+    pub xmodem_one_k: bool,
+    /// Ask for CRC-16 blocks when receiving.
+    pub xmodem_crc: bool,
+    pub xmodem_receive_path: String,
+    /// How the last XMODEM transfer ended.
+    pub xmodem_result: Option<String>,
+
     // Send options
+    pub monitor_only: bool,
+    /// Incremented on every successful connect so input typed for an earlier
+    /// connection can be recognised.
+    pub connection_generation: u64,
+    pub send_generation: Option<u64>,
+    pub confirm_stale_send: bool,
+    /// Send box, or typing straight to the port per character or per line.
+    pub send_discipline: SendDiscipline,
+    /// The line being typed in per-line mode.
+    pub line_editor: LineEditor,
+    /// Payloads matching one of these need confirming before they are sent.
+    pub send_guard: SendGuard,
+    pub guard_confirmation: Option<GuardConfirmation>,
+    /// Bytes the user confirmed; the next send of exactly these skips the guard.
+    pub guard_approved: Option<Vec<u8>>,
+    pub guard_lock_code: String,
+    /// Spaces out writes on every transmit path; unlimited by default.
+    pub tx_limiter: RateLimiter,
+    /// Sends waiting for the rate limit, written in order.
+    pub tx_pending: VecDeque<PendingTx>,
+    /// Sends that had to wait for the rate limit since startup.
+    pub tx_delayed: u64,
+    /// Sent after a quiet interval on the TX side while connected.
+    pub keepalive: KeepaliveConfig,
+    pub keepalive_timer: KeepaliveTimer,
+    pub keepalives_sent: u64,
+    /// Interval of the send box repeat, clamped to `MIN_REPEAT_MS`..=`MAX_REPEAT_MS`.
+    pub repeat_interval_ms: u64,
+    pub repeat: RepeatTimer,
+    pub macros: Vec<Macro>,
+    pub macro_edit: Option<MacroEdit>,
+    pub template_prompt: Option<TemplatePrompt>,
+    /// Payloads sent or copied recently, in every slot and saved across runs.
+    pub recent_payloads: PayloadRing,
+    /// Sent send box entries for Up/Down recall, per send mode.
+    pub send_history: SendHistory,
     pub send_mode: SendMode,
     pub line_ending: LineEnding,
+    /// Read `\r`, `\n`, `\t`, `\0`, `\e`, `\\` and `\xHH` in ASCII sends.
+    pub parse_escapes: bool,
+    /// Payload typed in the STX/ETX compose form.
+    pub stx_compose: String,
+    pub send_encoding: SendEncoding,
+    pub tx_parity: SoftParity,
+
+    // XON/XOFF flow control events
+    pub show_flow_events: bool,
+    pub flow_tracker: XonXoffTracker,
+    /// CTS as last read under hardware flow control; `None` if not read.
+    pub cts_state: Option<bool>,
+    pub last_cts_poll: Option<std::time::Instant>,
+
+    // Secondary port injecting event markers
+    pub event_port_name: Option<String>,
+    pub event_baud_rate: String,
+    pub event_port: Option<EventPort>,
+    pub has_event_markers: bool,
+
+    // Other RustCOM instances
+    pub instance_server: Option<InstanceServer>,
+    pub port_lock: Option<PortLock>,
+    pub port_owner: Option<PortOwner>,
+
+    // A/B configuration slots
+    pub config_slots: [Option<ConfigSlot>; 2],
+    pub active_slot: usize,
 
     // UI state
     pub error_message: Option<String>,
+    /// Where an unreadable settings file was moved at startup, and the error
+    /// saying so; offered from the error bar while that error is shown.
+    pub corrupt_settings: Option<(std::path::PathBuf, String)>,
     pub bytes_received: usize,
+    /// Data read from the port but not yet processed.
+    pub rx_queue: RxQueue,
+    /// Bytes taken from `rx_queue` per frame.
+    pub rx_frame_budget: usize,
     pub bytes_sent: usize,
 }
 
@@ -83,6 +682,11 @@ impl Default for ComAnalyzerApp {
         Self {
             available_ports: serialport::available_ports().unwrap_or_default(),
             selected_port: None,
+            connection_type: ConnectionType::default(),
+            tcp_address: String::new(),
+            failover: FailoverConfig::default(),
+            failover_input: String::new(),
+            port_opener: PortOpener::default(),
             baud_rate: "9600".to_string(),
             data_bits: DataBits::Eight,
             stop_bits: StopBits::One,
@@ -90,32 +694,243 @@ impl Default for ComAnalyzerApp {
             flow_control: FlowControl::None,
             connected: false,
             serial_port: None,
+            port_reader: None,
+            port_info: None,
+            low_latency: false,
+            echo_latency: EchoLatency::default(),
+            recording: None,
+            recorder_dir: RECORDER_DIR.to_string(),
+            recorder_gzip: false,
+            recorder_tail: true,
             receive_buffer: Vec::new(),
             receive_buffer_display: String::new(),
+            display_lines: Vec::new(),
+            display_highlights: Vec::new(),
+            display_highlight_rules: Vec::new(),
+            display_cursor: None,
+            evicted: Evicted::default(),
+            gutter: Gutter::Off,
+            goto_open: false,
+            command_palette: None,
+            focus_areas: Vec::new(),
+            announcement: None,
+            goto_input: String::new(),
+            goto_by_line: false,
+            goto_line: None,
+            terminal_selection: None,
+            doc_excerpt: None,
+            doc_excerpt_format: ExcerptFormat::Html,
+            doc_excerpt_path: String::new(),
+            terminal_font_size: DEFAULT_TERMINAL_FONT_SIZE,
+            terminal_rect: eframe::egui::Rect::NOTHING,
+            terminal_scroll_offset: eframe::egui::Vec2::ZERO,
             send_buffer: String::new(),
             view_mode: ViewMode::Ascii,
+            auto_view: AutoViewConfig::default(),
             strip_ansi: true,
+            timestamp_tx: false,
+            mask_7bit: false,
+            translation_enabled: false,
+            translation_table: None,
+            translation_path: String::new(),
+            display_encoding: TextEncoding::Utf8,
+            utf16_decoder: None,
+            piece_decoder: None,
+            rx_spans: Vec::new(),
+            rx_stream: 0,
+            encoding_detection: EncodingDetection::Offer,
+            encoding_detector: EncodingDetector::default(),
+            encoding_suggestion: None,
+            burst_grouping: false,
+            burst_gap_ms: DEFAULT_BURST_GAP_MS,
+            idle_flush: true,
+            idle_flush_ms: DEFAULT_IDLE_FLUSH_MS,
+            last_rx_at: None,
+            burst_tracker: BurstTracker::default(),
+            clock_watch: ClockWatch::default(),
+            display_marks: Vec::new(),
+            burst_stats: BurstStats::default(),
+            stats_started: std::time::Instant::now(),
+            stats_snapshots: Vec::new(),
+            stats_snapshot_path: String::new(),
+            snapshot_on_disconnect: false,
+            theme_setting: ThemeSetting::System,
+            rx_led: ActivityLed::default(),
+            tx_led: ActivityLed::default(),
+            activity_ticks: false,
+            sound_settings: SoundSettings::default(),
+            sound: None,
+            palette: Palette::default(),
             auto_scroll: true,
             dtr_state: false,
             rts_state: false,
             auto_reconnect: false,
             reconnect_delay_ms: DEFAULT_RECONNECT_DELAY_MS,
+            write_timeout_ms: DEFAULT_WRITE_TIMEOUT_MS,
             reconnecting: false,
+            retry_initial_connect: false,
+            fast_attach_enabled: false,
+            fast_attach_dtr: false,
+            fast_attach: None,
+            net_connect: None,
+            attach_timing: None,
+            reconnect_attempts: 0,
             last_reconnect_attempt: std::time::Instant::now(),
+            eof_detection: true,
+            eof_zero_reads: DEFAULT_EOF_ZERO_READS,
+            zero_reads: 0,
             auto_scan_ports: true,
             port_scan_interval_ms: DEFAULT_PORT_SCAN_INTERVAL_MS,
             last_port_scan: std::time::Instant::now(),
             logging_enabled: false,
             log_file_path: format!("rustcom_{}.log", Local::now().format("%Y%m%d_%H%M%S")),
-            log_entries: Vec::new(),
+            log_streaming: false,
+            log_keep_in_memory: true,
+            log_memory_entries: DEFAULT_LOG_MEMORY_ENTRIES,
+            log_stream: None,
+            log_import_path: String::new(),
+            offline_session: None,
+            offline_replay: None,
+            db_path: format!("rustcom_{}.sqlite", Local::now().format("%Y%m%d_%H%M%S")),
+            db_logger: None,
+            syslog: SyslogConfig::default(),
+            metrics: Arc::new(Metrics::default()),
+            metrics_config: MetricsConfig::default(),
+            metrics_server: None,
+            metric_gauges: HashMap::new(),
+            syslog_forwarder: None,
+            syslog_lines: LineAssembler::default(),
+            live_view: LiveViewConfig::default(),
+            live_view_server: None,
+            live_feed: Arc::new(LiveFeed::default()),
+            tcp_bridge: TcpBridgeConfig::default(),
+            tcp_bridge_server: None,
+            live_lines: LineAssembler::default(),
+            boot_log_enabled: false,
+            boot_log_seconds: DEFAULT_BOOT_LOG_SECONDS,
+            boot_log_kb: DEFAULT_BOOT_LOG_KB,
+            boot_capture: None,
+            boot_logs: Vec::new(),
+            split_log: None,
+            split_pattern: String::new(),
+            split_template: DEFAULT_SPLIT_TEMPLATE.to_string(),
+            split_display: false,
+            log_entries: LogBuffer::default(),
             filter_enabled: false,
-            filter_pattern: String::new(),
-            filter_regex: None,
+            filter_lines: LineBuffer::default(),
+            filter_line_at: None,
+            filter_hold_ms: DEFAULT_FILTER_HOLD_MS,
+            keep_rejected: false,
+            rejected_kb: DEFAULT_REJECTED_KB,
+            rejected: RejectedRing::default(),
+            rejected_open: false,
+            filter_rules: vec![FilterRule::new("")],
+            filter_set: FilterSet::default(),
+            rule_sets: Vec::new(),
+            selected_rule_set: None,
+            rule_set_name: String::new(),
+            rule_set_path: String::new(),
+            rule_set_errors: Vec::new(),
+            pinned_values: Vec::new(),
+            pinned_lines: LineBuffer::default(),
+            variables_enabled: false,
+            variables: VariableTable::default(),
+            variable_lines: LineAssembler::default(),
+            watches: Vec::new(),
+            rate_alarm: RateAlarmConfig::default(),
+            rate_tracker: RateAlarm::default(),
+            rate_alarm_rts: None,
+            schedule: Vec::new(),
+            schedule_running: false,
+            session_tag: SessionTag::default(),
+            renaming_session: false,
+            window_title: "RustCOM".to_string(),
             virtual_com_port: None,
+            cable_test: None,
+            cable_test_config: CableTestConfig::default(),
+            cable_test_port_b: None,
+            protocol_mode: ProtocolMode::None,
+            custom_framing: CustomFraming::Slip,
+            framing_rule: FramingRule::default(),
+            framing_delimiter_text: protocol::format_payload_hex(&FramingRule::default().delimiter),
+            decoder: None,
+            decoded_frames: Vec::new(),
+            decoded_views: HashMap::new(),
+            rtu_timing_stats: TimingStats::default(),
+            nmea_stats: NmeaStats::default(),
+            modbus_tracker: TransactionTracker::new(std::time::Duration::from_millis(
+                DEFAULT_RESPONSE_TIMEOUT_MS,
+            )),
+            modbus_timeout_ms: DEFAULT_RESPONSE_TIMEOUT_MS,
+            rtu_frame_gap_ms: 0,
+            slave_filter: BTreeSet::new(),
+            report_title: "RustCOM session report".to_string(),
+            report_excerpt: Excerpt::Everything,
+            export_range: ExportRange::LastMinutes,
+            export_format: ExportFormat::Text,
+            export_start: String::new(),
+            export_end: String::new(),
+            export_minutes: 5,
+            protocol_detector: ProtocolDetector::default(),
+            protocol_suggestion: None,
+            suggestion_dismissed: false,
+            file_send_path: String::new(),
+            file_preview: None,
+            firmware_send_mode: FirmwareSendMode::RawText,
+            file_line_conversion: LineConversion::AsIs,
+            file_transfer: None,
+            xmodem: None,
+            xmodem_one_k: true,
+            xmodem_crc: true,
+            xmodem_receive_path: format!("xmodem_{}.bin", Local::now().format("%Y%m%d_%H%M%S")),
+            xmodem_result: None,
             send_mode: SendMode::Ascii,
             line_ending: LineEnding::CrLf,
+            parse_escapes: false,
+            stx_compose: String::new(),
+            send_encoding: SendEncoding::None,
+            tx_parity: SoftParity::None,
+            monitor_only: false,
+            connection_generation: 0,
+            send_generation: None,
+            confirm_stale_send: false,
+            send_discipline: SendDiscipline::default(),
+            line_editor: LineEditor::default(),
+            send_guard: SendGuard::default(),
+            guard_confirmation: None,
+            guard_approved: None,
+            tx_limiter: RateLimiter::default(),
+            tx_pending: VecDeque::new(),
+            tx_delayed: 0,
+            keepalive: KeepaliveConfig::default(),
+            keepalive_timer: KeepaliveTimer::default(),
+            keepalives_sent: 0,
+            repeat_interval_ms: DEFAULT_REPEAT_MS,
+            repeat: RepeatTimer::default(),
+            macros: Vec::new(),
+            macro_edit: None,
+            template_prompt: None,
+            guard_lock_code: String::new(),
+            recent_payloads: PayloadRing::default(),
+            send_history: SendHistory::default(),
+            show_flow_events: true,
+            flow_tracker: XonXoffTracker::default(),
+            cts_state: None,
+            last_cts_poll: None,
+            event_port_name: None,
+            event_baud_rate: "9600".to_string(),
+            event_port: None,
+            has_event_markers: false,
+            instance_server: InstanceServer::bind().ok(),
+            port_lock: None,
+            port_owner: None,
+            config_slots: [None, None],
+            active_slot: 0,
             error_message: None,
+            corrupt_settings: None,
             bytes_received: 0,
+            rx_queue: RxQueue::default(),
+            rx_frame_budget: DEFAULT_RX_FRAME_BUDGET,
             bytes_sent: 0,
         }
     }
@@ -123,75 +938,2145 @@ impl Default for ComAnalyzerApp {
 
 impl ComAnalyzerApp {
     pub fn update_display_buffer(&mut self) {
-        self.receive_buffer_display = match self.view_mode {
-            ViewMode::Ascii => {
-                let raw = String::from_utf8_lossy(&self.receive_buffer).to_string();
-                if self.strip_ansi {
-                    hex::strip_ansi_codes(&raw)
-                } else {
-                    raw
-                }
+        self.display_cursor = None;
+        self.display_highlight_rules = self.highlight_rules();
+        if self.appendable_view() {
+            self.receive_buffer_display.clear();
+            self.display_lines.clear();
+            self.display_highlights.clear();
+            self.display_cursor = Some(DisplayCursor {
+                key: self.display_key(),
+                bytes: 0,
+                position: self.evicted.start(),
+                text_len: 0,
+                lines: 0,
+                chars: 0,
+            });
+            if self.append_display() {
+                return;
             }
-            ViewMode::Hex => hex::format_hex(&self.receive_buffer),
+            self.display_cursor = None;
+        }
+        if self.decoded_view() == DecodedView::Decoded {
+            let lines: Vec<String> = self
+                .decoded_frames
+                .iter()
+                .filter(|entry| self.slave_visible(entry.slave))
+                .map(|entry| entry.line())
+                .collect();
+            self.display_highlights = lines.iter().map(|line| self.line_highlight(line.as_bytes())).collect();
+            self.receive_buffer_display = lines.into_iter().map(|line| line + "\n").collect();
+            self.display_lines.clear();
+            return;
+        }
+        let (display, lines, highlights) = match self.view_mode {
+            ViewMode::Ascii | ViewMode::Auto => self.render_text(),
+            ViewMode::Hex => (hex::format_hex(&self.receive_buffer), self.hex_lines(), self.hex_highlights()),
             ViewMode::Both => {
-                let ascii_raw = String::from_utf8_lossy(&self.receive_buffer).to_string();
-                let ascii = if self.strip_ansi {
-                    hex::strip_ansi_codes(&ascii_raw)
-                } else {
-                    ascii_raw
-                };
+                let (ascii, ascii_lines, ascii_highlights) = self.render_text();
                 let hex_view = hex::format_hex(&self.receive_buffer);
-                format!("=== HEX ===\n{}\n\n=== ASCII ===\n{}", hex_view, ascii)
+                let hex_part = format!("{}{}\n\n=== ASCII ===\n", BOTH_VIEW_HEX_HEADER, hex_view);
+                // Header line, hex rows, then blanks up to the ASCII heading
+                let mut lines = vec![None];
+                lines.extend(self.hex_lines());
+                lines.resize(hex_part.lines().count(), None);
+                lines.extend(ascii_lines);
+                let mut highlights = vec![None];
+                highlights.extend(self.hex_highlights());
+                highlights.resize(hex_part.lines().count(), None);
+                highlights.extend(ascii_highlights);
+                (hex_part + &ascii, lines, highlights)
+            }
+        };
+        self.receive_buffer_display = display;
+        self.display_lines = lines;
+        self.display_highlights = highlights;
+    }
+
+    /// Active rules with a highlight colour, in the order they are tried.
+    pub fn highlight_rules(&self) -> Vec<(String, [u8; 3])> {
+        self.filter_rules
+            .iter()
+            .filter(|rule| rule.is_active())
+            .filter_map(|rule| Some((rule.pattern.clone(), rule.highlight?)))
+            .collect()
+    }
+
+    /// Highlight colour for a line of received bytes, or of decoded text.
+    fn line_highlight(&self, line: &[u8]) -> Option<[u8; 3]> {
+        if self.display_highlight_rules.is_empty() {
+            return None;
+        }
+        filter::highlight_for(&self.filter_rules, line)
+    }
+
+    /// Add data received since the display was last rendered, or render it
+    /// again when the view settings or the start of the buffer have changed.
+    pub fn extend_display_buffer(&mut self) {
+        let current = self
+            .display_cursor
+            .is_some_and(|cursor| cursor.key == self.display_key() && cursor.bytes <= self.receive_buffer.len());
+        if !(current && self.appendable_view() && self.append_display()) {
+            self.update_display_buffer();
+        }
+    }
+
+    fn display_key(&self) -> DisplayKey {
+        DisplayKey {
+            view_mode: self.view_mode,
+            auto_percent: self.auto_view.printable_percent,
+            encoding: self.display_encoding,
+            strip_ansi: self.strip_ansi,
+            mask_7bit: self.mask_7bit,
+            translate: self.translation_enabled
+                && self.translation_table.is_some()
+                && self.display_encoding == TextEncoding::Utf8,
+            evicted: self.evicted,
+        }
+    }
+
+    /// Whether the display is the buffer rendered in order, so it can grow at
+    /// the end; dividers and decoded frames between lines need a full render.
+    fn appendable_view(&self) -> bool {
+        let show_frames = self.decoded_view() == DecodedView::Interleaved;
+        match self.view_mode {
+            _ if self.decoded_view() == DecodedView::Decoded => false,
+            ViewMode::Hex => true,
+            ViewMode::Ascii | ViewMode::Auto => {
+                !((self.burst_grouping || show_frames) && !self.display_marks.is_empty())
             }
+            ViewMode::Both => false,
+        }
+    }
+
+    /// Render the buffer from the display cursor on and move the cursor up.
+    /// False when the text would grow past what is shown in full.
+    fn append_display(&mut self) -> bool {
+        let Some(cursor) = self.display_cursor else {
+            return false;
         };
+        let mut next = cursor;
+        let mut out = String::new();
+        let mut lines = Vec::new();
+        let mut highlights = Vec::new();
+
+        if self.view_mode == ViewMode::Hex {
+            let new = &self.receive_buffer[cursor.bytes..];
+            out = hex::format_hex_at(new, cursor.bytes);
+            for row in new.chunks(16) {
+                lines.push(Some(next.position));
+                highlights.push(self.line_highlight(row));
+                if row.len() == 16 {
+                    next.bytes += 16;
+                    next.position = next.position.advance(row);
+                    next.lines += 1;
+                    next.text_len = cursor.text_len + out.len();
+                }
+            }
+            // Only the rows before a partial last one are final
+            if !new.len().is_multiple_of(16) {
+                next.text_len = cursor.text_len + out.trim_end_matches('\n').rfind('\n').map_or(0, |i| i + 1);
+            }
+        } else {
+            let masked;
+            let mut new = &self.receive_buffer[cursor.bytes..];
+            if self.mask_7bit {
+                masked = hex::mask_7bit(new);
+                new = &masked;
+            }
+            // A character cut off at the end is shown once the rest arrives
+            let end = if cursor.key.translate {
+                new.len()
+            } else {
+                hex::complete_utf8_len(new)
+            };
+            let mut chars = cursor.chars;
+            for line in self.text_lines(&new[..end], cursor.position) {
+                let rows = (&mut lines, &mut highlights);
+                chars += self.push_piece(&mut out, rows, Some(line.start), line.piece, line.highlight);
+                if line.complete {
+                    next.bytes += line.bytes.len();
+                    next.position = line.start.advance(line.bytes);
+                    next.text_len = cursor.text_len + out.len();
+                    next.lines = cursor.lines + lines.len();
+                    next.chars = chars;
+                }
+            }
+            if chars > sanitize::MAX_DISPLAY_TEXT_CHARS {
+                return false;
+            }
+        }
+
+        self.receive_buffer_display.truncate(cursor.text_len);
+        self.display_lines.truncate(cursor.lines);
+        self.display_highlights.truncate(cursor.lines);
+        self.receive_buffer_display.push_str(&out);
+        self.display_lines.extend(lines);
+        self.display_highlights.extend(highlights);
+        self.display_cursor = Some(next);
+        true
+    }
+
+    /// Positions of the 16-byte rows of the hex dump.
+    fn hex_lines(&self) -> Vec<Option<LineStart>> {
+        let mut position = self.evicted.start();
+        self.receive_buffer
+            .chunks(16)
+            .map(|row| {
+                let start = position;
+                position = position.advance(row);
+                Some(start)
+            })
+            .collect()
     }
 
-    pub fn matches_filter(&self, data: &[u8]) -> bool {
-        if let Some(regex) = &self.filter_regex {
-            let text = String::from_utf8_lossy(data);
-            regex.is_match(&text)
+    /// Highlight colours of the 16-byte rows of the hex dump, matched on the row's bytes.
+    fn hex_highlights(&self) -> Vec<Option<[u8; 3]>> {
+        self.receive_buffer.chunks(16).map(|row| self.line_highlight(row)).collect()
+    }
+
+    /// Text rendering of the receive buffer with the display transforms applied,
+    /// and the session position and highlight of every line. Each received line
+    /// is rendered on its own so its position is known.
+    fn render_text(&self) -> RenderedText {
+        let masked;
+        let bytes = if self.mask_7bit {
+            masked = hex::mask_7bit(&self.receive_buffer);
+            &masked
+        } else {
+            &self.receive_buffer
+        };
+        let show_frames = self.decoded_view() == DecodedView::Interleaved;
+        let pieces = if (self.burst_grouping || show_frames) && !self.display_marks.is_empty() {
+            self.render_marks(bytes, show_frames)
         } else {
-            true
+            self.data_pieces(bytes, self.evicted.start())
+        };
+
+        let mut out = String::new();
+        let mut lines = Vec::new();
+        let mut highlights = Vec::new();
+        let mut total_chars = 0;
+        for (start, piece, highlight) in pieces {
+            total_chars += self.push_piece(&mut out, (&mut lines, &mut highlights), start, piece, highlight);
+        }
+
+        if total_chars > sanitize::MAX_DISPLAY_TEXT_CHARS {
+            let (shown, dropped) = sanitize::keep_tail(&out, total_chars);
+            let mut kept = vec![None];
+            kept.extend(lines.into_iter().skip(dropped));
+            let mut kept_highlights = vec![None];
+            kept_highlights.extend(highlights.into_iter().skip(dropped));
+            return (shown, kept, kept_highlights);
         }
+        (out, lines, highlights)
     }
 
-    pub fn update_filter(&mut self) {
-        if self.filter_pattern.is_empty() {
-            self.filter_regex = None;
+    /// Add one piece to the text view and its line positions and highlights,
+    /// and return the number of chars it adds.
+    fn push_piece(
+        &self,
+        out: &mut String,
+        (lines, highlights): (&mut Vec<Option<LineStart>>, &mut Vec<Option<[u8; 3]>>),
+        start: Option<LineStart>,
+        piece: String,
+        highlight: Option<[u8; 3]>,
+    ) -> usize {
+        let piece = if self.strip_ansi {
+            hex::strip_ansi_codes(&piece)
         } else {
-            match Regex::new(&self.filter_pattern) {
-                Ok(regex) => {
-                    self.filter_regex = Some(regex);
-                    self.error_message = None;
+            piece
+        };
+        if piece.is_empty() {
+            return 0;
+        }
+        let (shown, chars) = sanitize::escape_and_break(&piece);
+        // Breaks added for over-long lines start continuation lines
+        let rows = shown.matches('\n').count() + usize::from(!shown.ends_with('\n'));
+        lines.push(start);
+        lines.extend(std::iter::repeat_n(None, rows - 1));
+        highlights.extend(std::iter::repeat_n(highlight, rows));
+        out.push_str(&shown);
+        chars
+    }
+
+    /// Byte translation applies to 8-bit text, not to UTF-16.
+    fn decode_text(&self, bytes: &[u8]) -> String {
+        let translate = self.translation_enabled && self.display_encoding == TextEncoding::Utf8;
+        match self.translation_table.as_ref().filter(|_| translate) {
+            Some(table) => table.apply(bytes),
+            None => String::from_utf8_lossy(bytes).to_string(),
+        }
+    }
+
+    /// A received line as the text views show it: decoded, or in the Auto
+    /// view decoded or dumped as hex behind a badge.
+    fn line_piece(&self, line: &[u8]) -> String {
+        if self.view_mode != ViewMode::Auto {
+            return self.decode_text(line);
+        }
+        match auto_view::classify(line, &self.auto_view) {
+            Rendering::Text => format!("{}{}", auto_view::TEXT_BADGE, self.decode_text(line)),
+            Rendering::Hex => auto_view::hex_rows(line),
+        }
+    }
+
+    /// A received UTF-16 line as the text views show it, like `line_piece`;
+    /// the Auto view judges the text and dumps the bytes.
+    fn utf16_piece(&self, bytes: &[u8], text: String, complete: bool) -> String {
+        if self.view_mode != ViewMode::Auto {
+            return text;
+        }
+        match auto_view::classify(text.as_bytes(), &self.auto_view) {
+            Rendering::Text => format!("{}{}", auto_view::TEXT_BADGE, text),
+            Rendering::Hex => {
+                let mut rows = auto_view::hex_rows(bytes);
+                if complete && !rows.ends_with('\n') {
+                    rows.push('\n');
+                }
+                rows
+            }
+        }
+    }
+
+    /// Lines of `data`, which begins at `start`, as the text views show them.
+    /// While the display encoding is UTF-16 received data is decoded from it;
+    /// the notes and markers between are UTF-8 either way.
+    fn text_lines<'a>(&self, data: &'a [u8], start: LineStart) -> Vec<TextLine<'a>> {
+        let mut lines = TextLines {
+            data,
+            lines: Vec::new(),
+            next: start,
+        };
+        let mut done = 0;
+        if self.display_encoding != TextEncoding::Utf8 {
+            let base = (start.offset - self.evicted.bytes) as usize;
+            let first = self.rx_spans.partition_point(|span| span.end <= base);
+            for span in &self.rx_spans[first..] {
+                if span.start >= base + data.len() {
+                    break;
+                }
+                let from = span.start.max(base) - base;
+                let to = span.end.min(base + data.len()) - base;
+                self.utf8_lines(&mut lines, done, from);
+                let offset = span.stream + (base + from - span.start) as u64;
+                let mut at = from;
+                for line in split_lines_utf16(&data[from..to], self.display_encoding, offset).into_iter().flatten() {
+                    let len = line.bytes.len();
+                    // Judged on the text, as the filter judges UTF-16
+                    let highlight = self.line_highlight(line.text.as_bytes());
+                    let piece = self.utf16_piece(line.bytes, line.text, line.complete);
+                    lines.add(at, at + len, piece, line.complete, highlight);
+                    at += len;
+                }
+                done = to;
+            }
+        }
+        self.utf8_lines(&mut lines, done, data.len());
+        lines.lines
+    }
+
+    fn utf8_lines(&self, lines: &mut TextLines, from: usize, to: usize) {
+        let data = lines.data;
+        let mut at = from;
+        for line in data[from..to].split_inclusive(|&b| b == b'\n') {
+            let highlight = self.line_highlight(line);
+            lines.add(at, at + line.len(), self.line_piece(line), line.ends_with(b"\n"), highlight);
+            at += line.len();
+        }
+    }
+
+    /// Decoded received lines of `data`, which begins at `start`.
+    fn data_pieces(&self, data: &[u8], start: LineStart) -> Vec<DisplayPiece> {
+        self.text_lines(data, start)
+            .into_iter()
+            .map(|line| (Some(line.start), line.piece, line.highlight))
+            .collect()
+    }
+
+    /// Decode the buffer segment by segment, with burst dividers and (when
+    /// interleaving) decoded frame lines between the segments.
+    fn render_marks(&self, bytes: &[u8], show_frames: bool) -> Vec<DisplayPiece> {
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        let mut position = self.evicted.start();
+        for (offset, mark) in &self.display_marks {
+            let line = match mark {
+                DisplayMark::Burst(label) if self.burst_grouping => format!("──── {} ────\n", label),
+                DisplayMark::Frame(slave, line) if show_frames && self.slave_visible(*slave) => {
+                    format!("{}\n", line)
                 }
+                _ => continue,
+            };
+            let end = (*offset).min(bytes.len());
+            pieces.extend(self.data_pieces(&bytes[start..end], position));
+            position = position.advance(&bytes[start..end]);
+            if let Some((_, last, _)) = pieces.last_mut().filter(|(_, last, _)| !last.ends_with('\n')) {
+                last.push('\n');
+            }
+            pieces.push((None, line, None));
+            start = end;
+        }
+        pieces.extend(self.data_pieces(&bytes[start..], position));
+        pieces
+    }
+
+    pub fn record_burst(&mut self, len: usize, at: std::time::Instant) {
+        let gap = std::time::Duration::from_millis(self.burst_gap_ms);
+        if let Some(burst) = self.burst_tracker.feed(len, at, gap) {
+            self.close_burst(burst);
+        }
+    }
+
+    pub fn poll_bursts(&mut self) {
+        let gap = std::time::Duration::from_millis(self.burst_gap_ms);
+        if let Some(burst) = self.burst_tracker.poll(std::time::Instant::now(), gap) {
+            self.close_burst(burst);
+        }
+    }
+
+    /// Compare the two clocks read together; when they disagree (system sleep,
+    /// clock set) mark the capture and start burst timing afresh, since no gap
+    /// can be measured across the discontinuity.
+    pub fn check_clock(&mut self, instant: std::time::Instant, wall: DateTime<Local>) {
+        if let Some(jump) = self.clock_watch.observe(instant, wall) {
+            self.burst_tracker.reset();
+            self.inject_marker(&jump.label());
+        }
+    }
+
+    fn close_burst(&mut self, burst: Burst) {
+        self.burst_stats.record(&burst);
+        if self.burst_grouping {
+            self.display_marks
+                .push((self.receive_buffer.len(), DisplayMark::Burst(burst.label())));
+            self.update_display_buffer();
+        }
+    }
+
+    pub fn note_rx(&mut self, bytes: usize) {
+        let now = std::time::Instant::now();
+        if self.rx_led.trigger(now) && self.activity_ticks {
+            activity::tick();
+        }
+        if let Some(sound) = self.sound.as_mut() {
+            let cue = sound.cues.data(SoundEvent::Rx, bytes, self.decoder.is_some(), now);
+            sound.send(cue);
+        }
+    }
+
+    pub fn note_tx(&mut self, bytes: usize) {
+        let now = std::time::Instant::now();
+        self.keepalive_timer.note_tx(now);
+        if self.tx_led.trigger(now) && self.activity_ticks {
+            activity::tick();
+        }
+        if let Some(sound) = self.sound.as_mut() {
+            let cue = sound.cues.data(SoundEvent::Tx, bytes, false, now);
+            sound.send(cue);
+        }
+    }
+
+    /// Turn the sound monitor on or off, or pass it changed settings.
+    pub fn apply_sound_settings(&mut self) {
+        match (&mut self.sound, self.sound_settings.enabled) {
+            (Some(sound), true) => sound.configure(&self.sound_settings),
+            (None, true) => match SoundMonitor::start(self.sound_settings.clone()) {
+                Ok(sound) => self.sound = Some(sound),
                 Err(e) => {
-                    self.error_message = Some(format!("Invalid regex: {}", e));
+                    self.sound_settings.enabled = false;
+                    self.error_message = Some(e);
                 }
+            },
+            (_, false) => self.sound = None,
+        }
+    }
+
+    /// Zero the byte counters and the statistics gathered alongside them.
+    pub fn reset_counters(&mut self) {
+        self.bytes_received = 0;
+        self.bytes_sent = 0;
+        self.burst_stats.reset();
+        self.echo_latency.reset();
+        self.rtu_timing_stats = TimingStats::default();
+        self.nmea_stats = NmeaStats::default();
+        self.modbus_tracker.reset();
+        self.burst_tracker.reset();
+        self.flow_tracker.reset();
+        self.stats_started = std::time::Instant::now();
+    }
+
+    /// The current statistics in saveable form. Groups with nothing counted
+    /// yet are left out.
+    pub fn stats_snapshot(&self) -> StatsSnapshot {
+        let elapsed = self.stats_started.elapsed().as_secs_f64();
+        let throughput = if elapsed > 0.0 { self.bytes_received as f64 / elapsed } else { 0.0 };
+        let mut metrics = vec![
+            Metric::new("Duration", elapsed.round(), "s", Better::Neither),
+            Metric::new("RX bytes", self.bytes_received as f64, "", Better::Neither),
+            Metric::new("TX bytes", self.bytes_sent as f64, "", Better::Neither),
+            Metric::new("RX throughput", throughput, "B/s", Better::Higher),
+        ];
+
+        let sizes = &self.burst_stats.sizes;
+        let gaps = &self.burst_stats.gaps;
+        if sizes.count() > 0 {
+            metrics.push(Metric::new("Bursts", sizes.count() as f64, "", Better::Neither));
+            for (name, value) in [("Burst size mean", sizes.mean()), ("Burst size p95", sizes.percentile(95.0))] {
+                metrics.extend(value.map(|v| Metric::new(name, v, "B", Better::Neither)));
+            }
+            for (name, value) in [("Burst gap mean", gaps.mean()), ("Burst gap p95", gaps.percentile(95.0))] {
+                metrics.extend(value.map(|v| Metric::new(name, v, "ms", Better::Neither)));
             }
         }
+        if self.flow_tracker.pause_count > 0 {
+            metrics.push(Metric::new("XOFF pauses", self.flow_tracker.pause_count as f64, "", Better::Lower));
+        }
+        let rtu = &self.rtu_timing_stats;
+        if rtu.frames > 0 {
+            metrics.push(Metric::new("RTU frames", rtu.frames as f64, "", Better::Neither));
+            metrics.push(Metric::new("RTU short frame gaps", rtu.short_frame_gaps as f64, "", Better::Lower));
+            metrics.push(Metric::new("RTU long char gaps", rtu.long_char_gaps as f64, "", Better::Lower));
+        }
+        let nmea = &self.nmea_stats;
+        if nmea.sentences > 0 {
+            metrics.push(Metric::new("NMEA sentences", nmea.sentences as f64, "", Better::Neither));
+            metrics.push(Metric::new("NMEA checksum errors", nmea.checksum_errors as f64, "", Better::Lower));
+        }
+        let resyncs = self.metrics.resyncs.load(std::sync::atomic::Ordering::Relaxed);
+        if resyncs > 0 {
+            let skipped = self.metrics.resync_bytes.load(std::sync::atomic::Ordering::Relaxed);
+            metrics.push(Metric::new("Resyncs", resyncs as f64, "", Better::Lower));
+            metrics.push(Metric::new("Resync skipped bytes", skipped as f64, "B", Better::Lower));
+        }
+        for (slave, stats) in &self.modbus_tracker.slaves {
+            let name = |what: &str| format!("Slave {} {}", slave, what);
+            metrics.push(Metric::new(&name("requests"), stats.requests as f64, "", Better::Neither));
+            metrics.push(Metric::new(&name("responses"), stats.responses as f64, "", Better::Higher));
+            metrics.push(Metric::new(&name("exceptions"), stats.exceptions as f64, "", Better::Lower));
+            metrics.push(Metric::new(&name("timeouts"), stats.timeouts as f64, "", Better::Lower));
+            if let Some(latency) = stats.mean_latency() {
+                metrics.push(Metric::new(&name("latency"), burst::ms(latency), "ms", Better::Lower));
+            }
+        }
+
+        StatsSnapshot {
+            port: self.connection_name().unwrap_or("-").to_string(),
+            taken_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            metrics,
+        }
     }
 
-    pub fn save_log(&mut self) {
-        match logging::save_log(&self.log_entries, &self.log_file_path) {
-            Ok(msg) => self.error_message = Some(msg),
-            Err(msg) => self.error_message = Some(msg),
+    /// Write the current statistics to `stats_<timestamp>.json`.
+    pub fn save_stats_snapshot(&mut self) {
+        let filename = format!("{}.json", self.export_stem("stats"));
+        let saved = self
+            .stats_snapshot()
+            .to_json()
+            .and_then(|json| std::fs::write(&filename, json).map_err(|e| format!("Cannot write {}: {}", filename, e)));
+        self.error_message = Some(match saved {
+            Ok(()) => format!("Saved statistics to {}", filename),
+            Err(e) => e,
+        });
+    }
+
+    /// Save a snapshot on disconnect when enabled and anything was counted.
+    pub fn snapshot_on_disconnect(&mut self) {
+        if self.snapshot_on_disconnect && self.bytes_received + self.bytes_sent > 0 {
+            self.save_stats_snapshot();
         }
     }
 
-    pub fn save_buffer(&self) {
-        logging::save_buffer(&self.receive_buffer_display);
+    /// Add the snapshot file at `stats_snapshot_path` to the comparison.
+    pub fn load_stats_snapshot(&mut self) {
+        let path = self.stats_snapshot_path.trim();
+        let loaded = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path, e))
+            .and_then(|json| StatsSnapshot::from_json(&json));
+        match loaded {
+            Ok(snapshot) => {
+                self.stats_snapshots.push(snapshot);
+                self.error_message = None;
+            }
+            Err(e) => self.error_message = Some(e),
+        }
     }
 
-    pub fn create_virtual_com(&mut self) {
-        match crate::virtual_com::create_loopback_pair() {
-            Ok((port1, port2)) => {
-                self.virtual_com_port = Some(format!("{} <-> {}", port1, port2));
-                self.error_message =
-                    Some(format!("Created virtual COM pair: {} and {}", port1, port2));
+    pub fn export_burst_csv(&mut self) {
+        match self.burst_stats.save_csv(&self.export_stem("bursts")) {
+            Ok(filename) => {
+                let mut msg = format!(
+                    "Saved {} bursts to {}",
+                    self.burst_stats.record_count(),
+                    filename
+                );
+                if self.burst_stats.dropped_records > 0 {
+                    msg.push_str(&format!(
+                        " ({} later bursts not kept)",
+                        self.burst_stats.dropped_records
+                    ));
+                }
+                self.error_message = Some(msg);
             }
-            Err(e) => {
-                self.error_message = Some(e);
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// The receive buffer as text, for reports and marker lookup.
+    pub fn capture_text(&self) -> String {
+        String::from_utf8_lossy(&self.receive_buffer).to_string()
+    }
+
+    /// `prefix_<session name>_<timestamp>`, the stem of every exported file.
+    pub fn export_stem(&self, prefix: &str) -> String {
+        self.session_tag.file_stem(prefix, Local::now())
+    }
+
+    fn build_report(&self) -> Result<Report, String> {
+        let capture = self.capture_text();
+        let markers = report::find_markers(&capture);
+        let excerpt = report::excerpt(&capture, &markers, self.report_excerpt)?;
+        let row = |name: &str, value: String| (name.to_string(), value);
+        let mut metadata = vec![
+            row(
+                "State",
+                if self.connected { "Connected" } else { "Offline" }.to_string(),
+            ),
+            row("Received", format!("{} bytes", self.bytes_received)),
+            row("Sent", format!("{} bytes", self.bytes_sent)),
+            row("Capture held", format!("{} bytes", self.receive_buffer.len())),
+            row("Monitor only", if self.monitor_only { "Yes" } else { "No" }.to_string()),
+        ];
+        if !self.session_tag.name.trim().is_empty() {
+            metadata.insert(0, row("Name", self.session_tag.name.trim().to_string()));
+        }
+
+        let connection = vec![
+            row("Port", self.connection_name().unwrap_or("-").to_string()),
+            row("Baud rate", self.baud_rate.clone()),
+            row(
+                "Format",
+                format!(
+                    "{} data bits, {} parity, {} stop bits",
+                    self.data_bits.as_str(),
+                    self.parity.as_str(),
+                    self.stop_bits.as_str()
+                ),
+            ),
+            row("Flow control", self.flow_control.as_str().to_string()),
+            row("Protocol", self.protocol_mode.as_str().to_string()),
+        ];
+        let mut port = Vec::new();
+        if let Some(info) = &self.port_info {
+            port = info.rows();
+            port.extend(info.differences.iter().map(|difference| row("Differs", difference.clone())));
+        }
+
+        let statistics = self
+            .stats_snapshot()
+            .metrics
+            .iter()
+            .map(|metric| (metric.name.clone(), metric.display()))
+            .collect();
+
+        Ok(Report {
+            title: self.report_title.clone(),
+            metadata,
+            connection,
+            port,
+            statistics,
+            frames: self.decoded_frames.iter().map(DecodedFrame::line).collect(),
+            markers,
+            excerpt,
+        })
+    }
+
+    /// Write an HTML report of the session and open it in the browser.
+    pub fn generate_report(&mut self) {
+        let saved = self.build_report().and_then(|report| report.save(&self.export_stem("report")));
+        match saved {
+            Ok(filename) => {
+                self.error_message = Some(format!("Saved report to {}", filename));
+                if let Err(e) = boot_log::open_in_system(std::path::Path::new(&filename)) {
+                    self.error_message = Some(e);
+                }
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// The window picked in the export group. Times typed without a date are
+    /// taken as today.
+    pub fn export_time_window(&self) -> Result<TimeWindow, String> {
+        let now = Local::now().naive_local();
+        match self.export_range {
+            ExportRange::Timestamps => Ok(TimeWindow::between(
+                export::parse_user_time(&self.export_start, now.date())?,
+                export::parse_user_time(&self.export_end, now.date())?,
+            )),
+            ExportRange::Markers(a, b) => {
+                let events = self.log_entries.events();
+                let time = |index: usize| {
+                    events
+                        .get(index)
+                        .and_then(|(timestamp, _)| export::parse_timestamp(timestamp))
+                        .ok_or_else(|| "Selected marker is no longer in the log".to_string())
+                };
+                Ok(TimeWindow::between(time(a)?, time(b)?))
+            }
+            ExportRange::LastMinutes => Ok(TimeWindow::last_minutes(self.export_minutes, now)),
+        }
+    }
+
+    /// Write the logged entries inside the export window to
+    /// `export_<timestamp>.<ext>` in the working directory.
+    pub fn export_window(&mut self) {
+        let result = self.export_time_window().and_then(|window| {
+            let filename = format!("{}.{}", self.export_stem("export"), self.export_format.extension());
+            let file = std::fs::File::create(&filename)
+                .map_err(|e| format!("Cannot write {}: {}", filename, e))?;
+            let mut out = std::io::BufWriter::new(file);
+            let count = export::export_window(&mut self.log_entries, window, self.export_format, &mut out)
+                .map_err(|e| format!("Export failed: {}", e))?;
+            Ok(format!("Exported {} entries to {}", count, filename))
+        });
+        self.error_message = Some(result.unwrap_or_else(|e| e));
+    }
+
+    /// Run the cable test on the selected port, which must not be in use.
+    pub fn start_cable_test(&mut self) {
+        let Some(port_a) = self.selected_port.clone() else {
+            self.error_message = Some("Select a port to test".to_string());
+            return;
+        };
+        if self.connected {
+            self.error_message = Some("Disconnect before testing the port".to_string());
+            return;
+        }
+        if self.cable_test_port_b.as_deref() == Some(port_a.as_str()) {
+            self.error_message = Some("Pick a different second port, or none for a loopback plug".to_string());
+            return;
+        }
+        // The test sends patterns and toggles RTS/DTR on every port it opens
+        if let Err(e) = self.check_can_transmit() {
+            self.error_message = Some(e);
+            return;
+        }
+        let own_addr = self.instance_server.as_ref().and_then(|server| server.addr());
+        for port in std::iter::once(port_a.as_str()).chain(self.cable_test_port_b.as_deref()) {
+            if let Some(owner) = instance::find_owner(port, own_addr) {
+                self.error_message = Some(format!("{} is open in another RustCOM window (pid {})", port, owner.pid));
+                self.port_owner = Some(owner);
+                return;
+            }
+        }
+        self.cable_test = Some(CableTestRun::start(
+            &port_a,
+            self.cable_test_port_b.as_deref(),
+            self.cable_test_config.clone(),
+        ));
+    }
+
+    pub fn export_cable_test(&mut self) {
+        let Some(run) = &self.cable_test else {
+            return;
+        };
+        let filename = format!("{}.txt", self.export_stem("cable_test"));
+        self.error_message = Some(match std::fs::write(&filename, run.summary()) {
+            Ok(()) => format!("Saved cable test results to {}", filename),
+            Err(e) => format!("Cannot write {}: {}", filename, e),
+        });
+    }
+
+    /// Scroll the terminal to the byte offset or line number typed in the
+    /// "Go to" dialog.
+    pub fn goto(&mut self) -> Result<(), String> {
+        let target = if self.goto_by_line {
+            let line = self.goto_input.trim();
+            GotoTarget::Line(line.parse().map_err(|_| format!("Not a line number: {}", line))?)
+        } else {
+            GotoTarget::Offset(line_offsets::parse_offset(&self.goto_input)?)
+        };
+        let end = self.evicted.bytes + self.receive_buffer.len() as u64;
+        let index = line_offsets::find_line(&self.display_lines, target, end)?;
+        self.goto_line = Some(index);
+        self.auto_scroll = false;
+        self.goto_open = false;
+        Ok(())
+    }
+
+    /// Empty the terminal. Offsets keep counting from where it was.
+    pub fn clear_terminal(&mut self) {
+        self.evicted.drop_front(&self.receive_buffer);
+        self.receive_buffer.clear();
+        self.receive_buffer_display.clear();
+        self.display_lines.clear();
+        self.display_highlights.clear();
+        self.terminal_selection = None;
+        self.has_event_markers = false;
+        self.display_marks.clear();
+        self.rx_spans.clear();
+        self.live_feed.clear();
+        self.rejected.clear();
+        // A held line is dropped, but still counts towards UTF-16 alignment
+        if let Some(held) = self.filter_lines.flush() {
+            self.rx_stream += held.len() as u64;
+            if let Some(decoder) = self.piece_decoder.as_mut() {
+                decoder.decode(&held);
+            }
+        }
+        self.filter_line_at = None;
+    }
+
+    /// Hand the newest terminal line to the screen reader.
+    pub fn read_last_line(&mut self) {
+        let line = a11y::last_line(&self.receive_buffer_display).unwrap_or("The terminal is empty");
+        let count = self.announcement.as_ref().map_or(0, |(count, _)| count + 1);
+        self.announcement = Some((count, line.to_string()));
+    }
+
+    /// Drop the oldest data once the receive buffer is over its limit.
+    pub fn trim_receive_buffer(&mut self) {
+        if self.receive_buffer.len() <= MAX_BUFFER_SIZE {
+            return;
+        }
+        // Whole drain steps, enough to get back under the limit
+        let excess = self.receive_buffer.len() - MAX_BUFFER_SIZE;
+        let drain = excess.div_ceil(BUFFER_DRAIN_SIZE) * BUFFER_DRAIN_SIZE;
+        let dropped: Vec<u8> = self.receive_buffer.drain(0..drain).collect();
+        self.evicted.drop_front(&dropped);
+        self.display_marks.retain_mut(|(offset, _)| {
+            *offset = offset.saturating_sub(drain);
+            *offset > 0
+        });
+        self.rx_spans.retain_mut(|span| {
+            span.stream += drain.clamp(span.start, span.end) as u64 - span.start as u64;
+            span.start = span.start.saturating_sub(drain);
+            span.end = span.end.saturating_sub(drain);
+            span.end > span.start
+        });
+        self.update_display_buffer();
+    }
+
+    pub fn set_translation_table(&mut self, table: TranslationTable) {
+        self.translation_table = Some(table);
+        self.translation_enabled = true;
+        self.update_display_buffer();
+    }
+
+    pub fn load_translation_table(&mut self) {
+        match TranslationTable::load(&self.translation_path) {
+            Ok(table) => {
+                self.error_message = None;
+                self.set_translation_table(table);
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// Show received data as `encoding`, what is in the terminal included.
+    pub fn set_display_encoding(&mut self, encoding: TextEncoding) {
+        self.start_text_decoding(encoding, 0);
+        self.encoding_suggestion = None;
+        self.update_display_buffer();
+    }
+
+    /// Decode RX as `encoding` for the text features from the next read on,
+    /// the first of which is `offset` bytes into the session.
+    pub fn start_text_decoding(&mut self, encoding: TextEncoding, offset: u64) {
+        self.display_encoding = encoding;
+        self.utf16_decoder = Utf16Decoder::new_at(encoding, offset);
+        self.piece_decoder = Utf16Decoder::new_at(encoding, self.rx_stream);
+        self.filter_lines.set_newline_unit(encoding.newline_unit(), self.rx_stream % 2 == 1);
+    }
+
+    /// RX as text for the features that read text, while the display
+    /// encoding is UTF-16; `None` while it is UTF-8. While decoding as UTF-8,
+    /// the start of a session is checked for UTF-16, which is then switched to
+    /// or offered as the detection setting says; the terminal notes the decision.
+    pub fn decode_rx_text(&mut self, data: &[u8]) -> Option<String> {
+        if self.display_encoding == TextEncoding::Utf8 && self.encoding_detection != EncodingDetection::Off {
+            if let Some(detected) = self.encoding_detector.feed(data) {
+                self.apply_detected_encoding(detected);
             }
         }
+        self.utf16_decoder.as_mut().map(|decoder| decoder.decode(data))
+    }
+
+    /// Switch to or offer a detected encoding.
+    fn apply_detected_encoding(&mut self, detected: DetectedEncoding) {
+        let name = detected.encoding.as_str();
+        let apply = self.encoding_detection == EncodingDetection::Apply;
+        let decision = if apply {
+            format!("decoding RX as {}", name)
+        } else {
+            format!("offering {} decoding", name)
+        };
+        self.note_event(&format!("Encoding: RX looks like {} ({}), {}", name, detected.reason(), decision));
+        if apply {
+            self.start_text_decoding(detected.encoding, detected.offset as u64);
+        } else {
+            self.encoding_suggestion = Some(detected.encoding);
+        }
+    }
+
+    pub fn accept_encoding_suggestion(&mut self) {
+        if let Some(encoding) = self.encoding_suggestion.take() {
+            self.set_display_encoding(encoding);
+            self.note_event(&format!("Encoding: decoding RX as {}", encoding.as_str()));
+        }
+    }
+
+    pub fn matches_filter(&mut self, data: &[u8]) -> bool {
+        self.filter_set.matches(&mut self.filter_rules, data)
+    }
+
+    /// Split received data into what the filter should judge: whole lines
+    /// while it is on, with a partial line held back, and the data as it is
+    /// while it is off, after anything still held.
+    pub fn filter_pieces(&mut self, data: Vec<u8>, arrived: std::time::Instant) -> Vec<Vec<u8>> {
+        if self.filter_enabled {
+            let pieces = self.filter_lines.push(&data);
+            self.filter_line_at = (!self.filter_lines.is_empty()).then_some(arrived);
+            return pieces;
+        }
+        self.filter_line_at = None;
+        self.filter_lines.flush().into_iter().chain(std::iter::once(data)).collect()
+    }
+
+    /// Show `data` if the filter passes it, else keep it with the rejected
+    /// data when asked to. Returns whether anything was shown.
+    pub fn show_filtered(&mut self, data: &[u8], received_at: DateTime<Local>) -> bool {
+        let text = self.piece_decoder.as_mut().map(|decoder| decoder.decode(data));
+        let text = text.as_ref().map_or(data, |text| text.as_bytes());
+        let stream = self.rx_stream;
+        self.rx_stream += data.len() as u64;
+        if self.filter_enabled && !self.matches_filter(text) {
+            if self.keep_rejected {
+                self.rejected.push(data, received_at);
+            }
+            return false;
+        }
+        if data.is_empty() {
+            return false;
+        }
+        let start = self.receive_buffer.len();
+        match self.rx_spans.last_mut() {
+            Some(last) if last.end == start && last.stream + (last.end - last.start) as u64 == stream => {
+                last.end += data.len();
+            }
+            _ => self.rx_spans.push(RxSpan {
+                start,
+                end: start + data.len(),
+                stream,
+            }),
+        }
+        self.receive_buffer.extend_from_slice(data);
+        self.feed_live_view(text);
+        true
+    }
+
+    /// End the line in the buffer, if it is not ended already, so a note can
+    /// follow. Received UTF-16 ends with a newline unit rather than a byte.
+    pub fn break_line(&mut self) {
+        let rx_at_end = self.rx_spans.last().is_some_and(|span| span.end == self.receive_buffer.len());
+        let ended = match self.display_encoding.newline_unit().filter(|_| rx_at_end) {
+            Some(newline) => self.receive_buffer.ends_with(&newline),
+            None => matches!(self.receive_buffer.last(), None | Some(b'\n')),
+        };
+        if !ended {
+            self.receive_buffer.push(b'\n');
+        }
+    }
+
+    /// Once a held partial line has waited `filter_hold_ms` for its end,
+    /// filter it as it is, e.g. a prompt that never gets a newline.
+    pub fn poll_filter_hold(&mut self, now: std::time::Instant) {
+        let Some(held_since) = self.filter_line_at else {
+            return;
+        };
+        if now.duration_since(held_since) < std::time::Duration::from_millis(self.filter_hold_ms) {
+            return;
+        }
+        self.filter_line_at = None;
+        let Some(line) = self.filter_lines.flush() else {
+            return;
+        };
+        if self.show_filtered(&line, Local::now()) {
+            self.trim_receive_buffer();
+            self.extend_display_buffer();
+        }
+    }
+
+    /// Update pinned values from every completed RX line, filtered or not.
+    pub fn feed_pinned(&mut self, data: &[u8]) {
+        if self.pinned_values.is_empty() {
+            return;
+        }
+        for line in self.pinned_lines.push(data) {
+            self.update_pinned(&line);
+        }
+    }
+
+    /// Byte ranges index `line` as it came, before any invalid UTF-8 in it
+    /// is replaced for the regexes.
+    fn update_pinned(&mut self, line: &[u8]) {
+        let record = line.strip_suffix(b"\n").unwrap_or(line);
+        let record = record.strip_suffix(b"\r").unwrap_or(record);
+        let text = String::from_utf8_lossy(record);
+        for value in &mut self.pinned_values {
+            let previous = value.latest.clone();
+            let alert = value.update(&text, record);
+            if value.latest != previous {
+                if let Some(number) = value.latest.as_deref().and_then(variables::numeric_value) {
+                    if self.offline_replay.is_none() {
+                        set_gauge(&self.metrics, &mut self.metric_gauges, ValueSource::Pinned, &value.name, number);
+                    }
+                }
+            }
+            if alert && value.alert {
+                self.error_message = Some(format!(
+                    "{} is critical: {}",
+                    value.name,
+                    value.display()
+                ));
+                activity::tick();
+            }
+        }
+    }
+
+    /// Pick key=value pairs out of every completed RX line, filtered or not.
+    pub fn feed_variables(&mut self, data: &[u8]) {
+        if !self.variables_enabled {
+            return;
+        }
+        let now = Local::now();
+        for line in self.variable_lines.push(data) {
+            self.update_variables(&line, now);
+        }
+    }
+
+    fn update_variables(&mut self, line: &str, now: DateTime<Local>) {
+        self.variables.update(line, now);
+        if !self.is_live() {
+            return;
+        }
+        for (key, value) in variables::parse_pairs(line) {
+            if let Some(number) = variables::numeric_value(value) {
+                set_gauge(&self.metrics, &mut self.metric_gauges, ValueSource::Variable, key, number);
+            }
+        }
+        if let Some(db) = &self.db_logger {
+            for (key, value) in variables::parse_pairs(line) {
+                db.log(DbRecord::Value {
+                    at: now,
+                    key: key.to_string(),
+                    value: value.to_string(),
+                    number: variables::numeric_value(value),
+                });
+            }
+        }
+    }
+
+    /// Once RX has been quiet for `idle_flush_ms`, complete the partial line
+    /// held for pinned values, variables and syslog, for devices that leave
+    /// their last line (e.g. a prompt) unterminated. Syslog marks it as having
+    /// no terminator; display and filters work on raw chunks and need nothing.
+    pub fn poll_idle_flush(&mut self, now: std::time::Instant) {
+        let Some(last_rx) = self.last_rx_at else {
+            return;
+        };
+        if !self.idle_flush || now.duration_since(last_rx) < std::time::Duration::from_millis(self.idle_flush_ms) {
+            return;
+        }
+        self.last_rx_at = None;
+
+        if let Some(line) = self.pinned_lines.flush() {
+            self.update_pinned(&line);
+        }
+        if let Some(line) = self.variable_lines.flush() {
+            self.update_variables(&line, Local::now());
+        }
+        if let Some(line) = self.syslog_lines.flush() {
+            self.forward_syslog(Direction::Received, &format!("{}{}", line, NO_TERMINATOR_NOTE));
+        }
+        if let Some(line) = self.live_lines.flush() {
+            self.live_feed.push_line(line);
+        }
+    }
+
+    /// Whether data is live rather than replayed from a log. Only live data
+    /// goes to the capture database, syslog, metrics and the streaming log.
+    pub fn is_live(&self) -> bool {
+        self.offline_replay.is_none()
+    }
+
+    /// Queue a record for the capture database, if database logging is on.
+    pub fn db_log(&self, record: DbRecord) {
+        if !self.is_live() {
+            return;
+        }
+        if let Some(db) = &self.db_logger {
+            db.log(record);
+        }
+    }
+
+    /// Scale the terminal font by `factor`, within the zoom limits. Returns the
+    /// ratio of the new size to the old, or `None` if it did not change.
+    pub fn zoom_terminal(&mut self, factor: f32) -> Option<f32> {
+        self.set_terminal_font_size(self.terminal_font_size * factor)
+    }
+
+    /// Back to the default terminal font size; returns the ratio as `zoom_terminal` does.
+    pub fn reset_terminal_zoom(&mut self) -> Option<f32> {
+        self.set_terminal_font_size(DEFAULT_TERMINAL_FONT_SIZE)
+    }
+
+    fn set_terminal_font_size(&mut self, size: f32) -> Option<f32> {
+        let old = self.terminal_font_size;
+        self.terminal_font_size = size.clamp(MIN_TERMINAL_FONT_SIZE, MAX_TERMINAL_FONT_SIZE);
+        (self.terminal_font_size != old).then(|| self.terminal_font_size / old)
+    }
+
+    /// Serve Prometheus metrics at the configured address.
+    pub fn start_metrics(&mut self) {
+        self.metrics_server = None;
+        match MetricsServer::start(&self.metrics_config.address, self.metrics.clone()) {
+            Ok(server) => {
+                self.metrics_server = Some(server);
+                self.metrics_config.enabled = true;
+            }
+            Err(e) => {
+                self.metrics_config.enabled = false;
+                self.error_message = Some(e);
+            }
+        }
+    }
+
+    pub fn stop_metrics(&mut self) {
+        self.metrics_server = None;
+        self.metrics_config.enabled = false;
+    }
+
+    /// Serve the browser live view with the configured port, line count and token.
+    pub fn start_live_view(&mut self) {
+        self.live_view_server = None;
+        self.live_feed.set_capacity(self.live_view.lines);
+        match LiveViewServer::start(&self.live_view, self.live_feed.clone(), self.metrics.clone()) {
+            Ok(server) => {
+                self.live_view_server = Some(server);
+                self.live_view.enabled = true;
+            }
+            Err(e) => {
+                self.live_view.enabled = false;
+                self.error_message = Some(e);
+            }
+        }
+    }
+
+    pub fn stop_live_view(&mut self) {
+        self.live_view_server = None;
+        self.live_view.enabled = false;
+    }
+
+    /// Pass every completed line shown in the terminal to the live view.
+    pub fn feed_live_view(&mut self, data: &[u8]) {
+        if self.live_view_server.is_none() {
+            return;
+        }
+        for line in self.live_lines.push(data) {
+            self.live_feed.push_line(line);
+        }
+    }
+
+    /// Start forwarding to the configured syslog collector.
+    pub fn start_syslog(&mut self) {
+        match SyslogForwarder::start(&self.syslog) {
+            Ok(forwarder) => {
+                self.syslog_forwarder = Some(forwarder);
+                self.syslog.enabled = true;
+            }
+            Err(e) => {
+                self.syslog.enabled = false;
+                self.error_message = Some(e);
+            }
+        }
+    }
+
+    pub fn stop_syslog(&mut self) {
+        self.syslog_forwarder = None;
+        self.syslog.enabled = false;
+    }
+
+    /// Send `text` to the syslog collector if forwarding is on for `direction`.
+    pub fn forward_syslog(&self, direction: Direction, text: &str) {
+        if !self.is_live() {
+            return;
+        }
+        if let Some(forwarder) = &self.syslog_forwarder {
+            if self.syslog.forwards(direction) {
+                let message = self.syslog.message(direction, text, Local::now(), forwarder.local_hostname());
+                forwarder.send(message);
+            }
+        }
+    }
+
+    /// Forward every completed RX line to syslog.
+    pub fn feed_syslog(&mut self, data: &[u8]) {
+        if self.syslog_forwarder.is_none() || !self.is_live() {
+            return;
+        }
+        for line in self.syslog_lines.push(data) {
+            self.forward_syslog(Direction::Received, &line);
+        }
+    }
+
+    /// Record transmitted bytes in the capture database and syslog.
+    pub fn record_tx(&self, data: &[u8]) {
+        self.db_log(DbRecord::chunk(Direction::Sent, data));
+        if self.syslog_forwarder.is_some() {
+            for line in String::from_utf8_lossy(data).lines() {
+                self.forward_syslog(Direction::Sent, line);
+            }
+        }
+    }
+
+    /// Open the capture database at `db_path` and start writing to it,
+    /// beginning with the current session if connected.
+    pub fn start_db_logging(&mut self) {
+        match DbLogger::start(std::path::Path::new(self.db_path.trim())) {
+            Ok(db) => {
+                self.db_logger = Some(db);
+                self.error_message = None;
+                if self.connected {
+                    self.db_log(DbRecord::SessionStart {
+                        at: Local::now(),
+                        port: self.connection_name().unwrap_or_default().to_string(),
+                        baud_rate: self.baud_rate.parse().unwrap_or(0),
+                    });
+                }
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// Continue database logging in the file now named in `db_path`. The new
+    /// file is opened first, so if that fails the current one keeps logging.
+    /// Each file gets an event naming the other, and the open session ends in
+    /// the old file and starts again in the new one.
+    pub fn apply_db_path(&mut self) -> Result<(), String> {
+        let path = std::path::PathBuf::from(self.db_path.trim());
+        let Some(old_path) = self.db_logger.as_ref().map(|db| db.path().to_path_buf()) else {
+            return Ok(());
+        };
+        if path == old_path {
+            return Ok(());
+        }
+        let next = DbLogger::start(&path)?;
+
+        let at = Local::now();
+        self.db_log(DbRecord::Event {
+            at,
+            text: format!("Log continues in {}", path.display()),
+        });
+        self.db_log(DbRecord::SessionEnd { at });
+        // Dropping the old logger commits what it still has queued
+        self.db_logger = Some(next);
+        if self.connected {
+            self.db_log(DbRecord::SessionStart {
+                at,
+                port: self.connection_name().unwrap_or_default().to_string(),
+                baud_rate: self.baud_rate.parse().unwrap_or(0),
+            });
+        }
+        self.db_log(DbRecord::Event {
+            at,
+            text: format!("Log continued from {}", old_path.display()),
+        });
+        self.error_message = Some(format!("Database logging moved to {}", path.display()));
+        Ok(())
+    }
+
+    /// Turn logging on or off. In streaming mode turning it on opens
+    /// `log_file_path` first, and logging stays off with the reason shown if
+    /// it cannot be opened; turning it off closes the file.
+    pub fn set_logging(&mut self, enabled: bool) {
+        if enabled == self.logging_enabled {
+            return;
+        }
+        if !enabled {
+            self.stop_log_stream();
+        } else if self.log_streaming {
+            match LogStream::open(std::path::Path::new(self.log_file_path.trim())) {
+                Ok(stream) => {
+                    self.log_entries.keep_last = self.log_keep_in_memory.then_some(self.log_memory_entries);
+                    self.error_message = Some(format!("Logging to {}", stream.path().display()));
+                    self.log_stream = Some(stream);
+                }
+                Err(e) => {
+                    self.error_message = Some(format!("Logging not started: {}", e));
+                    return;
+                }
+            }
+        }
+        self.logging_enabled = enabled;
+    }
+
+    /// Keep a log entry: in memory, and in the log file while streaming. When
+    /// streaming without an in-memory copy the file is the only place it goes.
+    /// Replayed entries are only kept in memory.
+    pub fn log_entry(&mut self, entry: DataLogEntry) {
+        let stream = if self.is_live() { self.log_stream.as_mut() } else { None };
+        if let Some(stream) = stream {
+            stream.write(&entry);
+            if !self.log_keep_in_memory {
+                return;
+            }
+        }
+        self.log_entries.push(entry);
+    }
+
+    /// Stop logging if the streaming log's writer has failed. Returns whether a
+    /// file is still open.
+    pub fn check_log_stream(&mut self) -> bool {
+        let Some(stream) = &self.log_stream else {
+            return false;
+        };
+        let Some(e) = stream.last_error() else {
+            return true;
+        };
+        self.error_message = Some(format!("Logging stopped: cannot write {}: {}", stream.path().display(), e));
+        self.log_stream = None;
+        self.log_entries.keep_last = None;
+        self.logging_enabled = false;
+        false
+    }
+
+    /// Flush and close the streaming log file, if one is open.
+    fn stop_log_stream(&mut self) {
+        let Some(stream) = self.log_stream.take() else {
+            return;
+        };
+        self.log_entries.keep_last = None;
+        let path = stream.path().display().to_string();
+        let (entries, size) = (stream.entries(), stream.size());
+        self.error_message = Some(match stream.close() {
+            Ok(()) => format!("Log closed: {} entries, {} KB in {}", entries, size / 1024, path),
+            Err(e) => format!("Log {} may be incomplete: {}", path, e),
+        });
+    }
+
+    /// Close the open session and stop once everything queued is written.
+    pub fn stop_db_logging(&mut self) {
+        self.db_log(DbRecord::SessionEnd { at: Local::now() });
+        if let Some(db) = self.db_logger.take() {
+            let rows = db.rows_written();
+            let path = db.path().display().to_string();
+            drop(db);
+            self.error_message = Some(format!("Database logging stopped: {} rows in {}", rows, path));
+        }
+    }
+
+    /// Evaluate the watches against the latest variable values. An alarm going
+    /// on or off is marked in the terminal; going on also beeps and shows a
+    /// status message. Returns how long until a held condition may alarm.
+    pub fn check_watches(&mut self) -> Option<std::time::Duration> {
+        let now = Local::now();
+        let variables = &self.variables;
+        let lookup = |key: &str| variables.numeric(key);
+        let mut events = Vec::new();
+        for watch in &mut self.watches {
+            if let Some(event) = watch.update(&lookup, now) {
+                events.push((event, watch.expression.clone()));
+            }
+        }
+        for (event, expression) in events {
+            match event {
+                WatchEvent::Raised => {
+                    self.inject_marker(&format!("WATCH {}", expression));
+                    self.error_message = Some(format!("Watch triggered: {}", expression));
+                    activity::tick();
+                }
+                WatchEvent::Cleared => self.inject_marker(&format!("WATCH cleared: {}", expression)),
+            }
+        }
+        self.watches
+            .iter()
+            .filter_map(|watch| watch.pending(now))
+            .min()
+            .map(|left| left.to_std().unwrap_or_default())
+    }
+
+    /// Sample the RX byte count for the rate alarm while it is on and the
+    /// port is open. Raising it is marked, beeps and shows a status message
+    /// like a watch, and may start the raw recorder and set RTS; clearing it
+    /// puts RTS back. Returns true while the rate is being watched.
+    pub fn check_rate_alarm(&mut self, now: std::time::Instant) -> bool {
+        if !self.rate_alarm.enabled || !self.connected {
+            if self.rate_tracker.alarm {
+                self.inject_marker("RATE ALARM off");
+                self.restore_rate_alarm_rts();
+            }
+            self.rate_tracker.reset();
+            return false;
+        }
+        // Raw recording keeps RX out of the display counters
+        let recorded = self.recording.as_ref().map_or(0, |r| r.status.bytes());
+        let total = self.bytes_received as u64 + recorded;
+        match self.rate_tracker.sample(&self.rate_alarm, total, now) {
+            Some(RateEvent::Raised(rate)) => {
+                let text = format!(
+                    "RX above {} KB/s for {}s ({:.1} KB/s)",
+                    self.rate_alarm.threshold_kb,
+                    self.rate_alarm.sustain_secs,
+                    rate / 1024.0
+                );
+                self.inject_marker(&format!("RATE ALARM {}", text));
+                self.error_message = Some(format!("Rate alarm: {}", text));
+                activity::tick();
+                if self.rate_alarm.start_recorder {
+                    if let Err(e) = self.start_recording() {
+                        self.error_message = Some(format!("Rate alarm could not start the recorder: {}", e));
+                    }
+                }
+                if let Some(level) = self.rate_alarm.rts {
+                    match self.set_rts(level) {
+                        Ok(()) => {
+                            self.rate_alarm_rts = Some(self.rts_state);
+                            self.rts_state = level;
+                        }
+                        Err(e) => self.error_message = Some(format!("Rate alarm could not set RTS: {}", e)),
+                    }
+                }
+            }
+            Some(RateEvent::Cleared(rate)) => {
+                self.inject_marker(&format!("RATE ALARM cleared ({:.1} KB/s)", rate / 1024.0));
+                self.restore_rate_alarm_rts();
+            }
+            None => {}
+        }
+        true
+    }
+
+    fn restore_rate_alarm_rts(&mut self) {
+        if let Some(level) = self.rate_alarm_rts.take() {
+            if self.connected && self.set_rts(level).is_ok() {
+                self.rts_state = level;
+            }
+        }
+    }
+
+    pub fn export_variables_csv(&mut self) {
+        let filename = format!("{}.csv", self.export_stem("variables"));
+        self.error_message = Some(match std::fs::write(&filename, self.variables.to_csv()) {
+            Ok(()) => format!("Saved {} variables to {}", self.variables.visible().count(), filename),
+            Err(e) => format!("Cannot write {}: {}", filename, e),
+        });
+    }
+
+    /// Write the rejected data ring to `rejected_<timestamp>.txt`.
+    pub fn save_rejected(&mut self) {
+        let filename = format!("{}.txt", self.export_stem("rejected"));
+        self.error_message = Some(match std::fs::write(&filename, self.rejected.to_text()) {
+            Ok(()) => format!("Saved {} rejected bytes to {}", self.rejected.len(), filename),
+            Err(e) => format!("Cannot write {}: {}", filename, e),
+        });
+    }
+
+    pub fn add_filter_rule(&mut self, pattern: String) {
+        let rule = FilterRule::new(&pattern);
+        self.error_message = rule.error.clone();
+        // Reuse a blank rule left over from the UI instead of piling up empty ones
+        match self.filter_rules.iter_mut().find(|r| r.pattern.is_empty()) {
+            Some(blank) => *blank = rule,
+            None => self.filter_rules.push(rule),
+        }
+        self.filter_enabled = true;
+    }
+
+    /// Store the current filter rules as the set named in `rule_set_name`,
+    /// replacing the rules of a set with that name.
+    pub fn save_rule_set(&mut self) {
+        let name = self.rule_set_name.trim();
+        if name.is_empty() {
+            self.error_message = Some("Name the rule set first".to_string());
+            return;
+        }
+        let rules: Vec<FilterRule> = self.filter_rules.iter().filter(|r| !r.pattern.is_empty()).cloned().collect();
+        let index = match self.rule_sets.iter().position(|set| set.name == name) {
+            Some(index) => {
+                self.rule_sets[index].rules = rules;
+                index
+            }
+            None => {
+                self.rule_sets.push(RuleSet::new(name, rules));
+                self.rule_sets.len() - 1
+            }
+        };
+        self.selected_rule_set = Some(index);
+        self.error_message = None;
+    }
+
+    /// Replace the filter rules with those of set `index`.
+    pub fn apply_rule_set(&mut self, index: usize) {
+        let Some(set) = self.rule_sets.get(index) else {
+            return;
+        };
+        self.filter_rules = set.rules.clone();
+        self.rule_set_name = set.name.clone();
+        self.selected_rule_set = Some(index);
+        self.rule_set_errors.clear();
+    }
+
+    pub fn delete_rule_set(&mut self) {
+        if let Some(index) = self.selected_rule_set.take() {
+            if index < self.rule_sets.len() {
+                self.rule_sets.remove(index);
+            }
+        }
+    }
+
+    /// Write the selected set to `rules_<timestamp>.json`.
+    pub fn export_rule_set(&mut self) {
+        let Some(set) = self.selected_rule_set.and_then(|index| self.rule_sets.get(index)) else {
+            self.error_message = Some("Select a rule set to export".to_string());
+            return;
+        };
+        let filename = format!("{}.json", self.export_stem("rules"));
+        let saved = set
+            .to_json()
+            .and_then(|json| std::fs::write(&filename, json).map_err(|e| format!("Cannot write {}: {}", filename, e)));
+        self.error_message = Some(match saved {
+            Ok(()) => format!("Saved rule set '{}' to {}", set.name, filename),
+            Err(e) => e,
+        });
+    }
+
+    /// Import the set at `rule_set_path`, merging into a set of the same name,
+    /// and load it into the filter. Rules that fail to import are listed in
+    /// `rule_set_errors`.
+    pub fn import_rule_set(&mut self) {
+        let path = self.rule_set_path.trim();
+        let imported = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path, e))
+            .and_then(|json| rule_set::import(&mut self.rule_sets, &json));
+        match imported {
+            Ok((index, report)) => {
+                self.apply_rule_set(index);
+                self.error_message = Some(format!(
+                    "Imported rule set '{}': {}",
+                    self.rule_sets[index].name,
+                    report.summary()
+                ));
+                self.rule_set_errors = report.errors;
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    pub fn selection_text(&self, range: std::ops::Range<usize>) -> String {
+        self.receive_buffer_display
+            .chars()
+            .skip(range.start)
+            .take(range.len())
+            .collect()
+    }
+
+    /// A terminal selection as a character range of `format_hex(receive_buffer)`,
+    /// or `None` when it is not inside the hex dump.
+    fn hex_dump_chars(&self, range: std::ops::Range<usize>) -> Option<std::ops::Range<usize>> {
+        match self.view_mode {
+            ViewMode::Hex => Some(range),
+            ViewMode::Both => {
+                let hex_start = BOTH_VIEW_HEX_HEADER.len();
+                let hex_len = hex::hex_dump_len(self.receive_buffer.len());
+                (range.start >= hex_start && range.end <= hex_start + hex_len)
+                    .then(|| range.start - hex_start..range.end - hex_start)
+            }
+            ViewMode::Ascii | ViewMode::Auto => None,
+        }
+    }
+
+    /// The received bytes behind a terminal selection. In the hex dump this maps
+    /// columns back to buffer offsets; text views use the selected characters.
+    pub fn selection_bytes(&self, range: std::ops::Range<usize>) -> Vec<u8> {
+        match self.hex_dump_chars(range.clone()) {
+            Some(chars) => hex::hex_dump_selection(&self.receive_buffer, chars),
+            None => self.selection_text(range).into_bytes(),
+        }
+    }
+
+    /// The bytes a selection stands for as a value: bytes picked in the hex
+    /// dump, or hex written out in text such as `0x41F8 0000`. `None` when
+    /// there are none or more than the inspector reads.
+    pub fn selection_value_bytes(&self, range: std::ops::Range<usize>) -> Option<Vec<u8>> {
+        let bytes = match self.hex_dump_chars(range.clone()) {
+            Some(chars) => hex::hex_dump_selection(&self.receive_buffer, chars),
+            None => inspect::parse_hex_text(&self.selection_text(range))?,
+        };
+        (!bytes.is_empty() && bytes.len() <= inspect::MAX_INSPECT_BYTES).then_some(bytes)
+    }
+
+    /// An excerpt of the received bytes behind a hex dump selection, placed at
+    /// its capture offset.
+    pub fn selection_excerpt(&self, range: std::ops::Range<usize>) -> Result<excerpt::Excerpt, String> {
+        let chars = match self.view_mode {
+            ViewMode::Ascii | ViewMode::Auto => {
+                return Err("Switch to the Hex or Both view to export an excerpt".to_string())
+            }
+            _ => self
+                .hex_dump_chars(range)
+                .ok_or_else(|| "Select bytes in the hex dump to export an excerpt".to_string())?,
+        };
+        let bytes = hex::hex_dump_selection_range(&self.receive_buffer, chars)
+            .ok_or_else(|| "Selection contains no data bytes".to_string())?;
+        Ok(excerpt::Excerpt::new(
+            self.evicted.bytes + bytes.start as u64,
+            self.receive_buffer[bytes].to_vec(),
+        ))
+    }
+
+    /// Write the open excerpt to `excerpt_<timestamp>` in the chosen format,
+    /// with its annotations saved beside it as `.json` so it can be reopened.
+    pub fn export_doc_excerpt(&mut self) -> Result<String, String> {
+        let Some(excerpt) = &self.doc_excerpt else {
+            return Err("No excerpt is open".to_string());
+        };
+        excerpt.validate()?;
+        let stem = self.export_stem("excerpt");
+        let filename = format!("{}.{}", stem, self.doc_excerpt_format.extension());
+        std::fs::write(&filename, excerpt.render(self.doc_excerpt_format))
+            .map_err(|e| format!("Cannot write {}: {}", filename, e))?;
+        let saved = format!("{}.json", stem);
+        excerpt
+            .to_json()
+            .and_then(|json| std::fs::write(&saved, json).map_err(|e| format!("Cannot write {}: {}", saved, e)))?;
+        self.doc_excerpt_path = saved;
+        Ok(filename)
+    }
+
+    /// Reopen the excerpt saved at `doc_excerpt_path` in the annotation dialog.
+    pub fn open_doc_excerpt(&mut self) {
+        let path = self.doc_excerpt_path.trim();
+        let opened = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path, e))
+            .and_then(|json| excerpt::Excerpt::from_json(&json))
+            .and_then(|excerpt| {
+                if excerpt.bytes.is_empty() {
+                    Err(format!("{} holds an excerpt with no bytes", path))
+                } else {
+                    Ok(excerpt)
+                }
+            });
+        match opened {
+            Ok(excerpt) => self.doc_excerpt = Some(excerpt),
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// Recreate the decoder for the current protocol settings, discarding any partial frame.
+    pub fn update_decoder(&mut self) {
+        let created = protocol::create_decoder(
+            self.protocol_mode,
+            self.custom_framing,
+            &self.framing_rule,
+            self.baud_rate.parse().unwrap_or(9600),
+            self.char_time(),
+            (self.rtu_frame_gap_ms > 0).then(|| std::time::Duration::from_millis(self.rtu_frame_gap_ms)),
+        );
+        self.decoder = created.unwrap_or_else(|e| {
+            self.error_message = Some(e);
+            None
+        });
+        if self.decoder.is_some() {
+            self.protocol_suggestion = None;
+        }
+        self.update_display_buffer();
+    }
+
+    /// With software flow control active, strip XON/XOFF from received data and
+    /// return them as events. Otherwise the data is passed through untouched.
+    pub fn split_flow_control(&mut self, data: Vec<u8>) -> (Vec<u8>, Vec<FlowEvent>) {
+        if !self.show_flow_events || self.flow_control != FlowControl::Software {
+            return (data, Vec::new());
+        }
+        self.flow_tracker.process(&data, std::time::Instant::now())
+    }
+
+    pub fn annotate_flow_events(&mut self, events: &[FlowEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        for event in events {
+            let msg = format!(
+                "\n[{}] {}\n",
+                Local::now().format("%H:%M:%S%.3f"),
+                event.as_str()
+            );
+            self.receive_buffer.extend_from_slice(msg.as_bytes());
+        }
+        self.update_display_buffer();
+    }
+
+    /// Run the protocol detectors over received data while no decoder is active.
+    pub fn feed_detector(&mut self, data: &[u8]) {
+        if self.protocol_mode != ProtocolMode::None
+            || self.suggestion_dismissed
+            || self.protocol_suggestion.is_some()
+        {
+            return;
+        }
+        self.protocol_suggestion = self.protocol_detector.feed(data);
+    }
+
+    pub fn accept_protocol_suggestion(&mut self) {
+        if let Some((mode, framing)) = self.protocol_suggestion.take().and_then(|p| p.settings()) {
+            self.protocol_mode = mode;
+            self.custom_framing = framing;
+            self.update_decoder();
+        }
+    }
+
+    /// Switch to the next protocol decoder, which starts with no partial frame.
+    pub fn cycle_protocol(&mut self) {
+        self.protocol_mode = self.protocol_mode.next();
+        self.update_decoder();
+        self.error_message = Some(format!("Protocol: {}", self.protocol_mode.as_str()));
+    }
+
+    /// Decode received data whose last byte arrived at `arrived`, returning
+    /// how many frames it completed.
+    pub fn feed_decoder(&mut self, data: &[u8], arrived: std::time::Instant) -> usize {
+        let Some(decoder) = self.decoder.as_mut() else {
+            return 0;
+        };
+
+        let frames = decoder.feed_at(data, arrived);
+        self.push_frames(frames, arrived)
+    }
+
+    /// Let a silence-framed decoder finish its frame once the line goes quiet.
+    pub fn poll_decoder(&mut self) {
+        let Some(decoder) = self.decoder.as_mut() else {
+            return;
+        };
+
+        let now = std::time::Instant::now();
+        let frames = decoder.poll(now);
+        if self.protocol_mode.is_modbus() {
+            self.modbus_tracker.expire(now);
+        }
+        let count = self.push_frames(frames, now);
+        if count > 0 {
+            self.mark_decoded_frames(count);
+            self.update_display_buffer();
+        }
+    }
+
+    /// Record decoded frames; `at` is when their last byte arrived.
+    fn push_frames(&mut self, frames: Vec<protocol::Frame>, at: std::time::Instant) -> usize {
+        let count = frames.len();
+        let now = std::time::Instant::now();
+        for mut frame in frames {
+            let mut slave = None;
+            match self.protocol_mode {
+                ProtocolMode::ModbusRtu => self.rtu_timing_stats.record(&frame),
+                ProtocolMode::Nmea => self.nmea_stats.record(&frame),
+                _ => {}
+            }
+            if self.protocol_mode.is_modbus() && frame.error.is_none() {
+                if let Some(role) = self.modbus_tracker.record(&frame.payload, at) {
+                    // Summarize again without the CRC (RTU) or LRC (ASCII) now the role is known
+                    let check = if self.protocol_mode == ProtocolMode::ModbusAscii { 1 } else { 2 };
+                    let message = &frame.payload[..frame.payload.len().saturating_sub(check)];
+                    let summary = modbus::summarize_with_role(message, role);
+                    frame.summary = format!("{} {}", role.label(), summary);
+                }
+                slave = frame.payload.first().copied();
+            }
+            if let Some(sound) = self.sound.as_mut() {
+                let cue = sound.cues.frame(frame.error.is_some(), now);
+                sound.send(cue);
+            }
+            if self.is_live() {
+                count_frame(&self.metrics, &frame);
+            }
+            self.db_log(DbRecord::Frame {
+                at: Local::now(),
+                direction: Direction::Received,
+                data: frame.payload.clone(),
+                summary: Some(frame.summary.clone()),
+                error: frame.error.clone(),
+            });
+            let mut entry = DecodedFrame::new(frame);
+            entry.slave = slave;
+            self.decoded_frames.push(entry);
+        }
+
+        if self.decoded_frames.len() > MAX_DECODED_FRAMES {
+            self.decoded_frames.drain(0..DECODED_FRAMES_DRAIN);
+        }
+        count
+    }
+
+    /// Anchor the last `count` decoded frames at the end of the receive buffer,
+    /// so interleaved view can show them after the bytes they came from.
+    pub fn mark_decoded_frames(&mut self, count: usize) {
+        let offset = self.receive_buffer.len();
+        let start = self.decoded_frames.len().saturating_sub(count);
+        for entry in &self.decoded_frames[start..] {
+            self.display_marks
+                .push((offset, DisplayMark::Frame(entry.slave, entry.line())));
+        }
+    }
+
+    pub fn clear_decoded_frames(&mut self) {
+        self.decoded_frames.clear();
+        self.display_marks
+            .retain(|(_, mark)| matches!(mark, DisplayMark::Burst(_)));
+        self.update_display_buffer();
+    }
+
+    /// False for frames from a slave hidden by the slave filter. Frames without
+    /// a slave address are always shown.
+    pub fn slave_visible(&self, slave: Option<u8>) -> bool {
+        self.slave_filter.is_empty() || slave.is_none_or(|id| self.slave_filter.contains(&id))
+    }
+
+    pub fn toggle_slave_filter(&mut self, slave: u8) {
+        if !self.slave_filter.remove(&slave) {
+            self.slave_filter.insert(slave);
+        }
+        self.update_display_buffer();
+    }
+
+    /// The display preference for the active protocol mode. Without a decoder
+    /// the terminal is always raw.
+    pub fn decoded_view(&self) -> DecodedView {
+        if self.decoder.is_none() {
+            return DecodedView::Raw;
+        }
+        self.decoded_views
+            .get(&self.protocol_mode)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_decoded_view(&mut self, view: DecodedView) {
+        self.decoded_views.insert(self.protocol_mode, view);
+        self.update_display_buffer();
+    }
+
+    pub fn capture_slot(&self) -> ConfigSlot {
+        ConfigSlot {
+            selected_port: self.selected_port.clone(),
+            connection_type: self.connection_type,
+            tcp_address: self.tcp_address.clone(),
+            baud_rate: self.baud_rate.clone(),
+            data_bits: self.data_bits,
+            stop_bits: self.stop_bits,
+            parity: self.parity,
+            flow_control: self.flow_control,
+            filter_enabled: self.filter_enabled,
+            filter_rules: self.filter_rules.clone(),
+            protocol_mode: self.protocol_mode,
+            custom_framing: self.custom_framing,
+            framing_rule: self.framing_rule.clone(),
+            monitor_only: self.monitor_only,
+            pinned_values: self.pinned_values.clone(),
+            send_guard: self.send_guard.clone(),
+            send_discipline: self.send_discipline,
+            variables_enabled: self.variables_enabled,
+            hidden_variables: self.variables.hidden_keys().to_vec(),
+            watches: self.watches.clone(),
+            display_encoding: self.display_encoding,
+            encoding_detection: self.encoding_detection,
+            rate_alarm: self.rate_alarm.clone(),
+            schedule: self.schedule.clone(),
+            tx_rate_limit: self.tx_limiter.limit,
+            keepalive: self.keepalive.clone(),
+            macros: self.macros.clone(),
+            session: self.session_tag.clone(),
+            syslog: self.syslog.clone(),
+            failover: self.failover.clone(),
+        }
+    }
+
+    fn apply_slot(&mut self, slot: ConfigSlot) {
+        self.selected_port = slot.selected_port;
+        self.connection_type = slot.connection_type;
+        self.tcp_address = slot.tcp_address;
+        self.failover = slot.failover;
+        self.baud_rate = slot.baud_rate;
+        self.data_bits = slot.data_bits;
+        self.stop_bits = slot.stop_bits;
+        self.parity = slot.parity;
+        self.flow_control = slot.flow_control;
+        self.filter_enabled = slot.filter_enabled;
+        self.filter_rules = slot.filter_rules;
+        self.monitor_only = slot.monitor_only;
+        self.pinned_values = slot.pinned_values;
+        self.metric_gauges.clear();
+        self.metrics.clear_gauges();
+        self.send_guard = slot.send_guard;
+        self.guard_confirmation = None;
+        self.send_discipline = slot.send_discipline;
+        self.variables_enabled = slot.variables_enabled;
+        self.variables = VariableTable::with_hidden(slot.hidden_variables);
+        self.watches = slot.watches;
+        self.rate_alarm = slot.rate_alarm;
+        self.encoding_detection = slot.encoding_detection;
+        self.set_display_encoding(slot.display_encoding);
+        self.schedule = slot.schedule;
+        self.tx_limiter.limit = slot.tx_rate_limit;
+        self.keepalive = slot.keepalive;
+        self.macros = slot.macros;
+        self.macro_edit = None;
+        self.template_prompt = None;
+        self.session_tag = slot.session;
+        self.renaming_session = false;
+        self.syslog = slot.syslog;
+        self.syslog_forwarder = None;
+        if self.syslog.enabled {
+            self.start_syslog();
+        }
+        if self.protocol_mode != slot.protocol_mode
+            || self.custom_framing != slot.custom_framing
+            || self.framing_rule != slot.framing_rule
+        {
+            self.protocol_mode = slot.protocol_mode;
+            self.custom_framing = slot.custom_framing;
+            self.framing_delimiter_text = protocol::format_payload_hex(&slot.framing_rule.delimiter);
+            self.framing_rule = slot.framing_rule;
+            self.update_decoder();
+        }
+    }
+
+    /// Restore the settings saved on the last exit, if any.
+    pub fn load_settings(&mut self) {
+        let load = Settings::load();
+        self.corrupt_settings = None;
+        if let Some(problem) = load.problem {
+            let message = match &load.corrupt_path {
+                Some(path) => format!("{} (kept as {})", problem, path.display()),
+                None => problem,
+            };
+            self.corrupt_settings = load.corrupt_path.map(|path| (path, message.clone()));
+            self.error_message = Some(message);
+        }
+        if let Some(saved) = load.settings {
+            self.config_slots = saved.config_slots;
+            self.active_slot = saved.active_slot;
+            self.decoded_views = saved.decoded_views;
+            self.theme_setting = saved.theme;
+            self.recent_payloads = saved.recent_payloads;
+            self.send_history = saved.send_history;
+            self.rule_sets = saved.rule_sets;
+            self.metrics_config = saved.metrics;
+            self.terminal_font_size = saved
+                .terminal_font_size
+                .clamp(MIN_TERMINAL_FONT_SIZE, MAX_TERMINAL_FONT_SIZE);
+            if self.metrics_config.enabled {
+                self.start_metrics();
+            }
+            self.live_view = saved.live_view;
+            if self.live_view.enabled {
+                self.start_live_view();
+            }
+            self.tcp_bridge = saved.tcp_bridge;
+            self.auto_view = saved.auto_view;
+            self.apply_slot(saved.current);
+        }
+    }
+
+    pub fn save_settings(&mut self) {
+        let saved = Settings {
+            current: self.capture_slot(),
+            config_slots: self.config_slots.clone(),
+            active_slot: self.active_slot,
+            decoded_views: self.decoded_views.clone(),
+            theme: self.theme_setting,
+            recent_payloads: self.recent_payloads.clone(),
+            send_history: self.send_history.clone(),
+            rule_sets: self.rule_sets.clone(),
+            metrics: self.metrics_config.clone(),
+            live_view: self.live_view.clone(),
+            tcp_bridge: self.tcp_bridge.clone(),
+            auto_view: self.auto_view,
+            terminal_font_size: self.terminal_font_size,
+        };
+        if let Err(e) = saved.save() {
+            self.error_message = Some(e);
+        }
+    }
+
+    pub fn slot_name(index: usize) -> &'static str {
+        if index == 0 {
+            "A"
+        } else {
+            "B"
+        }
+    }
+
+    /// Store the current settings in the active slot and switch to the other one,
+    /// reconnecting if the port parameters differ. An empty slot starts as a copy.
+    pub fn toggle_config_slot(&mut self) {
+        let current = self.capture_slot();
+        let next_index = 1 - self.active_slot;
+        let next = self.config_slots[next_index]
+            .clone()
+            .unwrap_or_else(|| current.clone());
+
+        let reconnect = self.connected && current.port_settings_differ(&next);
+        self.config_slots[self.active_slot] = Some(current);
+        self.active_slot = next_index;
+
+        if reconnect {
+            self.disconnect();
+        }
+        self.apply_slot(next);
+
+        let msg = format!(
+            "[{}] Switched to Config {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            Self::slot_name(self.active_slot)
+        );
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+        self.update_display_buffer();
+
+        if reconnect {
+            self.connect();
+        }
+    }
+
+    pub fn save_log(&mut self) {
+        match logging::save_log(&mut self.log_entries, &self.log_file_path) {
+            Ok(msg) => self.error_message = Some(msg),
+            Err(msg) => self.error_message = Some(msg),
+        }
+    }
+
+    /// Start a fresh boot log for the connection that was just opened. Any capture
+    /// still running from the previous connection is closed first.
+    pub fn start_boot_capture(&mut self) {
+        self.boot_capture = None;
+        if !self.boot_log_enabled {
+            return;
+        }
+        let port = self.connection_name().unwrap_or_default().to_string();
+        match BootCapture::start(
+            std::path::Path::new(boot_log::BOOT_LOG_DIR),
+            &port,
+            Local::now(),
+            std::time::Duration::from_secs(self.boot_log_seconds),
+            self.boot_log_kb * 1024,
+        ) {
+            Ok(capture) => {
+                self.boot_logs.push(capture.path.clone());
+                self.boot_capture = Some(capture);
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// Tee received data into the running boot log, closing it once a limit is hit.
+    pub fn feed_boot_capture(&mut self, data: &[u8]) {
+        let now = std::time::Instant::now();
+        let Some(capture) = self.boot_capture.as_mut() else {
+            return;
+        };
+        if let Err(e) = capture.write(data, now) {
+            self.error_message = Some(e);
+            self.boot_capture = None;
+            return;
+        }
+        self.boot_capture.take_if(|capture| capture.is_finished(now));
+    }
+
+    /// Start writing RX to `split_logs`, one file per boot banner.
+    pub fn start_split_log(&mut self) {
+        match SplitLog::start(
+            std::path::Path::new(SPLIT_LOG_DIR),
+            &self.split_template,
+            &self.split_pattern,
+            Local::now(),
+        ) {
+            Ok(log) => self.split_log = Some(log),
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// Tee received data into the split log. Returns the data to show: as
+    /// given, or after a split with the terminal cleared, the banner line and
+    /// what followed it.
+    pub fn feed_split_log(&mut self, data: Vec<u8>, at: DateTime<Local>) -> Vec<u8> {
+        let Some(log) = self.split_log.as_mut() else {
+            return data;
+        };
+        match log.write(&data, at) {
+            Ok(Some(split)) if self.split_display => {
+                self.clear_terminal();
+                let mut shown = split.banner;
+                shown.extend_from_slice(&data[split.end..]);
+                shown
+            }
+            Ok(_) => data,
+            Err(e) => {
+                self.error_message = Some(e);
+                self.split_log = None;
+                data
+            }
+        }
+    }
+
+    pub fn save_buffer(&self) {
+        logging::save_buffer(&self.receive_buffer_display, &self.export_stem("capture"));
+    }
+
+    pub fn create_virtual_com(&mut self) {
+        match crate::virtual_com::create_loopback_pair() {
+            Ok((port1, port2)) => {
+                self.virtual_com_port = Some(format!("{} <-> {}", port1, port2));
+                self.error_message =
+                    Some(format!("Created virtual COM pair: {} and {}", port1, port2));
+            }
+            Err(e) => {
+                self.error_message = Some(e);
+            }
+        }
+    }
+}
+
+/// Set the gauge for `name`, registering it the first time it is seen.
+/// Count a decoded frame, or the resync or error it came with, in the metrics.
+fn count_frame(metrics: &Metrics, frame: &protocol::Frame) {
+    if let Some(resync) = frame.resync {
+        Metrics::add(&metrics.resyncs, 1);
+        Metrics::add(&metrics.resync_bytes, resync.skipped);
+    } else {
+        Metrics::add(&metrics.frames_decoded, 1);
+    }
+    if let Some(error) = &frame.error {
+        Metrics::add(&metrics.frame_errors, 1);
+        if metrics::is_check_failure(error) {
+            Metrics::add(&metrics.crc_errors, 1);
+        }
+    }
+}
+
+fn set_gauge(
+    metrics: &Metrics,
+    gauges: &mut HashMap<(ValueSource, String), Gauge>,
+    source: ValueSource,
+    name: &str,
+    value: f64,
+) {
+    match gauges.get(&(source, name.to_string())) {
+        Some(gauge) => gauge.set(value),
+        None => {
+            let gauge = metrics.gauge(source, name);
+            gauge.set(value);
+            gauges.insert((source, name.to_string()), gauge);
+        }
     }
 }
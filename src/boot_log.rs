@@ -0,0 +1,166 @@
+// Per-connect boot log capture: the first seconds / kilobytes of RX after each connect
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+
+pub const BOOT_LOG_DIR: &str = "boot_logs";
+pub const DEFAULT_BOOT_LOG_SECONDS: u64 = 10;
+pub const DEFAULT_BOOT_LOG_KB: u64 = 64;
+
+/// An open boot log. Capture stops at whichever limit is reached first.
+pub struct BootCapture {
+    pub path: PathBuf,
+    file: File,
+    started: Instant,
+    written: u64,
+    max_duration: Duration,
+    max_bytes: u64,
+}
+
+impl BootCapture {
+    /// Create `boot_<port>_<connect time>.log` in `dir`.
+    pub fn start(
+        dir: &Path,
+        port: &str,
+        connected_at: DateTime<Local>,
+        max_duration: Duration,
+        max_bytes: u64,
+    ) -> Result<Self, String> {
+        let (path, file) = create_capture_file(dir, "boot", port, connected_at, "log")?;
+        Ok(Self {
+            path,
+            file,
+            started: Instant::now(),
+            written: 0,
+            max_duration,
+            max_bytes,
+        })
+    }
+
+    pub fn is_finished(&self, now: Instant) -> bool {
+        self.written >= self.max_bytes
+            || now.saturating_duration_since(self.started) >= self.max_duration
+    }
+
+    /// Append received data up to the size limit.
+    pub fn write(&mut self, data: &[u8], now: Instant) -> Result<(), String> {
+        if self.is_finished(now) {
+            return Ok(());
+        }
+        let room = (self.max_bytes - self.written).min(data.len() as u64) as usize;
+        self.file
+            .write_all(&data[..room])
+            .map_err(|e| format!("Boot log write failed: {}", e))?;
+        self.written += room as u64;
+        Ok(())
+    }
+}
+
+/// Create `<kind>_<port>_<time>.<extension>` in `dir`, which is created if
+/// needed. A numeric suffix keeps files started within the same millisecond
+/// apart.
+pub fn create_capture_file(
+    dir: &Path,
+    kind: &str,
+    port: &str,
+    at: DateTime<Local>,
+    extension: &str,
+) -> Result<(PathBuf, File), String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+
+    let port: String = port
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let stem = format!("{}_{}_{}", kind, port, at.format("%Y%m%d_%H%M%S%.3f"));
+
+    for attempt in 0..100 {
+        let name = if attempt == 0 {
+            format!("{}.{}", stem, extension)
+        } else {
+            format!("{}_{}.{}", stem, attempt, extension)
+        };
+        let path = dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Cannot create {}: {}", path.display(), e)),
+        }
+    }
+    Err(format!("Too many files named {}", stem))
+}
+
+/// Open a file with the system's default application.
+pub fn open_in_system(path: &Path) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    let result = std::process::Command::new("cmd")
+        .args(["/C", "start", ""])
+        .arg(path)
+        .spawn();
+    #[cfg(target_os = "macos")]
+    let result = std::process::Command::new("open").arg(path).spawn();
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let result = std::process::Command::new("xdg-open").arg(path).spawn();
+
+    result
+        .map(|_| ())
+        .map_err(|e| format!("Cannot open {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustcom-boot-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn stops_at_byte_limit() {
+        let dir = test_dir("bytes");
+        let now = Instant::now();
+        let mut capture =
+            BootCapture::start(&dir, "COM7", Local::now(), Duration::from_secs(60), 5).unwrap();
+        capture.write(b"U-Boot", now).unwrap();
+        capture.write(b" 2024", now).unwrap();
+        assert!(capture.is_finished(now));
+        assert_eq!(std::fs::read(&capture.path).unwrap(), b"U-Boo");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stops_after_duration() {
+        let dir = test_dir("time");
+        let mut capture =
+            BootCapture::start(&dir, "/dev/ttyUSB0", Local::now(), Duration::from_secs(2), 1024)
+                .unwrap();
+        let later = capture.started + Duration::from_secs(3);
+        capture.write(b"late", later).unwrap();
+        assert!(capture.is_finished(later));
+        assert!(std::fs::read(&capture.path).unwrap().is_empty());
+        assert!(capture
+            .path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("boot__dev_ttyUSB0_"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn simultaneous_connects_get_separate_files() {
+        let dir = test_dir("unique");
+        let at = Local::now();
+        let first = BootCapture::start(&dir, "COM7", at, Duration::from_secs(1), 10).unwrap();
+        let second = BootCapture::start(&dir, "COM7", at, Duration::from_secs(1), 10).unwrap();
+        assert_ne!(first.path, second.path);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
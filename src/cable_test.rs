@@ -0,0 +1,122 @@
+// Cable/adapter test running on a worker thread, with its results collected for the UI
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serialport::SerialPort;
+
+use rustcom_core::cable_test::{self, CableTestConfig, TestOutcome};
+
+pub struct CableTestRun {
+    /// "COM3" or "COM3 -> COM4".
+    pub ports: String,
+    pub outcomes: Vec<TestOutcome>,
+    pub finished: bool,
+    results: Receiver<TestOutcome>,
+    cancel: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+fn open(name: &str) -> Result<Box<dyn SerialPort>, String> {
+    serialport::new(name, 9600)
+        .timeout(Duration::from_millis(10))
+        .open()
+        .map_err(|e| format!("cannot open {}: {}", name, e))
+}
+
+impl CableTestRun {
+    /// Open the ports and start testing. Without `port_b` a loopback plug on
+    /// `port_a` is assumed; with it, data goes out on A and comes back on B.
+    pub fn start(port_a: &str, port_b: Option<&str>, config: CableTestConfig) -> Self {
+        let (sender, results) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let ports = match port_b {
+            Some(b) => format!("{} -> {}", port_a, b),
+            None => port_a.to_string(),
+        };
+
+        let worker = {
+            let cancel = cancel.clone();
+            let port_a = port_a.to_string();
+            let port_b = port_b.map(str::to_string);
+            std::thread::spawn(move || {
+                let opened = open(&port_a).and_then(|a| match &port_b {
+                    Some(b) => open(b).map(|b| (a, Some(b))),
+                    None => Ok((a, None)),
+                });
+                match opened {
+                    Ok((mut a, mut b)) => cable_test::run(
+                        a.as_mut(),
+                        b.as_deref_mut(),
+                        &config,
+                        &cancel,
+                        |outcome| {
+                            let _ = sender.send(outcome);
+                        },
+                    ),
+                    Err(e) => {
+                        let _ = sender.send(TestOutcome {
+                            name: "Open ports".to_string(),
+                            passed: false,
+                            detail: e,
+                            throughput: None,
+                        });
+                    }
+                }
+            })
+        };
+
+        Self {
+            ports,
+            outcomes: Vec::new(),
+            finished: false,
+            results,
+            cancel,
+            worker: Some(worker),
+        }
+    }
+
+    /// Collect results that arrived since the last poll.
+    pub fn poll(&mut self) {
+        loop {
+            match self.results.try_recv() {
+                Ok(outcome) => self.outcomes.push(outcome),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// Header line plus one line per test and the overall verdict.
+    pub fn summary(&self) -> String {
+        let mut text = format!("RustCOM cable test on {}\n", self.ports);
+        if self.is_cancelled() {
+            text.push_str("(cancelled)\n");
+        }
+        text.push_str(&cable_test::summary(&self.outcomes));
+        text
+    }
+}
+
+impl Drop for CableTestRun {
+    fn drop(&mut self) {
+        self.cancel();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
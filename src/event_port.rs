@@ -0,0 +1,53 @@
+// Secondary serial port whose lines are injected into the main terminal as markers
+
+use std::time::Duration;
+
+use serialport::SerialPort;
+
+use rustcom_core::lines::LineAssembler;
+
+/// Marker lines in the terminal start with this so they can be highlighted.
+pub const MARKER_PREFIX: &str = "[EVENT ";
+
+pub struct EventPort {
+    pub name: String,
+    port: Box<dyn SerialPort>,
+    lines: LineAssembler,
+}
+
+impl EventPort {
+    pub fn open(name: &str, baud_rate: u32) -> Result<Self, String> {
+        let port = serialport::new(name, baud_rate)
+            .timeout(Duration::from_millis(1))
+            .open()
+            .map_err(|e| format!("Failed to open event port {}: {}", name, e))?;
+        Ok(Self {
+            name: name.to_string(),
+            port,
+            lines: LineAssembler::default(),
+        })
+    }
+
+    /// Complete lines received since the last poll. Blank lines are dropped.
+    pub fn poll(&mut self) -> Result<Vec<String>, String> {
+        let mut buffer = [0u8; 256];
+        match self.port.read(&mut buffer) {
+            Ok(count) => Ok(self
+                .lines
+                .push(&buffer[..count])
+                .into_iter()
+                .filter(|line| !line.trim().is_empty())
+                .collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => Ok(Vec::new()),
+            Err(e) => Err(format!("Event port {} lost: {}", self.name, e)),
+        }
+    }
+}
+
+pub fn format_marker(timestamp: &str, line: &str) -> String {
+    format!("{}{}] {}\n", MARKER_PREFIX, timestamp, line.trim())
+}
+
+pub fn is_marker_line(line: &str) -> bool {
+    line.starts_with(MARKER_PREFIX)
+}
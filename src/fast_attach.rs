@@ -0,0 +1,120 @@
+// Fast attach: a worker thread that opens a pre-configured port the moment it appears
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+use serialport::{SerialPort, SerialPortBuilder};
+
+/// How often the worker looks for the port. Short enough to catch the start of
+/// a boot banner at any common baud rate.
+const ATTACH_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Times measured from the port showing up.
+#[derive(Debug, Clone, Copy)]
+pub struct AttachTiming {
+    /// When the port was first listed, or opened if it never was (e.g. PTYs).
+    pub appeared_at: Instant,
+    pub opened_after: Duration,
+    /// Filled in when the first byte arrives.
+    pub first_byte_after: Option<Duration>,
+}
+
+impl AttachTiming {
+    /// Record the first byte if this is it.
+    pub fn note_data(&mut self, arrived: Instant) {
+        if self.first_byte_after.is_none() {
+            self.first_byte_after = Some(arrived.saturating_duration_since(self.appeared_at));
+        }
+    }
+}
+
+pub struct Attached {
+    pub port: Box<dyn SerialPort>,
+    pub port_name: String,
+    pub baud_rate: u32,
+    pub timing: AttachTiming,
+}
+
+pub struct FastAttach {
+    attached: Receiver<Attached>,
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+fn is_listed(port_name: &str) -> bool {
+    serialport::available_ports().is_ok_and(|ports| ports.iter().any(|p| p.port_name == port_name))
+}
+
+impl FastAttach {
+    /// Start watching for `port_name`. `builder` carries every port setting so
+    /// nothing is left to do between appearance and open; DTR is raised straight
+    /// after opening when `assert_dtr` is set. The UI is woken as soon as the
+    /// port is open.
+    pub fn start(
+        port_name: &str,
+        baud_rate: u32,
+        builder: SerialPortBuilder,
+        assert_dtr: bool,
+        ctx: egui::Context,
+    ) -> Self {
+        let (sender, attached) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let worker = {
+            let stop = stop.clone();
+            let port_name = port_name.to_string();
+            std::thread::spawn(move || {
+                let mut appeared_at = None;
+                while !stop.load(Ordering::Relaxed) {
+                    if appeared_at.is_none() && is_listed(&port_name) {
+                        appeared_at = Some(Instant::now());
+                    }
+                    if let Ok(mut port) = builder.clone().open() {
+                        let opened_at = Instant::now();
+                        if assert_dtr {
+                            let _ = port.write_data_terminal_ready(true);
+                        }
+                        let appeared_at = appeared_at.unwrap_or(opened_at);
+                        let _ = sender.send(Attached {
+                            port,
+                            port_name,
+                            baud_rate,
+                            timing: AttachTiming {
+                                appeared_at,
+                                opened_after: opened_at - appeared_at,
+                                first_byte_after: None,
+                            },
+                        });
+                        ctx.request_repaint();
+                        return;
+                    }
+                    std::thread::sleep(ATTACH_POLL_INTERVAL);
+                }
+            })
+        };
+
+        Self {
+            attached,
+            stop,
+            worker: Some(worker),
+        }
+    }
+
+    /// The opened port, once there is one.
+    pub fn poll(&self) -> Option<Attached> {
+        self.attached.try_recv().ok()
+    }
+}
+
+impl Drop for FastAttach {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
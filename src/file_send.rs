@@ -0,0 +1,337 @@
+// File send: previews, chunked transfer state and XMODEM transfers
+
+use std::io::{Cursor, Read, Write};
+use std::time::Instant;
+
+use crate::firmware::{self, FirmwareFormat, FirmwareImage, ParseError};
+use rustcom_core::xmodem::{Receiver, Transfer};
+
+/// Bytes written per UI frame while a file transfer is running.
+pub const FILE_SEND_CHUNK_SIZE: usize = 1024;
+
+/// How much of a file is checked for NUL bytes when deciding whether it is binary.
+const BINARY_SNIFF_SIZE: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FirmwareSendMode {
+    /// Send the file's text records unchanged.
+    RawText,
+    /// Send the decoded, gap-filled binary image.
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineConversion {
+    AsIs,
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineConversion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineConversion::AsIs => "As-is",
+            LineConversion::Lf => "LF",
+            LineConversion::CrLf => "CRLF",
+            LineConversion::Cr => "CR",
+        }
+    }
+
+    fn line_break(&self) -> &'static [u8] {
+        match self {
+            LineConversion::AsIs => b"",
+            LineConversion::Lf => b"\n",
+            LineConversion::CrLf => b"\r\n",
+            LineConversion::Cr => b"\r",
+        }
+    }
+}
+
+/// Rewrites `\r\n`, `\r` and `\n` line breaks chunk by chunk. A `\r` at the end of a
+/// chunk is held back until the next chunk shows whether a `\n` follows it.
+pub struct LineEndingConverter {
+    mode: LineConversion,
+    pending_cr: bool,
+}
+
+impl LineEndingConverter {
+    pub fn new(mode: LineConversion) -> Self {
+        Self {
+            mode,
+            pending_cr: false,
+        }
+    }
+
+    pub fn convert(&mut self, input: &[u8]) -> Vec<u8> {
+        if self.mode == LineConversion::AsIs || input.is_empty() {
+            return input.to_vec();
+        }
+
+        let line_break = self.mode.line_break();
+        let mut out = Vec::with_capacity(input.len() + input.len() / 16);
+        let mut bytes = input.iter().copied().peekable();
+
+        if self.pending_cr {
+            self.pending_cr = false;
+            out.extend_from_slice(line_break);
+            if bytes.peek() == Some(&b'\n') {
+                bytes.next();
+            }
+        }
+
+        while let Some(byte) = bytes.next() {
+            match byte {
+                b'\r' => match bytes.peek() {
+                    Some(&b'\n') => {
+                        bytes.next();
+                        out.extend_from_slice(line_break);
+                    }
+                    Some(_) => out.extend_from_slice(line_break),
+                    None => self.pending_cr = true,
+                },
+                b'\n' => out.extend_from_slice(line_break),
+                _ => out.push(byte),
+            }
+        }
+        out
+    }
+
+    /// Output still owed at end of file.
+    pub fn finish(&mut self) -> Vec<u8> {
+        if std::mem::take(&mut self.pending_cr) {
+            self.mode.line_break().to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// What the send group shows after a file has been loaded.
+pub struct FilePreview {
+    pub path: String,
+    pub size: u64,
+    pub firmware: Option<Result<FirmwareImage, ParseError>>,
+    /// NUL bytes near the start of the file; line-ending conversion is not offered.
+    pub is_binary: bool,
+}
+
+impl FilePreview {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let metadata =
+            std::fs::metadata(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+
+        let firmware = match FirmwareFormat::from_path(path) {
+            Some(format) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Cannot read {}: {}", path, e))?;
+                Some(firmware::parse(format, &text))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            path: path.to_string(),
+            size: metadata.len(),
+            firmware,
+            is_binary: sniff_binary(path)?,
+        })
+    }
+}
+
+fn sniff_binary(path: &str) -> Result<bool, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+    let mut head = Vec::with_capacity(BINARY_SNIFF_SIZE);
+    file.take(BINARY_SNIFF_SIZE as u64)
+        .read_to_end(&mut head)
+        .map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    Ok(head.contains(&0))
+}
+
+/// An in-progress file transfer. Bytes are pulled from `source` a chunk at a time
+/// so large files never block the UI. `total` and `read` count source bytes, `sent`
+/// counts bytes written after line-ending conversion.
+pub struct FileTransfer {
+    pub name: String,
+    pub total: u64,
+    pub read: u64,
+    pub sent: u64,
+    source: Box<dyn Read>,
+    converter: LineEndingConverter,
+    pending: Vec<u8>,
+    exhausted: bool,
+}
+
+impl FileTransfer {
+    pub fn from_file(path: &str, conversion: LineConversion) -> Result<Self, String> {
+        let file = std::fs::File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+        let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self::new(path, total, Box::new(file), conversion))
+    }
+
+    pub fn from_bytes(name: &str, data: Vec<u8>) -> Self {
+        let total = data.len() as u64;
+        Self::new(name, total, Box::new(Cursor::new(data)), LineConversion::AsIs)
+    }
+
+    fn new(name: &str, total: u64, source: Box<dyn Read>, conversion: LineConversion) -> Self {
+        Self {
+            name: name.to_string(),
+            total,
+            read: 0,
+            sent: 0,
+            source,
+            converter: LineEndingConverter::new(conversion),
+            pending: Vec::new(),
+            exhausted: false,
+        }
+    }
+
+    /// The next bytes to write, refilled from the source when the previous chunk is done.
+    pub fn next_chunk(&mut self) -> Result<&[u8], String> {
+        if self.pending.is_empty() && !self.exhausted {
+            let mut buffer = vec![0u8; FILE_SEND_CHUNK_SIZE];
+            let count = self
+                .source
+                .read(&mut buffer)
+                .map_err(|e| format!("Read error on {}: {}", self.name, e))?;
+            if count == 0 {
+                self.exhausted = true;
+                self.pending = self.converter.finish();
+            } else {
+                self.read += count as u64;
+                self.pending = self.converter.convert(&buffer[..count]);
+            }
+        }
+        Ok(&self.pending)
+    }
+
+    /// Record that `count` bytes of the current chunk were written.
+    pub fn advance(&mut self, count: usize) {
+        self.pending.drain(..count.min(self.pending.len()));
+        self.sent += count as u64;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.exhausted && self.pending.is_empty()
+    }
+
+    /// Expected output size: what has been written and converted so far, plus the
+    /// unread remainder of the source assuming it converts one-to-one.
+    pub fn estimated_total(&self) -> u64 {
+        self.sent + self.pending.len() as u64 + self.total.saturating_sub(self.read)
+    }
+
+    pub fn progress(&self) -> f32 {
+        let total = self.estimated_total();
+        if total == 0 {
+            1.0
+        } else {
+            (self.sent as f32 / total as f32).min(1.0)
+        }
+    }
+}
+
+/// A running XMODEM transfer, and for a receive, the file its blocks are
+/// written to as they arrive.
+pub struct XmodemRun {
+    pub name: String,
+    pub transfer: Transfer,
+    file: Option<std::fs::File>,
+    pub started: Instant,
+}
+
+impl XmodemRun {
+    pub fn send(name: &str, transfer: Transfer) -> Self {
+        Self {
+            name: name.to_string(),
+            transfer,
+            file: None,
+            started: Instant::now(),
+        }
+    }
+
+    /// Receive into `path`, replacing anything there.
+    pub fn receive(path: &str, crc: bool) -> Result<Self, String> {
+        let file = std::fs::File::create(path).map_err(|e| format!("Cannot create {}: {}", path, e))?;
+        let now = Instant::now();
+        Ok(Self {
+            name: path.to_string(),
+            transfer: Transfer::Receive(Receiver::new(crc, now)),
+            file: Some(file),
+            started: now,
+        })
+    }
+
+    pub fn is_receive(&self) -> bool {
+        matches!(self.transfer, Transfer::Receive(_))
+    }
+
+    /// Write the blocks received since the last call to the file.
+    pub fn save_received(&mut self) -> Result<(), String> {
+        let (Transfer::Receive(receiver), Some(file)) = (&mut self.transfer, &mut self.file) else {
+            return Ok(());
+        };
+        let data = receiver.take_data();
+        if data.is_empty() {
+            return Ok(());
+        }
+        file.write_all(&data).map_err(|e| format!("Write error on {}: {}", self.name, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert_in_pieces(mode: LineConversion, pieces: &[&[u8]]) -> Vec<u8> {
+        let mut converter = LineEndingConverter::new(mode);
+        let mut out = Vec::new();
+        for piece in pieces {
+            out.extend(converter.convert(piece));
+        }
+        out.extend(converter.finish());
+        out
+    }
+
+    #[test]
+    fn converts_mixed_line_endings() {
+        let input: &[&[u8]] = &[b"a\r\nb\nc\rd"];
+        assert_eq!(convert_in_pieces(LineConversion::Lf, input), b"a\nb\nc\nd");
+        assert_eq!(convert_in_pieces(LineConversion::CrLf, input), b"a\r\nb\r\nc\r\nd");
+        assert_eq!(convert_in_pieces(LineConversion::Cr, input), b"a\rb\rc\rd");
+        assert_eq!(convert_in_pieces(LineConversion::AsIs, input), b"a\r\nb\nc\rd");
+    }
+
+    #[test]
+    fn crlf_split_across_chunks_is_one_break() {
+        assert_eq!(convert_in_pieces(LineConversion::Lf, &[b"a\r", b"\nb"]), b"a\nb");
+        assert_eq!(convert_in_pieces(LineConversion::Lf, &[b"a\r", b"b"]), b"a\nb");
+        assert_eq!(convert_in_pieces(LineConversion::Lf, &[b"a\r", b"", b"\n"]), b"a\n");
+        assert_eq!(convert_in_pieces(LineConversion::CrLf, &[b"end\r"]), b"end\r\n");
+    }
+
+    #[test]
+    fn transfer_streams_conversion_and_tracks_progress() {
+        let mut data = vec![b'x'; FILE_SEND_CHUNK_SIZE - 1];
+        data.extend_from_slice(b"\r\nlast\r\n");
+        let total = data.len() as u64;
+        let mut transfer =
+            FileTransfer::new("test", total, Box::new(Cursor::new(data)), LineConversion::Lf);
+
+        let mut out = Vec::new();
+        while !transfer.is_done() {
+            let chunk = transfer.next_chunk().unwrap().to_vec();
+            out.extend_from_slice(&chunk);
+            transfer.advance(chunk.len());
+        }
+
+        let mut expected = vec![b'x'; FILE_SEND_CHUNK_SIZE - 1];
+        expected.extend_from_slice(b"\nlast\n");
+        assert_eq!(out, expected);
+        assert_eq!(transfer.read, total);
+        assert_eq!(transfer.sent, expected.len() as u64);
+        assert_eq!(transfer.estimated_total(), transfer.sent);
+        assert_eq!(transfer.progress(), 1.0);
+    }
+}
@@ -0,0 +1,500 @@
+// Intel HEX and Motorola S-record parsing for the file send preview
+
+use std::fmt;
+
+/// Largest address span converted to a flat binary image (gaps are filled with 0xFF).
+pub const MAX_IMAGE_SPAN: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FirmwareFormat {
+    IntelHex,
+    SRecord,
+}
+
+impl FirmwareFormat {
+    pub fn as_str(&self) -> &str {
+        match self {
+            FirmwareFormat::IntelHex => "Intel HEX",
+            FirmwareFormat::SRecord => "S-record",
+        }
+    }
+
+    /// Guess the format from a file extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let ext = std::path::Path::new(path)
+            .extension()?
+            .to_str()?
+            .to_ascii_lowercase();
+        match ext.as_str() {
+            "hex" | "ihx" | "ihex" => Some(FirmwareFormat::IntelHex),
+            "srec" | "s19" | "s28" | "s37" | "mot" | "mhx" => Some(FirmwareFormat::SRecord),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// 1-based line number of the offending record.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn error(line: usize, message: impl Into<String>) -> ParseError {
+    ParseError {
+        line,
+        message: message.into(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    /// Exclusive end address.
+    pub fn end(&self) -> u64 {
+        self.address as u64 + self.data.len() as u64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FirmwareImage {
+    pub format: FirmwareFormat,
+    pub segments: Vec<Segment>,
+    pub entry_point: Option<u32>,
+    pub record_count: usize,
+    /// S0 header text, if present.
+    pub header: Option<String>,
+}
+
+impl FirmwareImage {
+    fn new(format: FirmwareFormat) -> Self {
+        Self {
+            format,
+            segments: Vec::new(),
+            entry_point: None,
+            record_count: 0,
+            header: None,
+        }
+    }
+
+    fn add_data(&mut self, address: u32, data: &[u8]) {
+        if let Some(last) = self.segments.last_mut() {
+            if last.end() == address as u64 {
+                last.data.extend_from_slice(data);
+                return;
+            }
+        }
+        self.segments.push(Segment {
+            address,
+            data: data.to_vec(),
+        });
+    }
+
+    /// Sort segments and merge those that touch, so the summary shows real ranges.
+    fn normalize(&mut self) {
+        self.segments.sort_by_key(|s| s.address);
+        let mut merged: Vec<Segment> = Vec::with_capacity(self.segments.len());
+        for segment in self.segments.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.end() == segment.address as u64 => {
+                    last.data.extend_from_slice(&segment.data);
+                }
+                _ => merged.push(segment),
+            }
+        }
+        self.segments = merged;
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.segments.iter().map(|s| s.data.len()).sum()
+    }
+
+    /// Flatten into a contiguous image starting at the lowest address, filling gaps with 0xFF.
+    pub fn to_binary(&self) -> Result<Vec<u8>, String> {
+        let (Some(first), Some(end)) = (
+            self.segments.iter().map(|s| s.address as u64).min(),
+            self.segments.iter().map(|s| s.end()).max(),
+        ) else {
+            return Ok(Vec::new());
+        };
+
+        let span = end - first;
+        if span > MAX_IMAGE_SPAN {
+            return Err(format!(
+                "Address span of {} bytes is too large for a binary image",
+                span
+            ));
+        }
+
+        let mut image = vec![0xFF; span as usize];
+        for segment in &self.segments {
+            let offset = (segment.address as u64 - first) as usize;
+            image[offset..offset + segment.data.len()].copy_from_slice(&segment.data);
+        }
+        Ok(image)
+    }
+}
+
+pub fn parse(format: FirmwareFormat, text: &str) -> Result<FirmwareImage, ParseError> {
+    match format {
+        FirmwareFormat::IntelHex => parse_intel_hex(text),
+        FirmwareFormat::SRecord => parse_srecord(text),
+    }
+}
+
+fn decode_hex_bytes(line: usize, text: &str) -> Result<Vec<u8>, ParseError> {
+    if !text.len().is_multiple_of(2) {
+        return Err(error(line, "odd number of hex digits"));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            text.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| error(line, format!("invalid hex at column {}", i + 2)))
+        })
+        .collect()
+}
+
+pub fn parse_intel_hex(text: &str) -> Result<FirmwareImage, ParseError> {
+    let mut image = FirmwareImage::new(FirmwareFormat::IntelHex);
+    let mut base: u32 = 0;
+    let mut eof_line = None;
+    let mut last_line = 0;
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let record = raw.trim();
+        if record.is_empty() {
+            continue;
+        }
+        last_line = line;
+
+        if let Some(eof) = eof_line {
+            return Err(error(line, format!("data after end-of-file record on line {}", eof)));
+        }
+
+        let Some(body) = record.strip_prefix(':') else {
+            return Err(error(line, "record does not start with ':'"));
+        };
+        let bytes = decode_hex_bytes(line, body)?;
+        if bytes.len() < 5 {
+            return Err(error(line, "record too short"));
+        }
+
+        let count = bytes[0] as usize;
+        if bytes.len() != count + 5 {
+            return Err(error(
+                line,
+                format!("byte count {} does not match record length", count),
+            ));
+        }
+
+        let sum = bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        if sum != 0 {
+            return Err(error(line, "checksum mismatch"));
+        }
+
+        let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let record_type = bytes[3];
+        let data = &bytes[4..4 + count];
+        image.record_count += 1;
+
+        match record_type {
+            0x00 => image.add_data(base.wrapping_add(offset), data),
+            0x01 => eof_line = Some(line),
+            0x02 => {
+                if count != 2 {
+                    return Err(error(line, "extended segment address needs 2 data bytes"));
+                }
+                base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 4;
+            }
+            0x03 => {
+                if count != 4 {
+                    return Err(error(line, "start segment address needs 4 data bytes"));
+                }
+                let cs = u16::from_be_bytes([data[0], data[1]]) as u32;
+                let ip = u16::from_be_bytes([data[2], data[3]]) as u32;
+                image.entry_point = Some((cs << 4) + ip);
+            }
+            0x04 => {
+                if count != 2 {
+                    return Err(error(line, "extended linear address needs 2 data bytes"));
+                }
+                base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 16;
+            }
+            0x05 => {
+                if count != 4 {
+                    return Err(error(line, "start linear address needs 4 data bytes"));
+                }
+                image.entry_point = Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]));
+            }
+            other => return Err(error(line, format!("unknown record type {:02X}", other))),
+        }
+    }
+
+    if eof_line.is_none() {
+        return Err(error(last_line.max(1), "missing end-of-file record"));
+    }
+
+    image.normalize();
+    Ok(image)
+}
+
+pub fn parse_srecord(text: &str) -> Result<FirmwareImage, ParseError> {
+    let mut image = FirmwareImage::new(FirmwareFormat::SRecord);
+    let mut data_records: u32 = 0;
+    let mut terminated = false;
+    let mut last_line = 0;
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let record = raw.trim();
+        if record.is_empty() {
+            continue;
+        }
+        last_line = line;
+
+        if terminated {
+            return Err(error(line, "data after termination record"));
+        }
+
+        let mut chars = record.chars();
+        if !matches!(chars.next(), Some('S') | Some('s')) {
+            return Err(error(line, "record does not start with 'S'"));
+        }
+        let Some(record_type) = chars.next().and_then(|c| c.to_digit(10)) else {
+            return Err(error(line, "missing record type digit"));
+        };
+
+        let bytes = decode_hex_bytes(line, &record[2..])?;
+        if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+            return Err(error(line, "byte count does not match record length"));
+        }
+
+        let sum = bytes[..bytes.len() - 1]
+            .iter()
+            .fold(0u8, |acc, b| acc.wrapping_add(*b));
+        if !sum != bytes[bytes.len() - 1] {
+            return Err(error(line, "checksum mismatch"));
+        }
+
+        let address_len = match record_type {
+            0 | 1 | 5 | 9 => 2,
+            2 | 6 | 8 => 3,
+            3 | 7 => 4,
+            other => return Err(error(line, format!("unsupported record type S{}", other))),
+        };
+        if bytes.len() < 2 + address_len {
+            return Err(error(line, "record too short for its address field"));
+        }
+
+        let address = bytes[1..1 + address_len]
+            .iter()
+            .fold(0u32, |acc, b| (acc << 8) | *b as u32);
+        let data = &bytes[1 + address_len..bytes.len() - 1];
+        image.record_count += 1;
+
+        match record_type {
+            0 => image.header = Some(String::from_utf8_lossy(data).trim_end_matches('\0').to_string()),
+            1..=3 => {
+                image.add_data(address, data);
+                data_records += 1;
+            }
+            5 | 6 => {
+                if address != data_records {
+                    return Err(error(
+                        line,
+                        format!(
+                            "record count {} does not match {} data records",
+                            address, data_records
+                        ),
+                    ));
+                }
+            }
+            _ => {
+                image.entry_point = Some(address);
+                terminated = true;
+            }
+        }
+    }
+
+    if !terminated {
+        return Err(error(last_line.max(1), "missing S7/S8/S9 termination record"));
+    }
+
+    image.normalize();
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTEL_SAMPLE: &str = "\
+:10010000214601360121470136007EFE09D2190140
+:100110002146017E17C20001FF5F16002148011928
+:10012000194E79234623965778239EDA3F01B2CAA7
+:100130003F0156702B5E712B722B732146013421C7
+:00000001FF
+";
+
+    #[test]
+    fn detects_format_from_extension() {
+        assert_eq!(FirmwareFormat::from_path("fw.HEX"), Some(FirmwareFormat::IntelHex));
+        assert_eq!(FirmwareFormat::from_path("boot.s19"), Some(FirmwareFormat::SRecord));
+        assert_eq!(FirmwareFormat::from_path("app.srec"), Some(FirmwareFormat::SRecord));
+        assert_eq!(FirmwareFormat::from_path("notes.txt"), None);
+    }
+
+    #[test]
+    fn intel_data_and_eof_records() {
+        let image = parse_intel_hex(INTEL_SAMPLE).unwrap();
+        assert_eq!(image.record_count, 5);
+        assert_eq!(image.segments.len(), 1);
+        assert_eq!(image.segments[0].address, 0x0100);
+        assert_eq!(image.total_bytes(), 64);
+        assert_eq!(image.segments[0].data[0], 0x21);
+        assert_eq!(image.entry_point, None);
+    }
+
+    #[test]
+    fn intel_extended_segment_address() {
+        let text = ":020000021000EC\n:0100000055AA\n:00000001FF\n";
+        let image = parse_intel_hex(text).unwrap();
+        assert_eq!(image.segments[0].address, 0x10000);
+        assert_eq!(image.segments[0].data, vec![0x55]);
+    }
+
+    #[test]
+    fn intel_start_segment_address() {
+        let text = ":0400000312345678E5\n:00000001FF\n";
+        let image = parse_intel_hex(text).unwrap();
+        assert_eq!(image.entry_point, Some((0x1234 << 4) + 0x5678));
+    }
+
+    #[test]
+    fn intel_extended_linear_and_start_linear() {
+        let text = ":020000040800F2\n:0400000001020304F2\n:0400000508000131BD\n:00000001FF\n";
+        let image = parse_intel_hex(text).unwrap();
+        assert_eq!(image.segments[0].address, 0x0800_0000);
+        assert_eq!(image.segments[0].data, vec![1, 2, 3, 4]);
+        assert_eq!(image.entry_point, Some(0x0800_0131));
+    }
+
+    #[test]
+    fn intel_checksum_error_reports_line() {
+        let text = ":0100000055AA\n:0100010055AA\n:00000001FF\n";
+        let err = parse_intel_hex(text).unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("checksum"));
+    }
+
+    #[test]
+    fn intel_malformed_records() {
+        assert_eq!(parse_intel_hex("0100000055AA\n").unwrap_err().line, 1);
+        assert!(parse_intel_hex(":0200000055AA\n:00000001FF\n")
+            .unwrap_err()
+            .message
+            .contains("byte count"));
+        assert!(parse_intel_hex(":01000000G5AA\n").unwrap_err().message.contains("invalid hex"));
+        assert!(parse_intel_hex(":0100000655A4\n:00000001FF\n")
+            .unwrap_err()
+            .message
+            .contains("unknown record type"));
+        assert!(parse_intel_hex(":0100000055AA\n").unwrap_err().message.contains("end-of-file"));
+        assert_eq!(
+            parse_intel_hex(":00000001FF\n:0100000055AA\n").unwrap_err().line,
+            2
+        );
+    }
+
+    #[test]
+    fn intel_gaps_fill_binary_image() {
+        let text = ":0100000011EE\n:0100030022DA\n:00000001FF\n";
+        let image = parse_intel_hex(text).unwrap();
+        assert_eq!(image.segments.len(), 2);
+        assert_eq!(image.to_binary().unwrap(), vec![0x11, 0xFF, 0xFF, 0x22]);
+    }
+
+    #[test]
+    fn intel_rejects_huge_span() {
+        let text = ":0100000011EE\n:020000042000DA\n:0100000022DD\n:00000001FF\n";
+        let image = parse_intel_hex(text).unwrap();
+        assert!(image.to_binary().is_err());
+    }
+
+    const SREC_SAMPLE: &str = "\
+S00F000068656C6C6F202020202000003C
+S11F00007C0802A6900100049421FFF07C6C1B787C8C23783C6000003863000026
+S11F001C4BFFFFE5398000007D83637880010014382100107C0803A64E800020E9
+S111003848656C6C6F20776F726C642E0A0042
+S5030003F9
+S9030000FC
+";
+
+    #[test]
+    fn srecord_s0_s1_s5_s9() {
+        let image = parse_srecord(SREC_SAMPLE).unwrap();
+        assert_eq!(image.header.as_deref(), Some("hello     "));
+        assert_eq!(image.record_count, 6);
+        assert_eq!(image.segments.len(), 1);
+        assert_eq!(image.segments[0].address, 0);
+        assert_eq!(image.total_bytes(), 28 + 28 + 14);
+        assert_eq!(image.entry_point, Some(0));
+    }
+
+    #[test]
+    fn srecord_s2_s8() {
+        let text = "S2060100000102F5\nS804010000FA\n";
+        let image = parse_srecord(text).unwrap();
+        assert_eq!(image.segments[0].address, 0x010000);
+        assert_eq!(image.segments[0].data, vec![0x01, 0x02]);
+        assert_eq!(image.entry_point, Some(0x010000));
+    }
+
+    #[test]
+    fn srecord_s3_s6_s7() {
+        let text = "S30708000000AABB8B\nS604000001FA\nS70508000100F1\n";
+        let image = parse_srecord(text).unwrap();
+        assert_eq!(image.segments[0].address, 0x0800_0000);
+        assert_eq!(image.entry_point, Some(0x0800_0100));
+    }
+
+    #[test]
+    fn srecord_count_mismatch() {
+        let text = "S1050000AABB95\nS5030002FA\nS9030000FC\n";
+        let err = parse_srecord(text).unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("record count"));
+    }
+
+    #[test]
+    fn srecord_malformed_records() {
+        let bad_checksum = "S1050000AABB96\nS9030000FC\n";
+        assert_eq!(parse_srecord(bad_checksum).unwrap_err().line, 1);
+        assert!(parse_srecord("S4030000FC\n").unwrap_err().message.contains("S4"));
+        assert!(parse_srecord("X1050000AABB95\n").is_err());
+        assert!(parse_srecord("S1050000AABB95\n").unwrap_err().message.contains("termination"));
+        assert!(parse_srecord("S1060000AABB95\nS9030000FC\n")
+            .unwrap_err()
+            .message
+            .contains("byte count"));
+    }
+
+    #[test]
+    fn parse_error_display_includes_line() {
+        let err = parse_intel_hex("\n\nnonsense\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 3: record does not start with ':'");
+    }
+}
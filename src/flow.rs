@@ -0,0 +1,136 @@
+// XON/XOFF software flow control event tracking
+
+use std::time::{Duration, Instant};
+
+pub const XON: u8 = 0x11;
+pub const XOFF: u8 = 0x13;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlowEvent {
+    Xon,
+    Xoff,
+}
+
+impl FlowEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlowEvent::Xon => "XON received, transmission resumed",
+            FlowEvent::Xoff => "XOFF received, transmission paused",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FlowEvent::Xon => "XON",
+            FlowEvent::Xoff => "XOFF",
+        }
+    }
+
+    pub fn byte(&self) -> u8 {
+        match self {
+            FlowEvent::Xon => XON,
+            FlowEvent::Xoff => XOFF,
+        }
+    }
+}
+
+/// Pulls XON/XOFF bytes out of the RX stream and keeps pause statistics.
+#[derive(Default)]
+pub struct XonXoffTracker {
+    paused_since: Option<Instant>,
+    paused_total: Duration,
+    pub pause_count: u64,
+}
+
+impl XonXoffTracker {
+    /// Returns the data with XON/XOFF removed and the events found, in order.
+    /// A repeated XOFF while already paused is reported but not counted again.
+    pub fn process(&mut self, data: &[u8], now: Instant) -> (Vec<u8>, Vec<FlowEvent>) {
+        let mut stripped = Vec::with_capacity(data.len());
+        let mut events = Vec::new();
+
+        for &byte in data {
+            match byte {
+                XOFF => {
+                    if self.paused_since.is_none() {
+                        self.paused_since = Some(now);
+                        self.pause_count += 1;
+                    }
+                    events.push(FlowEvent::Xoff);
+                }
+                XON => {
+                    if let Some(since) = self.paused_since.take() {
+                        self.paused_total += now.saturating_duration_since(since);
+                    }
+                    events.push(FlowEvent::Xon);
+                }
+                _ => stripped.push(byte),
+            }
+        }
+
+        (stripped, events)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_since.is_some()
+    }
+
+    /// Total time spent in the XOFF state, including a pause still in progress.
+    pub fn time_paused(&self, now: Instant) -> Duration {
+        self.paused_total
+            + self
+                .paused_since
+                .map(|since| now.saturating_duration_since(since))
+                .unwrap_or_default()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_control_bytes_and_reports_events() {
+        let mut tracker = XonXoffTracker::default();
+        let now = Instant::now();
+        let (data, events) = tracker.process(&[b'a', XOFF, b'b', XON, b'c'], now);
+        assert_eq!(data, b"abc");
+        assert_eq!(events, vec![FlowEvent::Xoff, FlowEvent::Xon]);
+        assert_eq!(tracker.pause_count, 1);
+        assert!(!tracker.is_paused());
+    }
+
+    #[test]
+    fn accumulates_time_paused_across_chunks() {
+        let mut tracker = XonXoffTracker::default();
+        let start = Instant::now();
+        tracker.process(&[XOFF], start);
+        assert!(tracker.is_paused());
+        assert_eq!(
+            tracker.time_paused(start + Duration::from_millis(50)),
+            Duration::from_millis(50)
+        );
+
+        tracker.process(&[XON], start + Duration::from_millis(100));
+        tracker.process(&[XOFF], start + Duration::from_millis(200));
+        tracker.process(&[XON], start + Duration::from_millis(250));
+        assert_eq!(tracker.pause_count, 2);
+        assert_eq!(
+            tracker.time_paused(start + Duration::from_secs(10)),
+            Duration::from_millis(150)
+        );
+    }
+
+    #[test]
+    fn repeated_xoff_counts_one_pause() {
+        let mut tracker = XonXoffTracker::default();
+        let now = Instant::now();
+        let (_, events) = tracker.process(&[XOFF, XOFF, XOFF], now);
+        assert_eq!(events.len(), 3);
+        assert_eq!(tracker.pause_count, 1);
+    }
+}
@@ -0,0 +1,54 @@
+// Terminal font set-up: egui's Hack plus a fallback for the glyphs it lacks
+
+use eframe::egui::{FontData, FontDefinitions, FontFamily};
+
+const FALLBACK_NAME: &str = "DejaVuSansMono";
+
+/// Hack has no U+207F (ⁿ, CP437 0xFC), and DejaVu Sans Mono covers the rest of
+/// the code page with matching box-drawing metrics.
+const FALLBACK_FONT: &[u8] = include_bytes!("../assets/DejaVuSansMono.ttf");
+
+/// egui's default fonts with DejaVu Sans Mono tried after Hack for monospace text.
+pub fn definitions() -> FontDefinitions {
+    let mut fonts = FontDefinitions::default();
+    fonts
+        .font_data
+        .insert(FALLBACK_NAME.to_string(), FontData::from_static(FALLBACK_FONT));
+    let monospace = fonts.families.entry(FontFamily::Monospace).or_default();
+    let after_hack = monospace.iter().position(|name| name == "Hack").map_or(0, |i| i + 1);
+    monospace.insert(after_hack, FALLBACK_NAME.to_string());
+    fonts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translate::CP437_HIGH;
+    use eframe::egui::{Context, FontId};
+
+    fn missing_glyphs(fonts: FontDefinitions) -> String {
+        let ctx = Context::default();
+        ctx.set_fonts(fonts);
+        // Fonts are only built once a frame has run
+        let _ = ctx.run(Default::default(), |_| {});
+        let font = FontId::monospace(14.0);
+        CP437_HIGH
+            .chars()
+            .filter(|&c| !ctx.fonts(|f| f.has_glyph(&font, c)))
+            .collect()
+    }
+
+    #[test]
+    fn monospace_covers_the_cp437_high_range() {
+        assert_eq!(missing_glyphs(FontDefinitions::default()), "ⁿ");
+        assert_eq!(missing_glyphs(definitions()), "");
+    }
+
+    #[test]
+    fn hack_stays_the_primary_font() {
+        let fonts = definitions();
+        let monospace = &fonts.families[&FontFamily::Monospace];
+        assert_eq!(monospace[0], "Hack");
+        assert_eq!(monospace[1], FALLBACK_NAME);
+    }
+}
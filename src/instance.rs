@@ -0,0 +1,185 @@
+// Cross-instance port locks and release requests over a loopback socket
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const PROBE_TIMEOUT: Duration = Duration::from_millis(200);
+const RELEASE_COMMAND: &str = "RELEASE";
+
+/// The RustCOM instance currently holding a port.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortOwner {
+    pub port: String,
+    pub pid: u32,
+    pub addr: SocketAddr,
+}
+
+fn lock_dir() -> PathBuf {
+    std::env::temp_dir().join("rustcom-locks")
+}
+
+fn lock_path(port: &str) -> PathBuf {
+    let name: String = port
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    lock_dir().join(format!("{}.lock", name))
+}
+
+fn read_lock(port: &str) -> Option<PortOwner> {
+    let text = std::fs::read_to_string(lock_path(port)).ok()?;
+    let mut pid = None;
+    let mut addr = None;
+    for line in text.lines() {
+        if let Some(value) = line.strip_prefix("pid=") {
+            pid = value.trim().parse().ok();
+        } else if let Some(value) = line.strip_prefix("addr=") {
+            addr = value.trim().parse().ok();
+        }
+    }
+    Some(PortOwner {
+        port: port.to_string(),
+        pid: pid?,
+        addr: addr?,
+    })
+}
+
+/// Listens on loopback for release requests from other instances.
+pub struct InstanceServer {
+    listener: TcpListener,
+}
+
+impl InstanceServer {
+    pub fn bind() -> Result<Self, String> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .map_err(|e| format!("Cannot open instance socket: {}", e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Cannot open instance socket: {}", e))?;
+        Ok(Self { listener })
+    }
+
+    pub fn addr(&self) -> Option<SocketAddr> {
+        self.listener.local_addr().ok()
+    }
+
+    /// Port names other instances have asked us to release since the last poll.
+    /// Connections that send nothing (liveness probes) are ignored.
+    pub fn poll(&self) -> Vec<String> {
+        let mut requests = Vec::new();
+        while let Ok((stream, _)) = self.listener.accept() {
+            let _ = stream.set_nonblocking(false);
+            let _ = stream.set_read_timeout(Some(PROBE_TIMEOUT));
+            let mut line = String::new();
+            if BufReader::new(stream).read_line(&mut line).is_ok() {
+                if let Some(port) = line.trim().strip_prefix(RELEASE_COMMAND) {
+                    requests.push(port.trim().to_string());
+                }
+            }
+        }
+        requests
+    }
+}
+
+/// Marks a port as held by this instance; the lock file is removed on drop.
+pub struct PortLock {
+    path: PathBuf,
+}
+
+impl PortLock {
+    pub fn acquire(port: &str, addr: SocketAddr) -> Result<Self, String> {
+        std::fs::create_dir_all(lock_dir()).map_err(|e| format!("Cannot create lock dir: {}", e))?;
+        let path = lock_path(port);
+        let contents = format!("pid={}\naddr={}\n", std::process::id(), addr);
+        std::fs::write(&path, contents).map_err(|e| format!("Cannot write lock file: {}", e))?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for PortLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Another live instance holding `port`, if any. Locks left behind by crashed
+/// instances (nothing listening at the recorded address) are deleted.
+pub fn find_owner(port: &str, own_addr: Option<SocketAddr>) -> Option<PortOwner> {
+    let owner = read_lock(port)?;
+    if Some(owner.addr) == own_addr {
+        return None;
+    }
+    if TcpStream::connect_timeout(&owner.addr, PROBE_TIMEOUT).is_err() {
+        let _ = std::fs::remove_file(lock_path(port));
+        return None;
+    }
+    Some(owner)
+}
+
+/// Ask the owning instance to disconnect from its port.
+pub fn request_release(owner: &PortOwner) -> Result<(), String> {
+    let mut stream = TcpStream::connect_timeout(&owner.addr, PROBE_TIMEOUT)
+        .map_err(|e| format!("Other instance is not responding: {}", e))?;
+    writeln!(stream, "{} {}", RELEASE_COMMAND, owner.port)
+        .map_err(|e| format!("Release request failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_port(name: &str) -> String {
+        format!("TEST-{}-{}", std::process::id(), name)
+    }
+
+    #[test]
+    fn live_lock_is_reported_and_removed_on_drop() {
+        let port = test_port("live");
+        let server = InstanceServer::bind().unwrap();
+        let lock = PortLock::acquire(&port, server.addr().unwrap()).unwrap();
+
+        let owner = find_owner(&port, None).expect("owner should be found");
+        assert_eq!(owner.pid, std::process::id());
+        assert_eq!(owner.addr, server.addr().unwrap());
+        assert_eq!(find_owner(&port, server.addr()), None);
+
+        drop(lock);
+        assert_eq!(find_owner(&port, None), None);
+    }
+
+    #[test]
+    fn stale_lock_is_ignored_and_cleaned_up() {
+        let port = test_port("stale");
+        let addr = {
+            let dead = TcpListener::bind("127.0.0.1:0").unwrap();
+            dead.local_addr().unwrap()
+        };
+        let lock = PortLock::acquire(&port, addr).unwrap();
+        std::mem::forget(lock);
+
+        assert_eq!(find_owner(&port, None), None);
+        assert!(!lock_path(&port).exists());
+    }
+
+    #[test]
+    fn release_request_reaches_owner() {
+        let port = test_port("release");
+        let server = InstanceServer::bind().unwrap();
+        let _lock = PortLock::acquire(&port, server.addr().unwrap()).unwrap();
+
+        let owner = find_owner(&port, None).unwrap();
+        request_release(&owner).unwrap();
+
+        let mut requests = Vec::new();
+        for _ in 0..50 {
+            requests.extend(server.poll());
+            if !requests.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(requests, vec![port]);
+    }
+}
@@ -0,0 +1,180 @@
+// Keyboard events turned into port bytes (per-character mode), line edits
+// (per-line mode) or send box actions
+
+use eframe::egui::{Event, Key};
+
+use rustcom_core::line_editor::LineEditor;
+
+/// What a key did to the local line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineKey {
+    Ignored,
+    Edited,
+    Enter,
+}
+
+/// What a key does in the send box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SendBoxKey {
+    /// Enter: send the box.
+    Send,
+    /// Shift+Enter: a line break inside the payload, left to the text field.
+    Newline,
+    /// Up: the previous history entry, from the first line of the box.
+    Older,
+    /// Down: the next history entry, from the last line of the box.
+    Newer,
+}
+
+/// The send box's reading of an event; `None` for anything the text field
+/// handles itself. A held Enter does not repeat.
+pub fn send_box_key(event: &Event) -> Option<SendBoxKey> {
+    match event {
+        Event::Key {
+            key: Key::Enter,
+            pressed: true,
+            repeat: false,
+            modifiers,
+            ..
+        } => Some(if modifiers.shift { SendBoxKey::Newline } else { SendBoxKey::Send }),
+        Event::Key {
+            key: key @ (Key::ArrowUp | Key::ArrowDown),
+            pressed: true,
+            modifiers,
+            ..
+        } if modifiers.is_none() => Some(if *key == Key::ArrowUp { SendBoxKey::Older } else { SendBoxKey::Newer }),
+        _ => None,
+    }
+}
+
+/// The bytes a keystroke sends in per-character mode: text as typed, control
+/// keys as their ASCII codes, arrows as VT100 sequences and Ctrl+letter as the
+/// matching control character. `enter` is what the Enter key sends.
+pub fn keystroke_bytes(event: &Event, enter: &[u8]) -> Option<Vec<u8>> {
+    match event {
+        Event::Text(text) | Event::Paste(text) => Some(text.as_bytes().to_vec()),
+        // The platform turns Ctrl+C / Ctrl+X into clipboard events
+        Event::Copy => Some(vec![0x03]),
+        Event::Cut => Some(vec![0x18]),
+        Event::Key {
+            key,
+            pressed: true,
+            modifiers,
+            ..
+        } => {
+            let bytes: &[u8] = match key {
+                Key::Enter => enter,
+                Key::Backspace => b"\x08",
+                Key::Tab => b"\t",
+                Key::Escape => b"\x1b",
+                Key::Delete => b"\x7f",
+                Key::ArrowUp => b"\x1b[A",
+                Key::ArrowDown => b"\x1b[B",
+                Key::ArrowRight => b"\x1b[C",
+                Key::ArrowLeft => b"\x1b[D",
+                _ if modifiers.ctrl => {
+                    let letter = key.name().as_bytes();
+                    return match letter {
+                        [c] if c.is_ascii_uppercase() => Some(vec![c - b'A' + 1]),
+                        _ => None,
+                    };
+                }
+                _ => return None,
+            };
+            Some(bytes.to_vec())
+        }
+        _ => None,
+    }
+}
+
+/// Apply a keystroke to the local line. Escape clears it.
+pub fn edit_line(editor: &mut LineEditor, event: &Event) -> LineKey {
+    match event {
+        Event::Text(text) | Event::Paste(text) => editor.insert(text),
+        Event::Key { key, pressed: true, .. } => match key {
+            Key::Enter => return LineKey::Enter,
+            Key::Backspace => editor.backspace(),
+            Key::Delete => editor.delete(),
+            Key::ArrowLeft => editor.left(),
+            Key::ArrowRight => editor.right(),
+            Key::Home => editor.home(),
+            Key::End => editor.end(),
+            Key::Escape => editor.clear(),
+            _ => return LineKey::Ignored,
+        },
+        _ => return LineKey::Ignored,
+    }
+    LineKey::Edited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::Modifiers;
+
+    fn key(key: Key, modifiers: Modifiers) -> Event {
+        Event::Key {
+            key,
+            physical_key: None,
+            pressed: true,
+            repeat: false,
+            modifiers,
+        }
+    }
+
+    #[test]
+    fn keystrokes_become_terminal_bytes() {
+        let enter = b"\r".as_slice();
+        let none = Modifiers::NONE;
+        assert_eq!(keystroke_bytes(&Event::Text("é".to_string()), enter), Some("é".as_bytes().to_vec()));
+        assert_eq!(keystroke_bytes(&key(Key::Enter, none), enter), Some(vec![b'\r']));
+        assert_eq!(keystroke_bytes(&key(Key::Backspace, none), enter), Some(vec![0x08]));
+        assert_eq!(keystroke_bytes(&key(Key::ArrowUp, none), enter), Some(b"\x1b[A".to_vec()));
+        assert_eq!(keystroke_bytes(&key(Key::Z, Modifiers::CTRL), enter), Some(vec![0x1A]));
+        assert_eq!(keystroke_bytes(&Event::Copy, enter), Some(vec![0x03]));
+        // Plain letters arrive as text events; the key event alone sends nothing
+        assert_eq!(keystroke_bytes(&key(Key::A, none), enter), None);
+        assert_eq!(keystroke_bytes(&key(Key::Num1, Modifiers::CTRL), enter), None);
+    }
+
+    #[test]
+    fn line_mode_edits_locally_until_enter() {
+        let mut editor = LineEditor::default();
+        let none = Modifiers::NONE;
+        assert_eq!(edit_line(&mut editor, &Event::Text("AT+X".to_string())), LineKey::Edited);
+        edit_line(&mut editor, &key(Key::Backspace, none));
+        edit_line(&mut editor, &Event::Paste("GMR".to_string()));
+        assert_eq!(editor.text(), "AT+GMR");
+        assert_eq!(edit_line(&mut editor, &key(Key::F1, none)), LineKey::Ignored);
+        assert_eq!(edit_line(&mut editor, &key(Key::Enter, none)), LineKey::Enter);
+        assert_eq!(editor.text(), "AT+GMR");
+        edit_line(&mut editor, &key(Key::Escape, none));
+        assert!(editor.is_empty());
+    }
+
+    #[test]
+    fn enter_sends_and_shift_enter_breaks_the_line() {
+        assert_eq!(send_box_key(&key(Key::Enter, Modifiers::NONE)), Some(SendBoxKey::Send));
+        assert_eq!(send_box_key(&key(Key::Enter, Modifiers::SHIFT)), Some(SendBoxKey::Newline));
+        assert_eq!(send_box_key(&key(Key::Enter, Modifiers::CTRL)), Some(SendBoxKey::Send));
+        let repeat = Event::Key {
+            key: Key::Enter,
+            physical_key: None,
+            pressed: true,
+            repeat: true,
+            modifiers: Modifiers::NONE,
+        };
+        assert_eq!(send_box_key(&repeat), None);
+        assert_eq!(send_box_key(&Event::Text("\n".to_string())), None);
+        assert_eq!(send_box_key(&key(Key::Tab, Modifiers::SHIFT)), None);
+    }
+
+    #[test]
+    fn plain_arrows_browse_the_history() {
+        assert_eq!(send_box_key(&key(Key::ArrowUp, Modifiers::NONE)), Some(SendBoxKey::Older));
+        assert_eq!(send_box_key(&key(Key::ArrowDown, Modifiers::NONE)), Some(SendBoxKey::Newer));
+        // Shift selects and Ctrl steps the baud rate
+        assert_eq!(send_box_key(&key(Key::ArrowUp, Modifiers::SHIFT)), None);
+        assert_eq!(send_box_key(&key(Key::ArrowDown, Modifiers::CTRL)), None);
+    }
+}
@@ -1,9 +1,30 @@
 #![windows_subsystem = "windows"]
 
+mod a11y;
+mod actions;
+mod activity;
 mod app;
-mod hex;
-mod logging;
+mod boot_log;
+mod cable_test;
+mod event_port;
+mod fast_attach;
+mod file_send;
+mod firmware;
+mod flow;
+mod fonts;
+mod instance;
+mod keyboard;
+mod net_connect;
+mod portinfo;
+mod reader;
+mod recorder;
+mod report;
+mod sanitize;
 mod serial;
+mod sound;
+mod stats;
+mod theme;
+mod translate;
 mod ui;
 mod virtual_com;
 
@@ -15,6 +36,7 @@ fn main() -> Result<(), eframe::Error> {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1200.0, 700.0])
             .with_title("RustCOM"),
+        follow_system_theme: true,
         ..Default::default()
     };
 
@@ -22,8 +44,11 @@ fn main() -> Result<(), eframe::Error> {
         "RustCOM",
         options,
         Box::new(|cc| {
-            cc.egui_ctx.set_visuals(egui::Visuals::dark());
-            Ok(Box::new(ComAnalyzerApp::default()))
+            cc.egui_ctx.set_fonts(fonts::definitions());
+            let mut app = ComAnalyzerApp::default();
+            app.load_settings();
+            app.apply_theme(&cc.egui_ctx, cc.integration_info.system_theme);
+            Ok(Box::new(app))
         }),
     )
 }
@@ -0,0 +1,76 @@
+// Network connect: a worker thread that resolves and connects a TCP or RFC 2217
+// target, so a slow or unreachable host never holds up the UI
+
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+
+use rustcom_core::rfc2217::{RemoteSettings, Rfc2217Port};
+use rustcom_core::tcp_port::TcpPort;
+use serialport::SerialPort;
+
+/// How often the UI looks for the result while a connect is under way.
+pub const NET_CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What to open: a raw socket, or an RFC 2217 port server with the line
+/// settings to negotiate.
+pub enum NetTarget {
+    Tcp,
+    Rfc2217(RemoteSettings),
+}
+
+pub struct Connected {
+    pub port: Box<dyn SerialPort>,
+    /// Why the port settings do not reach an RFC 2217 server, if they do not.
+    pub fallback: Option<&'static str>,
+}
+
+/// A connect under way. Dropping it abandons the attempt; the worker finishes
+/// on its own and the socket is closed.
+pub struct NetConnect {
+    result: Receiver<Result<Connected, String>>,
+    pub address: String,
+    pub baud_rate: u32,
+    /// Started by the Connect button, which may switch to retrying on failure.
+    pub manual: bool,
+}
+
+impl NetConnect {
+    /// Start connecting to `address`, with reads and writes on the port timing
+    /// out after `timeout`. For RFC 2217 the Telnet negotiation is part of the
+    /// connect, so its wait stays on the worker too.
+    pub fn start(address: &str, baud_rate: u32, target: NetTarget, timeout: Duration) -> Self {
+        let (sender, result) = mpsc::channel();
+        let worker_address = address.to_string();
+        std::thread::spawn(move || {
+            let connected = match target {
+                NetTarget::Tcp => TcpPort::connect(&worker_address, baud_rate, timeout).map(|port| Connected {
+                    port: Box::new(port),
+                    fallback: None,
+                }),
+                NetTarget::Rfc2217(settings) => {
+                    Rfc2217Port::connect(&worker_address, settings, timeout).map(|port| Connected {
+                        fallback: port.fallback_reason(),
+                        port: Box::new(port),
+                    })
+                }
+            };
+            let _ = sender.send(connected);
+        });
+
+        Self {
+            result,
+            address: address.to_string(),
+            baud_rate,
+            manual: false,
+        }
+    }
+
+    /// The outcome, once the worker has one.
+    pub fn poll(&self) -> Option<Result<Connected, String>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("the connect worker stopped".to_string())),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+// Port info: what the OS and driver actually granted once a port is open,
+// read back from the port and the platform rather than taken from the
+// settings, since serial bugs seen on one machine only are mostly driver
+// differences
+
+use std::time::Duration;
+
+use rustcom_core::port_config::FlowControl;
+use serialport::SerialPort;
+
+const UNKNOWN: &str = "unknown";
+
+/// What the platform reports about the driver behind a port.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DriverDetails {
+    pub driver: Option<String>,
+    /// Platform-specific extras such as FIFO or queue sizes.
+    pub rows: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PortInfo {
+    pub baud_rate: Option<u32>,
+    /// e.g. `8N1`.
+    pub format: Option<String>,
+    pub flow_control: Option<serialport::FlowControl>,
+    pub timeout: Duration,
+    pub driver: DriverDetails,
+    /// Where the port differs from what was asked for.
+    pub differences: Vec<String>,
+}
+
+impl PortInfo {
+    /// Read the settings back from the open `port` and compare them with the
+    /// baud rate and flow control asked for.
+    pub fn query(port: &dyn SerialPort, port_name: &str, baud_rate: u32, flow_control: FlowControl) -> Self {
+        let format = match (port.data_bits(), port.parity(), port.stop_bits()) {
+            (Ok(data_bits), Ok(parity), Ok(stop_bits)) => {
+                let parity = match parity {
+                    serialport::Parity::None => 'N',
+                    serialport::Parity::Odd => 'O',
+                    serialport::Parity::Even => 'E',
+                };
+                let stop_bits = match stop_bits {
+                    serialport::StopBits::One => 1,
+                    serialport::StopBits::Two => 2,
+                };
+                Some(format!("{}{}{}", u8::from(data_bits), parity, stop_bits))
+            }
+            _ => None,
+        };
+        let mut info = PortInfo {
+            baud_rate: port.baud_rate().ok(),
+            format,
+            flow_control: port.flow_control().ok(),
+            timeout: port.timeout(),
+            driver: driver_details(port_name),
+            differences: Vec::new(),
+        };
+        if let Some(granted) = info.baud_rate.filter(|&granted| granted != baud_rate) {
+            info.differences
+                .push(format!("Asked for {} baud, the driver reports {}", baud_rate, granted));
+        }
+        let wanted = flow_control.to_serial();
+        if let Some(granted) = info.flow_control.filter(|&granted| granted != wanted) {
+            info.differences.push(format!(
+                "Asked for {} flow control, the driver reports {}",
+                flow_name(wanted),
+                flow_name(granted)
+            ));
+        }
+        info
+    }
+
+    /// Name and value rows for the port info panel and reports, with
+    /// "unknown" where the platform could not say.
+    pub fn rows(&self) -> Vec<(String, String)> {
+        let known = |value: Option<String>| value.unwrap_or_else(|| UNKNOWN.to_string());
+        let mut rows = vec![
+            ("Driver".to_string(), known(self.driver.driver.clone())),
+            ("Baud rate".to_string(), known(self.baud_rate.map(|baud| baud.to_string()))),
+            ("Format".to_string(), known(self.format.clone())),
+            ("Flow control".to_string(), known(self.flow_control.map(|flow| flow_name(flow).to_string()))),
+            (
+                "RTS/CTS".to_string(),
+                known(self.flow_control.map(|flow| {
+                    let on = flow == serialport::FlowControl::Hardware;
+                    if on { "enabled" } else { "disabled" }.to_string()
+                })),
+            ),
+            ("Timeout".to_string(), format!("{} ms", self.timeout.as_millis())),
+        ];
+        rows.extend(self.driver.rows.iter().cloned());
+        rows
+    }
+}
+
+fn flow_name(flow: serialport::FlowControl) -> &'static str {
+    match flow {
+        serialport::FlowControl::None => "no",
+        serialport::FlowControl::Software => "XON/XOFF",
+        serialport::FlowControl::Hardware => "RTS/CTS",
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub mod windows {
+    use std::process::Command;
+
+    use super::DriverDetails;
+
+    /// The kernel device behind a COM port from the SERIALCOMM device map,
+    /// e.g. `\Device\Serial0` for the inbox driver or `\Device\VCP0` for FTDI.
+    /// Queue sizes (COMMPROP) need the open handle, which the port does not
+    /// expose through the `SerialPort` trait, so they are left out.
+    pub fn driver_details(port_name: &str) -> DriverDetails {
+        let output = Command::new("reg")
+            .args(["query", r"HKLM\HARDWARE\DEVICEMAP\SERIALCOMM"])
+            .output();
+        let driver = output.ok().and_then(|output| {
+            String::from_utf8_lossy(&output.stdout).lines().find_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                match fields.as_slice() {
+                    [device, "REG_SZ", name] if name.eq_ignore_ascii_case(port_name) => Some(device.to_string()),
+                    _ => None,
+                }
+            })
+        });
+        DriverDetails {
+            driver,
+            rows: Vec::new(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub mod linux {
+    use std::path::Path;
+
+    use super::DriverDetails;
+
+    pub fn driver_details(port_name: &str) -> DriverDetails {
+        details_in(Path::new("/sys/class/tty"), port_name)
+    }
+
+    /// From sysfs under `class_tty`: the driver bound to the tty's device
+    /// (`ftdi_sio`, `cp210x`, `cdc_acm`, `serial8250`, ...), and the UART
+    /// transmit FIFO of on-board ports. A symlinked name is resolved first.
+    pub fn details_in(class_tty: &Path, port_name: &str) -> DriverDetails {
+        let device = std::fs::canonicalize(port_name).unwrap_or_else(|_| port_name.into());
+        let Some(tty) = device.file_name() else {
+            return DriverDetails::default();
+        };
+        let dir = class_tty.join(tty);
+        let driver = std::fs::read_link(dir.join("device").join("driver"))
+            .ok()
+            .and_then(|target| target.file_name().map(|name| name.to_string_lossy().to_string()));
+        let mut rows = Vec::new();
+        let fifo = std::fs::read_to_string(dir.join("xmit_fifo_size")).ok();
+        if let Some(size) = fifo.and_then(|size| size.trim().parse::<u32>().ok()).filter(|&size| size > 0) {
+            rows.push(("UART TX FIFO".to_string(), format!("{} bytes", size)));
+        }
+        DriverDetails { driver, rows }
+    }
+}
+
+/// The driver details on this platform; nothing where there is no query.
+pub fn driver_details(port_name: &str) -> DriverDetails {
+    #[cfg(target_os = "windows")]
+    return windows::driver_details(port_name);
+
+    #[cfg(target_os = "linux")]
+    return linux::driver_details(port_name);
+
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    {
+        let _ = port_name;
+        DriverDetails::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustcom_core::mock_port::MockPort;
+
+    #[test]
+    fn differences_from_the_settings_are_listed() {
+        let mut port = MockPort::default();
+        port.set_baud_rate(115_200).unwrap();
+        port.set_timeout(Duration::from_millis(10)).unwrap();
+
+        let info = PortInfo::query(&port, "TEST", 115_200, FlowControl::None);
+        assert!(info.differences.is_empty());
+        let rows = info.rows();
+        assert!(rows.contains(&("Format".to_string(), "8N1".to_string())));
+        assert!(rows.contains(&("RTS/CTS".to_string(), "disabled".to_string())));
+        assert!(rows.contains(&("Timeout".to_string(), "10 ms".to_string())));
+
+        let info = PortInfo::query(&port, "TEST", 250_000, FlowControl::Hardware);
+        assert_eq!(
+            info.differences,
+            [
+                "Asked for 250000 baud, the driver reports 115200",
+                "Asked for RTS/CTS flow control, the driver reports no"
+            ]
+        );
+        assert!(PortInfo::default().rows().contains(&("Driver".to_string(), "unknown".to_string())));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sysfs_names_the_driver_and_fifo() {
+        let class_tty = std::env::temp_dir().join(format!("rustcom_sysfs_{}", std::process::id()));
+        let tty = class_tty.join("ttyS4");
+        std::fs::create_dir_all(tty.join("device")).unwrap();
+        std::os::unix::fs::symlink("../../../bus/platform/drivers/serial8250", tty.join("device").join("driver"))
+            .unwrap();
+        std::fs::write(tty.join("xmit_fifo_size"), "16\n").unwrap();
+
+        let details = linux::details_in(&class_tty, "/dev/ttyS4");
+        assert_eq!(details.driver.as_deref(), Some("serial8250"));
+        assert_eq!(details.rows, [("UART TX FIFO".to_string(), "16 bytes".to_string())]);
+        assert_eq!(linux::details_in(&class_tty, "/dev/ttyUSB7"), DriverDetails::default());
+        std::fs::remove_dir_all(class_tty).unwrap();
+    }
+}
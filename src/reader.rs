@@ -0,0 +1,178 @@
+// Port reader: a thread that reads the serial port and hands the data to the UI
+// over a channel, so throughput no longer depends on the frame rate
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, OnceLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use eframe::egui;
+use serialport::SerialPort;
+
+use crate::app::SERIAL_READ_BUFFER_SIZE;
+use crate::recorder::Recorder;
+use crate::serial::{read_port, ReadOutcome};
+
+/// Pause after an `Ok(0)` read, which returns at once instead of waiting out
+/// the port timeout.
+const ZERO_READ_PAUSE: Duration = Duration::from_millis(10);
+
+/// Port read timeout. A read returns as soon as data arrives, so this mostly
+/// bounds how long stopping the reader takes.
+pub const READ_TIMEOUT: Duration = Duration::from_millis(10);
+/// Read timeout in low-latency mode, so no read waits out more than 1 ms.
+pub const LOW_LATENCY_READ_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// Received bytes held for the UI before further reads are dropped, so a
+/// stalled UI cannot make memory grow without limit.
+pub const READER_QUEUE_BYTES: usize = 16 * 1024 * 1024;
+/// Reads held for the UI, however small, for the same reason.
+pub const READER_QUEUE_READS: usize = 16 * 1024;
+
+pub struct PortReader {
+    outcomes: Receiver<ReadOutcome>,
+    /// Bytes sent over `outcomes` and not yet polled.
+    queued: Arc<AtomicUsize>,
+    stop: Arc<AtomicBool>,
+    /// Woken when something is read, once the UI has handed over its context.
+    waker: Arc<OnceLock<egui::Context>>,
+    /// Hands back the recorder, if there was one, when it ends.
+    worker: Option<JoinHandle<Option<Recorder>>>,
+}
+
+impl PortReader {
+    /// Start reading `port`, normally a clone of the one used for writing.
+    /// Timeouts are only passed on after a zero read, to reset the count; the
+    /// reader stops after a failed read. With a `recorder`, data goes to its
+    /// file instead, until a write to it fails. Reads that find the UI too far
+    /// behind are dropped and reported as `ReadOutcome::Dropped` once there is room.
+    pub fn start(mut port: Box<dyn SerialPort>, mut recorder: Option<Recorder>) -> Self {
+        let (sender, outcomes) = mpsc::sync_channel(READER_QUEUE_READS);
+        let queued = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let waker = Arc::new(OnceLock::<egui::Context>::new());
+
+        let worker = {
+            let queued = queued.clone();
+            let stop = stop.clone();
+            let waker = waker.clone();
+            std::thread::spawn(move || {
+                let mut buffer = vec![0u8; SERIAL_READ_BUFFER_SIZE];
+                let mut after_zero = false;
+                let mut dropped = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    let outcome = read_port(&mut *port, &mut buffer);
+                    match outcome {
+                        ReadOutcome::Idle if !after_zero => continue,
+                        ReadOutcome::Zero => std::thread::sleep(ZERO_READ_PAUSE),
+                        _ => {}
+                    }
+                    after_zero = matches!(outcome, ReadOutcome::Zero);
+                    if let (Some(rec), ReadOutcome::Data { data, .. }) = (recorder.as_mut(), &outcome) {
+                        match rec.write(data) {
+                            Ok(()) => continue,
+                            // The data goes to the UI, and so does the rest
+                            Err(e) => {
+                                rec.status().fail(e);
+                                recorder = None;
+                            }
+                        }
+                    }
+                    if matches!(outcome, ReadOutcome::Failed) {
+                        // The UI has to hear about this one, so wait for room
+                        if dropped > 0 {
+                            send_waiting(&sender, ReadOutcome::Dropped(dropped), &stop);
+                        }
+                        send_waiting(&sender, outcome, &stop);
+                        break;
+                    }
+                    let size = match &outcome {
+                        ReadOutcome::Data { data, .. } => data.len(),
+                        _ => 0,
+                    };
+                    let held = queued.fetch_add(size, Ordering::Relaxed);
+                    let room = held + size <= READER_QUEUE_BYTES;
+                    // The count goes ahead of the next data there is room for, so
+                    // the gap is reported where it is
+                    if dropped > 0 && size > 0 && room {
+                        match sender.try_send(ReadOutcome::Dropped(dropped)) {
+                            Ok(()) => dropped = 0,
+                            Err(TrySendError::Full(_)) => {}
+                            Err(TrySendError::Disconnected(_)) => break,
+                        }
+                    }
+                    let sent = if dropped == 0 && room {
+                        sender.try_send(outcome)
+                    } else {
+                        Err(TrySendError::Full(outcome))
+                    };
+                    if sent.is_err() {
+                        queued.fetch_sub(size, Ordering::Relaxed);
+                    }
+                    match sent {
+                        Ok(()) => {}
+                        Err(TrySendError::Full(_)) => dropped += size,
+                        Err(TrySendError::Disconnected(_)) => break,
+                    }
+                    if let Some(ctx) = waker.get() {
+                        ctx.request_repaint();
+                    }
+                }
+                recorder
+            })
+        };
+
+        Self {
+            outcomes,
+            queued,
+            stop,
+            waker,
+            worker: Some(worker),
+        }
+    }
+
+    /// Repaint `ctx` whenever something is read from now on.
+    pub fn wake(&self, ctx: &egui::Context) {
+        let _ = self.waker.set(ctx.clone());
+    }
+
+    /// The next read, if one is waiting.
+    pub fn poll(&self) -> Option<ReadOutcome> {
+        let outcome = self.outcomes.try_recv().ok()?;
+        if let ReadOutcome::Data { data, .. } = &outcome {
+            self.queued.fetch_sub(data.len(), Ordering::Relaxed);
+        }
+        Some(outcome)
+    }
+
+    /// Stop reading and return the reads not yet polled, so a replacement
+    /// reader can take over without losing data, and the recorder it was
+    /// writing to.
+    pub fn finish(mut self) -> (Vec<ReadOutcome>, Option<Recorder>) {
+        self.stop.store(true, Ordering::Relaxed);
+        let recorder = self.worker.take().and_then(|worker| worker.join().ok().flatten());
+        (self.outcomes.try_iter().collect(), recorder)
+    }
+}
+
+/// Send `outcome` once the UI has made room, unless the reader is stopped first.
+fn send_waiting(sender: &SyncSender<ReadOutcome>, mut outcome: ReadOutcome, stop: &AtomicBool) {
+    while !stop.load(Ordering::Relaxed) {
+        match sender.try_send(outcome) {
+            Err(TrySendError::Full(back)) => outcome = back,
+            _ => return,
+        }
+        std::thread::sleep(ZERO_READ_PAUSE);
+    }
+}
+
+impl Drop for PortReader {
+    fn drop(&mut self) {
+        // A read returns within the port timeout, so this is quick
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
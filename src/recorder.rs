@@ -0,0 +1,233 @@
+// Raw recorder: the reader thread writes RX straight to a file, optionally
+// gzipped, for captures too large for the display pipeline
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::boot_log::create_capture_file;
+
+pub const RECORDER_DIR: &str = "recordings";
+/// Bytes kept for the tail preview.
+pub const TAIL_PREVIEW_BYTES: usize = 1024;
+/// How often the throughput readout is updated.
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Shared between the reader thread, which records, and the UI, which shows
+/// the counters.
+#[derive(Debug, Default)]
+pub struct RecorderStatus {
+    bytes: AtomicU64,
+    /// The last bytes written, when the preview is on.
+    tail: Option<Mutex<VecDeque<u8>>>,
+    error: Mutex<Option<String>>,
+}
+
+impl RecorderStatus {
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// The last `TAIL_PREVIEW_BYTES` recorded; `None` without a preview.
+    pub fn tail(&self) -> Option<Vec<u8>> {
+        let tail = self.tail.as_ref()?.lock().ok()?;
+        Some(tail.iter().copied().collect())
+    }
+
+    /// Why recording stopped early, if it did.
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().ok()?.clone()
+    }
+
+    pub fn fail(&self, error: String) {
+        if let Ok(mut slot) = self.error.lock() {
+            slot.get_or_insert(error);
+        }
+    }
+}
+
+enum Sink {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+/// The open recording, owned by the reader thread while it records.
+pub struct Recorder {
+    pub path: PathBuf,
+    sink: Sink,
+    status: Arc<RecorderStatus>,
+}
+
+impl Recorder {
+    /// Create `raw_<port>_<time>.bin` in `dir`, or `.bin.gz` with `gzip`.
+    pub fn create(dir: &Path, port: &str, gzip: bool, tail_preview: bool) -> Result<Self, String> {
+        let extension = if gzip { "bin.gz" } else { "bin" };
+        let (path, file) = create_capture_file(dir, "raw", port, Local::now(), extension)?;
+        let file = BufWriter::new(file);
+        let sink = if gzip {
+            Sink::Gzip(GzEncoder::new(file, Compression::fast()))
+        } else {
+            Sink::Plain(file)
+        };
+        let status = RecorderStatus {
+            tail: tail_preview.then(|| Mutex::new(VecDeque::with_capacity(TAIL_PREVIEW_BYTES))),
+            ..RecorderStatus::default()
+        };
+        Ok(Self {
+            path,
+            sink,
+            status: Arc::new(status),
+        })
+    }
+
+    pub fn status(&self) -> Arc<RecorderStatus> {
+        self.status.clone()
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        let result = match &mut self.sink {
+            Sink::Plain(file) => file.write_all(data),
+            Sink::Gzip(encoder) => encoder.write_all(data),
+        };
+        result.map_err(|e| format!("Recording to {} failed: {}", self.path.display(), e))?;
+        self.status.bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+        if let Some(Ok(mut tail)) = self.status.tail.as_ref().map(|tail| tail.lock()) {
+            let keep = &data[data.len().saturating_sub(TAIL_PREVIEW_BYTES)..];
+            let excess = (tail.len() + keep.len()).saturating_sub(TAIL_PREVIEW_BYTES);
+            tail.drain(..excess);
+            tail.extend(keep);
+        }
+        Ok(())
+    }
+
+    /// Flush and close the file, ending the gzip stream.
+    pub fn finish(self) -> Result<(), String> {
+        let result = match self.sink {
+            Sink::Plain(mut file) => file.flush(),
+            Sink::Gzip(encoder) => encoder.finish().and_then(|mut file| file.flush()),
+        };
+        result.map_err(|e| format!("Closing {} failed: {}", self.path.display(), e))
+    }
+}
+
+/// A recording as the UI sees it.
+pub struct Recording {
+    pub path: PathBuf,
+    pub status: Arc<RecorderStatus>,
+    pub started: Instant,
+    pub rate: RateMeter,
+}
+
+impl Recording {
+    pub fn new(recorder: &Recorder) -> Self {
+        Self {
+            path: recorder.path.clone(),
+            status: recorder.status(),
+            started: Instant::now(),
+            rate: RateMeter::default(),
+        }
+    }
+}
+
+/// "1.5 GB", "12.0 MB", "3.4 KB" or "900 bytes".
+pub fn format_size(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    let size = bytes as f64;
+    if size >= KB * KB * KB {
+        format!("{:.2} GB", size / (KB * KB * KB))
+    } else if size >= KB * KB {
+        format!("{:.1} MB", size / (KB * KB))
+    } else if size >= KB {
+        format!("{:.1} KB", size / KB)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+/// Throughput over the last whole second, from a running byte count.
+#[derive(Debug, Default)]
+pub struct RateMeter {
+    last: Option<(Instant, u64)>,
+    pub bytes_per_second: f64,
+}
+
+impl RateMeter {
+    pub fn sample(&mut self, bytes: u64, now: Instant) {
+        let Some((at, count)) = self.last else {
+            self.last = Some((now, bytes));
+            return;
+        };
+        let elapsed = now.saturating_duration_since(at);
+        if elapsed >= RATE_INTERVAL {
+            self.bytes_per_second = bytes.saturating_sub(count) as f64 / elapsed.as_secs_f64();
+            self.last = Some((now, bytes));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustcom-recorder-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn records_plain_and_gzip_with_a_tail() {
+        let dir = test_dir("files");
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut plain = Recorder::create(&dir, "/dev/ttyUSB0", false, true).unwrap();
+        let mut gzip = Recorder::create(&dir, "/dev/ttyUSB0", true, false).unwrap();
+        for chunk in data.chunks(700) {
+            plain.write(chunk).unwrap();
+            gzip.write(chunk).unwrap();
+        }
+        let (plain_status, gzip_status) = (plain.status(), gzip.status());
+        assert_eq!(plain_status.bytes(), 5000);
+        assert_eq!(plain_status.tail().unwrap(), &data[data.len() - TAIL_PREVIEW_BYTES..]);
+        assert_eq!(gzip_status.tail(), None);
+
+        let (plain_path, gzip_path) = (plain.path.clone(), gzip.path.clone());
+        assert!(gzip_path.to_string_lossy().ends_with(".bin.gz"));
+        assert_ne!(plain_path, gzip_path);
+        plain.finish().unwrap();
+        gzip.finish().unwrap();
+        assert_eq!(std::fs::read(&plain_path).unwrap(), data);
+        let mut unzipped = Vec::new();
+        flate2::read::GzDecoder::new(File::open(&gzip_path).unwrap())
+            .read_to_end(&mut unzipped)
+            .unwrap();
+        assert_eq!(unzipped, data);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rate_is_bytes_over_the_last_second() {
+        let start = Instant::now();
+        let mut rate = RateMeter::default();
+        rate.sample(0, start);
+        rate.sample(500, start + Duration::from_millis(400));
+        assert_eq!(rate.bytes_per_second, 0.0);
+        rate.sample(2000, start + Duration::from_secs(2));
+        assert_eq!(rate.bytes_per_second, 1000.0);
+        rate.sample(2000, start + Duration::from_secs(3));
+        assert_eq!(rate.bytes_per_second, 0.0);
+
+        assert_eq!(format_size(900), "900 bytes");
+        assert_eq!(format_size(3 * 1024 * 1024 / 2), "1.5 MB");
+        assert_eq!(format_size(5 << 30), "5.00 GB");
+    }
+}
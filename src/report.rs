@@ -0,0 +1,233 @@
+// Standalone HTML session reports
+
+use chrono::Local;
+
+use crate::event_port;
+
+const TEMPLATE: &str = include_str!("report_template.html");
+
+/// Only the newest frames are listed; a capture can hold thousands.
+pub const MAX_REPORT_FRAMES: usize = 1000;
+
+/// Which part of the capture goes into the report.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Excerpt {
+    None,
+    Everything,
+    /// From one event marker through another, by index into the marker list.
+    BetweenMarkers(usize, usize),
+}
+
+impl Excerpt {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Excerpt::None => "No capture",
+            Excerpt::Everything => "Whole capture",
+            Excerpt::BetweenMarkers(..) => "Between markers",
+        }
+    }
+}
+
+/// An event marker found in the capture text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    /// Line number in the capture text.
+    pub line: usize,
+    pub text: String,
+}
+
+/// Event marker lines in the capture, in order.
+pub fn find_markers(capture: &str) -> Vec<Marker> {
+    capture
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| event_port::is_marker_line(line))
+        .map(|(line, text)| Marker {
+            line,
+            text: text.to_string(),
+        })
+        .collect()
+}
+
+/// The capture lines selected by `range`. A marker range includes both marker
+/// lines and works in either order.
+pub fn excerpt(capture: &str, markers: &[Marker], range: Excerpt) -> Result<String, String> {
+    match range {
+        Excerpt::None => Ok(String::new()),
+        Excerpt::Everything => Ok(capture.to_string()),
+        Excerpt::BetweenMarkers(a, b) => {
+            let (Some(first), Some(second)) = (markers.get(a), markers.get(b)) else {
+                return Err("Selected marker is no longer in the capture".to_string());
+            };
+            let start = first.line.min(second.line);
+            let end = first.line.max(second.line);
+            let lines: Vec<&str> = capture.lines().skip(start).take(end - start + 1).collect();
+            Ok(lines.join("\n"))
+        }
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn table(rows: &[(String, String)]) -> String {
+    let mut html = String::from("<table>\n");
+    for (name, value) in rows {
+        html.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>\n",
+            escape_html(name),
+            escape_html(value)
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn section(title: &str, body: &str) -> String {
+    format!("<h2>{}</h2>\n{}", escape_html(title), body)
+}
+
+/// Everything that goes into a report. Built from the app state, whether or not
+/// a port is open.
+#[derive(Default)]
+pub struct Report {
+    pub title: String,
+    pub metadata: Vec<(String, String)>,
+    pub connection: Vec<(String, String)>,
+    /// The port as the OS and driver granted it.
+    pub port: Vec<(String, String)>,
+    pub statistics: Vec<(String, String)>,
+    /// Decoded frame summaries, oldest first.
+    pub frames: Vec<String>,
+    pub markers: Vec<Marker>,
+    pub excerpt: String,
+}
+
+impl Report {
+    /// Fill the embedded template. Empty sections are left out.
+    pub fn to_html(&self) -> String {
+        let mut sections = String::new();
+        if !self.metadata.is_empty() {
+            sections.push_str(&section("Session", &table(&self.metadata)));
+        }
+        if !self.connection.is_empty() {
+            sections.push_str(&section("Connection", &table(&self.connection)));
+        }
+        if !self.port.is_empty() {
+            sections.push_str(&section("Port as opened", &table(&self.port)));
+        }
+        if !self.statistics.is_empty() {
+            sections.push_str(&section("Statistics", &table(&self.statistics)));
+        }
+        if !self.markers.is_empty() {
+            let rows: Vec<(String, String)> = self
+                .markers
+                .iter()
+                .map(|marker| (format!("Line {}", marker.line + 1), marker.text.clone()))
+                .collect();
+            sections.push_str(&section("Markers", &table(&rows)));
+        }
+        if !self.frames.is_empty() {
+            let skipped = self.frames.len().saturating_sub(MAX_REPORT_FRAMES);
+            let mut body = String::new();
+            if skipped > 0 {
+                body.push_str(&format!(
+                    "<p class=\"note\">Last {} of {} frames.</p>\n",
+                    MAX_REPORT_FRAMES,
+                    self.frames.len()
+                ));
+            }
+            let lines: Vec<&str> = self.frames[skipped..].iter().map(String::as_str).collect();
+            body.push_str(&format!("<pre>{}</pre>\n", escape_html(&lines.join("\n"))));
+            sections.push_str(&section("Decoded frames", &body));
+        }
+        if !self.excerpt.is_empty() {
+            sections.push_str(&section(
+                "Capture",
+                &format!("<pre>{}</pre>\n", escape_html(&self.excerpt)),
+            ));
+        }
+
+        TEMPLATE
+            .replace("{{title}}", &escape_html(&self.title))
+            .replace(
+                "{{generated}}",
+                &Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            )
+            .replace("{{sections}}", &sections)
+    }
+
+    /// Write `<stem>.html` to the working directory.
+    pub fn save(&self, stem: &str) -> Result<String, String> {
+        let filename = format!("{}.html", stem);
+        std::fs::write(&filename, self.to_html())
+            .map_err(|e| format!("Cannot write {}: {}", filename, e))?;
+        Ok(filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPTURE: &str = "boot\n[EVENT 10:00:00.000] RELAY ON\nv=1\nv=2\n[EVENT 10:00:01.000] RELAY OFF\ntail";
+
+    #[test]
+    fn finds_marker_lines() {
+        let markers = find_markers(CAPTURE);
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[0].line, 1);
+        assert!(markers[1].text.ends_with("RELAY OFF"));
+    }
+
+    #[test]
+    fn excerpt_between_markers_in_either_order() {
+        let markers = find_markers(CAPTURE);
+        let expected = "[EVENT 10:00:00.000] RELAY ON\nv=1\nv=2\n[EVENT 10:00:01.000] RELAY OFF";
+        assert_eq!(excerpt(CAPTURE, &markers, Excerpt::BetweenMarkers(0, 1)).unwrap(), expected);
+        assert_eq!(excerpt(CAPTURE, &markers, Excerpt::BetweenMarkers(1, 0)).unwrap(), expected);
+        assert!(excerpt(CAPTURE, &markers, Excerpt::BetweenMarkers(0, 2)).is_err());
+        assert_eq!(excerpt(CAPTURE, &markers, Excerpt::None).unwrap(), "");
+        assert_eq!(excerpt(CAPTURE, &markers, Excerpt::Everything).unwrap(), CAPTURE);
+    }
+
+    #[test]
+    fn html_escapes_content_and_skips_empty_sections() {
+        let report = Report {
+            title: "Bench <A>".to_string(),
+            connection: vec![("Port".to_string(), "COM3".to_string())],
+            excerpt: "if a < b && c > d".to_string(),
+            ..Report::default()
+        };
+        let html = report.to_html();
+        assert!(html.contains("<title>Bench &lt;A&gt;</title>"));
+        assert!(html.contains("<tr><th>Port</th><td>COM3</td></tr>"));
+        assert!(html.contains("if a &lt; b &amp;&amp; c &gt; d"));
+        assert!(!html.contains("<h2>Statistics</h2>"));
+        assert!(!html.contains("<h2>Port as opened</h2>"));
+        assert!(!html.contains("{{"));
+    }
+
+    #[test]
+    fn long_frame_lists_are_truncated_to_the_newest() {
+        let report = Report {
+            frames: (0..MAX_REPORT_FRAMES + 5).map(|i| format!("frame {}", i)).collect(),
+            ..Report::default()
+        };
+        let html = report.to_html();
+        assert!(html.contains("Last 1000 of 1005 frames."));
+        assert!(!html.contains("frame 4\n"));
+        assert!(html.contains("frame 1004"));
+    }
+}
@@ -0,0 +1,205 @@
+// Defensive clean-up of terminal text before egui lays it out
+
+/// Longer lines are broken, ending each piece with `CONTINUATION_MARKER`.
+pub const MAX_DISPLAY_LINE_CHARS: usize = 1000;
+pub const CONTINUATION_MARKER: char = '↩';
+
+/// Upper bound on the text handed to the terminal each frame; older text is
+/// dropped from the view (not from the capture).
+pub const MAX_DISPLAY_TEXT_CHARS: usize = 200_000;
+
+/// Characters that change how surrounding text is laid out without being
+/// visible themselves: bidi overrides and isolates, zero-width characters, and
+/// the Unicode line/paragraph separators.
+fn is_layout_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061C}' | '\u{200B}'..='\u{200F}' | '\u{2028}'..='\u{202E}' | '\u{2060}'..='\u{2069}' | '\u{FEFF}'
+    )
+}
+
+/// Push the visible form of one character: control and layout characters become
+/// escaped tokens like `<1B>` or `<U+202E>`. Returns the number of chars pushed.
+fn push_visible(out: &mut String, c: char) -> usize {
+    if c.is_control() {
+        let token = format!("<{:02X}>", c as u32);
+        out.push_str(&token);
+        token.len()
+    } else if is_layout_control(c) {
+        let token = format!("<U+{:04X}>", c as u32);
+        out.push_str(&token);
+        token.len()
+    } else {
+        out.push(c);
+        1
+    }
+}
+
+/// Make decoded text safe to lay out: tabs, CR and LF are kept, other control
+/// characters are escaped and over-long lines are broken. Returns the text and
+/// its length in chars; past `MAX_DISPLAY_TEXT_CHARS`, pass it to `keep_tail`.
+pub fn escape_and_break(text: &str) -> (String, usize) {
+    let mut out = String::with_capacity(text.len());
+    let mut line_chars = 0;
+    let mut total_chars = 0;
+
+    for c in text.chars() {
+        let added = match c {
+            '\n' => {
+                out.push(c);
+                line_chars = 0;
+                total_chars += 1;
+                continue;
+            }
+            '\t' | '\r' => {
+                out.push(c);
+                1
+            }
+            _ => push_visible(&mut out, c),
+        };
+        line_chars += added;
+        total_chars += added;
+
+        if line_chars >= MAX_DISPLAY_LINE_CHARS {
+            out.push(CONTINUATION_MARKER);
+            out.push('\n');
+            line_chars = 0;
+            total_chars += 2;
+        }
+    }
+
+    (out, total_chars)
+}
+
+/// The last `MAX_DISPLAY_TEXT_CHARS` of `text`, starting at a line boundary,
+/// behind a one-line note. Also returns how many lines were dropped.
+pub fn keep_tail(text: &str, total_chars: usize) -> (String, usize) {
+    let skip = total_chars - MAX_DISPLAY_TEXT_CHARS;
+    let start = text.char_indices().nth(skip).map(|(i, _)| i).unwrap_or(text.len());
+    let start = text[start..]
+        .find('\n')
+        .map(|i| start + i + 1)
+        .unwrap_or(start);
+    let shown = format!(
+        "[… {} earlier characters not shown]\n{}",
+        text[..start].chars().count(),
+        &text[start..]
+    );
+    (shown, text[..start].matches('\n').count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{ComAnalyzerApp, ViewMode};
+    use rustcom_core::line_offsets::LineStart;
+
+    fn sanitize_for_display(text: &str) -> String {
+        let (out, total_chars) = escape_and_break(text);
+        if total_chars > MAX_DISPLAY_TEXT_CHARS {
+            keep_tail(&out, total_chars).0
+        } else {
+            out
+        }
+    }
+
+    #[test]
+    fn control_and_bidi_characters_become_tokens() {
+        let text = "ok\tA\u{0007}B\u{202E}evil\u{200B}\r\n\u{0085}";
+        assert_eq!(
+            sanitize_for_display(text),
+            "ok\tA<07>B<U+202E>evil<U+200B>\r\n<85>"
+        );
+    }
+
+    #[test]
+    fn long_lines_are_broken_with_a_marker() {
+        let text = "x".repeat(MAX_DISPLAY_LINE_CHARS * 2 + 5);
+        let shown = sanitize_for_display(&text);
+        let lines: Vec<&str> = shown.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with(CONTINUATION_MARKER));
+        assert_eq!(lines[2], "xxxxx");
+    }
+
+    #[test]
+    fn oversized_text_keeps_the_newest_lines() {
+        let line = "y".repeat(99);
+        let text: String = (0..MAX_DISPLAY_TEXT_CHARS / 50)
+            .map(|i| format!("{} {}\n", i, line))
+            .collect();
+        let shown = sanitize_for_display(&text);
+        assert!(shown.chars().count() <= MAX_DISPLAY_TEXT_CHARS + 50);
+        assert!(shown.starts_with("[… "));
+        assert!(shown.ends_with(&format!("{} {}\n", MAX_DISPLAY_TEXT_CHARS / 50 - 1, line)));
+        // Starts on a whole line
+        let first = shown.lines().nth(1).unwrap();
+        assert!(first.ends_with(&line));
+    }
+
+    /// xorshift64, so the test needs no RNG dependency and is reproducible.
+    fn random_bytes(seed: &mut u64, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                *seed ^= *seed << 13;
+                *seed ^= *seed >> 7;
+                *seed ^= *seed << 17;
+                *seed as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn random_bytes_render_within_bounds() {
+        let mut seed = 0x9E37_79B9_7F4A_7C15;
+        let mut app = ComAnalyzerApp::default();
+        // Mostly no newlines, with UTF-8 bidi overrides mixed in
+        let bidi = "\u{202E}\u{2066}\u{200F}".as_bytes();
+
+        for round in 0..24 {
+            let mut data = random_bytes(&mut seed, 20_000);
+            data.retain(|&b| b != b'\n');
+            for chunk in data.chunks_mut(997) {
+                chunk[..bidi.len()].copy_from_slice(bidi);
+            }
+            app.receive_buffer = data;
+            app.view_mode = [ViewMode::Ascii, ViewMode::Hex, ViewMode::Both, ViewMode::Auto][round % 4];
+            app.strip_ansi = round % 2 == 0;
+            app.mask_7bit = round % 3 == 1;
+            app.update_display_buffer();
+
+            let display = &app.receive_buffer_display;
+            assert_eq!(app.display_lines.len(), display.lines().count(), "gutter out of step in {:?}", app.view_mode);
+            assert_eq!(app.display_highlights.len(), app.display_lines.len(), "highlights out of step");
+            for line in display.lines() {
+                assert!(line.chars().count() <= MAX_DISPLAY_LINE_CHARS + 16, "line too long");
+            }
+            assert!(!display.chars().any(is_layout_control));
+            assert!(!display.chars().any(|c| c.is_control() && !"\n\r\t".contains(c)));
+        }
+    }
+
+    #[test]
+    fn gutter_offsets_stay_absolute_after_trimming() {
+        let mut app = ComAnalyzerApp::default();
+        let line = format!("{}\n", "z".repeat(99));
+        app.receive_buffer = line.repeat(crate::app::MAX_BUFFER_SIZE / 100 + 50).into_bytes();
+        app.trim_receive_buffer();
+
+        let first = app.display_lines.iter().flatten().next().copied().unwrap();
+        assert_eq!(first.offset, crate::app::BUFFER_DRAIN_SIZE as u64);
+        assert_eq!(first.line, crate::app::BUFFER_DRAIN_SIZE as u64 / 100 + 1);
+
+        app.goto_input = "0x2775".to_string();
+        app.goto().unwrap();
+        let shown = app.display_lines[app.goto_line.unwrap()].unwrap();
+        assert_eq!(shown.offset, 10_100);
+        app.goto_input = "50".to_string();
+        assert!(app.goto().unwrap_err().contains("no longer held"));
+
+        app.clear_terminal();
+        app.receive_buffer = b"next\n".to_vec();
+        app.update_display_buffer();
+        assert_eq!(app.display_lines[0], Some(LineStart { offset: 105_000, line: 1051 }));
+    }
+}
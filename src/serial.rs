@@ -1,180 +1,259 @@
-use std::io::Write as IoWrite;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use chrono::Local;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local, TimeZone};
+use serialport::{SerialPort, SerialPortBuilder, SerialPortInfo};
 
-use crate::app::ComAnalyzerApp;
-use crate::logging::{self, Direction};
+use crate::app::{ComAnalyzerApp, OfflineReplay, OfflineSession, TemplatePrompt};
+use crate::event_port::{self, EventPort};
+use crate::fast_attach::{Attached, FastAttach};
+use crate::file_send::{FilePreview, FileTransfer, FirmwareSendMode, LineConversion, XmodemRun};
+use crate::flow::FlowEvent;
+use crate::instance::{self, PortLock};
+use crate::net_connect::{Connected, NetConnect, NetTarget};
+use crate::portinfo::PortInfo;
+use crate::reader::{PortReader, LOW_LATENCY_READ_TIMEOUT, READ_TIMEOUT};
+use crate::recorder::{Recorder, Recording};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum DataBits {
-    Five,
-    Six,
-    Seven,
-    Eight,
-}
+use rustcom_core::export;
+use rustcom_core::failover::{self, PortCandidate};
+use rustcom_core::log_import;
+use rustcom_core::logging::database::DbRecord;
+use rustcom_core::logging::{self, Direction};
+use rustcom_core::metrics::Metrics;
+pub use rustcom_core::payloads::{LineEnding, SendMode};
+use rustcom_core::port_config::{char_time, FlowControl};
+use rustcom_core::protocol::{format_payload_hex, SendEncoding};
+use rustcom_core::repeat::{MAX_REPEAT_MS, MIN_REPEAT_MS};
+use rustcom_core::rx_queue::RX_QUEUE_LIMIT;
+use rustcom_core::schedule::{self, ScheduleRun, ScheduledSend};
+use rustcom_core::tcp_bridge::{BridgeEvent, TcpBridge};
+use rustcom_core::rfc2217::RemoteSettings;
+use rustcom_core::tcp_port::ConnectionType;
+use rustcom_core::xmodem::{self, Transfer};
 
-impl DataBits {
-    pub fn to_serial(self) -> serialport::DataBits {
-        match self {
-            DataBits::Five => serialport::DataBits::Five,
-            DataBits::Six => serialport::DataBits::Six,
-            DataBits::Seven => serialport::DataBits::Seven,
-            DataBits::Eight => serialport::DataBits::Eight,
-        }
-    }
+/// Most log entries an offline replay works through per frame, since TX and
+/// events each render the terminal again.
+pub const REPLAY_ENTRIES_PER_FRAME: usize = 256;
 
-    pub fn as_str(&self) -> &str {
-        match self {
-            DataBits::Five => "5",
-            DataBits::Six => "6",
-            DataBits::Seven => "7",
-            DataBits::Eight => "8",
-        }
-    }
+/// How soon the schedule looks again while a file send has the port.
+pub const SCHEDULE_BUSY_RETRY: Duration = Duration::from_millis(100);
+
+/// How often the CTS readout is refreshed.
+pub const CTS_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the RX rate is sampled while the rate alarm is on.
+pub const RATE_ALARM_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often client data is taken from the TCP bridge while it runs.
+pub const TCP_BRIDGE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Sends held back by the TX rate limit before further ones are refused.
+pub const MAX_PENDING_TX: usize = 256;
+
+/// What a transmit path does once its bytes are out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AfterTx {
+    /// TX data: logged, recorded and echoed with this line.
+    Echo(String),
+    /// A per-character keystroke: logged, not echoed; the device echoes it.
+    Keystroke,
+    /// A lone XON/XOFF, logged as an event rather than as TX data.
+    Flow(FlowEvent),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum StopBits {
-    One,
-    Two,
+/// A write held back by the TX rate limit, sent in order once its turn comes.
+#[derive(Debug, Clone)]
+pub struct PendingTx {
+    pub data: Vec<u8>,
+    after: AfterTx,
 }
 
-impl StopBits {
-    pub fn to_serial(self) -> serialport::StopBits {
-        match self {
-            StopBits::One => serialport::StopBits::One,
-            StopBits::Two => serialport::StopBits::Two,
-        }
-    }
+/// What one poll of the port returned.
+pub enum ReadOutcome {
+    /// Data, stamped when it was read.
+    Data {
+        data: Vec<u8>,
+        received_at: DateTime<Local>,
+        arrived: Instant,
+    },
+    /// Timed out, or the port was busy.
+    Idle,
+    /// `Ok(0)`: nothing read, but not a timeout either. Repeated, it means the
+    /// other end of a PTY or pipe has gone.
+    Zero,
+    Failed,
+    /// Bytes the reader thread dropped because the UI fell behind, sent in
+    /// place of the reads they came from.
+    Dropped(usize),
+}
 
-    pub fn as_str(&self) -> &str {
-        match self {
-            StopBits::One => "1",
-            StopBits::Two => "2",
-        }
+/// One read of `port` into `buffer`.
+pub fn read_port<R: std::io::Read + ?Sized>(port: &mut R, buffer: &mut [u8]) -> ReadOutcome {
+    match port.read(buffer) {
+        Ok(0) => ReadOutcome::Zero,
+        Ok(count) => ReadOutcome::Data {
+            data: buffer[..count].to_vec(),
+            received_at: Local::now(),
+            arrived: Instant::now(),
+        },
+        Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => ReadOutcome::Idle,
+        Err(_) => ReadOutcome::Failed,
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Parity {
-    None,
-    Even,
-    Odd,
+/// Pause between attempts while the port takes no bytes.
+const WRITE_RETRY_PAUSE: Duration = Duration::from_millis(1);
+
+/// Why a write stopped before all its bytes went out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WriteError {
+    /// The port took nothing for the whole write timeout, e.g. held off by
+    /// flow control.
+    TimedOut { written: usize },
+    Failed { written: usize, reason: String },
 }
 
-impl Parity {
-    pub fn to_serial(self) -> serialport::Parity {
+impl WriteError {
+    /// Bytes that went out before the write stopped.
+    pub fn written(&self) -> usize {
         match self {
-            Parity::None => serialport::Parity::None,
-            Parity::Even => serialport::Parity::Even,
-            Parity::Odd => serialport::Parity::Odd,
+            WriteError::TimedOut { written } | WriteError::Failed { written, .. } => *written,
         }
     }
 
-    pub fn as_str(&self) -> &str {
+    pub fn message(&self, total: usize) -> String {
         match self {
-            Parity::None => "None",
-            Parity::Even => "Even",
-            Parity::Odd => "Odd",
+            WriteError::TimedOut { written } => format!("Write timed out after {} of {} bytes", written, total),
+            WriteError::Failed { written, reason } => {
+                format!("Write failed after {} of {} bytes: {}", written, total, reason)
+            }
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum FlowControl {
-    None,
-    Software,
-    Hardware,
-}
-
-impl FlowControl {
-    pub fn to_serial(self) -> serialport::FlowControl {
-        match self {
-            FlowControl::None => serialport::FlowControl::None,
-            FlowControl::Software => serialport::FlowControl::Software,
-            FlowControl::Hardware => serialport::FlowControl::Hardware,
+/// Write all of `data`, carrying on after short writes and port timeouts
+/// until the port has taken nothing for `timeout`. Returns the bytes written.
+pub fn write_port<W: std::io::Write + ?Sized>(port: &mut W, data: &[u8], timeout: Duration) -> Result<usize, WriteError> {
+    let mut written = 0;
+    let mut last_progress = Instant::now();
+    while written < data.len() {
+        match port.write(&data[written..]) {
+            Ok(0) => {}
+            Ok(count) => {
+                written += count;
+                last_progress = Instant::now();
+                continue;
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(ref e) if matches!(e.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock) => {}
+            Err(e) => {
+                return Err(WriteError::Failed {
+                    written,
+                    reason: e.to_string(),
+                })
+            }
         }
+        if last_progress.elapsed() >= timeout {
+            return Err(WriteError::TimedOut { written });
+        }
+        std::thread::sleep(WRITE_RETRY_PAUSE);
     }
+    Ok(written)
+}
 
-    pub fn as_str(&self) -> &str {
-        match self {
-            FlowControl::None => "None",
-            FlowControl::Software => "Software",
-            FlowControl::Hardware => "Hardware",
-        }
+/// Rates offered in the baud list and stepped through by the baud ladder.
+pub const BAUD_RATES: [u32; 12] = [
+    300, 1200, 2400, 4800, 9600, 19200, 38400, 57600, 115_200, 230_400, 460_800, 921_600,
+];
+
+/// The standard rate above (`up`) or below `current`, or `None` past the end of
+/// the list. A non-standard rate steps to the nearest standard one that way.
+pub fn adjacent_baud(current: u32, up: bool) -> Option<u32> {
+    if up {
+        BAUD_RATES.iter().copied().find(|&rate| rate > current)
+    } else {
+        BAUD_RATES.iter().rev().copied().find(|&rate| rate < current)
     }
 }
 
+pub const MONITOR_ONLY_ERROR: &str = "Monitor-only mode is on: transmitting is disabled";
+pub const XMODEM_BUSY_ERROR: &str = "An XMODEM transfer is running";
+
+/// How often a running XMODEM transfer checks its timeouts.
+const XMODEM_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A payload held back by a send guard rule until the user confirms it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuardConfirmation {
+    /// Description of the rule that matched.
+    pub rule: String,
+    /// Exactly what would be written to the port, after encoding and parity.
+    pub wire_bytes: Vec<u8>,
+    payload: Vec<u8>,
+    resend: GuardedSend,
+}
+
+/// Where a guarded payload came from, so confirming it sends that again.
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum LineEnding {
-    None,
-    Cr,
-    Lf,
-    CrLf,
+pub enum GuardedSend {
+    SendBox,
+    Compose,
 }
 
-impl LineEnding {
-    pub fn as_str(&self) -> &str {
-        match self {
-            LineEnding::None => "None",
-            LineEnding::Cr => "\\r",
-            LineEnding::Lf => "\\n",
-            LineEnding::CrLf => "\\r\\n",
-        }
-    }
+/// Opens a port by name with a prepared builder.
+pub type OpenPort = fn(&str, SerialPortBuilder) -> Result<Box<dyn SerialPort>, String>;
 
-    pub fn as_bytes(&self) -> &[u8] {
-        match self {
-            LineEnding::None => b"",
-            LineEnding::Cr => b"\r",
-            LineEnding::Lf => b"\n",
-            LineEnding::CrLf => b"\r\n",
-        }
-    }
+/// How ports are listed and opened. The real serial ports by default; tests
+/// put fakes here to drive connect and failover without hardware.
+#[derive(Clone, Copy)]
+pub struct PortOpener {
+    pub list: fn() -> Vec<SerialPortInfo>,
+    pub open: OpenPort,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum SendMode {
-    Ascii,
-    Hex,
+impl Default for PortOpener {
+    fn default() -> Self {
+        Self {
+            list: || serialport::available_ports().unwrap_or_default(),
+            open: |_, builder| builder.open().map_err(|e| e.to_string()),
+        }
+    }
 }
 
 impl ComAnalyzerApp {
+    /// Character time for the current line settings, assuming 9600 baud when the
+    /// baud rate field does not parse.
+    pub fn char_time(&self) -> Duration {
+        let baud = self.baud_rate.parse().unwrap_or(9600);
+        char_time(baud, self.data_bits, self.parity, self.stop_bits)
+    }
+
     pub fn connect(&mut self) {
         self.error_message = None;
-
-        let Some(port_name) = &self.selected_port else {
-            self.error_message = Some("Please select a port".to_string());
+        if self.connection_type.is_network() {
+            self.connect_tcp();
             return;
-        };
+        }
+        if self.failover.active() {
+            self.connect_failover();
+            return;
+        }
 
-        let baud_rate: u32 = match self.baud_rate.parse() {
-            Ok(rate) => rate,
-            Err(_) => {
-                self.error_message = Some("Invalid baud rate".to_string());
+        let (port_name, baud_rate, builder) = match self.port_builder() {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                self.error_message = Some(e);
                 return;
             }
         };
 
-        match serialport::new(port_name, baud_rate)
-            .data_bits(self.data_bits.to_serial())
-            .stop_bits(self.stop_bits.to_serial())
-            .parity(self.parity.to_serial())
-            .flow_control(self.flow_control.to_serial())
-            .timeout(Duration::from_millis(10))
-            .open()
-        {
+        match (self.port_opener.open)(&port_name, builder) {
             Ok(port) => {
-                self.serial_port = Some(Arc::new(Mutex::new(port)));
-                self.connected = true;
-                let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-                let msg = format!(
-                    "[{}] Connected to {} at {} baud\n",
-                    timestamp, port_name, baud_rate
-                );
-                self.receive_buffer.extend_from_slice(msg.as_bytes());
-                self.update_display_buffer();
+                self.attach_timing = None;
+                self.attach_port(port, &port_name, baud_rate);
             }
             Err(e) => {
                 self.error_message = Some(format!("Failed to open port: {}", e));
@@ -182,96 +261,3996 @@ impl ComAnalyzerApp {
         }
     }
 
-    pub fn disconnect(&mut self) {
-        self.serial_port = None;
-        self.connected = false;
-        self.reconnecting = false;
+    /// Try each port of the failover list in order and attach the first that
+    /// opens, noting in the terminal which one it was and why earlier ones failed.
+    fn connect_failover(&mut self) {
+        let ports = if self.failover.needs_port_list() {
+            (self.port_opener.list)()
+        } else {
+            Vec::new()
+        };
+        let candidates = self.failover.candidates.clone();
+        let open = self.port_opener.open;
+        let mut baud_rate = 0;
+        let opened = failover::open_first(&candidates, &ports, |port_name| {
+            let (_, baud, builder) = self.port_builder_for(port_name)?;
+            baud_rate = baud;
+            open(port_name, builder)
+        });
+
         let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-        let msg = format!("[{}] Disconnected\n", timestamp);
-        self.receive_buffer.extend_from_slice(msg.as_bytes());
-        self.update_display_buffer();
+        match opened {
+            Ok(opened) => {
+                let mut msg = format!(
+                    "[{}] Failover: using {} (candidate {} of {}, {})",
+                    timestamp,
+                    opened.port_name,
+                    opened.index + 1,
+                    candidates.len(),
+                    candidates[opened.index].label()
+                );
+                if !opened.failures.is_empty() {
+                    msg.push_str(&format!("; skipped {}", failover::describe_failures(&opened.failures)));
+                }
+                msg.push('\n');
+                self.receive_buffer.extend_from_slice(msg.as_bytes());
+                self.selected_port = Some(opened.port_name.clone());
+                self.attach_timing = None;
+                self.attach_port(opened.port, &opened.port_name, baud_rate);
+            }
+            Err(failures) => {
+                let reasons = failover::describe_failures(&failures);
+                let msg = format!("[{}] Failover: no candidate opened ({})\n", timestamp, reasons);
+                self.receive_buffer.extend_from_slice(msg.as_bytes());
+                self.update_display_buffer();
+                self.error_message = Some(format!("No port in the failover list could be opened: {}", reasons));
+            }
+        }
     }
 
-    pub fn send_data(&mut self) {
-        let mut data = self.send_buffer.clone().into_bytes();
-        data.extend_from_slice(self.line_ending.as_bytes());
+    /// Start opening a socket to `tcp_address` on a worker thread; the result
+    /// is attached like a port by `finish_net_connect`, so its data takes the
+    /// same path as a serial port's. Over RFC 2217 the line settings and
+    /// DTR/RTS go to the server's port.
+    fn connect_tcp(&mut self) {
+        let address = self.tcp_address.trim().to_string();
+        if address.is_empty() {
+            self.error_message = Some("Please enter a host:port".to_string());
+            return;
+        }
+        let baud_rate = self.baud_rate.parse().unwrap_or(9600);
+        let target = match self.connection_type {
+            ConnectionType::Rfc2217 => NetTarget::Rfc2217(RemoteSettings {
+                baud_rate,
+                data_bits: self.data_bits.to_serial(),
+                parity: self.parity.to_serial(),
+                stop_bits: self.stop_bits.to_serial(),
+                flow_control: self.flow_control.to_serial(),
+                dtr: self.dtr_state,
+                rts: self.rts_state,
+            }),
+            _ => NetTarget::Tcp,
+        };
+        self.net_connect = Some(NetConnect::start(&address, baud_rate, target, READ_TIMEOUT));
+    }
 
-        let send_result = if let Some(port) = &self.serial_port {
-            if let Ok(mut port_guard) = port.lock() {
-                port_guard.write(&data).ok()
-            } else {
-                None
+    /// Attach the socket a network connect opened, or report why it failed,
+    /// then carry on with the reconnect or retry that started it. A server that
+    /// will not take RFC 2217 port control is used without it, with a warning.
+    pub fn finish_net_connect(&mut self, result: Result<Connected, String>) {
+        let Some(pending) = self.net_connect.take() else {
+            return;
+        };
+        match result {
+            Ok(connected) => {
+                self.attach_timing = None;
+                self.attach_port(connected.port, &pending.address, pending.baud_rate);
+                if let Some(reason) = connected.fallback {
+                    let warning = format!("RFC 2217: {}; continuing without COM port control", reason);
+                    self.note_event(&warning);
+                    self.error_message = Some(warning);
+                }
+            }
+            Err(e) => self.error_message = Some(format!("Failed to connect: {}", e)),
+        }
+        if self.reconnecting {
+            self.finish_reconnect_attempt();
+        } else if pending.manual {
+            self.retry_failed_connect();
+        }
+    }
+
+    /// Retrying, or waiting on a network connect.
+    pub fn connecting(&self) -> bool {
+        self.reconnecting || self.net_connect.is_some()
+    }
+
+    /// Whether Connect has anything to open: a selected port or a failover
+    /// list, or a host:port in TCP client and RFC 2217 modes.
+    pub fn has_connect_target(&self) -> bool {
+        match self.connection_type {
+            ConnectionType::Serial => self.selected_port.is_some() || self.failover.active(),
+            ConnectionType::TcpClient | ConnectionType::Rfc2217 => !self.tcp_address.trim().is_empty(),
+        }
+    }
+
+    /// What Connect opens, for messages: the selected port, or the host:port
+    /// in TCP client and RFC 2217 modes.
+    pub fn connection_name(&self) -> Option<&str> {
+        match self.connection_type {
+            ConnectionType::Serial => self.selected_port.as_deref(),
+            ConnectionType::TcpClient | ConnectionType::Rfc2217 => {
+                Some(self.tcp_address.trim()).filter(|address| !address.is_empty())
             }
+        }
+    }
+
+    /// Add the typed failover candidate, or the selected port when nothing is
+    /// typed: its USB identity if it has one, else its name.
+    pub fn add_failover_candidate(&mut self) -> Result<(), String> {
+        let candidate = if self.failover_input.trim().is_empty() {
+            let Some(selected) = &self.selected_port else {
+                return Err("Type a port or usb:VID:PID, or select a port to add".to_string());
+            };
+            self.available_ports
+                .iter()
+                .find(|port| &port.port_name == selected)
+                .map_or_else(|| PortCandidate::Name(selected.clone()), PortCandidate::for_port)
         } else {
-            None
+            PortCandidate::parse(&self.failover_input)?
         };
+        if self.failover.candidates.contains(&candidate) {
+            return Err(format!("{} is already in the list", candidate.label()));
+        }
+        self.failover.candidates.push(candidate);
+        self.failover_input.clear();
+        Ok(())
+    }
 
-        if let Some(bytes) = send_result {
-            self.bytes_sent += bytes;
+    /// The selected port with every setting applied, ready to open. Fails when
+    /// nothing is selected, the baud rate is invalid or another RustCOM window
+    /// holds the port.
+    pub fn port_builder(&mut self) -> Result<(String, u32, SerialPortBuilder), String> {
+        let Some(port_name) = self.selected_port.clone() else {
+            return Err("Please select a port".to_string());
+        };
+        self.port_builder_for(&port_name)
+    }
 
-            if self.logging_enabled {
-                self.log_entries
-                    .push(logging::create_log_entry(Direction::Sent, &data));
+    /// `port_name` with every setting applied, as for `port_builder`.
+    pub fn port_builder_for(&mut self, port_name: &str) -> Result<(String, u32, SerialPortBuilder), String> {
+        let port_name = port_name.to_string();
+        let baud_rate: u32 = self
+            .baud_rate
+            .parse()
+            .map_err(|_| "Invalid baud rate".to_string())?;
+
+        let own_addr = self.instance_server.as_ref().and_then(|server| server.addr());
+        if let Some(owner) = instance::find_owner(&port_name, own_addr) {
+            let message = format!("{} is open in another RustCOM window (pid {})", port_name, owner.pid);
+            self.port_owner = Some(owner);
+            return Err(message);
+        }
+        self.port_owner = None;
+
+        let builder = serialport::new(&port_name, baud_rate)
+            .data_bits(self.data_bits.to_serial())
+            .stop_bits(self.stop_bits.to_serial())
+            .parity(self.parity.to_serial())
+            .flow_control(self.flow_control.to_serial())
+            .timeout(READ_TIMEOUT);
+        Ok((port_name, baud_rate, builder))
+    }
+
+    /// Take an opened port into use and reset the per-connection state.
+    pub fn attach_port(&mut self, port: Box<dyn SerialPort>, port_name: &str, baud_rate: u32) {
+        let own_addr = self.instance_server.as_ref().and_then(|server| server.addr());
+        self.port_info = Some(PortInfo::query(&*port, port_name, baud_rate, self.flow_control));
+        self.serial_port = Some(Arc::new(Mutex::new(port)));
+        self.port_reader = self.start_reader(None);
+        self.connected = true;
+        self.metrics.connected.store(true, Ordering::Relaxed);
+        self.live_feed.set_port(port_name);
+        self.connection_generation += 1;
+        self.zero_reads = 0;
+        self.port_lock = own_addr.and_then(|addr| PortLock::acquire(port_name, addr).ok());
+        let char_time = char_time(baud_rate, self.data_bits, self.parity, self.stop_bits);
+        if let Some(decoder) = self.decoder.as_mut() {
+            decoder.reset();
+            decoder.set_line_timing(baud_rate, char_time);
+        }
+        self.protocol_detector.reset();
+        self.encoding_detector.reset();
+        self.encoding_suggestion = None;
+        self.rx_stream = 0;
+        self.start_text_decoding(self.display_encoding, 0);
+        self.offline_session = None;
+        self.offline_replay = None;
+        self.flow_tracker.reset();
+        self.burst_tracker.reset();
+        self.clock_watch.reset();
+        self.echo_latency.forget_tx();
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+        let msg = match self.connection_type {
+            ConnectionType::Serial => format!("[{}] Connected to {} at {} baud\n", timestamp, port_name, baud_rate),
+            ConnectionType::TcpClient => format!("[{}] Connected to {} over TCP\n", timestamp, port_name),
+            ConnectionType::Rfc2217 => {
+                format!("[{}] Connected to {} over RFC 2217 at {} baud\n", timestamp, port_name, baud_rate)
             }
+        };
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+        self.update_display_buffer();
+        self.start_boot_capture();
+        self.db_log(DbRecord::SessionStart {
+            at: Local::now(),
+            port: port_name.to_string(),
+            baud_rate,
+        });
+    }
 
-            let msg = format!("TX: {}\n", self.send_buffer);
-            self.receive_buffer.extend_from_slice(msg.as_bytes());
-            self.update_display_buffer();
-            self.send_buffer.clear();
-        } else {
-            self.error_message = Some("Send failed".to_string());
+    /// A thread reading a clone of the open port, so writes stay here, with
+    /// the read timeout for the latency mode. `None` if the port cannot be
+    /// cloned; the UI then reads it every frame.
+    fn start_reader(&self, recorder: Option<Recorder>) -> Option<PortReader> {
+        let mut port = self.serial_port.as_ref()?.lock().ok()?.try_clone().ok()?;
+        let timeout = if self.low_latency { LOW_LATENCY_READ_TIMEOUT } else { READ_TIMEOUT };
+        let _ = port.set_timeout(timeout);
+        Some(PortReader::start(port, recorder))
+    }
+
+    /// Stop the reader thread and work off everything it read, so a
+    /// replacement starts exactly where it stopped. Returns its recorder.
+    fn stop_reader(&mut self) -> Option<Recorder> {
+        let (outcomes, recorder) = self.port_reader.take()?.finish();
+        for outcome in outcomes {
+            self.handle_read(outcome);
         }
+        self.drain_rx_queue();
+        recorder
     }
 
-    pub fn send_hex_input(&mut self) {
-        match crate::hex::parse_hex_input(&self.send_buffer) {
-            Ok(bytes) => {
-                let send_result = if let Some(port) = &self.serial_port {
-                    if let Ok(mut port_guard) = port.lock() {
-                        port_guard.write(&bytes).ok()
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
+    /// Stop the reader thread of a closing port, ending any recording.
+    fn drop_reader(&mut self) {
+        let recorder = self.port_reader.take().and_then(|reader| reader.finish().1);
+        self.end_recording(recorder);
+    }
 
-                if let Some(count) = send_result {
-                    self.bytes_sent += count;
+    /// Switch to raw recording: the reader thread writes RX to a new file in
+    /// `recorder_dir`, gzipped if chosen, and the display, decoders and filters
+    /// see none of it. The switch happens between two reads, so no byte is
+    /// lost or recorded twice.
+    pub fn start_recording(&mut self) -> Result<(), String> {
+        if self.recording.is_some() {
+            return Ok(());
+        }
+        if !self.connected {
+            return Err("Not connected".to_string());
+        }
+        if self.xmodem.is_some() {
+            // The recorder would take the transfer's ACKs and NAKs
+            return Err("Wait for the XMODEM transfer to end first".to_string());
+        }
+        if self.port_reader.is_none() {
+            return Err("Raw recording needs the reader thread, and this port could not be cloned".to_string());
+        }
+        let port_name = self.connection_name().unwrap_or_default().to_string();
+        let recorder = Recorder::create(
+            std::path::Path::new(&self.recorder_dir),
+            &port_name,
+            self.recorder_gzip,
+            self.recorder_tail,
+        )?;
+        let recording = Recording::new(&recorder);
+        let previous = self.stop_reader();
+        self.end_recording(previous);
+        self.port_reader = self.start_reader(Some(recorder));
+        self.note_recording(&format!("Raw recording to {}", recording.path.display()));
+        self.recording = Some(recording);
+        Ok(())
+    }
 
-                    if self.logging_enabled {
-                        self.log_entries
-                            .push(logging::create_log_entry(Direction::Sent, &bytes));
-                    }
+    /// Leave raw recording and hand RX back to the display, from the byte
+    /// after the last one recorded.
+    pub fn stop_recording(&mut self) {
+        let recorder = self.stop_reader();
+        self.end_recording(recorder);
+        if self.connected {
+            self.port_reader = self.start_reader(None);
+        }
+    }
 
-                    let hex_str: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
-                    let msg = format!("TX [hex]: {}\n", hex_str.join(" "));
-                    self.receive_buffer.extend_from_slice(msg.as_bytes());
-                    self.update_display_buffer();
-                    self.send_buffer.clear();
-                } else {
-                    self.error_message = Some("Send failed".to_string());
-                }
+    /// Close the recording's file and say how it went in the terminal.
+    fn end_recording(&mut self, recorder: Option<Recorder>) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+        let closed = recorder.map_or(Ok(()), Recorder::finish);
+        if let Some(e) = recording.status.error().or(closed.err()) {
+            self.error_message = Some(e);
+        }
+        self.note_recording(&format!(
+            "Raw recording stopped: {} bytes in {}",
+            recording.status.bytes(),
+            recording.path.display()
+        ));
+    }
+
+    fn note_recording(&mut self, what: &str) {
+        let msg = format!("[{}] {}\n", Local::now().format("%Y-%m-%d %H:%M:%S"), what);
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+        self.update_display_buffer();
+    }
+
+    /// Switch low-latency mode, for timing device echoes. The reader thread
+    /// reads with a 1 ms timeout, and each read is timed against the last
+    /// write as it comes off the reader, not when the frame budget gets to
+    /// it. A connected reader is restarted with the new timeout.
+    pub fn set_low_latency(&mut self, on: bool) {
+        self.low_latency = on;
+        self.echo_latency.reset();
+        let recorder = self.stop_reader();
+        if self.connected {
+            self.port_reader = self.start_reader(recorder);
+        }
+    }
+
+    /// Start watching for the selected port so it is opened the moment it
+    /// appears, instead of on the next reconnect tick.
+    pub fn arm_fast_attach(&mut self, ctx: &eframe::egui::Context) {
+        match self.port_builder() {
+            Ok((port_name, baud_rate, builder)) => {
+                self.fast_attach = Some(FastAttach::start(
+                    &port_name,
+                    baud_rate,
+                    builder,
+                    // Raising DTR resets many boards, which monitor-only is there to prevent
+                    self.fast_attach_dtr && !self.monitor_only,
+                    ctx.clone(),
+                ));
             }
             Err(e) => {
+                self.reconnecting = false;
                 self.error_message = Some(e);
             }
         }
     }
 
-    pub fn set_dtr(&mut self, state: bool) {
-        if let Some(port) = &self.serial_port {
-            if let Ok(mut port_guard) = port.lock() {
-                let _ = port_guard.write_data_terminal_ready(state);
-            }
+    /// Take over the port the fast attach worker opened.
+    pub fn complete_fast_attach(&mut self, attached: Attached) {
+        self.fast_attach = None;
+        self.reconnecting = false;
+        self.attach_port(attached.port, &attached.port_name, attached.baud_rate);
+        if self.fast_attach_dtr && !self.monitor_only {
+            self.dtr_state = true;
         }
+        let msg = format!(
+            "[{}] Fast attach: {} opened {} ms after it appeared\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            attached.port_name,
+            attached.timing.opened_after.as_millis()
+        );
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+        self.restore_signals();
+        self.update_display_buffer();
+        self.attach_timing = Some(attached.timing);
     }
 
-    pub fn set_rts(&mut self, state: bool) {
-        if let Some(port) = &self.serial_port {
-            if let Ok(mut port_guard) = port.lock() {
-                let _ = port_guard.write_request_to_send(state);
+    /// Baud ladder: move to the next (`up`) or previous standard baud rate and,
+    /// when connected, reopen the port at that rate, logging the step.
+    pub fn step_baud(&mut self, up: bool) {
+        let Ok(current) = self.baud_rate.parse::<u32>() else {
+            self.error_message = Some("Invalid baud rate".to_string());
+            return;
+        };
+        let Some(next) = adjacent_baud(current, up) else {
+            let end = if up { "highest" } else { "lowest" };
+            self.error_message = Some(format!("{} is the {} standard baud rate", current, end));
+            return;
+        };
+        self.baud_rate = next.to_string();
+        self.error_message = None;
+        // A waiting fast attach re-arms with the new rate
+        self.fast_attach = None;
+        if !self.connected {
+            return;
+        }
+
+        let msg = format!(
+            "[{}] Baud ladder: {} -> {}, reopening {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            current,
+            next,
+            self.connection_name().unwrap_or("unknown")
+        );
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+        // A full disconnect, so repeats, bridges and transfers stop as they would by hand
+        self.disconnect();
+        self.connect();
+        if !self.connected && self.net_connect.is_none() {
+            let msg = format!(
+                "[{}] Baud ladder: could not reopen at {} baud\n",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                next
+            );
+            self.receive_buffer.extend_from_slice(msg.as_bytes());
+            self.update_display_buffer();
+        }
+    }
+
+    /// One auto-reconnect attempt. Once the port is back, DTR and RTS are put
+    /// back the way they were.
+    pub fn attempt_reconnect(&mut self) {
+        self.reconnect_attempts += 1;
+        self.connect();
+        if self.net_connect.is_none() {
+            self.finish_reconnect_attempt();
+        }
+    }
+
+    /// Note how a reconnect attempt went, once it is over.
+    fn finish_reconnect_attempt(&mut self) {
+        if !self.connected {
+            let msg = format!(
+                "[{}] Connect attempt {} failed\n",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                self.reconnect_attempts
+            );
+            self.receive_buffer.extend_from_slice(msg.as_bytes());
+            self.update_display_buffer();
+            return;
+        }
+
+        self.reconnecting = false;
+        Metrics::add(&self.metrics.reconnects, 1);
+        let msg = format!(
+            "[{}] Reconnected successfully\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S")
+        );
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+        self.restore_signals();
+        self.update_display_buffer();
+        self.error_message = Some("Reconnected!".to_string());
+    }
+
+    /// Re-apply the DTR and RTS states last chosen, DTR first, since a reopened
+    /// port starts with the driver's defaults whatever the checkboxes show.
+    /// Each line is noted in the terminal. Monitor-only sessions leave them alone.
+    pub fn restore_signals(&mut self) {
+        if self.monitor_only {
+            return;
+        }
+        let Some(port) = self.serial_port.clone() else {
+            return;
+        };
+        let Ok(mut port_guard) = port.lock() else {
+            return;
+        };
+        let dtr = port_guard.write_data_terminal_ready(self.dtr_state);
+        let rts = port_guard.write_request_to_send(self.rts_state);
+        drop(port_guard);
+
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+        for (name, state, result) in [("DTR", self.dtr_state, dtr), ("RTS", self.rts_state, rts)] {
+            let msg = match result {
+                Ok(()) => format!("[{}] Restored {}: {}\n", timestamp, name, if state { "on" } else { "off" }),
+                Err(e) => format!("[{}] Could not restore {}: {}\n", timestamp, name, e),
+            };
+            self.receive_buffer.extend_from_slice(msg.as_bytes());
+        }
+    }
+
+    /// Drop a connection that failed underneath us, logging `reason`, and start
+    /// auto-reconnecting if enabled.
+    pub fn connection_lost(&mut self, reason: &str) {
+        // Data read before the failure belongs above the marker
+        self.drain_rx_queue();
+        let msg = format!(
+            "\n[{}] Connection lost to {}: {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            self.connection_name().unwrap_or("unknown"),
+            reason
+        );
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+        self.drop_tx_pending();
+        self.update_display_buffer();
+
+        self.serial_port = None;
+        self.drop_reader();
+        self.port_lock = None;
+        self.connected = false;
+        self.repeat.stop();
+        self.stop_tcp_bridge();
+        self.metrics.connected.store(false, Ordering::Relaxed);
+        self.snapshot_on_disconnect();
+        self.db_log(DbRecord::SessionEnd { at: Local::now() });
+
+        if self.auto_reconnect {
+            self.reconnecting = true;
+            self.reconnect_attempts = 0;
+            self.error_message = Some(format!("Connection lost ({}). Auto-reconnecting...", reason));
+        } else {
+            self.error_message = Some(format!("Connection lost ({}).", reason));
+        }
+    }
+
+    /// Count a zero-byte read. Returns true once there were enough in a row to
+    /// treat the port as closed by its peer.
+    pub fn note_zero_read(&mut self) -> bool {
+        self.zero_reads += 1;
+        self.eof_detection && self.zero_reads >= self.eof_zero_reads.max(1)
+    }
+
+    /// Connect button handler. With "keep trying" enabled, a failed first attempt
+    /// switches to the reconnecting state instead of giving up.
+    pub fn connect_manually(&mut self) {
+        self.connect();
+        if let Some(pending) = self.net_connect.as_mut() {
+            pending.manual = true;
+            return;
+        }
+        self.retry_failed_connect();
+    }
+
+    /// Switch to retrying after a failed first attempt, if so configured.
+    fn retry_failed_connect(&mut self) {
+        if self.connected || !self.retry_initial_connect || !self.has_connect_target() {
+            return;
+        }
+
+        self.reconnecting = true;
+        self.reconnect_attempts = 1;
+        self.last_reconnect_attempt = std::time::Instant::now();
+        let target = if self.connection_type == ConnectionType::Serial && self.failover.active() {
+            "any failover port"
+        } else {
+            self.connection_name().unwrap_or("unknown")
+        };
+        let msg = format!(
+            "[{}] Could not open {}, retrying every {} ms\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            target,
+            self.reconnect_delay_ms
+        );
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+        self.update_display_buffer();
+    }
+
+    pub fn cancel_reconnect(&mut self) {
+        let pending = self.net_connect.take();
+        self.fast_attach = None;
+        self.error_message = None;
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+        let msg = match pending {
+            Some(pending) if !self.reconnecting => format!("[{}] Connecting to {} cancelled\n", timestamp, pending.address),
+            _ => format!("[{}] Connecting cancelled after {} attempt(s)\n", timestamp, self.reconnect_attempts),
+        };
+        self.reconnecting = false;
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+        self.update_display_buffer();
+    }
+
+    /// Ask the instance holding the selected port to let go of it, then keep
+    /// retrying until it does.
+    pub fn request_port_release(&mut self) {
+        let Some(owner) = self.port_owner.take() else {
+            return;
+        };
+
+        match instance::request_release(&owner) {
+            Ok(()) => {
+                self.error_message = None;
+                self.reconnecting = true;
+                self.reconnect_attempts = 0;
+                self.last_reconnect_attempt = std::time::Instant::now();
+                let msg = format!(
+                    "[{}] Asked the other RustCOM window to release {}\n",
+                    Local::now().format("%Y-%m-%d %H:%M:%S"),
+                    owner.port
+                );
+                self.receive_buffer.extend_from_slice(msg.as_bytes());
+                self.update_display_buffer();
             }
+            Err(e) => self.error_message = Some(e),
         }
     }
+
+    pub fn disconnect(&mut self) {
+        // Tell the other side while the port is still open
+        if let Some(run) = self.xmodem.as_mut() {
+            let out = run.transfer.cancel();
+            let _ = self.write_to_port(&out);
+            self.end_xmodem(Some("disconnected"));
+        }
+        self.serial_port = None;
+        self.drop_reader();
+        self.port_lock = None;
+        self.connected = false;
+        self.repeat.stop();
+        self.stop_tcp_bridge();
+        self.metrics.connected.store(false, Ordering::Relaxed);
+        self.reconnecting = false;
+        self.fast_attach = None;
+        self.net_connect = None;
+        self.drop_tx_pending();
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+        let msg = format!("[{}] Disconnected\n", timestamp);
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+        self.update_display_buffer();
+        self.snapshot_on_disconnect();
+        self.db_log(DbRecord::SessionEnd { at: Local::now() });
+    }
+
+    pub fn open_event_port(&mut self) {
+        let Some(name) = self.event_port_name.clone() else {
+            self.error_message = Some("Please select an event port".to_string());
+            return;
+        };
+        if self.selected_port.as_deref() == Some(name.as_str()) && self.connected {
+            self.error_message = Some("The event port must differ from the main port".to_string());
+            return;
+        }
+        let baud_rate: u32 = match self.event_baud_rate.parse() {
+            Ok(rate) => rate,
+            Err(_) => {
+                self.error_message = Some("Invalid event port baud rate".to_string());
+                return;
+            }
+        };
+
+        match EventPort::open(&name, baud_rate) {
+            Ok(port) => {
+                self.event_port = Some(port);
+                self.error_message = None;
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// Insert a line from the event port into the terminal as a timestamped marker.
+    pub fn inject_marker(&mut self, line: &str) {
+        self.inject_marker_at(line, Local::now());
+    }
+
+    /// Insert a marker stamped `at`, e.g. one replayed from a log.
+    pub fn inject_marker_at(&mut self, line: &str, at: DateTime<Local>) {
+        self.break_line();
+        let timestamp = at.format("%H:%M:%S%.3f").to_string();
+        let marker = event_port::format_marker(&timestamp, line);
+        self.receive_buffer.extend_from_slice(marker.as_bytes());
+        if self.logging_enabled {
+            self.log_entry(logging::create_log_entry_at(Direction::Event, line.as_bytes(), at));
+        }
+        self.has_event_markers = true;
+        self.update_display_buffer();
+        self.db_log(DbRecord::Event {
+            at,
+            text: line.to_string(),
+        });
+        self.forward_syslog(Direction::Event, line);
+    }
+
+    /// Replay the log file at `log_import_path` as an offline session: the
+    /// terminal and statistics start over, RX goes through the receive path
+    /// with its logged times, TX is echoed and events become markers. Only
+    /// while disconnected. The replay runs a frame's budget at a time, see
+    /// `step_offline_replay`.
+    pub fn open_offline_log(&mut self) -> Result<(), String> {
+        if self.connected || self.connecting() {
+            return Err("Disconnect before opening a log".to_string());
+        }
+        let path = self.log_import_path.trim().to_string();
+        let data = std::fs::read(&path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let (format, log) = log_import::import(&data)?;
+
+        self.clear_terminal();
+        self.reset_counters();
+        self.clock_watch.reset();
+        if let Some(decoder) = self.decoder.as_mut() {
+            decoder.reset();
+        }
+        self.protocol_detector.reset();
+        self.encoding_detector.reset();
+        self.encoding_suggestion = None;
+        self.rx_stream = 0;
+        self.start_text_decoding(self.display_encoding, 0);
+        let mut msg = format!(
+            "[{}] Offline session: {} ({}, {} entries",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            path,
+            format.as_str(),
+            log.entries.len()
+        );
+        if log.skipped > 0 {
+            msg.push_str(&format!(", {} unreadable skipped", log.skipped));
+        }
+        msg.push_str(")\n");
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+
+        let entries = log.entries.len();
+        let stamped: VecDeque<_> = log
+            .entries
+            .into_iter()
+            .filter_map(|entry| {
+                let time = export::parse_timestamp(&entry.timestamp)?;
+                let at = Local.from_local_datetime(&time).earliest()?;
+                Some((entry, at))
+            })
+            .collect();
+        // Monotonic arrival times spaced like the logged ones, ending now, so
+        // bursts and gaps come out as they were recorded
+        let first = stamped.front().map(|(_, at)| *at).unwrap_or_else(Local::now);
+        let span = stamped.back().map_or(Duration::ZERO, |(_, at)| (*at - first).to_std().unwrap_or_default());
+        self.offline_replay = Some(OfflineReplay {
+            entries: stamped,
+            first,
+            start: Instant::now().checked_sub(span).unwrap_or_else(Instant::now),
+        });
+        self.offline_session = Some(OfflineSession {
+            path,
+            format,
+            entries,
+            skipped: log.skipped,
+        });
+        self.step_offline_replay();
+        Ok(())
+    }
+
+    /// Replay the next frame's budget of the offline log, kept out of the
+    /// database, syslog, metrics and streaming log. Returns whether any is left.
+    pub fn step_offline_replay(&mut self) -> bool {
+        let budget = self.rx_frame_budget;
+        let Some(replay) = self.offline_replay.as_mut() else {
+            return false;
+        };
+        let (first, start) = (replay.first, replay.start);
+        let mut batch = Vec::new();
+        let mut bytes = 0;
+        while bytes < budget && batch.len() < REPLAY_ENTRIES_PER_FRAME {
+            let Some(next) = replay.entries.pop_front() else {
+                break;
+            };
+            bytes += next.0.data.len();
+            batch.push(next);
+        }
+
+        let since_first = |at: DateTime<Local>| (at - first).to_std().unwrap_or_default();
+        for (entry, at) in batch {
+            match entry.direction {
+                Direction::Received => {
+                    if self.rx_queue.len() >= RX_QUEUE_LIMIT {
+                        self.drain_rx_queue();
+                    }
+                    self.rx_queue.push(&entry.data, at, start + since_first(at), self.char_time());
+                }
+                Direction::Sent => {
+                    self.drain_rx_queue();
+                    self.bytes_sent += entry.data.len();
+                    if self.logging_enabled {
+                        self.log_entry(logging::create_log_entry_at(Direction::Sent, &entry.data, at));
+                    }
+                    let shown = String::from_utf8_lossy(&entry.data).escape_debug().to_string();
+                    self.echo_tx(&format!("TX: {}", shown), at);
+                }
+                Direction::Event => {
+                    self.drain_rx_queue();
+                    self.inject_marker_at(&String::from_utf8_lossy(&entry.data), at);
+                }
+            }
+        }
+        self.drain_rx_queue();
+        if self.offline_replay.as_ref().is_some_and(|replay| !replay.entries.is_empty()) {
+            return true;
+        }
+        self.offline_replay = None;
+        self.clock_watch.reset();
+        self.update_display_buffer();
+        false
+    }
+
+    /// Work off everything queued for the receive path.
+    pub(crate) fn drain_rx_queue(&mut self) {
+        while !self.rx_queue.is_empty() {
+            self.process_rx_frame();
+        }
+    }
+
+    /// Write all of `data` within the write timeout, without any bookkeeping.
+    fn write_uncounted(&self, data: &[u8]) -> Result<usize, WriteError> {
+        let Some(port) = &self.serial_port else {
+            return Err(WriteError::Failed {
+                written: 0,
+                reason: "not connected".to_string(),
+            });
+        };
+        let timeout = Duration::from_millis(self.write_timeout_ms);
+        match port.lock() {
+            Ok(mut port_guard) => write_port(&mut **port_guard, data, timeout),
+            Err(_) => Err(WriteError::Failed {
+                written: 0,
+                reason: "port unavailable".to_string(),
+            }),
+        }
+    }
+
+    /// Write all of `data` within the write timeout. The bytes that actually
+    /// went out are counted as sent, even when the write stops part way.
+    pub fn write_to_port(&mut self, data: &[u8]) -> Result<usize, String> {
+        if self.serial_port.is_none() {
+            return Err("Send failed: not connected".to_string());
+        }
+        let result = self.write_uncounted(data);
+        let written = result.as_ref().map_or_else(WriteError::written, |&count| count);
+        if written > 0 && self.low_latency {
+            self.echo_latency.note_tx(Instant::now());
+        }
+        if written > 0 {
+            self.bytes_sent += written;
+            Metrics::add(&self.metrics.bytes_sent, written);
+            self.note_tx(written);
+        }
+        result.map_err(|e| e.message(data.len()))
+    }
+
+    /// Write `data` and do its bookkeeping, or queue it behind earlier held
+    /// sends while the TX rate limit says wait. Returns the bytes written, or
+    /// `None` when the send was queued.
+    fn transmit(&mut self, data: Vec<u8>, after: AfterTx) -> Result<Option<usize>, String> {
+        let now = Instant::now();
+        if self.tx_pending.is_empty() && self.tx_limiter.wait(now).is_zero() {
+            self.tx_limiter.sent(data.len(), now);
+            let count = self.write_to_port(&data)?;
+            self.finish_tx(&data, after);
+            return Ok(Some(count));
+        }
+        if self.tx_pending.len() >= MAX_PENDING_TX {
+            return Err(format!("TX rate limit: {} sends are already waiting", MAX_PENDING_TX));
+        }
+        self.tx_pending.push_back(PendingTx { data, after });
+        self.tx_delayed += 1;
+        Ok(None)
+    }
+
+    fn finish_tx(&mut self, data: &[u8], after: AfterTx) {
+        match after {
+            AfterTx::Echo(line) => {
+                // Stamped when the write returned, when the bytes actually went out
+                let sent_at = Local::now();
+                if self.logging_enabled {
+                    self.log_entry(logging::create_log_entry(Direction::Sent, data));
+                }
+                self.record_tx(data);
+                self.echo_tx(&line, sent_at);
+            }
+            AfterTx::Keystroke => {
+                if self.logging_enabled {
+                    self.log_entry(logging::create_log_entry(Direction::Sent, data));
+                }
+                self.db_log(DbRecord::chunk(Direction::Sent, data));
+            }
+            AfterTx::Flow(event) => {
+                let text = format!("{} sent (0x{:02X})", event.name(), event.byte());
+                self.break_line();
+                let line = format!("[{}] {}\n", Local::now().format("%H:%M:%S%.3f"), text);
+                self.receive_buffer.extend_from_slice(line.as_bytes());
+                self.update_display_buffer();
+                if self.logging_enabled {
+                    self.log_entry(logging::create_log_entry(Direction::Event, text.as_bytes()));
+                }
+                self.db_log(DbRecord::Event {
+                    at: Local::now(),
+                    text: text.clone(),
+                });
+                self.forward_syslog(Direction::Event, &text);
+            }
+        }
+    }
+
+    /// Write the held sends whose turn has come, oldest first. Returns how
+    /// long until the next one may go. Held sends are dropped once transmitting
+    /// is no longer allowed, e.g. monitor-only mode was switched on meanwhile.
+    pub fn flush_tx_pending(&mut self, now: Instant) -> Option<Duration> {
+        while !self.tx_pending.is_empty() {
+            if let Err(e) = self.check_can_transmit() {
+                let count = self.tx_pending.len();
+                self.drop_tx_pending();
+                self.error_message = Some(format!("{}: {} queued sends dropped", e, count));
+                return None;
+            }
+            let wait = self.tx_limiter.wait(now);
+            if !wait.is_zero() {
+                return Some(wait);
+            }
+            let Some(pending) = self.tx_pending.pop_front() else {
+                break;
+            };
+            self.tx_limiter.sent(pending.data.len(), now);
+            match self.write_to_port(&pending.data) {
+                Ok(_) => self.finish_tx(&pending.data, pending.after),
+                Err(e) => self.error_message = Some(e),
+            }
+        }
+        None
+    }
+
+    /// Forget held sends and the rate limiter's last write, noting anything
+    /// dropped in the terminal.
+    pub fn drop_tx_pending(&mut self) {
+        if !self.tx_pending.is_empty() {
+            let msg = format!(
+                "[{}] {} rate-limited sends dropped\n",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                self.tx_pending.len()
+            );
+            self.receive_buffer.extend_from_slice(msg.as_bytes());
+            self.update_display_buffer();
+            self.tx_pending.clear();
+        }
+        self.tx_limiter.reset();
+    }
+
+    /// Every transmit path checks this first, so monitor-only mode holds even if
+    /// a disabled control is triggered some other way, and nothing is sent into
+    /// the middle of an XMODEM transfer.
+    pub fn check_can_transmit(&self) -> Result<(), String> {
+        if self.monitor_only {
+            Err(MONITOR_ONLY_ERROR.to_string())
+        } else if self.xmodem.is_some() {
+            Err(XMODEM_BUSY_ERROR.to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Hold back a payload matching a send guard rule until it is confirmed.
+    /// Returns false when the send has to wait. Every transmit path composing a
+    /// payload calls this with the bytes before and after encoding.
+    pub fn pass_send_guard(&mut self, payload: &[u8], wire_bytes: &[u8], resend: GuardedSend) -> bool {
+        if self.guard_approved.take().as_deref() == Some(payload) {
+            return true;
+        }
+        match self.send_guard.matching_rule(payload) {
+            Some(rule) => {
+                self.guard_confirmation = Some(GuardConfirmation {
+                    rule: rule.description.clone(),
+                    wire_bytes: wire_bytes.to_vec(),
+                    payload: payload.to_vec(),
+                    resend,
+                });
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Send the payload shown in the guard confirmation.
+    pub fn confirm_guarded_send(&mut self) -> Result<(), String> {
+        let Some(confirmation) = self.guard_confirmation.take() else {
+            return Ok(());
+        };
+        self.guard_approved = Some(confirmation.payload);
+        match confirmation.resend {
+            GuardedSend::SendBox => self.submit_send(true),
+            GuardedSend::Compose => self.send_composed_frame(),
+        }
+    }
+
+    /// True when the input was typed for a connection that has since been replaced.
+    pub fn send_is_stale(&self) -> bool {
+        self.send_generation
+            .is_some_and(|generation| generation != self.connection_generation)
+    }
+
+    /// Send the input box. Text composed before a reconnect is held back until the
+    /// user confirms with `force`.
+    pub fn submit_send(&mut self, force: bool) -> Result<(), String> {
+        if !force && self.send_is_stale() {
+            self.confirm_stale_send = true;
+            return Ok(());
+        }
+        self.confirm_stale_send = false;
+        self.send_generation = Some(self.connection_generation);
+
+        match self.send_mode {
+            SendMode::Ascii => self.send_data(),
+            SendMode::Hex => self.send_hex_input(),
+        }
+    }
+
+    /// The send box text as sent in ASCII mode: escapes read if switched on,
+    /// and the line ending after each line.
+    fn ascii_payload(&self) -> Result<Vec<u8>, String> {
+        if self.parse_escapes {
+            self.line_ending.terminate_escaped(&self.send_buffer)
+        } else {
+            Ok(self.line_ending.terminate(&self.send_buffer))
+        }
+    }
+
+    pub fn send_data(&mut self) -> Result<(), String> {
+        self.check_can_transmit()?;
+
+        let payload = self.ascii_payload()?;
+        let data = self.send_encoding.encode(&payload);
+        let data = rustcom_core::hex::apply_soft_parity(&data, self.tx_parity);
+        if !self.pass_send_guard(&payload, &data, GuardedSend::SendBox) {
+            return Ok(());
+        }
+
+        let text = self.send_buffer.replace('\n', "\\n");
+        let line = match self.send_encoding {
+            SendEncoding::None => format!("TX: {}", text),
+            encoding => format!("TX [{}]: {}", encoding.as_str(), text),
+        };
+        self.transmit(data, AfterTx::Echo(line))?;
+        self.recent_payloads.add(&self.send_buffer, SendMode::Ascii, "");
+        self.send_history.record(SendMode::Ascii, &self.send_buffer);
+        self.send_buffer.clear();
+        Ok(())
+    }
+
+    /// The bytes the STX/ETX compose form sends: its payload and the line
+    /// ending, framed with STX, ETX and the BCC.
+    pub fn composed_frame(&self) -> Vec<u8> {
+        SendEncoding::StxEtx.encode(&self.composed_payload())
+    }
+
+    fn composed_payload(&self) -> Vec<u8> {
+        let mut payload = self.stx_compose.clone().into_bytes();
+        payload.extend_from_slice(self.line_ending.as_bytes());
+        payload
+    }
+
+    /// Send the compose form as one frame, through the send guard and with
+    /// soft parity, leaving the send box and its settings as they are.
+    pub fn send_composed_frame(&mut self) -> Result<(), String> {
+        self.check_can_transmit()?;
+
+        let payload = self.composed_payload();
+        let data = rustcom_core::hex::apply_soft_parity(&self.composed_frame(), self.tx_parity);
+        if !self.pass_send_guard(&payload, &data, GuardedSend::Compose) {
+            return Ok(());
+        }
+
+        let line = format!("TX [{}]: {}", SendEncoding::StxEtx.as_str(), self.stx_compose);
+        self.transmit(data, AfterTx::Echo(line))?;
+        Ok(())
+    }
+
+    pub fn send_hex_input(&mut self) -> Result<(), String> {
+        self.check_can_transmit()?;
+
+        let payload = rustcom_core::hex::parse_hex_input(&self.send_buffer)?;
+        let bytes = self.send_encoding.encode(&payload);
+        let bytes = rustcom_core::hex::apply_soft_parity(&bytes, self.tx_parity);
+        if !self.pass_send_guard(&payload, &bytes, GuardedSend::SendBox) {
+            return Ok(());
+        }
+
+        let hex_str: Vec<String> = payload.iter().map(|b| format!("{:02X}", b)).collect();
+        let line = match self.send_encoding {
+            SendEncoding::None => format!("TX [hex]: {}", hex_str.join(" ")),
+            encoding => format!("TX [hex, {}]: {}", encoding.as_str(), hex_str.join(" ")),
+        };
+        self.transmit(bytes, AfterTx::Echo(line))?;
+        self.recent_payloads.add(&self.send_buffer, SendMode::Hex, "");
+        self.send_history.record(SendMode::Hex, &self.send_buffer);
+        self.send_buffer.clear();
+        Ok(())
+    }
+
+    /// Send macro `index` like the send box would, with its send encoding and
+    /// soft parity. A payload matching a send guard rule is refused, since the
+    /// confirmation resends the send box, not the macro.
+    pub fn send_macro(&mut self, index: usize) -> Result<(), String> {
+        self.send_template(index, &[])
+    }
+
+    /// Send macro `index` with `values` filled in for its placeholders; the
+    /// echo shows the text sent, not the template.
+    pub fn send_template(&mut self, index: usize, values: &[String]) -> Result<(), String> {
+        self.check_can_transmit()?;
+        let Some(m) = self.macros.get(index) else {
+            return Ok(());
+        };
+        let m = &m.filled(values)?;
+        let payload = m.payload(self.line_ending)?;
+        if let Some(rule) = self.send_guard.matching_rule(&payload) {
+            return Err(format!(
+                "Macro \"{}\" blocked by send guard rule \"{}\"",
+                m.label(),
+                rule.description
+            ));
+        }
+        let data = self.send_encoding.encode(&payload);
+        let data = rustcom_core::hex::apply_soft_parity(&data, self.tx_parity);
+
+        let mut tags = Vec::new();
+        let shown = match m.mode {
+            SendMode::Ascii => m.text.clone(),
+            SendMode::Hex => {
+                tags.push("hex");
+                format_payload_hex(&payload)
+            }
+        };
+        if self.send_encoding != SendEncoding::None {
+            tags.push(self.send_encoding.as_str());
+        }
+        let name = format!("macro {}", m.label());
+        tags.push(&name);
+        let line = format!("TX [{}]: {}", tags.join(", "), shown);
+        self.transmit(data, AfterTx::Echo(line))?;
+        Ok(())
+    }
+
+    /// Send macro `index`, or for a template with placeholders, open the
+    /// prompt for their values.
+    pub fn run_macro(&mut self, index: usize) -> Result<(), String> {
+        let Some(m) = self.macros.get(index) else {
+            return Ok(());
+        };
+        match m.parse_template()? {
+            Some(template) if !template.placeholders.is_empty() => {
+                self.check_can_transmit()?;
+                self.template_prompt = Some(TemplatePrompt::new(index, template));
+                Ok(())
+            }
+            _ => self.send_macro(index),
+        }
+    }
+
+    /// Send the send box every repeat interval while repeat is on; it stops
+    /// when the port is not connected. Returns how long until the next send.
+    pub fn run_repeat(&mut self, now: Instant) -> Option<Duration> {
+        if !self.connected {
+            self.repeat.stop();
+            return None;
+        }
+        let interval = Duration::from_millis(self.repeat_interval_ms.clamp(MIN_REPEAT_MS, MAX_REPEAT_MS));
+        if self.repeat.due(interval, now) {
+            let result = self.send_repeat();
+            if let Some(e) = self.repeat.sent(result) {
+                self.error_message = Some(e);
+            }
+        }
+        self.repeat.wait(interval, now)
+    }
+
+    /// Write the send box for one repeat, with its mode, line ending and send
+    /// encoding, leaving the box and its history alone. A payload matching a
+    /// send guard rule is not sent, as nobody is there to confirm each one.
+    fn send_repeat(&mut self) -> Result<(), String> {
+        self.check_can_transmit()?;
+        let mut tags = Vec::new();
+        let (payload, shown) = match self.send_mode {
+            SendMode::Ascii => (self.ascii_payload()?, self.send_buffer.replace('\n', "\\n")),
+            SendMode::Hex => {
+                tags.push("hex");
+                let payload = rustcom_core::hex::parse_hex_input(&self.send_buffer)?;
+                let shown = format_payload_hex(&payload);
+                (payload, shown)
+            }
+        };
+        if payload.is_empty() {
+            return Err("Nothing to repeat: the send box is empty".to_string());
+        }
+        if let Some(rule) = self.send_guard.matching_rule(&payload) {
+            return Err(format!("Repeat blocked by send guard rule \"{}\"", rule.description));
+        }
+        let data = self.send_encoding.encode(&payload);
+        let data = rustcom_core::hex::apply_soft_parity(&data, self.tx_parity);
+
+        if self.send_encoding != SendEncoding::None {
+            tags.push(self.send_encoding.as_str());
+        }
+        tags.push("repeat");
+        let line = format!("TX [{}]: {}", tags.join(", "), shown);
+        self.transmit(data, AfterTx::Echo(line))?;
+        Ok(())
+    }
+
+    /// Send the scheduled entries that are due. The schedule runs while it is
+    /// switched on and the port is connected, starting over on each connection,
+    /// and waits while a file is being sent. Returns how long until the next
+    /// entry is due.
+    pub fn run_schedule(&mut self, now: Instant) -> Option<Duration> {
+        if !(self.schedule_running && self.connected) {
+            self.schedule.iter_mut().for_each(ScheduledSend::stop);
+            return None;
+        }
+        for entry in &mut self.schedule {
+            if !entry.enabled {
+                entry.stop();
+            } else if !entry.is_started() {
+                entry.start(now);
+            }
+        }
+        if self.file_transfer.is_some() || self.xmodem.is_some() {
+            return Some(SCHEDULE_BUSY_RETRY);
+        }
+        for index in schedule::due(&self.schedule, now) {
+            let result = self.send_scheduled(index);
+            self.schedule[index].ran(now, ScheduleRun { at: Local::now(), result });
+        }
+        schedule::next_wait(&self.schedule, now)
+    }
+
+    /// Write one schedule entry: text with the line ending, or hex bytes, with
+    /// soft parity. A payload matching a send guard rule is not sent, as nobody
+    /// is there to confirm it.
+    fn send_scheduled(&mut self, index: usize) -> Result<usize, String> {
+        self.check_can_transmit()?;
+        let entry = &self.schedule[index];
+        let (payload, shown) = match entry.mode {
+            SendMode::Ascii => (self.line_ending.terminate(&entry.text), entry.text.replace('\n', "\\n")),
+            SendMode::Hex => {
+                let payload = rustcom_core::hex::parse_hex_input(&entry.text)?;
+                let hex_str: Vec<String> = payload.iter().map(|b| format!("{:02X}", b)).collect();
+                (payload, hex_str.join(" "))
+            }
+        };
+        if let Some(rule) = self.send_guard.matching_rule(&payload) {
+            return Err(format!("Blocked by send guard rule \"{}\"", rule.description));
+        }
+        let data = rustcom_core::hex::apply_soft_parity(&payload, self.tx_parity);
+
+        let label = match self.schedule[index].mode {
+            SendMode::Ascii => "schedule",
+            SendMode::Hex => "hex, schedule",
+        };
+        let len = data.len();
+        // A send held by the rate limit counts as sent; it goes out in order
+        let count = self.transmit(data, AfterTx::Echo(format!("TX [{}]: {}", label, shown)))?;
+        Ok(count.unwrap_or(len))
+    }
+
+    /// Send the keepalive once a whole interval has passed without TX, while
+    /// it is switched on and the port is connected; the interval starts over on
+    /// each connection. Returns how long until the next one may be due.
+    pub fn run_keepalive(&mut self, now: Instant) -> Option<Duration> {
+        if !(self.keepalive.enabled && self.connected) || self.monitor_only {
+            self.keepalive_timer.stop();
+            return None;
+        }
+        let interval = self.keepalive.interval();
+        let wait = self.keepalive_timer.wait(interval, now);
+        if !wait.is_zero() {
+            return Some(wait);
+        }
+        // Held sends are traffic that is about to go
+        if !self.tx_pending.is_empty() || self.file_transfer.is_some() || self.xmodem.is_some() {
+            return Some(SCHEDULE_BUSY_RETRY);
+        }
+        // The TX rate limit holds back keepalives too, quiet ones included
+        let limited = self.tx_limiter.wait(now);
+        if !limited.is_zero() {
+            return Some(limited);
+        }
+        match self.send_keepalive(now) {
+            Ok(()) => self.keepalives_sent += 1,
+            Err(e) => self.error_message = Some(e),
+        }
+        // A failed keepalive is retried an interval later, not every frame
+        self.keepalive_timer.note_tx(now);
+        Some(interval)
+    }
+
+    /// Write the keepalive payload. A quiet keepalive skips the TX log, echo
+    /// and counters; otherwise it is logged like any other send.
+    fn send_keepalive(&mut self, now: Instant) -> Result<(), String> {
+        let payload = self.keepalive.payload()?;
+        if let Some(rule) = self.send_guard.matching_rule(&payload) {
+            return Err(format!("Keepalive blocked by send guard rule \"{}\"", rule.description));
+        }
+        let data = rustcom_core::hex::apply_soft_parity(&payload, self.tx_parity);
+        if !self.keepalive.quiet {
+            let shown = match self.keepalive.mode {
+                SendMode::Ascii => String::from_utf8_lossy(&payload).escape_debug().to_string(),
+                SendMode::Hex => format_payload_hex(&payload),
+            };
+            self.transmit(data, AfterTx::Echo(format!("TX [keepalive]: {}", shown)))?;
+            return Ok(());
+        }
+        self.tx_limiter.sent(data.len(), now);
+        let result = self.write_uncounted(&data);
+        let written = result.as_ref().map_or_else(WriteError::written, |&count| count);
+        if written > 0 {
+            self.note_tx(written);
+        }
+        result.map(|_| ()).map_err(|e| e.message(data.len()))
+    }
+
+    /// Replace the send box text, as if it had just been typed.
+    fn fill_send_box(&mut self, text: String) {
+        self.send_buffer = text;
+        self.send_generation = Some(self.connection_generation);
+        self.confirm_stale_send = false;
+        self.guard_confirmation = None;
+    }
+
+    /// Put a recent payload into the send box and move it to the top.
+    pub fn insert_recent_payload(&mut self, index: usize) {
+        if let Some(entry) = self.recent_payloads.reuse(index) {
+            self.send_mode = entry.mode;
+            self.fill_send_box(entry.text);
+        }
+    }
+
+    /// Up/Down in the send box: show the older or newer history entry for the
+    /// send mode. False when there is none, so the key can move the cursor.
+    pub fn recall_send_history(&mut self, older: bool) -> bool {
+        let entry = if older {
+            self.send_history.older(self.send_mode, &self.send_buffer)
+        } else {
+            self.send_history.newer(self.send_mode)
+        };
+        match entry {
+            Some(text) => {
+                self.fill_send_box(text);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Put a history entry picked with the mouse into the send box.
+    pub fn insert_history_entry(&mut self, index: usize) {
+        if let Some(text) = self.send_history.entries(self.send_mode).get(index).cloned() {
+            self.send_history.stop_browsing();
+            self.fill_send_box(text);
+        }
+    }
+
+    /// Write one keystroke in per-character mode. Keystrokes are not echoed;
+    /// the device's own echo shows them. A keystroke matching a send guard rule
+    /// is refused, as there is no complete payload to confirm.
+    pub fn send_keystroke(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.check_can_transmit()?;
+        if let Some(rule) = self.send_guard.matching_rule(bytes) {
+            return Err(format!(
+                "Keystroke blocked by send guard rule \"{}\"; use per-line mode to confirm it",
+                rule.description
+            ));
+        }
+        let data = rustcom_core::hex::apply_soft_parity(bytes, self.tx_parity);
+        self.transmit(data, AfterTx::Keystroke)?;
+        Ok(())
+    }
+
+    /// Send the line typed in per-line mode the way the send box would, so
+    /// encoding, the send guard and the reconnect check all apply.
+    pub fn send_typed_line(&mut self) -> Result<(), String> {
+        if !self.connected {
+            return Err("Not connected".to_string());
+        }
+        self.send_buffer = self.line_editor.take_line();
+        self.submit_send(false)
+    }
+
+    pub fn load_file_preview(&mut self) {
+        match FilePreview::load(&self.file_send_path) {
+            Ok(preview) => {
+                if !matches!(preview.firmware, Some(Ok(_))) {
+                    self.firmware_send_mode = FirmwareSendMode::RawText;
+                }
+                self.file_preview = Some(preview);
+                self.error_message = None;
+            }
+            Err(e) => {
+                self.file_preview = None;
+                self.error_message = Some(e);
+            }
+        }
+    }
+
+    pub fn start_file_send(&mut self) -> Result<(), String> {
+        self.check_can_transmit()?;
+
+        let Some(preview) = &self.file_preview else {
+            return Ok(());
+        };
+
+        let transfer = match (&preview.firmware, self.firmware_send_mode) {
+            (Some(Ok(image)), FirmwareSendMode::Binary) => match image.to_binary() {
+                Ok(data) => Ok(FileTransfer::from_bytes(&preview.path, data)),
+                Err(e) => Err(e),
+            },
+            (Some(Err(e)), FirmwareSendMode::Binary) => Err(format!("Cannot decode file: {}", e)),
+            _ => {
+                let conversion = if preview.is_binary {
+                    LineConversion::AsIs
+                } else {
+                    self.file_line_conversion
+                };
+                FileTransfer::from_file(&preview.path, conversion)
+            }
+        };
+
+        match transfer {
+            Ok(transfer) => {
+                let msg = format!(
+                    "[{}] Sending file {} ({} bytes)\n",
+                    Local::now().format("%Y-%m-%d %H:%M:%S"),
+                    transfer.name,
+                    transfer.total
+                );
+                self.receive_buffer.extend_from_slice(msg.as_bytes());
+                self.update_display_buffer();
+                self.file_transfer = Some(transfer);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stop the current file transfer, noting how far it got.
+    pub fn abort_file_send(&mut self, reason: &str) {
+        if let Some(transfer) = self.file_transfer.take() {
+            let msg = format!(
+                "[{}] File send aborted after {} of {} bytes: {}\n",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                transfer.sent,
+                transfer.total,
+                reason
+            );
+            self.receive_buffer.extend_from_slice(msg.as_bytes());
+            self.update_display_buffer();
+        }
+    }
+
+    /// Send the loaded file (its decoded image in binary mode) by XMODEM. The
+    /// transfer starts when the receiver asks for the first block.
+    pub fn start_xmodem_send(&mut self) -> Result<(), String> {
+        self.check_xmodem_start()?;
+        let Some(preview) = &self.file_preview else {
+            return Err("Load a file to send first".to_string());
+        };
+        let data = match (&preview.firmware, self.firmware_send_mode) {
+            (Some(Ok(image)), FirmwareSendMode::Binary) => image.to_binary()?,
+            _ => std::fs::read(&preview.path).map_err(|e| format!("Cannot read {}: {}", preview.path, e))?,
+        };
+        let note = format!(
+            "XMODEM: sending {} ({} bytes), waiting for the receiver",
+            preview.path,
+            data.len()
+        );
+        let sender = xmodem::Sender::new(data, self.xmodem_one_k, Instant::now());
+        self.xmodem = Some(XmodemRun::send(&preview.path, Transfer::Send(sender)));
+        self.note_event(&note);
+        Ok(())
+    }
+
+    /// Receive a file by XMODEM into `xmodem_receive_path`.
+    pub fn start_xmodem_receive(&mut self) -> Result<(), String> {
+        self.check_xmodem_start()?;
+        let run = XmodemRun::receive(self.xmodem_receive_path.trim(), self.xmodem_crc)?;
+        let note = format!("XMODEM: receiving into {}, waiting for the sender", run.name);
+        self.xmodem = Some(run);
+        self.note_event(&note);
+        self.run_xmodem(Instant::now());
+        Ok(())
+    }
+
+    /// A transfer needs the port to itself, sends still queued by the TX rate
+    /// limit included, and the data the reader thread would hand the raw recorder.
+    fn check_xmodem_start(&self) -> Result<(), String> {
+        self.check_can_transmit()?;
+        if !self.connected {
+            Err("Not connected".to_string())
+        } else if self.file_transfer.is_some() {
+            Err("A file is being sent".to_string())
+        } else if !self.tx_pending.is_empty() {
+            Err("Sends are still queued by the TX rate limit".to_string())
+        } else if self.recording.is_some() {
+            Err("Stop the raw recorder first".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Received data goes to a running XMODEM transfer instead of the
+    /// terminal; it is still counted and logged.
+    pub fn feed_xmodem(&mut self, data: &[u8]) {
+        self.bytes_received += data.len();
+        Metrics::add(&self.metrics.bytes_received, data.len());
+        self.note_rx(data.len());
+        if self.logging_enabled {
+            self.log_entry(logging::create_log_entry(Direction::Received, data));
+        }
+        self.db_log(DbRecord::chunk(Direction::Received, data));
+        let Some(run) = self.xmodem.as_mut() else {
+            return;
+        };
+        let reply = run.transfer.feed(data, Instant::now());
+        self.xmodem_step(reply);
+    }
+
+    /// Check a running transfer's timeouts. Returns how long until the next
+    /// check, or `None` when no transfer is running.
+    pub fn run_xmodem(&mut self, now: Instant) -> Option<Duration> {
+        let run = self.xmodem.as_mut()?;
+        if !self.connected || self.monitor_only {
+            let reason = if self.connected { "monitor-only mode is on" } else { "not connected" };
+            run.transfer.cancel();
+            self.end_xmodem(Some(reason));
+            return None;
+        }
+        let out = run.transfer.poll(now);
+        self.xmodem_step(out);
+        self.xmodem.as_ref().map(|_| XMODEM_POLL_INTERVAL)
+    }
+
+    /// Stop the running transfer, telling the other side.
+    pub fn cancel_xmodem(&mut self) {
+        if let Some(run) = self.xmodem.as_mut() {
+            let out = run.transfer.cancel();
+            self.xmodem_step(out);
+        }
+    }
+
+    /// Write what the transfer wants written, save received blocks, and wrap
+    /// up once it has ended.
+    fn xmodem_step(&mut self, out: Vec<u8>) {
+        if !out.is_empty() {
+            if let Err(e) = self.write_to_port(&out) {
+                if let Some(run) = self.xmodem.as_mut() {
+                    run.transfer.cancel();
+                }
+                self.end_xmodem(Some(&e));
+                return;
+            }
+            if self.logging_enabled {
+                self.log_entry(logging::create_log_entry(Direction::Sent, &out));
+            }
+            self.db_log(DbRecord::chunk(Direction::Sent, &out));
+        }
+        let Some(run) = self.xmodem.as_mut() else {
+            return;
+        };
+        if let Err(e) = run.save_received() {
+            let out = run.transfer.cancel();
+            let _ = self.write_to_port(&out);
+            self.end_xmodem(Some(&e));
+            return;
+        }
+        if *run.transfer.status() != xmodem::Status::Running {
+            self.end_xmodem(None);
+        }
+    }
+
+    /// Note how the transfer went in the terminal and the XMODEM group. A
+    /// `reason` ends it even if the protocol had not finished.
+    fn end_xmodem(&mut self, reason: Option<&str>) {
+        let Some(run) = self.xmodem.take() else {
+            return;
+        };
+        let progress = run.transfer.progress();
+        let direction = if run.is_receive() { "receive" } else { "send" };
+        let outcome = match (reason, run.transfer.status()) {
+            (Some(reason), _) => format!("failed: {}", reason),
+            (None, xmodem::Status::Failed(reason)) => format!("failed: {}", reason),
+            _ => "done".to_string(),
+        };
+        let checksum = progress.checksum.map_or("", |checksum| checksum.as_str());
+        let result = format!(
+            "XMODEM {} {}: {}, {} blocks, {} bytes, {} retries, {} {:.1} s",
+            direction,
+            outcome,
+            run.name,
+            progress.blocks,
+            progress.bytes,
+            progress.retries,
+            checksum,
+            run.started.elapsed().as_secs_f32()
+        );
+        self.note_event(&result);
+        self.xmodem_result = Some(result);
+    }
+
+    /// Listen for TCP bridge clients; only while connected, as the bridge
+    /// stops with the port.
+    pub fn start_tcp_bridge(&mut self) {
+        self.tcp_bridge_server = None;
+        if !self.connected {
+            self.error_message = Some("Connect before starting the TCP bridge".to_string());
+            return;
+        }
+        match TcpBridge::start(&self.tcp_bridge) {
+            Ok(bridge) => {
+                let scope = if self.tcp_bridge.expose_lan { "all interfaces" } else { "localhost" };
+                self.note_event(&format!("TCP bridge listening on port {} ({})", bridge.port(), scope));
+                self.tcp_bridge_server = Some(bridge);
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// Stop listening and disconnect every bridge client.
+    pub fn stop_tcp_bridge(&mut self) {
+        let Some(bridge) = self.tcp_bridge_server.take() else {
+            return;
+        };
+        let clients = bridge.client_count();
+        drop(bridge);
+        self.note_event(&format!("TCP bridge stopped, {} client(s) disconnected", clients));
+    }
+
+    /// Note bridge clients coming and going and write what they send to the
+    /// port. Returns false when the bridge is not running.
+    pub fn run_tcp_bridge(&mut self) -> bool {
+        let Some(bridge) = &self.tcp_bridge_server else {
+            return false;
+        };
+        bridge.set_read_only(self.tcp_bridge.read_only);
+        for event in bridge.poll() {
+            match event {
+                BridgeEvent::Connected(peer) => self.note_event(&format!("TCP bridge: {} connected", peer)),
+                BridgeEvent::Disconnected(peer) => self.note_event(&format!("TCP bridge: {} disconnected", peer)),
+                BridgeEvent::Data(peer, data) => {
+                    if let Err(e) = self.send_bridged(peer, &data) {
+                        self.error_message =
+                            Some(format!("TCP bridge: {} bytes from {} not sent: {}", data.len(), peer, e));
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Write bytes from a bridge client as they are, apart from soft parity,
+    /// and show them as TX. As with the schedule, nobody is there to confirm a
+    /// payload matching a send guard rule, so it is not sent.
+    fn send_bridged(&mut self, peer: SocketAddr, payload: &[u8]) -> Result<(), String> {
+        self.check_can_transmit()?;
+        if let Some(rule) = self.send_guard.matching_rule(payload) {
+            return Err(format!("Blocked by send guard rule \"{}\"", rule.description));
+        }
+        let data = rustcom_core::hex::apply_soft_parity(payload, self.tx_parity);
+        let shown = String::from_utf8_lossy(payload).escape_debug().to_string();
+        self.transmit(data, AfterTx::Echo(format!("TX [tcp {}]: {}", peer, shown)))?;
+        Ok(())
+    }
+
+    /// A timestamped line in the terminal and the log, e.g. for an XMODEM
+    /// transfer, whose traffic the terminal does not show, or a bridge client.
+    pub(crate) fn note_event(&mut self, text: &str) {
+        self.break_line();
+        let line = format!("[{}] {}\n", Local::now().format("%Y-%m-%d %H:%M:%S"), text);
+        self.receive_buffer.extend_from_slice(line.as_bytes());
+        self.update_display_buffer();
+        if self.logging_enabled {
+            self.log_entry(logging::create_log_entry(Direction::Event, text.as_bytes()));
+        }
+    }
+
+    /// Append a TX echo line to the terminal. `sent_at` is the instant the
+    /// write returned, so the stamp reflects when the bytes actually went out.
+    fn echo_tx(&mut self, line: &str, sent_at: DateTime<Local>) {
+        let msg = if self.timestamp_tx {
+            format!("[{}] {}\n", sent_at.format("%Y-%m-%d %H:%M:%S%.3f"), line)
+        } else {
+            format!("{}\n", line)
+        };
+        self.receive_buffer.extend_from_slice(msg.as_bytes());
+        self.update_display_buffer();
+    }
+
+    pub fn set_dtr(&mut self, state: bool) -> Result<(), String> {
+        self.check_can_transmit()?;
+        if let Some(port) = &self.serial_port {
+            if let Ok(mut port_guard) = port.lock() {
+                let _ = port_guard.write_data_terminal_ready(state);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_rts(&mut self, state: bool) -> Result<(), String> {
+        self.check_can_transmit()?;
+        if let Some(port) = &self.serial_port {
+            if let Ok(mut port_guard) = port.lock() {
+                let _ = port_guard.write_request_to_send(state);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a lone XON or XOFF, e.g. to unstick a device waiting on software
+    /// flow control. The byte goes out as is, whatever the send mode, line
+    /// ending and encoding, and is logged as an event rather than as TX data.
+    pub fn inject_flow_byte(&mut self, event: FlowEvent) -> Result<(), String> {
+        self.check_can_transmit()?;
+        let data = rustcom_core::hex::apply_soft_parity(&[event.byte()], self.tx_parity);
+        self.transmit(data, AfterTx::Flow(event))?;
+        Ok(())
+    }
+
+    /// Re-read CTS for the held-off readout while hardware flow control is on,
+    /// at most every `CTS_POLL_INTERVAL`. Stays `None` if the driver cannot report it.
+    pub fn poll_cts(&mut self, now: Instant) {
+        if !self.connected || self.flow_control != FlowControl::Hardware {
+            self.cts_state = None;
+            return;
+        }
+        if self.last_cts_poll.is_some_and(|last| now.duration_since(last) < CTS_POLL_INTERVAL) {
+            return;
+        }
+        self.last_cts_poll = Some(now);
+        self.cts_state = self
+            .serial_port
+            .as_ref()
+            .and_then(|port| port.lock().ok()?.read_clear_to_send().ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::Action;
+    use crate::app::{ViewMode, MAX_TERMINAL_FONT_SIZE, MIN_TERMINAL_FONT_SIZE};
+    use rustcom_core::hex;
+    use rustcom_core::keepalive::KeepaliveConfig;
+    use rustcom_core::macros::Macro;
+    use rustcom_core::mock_port::MockPort;
+    use rustcom_core::protocol::framing_rule::TrailingChecksum;
+    use rustcom_core::protocol::{self, CustomFraming, DecodedView, ProtocolMode};
+    use rustcom_core::rate_alarm::RateAlarmConfig;
+    use rustcom_core::send_guard::{GuardKind, GuardRule};
+    use rustcom_core::split_log::SplitLog;
+    use rustcom_core::text_encoding::{EncodingDetection, TextEncoding};
+
+    fn connected_app(monitor_only: bool) -> (ComAnalyzerApp, MockPort) {
+        let port = MockPort::default();
+        let mut app = ComAnalyzerApp {
+            serial_port: Some(Arc::new(Mutex::new(Box::new(port.clone()) as Box<dyn SerialPort>))),
+            connected: true,
+            monitor_only,
+            ..Default::default()
+        };
+        app.file_preview = Some(FilePreview {
+            path: "firmware.bin".to_string(),
+            size: 4,
+            firmware: None,
+            is_binary: true,
+        });
+        (app, port)
+    }
+
+    /// Poll like the UI does until `done`, for ports read on the reader thread.
+    fn poll_until(app: &mut ComAnalyzerApp, done: impl Fn(&ComAnalyzerApp) -> bool) {
+        let ctx = eframe::egui::Context::default();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(app) {
+            assert!(Instant::now() < deadline, "timed out polling the port");
+            app.poll_serial(&ctx);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Wait out a network connect on its worker and take its result.
+    fn finish_connecting(app: &mut ComAnalyzerApp) {
+        let ctx = eframe::egui::Context::default();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.net_connect.is_some() {
+            assert!(Instant::now() < deadline, "timed out connecting");
+            app.poll_net_connect(&ctx);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn baud_ladder_steps_through_the_standard_rates() {
+        assert_eq!(adjacent_baud(9600, true), Some(19200));
+        assert_eq!(adjacent_baud(9600, false), Some(4800));
+        assert_eq!(adjacent_baud(100_000, true), Some(115_200));
+        assert_eq!(adjacent_baud(100_000, false), Some(57600));
+        assert_eq!(adjacent_baud(921_600, true), None);
+        assert_eq!(adjacent_baud(300, false), None);
+
+        let mut app = ComAnalyzerApp {
+            baud_rate: "921600".to_string(),
+            ..Default::default()
+        };
+        app.step_baud(true);
+        assert_eq!(app.baud_rate, "921600");
+        assert!(app.error_message.as_deref().unwrap().contains("highest"));
+        app.step_baud(false);
+        assert_eq!(app.baud_rate, "460800");
+        assert!(app.error_message.is_none());
+
+        // Connected: the old port is closed and the step is logged
+        let (mut app, _port) = connected_app(false);
+        app.baud_rate = "9600".to_string();
+        app.repeat.start();
+        app.step_baud(true);
+        assert_eq!(app.baud_rate, "19200");
+        assert!(!app.connected);
+        assert!(app.serial_port.is_none());
+        assert!(!app.repeat.is_running());
+        let log = app.capture_text();
+        assert!(log.contains("Baud ladder: 9600 -> 19200"));
+        assert!(log.contains("] Disconnected"));
+        assert!(log.contains("could not reopen at 19200 baud"));
+    }
+
+    #[test]
+    fn forwards_rx_lines_and_markers_to_syslog() {
+        let collector = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        collector.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut app = ComAnalyzerApp::default();
+        app.syslog.host = "127.0.0.1".to_string();
+        app.syslog.port = collector.local_addr().unwrap().port();
+        app.start_syslog();
+        assert!(app.syslog.enabled);
+
+        app.feed_syslog(b"boot ok\r\nI2C ERR");
+        app.feed_syslog(b"OR 5\n");
+        app.inject_marker("RELAY ON");
+
+        let mut received = Vec::new();
+        let mut buf = [0; 512];
+        for _ in 0..3 {
+            let len = collector.recv(&mut buf).unwrap();
+            received.push(String::from_utf8_lossy(&buf[..len]).into_owned());
+        }
+        assert!(received[0].starts_with("<134>1 ") && received[0].ends_with(" RX - boot ok"));
+        assert!(received[1].starts_with("<131>1 ") && received[1].ends_with(" RX - I2C ERROR 5"));
+        assert!(received[2].starts_with("<134>1 ") && received[2].ends_with(" EV - RELAY ON"));
+
+        app.stop_syslog();
+        assert!(!app.capture_slot().syslog.enabled);
+    }
+
+    #[test]
+    fn idle_flush_completes_an_unterminated_line_once() {
+        let collector = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        collector.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut app = ComAnalyzerApp {
+            variables_enabled: true,
+            ..Default::default()
+        };
+        app.syslog.host = "127.0.0.1".to_string();
+        app.syslog.port = collector.local_addr().unwrap().port();
+        app.start_syslog();
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), std::time::Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+        let idle = Duration::from_millis(app.idle_flush_ms);
+
+        // Quiet for less than the idle time: still pending
+        feed(&mut app, b"vbat=3.7");
+        app.poll_idle_flush(app.last_rx_at.unwrap() + idle / 2);
+        assert!(app.variables.get("vbat").is_none());
+        app.poll_idle_flush(app.last_rx_at.unwrap() + idle);
+        assert_eq!(app.variables.numeric("vbat"), Some(3.7));
+        assert!(app.last_rx_at.is_none());
+
+        // The late terminator does not log the line again
+        feed(&mut app, b"\r\n");
+        // A continuation after a flush is a line of its own
+        feed(&mut app, b"READY>");
+        app.poll_idle_flush(app.last_rx_at.unwrap() + idle);
+        feed(&mut app, b"temp=41\n");
+        assert_eq!(app.variables.numeric("temp"), Some(41.0));
+
+        let mut received = Vec::new();
+        let mut buf = [0; 512];
+        for _ in 0..3 {
+            let len = collector.recv(&mut buf).unwrap();
+            received.push(String::from_utf8_lossy(&buf[..len]).into_owned());
+        }
+        assert!(received[0].ends_with(" RX - vbat=3.7 [no terminator]"));
+        assert!(received[1].ends_with(" RX - READY> [no terminator]"));
+        assert!(received[2].ends_with(" RX - temp=41"));
+    }
+
+    #[test]
+    fn filtered_out_data_is_kept_for_review() {
+        let mut app = ComAnalyzerApp {
+            filter_enabled: true,
+            filter_rules: vec![rustcom_core::filter::FilterRule::new("ERR")],
+            ..Default::default()
+        };
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), std::time::Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+        feed(&mut app, b"DBG tick\n");
+        assert!(app.rejected.is_empty(), "off by default");
+
+        app.keep_rejected = true;
+        feed(&mut app, b"DBG tock\n");
+        feed(&mut app, b"ERR overheat\n");
+        let held: Vec<&[u8]> = app.rejected.chunks().map(|c| c.data.as_slice()).collect();
+        assert_eq!(held, [b"DBG tock\n".as_slice()]);
+        assert!(app.capture_text().contains("ERR overheat"));
+
+        app.clear_terminal();
+        assert!(app.rejected.is_empty());
+    }
+
+    #[test]
+    fn filter_matches_lines_split_across_reads() {
+        let mut app = ComAnalyzerApp {
+            filter_enabled: true,
+            filter_rules: vec![rustcom_core::filter::FilterRule::new("ERROR")],
+            keep_rejected: true,
+            ..Default::default()
+        };
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), std::time::Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+        feed(&mut app, b"INFO boot\r\nERROR: sensor");
+        assert!(app.receive_buffer.is_empty(), "the partial line waits for its end");
+        feed(&mut app, b" timeout\r\nDBG tick\n");
+        assert_eq!(app.capture_text(), "ERROR: sensor timeout\r\n");
+        let held: Vec<&[u8]> = app.rejected.chunks().map(|c| c.data.as_slice()).collect();
+        assert_eq!(held, [b"INFO boot\r\n".as_slice(), b"DBG tick\n"]);
+
+        // Turning the filter off passes a held partial line on unfiltered
+        feed(&mut app, b"DBG to");
+        app.filter_enabled = false;
+        feed(&mut app, b"ck\n");
+        assert!(app.capture_text().ends_with("DBG tock\n"));
+    }
+
+    #[test]
+    fn filter_lets_binary_through_in_pieces_and_times_out_partial_lines() {
+        let mut app = ComAnalyzerApp {
+            filter_enabled: true,
+            filter_rules: vec![rustcom_core::filter::FilterRule::new("ERROR")],
+            keep_rejected: true,
+            ..Default::default()
+        };
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), std::time::Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+        // No newline at all: matched a chunk at a time once the line limit is reached
+        let mut blob = b"ERROR".to_vec();
+        blob.extend(std::iter::repeat_n(0u8, 600));
+        feed(&mut app, &blob);
+        assert!(app.receive_buffer.is_empty());
+        feed(&mut app, &[0u8; 600]);
+        assert_eq!(app.receive_buffer.len(), 1205);
+        assert!(app.filter_lines.is_empty());
+        feed(&mut app, &[0u8; 2000]);
+        assert_eq!(app.rejected.len(), 2000);
+
+        // An unterminated prompt is filtered once the hold time runs out
+        feed(&mut app, b"ERROR> ");
+        let hold = Duration::from_millis(app.filter_hold_ms);
+        let held_since = app.filter_line_at.unwrap();
+        app.poll_filter_hold(held_since + hold / 2);
+        assert_eq!(app.receive_buffer.len(), 1205);
+        app.poll_filter_hold(held_since + hold);
+        assert!(app.capture_text().ends_with("ERROR> "));
+        assert!(app.filter_line_at.is_none());
+    }
+
+    #[test]
+    fn highlights_are_matched_on_the_bytes_of_each_rendered_line() {
+        use rustcom_core::filter::{hex_pattern, FilterRule};
+        let (red, blue) = (Some([255, 0, 0]), Some([0, 0, 255]));
+        let mut error = FilterRule::new("ERR");
+        error.highlight = red;
+        let mut binary = FilterRule::new(&hex_pattern(&[0xA5, 0x5A]));
+        binary.highlight = blue;
+        let mut app = ComAnalyzerApp {
+            filter_rules: vec![error, binary],
+            ..Default::default()
+        };
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), std::time::Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+        feed(&mut app, b"ok\nERR 5\n");
+        feed(&mut app, &[0xA5, 0x5A, b'\n']);
+        feed(&mut app, b"ERR 6\n");
+        assert_eq!(app.display_highlights, [None, red, blue, red]);
+        let appended = app.display_highlights.clone();
+        app.update_display_buffer();
+        assert_eq!(app.display_highlights, appended);
+
+        // Hex rows are matched on their bytes, not on the hex text shown
+        app.view_mode = ViewMode::Hex;
+        app.update_display_buffer();
+        assert_eq!(app.display_highlights, [red, None]);
+        app.filter_rules[0].enabled = false;
+        assert_ne!(app.highlight_rules(), app.display_highlight_rules);
+        app.update_display_buffer();
+        assert_eq!(app.display_highlights, [blue, None]);
+    }
+
+    #[test]
+    fn received_data_is_appended_to_the_display() {
+        let mut app = ComAnalyzerApp::default();
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), std::time::Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+        let data = "T=21.5 °C\r\nstatus → ok\n\x1b[1mbold\x1b[0m\n".repeat(8).into_bytes();
+        for view_mode in [ViewMode::Ascii, ViewMode::Hex] {
+            app.view_mode = view_mode;
+            app.strip_ansi = view_mode == ViewMode::Ascii;
+            app.clear_terminal();
+            for chunk in data.chunks(5) {
+                feed(&mut app, chunk);
+                assert!(!app.receive_buffer_display.contains('\u{FFFD}'));
+                let appended = (app.receive_buffer_display.clone(), app.display_lines.clone());
+                app.update_display_buffer();
+                assert_eq!(appended, (app.receive_buffer_display.clone(), app.display_lines.clone()));
+            }
+            assert!(app.display_cursor.is_some());
+        }
+        assert!(app.receive_buffer_display.starts_with("0000  54 3D 32 31"));
+
+        // Switching views or stripping ANSI codes renders everything again
+        app.view_mode = ViewMode::Ascii;
+        app.strip_ansi = true;
+        feed(&mut app, b"x");
+        assert!(app.receive_buffer_display.starts_with("T=21.5 °C\r\nstatus → ok\nbold\n"));
+        assert!(app.receive_buffer_display.ends_with("bold\nx"));
+    }
+
+    #[test]
+    fn extracted_values_become_metric_gauges() {
+        let mut app = ComAnalyzerApp {
+            variables_enabled: true,
+            ..Default::default()
+        };
+        app.attach_port(Box::new(MockPort::default()), "COM3", 115_200);
+        app.pinned_values = vec![rustcom_core::pinned::PinnedValue::new("Temp", r"T=([\d.]+)")];
+        app.feed_pinned(b"T=21.5 C\n");
+        app.feed_variables(b"vbat=3.72V state=RUN\n");
+
+        let text = app.metrics.render();
+        assert!(text.contains("\nrustcom_connected 1\n"));
+        assert!(text.contains("rustcom_pinned_value{name=\"Temp\"} 21.5\n"));
+        assert!(text.contains("rustcom_variable{key=\"vbat\"} 3.72\n"));
+        assert!(!text.contains("key=\"state\""));
+
+        app.disconnect();
+        assert!(app.metrics.render().contains("\nrustcom_connected 0\n"));
+    }
+
+    #[test]
+    fn pinned_byte_ranges_index_binary_records_as_received() {
+        use rustcom_core::pinned::{PinSource, PinnedValue};
+        let mut volts = PinnedValue::new("Volts", "");
+        volts.source = PinSource::Bytes;
+        volts.offset = 3;
+        volts.length = 4;
+        let mut app = ComAnalyzerApp {
+            pinned_values: vec![volts, PinnedValue::new("Mode", r"MODE=(\w+)")],
+            ..Default::default()
+        };
+        app.feed_pinned(b"\xA5\xFF\x80");
+        app.feed_pinned(b"12.5 MODE=RUN\r\n");
+        assert_eq!(app.pinned_values[0].latest.as_deref(), Some("12.5"));
+        assert_eq!(app.pinned_values[1].latest.as_deref(), Some("RUN"));
+    }
+
+    #[test]
+    fn terminal_zoom_stays_within_limits() {
+        let mut app = ComAnalyzerApp::default();
+        assert_eq!(app.zoom_terminal(1.5), Some(1.5));
+        assert_eq!(app.terminal_font_size, 18.0);
+        assert_eq!(app.zoom_terminal(10.0), Some(2.0));
+        assert_eq!(app.terminal_font_size, MAX_TERMINAL_FONT_SIZE);
+        assert_eq!(app.zoom_terminal(1.1), None);
+        assert_eq!(app.reset_terminal_zoom(), Some(1.0 / 3.0));
+        assert_eq!(app.zoom_terminal(0.01), Some(0.5));
+        assert_eq!(app.terminal_font_size, MIN_TERMINAL_FONT_SIZE);
+    }
+
+    #[test]
+    fn hex_selection_becomes_an_excerpt_at_its_capture_offset() {
+        let mut app = ComAnalyzerApp {
+            receive_buffer: (0u8..40).collect(),
+            view_mode: ViewMode::Hex,
+            ..Default::default()
+        };
+        app.evicted.bytes = 0x100;
+        // "12 13 14" in the second row of the dump
+        let row = hex::HEX_DUMP_LINE_WIDTH;
+        let excerpt = app.selection_excerpt(row + 12..row + 20).unwrap();
+        assert_eq!(excerpt.offset, 0x112);
+        assert_eq!(excerpt.bytes, [0x12, 0x13, 0x14]);
+        assert!(app.selection_excerpt(0..4).is_err());
+
+        app.view_mode = ViewMode::Ascii;
+        assert!(app.selection_excerpt(row + 12..row + 20).is_err());
+
+        // A saved excerpt without bytes is refused, as the dialog needs one
+        let path = std::env::temp_dir().join(format!("rustcom_empty_excerpt_{}.json", std::process::id()));
+        let empty = rustcom_core::excerpt::Excerpt::new(0, Vec::new());
+        std::fs::write(&path, empty.to_json().unwrap()).unwrap();
+        app.doc_excerpt_path = path.display().to_string();
+        app.open_doc_excerpt();
+        assert!(app.doc_excerpt.is_none());
+        assert!(app.error_message.as_deref().unwrap().ends_with("holds an excerpt with no bytes"));
+        std::fs::write(&path, excerpt.to_json().unwrap()).unwrap();
+        app.open_doc_excerpt();
+        assert_eq!(app.doc_excerpt.as_ref().unwrap().bytes, [0x12, 0x13, 0x14]);
+        let _ = std::fs::remove_file(path);
+
+        // Past 64 KiB the offsets have five digits and the rows are wider
+        app.receive_buffer = (0..0x18000u32).map(|i| (i % 251) as u8).collect();
+        app.view_mode = ViewMode::Both;
+        let row = crate::app::BOTH_VIEW_HEX_HEADER.len() + hex::hex_dump_len(0x11000);
+        let excerpt = app.selection_excerpt(row + 7..row + 12).unwrap();
+        assert_eq!(excerpt.offset, 0x100 + 0x11000);
+        assert_eq!(excerpt.bytes, app.receive_buffer[0x11000..0x11002]);
+        let end = crate::app::BOTH_VIEW_HEX_HEADER.len() + hex::hex_dump_len(0x18000);
+        assert_eq!(app.selection_excerpt(end - 17..end).unwrap().bytes, app.receive_buffer[0x17FF0..]);
+    }
+
+    #[test]
+    fn selected_hex_is_read_as_a_value() {
+        let mut app = ComAnalyzerApp {
+            receive_buffer: vec![0x00, 0x41, 0xF8, 0x00, 0x00, 0x55],
+            receive_buffer_display: "T 0x41F8 0000 ok".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(app.selection_value_bytes(2..13), Some(vec![0x41, 0xF8, 0x00, 0x00]));
+        assert_eq!(app.selection_value_bytes(14..16), None);
+
+        // In the dump the bytes themselves are used; "41 F8 00 00" starts at column 9
+        app.view_mode = ViewMode::Hex;
+        assert_eq!(app.selection_value_bytes(9..20), Some(vec![0x41, 0xF8, 0x00, 0x00]));
+        app.receive_buffer = vec![0; 64];
+        assert_eq!(app.selection_value_bytes(6..hex::HEX_DUMP_LINE_WIDTH), None);
+
+        // Rows past 64 KiB have a five-digit offset
+        app.receive_buffer = vec![0; 0x11010];
+        app.receive_buffer[0x11004..0x11008].copy_from_slice(&[0x41, 0xF8, 0x00, 0x00]);
+        let row = hex::hex_dump_len(0x11000);
+        assert_eq!(app.selection_value_bytes(row + 19..row + 30), Some(vec![0x41, 0xF8, 0x00, 0x00]));
+    }
+
+    fn usb_adapter() -> Vec<SerialPortInfo> {
+        vec![SerialPortInfo {
+            port_name: "/dev/ttyUSB1".to_string(),
+            port_type: serialport::SerialPortType::UsbPort(serialport::UsbPortInfo {
+                vid: 0x0403,
+                pid: 0x6001,
+                serial_number: Some("A10K".to_string()),
+                manufacturer: None,
+                product: None,
+            }),
+        }]
+    }
+
+    fn failover_app(open: OpenPort) -> ComAnalyzerApp {
+        let mut app = ComAnalyzerApp {
+            port_opener: PortOpener { list: usb_adapter, open },
+            ..Default::default()
+        };
+        app.failover.enabled = true;
+        for candidate in ["COM3", "usb:0403:6001", "COM9"] {
+            app.failover_input = candidate.to_string();
+            app.add_failover_candidate().unwrap();
+        }
+        app
+    }
+
+    #[test]
+    fn failover_uses_the_second_candidate_when_the_first_fails() {
+        let mut app = failover_app(|name, _| match name {
+            "COM3" => Err("Access denied".to_string()),
+            _ => Ok(Box::new(MockPort::default())),
+        });
+        app.connect();
+        assert!(app.connected);
+        assert_eq!(app.selected_port.as_deref(), Some("/dev/ttyUSB1"));
+        let terminal = String::from_utf8_lossy(&app.receive_buffer);
+        assert!(terminal.contains(
+            "Failover: using /dev/ttyUSB1 (candidate 2 of 3, usb:0403:6001); skipped COM3: Access denied\n"
+        ));
+        assert!(terminal.contains("Connected to /dev/ttyUSB1"));
+    }
+
+    #[test]
+    fn failover_reports_every_candidate_when_none_opens() {
+        let mut app = failover_app(|_, _| Err("No such file or directory".to_string()));
+        app.port_opener.list = Vec::new;
+        app.retry_initial_connect = true;
+        app.connect_manually();
+        assert!(!app.connected);
+        assert!(app.reconnecting);
+        let reasons = "COM3: No such file or directory; usb:0403:6001: no such USB device present; COM9: No such file or directory";
+        assert!(app.error_message.as_deref().unwrap().ends_with(reasons));
+        let terminal = String::from_utf8_lossy(&app.receive_buffer);
+        assert!(terminal.contains(&format!("Failover: no candidate opened ({})\n", reasons)));
+        assert!(terminal.contains("Could not open any failover port"));
+        assert!(app.add_failover_candidate().is_err());
+    }
+
+    #[test]
+    fn watches_mark_alarms_in_the_terminal() {
+        let mut app = ComAnalyzerApp {
+            variables_enabled: true,
+            watches: vec![rustcom_core::watch::Watch::new("vbat < 3.3")],
+            ..Default::default()
+        };
+        app.feed_variables(b"vbat=3.41 temp=40\n");
+        assert_eq!(app.check_watches(), None);
+        assert!(!app.watches[0].alarm);
+
+        app.feed_variables(b"vbat=3.2");
+        app.check_watches();
+        assert!(!app.watches[0].alarm, "line not complete yet");
+        app.feed_variables(b"5\r\n");
+        app.check_watches();
+        assert!(app.watches[0].alarm);
+        assert!(app.capture_text().contains("WATCH vbat < 3.3"));
+        assert_eq!(app.error_message.as_deref(), Some("Watch triggered: vbat < 3.3"));
+    }
+
+    #[test]
+    fn monitor_only_blocks_every_transmit_path() {
+        let (mut app, port) = connected_app(true);
+        let blocked = Err(MONITOR_ONLY_ERROR.to_string());
+
+        app.send_buffer = "hello".to_string();
+        assert_eq!(app.send_data(), blocked);
+        app.send_buffer = "AA BB".to_string();
+        assert_eq!(app.send_hex_input(), blocked);
+        assert_eq!(app.start_file_send(), blocked);
+        assert_eq!(app.set_dtr(true), blocked);
+        assert_eq!(app.set_rts(true), blocked);
+        assert_eq!(app.send_keystroke(b"x"), blocked);
+
+        // A transfer already running when monitor mode was switched on is aborted
+        app.file_transfer = Some(FileTransfer::from_bytes("queued", vec![1, 2, 3]));
+        app.poll_file_send(&eframe::egui::Context::default());
+        assert!(app.file_transfer.is_none());
+
+        assert!(port.activity().is_empty());
+        assert_eq!(app.bytes_sent, 0);
+        assert!(app.log_entries.is_empty());
+    }
+
+    #[test]
+    fn text_typed_before_a_reconnect_needs_confirmation() {
+        let (mut app, port) = connected_app(false);
+        app.connection_generation = 1;
+        app.send_buffer = "reboot".to_string();
+        app.send_generation = Some(1);
+
+        // Send failed, then the link dropped and came back
+        app.connection_generation = 2;
+
+        assert_eq!(app.submit_send(false), Ok(()));
+        assert!(app.confirm_stale_send);
+        assert!(port.activity().is_empty());
+        assert_eq!(app.send_buffer, "reboot");
+
+        assert_eq!(app.submit_send(true), Ok(()));
+        assert!(!app.confirm_stale_send);
+        assert_eq!(port.activity().len(), 1);
+        assert!(app.send_buffer.is_empty());
+        assert!(!app.send_is_stale());
+    }
+
+    #[test]
+    fn compose_form_sends_an_stx_etx_frame() {
+        let (mut app, port) = connected_app(false);
+        app.line_ending = LineEnding::None;
+        app.stx_compose = "R01".to_string();
+        assert_eq!(app.composed_frame(), [0x02, b'R', b'0', b'1', 0x03, 0x50]);
+
+        app.send_buffer = "typed".to_string();
+        assert_eq!(app.send_composed_frame(), Ok(()));
+        assert_eq!(port.activity(), ["write [02, 52, 30, 31, 03, 50]"]);
+        assert!(app.capture_text().contains("TX [STX/ETX]: R01"));
+        assert_eq!(app.send_buffer, "typed");
+        assert_eq!((app.send_mode, app.send_encoding), (SendMode::Ascii, SendEncoding::None));
+
+        // A guarded frame waits, and confirming sends the frame, not the send box
+        app.send_guard.rules.push(GuardRule::new("Reset", GuardKind::Regex, "^R0"));
+        assert_eq!(app.send_composed_frame(), Ok(()));
+        assert_eq!(port.activity().len(), 1);
+        assert_eq!(app.guard_confirmation.as_ref().unwrap().rule, "Reset");
+        assert_eq!(app.confirm_guarded_send(), Ok(()));
+        assert_eq!(port.activity()[1], "write [02, 52, 30, 31, 03, 50]");
+        assert_eq!(app.send_buffer, "typed");
+    }
+
+    #[test]
+    fn resyncs_are_counted_in_the_statistics() {
+        let (mut app, _port) = connected_app(true);
+        app.protocol_mode = ProtocolMode::Custom;
+        app.custom_framing = CustomFraming::StxEtx;
+        app.update_decoder();
+        let mut wire = vec![0x02, b'x', 0x03, 0x00, 0xFF, 0x13];
+        wire.extend_from_slice(&protocol::stx_etx::encode(b"R01"));
+        app.rx_queue.push(&wire, Local::now(), Instant::now(), Duration::ZERO);
+        app.process_rx_frame();
+
+        let summaries: Vec<&str> = app.decoded_frames.iter().map(|e| e.frame.summary.as_str()).collect();
+        assert_eq!(summaries, ["1 bytes", "resync: skipped 2 bytes at offset 4", "STX/ETX 3 bytes"]);
+        let text = app.metrics.render();
+        assert!(text.contains("\nrustcom_frames_decoded_total 2\n"));
+        assert!(text.contains("\nrustcom_resyncs_total 1\n"));
+        assert!(text.contains("\nrustcom_resync_skipped_bytes_total 2\n"));
+        let snapshot = app.stats_snapshot();
+        let skipped = snapshot.metrics.iter().find(|m| m.name == "Resync skipped bytes").unwrap();
+        assert_eq!(skipped.value, 2.0);
+    }
+
+    #[test]
+    fn framing_rule_edits_apply_to_new_data_while_connected() {
+        let (mut app, _port) = connected_app(true);
+        app.protocol_mode = ProtocolMode::Custom;
+        app.custom_framing = CustomFraming::Rule;
+        app.update_decoder();
+        app.set_decoded_view(DecodedView::Decoded);
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+        feed(&mut app, b"OK\n");
+        assert!(app.receive_buffer_display.ends_with("--   2 bytes  4F 4B\n"));
+
+        // Switch to an XOR check byte mid-line: the partial line is dropped
+        feed(&mut app, b"partial");
+        app.framing_rule.checksum = TrailingChecksum::Xor;
+        app.framing_rule.max_len = 8;
+        app.update_decoder();
+        feed(&mut app, b"\nAB\x03\nAB\x00\n0123456789\n");
+        let lines: Vec<&str> = app.receive_buffer_display.lines().map(|line| line.split_once("] ").unwrap().1).collect();
+        assert_eq!(
+            lines,
+            [
+                "--   2 bytes  4F 4B",
+                "PASS 2 bytes  41 42",
+                "FAIL 2 bytes  41 42  (checksum mismatch: expected 03, got 00)",
+                "FAIL 9 bytes  30 31 32 33 34 35 36 37 38  (no delimiter within 8 bytes)",
+            ]
+        );
+        assert_eq!(app.capture_slot().framing_rule.checksum, TrailingChecksum::Xor);
+    }
+
+    #[test]
+    fn modbus_ascii_frames_pair_up_and_a_mode_switch_drops_a_partial_frame() {
+        let (mut app, _port) = connected_app(true);
+        app.protocol_mode = ProtocolMode::ModbusAscii;
+        app.update_decoder();
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+        feed(&mut app, b":0103");
+        app.protocol_mode = ProtocolMode::ModbusRtu;
+        app.update_decoder();
+        app.protocol_mode = ProtocolMode::ModbusAscii;
+        app.update_decoder();
+        feed(&mut app, b"00000002FA\r\n:010300000002FA\r\n:0103040064003262\r\n:0103040064003263\r\n");
+
+        let frames: Vec<(&str, Option<&str>)> = app
+            .decoded_frames
+            .iter()
+            .map(|e| (e.frame.summary.as_str(), e.frame.error.as_deref()))
+            .collect();
+        assert_eq!(frames[0], ("→ Slave 1 Read Holding Registers addr=0x0000 qty=2", None));
+        assert!(frames[1].0.starts_with("← ") && frames[1].0.ends_with("bytes=4 values=100,50"));
+        assert_eq!(frames[2].1, Some("LRC mismatch: expected 62, got 63"));
+        assert_eq!(frames.len(), 3);
+        assert_eq!(app.decoded_frames[0].slave, Some(1));
+        assert_eq!(app.modbus_tracker.slaves[&1].responses, 1);
+        assert!(app.metrics.render().contains("\nrustcom_crc_errors_total 1\n"));
+    }
+
+    #[test]
+    fn rtu_frames_are_timed_by_when_they_arrived_not_when_processed() {
+        let (mut app, _port) = connected_app(true);
+        app.protocol_mode = ProtocolMode::ModbusRtu;
+        app.update_decoder();
+        let with_crc = |body: &[u8]| {
+            let mut adu = body.to_vec();
+            adu.extend_from_slice(&protocol::checksum::crc16_modbus(body).to_le_bytes());
+            adu
+        };
+        let request = with_crc(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x02]);
+        let response = with_crc(&[0x01, 0x03, 0x04, 0x00, 0x64, 0x00, 0x32]);
+        // Read 50 ms apart but worked off together in one frame
+        let start = Instant::now() - Duration::from_millis(100);
+        app.rx_queue.push(&request, Local::now(), start, Duration::ZERO);
+        app.rx_queue.push(&response, Local::now(), start + Duration::from_millis(50), Duration::ZERO);
+        // A 3-byte coil response is as long as a request; its role decides the fields
+        let coils = with_crc(&[0x01, 0x01, 0x00, 0x00, 0x00, 0x14]);
+        let coil_response = with_crc(&[0x01, 0x01, 0x03, 0xCD, 0x6B, 0x05]);
+        app.rx_queue.push(&coils, Local::now(), start + Duration::from_millis(70), Duration::ZERO);
+        app.rx_queue.push(&coil_response, Local::now(), start + Duration::from_millis(90), Duration::ZERO);
+        app.process_rx_frame();
+        app.poll_decoder();
+
+        let summaries: Vec<&str> = app.decoded_frames.iter().map(|e| e.frame.summary.as_str()).collect();
+        assert_eq!(summaries.len(), 4, "{:?}", summaries);
+        assert!(summaries[0].starts_with("→ ") && summaries[1].starts_with("← "), "{:?}", summaries);
+        assert!(summaries[2].ends_with("Read Coils addr=0x0000 qty=20"), "{:?}", summaries);
+        assert!(summaries[3].starts_with("← ") && summaries[3].ends_with("Read Coils bytes=3"), "{:?}", summaries);
+        assert_eq!(app.modbus_tracker.slaves[&1].responses, 2);
+    }
+
+    #[test]
+    fn nmea_checksum_errors_are_counted() {
+        let (mut app, _port) = connected_app(true);
+        app.protocol_mode = ProtocolMode::Nmea;
+        app.update_decoder();
+        let gga = b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n";
+        let mut bad = gga.to_vec();
+        bad[7] = b'2';
+        for data in [&gga[..30], &gga[30..], &bad[..]] {
+            app.rx_queue.push(data, Local::now(), Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        }
+
+        assert_eq!(app.decoded_frames[0].frame.summary, "NMEA GPGGA fix=GPS sats=8 lat=48.11730 lon=11.51667 alt=545.4 m");
+        assert!(app.decoded_frames[1].frame.error.as_deref().unwrap().contains("checksum mismatch"));
+        assert_eq!((app.nmea_stats.sentences, app.nmea_stats.checksum_errors), (2, 1));
+        let snapshot = app.stats_snapshot();
+        assert!(snapshot.metrics.iter().any(|m| m.name == "NMEA checksum errors" && m.value == 1.0));
+        app.reset_counters();
+        assert_eq!(app.nmea_stats.sentences, 0);
+    }
+
+    #[test]
+    fn protocol_hotkey_cycles_through_the_decoders() {
+        let (mut app, _port) = connected_app(true);
+        app.protocol_mode = ProtocolMode::ModbusAscii;
+        app.update_decoder();
+        app.cycle_protocol();
+        assert_eq!(app.protocol_mode, ProtocolMode::Custom);
+        assert!(app.decoder.is_some());
+        app.cycle_protocol();
+        assert_eq!(app.protocol_mode, ProtocolMode::None);
+        assert!(app.decoder.is_none());
+        assert_eq!(app.error_message.as_deref(), Some("Protocol: None"));
+        app.cycle_protocol();
+        assert_eq!(app.protocol_mode, ProtocolMode::Nmea);
+    }
+
+    #[test]
+    fn sleep_between_chunks_is_marked_in_the_capture_and_log() {
+        let (mut app, _port) = connected_app(true);
+        app.logging_enabled = true;
+        let (wall, instant) = (Local::now(), Instant::now());
+        app.rx_queue.push(b"before\n", wall, instant, Duration::ZERO);
+        app.process_rx_frame();
+        // Overnight suspend: the monotonic clock stood still
+        let later = wall + chrono::TimeDelta::hours(8);
+        app.rx_queue.push(b"after\n", later, instant + Duration::from_secs(1), Duration::ZERO);
+        app.process_rx_frame();
+
+        let text = app.capture_text();
+        let marker = text.find("Clock discontinuity: wall clock +7h 59m 59s (sleep or clock change)").unwrap();
+        assert!(text.find("before").unwrap() < marker && marker < text.find("after").unwrap());
+        let mut directions = Vec::new();
+        app.log_entries.for_each(|e| directions.push(e.direction)).unwrap();
+        assert_eq!(directions, [Direction::Received, Direction::Event, Direction::Received]);
+    }
+
+    #[test]
+    fn schedule_sends_a_spaced_sequence_while_connected() {
+        let (mut app, port) = connected_app(false);
+        app.line_ending = LineEnding::Cr;
+        app.schedule = vec![
+            ScheduledSend::new("T?", SendMode::Ascii, 10_000),
+            ScheduledSend::new("01 03", SendMode::Hex, 10_000),
+            ScheduledSend::new("BAD", SendMode::Hex, 10_000),
+        ];
+        app.schedule[1].phase_ms = 200;
+        app.schedule[2].phase_ms = 400;
+        let start = Instant::now();
+        assert_eq!(app.run_schedule(start), None, "off until switched on");
+
+        app.schedule_running = true;
+        assert_eq!(app.run_schedule(start), Some(Duration::from_millis(200)));
+        assert_eq!(app.run_schedule(start + Duration::from_millis(450)), Some(Duration::from_millis(9_550)));
+        assert_eq!(port.activity(), ["write [54, 3F, 0D]", "write [01, 03]"]);
+        assert!(app.capture_text().contains("TX [hex, schedule]: 01 03"));
+        assert_eq!(app.schedule[1].last_run.as_ref().unwrap().result, Ok(2));
+        assert!(app.schedule[2].last_run.as_ref().unwrap().result.is_err());
+
+        // Paused while disconnected, and started over on the next connection
+        app.connected = false;
+        assert_eq!(app.run_schedule(start + Duration::from_secs(20)), None);
+        app.connected = true;
+        app.monitor_only = true;
+        app.run_schedule(start + Duration::from_secs(30));
+        assert_eq!(port.activity().len(), 2);
+        assert_eq!(app.schedule[0].last_run.as_ref().unwrap().result, Err(MONITOR_ONLY_ERROR.to_string()));
+    }
+
+    #[test]
+    fn database_log_moves_to_a_new_file_with_a_note_in_each() {
+        let file = |name: &str| std::env::temp_dir().join(format!("rustcom_move_{}_{}.sqlite", name, std::process::id()));
+        let remove = |path: &std::path::Path| {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut name = path.as_os_str().to_owned();
+                name.push(suffix);
+                let _ = std::fs::remove_file(name);
+            }
+        };
+        let (first, second) = (file("a"), file("b"));
+        remove(&first);
+        remove(&second);
+
+        let (mut app, _port) = connected_app(false);
+        app.db_path = first.display().to_string();
+        app.start_db_logging();
+        app.db_log(DbRecord::chunk(Direction::Received, b"before"));
+
+        // A file that cannot be opened leaves the current one logging
+        app.db_path = first.join("missing").display().to_string();
+        assert!(app.apply_db_path().is_err());
+        assert_eq!(app.db_logger.as_ref().unwrap().path(), first);
+
+        app.db_path = second.display().to_string();
+        assert_eq!(app.apply_db_path(), Ok(()));
+        app.db_log(DbRecord::chunk(Direction::Received, b"after"));
+        app.stop_db_logging();
+
+        let read = |path: &std::path::Path| {
+            let conn = logging::database::open_database(path).unwrap();
+            let event: String = conn.query_row("SELECT text FROM events", [], |row| row.get(0)).unwrap();
+            let frame: Vec<u8> = conn.query_row("SELECT data FROM frames", [], |row| row.get(0)).unwrap();
+            let open: i64 = conn
+                .query_row("SELECT COUNT(*) FROM sessions WHERE ended_at IS NULL", [], |row| row.get(0))
+                .unwrap();
+            (event, frame, open)
+        };
+        assert_eq!(read(&first), (format!("Log continues in {}", second.display()), b"before".to_vec(), 0));
+        assert_eq!(read(&second), (format!("Log continued from {}", first.display()), b"after".to_vec(), 0));
+        remove(&first);
+        remove(&second);
+    }
+
+    #[test]
+    fn short_writes_are_continued_until_everything_is_out() {
+        let port = MockPort::default();
+        port.set_write_limit(Some(3));
+        let timeout = Duration::from_millis(20);
+        assert_eq!(write_port(&mut port.clone(), b"ABCDEFG", timeout), Ok(7));
+        assert_eq!(port.activity(), ["write [41, 42, 43]", "write [44, 45, 46]", "write [47]"]);
+
+        // A port that takes two bytes and then stalls
+        struct Stalls(usize);
+        impl std::io::Write for Stalls {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                let count = buf.len().min(self.0);
+                self.0 -= count;
+                Ok(count)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let stalled = write_port(&mut Stalls(2), b"ABCDEFG", timeout).unwrap_err();
+        assert_eq!(stalled, WriteError::TimedOut { written: 2 });
+        assert_eq!(stalled.message(7), "Write timed out after 2 of 7 bytes");
+
+        port.set_write_error(Some(std::io::ErrorKind::BrokenPipe));
+        let failed = write_port(&mut port.clone(), b"AB", timeout).unwrap_err();
+        assert!(failed.message(2).starts_with("Write failed after 0 of 2 bytes: "));
+    }
+
+    #[test]
+    fn sends_count_only_the_bytes_written() {
+        let (mut app, port) = connected_app(false);
+        app.line_ending = LineEnding::None;
+        app.write_timeout_ms = 20;
+        port.set_write_limit(Some(2));
+        app.send_buffer = "HELLO".to_string();
+        assert_eq!(app.submit_send(false), Ok(()));
+        assert_eq!(app.bytes_sent, 5);
+        assert_eq!(port.activity().len(), 3);
+
+        port.set_write_limit(Some(0));
+        app.send_mode = SendMode::Hex;
+        app.send_buffer = "AA BB".to_string();
+        assert_eq!(app.submit_send(false), Err("Write timed out after 0 of 2 bytes".to_string()));
+        assert_eq!(app.bytes_sent, 5);
+        assert_eq!(app.send_buffer, "AA BB", "kept for another try");
+    }
+
+    #[test]
+    fn line_breaks_in_the_send_box_use_the_line_ending() {
+        let (mut app, port) = connected_app(false);
+        app.line_ending = LineEnding::CrLf;
+        app.send_buffer = "AT\nAT+GMR".to_string();
+        app.send_data().unwrap();
+        assert_eq!(port.activity(), ["write [41, 54, 0D, 0A, 41, 54, 2B, 47, 4D, 52, 0D, 0A]"]);
+        assert!(app.capture_text().contains("TX: AT\\nAT+GMR\n"));
+
+        assert_eq!(LineEnding::None.terminate("a\nb"), b"a\nb");
+        assert_eq!(LineEnding::Cr.terminate("a"), b"a\r");
+    }
+
+    #[test]
+    fn xon_is_injected_raw_and_logged_as_an_event() {
+        let (mut app, port) = connected_app(false);
+        app.line_ending = LineEnding::CrLf;
+        app.send_mode = SendMode::Hex;
+        app.send_encoding = SendEncoding::Slip;
+        app.logging_enabled = true;
+
+        app.inject_flow_byte(FlowEvent::Xon).unwrap();
+        assert_eq!(port.activity(), ["write [11]"]);
+        assert_eq!(app.bytes_sent, 1);
+        assert!(app.capture_text().contains("XON sent (0x11)"));
+        assert!(!app.capture_text().contains("TX:"));
+        assert!(app.recent_payloads.entries().is_empty());
+        let mut directions = Vec::new();
+        app.log_entries.for_each(|e| directions.push(e.direction)).unwrap();
+        assert_eq!(directions, [Direction::Event]);
+
+        let (mut app, port) = connected_app(true);
+        assert!(app.inject_flow_byte(FlowEvent::Xoff).is_err());
+        assert!(port.activity().is_empty());
+    }
+
+    #[test]
+    fn cts_is_read_under_hardware_flow_control() {
+        let (mut app, port) = connected_app(false);
+        let start = Instant::now();
+        app.poll_cts(start);
+        assert_eq!(app.cts_state, None);
+
+        app.flow_control = FlowControl::Hardware;
+        port.set_loopback(true);
+        app.poll_cts(start);
+        assert_eq!(app.cts_state, Some(false));
+        app.set_rts(true).unwrap();
+        app.poll_cts(start + Duration::from_millis(10));
+        assert_eq!(app.cts_state, Some(false), "polled too soon");
+        app.poll_cts(start + CTS_POLL_INTERVAL);
+        assert_eq!(app.cts_state, Some(true));
+    }
+
+    #[test]
+    fn guarded_payloads_wait_for_confirmation() {
+        let (mut app, port) = connected_app(false);
+        app.send_guard.rules.push(GuardRule::new("Memory write", GuardKind::Hex, "A5 5A"));
+        app.send_encoding = SendEncoding::Slip;
+
+        app.send_mode = SendMode::Hex;
+        app.send_buffer = "A5 5A 00 10".to_string();
+        assert_eq!(app.submit_send(false), Ok(()));
+        assert!(port.activity().is_empty());
+        let confirmation = app.guard_confirmation.clone().unwrap();
+        assert_eq!(confirmation.rule, "Memory write");
+        assert_eq!(confirmation.wire_bytes, [0xC0, 0xA5, 0x5A, 0x00, 0x10, 0xC0]);
+
+        assert_eq!(app.confirm_guarded_send(), Ok(()));
+        assert!(app.guard_confirmation.is_none());
+        assert_eq!(port.activity(), ["write [C0, A5, 5A, 00, 10, C0]"]);
+
+        // Approval covers that one send only
+        app.send_buffer = "A5 5A 00 10".to_string();
+        assert_eq!(app.submit_send(false), Ok(()));
+        assert!(app.guard_confirmation.is_some());
+        assert_eq!(port.activity().len(), 1);
+
+        // Text sends are guarded too; unmatched payloads go straight out
+        app.guard_confirmation = None;
+        app.send_guard.rules.push(GuardRule::new("Erase", GuardKind::Regex, "^ERASE"));
+        app.send_mode = SendMode::Ascii;
+        app.send_buffer = "ERASE ALL".to_string();
+        assert_eq!(app.submit_send(false), Ok(()));
+        assert_eq!(app.guard_confirmation.as_ref().unwrap().rule, "Erase");
+        app.guard_confirmation = None;
+        app.send_buffer = "STATUS".to_string();
+        assert_eq!(app.submit_send(false), Ok(()));
+        assert_eq!(port.activity().len(), 2);
+    }
+
+    #[test]
+    fn sent_payloads_can_be_reinserted() {
+        let (mut app, _port) = connected_app(false);
+        app.send_buffer = "AT".to_string();
+        app.send_data().unwrap();
+        app.send_buffer = "01 03".to_string();
+        app.send_hex_input().unwrap();
+        app.send_buffer = "AT".to_string();
+        app.send_data().unwrap();
+
+        let recent: Vec<_> = app.recent_payloads.entries().iter().map(|e| (e.text.as_str(), e.mode)).collect();
+        assert_eq!(recent, [("AT", SendMode::Ascii), ("01 03", SendMode::Hex)]);
+
+        app.insert_recent_payload(1);
+        assert_eq!(app.send_buffer, "01 03");
+        assert_eq!(app.send_mode, SendMode::Hex);
+        assert_eq!(app.recent_payloads.entries()[0].text, "01 03");
+    }
+
+    #[test]
+    fn typed_keys_and_lines_reach_the_port() {
+        let (mut app, port) = connected_app(false);
+        app.send_keystroke(b"a").unwrap();
+        app.send_keystroke(b"\x1b[A").unwrap();
+        assert_eq!(port.activity(), ["write [61]", "write [1B, 5B, 41]"]);
+        assert_eq!(app.bytes_sent, 4);
+
+        app.send_guard.rules.push(GuardRule::new("Reset", GuardKind::Hex, "12"));
+        assert!(app.send_keystroke(&[0x12]).is_err());
+        assert_eq!(port.activity().len(), 2);
+
+        // A line goes out whole, with the line ending and through the guard
+        app.line_ending = LineEnding::Cr;
+        app.line_editor.insert("AT");
+        app.send_typed_line().unwrap();
+        assert_eq!(port.activity()[2], "write [41, 54, 0D]");
+        assert!(app.line_editor.is_empty());
+        assert!(app.send_buffer.is_empty());
+
+        app.line_editor.insert("\x12");
+        app.send_typed_line().unwrap();
+        assert_eq!(port.activity().len(), 3);
+        assert_eq!(app.guard_confirmation.as_ref().unwrap().rule, "Reset");
+    }
+
+    thread_local! {
+        static REOPENED_PORT: MockPort = MockPort::default();
+    }
+
+    #[test]
+    fn reconnect_restores_dtr_and_rts_in_order() {
+        let (mut app, old_port) = connected_app(false);
+        app.selected_port = Some("COM9".to_string());
+        app.auto_reconnect = true;
+        app.port_opener.open = |_, _| Ok(Box::new(REOPENED_PORT.with(MockPort::clone)));
+        app.dtr_state = true;
+        app.set_dtr(true).unwrap();
+        app.set_rts(false).unwrap();
+        assert_eq!(old_port.activity(), ["dtr true", "rts false"]);
+
+        app.rx_queue.push(b"last words\n", Local::now(), Instant::now(), Duration::ZERO);
+        app.connection_lost("device power-cycled");
+        assert!(app.reconnecting);
+        app.attempt_reconnect();
+        assert!(app.connected && !app.reconnecting);
+        assert_eq!(REOPENED_PORT.with(MockPort::activity), ["dtr true", "rts false"]);
+
+        let terminal = String::from_utf8_lossy(&app.receive_buffer).into_owned();
+        let position = |text: &str| terminal.find(text).unwrap_or_else(|| panic!("{} not logged", text));
+        let order = [
+            position("last words"),
+            position("Connection lost to COM9"),
+            position("Connected to COM9"),
+            position("Reconnected successfully"),
+            position("Restored DTR: on"),
+            position("Restored RTS: off"),
+        ];
+        assert!(order.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn cable_test_refuses_monitor_only_and_ports_held_elsewhere() {
+        let port = format!("TEST-{}-cable", std::process::id());
+        let mut app = ComAnalyzerApp {
+            selected_port: Some(port.clone()),
+            monitor_only: true,
+            ..Default::default()
+        };
+        app.start_cable_test();
+        assert!(app.cable_test.is_none());
+        assert_eq!(app.error_message.as_deref(), Some(MONITOR_ONLY_ERROR));
+
+        let other = instance::InstanceServer::bind().unwrap();
+        let _lock = PortLock::acquire(&port, other.addr().unwrap()).unwrap();
+        app.monitor_only = false;
+        app.start_cable_test();
+        assert!(app.cable_test.is_none());
+        assert!(app.error_message.as_deref().unwrap().contains("is open in another RustCOM window"));
+    }
+
+    #[test]
+    fn fast_attach_times_the_first_byte() {
+        let ctx = eframe::egui::Context::default();
+        let mut app = ComAnalyzerApp {
+            reconnecting: true,
+            fast_attach_dtr: true,
+            ..Default::default()
+        };
+        let port = MockPort::default();
+        let appeared_at = std::time::Instant::now();
+        app.complete_fast_attach(Attached {
+            port: Box::new(port.clone()),
+            port_name: "COM9".to_string(),
+            baud_rate: 115200,
+            timing: crate::fast_attach::AttachTiming {
+                appeared_at,
+                opened_after: Duration::from_millis(3),
+                first_byte_after: None,
+            },
+        });
+        assert!(app.connected && !app.reconnecting && app.dtr_state);
+
+        app.poll_serial(&ctx);
+        assert!(app.attach_timing.unwrap().first_byte_after.is_none());
+        std::thread::sleep(Duration::from_millis(20));
+        port.push_rx(b"U-Boot 2024.01");
+        poll_until(&mut app, |app| app.bytes_received == 14);
+        let first = app.attach_timing.unwrap().first_byte_after.unwrap();
+        assert!(first >= Duration::from_millis(20));
+
+        // Later data does not move it
+        port.push_rx(b"more");
+        poll_until(&mut app, |app| app.bytes_received == 18);
+        assert_eq!(app.attach_timing.unwrap().first_byte_after, Some(first));
+
+        // Monitor-only attaches never raise DTR
+        let mut app = ComAnalyzerApp {
+            reconnecting: true,
+            fast_attach_dtr: true,
+            monitor_only: true,
+            ..Default::default()
+        };
+        app.complete_fast_attach(Attached {
+            port: Box::new(MockPort::default()),
+            port_name: "COM9".to_string(),
+            baud_rate: 115200,
+            timing: crate::fast_attach::AttachTiming {
+                appeared_at,
+                opened_after: Duration::from_millis(3),
+                first_byte_after: None,
+            },
+        });
+        assert!(app.connected && !app.dtr_state);
+    }
+
+    #[test]
+    fn a_stalled_ui_makes_the_reader_drop_and_report_reads() {
+        use crate::app::SERIAL_READ_BUFFER_SIZE;
+        use crate::reader::READER_QUEUE_BYTES;
+
+        // More than the reader may hold, then the port goes away
+        let port = MockPort::default();
+        let total = READER_QUEUE_BYTES + 20 * SERIAL_READ_BUFFER_SIZE;
+        port.push_rx(&vec![b'x'; total]);
+        port.set_read_error(Some(std::io::ErrorKind::BrokenPipe));
+        let reader = PortReader::start(Box::new(port.clone()), None);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while port.pending_rx() > 0 {
+            assert!(Instant::now() < deadline, "the reader stopped reading");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        // Let it get to the failed read, which waits for room
+        std::thread::sleep(Duration::from_millis(50));
+
+        let mut outcomes = Vec::new();
+        while !matches!(outcomes.last(), Some(ReadOutcome::Failed)) {
+            assert!(Instant::now() < deadline, "the reader never failed");
+            match reader.poll() {
+                Some(outcome) => outcomes.push(outcome),
+                None => std::thread::sleep(Duration::from_millis(1)),
+            }
+        }
+        let read: usize = outcomes
+            .iter()
+            .map(|outcome| match outcome {
+                ReadOutcome::Data { data, .. } => data.len(),
+                _ => 0,
+            })
+            .sum();
+        let [.., ReadOutcome::Dropped(dropped), ReadOutcome::Failed] = outcomes[..] else {
+            panic!("the drop is reported before the failure");
+        };
+        assert_eq!(read, READER_QUEUE_BYTES);
+        assert_eq!(read + dropped, total);
+
+        // The UI notes the gap after what was read before it
+        let (mut app, _port) = connected_app(false);
+        app.handle_read(ReadOutcome::Data {
+            data: b"before\n".to_vec(),
+            received_at: Local::now(),
+            arrived: Instant::now(),
+        });
+        app.handle_read(ReadOutcome::Dropped(dropped));
+        let text = app.capture_text();
+        let note = format!("RX overrun: {} bytes dropped", dropped);
+        assert!(text.find("before").unwrap() < text.find(&note).unwrap(), "{}", text);
+        app.disconnect();
+    }
+
+    #[test]
+    fn huge_reads_are_processed_a_budget_per_frame() {
+        let (mut app, port) = connected_app(false);
+        app.logging_enabled = true;
+        let data: Vec<u8> = (0..1024 * 1024).map(|i| b'a' + (i % 26) as u8).collect();
+        port.push_rx(&data);
+        let ctx = eframe::egui::Context::default();
+
+        let mut frames = 0;
+        while app.bytes_received < data.len() {
+            let before = app.bytes_received;
+            app.poll_serial(&ctx);
+            assert!(app.bytes_received - before <= app.rx_frame_budget);
+            assert!(app.rx_queue.len() <= rustcom_core::rx_queue::RX_QUEUE_LIMIT);
+            frames += 1;
+            assert!(frames < 1000, "queue stopped draining");
+        }
+        assert!(frames >= data.len() / app.rx_frame_budget);
+        assert!(app.rx_queue.is_empty());
+        assert!(app.receive_buffer.len() <= crate::app::MAX_BUFFER_SIZE);
+        assert!(app.receive_buffer.ends_with(&data[data.len() - 1000..]));
+        assert_eq!(app.log_entries.len(), data.len() / rustcom_core::rx_queue::MAX_RX_SUB_CHUNK);
+    }
+
+    #[test]
+    fn reader_thread_delivers_a_continuous_stream_in_order() {
+        let mut app = ComAnalyzerApp::default();
+        let port = MockPort::default();
+        app.attach_port(Box::new(port.clone()), "COM3", 1_000_000);
+        assert!(app.port_reader.is_some());
+        let start = app.receive_buffer.len();
+
+        // 1 Mbit/s for two seconds, written in bursts as a UART driver would
+        let data: Vec<u8> = (0..250_000u32).map(|i| (i % 251) as u8).collect();
+        let mut received = Vec::new();
+        for burst in data.chunks(4096) {
+            port.push_rx(burst);
+            app.poll_serial(&eframe::egui::Context::default());
+            received.extend_from_slice(&app.receive_buffer[start..]);
+            app.receive_buffer.truncate(start);
+        }
+        while app.bytes_received < data.len() {
+            poll_until(&mut app, |app| app.receive_buffer.len() > start || app.bytes_received == data.len());
+            received.extend_from_slice(&app.receive_buffer[start..]);
+            app.receive_buffer.truncate(start);
+        }
+        assert_eq!(received, data);
+
+        // Disconnecting stops the thread, so later data stays unread
+        app.disconnect();
+        assert!(app.port_reader.is_none());
+        port.push_rx(b"late");
+        std::thread::sleep(Duration::from_millis(30));
+        let mut buf = [0u8; 8];
+        assert_eq!(std::io::Read::read(&mut port.clone(), &mut buf).unwrap(), 4);
+    }
+
+    #[test]
+    fn reader_thread_reports_a_closed_peer() {
+        let mut app = ComAnalyzerApp {
+            auto_reconnect: true,
+            ..Default::default()
+        };
+        let port = MockPort::default();
+        app.attach_port(Box::new(port.clone()), "COM3", 115_200);
+        port.push_rx(b"bye");
+        port.set_eof(true);
+        poll_until(&mut app, |app| !app.connected);
+        assert!(app.reconnecting);
+        assert!(app.port_reader.is_none());
+        let text = String::from_utf8_lossy(&app.receive_buffer);
+        assert!(text.contains("bye"));
+        assert!(text.contains("peer closed (5 empty reads)"));
+    }
+
+    #[test]
+    fn repeated_zero_reads_mean_the_peer_closed() {
+        let ctx = eframe::egui::Context::default();
+        let (mut app, port) = connected_app(false);
+        app.auto_reconnect = true;
+        port.push_rx(b"bye");
+        port.set_eof(true);
+        app.poll_serial(&ctx);
+
+        for _ in 0..app.eof_zero_reads {
+            assert!(app.connected);
+            app.poll_serial(&ctx);
+        }
+        assert!(!app.connected);
+        assert!(app.reconnecting);
+        let text = String::from_utf8_lossy(&app.receive_buffer);
+        assert!(text.starts_with("bye"));
+        assert!(text.contains("peer closed (5 empty reads)"));
+
+        // A timeout in between resets the count
+        let (mut app, port) = connected_app(false);
+        port.set_eof(true);
+        for _ in 0..app.eof_zero_reads - 1 {
+            app.poll_serial(&ctx);
+        }
+        port.set_eof(false);
+        app.poll_serial(&ctx);
+        assert_eq!(app.zero_reads, 0);
+
+        // Disabled, zero reads are ignored
+        let (mut app, port) = connected_app(false);
+        app.eof_detection = false;
+        port.set_eof(true);
+        for _ in 0..100 {
+            app.poll_serial(&ctx);
+        }
+        assert!(app.connected);
+    }
+
+    #[test]
+    fn a_failed_read_means_the_device_is_gone() {
+        let ctx = eframe::egui::Context::default();
+        let (mut app, port) = connected_app(false);
+        app.auto_reconnect = false;
+        port.push_rx(b"last words\n");
+        port.set_read_error(Some(std::io::ErrorKind::BrokenPipe));
+        app.poll_serial(&ctx);
+        assert!(app.connected, "queued data is read first");
+
+        app.poll_serial(&ctx);
+        assert!(!app.connected);
+        assert!(!app.reconnecting);
+        assert!(app.serial_port.is_none());
+        assert!(!app.metrics.connected.load(Ordering::Relaxed));
+        assert_eq!(app.error_message.as_deref(), Some("Connection lost (device disconnected)."));
+        let text = String::from_utf8_lossy(&app.receive_buffer);
+        assert!(text.starts_with("last words\n"));
+        assert!(text.contains("Connection lost to "));
+
+        // Nothing is written to a port that is gone
+        app.send_buffer = "hello?".to_string();
+        assert_eq!(app.submit_send(false), Err("Send failed: not connected".to_string()));
+        assert_eq!(app.bytes_sent, 0);
+        assert_eq!(port.activity(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn transmit_paths_reach_the_port_when_not_monitoring() {
+        let (mut app, port) = connected_app(false);
+
+        app.send_buffer = "hi".to_string();
+        assert_eq!(app.send_data(), Ok(()));
+        app.send_buffer = "AA".to_string();
+        assert_eq!(app.send_hex_input(), Ok(()));
+        assert_eq!(app.set_dtr(true), Ok(()));
+        assert_eq!(app.set_rts(false), Ok(()));
+        app.file_transfer = Some(FileTransfer::from_bytes("queued", vec![1, 2, 3]));
+        app.poll_file_send(&eframe::egui::Context::default());
+
+        assert_eq!(
+            port.activity(),
+            vec![
+                "write [68, 69, 0D, 0A]".to_string(),
+                "write [AA]".to_string(),
+                "dtr true".to_string(),
+                "rts false".to_string(),
+                "write [01, 02, 03]".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn rate_limited_sends_wait_their_turn_in_order() {
+        let (mut app, port) = connected_app(false);
+        app.line_ending = LineEnding::None;
+        app.tx_limiter.limit.payloads_per_sec = 10;
+        for text in ["A", "B"] {
+            app.send_buffer = text.to_string();
+            app.send_data().unwrap();
+        }
+        app.inject_flow_byte(FlowEvent::Xon).unwrap();
+        assert_eq!(port.activity(), ["write [41]"]);
+        assert_eq!((app.tx_pending.len(), app.tx_delayed), (2, 2));
+        assert!(app.capture_text().contains("TX: A") && !app.capture_text().contains("TX: B"));
+
+        // One per 100 ms, however late the poll
+        let start = Instant::now();
+        assert_eq!(app.flush_tx_pending(start), Some(app.tx_limiter.wait(start)));
+        app.flush_tx_pending(start + Duration::from_millis(150));
+        assert_eq!(port.activity(), ["write [41]", "write [42]"]);
+        assert_eq!(app.flush_tx_pending(start + Duration::from_millis(1000)), None);
+        assert_eq!(port.activity(), ["write [41]", "write [42]", "write [11]"]);
+        assert!(app.capture_text().contains("TX: B"));
+        assert!(app.capture_text().contains("XON sent (0x11)"));
+
+        app.send_buffer = "C".to_string();
+        app.send_data().unwrap();
+        app.disconnect();
+        assert!(app.tx_pending.is_empty());
+        assert!(app.capture_text().contains("1 rate-limited sends dropped"));
+
+        // Switching to monitor-only drops what is still held back
+        let (mut app, port) = connected_app(false);
+        app.line_ending = LineEnding::None;
+        app.tx_limiter.limit.payloads_per_sec = 10;
+        for text in ["D", "E", "F"] {
+            app.send_buffer = text.to_string();
+            app.send_data().unwrap();
+        }
+        app.monitor_only = true;
+        assert_eq!(app.flush_tx_pending(Instant::now() + Duration::from_secs(1)), None);
+        assert_eq!(port.activity(), ["write [44]"]);
+        assert!(app.tx_pending.is_empty());
+        assert_eq!(app.error_message, Some(format!("{}: 2 queued sends dropped", MONITOR_ONLY_ERROR)));
+    }
+
+    #[test]
+    fn boot_banner_splits_the_capture_and_the_terminal() {
+        let (mut app, _port) = connected_app(true);
+        let dir = std::env::temp_dir().join(format!("rustcom-gui-split-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        app.split_log = Some(SplitLog::start(&dir, "boot{n}.log", "^U-Boot", Local::now()).unwrap());
+        app.split_display = true;
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+        feed(&mut app, b"login: \nU-Bo");
+        feed(&mut app, b"ot 2024.04\nDRAM: 512 MiB\n");
+        assert_eq!(app.receive_buffer, b"U-Boot 2024.04\nDRAM: 512 MiB\n");
+        assert_eq!(app.split_log.as_ref().unwrap().boot, 1);
+        app.split_log = None;
+        assert_eq!(std::fs::read_to_string(dir.join("boot000.log")).unwrap(), "login: \n");
+        assert_eq!(std::fs::read_to_string(dir.join("boot001.log")).unwrap(), "U-Boot 2024.04\nDRAM: 512 MiB\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keepalive_fills_quiet_gaps_only() {
+        let (mut app, port) = connected_app(false);
+        app.line_ending = LineEnding::None;
+        app.keepalive = KeepaliveConfig {
+            enabled: true,
+            text: "K".to_string(),
+            interval_secs: 10,
+            ..KeepaliveConfig::default()
+        };
+        let secs = Duration::from_secs;
+        let start = Instant::now();
+        assert_eq!(app.run_keepalive(start), Some(secs(10)));
+
+        // Real traffic restarts the window
+        app.send_buffer = "A".to_string();
+        app.send_data().unwrap();
+        assert!(app.run_keepalive(start + secs(9)).unwrap() > Duration::ZERO);
+        assert_eq!(port.activity(), ["write [41]"]);
+
+        // A quiet keepalive is left out of the counters and the capture
+        assert_eq!(app.run_keepalive(start + secs(11)), Some(secs(10)));
+        assert_eq!(port.activity(), ["write [41]", "write [4B]"]);
+        assert_eq!((app.bytes_sent, app.keepalives_sent), (1, 1));
+        assert!(!app.capture_text().contains("keepalive"));
+        assert_eq!(app.run_keepalive(start + secs(15)), Some(secs(6)));
+
+        app.keepalive.quiet = false;
+        app.run_keepalive(start + secs(21));
+        assert_eq!(app.bytes_sent, 2);
+        assert!(app.capture_text().contains("TX [keepalive]: K"));
+
+        // The TX rate limit holds a due keepalive back until it allows one
+        app.keepalive.quiet = true;
+        app.tx_limiter.limit.bytes_per_sec = 1;
+        app.tx_limiter.sent(15, start + secs(21));
+        assert_eq!(app.run_keepalive(start + secs(31)), Some(secs(5)));
+        assert_eq!(port.activity().len(), 3);
+        assert_eq!(app.run_keepalive(start + secs(36)), Some(secs(10)));
+        assert_eq!(port.activity().len(), 4);
+
+        app.disconnect();
+        assert_eq!(app.run_keepalive(start + secs(50)), None);
+        assert!(!app.keepalive_timer.is_running());
+        assert_eq!(port.activity().len(), 4);
+    }
+
+    #[test]
+    fn actions_run_only_while_enabled() {
+        let (mut app, port) = connected_app(true);
+        assert_eq!(app.connect_action(), Action::Disconnect);
+        assert!(!app.action_enabled(Action::Connect));
+
+        // Monitor-only mode keeps the control lines where they are
+        app.run_action(Action::Dtr);
+        assert_eq!((app.dtr_state, port.activity().len()), (false, 0));
+        app.run_action(Action::MonitorOnly);
+        app.run_action(Action::Dtr);
+        assert!(app.dtr_state);
+        assert_eq!(app.action_checked(Action::Dtr), Some(true));
+        assert_eq!(port.activity(), ["dtr true"]);
+
+        app.run_action(Action::Disconnect);
+        assert!(!app.connected);
+        assert_eq!(app.connect_action(), Action::Connect);
+        assert!(!app.action_enabled(Action::Disconnect));
+        assert_eq!(app.action_checked(Action::ClearTerminal), None);
+
+        app.logging_enabled = false;
+        app.run_action(Action::SaveLog);
+        assert_eq!(app.error_message, None, "disabled actions do nothing");
+        app.run_action(Action::GoTo);
+        assert!(app.goto_open);
+    }
+
+    #[test]
+    fn sent_entries_are_recalled_per_mode() {
+        let (mut app, _port) = connected_app(false);
+        let sends = [
+            (SendMode::Ascii, "AT"),
+            (SendMode::Ascii, "AT"),
+            (SendMode::Hex, "01 03"),
+            (SendMode::Ascii, "ATI"),
+        ];
+        for (mode, text) in sends {
+            app.send_mode = mode;
+            app.send_buffer = text.to_string();
+            app.submit_send(false).unwrap();
+        }
+        assert_eq!(app.send_history.entries(SendMode::Ascii), ["AT", "ATI"]);
+
+        app.send_mode = SendMode::Ascii;
+        app.send_buffer = "AT+".to_string();
+        assert!(app.recall_send_history(true));
+        assert!(app.recall_send_history(true));
+        assert_eq!(app.send_buffer, "AT");
+        assert!(!app.recall_send_history(true));
+        assert!(app.recall_send_history(false) && app.recall_send_history(false));
+        assert_eq!(app.send_buffer, "AT+");
+
+        app.send_mode = SendMode::Hex;
+        app.insert_history_entry(0);
+        assert_eq!(app.send_buffer, "01 03");
+        assert!(!app.recall_send_history(false));
+    }
+
+    #[test]
+    fn macros_send_like_the_send_box() {
+        let (mut app, port) = connected_app(false);
+        app.line_ending = LineEnding::CrLf;
+        app.macros = vec![
+            Macro {
+                name: "Break".to_string(),
+                text: r"\x03".to_string(),
+                ..Macro::default()
+            },
+            Macro {
+                text: r"AT\tI".to_string(),
+                line_ending: Some(LineEnding::Cr),
+                ..Macro::default()
+            },
+            Macro {
+                name: "Read".to_string(),
+                text: "01 03".to_string(),
+                mode: SendMode::Hex,
+                ..Macro::default()
+            },
+        ];
+        app.send_buffer = "typed".to_string();
+        for index in 0..3 {
+            app.send_macro(index).unwrap();
+        }
+        assert_eq!(
+            port.activity(),
+            ["write [03, 0D, 0A]", "write [41, 54, 09, 49, 0D]", "write [01, 03]"]
+        );
+        let capture = app.capture_text();
+        assert!(capture.contains(r"TX [macro AT\tI]: AT\tI"));
+        assert!(capture.contains("TX [hex, macro Read]: 01 03"));
+        assert_eq!(app.send_buffer, "typed", "the send box is left alone");
+        assert!(app.send_history.entries(SendMode::Ascii).is_empty());
+
+        // The send encoding applies; a guarded payload is refused, not held
+        app.send_encoding = SendEncoding::Slip;
+        app.send_macro(2).unwrap();
+        assert_eq!(port.activity()[3], "write [C0, 01, 03, C0]");
+        app.send_guard.rules.push(GuardRule::new("Reset", GuardKind::Hex, "03"));
+        assert_eq!(
+            app.send_macro(0),
+            Err("Macro \"Break\" blocked by send guard rule \"Reset\"".to_string())
+        );
+        assert!(app.guard_confirmation.is_none());
+        assert_eq!(port.activity().len(), 4);
+
+        app.monitor_only = true;
+        assert_eq!(app.send_macro(1), Err(MONITOR_ONLY_ERROR.to_string()));
+    }
+
+    #[test]
+    fn low_latency_mode_times_echoes_on_the_io_path() {
+        let (mut app, port) = connected_app(false);
+        port.set_loopback(true);
+        port.set_loopback_delay(Duration::from_millis(20));
+        app.set_low_latency(true);
+        assert!(app.port_reader.is_some());
+        assert_eq!(port.timeout(), LOW_LATENCY_READ_TIMEOUT);
+
+        // The UI only gets to each echo a whole idle frame after the reader took
+        // it, so timing it when processed would take in that frame too
+        for sent in 1..=3 {
+            app.send_buffer = "ping".to_string();
+            let before_send = Instant::now();
+            app.send_data().unwrap();
+            let deadline = before_send + Duration::from_secs(5);
+            while port.pending_rx() > 0 {
+                assert!(Instant::now() < deadline, "the echo was never read");
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            std::thread::sleep(Duration::from_millis(crate::app::DEFAULT_REPAINT_INTERVAL_MS));
+            let unprocessed = before_send.elapsed();
+            poll_until(&mut app, |app| app.echo_latency.latencies.count() == sent);
+            let latency = app.echo_latency.last.unwrap();
+            assert!(latency >= Duration::from_millis(20), "{:?}", latency);
+            assert!(latency < unprocessed, "{:?} not under {:?}", latency, unprocessed);
+        }
+
+        app.set_low_latency(false);
+        assert_eq!(port.timeout(), READ_TIMEOUT);
+        app.send_buffer = "ping".to_string();
+        app.send_data().unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        poll_until(&mut app, |app| app.bytes_received == app.bytes_sent);
+        assert_eq!(app.echo_latency.latencies.count(), 0);
+    }
+
+    #[test]
+    fn repeat_resends_the_box_until_disconnected() {
+        let (mut app, port) = connected_app(false);
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        app.repeat_interval_ms = 100;
+        app.line_ending = LineEnding::Cr;
+        app.send_buffer = "P".to_string();
+        assert_eq!(app.run_repeat(at(0)), None);
+
+        app.run_action(Action::Repeat);
+        assert_eq!(app.run_repeat(at(0)), Some(Duration::from_millis(100)));
+        assert_eq!(app.run_repeat(at(150)), Some(Duration::from_millis(50)));
+        app.run_repeat(at(199));
+        assert_eq!(port.activity(), ["write [50, 0D]", "write [50, 0D]"]);
+        assert_eq!(app.repeat.fired, 2);
+        assert_eq!(app.send_buffer, "P", "the box is kept for the next send");
+        assert!(app.capture_text().contains("TX [repeat]: P"));
+
+        // A failing send is reported once, not on every tick
+        port.set_write_error(Some(std::io::ErrorKind::BrokenPipe));
+        app.run_repeat(at(200));
+        assert!(app.error_message.take().is_some());
+        app.run_repeat(at(300));
+        assert_eq!(app.error_message, None);
+        assert_eq!(app.repeat.fired, 4);
+
+        port.set_write_error(None);
+        app.send_mode = SendMode::Hex;
+        app.send_buffer = "01 03".to_string();
+        app.run_repeat(at(400));
+        assert_eq!(port.activity()[2], "write [01, 03]");
+
+        app.disconnect();
+        assert!(!app.repeat.is_running());
+        assert_eq!(app.run_repeat(at(500)), None);
+        assert_eq!(port.activity().len(), 3);
+    }
+
+    #[test]
+    fn escapes_are_sent_as_bytes_only_when_parsed() {
+        let (mut app, port) = connected_app(false);
+        app.line_ending = LineEnding::None;
+        app.send_buffer = r"\x02A\t\\".to_string();
+        app.send_data().unwrap();
+        assert_eq!(port.activity()[0], "write [5C, 78, 30, 32, 41, 5C, 74, 5C, 5C]");
+
+        app.parse_escapes = true;
+        app.send_buffer = r"\x02A\t\\".to_string();
+        app.send_data().unwrap();
+        assert_eq!(port.activity()[1], "write [02, 41, 09, 5C]");
+        assert!(app.capture_text().contains(r"TX: \x02A\t\\"), "the echo shows what was typed");
+
+        app.send_buffer = r"AT\xZZ".to_string();
+        assert_eq!(
+            app.send_data(),
+            Err(r"Invalid escape '\xZZ': expected two hex digits".to_string())
+        );
+        assert_eq!(port.activity().len(), 2);
+        assert_eq!(app.send_buffer, r"AT\xZZ", "kept to be fixed");
+    }
+
+    #[test]
+    fn connecting_reads_back_what_the_port_granted() {
+        let mut app = ComAnalyzerApp {
+            flow_control: FlowControl::Hardware,
+            ..Default::default()
+        };
+        let port = MockPort::default();
+        app.attach_port(Box::new(port.clone()), "COM3", 115_200);
+        let info = app.port_info.clone().unwrap();
+        assert_eq!(info.baud_rate, Some(9600));
+        assert_eq!(info.differences.len(), 2, "{:?}", info.differences);
+
+        app.disconnect();
+        assert!(app.port_info.is_some(), "kept for the report");
+        app.flow_control = FlowControl::None;
+        app.attach_port(Box::new(port), "COM3", 9600);
+        assert!(app.port_info.as_ref().unwrap().differences.is_empty());
+    }
+
+    #[test]
+    fn raw_recording_switches_without_losing_bytes() {
+        let dir = std::env::temp_dir().join(format!("rustcom-raw-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut app = ComAnalyzerApp {
+            logging_enabled: true,
+            recorder_dir: dir.display().to_string(),
+            ..Default::default()
+        };
+        let port = MockPort::default();
+        app.attach_port(Box::new(port.clone()), "COM3", 1_000_000);
+
+        // Numbered words arriving all the while the mode switches back and forth
+        let stream: Vec<u8> = (0..40_000u32).flat_map(|n| n.to_be_bytes()).collect();
+        let writer = {
+            let port = port.clone();
+            let stream = stream.clone();
+            std::thread::spawn(move || {
+                for chunk in stream.chunks(400) {
+                    port.push_rx(chunk);
+                    std::thread::sleep(Duration::from_micros(500));
+                }
+            })
+        };
+        let mut switches = Vec::new();
+        let mut files = Vec::new();
+        for round in 0..6 {
+            let displayed = app.bytes_received;
+            poll_until(&mut app, |app| match &app.recording {
+                Some(recording) => recording.status.bytes() > 0,
+                None => app.bytes_received > displayed,
+            });
+            if app.recording.is_none() {
+                app.recorder_gzip = round == 2;
+                app.start_recording().unwrap();
+                files.push(app.recording.as_ref().unwrap().path.clone());
+            } else {
+                app.stop_recording();
+            }
+            switches.push(app.log_entries.len());
+        }
+        writer.join().unwrap();
+        assert!(app.recording.is_none());
+
+        let recorded: Vec<Vec<u8>> = files
+            .iter()
+            .map(|path| {
+                let file = std::fs::read(path).unwrap();
+                if !path.to_string_lossy().ends_with(".gz") {
+                    return file;
+                }
+                let mut unzipped = Vec::new();
+                std::io::Read::read_to_end(&mut flate2::read::GzDecoder::new(&file[..]), &mut unzipped).unwrap();
+                unzipped
+            })
+            .collect();
+        let recorded_len: usize = recorded.iter().map(Vec::len).sum();
+        poll_until(&mut app, |app| app.bytes_received + recorded_len == stream.len());
+
+        let mut entries = Vec::new();
+        app.log_entries.for_each(|entry| entries.push(entry.data.clone())).unwrap();
+        let displayed = |range: std::ops::Range<usize>| entries[range].concat();
+        // Nothing reaches the display while recording
+        for pair in switches.chunks(2) {
+            assert!(displayed(pair[0]..pair[1]).is_empty());
+        }
+        let mut joined = displayed(0..switches[0]);
+        for (file, from) in recorded.iter().zip([1, 3, 5]) {
+            assert!(!file.is_empty());
+            joined.extend_from_slice(file);
+            let to = switches.get(from + 1).copied().unwrap_or(entries.len());
+            joined.extend(displayed(switches[from]..to));
+        }
+        assert_eq!(joined.len(), stream.len());
+        assert!(joined == stream, "bytes lost, repeated or reordered at a switch");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_last_line_announces_every_time() {
+        let mut app = ComAnalyzerApp::default();
+        app.run_action(Action::ReadLastLine);
+        assert_eq!(app.announcement, Some((0, "The terminal is empty".to_string())));
+
+        app.receive_buffer_display = "boot\nready> \n\n".to_string();
+        app.run_action(Action::ReadLastLine);
+        app.run_action(Action::ReadLastLine);
+        assert_eq!(app.announcement, Some((2, "ready>".to_string())));
+    }
+
+    #[test]
+    fn template_macros_ask_then_echo_what_was_sent() {
+        let (mut app, port) = connected_app(false);
+        app.macros = vec![Macro {
+            name: "Channel".to_string(),
+            text: r"SET CHAN {n:int(1..16)} {mode:ON|OFF=ON}\r".to_string(),
+            line_ending: Some(LineEnding::None),
+            template: true,
+            ..Macro::default()
+        }];
+        app.run_macro(0).unwrap();
+        let prompt = app.template_prompt.clone().expect("a template opens the prompt");
+        assert_eq!(prompt.values, ["", "ON"], "choices start at their default");
+        assert!(port.activity().is_empty());
+
+        assert_eq!(app.send_macro(0), Err("No value for {n}".to_string()));
+        app.send_template(0, &["7".to_string(), "off".to_string()]).unwrap();
+        assert_eq!(port.activity(), ["write [53, 45, 54, 20, 43, 48, 41, 4E, 20, 37, 20, 4F, 46, 46, 0D]"]);
+        assert!(app.capture_text().contains(r"TX [macro Channel]: SET CHAN 7 OFF\r"));
+        assert_eq!(
+            app.send_template(0, &["0".to_string()]),
+            Err("n must be 1..16".to_string())
+        );
+    }
+
+    #[test]
+    fn xmodem_receive_keeps_transfer_traffic_out_of_the_terminal() {
+        use rustcom_core::xmodem::{block, Checksum, ACK, CRC_REQUEST, EOT};
+        let (mut app, port) = connected_app(false);
+        let path = std::env::temp_dir().join(format!("rustcom-xmodem-{}.bin", std::process::id()));
+        app.xmodem_receive_path = path.display().to_string();
+        app.start_xmodem_receive().unwrap();
+        assert_eq!(port.take_written(), [CRC_REQUEST]);
+        assert_eq!(app.send_macro(0), Err(XMODEM_BUSY_ERROR.to_string()), "nothing else goes out meanwhile");
+
+        app.handle_read(ReadOutcome::Data {
+            data: block(1, b"firmware", Checksum::Crc16),
+            received_at: Local::now(),
+            arrived: Instant::now(),
+        });
+        app.feed_xmodem(&[EOT]);
+        assert_eq!(port.take_written(), [ACK, ACK]);
+        assert!(app.xmodem.is_none());
+        assert_eq!(std::fs::read(&path).unwrap(), b"firmware");
+        assert!(app.xmodem_result.as_deref().unwrap().starts_with("XMODEM receive done"));
+        assert!(!app.capture_text().contains("firmware\u{1A}"));
+        assert_eq!(app.bytes_received, 3 + 128 + 2 + 1);
+        let _ = std::fs::remove_file(&path);
+
+        // Cancelling sends CANs and leaves the port usable
+        app.start_xmodem_send().unwrap_err();
+        app.file_preview = Some(FilePreview {
+            path: path.display().to_string(),
+            size: 0,
+            firmware: None,
+            is_binary: true,
+        });
+        std::fs::write(&path, b"image").unwrap();
+        app.start_xmodem_send().unwrap();
+        app.feed_xmodem(&[CRC_REQUEST]);
+        assert_eq!(port.take_written().len(), 3 + 128 + 2);
+        app.cancel_xmodem();
+        assert_eq!(port.take_written(), [0x18, 0x18]);
+        assert!(app.xmodem_result.as_deref().unwrap().contains("failed: cancelled"));
+        app.send_buffer = "AT".to_string();
+        app.send_data().unwrap();
+        assert!(app.capture_text().contains("TX: AT"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn xmodem_keeps_the_recorder_and_queued_sends_out_and_cancels_on_disconnect() {
+        use rustcom_core::xmodem::CRC_REQUEST;
+        let (mut app, port) = connected_app(false);
+        let path = std::env::temp_dir().join(format!("rustcom-xmodem-busy-{}.bin", std::process::id()));
+        app.xmodem_receive_path = path.display().to_string();
+
+        app.tx_pending.push_back(PendingTx {
+            data: b"AT\r".to_vec(),
+            after: AfterTx::Keystroke,
+        });
+        assert_eq!(app.start_xmodem_receive(), Err("Sends are still queued by the TX rate limit".to_string()));
+        app.tx_pending.clear();
+
+        app.start_xmodem_receive().unwrap();
+        assert_eq!(port.take_written(), [CRC_REQUEST]);
+        assert_eq!(app.start_recording(), Err("Wait for the XMODEM transfer to end first".to_string()));
+        assert!(app.recording.is_none());
+
+        app.disconnect();
+        assert_eq!(port.take_written(), [0x18, 0x18], "CANs go out before the port closes");
+        assert!(app.xmodem.is_none());
+        assert!(app.xmodem_result.as_deref().unwrap().contains("failed: disconnected"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn rate_alarm_marks_a_runaway_device_and_holds_rts_until_cleared() {
+        let (mut app, port) = connected_app(false);
+        app.rate_alarm = RateAlarmConfig {
+            enabled: true,
+            threshold_kb: 1.0,
+            sustain_secs: 1.0,
+            rts: Some(true),
+            ..RateAlarmConfig::default()
+        };
+        let mut at = Instant::now();
+        let mut feed = |app: &mut ComAnalyzerApp, bytes_per_tick: usize, ticks: usize| {
+            for _ in 0..ticks {
+                at += Duration::from_millis(250);
+                app.bytes_received += bytes_per_tick;
+                assert!(app.check_rate_alarm(at));
+            }
+        };
+        feed(&mut app, 4096, 8);
+        assert!(app.rate_tracker.alarm);
+        assert!(app.capture_text().contains("RATE ALARM RX above 1 KB/s for 1s (16.0 KB/s)"));
+        assert!(app.error_message.as_deref().unwrap().starts_with("Rate alarm:"));
+        assert!(app.rts_state);
+
+        feed(&mut app, 0, 12);
+        assert!(!app.rate_tracker.alarm);
+        assert!(app.capture_text().contains("RATE ALARM cleared (0.0 KB/s)"));
+        assert!(!app.rts_state);
+        assert_eq!(port.activity(), ["rts true", "rts false"]);
+
+        app.rate_alarm.enabled = false;
+        assert!(!app.check_rate_alarm(at));
+    }
+
+    #[test]
+    fn tcp_bridge_forwards_both_ways_and_stops_with_the_port() {
+        use std::io::{Read, Write};
+
+        let mut app = ComAnalyzerApp::default();
+        app.start_tcp_bridge();
+        assert_eq!(app.error_message.as_deref(), Some("Connect before starting the TCP bridge"));
+
+        let (mut app, port) = connected_app(false);
+        app.tcp_bridge.port = 0;
+        app.start_tcp_bridge();
+        let bridge_port = app.tcp_bridge_server.as_ref().unwrap().port();
+        let mut client = std::net::TcpStream::connect(("127.0.0.1", bridge_port)).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let peer = client.local_addr().unwrap();
+
+        client.write_all(b"AT\r").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while port.activity().is_empty() {
+            assert!(Instant::now() < deadline, "client data never reached the port");
+            assert!(app.run_tcp_bridge());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(port.activity(), ["write [41, 54, 0D]"]);
+        let terminal = app.capture_text();
+        assert!(terminal.contains(&format!("TCP bridge: {} connected", peer)));
+        assert!(terminal.contains(&format!("TX [tcp {}]: AT\\r", peer)));
+
+        app.handle_read(ReadOutcome::Data {
+            data: b"OK\r\n".to_vec(),
+            received_at: Local::now(),
+            arrived: Instant::now(),
+        });
+        let mut buf = [0u8; 4];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"OK\r\n");
+
+        app.disconnect();
+        assert!(app.tcp_bridge_server.is_none());
+        assert!(!app.run_tcp_bridge());
+        assert!(app.capture_text().contains("TCP bridge stopped, 1 client(s) disconnected"));
+        let mut rest = Vec::new();
+        assert_eq!(client.read_to_end(&mut rest).unwrap(), 0);
+    }
+
+    #[test]
+    fn utf16_sessions_are_detected_and_decoded_across_reads() {
+        let utf16 = |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+
+        // A byte order mark, switched to straight away; reads of odd length
+        // and a surrogate pair split between two reads still decode
+        let mut app = ComAnalyzerApp {
+            encoding_detection: EncodingDetection::Apply,
+            ..Default::default()
+        };
+        let mut data = vec![0xFF, 0xFE];
+        data.extend(utf16("Ready 🌡\r\n"));
+        feed(&mut app, &data[..7]);
+        feed(&mut app, &data[7..15]);
+        feed(&mut app, &data[15..]);
+        assert_eq!(app.display_encoding, TextEncoding::Utf16Le);
+        // The buffer keeps the wire bytes; the text view decodes them
+        assert!(app.receive_buffer.ends_with(&data));
+        let terminal = app.receive_buffer_display.clone();
+        assert!(terminal.contains("Encoding: RX looks like UTF-16LE (byte order mark), decoding RX as UTF-16LE\n"));
+        assert!(terminal.ends_with("Ready 🌡\r\n"), "{:?}", terminal);
+        app.view_mode = ViewMode::Hex;
+        app.update_display_buffer();
+        assert!(app.receive_buffer_display.contains("FF FE 52 00 65 00"), "{}", app.receive_buffer_display);
+
+        // Without a mark the NUL pattern is only offered by default; once
+        // accepted, what is already in the terminal is decoded too, and the
+        // filter splits and matches the decoded lines
+        let mut app = ComAnalyzerApp::default();
+        feed(&mut app, &utf16("Controller up\r\n"));
+        assert_eq!(app.display_encoding, TextEncoding::Utf8);
+        assert_eq!(app.encoding_suggestion, Some(TextEncoding::Utf16Le));
+        assert!(app.capture_text().contains("(NUL every other byte), offering UTF-16LE decoding"));
+        app.accept_encoding_suggestion();
+        app.filter_enabled = true;
+        app.filter_rules = vec![rustcom_core::filter::FilterRule::new("OK")];
+        feed(&mut app, &utf16("skipped\r\nOK\r"));
+        feed(&mut app, &utf16("\n"));
+        assert!(app.receive_buffer.ends_with(&utf16("OK\r\n")), "filtered on the decoded lines");
+        assert!(!app.receive_buffer_display.contains("skipped"));
+        assert!(app.receive_buffer_display.contains("Controller up\r\n"), "{:?}", app.receive_buffer_display);
+        assert!(app.receive_buffer_display.ends_with("Encoding: decoding RX as UTF-16LE\nOK\r\n"));
+    }
+
+    #[test]
+    fn tcp_client_mode_reads_sends_and_reconnects_through_the_port_path() {
+        use std::io::{Read, Write};
+
+        let mut app = ComAnalyzerApp {
+            connection_type: ConnectionType::TcpClient,
+            auto_reconnect: true,
+            ..Default::default()
+        };
+        assert!(!app.has_connect_target());
+        app.connect();
+        assert_eq!(app.error_message.as_deref(), Some("Please enter a host:port"));
+
+        // Connecting runs on a worker; a refused first attempt switches to
+        // retrying when asked to
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        app.tcp_address = closed.local_addr().unwrap().to_string();
+        drop(closed);
+        app.retry_initial_connect = true;
+        app.connect_manually();
+        assert!(!app.connected && app.connecting());
+        assert_eq!(app.connect_action(), crate::actions::Action::CancelConnecting);
+        finish_connecting(&mut app);
+        assert!(app.reconnecting);
+        assert!(app.error_message.as_deref().is_some_and(|e| e.starts_with("Failed to connect")));
+        assert!(app.capture_text().contains("retrying every"));
+        app.cancel_reconnect();
+        app.retry_initial_connect = false;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        app.tcp_address = listener.local_addr().unwrap().to_string();
+        app.connect_manually();
+        finish_connecting(&mut app);
+        assert!(app.connected);
+        let (mut server, _) = listener.accept().unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert!(app.capture_text().contains(&format!("Connected to {} over TCP", app.tcp_address)));
+
+        server.write_all(b"ready\r\n").unwrap();
+        poll_until(&mut app, |app| app.capture_text().contains("ready"));
+        app.send_buffer = "AT".to_string();
+        app.send_data().unwrap();
+        let mut received = [0u8; 3];
+        server.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"AT\r");
+
+        // The server going away is a lost connection, retried like a port
+        drop(server);
+        poll_until(&mut app, |app| !app.connected);
+        assert!(app.reconnecting);
+        assert!(app.capture_text().contains(&format!("Connection lost to {}", app.tcp_address)));
+        app.attempt_reconnect();
+        finish_connecting(&mut app);
+        assert!(app.connected);
+        assert!(!app.reconnecting);
+        assert!(app.capture_text().contains("Reconnected successfully"));
+        let (mut server, _) = listener.accept().unwrap();
+        server.write_all(b"again\r\n").unwrap();
+        poll_until(&mut app, |app| app.capture_text().contains("again"));
+        app.disconnect();
+    }
+
+    #[test]
+    fn rfc2217_mode_configures_the_remote_port_or_falls_back_with_a_warning() {
+        use rustcom_core::rfc2217::{DO, DONT, IAC, SB, SE};
+        use std::io::{Read, Write};
+
+        // A server that takes COM-PORT-OPTION and hands back what it was sent
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut app = ComAnalyzerApp {
+            connection_type: ConnectionType::Rfc2217,
+            tcp_address: listener.local_addr().unwrap().to_string(),
+            baud_rate: "57600".to_string(),
+            ..Default::default()
+        };
+        let server = std::thread::spawn(move || {
+            let (mut server, _) = listener.accept().unwrap();
+            server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let mut negotiation = [0u8; 12];
+            server.read_exact(&mut negotiation).unwrap();
+            server.write_all(&[IAC, DO, 44]).unwrap();
+            let mut settings = [0u8; 52];
+            server.read_exact(&mut settings).unwrap();
+            (server, settings)
+        });
+        app.connect();
+        assert!(app.connecting() && !app.connected, "the negotiation waits on the worker");
+        finish_connecting(&mut app);
+        let (mut server, settings) = server.join().unwrap();
+        assert!(app.connected);
+        assert_eq!(app.error_message, None);
+        assert!(app.capture_text().contains("over RFC 2217 at 57600 baud"));
+        assert_eq!(settings[..10], [IAC, SB, 44, 1, 0x00, 0x00, 0xE1, 0x00, IAC, SE]);
+
+        app.set_dtr(false).unwrap();
+        let mut command = [0u8; 7];
+        server.read_exact(&mut command).unwrap();
+        assert_eq!(command, [IAC, SB, 44, 5, 9, IAC, SE]);
+        server.write_all(&[b'>', IAC, IAC, b'\n']).unwrap();
+        poll_until(&mut app, |app| app.bytes_received == 3);
+        app.disconnect();
+
+        // A Telnet server without COM port control
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        app.tcp_address = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (mut server, _) = listener.accept().unwrap();
+            server.write_all(&[IAC, DONT, 44]).unwrap();
+            server
+        });
+        app.connect();
+        finish_connecting(&mut app);
+        let _server = server.join().unwrap();
+        assert!(app.connected);
+        let warning = "RFC 2217: the server refused COM port control; continuing without COM port control";
+        assert_eq!(app.error_message.as_deref(), Some(warning));
+        assert!(app.capture_text().contains(warning));
+        app.disconnect();
+    }
+
+    #[test]
+    fn saved_logs_replay_as_an_offline_session() {
+        let path = std::env::temp_dir().join(format!("rustcom-offline-{}.log", std::process::id()));
+        std::fs::write(
+            &path,
+            "garbage from an editor\n\
+             [2024-03-01 10:00:00.000] RX: DBG tick\nERR overheat\n\n\
+             [2024-03-01 10:00:00.500] TX: AT\r\n\
+             [2024-03-01 10:00:01.000] EV: FAULT\n\
+             [2024-03-01 10:00:02.000] RX: ERR again\n\n",
+        )
+        .unwrap();
+        let stream_path = std::env::temp_dir().join(format!("rustcom-offline-stream-{}.log", std::process::id()));
+        let mut app = ComAnalyzerApp {
+            filter_enabled: true,
+            filter_rules: vec![rustcom_core::filter::FilterRule::new("ERR")],
+            log_streaming: true,
+            log_file_path: stream_path.to_string_lossy().into_owned(),
+            log_import_path: path.to_string_lossy().into_owned(),
+            rx_frame_budget: 16,
+            ..Default::default()
+        };
+        app.set_logging(true);
+        app.connected = true;
+        assert_eq!(app.open_offline_log(), Err("Disconnect before opening a log".to_string()));
+        app.connected = false;
+        app.receive_buffer.extend_from_slice(b"old session\n");
+
+        // Replayed a frame's budget at a time
+        app.open_offline_log().unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(app.offline_replay.is_some());
+        while app.step_offline_replay() {}
+        assert!(app.offline_replay.is_none());
+        let terminal = app.capture_text();
+        assert!(!terminal.contains("old session"));
+        assert!(terminal.contains("(Text, 4 entries, 1 unreadable skipped)"));
+        assert!(!terminal.contains("DBG tick"), "filtered like live data");
+        let order = ["ERR overheat", "TX: AT\\r", "10:00:01.000] FAULT", "ERR again"];
+        let at: Vec<usize> = order.iter().map(|text| terminal.find(text).unwrap()).collect();
+        assert!(at.windows(2).all(|pair| pair[0] < pair[1]), "{}", terminal);
+        assert_eq!((app.bytes_received, app.bytes_sent), (32, 3));
+
+        // Logged again with the times from the file
+        let mut logged = Vec::new();
+        app.log_entries
+            .for_each(|entry| logged.push(format!("{} {}", entry.timestamp, entry.direction.as_str())))
+            .unwrap();
+        assert_eq!(
+            logged,
+            [
+                "2024-03-01 10:00:00.000 RX",
+                "2024-03-01 10:00:00.500 TX",
+                "2024-03-01 10:00:01.000 EV",
+                "2024-03-01 10:00:02.000 RX"
+            ]
+        );
+        let session = app.offline_session.as_ref().unwrap();
+        assert_eq!((session.entries, session.skipped), (4, 1));
+
+        // Old data stays out of the streaming log and the live metrics
+        assert_eq!(app.log_stream.as_ref().unwrap().entries(), 0);
+        assert_eq!(app.metrics.bytes_received.load(Ordering::Relaxed), 0);
+        app.set_logging(false);
+        let _ = std::fs::remove_file(&stream_path);
+
+        app.log_import_path = "no-such-file.log".to_string();
+        assert!(app.open_offline_log().unwrap_err().starts_with("Cannot read no-such-file.log"));
+    }
+
+    #[test]
+    fn auto_view_picks_text_or_hex_per_reassembled_line() {
+        use crate::app::ViewMode;
+
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), std::time::Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+        let mut data = b"boot ok\r\n".to_vec();
+        data.extend_from_slice(&[0x02, 0x10, 0x00, 0xFF, 0x80, 0x7F, 0x03, b'\n']);
+        data.extend_from_slice(b"T=21\0\n");
+        let expected = "TXT│ boot ok\r\nHEX│ 02 10 00 FF 80 7F 03 0A\nHEX│ 54 3D 32 31 00 0A\n";
+
+        let mut whole = ComAnalyzerApp {
+            view_mode: ViewMode::Auto,
+            ..Default::default()
+        };
+        feed(&mut whole, &data);
+        assert_eq!(whole.receive_buffer_display, expected);
+
+        // Reads splitting the binary line anywhere end up the same
+        for size in [1, 3, 5] {
+            let mut app = ComAnalyzerApp {
+                view_mode: ViewMode::Auto,
+                ..Default::default()
+            };
+            for chunk in data.chunks(size) {
+                feed(&mut app, chunk);
+                let appended = (app.receive_buffer_display.clone(), app.display_lines.clone());
+                app.update_display_buffer();
+                assert_eq!(appended, (app.receive_buffer_display.clone(), app.display_lines.clone()));
+            }
+            assert_eq!(app.receive_buffer_display, expected, "reads of {} bytes", size);
+            assert_eq!(app.display_lines, whole.display_lines);
+        }
+        let offsets: Vec<u64> = whole.display_lines.iter().map(|line| line.unwrap().offset).collect();
+        assert_eq!(offsets, [0, 9, 17]);
+
+        // A lower threshold takes the 80% printable line for text
+        whole.auto_view.printable_percent = 80;
+        whole.extend_display_buffer();
+        assert!(whole.receive_buffer_display.ends_with("0A\nTXT│ T=21<00>\n"), "{}", whole.receive_buffer_display);
+    }
+
+    #[test]
+    fn streamed_logging_writes_entries_as_they_arrive() {
+        let path = std::env::temp_dir().join(format!("rustcom-stream-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let feed = |app: &mut ComAnalyzerApp, data: &[u8]| {
+            app.rx_queue.push(data, Local::now(), Instant::now(), Duration::ZERO);
+            app.process_rx_frame();
+        };
+        let mut app = ComAnalyzerApp {
+            log_streaming: true,
+            log_memory_entries: 2,
+            log_file_path: path.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        app.set_logging(true);
+        assert!(app.logging_enabled && app.log_stream.is_some());
+        assert!(!app.action_enabled(Action::SaveLog), "the file is already being written");
+        for line in [&b"one\n"[..], b"two\n", b"three\n"] {
+            feed(&mut app, line);
+        }
+        // Only the newest entries stay in memory, all of them in the file
+        assert_eq!(app.log_entries.len(), 2);
+        assert_eq!(app.log_stream.as_ref().unwrap().entries(), 3);
+        assert!(app.check_log_stream());
+
+        app.set_logging(false);
+        assert!(app.log_stream.is_none() && app.log_entries.keep_last.is_none());
+        assert!(app.error_message.as_deref().unwrap().starts_with("Log closed: 3 entries"));
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(["one", "two", "three"].iter().all(|text| written.contains(&format!("RX: {}", text))), "{}", written);
+        feed(&mut app, b"not logged\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), written);
+        let _ = std::fs::remove_file(&path);
+
+        // A file that cannot be opened leaves logging off
+        app.log_file_path = std::env::temp_dir().join("no-such-dir").join("x.log").to_string_lossy().into_owned();
+        app.set_logging(true);
+        assert!(!app.logging_enabled && app.log_stream.is_none());
+        assert!(app.error_message.as_deref().unwrap().starts_with("Logging not started: Cannot open log file"));
+    }
 }
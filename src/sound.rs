@@ -0,0 +1,366 @@
+// Sound monitor: an audible tick per frame or per N bytes, so bus activity can
+// be heard while looking at the hardware. A flood turns into a steady hum
+// rather than thousands of overlapping clicks.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use rodio::source::{SineWave, Source};
+use rodio::{OutputStream, OutputStreamHandle, Sink};
+
+/// Clicks closer together than this are a flood, heard as a hum instead.
+pub const MIN_CLICK_GAP: Duration = Duration::from_millis(50);
+/// How long a hum lasts after the last event that fed it.
+pub const HUM_HOLD: Duration = Duration::from_millis(150);
+pub const DEFAULT_SOUND_BYTES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoundEvent {
+    Rx,
+    Tx,
+    Error,
+}
+
+impl SoundEvent {
+    pub const ALL: [SoundEvent; 3] = [SoundEvent::Rx, SoundEvent::Tx, SoundEvent::Error];
+
+    fn index(self) -> usize {
+        match self {
+            SoundEvent::Rx => 0,
+            SoundEvent::Tx => 1,
+            SoundEvent::Error => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoundTheme {
+    Clicks,
+    Tones,
+    Soft,
+}
+
+impl SoundTheme {
+    pub const ALL: [SoundTheme; 3] = [SoundTheme::Clicks, SoundTheme::Tones, SoundTheme::Soft];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            SoundTheme::Clicks => "Clicks",
+            SoundTheme::Tones => "Tones",
+            SoundTheme::Soft => "Soft",
+        }
+    }
+
+    /// Pitch in Hz and length of the sound for `event`.
+    fn voice(self, event: SoundEvent) -> (f32, Duration) {
+        let ms = Duration::from_millis;
+        match (self, event) {
+            (SoundTheme::Clicks, SoundEvent::Rx) => (2400.0, ms(4)),
+            (SoundTheme::Clicks, SoundEvent::Tx) => (1500.0, ms(4)),
+            (SoundTheme::Clicks, SoundEvent::Error) => (400.0, ms(80)),
+            (SoundTheme::Tones, SoundEvent::Rx) => (880.0, ms(30)),
+            (SoundTheme::Tones, SoundEvent::Tx) => (660.0, ms(30)),
+            (SoundTheme::Tones, SoundEvent::Error) => (220.0, ms(200)),
+            (SoundTheme::Soft, SoundEvent::Rx) => (520.0, ms(15)),
+            (SoundTheme::Soft, SoundEvent::Tx) => (390.0, ms(15)),
+            (SoundTheme::Soft, SoundEvent::Error) => (180.0, ms(120)),
+        }
+    }
+}
+
+/// What makes RX and TX tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoundTrigger {
+    /// Each decoded frame, or each read or write without a decoder.
+    Frame,
+    /// Every `SoundSettings::every_bytes` bytes.
+    Bytes,
+}
+
+impl SoundTrigger {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SoundTrigger::Frame => "Per frame",
+            SoundTrigger::Bytes => "Per N bytes",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundSettings {
+    pub enabled: bool,
+    pub theme: SoundTheme,
+    /// 0.0 to 1.0.
+    pub volume: f32,
+    pub trigger: SoundTrigger,
+    pub every_bytes: usize,
+    pub rx: bool,
+    pub tx: bool,
+    /// Malformed frames, such as CRC failures.
+    pub errors: bool,
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            theme: SoundTheme::Clicks,
+            volume: 0.5,
+            trigger: SoundTrigger::Frame,
+            every_bytes: DEFAULT_SOUND_BYTES,
+            rx: true,
+            tx: true,
+            errors: true,
+        }
+    }
+}
+
+impl SoundSettings {
+    fn plays(&self, event: SoundEvent) -> bool {
+        match event {
+            SoundEvent::Rx => self.rx,
+            SoundEvent::Tx => self.tx,
+            SoundEvent::Error => self.errors,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cue {
+    Click(SoundEvent),
+    Hum(SoundEvent, bool),
+}
+
+/// Keeps one event's clicks apart: a click that would follow the last within
+/// `MIN_CLICK_GAP` starts or extends a hum instead.
+#[derive(Debug, Default)]
+struct ClickLimiter {
+    last_click: Option<Instant>,
+    hum_until: Option<Instant>,
+}
+
+impl ClickLimiter {
+    /// `count` events at `now`.
+    fn hit(&mut self, event: SoundEvent, count: usize, now: Instant) -> Option<Cue> {
+        let humming = self.hum_until.is_some_and(|until| now < until);
+        let crowded = self.last_click.is_some_and(|last| now.saturating_duration_since(last) < MIN_CLICK_GAP);
+        if humming || crowded || count > 1 {
+            self.hum_until = Some(now + HUM_HOLD);
+            return (!humming).then_some(Cue::Hum(event, true));
+        }
+        self.last_click = Some(now);
+        Some(Cue::Click(event))
+    }
+
+    /// Ends a hum once its events have stopped.
+    fn expire(&mut self, event: SoundEvent, now: Instant) -> Option<Cue> {
+        let until = self.hum_until?;
+        if now < until {
+            return None;
+        }
+        self.hum_until = None;
+        // The hum ran to now; a click right after it would stand out
+        self.last_click = Some(now);
+        Some(Cue::Hum(event, false))
+    }
+}
+
+/// Turns traffic into cues, without any audio, so the pacing can be tested.
+#[derive(Debug, Default)]
+pub struct SoundCues {
+    pub settings: SoundSettings,
+    limiters: [ClickLimiter; 3],
+    /// RX and TX bytes not yet ticked for, in `SoundTrigger::Bytes`.
+    pending: [usize; 2],
+}
+
+impl SoundCues {
+    pub fn new(settings: SoundSettings) -> Self {
+        Self {
+            settings,
+            ..Self::default()
+        }
+    }
+
+    /// `bytes` read or written. Without a decoder a read is a frame; with
+    /// one (`decoded`), RX frames come from `frame`.
+    pub fn data(&mut self, event: SoundEvent, bytes: usize, decoded: bool, now: Instant) -> Option<Cue> {
+        if !self.settings.plays(event) || bytes == 0 {
+            return None;
+        }
+        match self.settings.trigger {
+            SoundTrigger::Frame if decoded && event == SoundEvent::Rx => None,
+            SoundTrigger::Frame => self.limiters[event.index()].hit(event, 1, now),
+            SoundTrigger::Bytes => {
+                let every = self.settings.every_bytes.max(1);
+                let pending = &mut self.pending[event.index()];
+                *pending += bytes;
+                let ticks = *pending / every;
+                *pending %= every;
+                (ticks > 0).then(|| self.limiters[event.index()].hit(event, ticks, now)).flatten()
+            }
+        }
+    }
+
+    /// A decoded RX frame, with its error if it was malformed.
+    pub fn frame(&mut self, error: bool, now: Instant) -> Option<Cue> {
+        if error && self.settings.errors {
+            return self.limiters[SoundEvent::Error.index()].hit(SoundEvent::Error, 1, now);
+        }
+        if self.settings.trigger == SoundTrigger::Frame && self.settings.rx {
+            return self.limiters[SoundEvent::Rx.index()].hit(SoundEvent::Rx, 1, now);
+        }
+        None
+    }
+
+    /// Hums whose traffic has stopped.
+    pub fn expire(&mut self, now: Instant) -> Vec<Cue> {
+        SoundEvent::ALL
+            .into_iter()
+            .filter_map(|event| self.limiters[event.index()].expire(event, now))
+            .collect()
+    }
+}
+
+enum Command {
+    Cue(Cue),
+    Voice(SoundTheme, f32),
+}
+
+/// Plays cues on an audio thread, which owns the output device.
+pub struct SoundMonitor {
+    pub cues: SoundCues,
+    commands: Sender<Command>,
+}
+
+impl SoundMonitor {
+    /// Open the default audio output. Fails when there is none.
+    pub fn start(settings: SoundSettings) -> Result<Self, String> {
+        let (commands, received) = mpsc::channel();
+        let (opened, result) = mpsc::sync_channel(1);
+        let (theme, volume) = (settings.theme, settings.volume);
+        std::thread::spawn(move || match OutputStream::try_default() {
+            Ok((_stream, handle)) => {
+                let _ = opened.send(Ok(()));
+                play(&handle, received, theme, volume);
+            }
+            Err(e) => {
+                let _ = opened.send(Err(format!("No audio output: {}", e)));
+            }
+        });
+        result.recv().map_err(|_| "Audio thread failed".to_string())??;
+        Ok(Self {
+            cues: SoundCues::new(settings),
+            commands,
+        })
+    }
+
+    /// Take new settings; theme and volume changes reach the audio thread.
+    pub fn configure(&mut self, settings: &SoundSettings) {
+        if (settings.theme, settings.volume) != (self.cues.settings.theme, self.cues.settings.volume) {
+            let _ = self.commands.send(Command::Voice(settings.theme, settings.volume));
+        }
+        self.cues.settings = settings.clone();
+    }
+
+    pub fn send(&self, cue: Option<Cue>) {
+        if let Some(cue) = cue {
+            let _ = self.commands.send(Command::Cue(cue));
+        }
+    }
+
+    pub fn expire(&mut self, now: Instant) {
+        for cue in self.cues.expire(now) {
+            self.send(Some(cue));
+        }
+    }
+}
+
+/// The audio thread: runs until the monitor is dropped.
+fn play(handle: &OutputStreamHandle, commands: Receiver<Command>, mut theme: SoundTheme, mut volume: f32) {
+    let mut hums: [Option<Sink>; 3] = Default::default();
+    for command in commands {
+        match command {
+            Command::Voice(new_theme, new_volume) => {
+                (theme, volume) = (new_theme, new_volume);
+                for hum in hums.iter().flatten() {
+                    hum.set_volume(volume / 2.0);
+                }
+            }
+            Command::Cue(Cue::Click(event)) => {
+                let (pitch, length) = theme.voice(event);
+                let click = SineWave::new(pitch)
+                    .take_duration(length)
+                    .fade_in(length / 4)
+                    .amplify(volume);
+                let _ = handle.play_raw(click);
+            }
+            Command::Cue(Cue::Hum(event, on)) => {
+                let slot = &mut hums[event.index()];
+                if let Some(hum) = slot.take() {
+                    hum.stop();
+                }
+                if on {
+                    if let Ok(sink) = Sink::try_new(handle) {
+                        sink.set_volume(volume / 2.0);
+                        sink.append(SineWave::new(theme.voice(event).0).fade_in(Duration::from_millis(10)));
+                        *slot = Some(sink);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_flood_hums_instead_of_clicking() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut cues = SoundCues::new(SoundSettings::default());
+        assert_eq!(cues.data(SoundEvent::Rx, 10, false, at(0)), Some(Cue::Click(SoundEvent::Rx)));
+        assert_eq!(cues.data(SoundEvent::Rx, 10, false, at(100)), Some(Cue::Click(SoundEvent::Rx)));
+        // TX keeps its own pace
+        assert_eq!(cues.data(SoundEvent::Tx, 1, false, at(101)), Some(Cue::Click(SoundEvent::Tx)));
+
+        // A thousand reads a second: one hum, held while they keep coming
+        assert_eq!(cues.data(SoundEvent::Rx, 10, false, at(120)), Some(Cue::Hum(SoundEvent::Rx, true)));
+        for ms in 121..400 {
+            assert_eq!(cues.data(SoundEvent::Rx, 10, false, at(ms)), None);
+        }
+        assert!(cues.expire(at(400)).is_empty());
+        assert_eq!(cues.expire(at(399 + 150)), [Cue::Hum(SoundEvent::Rx, false)]);
+        assert!(cues.expire(at(600)).is_empty());
+        assert_eq!(cues.data(SoundEvent::Rx, 10, false, at(700)), Some(Cue::Click(SoundEvent::Rx)));
+    }
+
+    #[test]
+    fn ticks_per_n_bytes_or_per_decoded_frame() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut cues = SoundCues::new(SoundSettings {
+            trigger: SoundTrigger::Bytes,
+            every_bytes: 100,
+            ..SoundSettings::default()
+        });
+        assert_eq!(cues.data(SoundEvent::Rx, 60, false, at(0)), None);
+        assert_eq!(cues.data(SoundEvent::Rx, 60, false, at(100)), Some(Cue::Click(SoundEvent::Rx)));
+        assert_eq!(cues.data(SoundEvent::Rx, 79, false, at(200)), None);
+        // Several ticks' worth at once is a flood
+        assert_eq!(cues.data(SoundEvent::Rx, 500, false, at(300)), Some(Cue::Hum(SoundEvent::Rx, true)));
+        assert_eq!(cues.frame(false, at(400)), None, "frames do not tick per N bytes");
+
+        let mut cues = SoundCues::new(SoundSettings::default());
+        assert_eq!(cues.data(SoundEvent::Rx, 8, true, at(0)), None, "a decoder's frames tick instead");
+        assert_eq!(cues.frame(false, at(0)), Some(Cue::Click(SoundEvent::Rx)));
+        assert_eq!(cues.frame(true, at(10)), Some(Cue::Click(SoundEvent::Error)));
+
+        cues.settings.errors = false;
+        cues.settings.tx = false;
+        assert_eq!(cues.frame(true, at(100)), Some(Cue::Click(SoundEvent::Rx)));
+        assert_eq!(cues.data(SoundEvent::Tx, 8, false, at(100)), None);
+    }
+}
@@ -0,0 +1,227 @@
+// Session statistics: burst size and inter-burst gap distributions, and the
+// TX→RX echo latency
+
+use std::time::{Duration, Instant};
+
+use rustcom_core::burst::{self, Burst};
+
+/// Per-burst records kept for CSV export. The distributions keep counting past this.
+pub const MAX_BURST_RECORDS: usize = 100_000;
+
+/// Values kept sorted as they arrive so percentiles are cheap to read every frame.
+#[derive(Default)]
+pub struct Distribution {
+    sorted: Vec<f64>,
+    sum: f64,
+}
+
+impl Distribution {
+    pub fn push(&mut self, value: f64) {
+        let index = self.sorted.partition_point(|&v| v <= value);
+        self.sorted.insert(index, value);
+        self.sum += value;
+    }
+
+    pub fn count(&self) -> usize {
+        self.sorted.len()
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        self.sorted.first().copied()
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        self.sorted.last().copied()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (!self.sorted.is_empty()).then(|| self.sum / self.sorted.len() as f64)
+    }
+
+    /// Nearest-rank percentile, `p` in 0..=100.
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        if self.sorted.is_empty() {
+            return None;
+        }
+        let rank = ((p / 100.0) * self.sorted.len() as f64).ceil() as usize;
+        Some(self.sorted[rank.clamp(1, self.sorted.len()) - 1])
+    }
+}
+
+#[derive(Default)]
+pub struct BurstStats {
+    /// Burst sizes in bytes.
+    pub sizes: Distribution,
+    /// Idle gaps before each burst, in milliseconds.
+    pub gaps: Distribution,
+    records: Vec<Burst>,
+    pub dropped_records: usize,
+}
+
+impl BurstStats {
+    pub fn record(&mut self, burst: &Burst) {
+        self.sizes.push(burst.size as f64);
+        if let Some(gap) = burst.gap_before {
+            self.gaps.push(burst::ms(gap));
+        }
+        if self.records.len() < MAX_BURST_RECORDS {
+            self.records.push(burst.clone());
+        } else {
+            self.dropped_records += 1;
+        }
+    }
+
+    pub fn record_count(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("start_time,size_bytes,duration_ms,gap_before_ms\n");
+        for burst in &self.records {
+            let gap = burst
+                .gap_before
+                .map(|gap| format!("{:.3}", burst::ms(gap)))
+                .unwrap_or_default();
+            csv.push_str(&format!(
+                "{},{},{:.3},{}\n",
+                burst.started_at.format("%Y-%m-%d %H:%M:%S%.3f"),
+                burst.size,
+                burst::ms(burst.duration),
+                gap
+            ));
+        }
+        csv
+    }
+
+    /// Write the per-burst records to `<stem>.csv` and return the file name.
+    pub fn save_csv(&self, stem: &str) -> Result<String, String> {
+        let filename = format!("{}.csv", stem);
+        std::fs::write(&filename, self.to_csv())
+            .map_err(|e| format!("Cannot write {}: {}", filename, e))?;
+        Ok(filename)
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Time from a write returning to the first read after it, both stamped on
+/// the I/O path rather than when the UI gets to them. A second write before
+/// the answer restarts the timing.
+#[derive(Default)]
+pub struct EchoLatency {
+    sent: Option<Instant>,
+    /// In milliseconds.
+    pub latencies: Distribution,
+    pub last: Option<Duration>,
+}
+
+impl EchoLatency {
+    pub fn note_tx(&mut self, at: Instant) {
+        self.sent = Some(at);
+    }
+
+    /// Time the read at `at` against the write waiting for an answer.
+    pub fn note_rx(&mut self, at: Instant) -> Option<Duration> {
+        let latency = at.saturating_duration_since(self.sent.take()?);
+        self.latencies.push(burst::ms(latency));
+        self.last = Some(latency);
+        Some(latency)
+    }
+
+    /// Stop waiting for an answer, e.g. after a reconnect.
+    pub fn forget_tx(&mut self) {
+        self.sent = None;
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+
+    #[test]
+    fn distribution_summary() {
+        let mut dist = Distribution::default();
+        assert_eq!(dist.mean(), None);
+        assert_eq!(dist.percentile(95.0), None);
+
+        for value in [5.0, 1.0, 4.0, 2.0, 3.0] {
+            dist.push(value);
+        }
+        assert_eq!(dist.count(), 5);
+        assert_eq!(dist.min(), Some(1.0));
+        assert_eq!(dist.max(), Some(5.0));
+        assert_eq!(dist.mean(), Some(3.0));
+        assert_eq!(dist.percentile(50.0), Some(3.0));
+        assert_eq!(dist.percentile(95.0), Some(5.0));
+        assert_eq!(dist.percentile(0.0), Some(1.0));
+    }
+
+    #[test]
+    fn p95_of_one_to_hundred() {
+        let mut dist = Distribution::default();
+        for value in (1..=100).rev() {
+            dist.push(value as f64);
+        }
+        assert_eq!(dist.percentile(95.0), Some(95.0));
+    }
+
+    #[test]
+    fn records_bursts_and_exports_csv() {
+        let started_at = Local::now();
+        let mut stats = BurstStats::default();
+        stats.record(&Burst {
+            started_at,
+            size: 82,
+            duration: Duration::from_micros(3100),
+            gap_before: None,
+        });
+        stats.record(&Burst {
+            started_at,
+            size: 8,
+            duration: Duration::ZERO,
+            gap_before: Some(Duration::from_millis(250)),
+        });
+
+        assert_eq!(stats.sizes.count(), 2);
+        assert_eq!(stats.gaps.count(), 1);
+        assert_eq!(stats.gaps.mean(), Some(250.0));
+
+        let csv = stats.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "start_time,size_bytes,duration_ms,gap_before_ms");
+        assert!(lines[1].ends_with(",82,3.100,"));
+        assert!(lines[2].ends_with(",8,0.000,250.000"));
+
+        stats.reset();
+        assert_eq!(stats.record_count(), 0);
+        assert_eq!(stats.sizes.count(), 0);
+    }
+
+    #[test]
+    fn echo_latency_times_the_first_read_after_the_last_write() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut echo = EchoLatency::default();
+        assert_eq!(echo.note_rx(at(1)), None, "nothing sent yet");
+
+        echo.note_tx(at(10));
+        echo.note_tx(at(20));
+        assert_eq!(echo.note_rx(at(23)), Some(Duration::from_millis(3)));
+        assert_eq!(echo.note_rx(at(24)), None, "the rest of the answer");
+
+        echo.note_tx(at(30));
+        echo.forget_tx();
+        assert_eq!(echo.note_rx(at(90)), None);
+        echo.note_tx(at(100));
+        echo.note_rx(at(107));
+        assert_eq!((echo.latencies.count(), echo.latencies.mean()), (2, Some(5.0)));
+        assert_eq!(echo.last, Some(Duration::from_millis(7)));
+    }
+}
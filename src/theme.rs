@@ -0,0 +1,113 @@
+// Dark/light theme selection and theme-aware accent colours
+
+use eframe::egui::{ecolor::Hsva, Color32};
+use eframe::Theme;
+use rustcom_core::settings::ThemeSetting;
+
+/// The theme to show. When the OS theme is unknown, dark is used.
+pub fn resolve(setting: ThemeSetting, system: Option<Theme>) -> Theme {
+    match setting {
+        ThemeSetting::System => system.unwrap_or(Theme::Dark),
+        ThemeSetting::Dark => Theme::Dark,
+        ThemeSetting::Light => Theme::Light,
+    }
+}
+
+/// Accent colours used for status text and terminal highlights, chosen to stay
+/// readable on the current background.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub error: Color32,
+    pub warning: Color32,
+    pub ok: Color32,
+    /// Event markers and timing violations.
+    pub marker: Color32,
+    dark: bool,
+}
+
+impl Palette {
+    pub fn new(theme: Theme) -> Self {
+        match theme {
+            Theme::Dark => Self {
+                error: Color32::from_rgb(255, 100, 100),
+                warning: Color32::YELLOW,
+                ok: Color32::GREEN,
+                marker: Color32::from_rgb(255, 170, 60),
+                dark: true,
+            },
+            Theme::Light => Self {
+                error: Color32::from_rgb(190, 20, 20),
+                warning: Color32::from_rgb(150, 100, 0),
+                ok: Color32::from_rgb(0, 120, 0),
+                marker: Color32::from_rgb(190, 90, 0),
+                dark: false,
+            },
+        }
+    }
+
+    /// A distinct, stable colour per Modbus slave address.
+    pub fn slave(&self, slave: u8) -> Color32 {
+        let hue = (slave as f32 * 0.618_034).fract();
+        if self.dark {
+            Hsva::new(hue, 0.55, 1.0, 1.0).into()
+        } else {
+            Hsva::new(hue, 0.9, 0.25, 1.0).into()
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new(Theme::Dark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// WCAG relative luminance.
+    fn luminance(color: Color32) -> f32 {
+        let channel = |c: u8| {
+            let c = c as f32 / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * channel(color.r()) + 0.7152 * channel(color.g()) + 0.0722 * channel(color.b())
+    }
+
+    fn contrast(a: Color32, b: Color32) -> f32 {
+        let (la, lb) = (luminance(a), luminance(b));
+        (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+    }
+
+    #[test]
+    fn system_setting_follows_os_and_defaults_to_dark() {
+        assert_eq!(resolve(ThemeSetting::System, Some(Theme::Light)), Theme::Light);
+        assert_eq!(resolve(ThemeSetting::System, None), Theme::Dark);
+        assert_eq!(resolve(ThemeSetting::Dark, Some(Theme::Light)), Theme::Dark);
+        assert_eq!(resolve(ThemeSetting::Light, Some(Theme::Dark)), Theme::Light);
+    }
+
+    #[test]
+    fn accents_are_readable_on_their_background() {
+        for theme in [Theme::Dark, Theme::Light] {
+            let palette = Palette::new(theme);
+            let background = theme.egui_visuals().extreme_bg_color;
+            let mut colors = vec![palette.error, palette.warning, palette.ok, palette.marker];
+            colors.extend((0..=247).map(|slave| palette.slave(slave)));
+            for color in colors {
+                assert!(
+                    contrast(color, background) >= 3.0,
+                    "{:?} on {:?} in {:?}",
+                    color,
+                    background,
+                    theme
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+// Byte -> text translation tables for the display
+
+use std::collections::BTreeMap;
+
+/// IBM PC code page 437, bytes 0x80..=0xFF: accented letters, box drawing,
+/// shading blocks and maths symbols. The lower half is plain ASCII.
+pub const CP437_HIGH: &str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}";
+
+/// EBCDIC code page 037, bytes 0x40..=0xFE. Control codes below 0x40 are handled separately.
+const CP037_PRINTABLE: &str = " \u{a0}âäàáãåçñ¢.<(+|&éêëèíîïìß!$*);¬-/ÂÄÀÁÃÅÇÑ¦,%_>?øÉÊËÈÍÎÏÌ`:#@'=\"Øabcdefghi«»ðýþ±°jklmnopqrªºæ¸Æ¤µ~stuvwxyz¡¿ÐÝÞ®^£¥·©§¶¼½¾[]¯¨´×{ABCDEFGHI\u{ad}ôöòóõ}JKLMNOPQR¹ûüùúÿ\\÷STUVWXYZ²ÔÖÒÓÕ0123456789³ÛÜÙÚ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuiltinTable {
+    Cp037,
+    Cp437,
+    Petscii,
+}
+
+impl BuiltinTable {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuiltinTable::Cp037 => "EBCDIC CP037",
+            BuiltinTable::Cp437 => "CP437 (DOS)",
+            BuiltinTable::Petscii => "PETSCII",
+        }
+    }
+
+    pub fn table(&self) -> TranslationTable {
+        match self {
+            BuiltinTable::Cp037 => cp037(),
+            BuiltinTable::Cp437 => cp437(),
+            BuiltinTable::Petscii => petscii(),
+        }
+    }
+}
+
+/// Maps each byte value to the text shown for it. Bytes without an entry are
+/// shown as-is when ASCII and as U+FFFD otherwise.
+#[derive(Debug, Clone)]
+pub struct TranslationTable {
+    pub name: String,
+    entries: Vec<Option<String>>,
+}
+
+impl TranslationTable {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            entries: vec![None; 256],
+        }
+    }
+
+    pub fn set(&mut self, byte: u8, text: &str) {
+        self.entries[byte as usize] = Some(text.to_string());
+    }
+
+    pub fn get(&self, byte: u8) -> Option<&str> {
+        self.entries[byte as usize].as_deref()
+    }
+
+    pub fn mapped_count(&self) -> usize {
+        self.entries.iter().filter(|e| e.is_some()).count()
+    }
+
+    pub fn apply(&self, data: &[u8]) -> String {
+        let mut out = String::with_capacity(data.len());
+        for &byte in data {
+            match self.get(byte) {
+                Some(text) => out.push_str(text),
+                None if byte.is_ascii() => out.push(byte as char),
+                None => out.push(char::REPLACEMENT_CHARACTER),
+            }
+        }
+        out
+    }
+
+    /// Load a table from `.json` (an object of byte -> text) or CSV (`byte,text` per line).
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let name = std::path::Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string());
+
+        if path.to_ascii_lowercase().ends_with(".json") {
+            Self::from_json(&name, &text)
+        } else {
+            Self::from_csv(&name, &text)
+        }
+    }
+
+    /// One `byte,text` pair per line. Bytes are decimal or `0x` hex; the text may be
+    /// wrapped in double quotes to keep surrounding spaces. `#` starts a comment line.
+    pub fn from_csv(name: &str, text: &str) -> Result<Self, String> {
+        let mut table = Self::new(name);
+        for (index, line) in text.lines().enumerate() {
+            let line_no = index + 1;
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(',')
+                .ok_or_else(|| format!("line {}: expected 'byte,text'", line_no))?;
+            let byte = parse_byte(key).map_err(|e| format!("line {}: {}", line_no, e))?;
+            table
+                .insert_unique(byte, unquote(value))
+                .map_err(|e| format!("line {}: {}", line_no, e))?;
+        }
+        Ok(table)
+    }
+
+    pub fn from_json(name: &str, text: &str) -> Result<Self, String> {
+        let map: BTreeMap<String, String> =
+            serde_json::from_str(text).map_err(|e| format!("Invalid translation table: {}", e))?;
+        let mut table = Self::new(name);
+        for (key, value) in &map {
+            let byte = parse_byte(key)?;
+            table.insert_unique(byte, value)?;
+        }
+        Ok(table)
+    }
+
+    fn insert_unique(&mut self, byte: u8, text: &str) -> Result<(), String> {
+        if self.get(byte).is_some() {
+            return Err(format!("byte 0x{:02X} is mapped more than once", byte));
+        }
+        self.set(byte, text);
+        Ok(())
+    }
+}
+
+fn parse_byte(key: &str) -> Result<u8, String> {
+    let key = key.trim();
+    let value = match key.strip_prefix("0x").or_else(|| key.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => key.parse::<u32>(),
+    }
+    .map_err(|_| format!("'{}' is not a byte value", key))?;
+    u8::try_from(value).map_err(|_| format!("{} is out of range 0-255", key))
+}
+
+fn unquote(value: &str) -> &str {
+    let trimmed = value.trim();
+    trimmed
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(trimmed)
+}
+
+fn cp037() -> TranslationTable {
+    let mut table = TranslationTable::new(BuiltinTable::Cp037.as_str());
+    for (offset, c) in CP037_PRINTABLE.chars().enumerate() {
+        table.set(0x40 + offset as u8, c.encode_utf8(&mut [0; 4]));
+    }
+    table.set(0x05, "\t");
+    table.set(0x0D, "\r");
+    table.set(0x15, "\n");
+    table.set(0x25, "\n");
+    for byte in (0x00..0x40).chain([0xFF]) {
+        if table.get(byte).is_none() {
+            table.set(byte, ".");
+        }
+    }
+    table
+}
+
+/// Only the high half is mapped; control codes and ASCII pass through as-is.
+fn cp437() -> TranslationTable {
+    let mut table = TranslationTable::new(BuiltinTable::Cp437.as_str());
+    for (offset, c) in CP437_HIGH.chars().enumerate() {
+        table.set(0x80 + offset as u8, c.encode_utf8(&mut [0; 4]));
+    }
+    table
+}
+
+/// Commodore "lowercase/uppercase" character set with control codes shown as tokens.
+fn petscii() -> TranslationTable {
+    let mut table = TranslationTable::new(BuiltinTable::Petscii.as_str());
+    for byte in 0x20..=0x40u8 {
+        table.set(byte, (byte as char).encode_utf8(&mut [0; 4]));
+    }
+    for offset in 0..26u8 {
+        table.set(0x41 + offset, ((b'a' + offset) as char).encode_utf8(&mut [0; 4]));
+        table.set(0x61 + offset, ((b'A' + offset) as char).encode_utf8(&mut [0; 4]));
+        table.set(0xC1 + offset, ((b'A' + offset) as char).encode_utf8(&mut [0; 4]));
+    }
+    for (byte, text) in [
+        (0x5B, "["),
+        (0x5C, "£"),
+        (0x5D, "]"),
+        (0x5E, "↑"),
+        (0x5F, "←"),
+        (0x05, "{WHT}"),
+        (0x0D, "\n"),
+        (0x11, "{DOWN}"),
+        (0x12, "{RVS ON}"),
+        (0x13, "{HOME}"),
+        (0x14, "{DEL}"),
+        (0x1D, "{RIGHT}"),
+        (0x8D, "\n"),
+        (0x91, "{UP}"),
+        (0x92, "{RVS OFF}"),
+        (0x93, "{CLR}"),
+        (0x9D, "{LEFT}"),
+    ] {
+        table.set(byte, text);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cp037_decodes_ebcdic_text() {
+        let table = cp037();
+        assert_eq!(table.mapped_count(), 256);
+        // "Hello, World!" followed by EBCDIC NL
+        let data = [
+            0xC8, 0x85, 0x93, 0x93, 0x96, 0x6B, 0x40, 0xE6, 0x96, 0x99, 0x93, 0x84, 0x5A, 0x15,
+        ];
+        assert_eq!(table.apply(&data), "Hello, World!\n");
+    }
+
+    #[test]
+    fn cp437_decodes_the_whole_high_range() {
+        let table = cp437();
+        assert_eq!(table.mapped_count(), 128);
+        let high: Vec<u8> = (0x80..=0xFF).collect();
+        assert_eq!(table.apply(&high), CP437_HIGH);
+        assert_eq!(CP437_HIGH.chars().count(), 128);
+        // Spot checks against the IBM code chart
+        assert_eq!(table.get(0x80), Some("Ç"));
+        assert_eq!(table.get(0xB0), Some("░"));
+        assert_eq!(table.get(0xDB), Some("█"));
+        assert_eq!(table.get(0xE1), Some("ß"));
+        assert_eq!(table.get(0xFE), Some("■"));
+        assert_eq!(table.get(0xFF), Some("\u{a0}"));
+    }
+
+    #[test]
+    fn cp437_draws_menu_borders() {
+        let table = cp437();
+        let frame = [
+            0xC9, 0xCD, 0xCD, 0xBB, b'\n', 0xBA, b'O', b'K', 0xBA, b'\n', 0xC8, 0xCD, 0xCD, 0xBC,
+        ];
+        assert_eq!(table.apply(&frame), "╔══╗\n║OK║\n╚══╝");
+    }
+
+    #[test]
+    fn petscii_swaps_case_and_names_control_codes() {
+        let table = petscii();
+        assert_eq!(table.apply(&[0x93, 0x48, 0x49, 0x20, 0x68, 0x69]), "{CLR}hi HI");
+    }
+
+    #[test]
+    fn unmapped_bytes_fall_back_to_ascii() {
+        let mut table = TranslationTable::new("custom");
+        table.set(0x7F, "Δ");
+        assert_eq!(table.apply(&[b'a', 0x7F, 0xC0]), "aΔ\u{FFFD}");
+    }
+
+    #[test]
+    fn loads_csv_with_hex_decimal_and_quotes() {
+        let csv = "# custom map\n0x7F,Δ\n128,<STX>\n0x20,\" \"\n\n";
+        let table = TranslationTable::from_csv("map.csv", csv).unwrap();
+        assert_eq!(table.get(0x7F), Some("Δ"));
+        assert_eq!(table.get(128), Some("<STX>"));
+        assert_eq!(table.get(0x20), Some(" "));
+        assert_eq!(table.mapped_count(), 3);
+    }
+
+    #[test]
+    fn csv_errors_name_the_line() {
+        let err = TranslationTable::from_csv("map.csv", "0x41,A\n300,x").unwrap_err();
+        assert_eq!(err, "line 2: 300 is out of range 0-255");
+        let err = TranslationTable::from_csv("map.csv", "0x41,A\nnonsense").unwrap_err();
+        assert_eq!(err, "line 2: expected 'byte,text'");
+        let err = TranslationTable::from_csv("map.csv", "zz,A").unwrap_err();
+        assert_eq!(err, "line 1: 'zz' is not a byte value");
+    }
+
+    #[test]
+    fn rejects_duplicate_bytes() {
+        let err = TranslationTable::from_csv("map.csv", "0x41,A\n65,B").unwrap_err();
+        assert_eq!(err, "line 2: byte 0x41 is mapped more than once");
+        let err = TranslationTable::from_json("map.json", r#"{"0x41": "A", "65": "B"}"#).unwrap_err();
+        assert_eq!(err, "byte 0x41 is mapped more than once");
+    }
+
+    #[test]
+    fn loads_json_object() {
+        let table = TranslationTable::from_json("map.json", r#"{"0x7F": "Δ", "1": "<SOH>"}"#).unwrap();
+        assert_eq!(table.apply(&[0x01, 0x7F]), "<SOH>Δ");
+        assert!(TranslationTable::from_json("map.json", "[1, 2]").is_err());
+        assert!(TranslationTable::from_json("map.json", r#"{"256": "x"}"#).is_err());
+    }
+}
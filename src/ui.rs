@@ -2,19 +2,115 @@ use eframe::egui;
 use std::time::Duration;
 use chrono::Local;
 
+use crate::a11y::{self, Spoken};
+use crate::actions::{self, Action, CommandPalette, PaletteItem, PALETTE_SHORTCUT};
+use crate::activity::{ActivityLed, LED_REPAINT_INTERVAL};
 use crate::app::*;
-use crate::logging::{self, Direction};
+use crate::event_port;
+use crate::fast_attach::FastAttach;
+use crate::net_connect::NET_CONNECT_POLL_INTERVAL;
+use crate::file_send::{FirmwareSendMode, LineConversion};
+use crate::flow::FlowEvent;
+use crate::keyboard::{self, LineKey, SendBoxKey};
+use crate::reader::{PortReader, LOW_LATENCY_READ_TIMEOUT};
+use crate::recorder::{self, TAIL_PREVIEW_BYTES};
+use crate::report::{self, Excerpt};
 use crate::serial::*;
+use crate::sound::{self, SoundTheme, SoundTrigger};
+use crate::theme::{self, Palette};
+use crate::translate::BuiltinTable;
+
+use rustcom_core::auto_view;
+use rustcom_core::burst;
+use rustcom_core::cable_test::{self, TestPattern};
+use rustcom_core::excerpt::{self as doc_excerpt, ExcerptFormat, EXCERPT_ROW_BYTES};
+use rustcom_core::export::{ExportFormat, ExportRange};
+use rustcom_core::filter::{self, FilterRule};
+use rustcom_core::hex::{self, SoftParity};
+use rustcom_core::inspect;
+use rustcom_core::keepalive::MIN_KEEPALIVE_SECS;
+use rustcom_core::line_editor::SendDiscipline;
+use rustcom_core::live_view::{self, MAX_LIVE_VIEW_LINES};
+use rustcom_core::metrics::{Metrics, DEFAULT_METRICS_ADDRESS};
+use rustcom_core::logging::database::DbRecord;
+use rustcom_core::logging::stream::FLUSH_INTERVAL;
+use rustcom_core::logging::syslog::{SeverityRule, Transport, FACILITY_NAMES, SEVERITY_NAMES};
+use rustcom_core::logging::{self, Direction};
+use rustcom_core::macros::{self, Macro, MACRO_KEY_COUNT, MAX_MACROS};
+use rustcom_core::pinned::{PinLevel, PinSource, PinnedValue};
+use rustcom_core::port_config::{DataBits, FlowControl, Parity, StopBits};
+use rustcom_core::protocol::detect::DetectedProtocol;
+use rustcom_core::protocol::framing_rule::{FrameBoundary, TrailingChecksum};
+use rustcom_core::protocol::{self, CustomFraming, DecodedView, ProtocolMode, SendEncoding, MAX_FRAME_SIZE};
+use rustcom_core::rejected::MAX_REJECTED_KB;
+use rustcom_core::rx_queue::{MAX_RX_SUB_CHUNK, RX_QUEUE_LIMIT};
+use rustcom_core::repeat::{MAX_REPEAT_MS, MIN_REPEAT_MS};
+use rustcom_core::schedule::{ScheduledSend, DEFAULT_SCHEDULE_INTERVAL_MS, MIN_SCHEDULE_INTERVAL_MS};
+use rustcom_core::send_guard::{GuardKind, GuardRule};
+use rustcom_core::session::SESSION_COLORS;
+use rustcom_core::settings::ThemeSetting;
+use rustcom_core::stats_snapshot::{self, Change, Metric};
+use rustcom_core::tcp_port::ConnectionType;
+use rustcom_core::template::{self, PlaceholderKind};
+use rustcom_core::text_encoding::{EncodingDetection, TextEncoding};
+use rustcom_core::variables::Variable;
+use rustcom_core::watch::Watch;
 
 impl eframe::App for ComAnalyzerApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.apply_theme(ctx, frame.info().system_theme);
         self.poll_ports(ctx);
+        self.poll_fast_attach();
+        self.poll_net_connect(ctx);
         self.poll_serial(ctx);
+        self.poll_offline_replay(ctx);
+        self.poll_log_stream(ctx);
         self.poll_reconnect(ctx);
+        self.poll_file_send(ctx);
+        self.poll_xmodem(ctx);
+        self.poll_tcp_bridge(ctx);
+        self.poll_instance_requests();
+        self.poll_event_port();
+        self.poll_cable_test(ctx);
+        self.poll_watches(ctx);
+        self.poll_rate_alarm(ctx);
+        self.poll_schedule(ctx);
+        self.poll_tx_pending(ctx);
+        self.poll_keepalive(ctx);
+        self.poll_repeat(ctx);
+        self.poll_recording(ctx);
+        self.poll_sound(ctx);
+        self.poll_window_title(ctx);
+
+        if ctx.input(|i| i.viewport().close_requested()) {
+            self.save_settings();
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&PALETTE_SHORTCUT)) {
+            self.command_palette = Some(CommandPalette::default());
+        }
+        for action in Action::ALL {
+            if let Some(shortcut) = action.shortcut() {
+                if ctx.input_mut(|i| i.consume_shortcut(&shortcut)) {
+                    self.run_action(action);
+                }
+            }
+        }
+        if self.connected {
+            self.poll_macro_keys(ctx);
+        }
+        self.poll_area_keys(ctx);
 
         self.render_top_panel(ctx);
         self.render_side_panel(ctx);
         self.render_central_panel(ctx);
+        self.render_guard_confirmation(ctx);
+        self.render_goto_dialog(ctx);
+        self.render_command_palette(ctx);
+        self.render_macro_dialog(ctx);
+        self.render_template_prompt(ctx);
+        self.render_excerpt_dialog(ctx);
+        self.render_rejected_window(ctx);
     }
 }
 
@@ -60,81 +156,197 @@ impl ComAnalyzerApp {
         }
     }
 
-    fn poll_serial(&mut self, ctx: &egui::Context) {
-        if !self.connected {
+    pub(crate) fn poll_serial(&mut self, ctx: &egui::Context) {
+        // Data read before a disconnect is still worked off
+        if !self.connected && self.rx_queue.is_empty() {
             return;
         }
 
-        let (read_result, connection_error) = if let Some(port) = &self.serial_port {
-            if let Ok(mut port_guard) = port.try_lock() {
-                let mut buffer = vec![0u8; SERIAL_READ_BUFFER_SIZE];
-                match port_guard.read(&mut buffer) {
-                    Ok(bytes_read) if bytes_read > 0 => {
-                        (Some(buffer[..bytes_read].to_vec()), false)
-                    }
-                    Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => (None, false),
-                    Err(_) => (None, true),
-                    _ => (None, false),
+        if let Some(reader) = &self.port_reader {
+            reader.wake(ctx);
+            // Everything the reader thread has, unless the queue is already full
+            while self.connected && self.rx_queue.len() < RX_QUEUE_LIMIT {
+                match self.port_reader.as_ref().and_then(PortReader::poll) {
+                    Some(outcome) => self.handle_read(outcome),
+                    None => break,
                 }
-            } else {
-                (None, false)
             }
-        } else {
-            (None, false)
-        };
-
-        if connection_error {
-            let msg = format!(
-                "\n[{}] Connection lost to {}\n",
-                Local::now().format("%Y-%m-%d %H:%M:%S"),
-                self.selected_port.as_deref().unwrap_or("unknown")
-            );
-            self.receive_buffer.extend_from_slice(msg.as_bytes());
-            self.update_display_buffer();
-
-            self.serial_port = None;
-            self.connected = false;
+        } else if self.rx_queue.len() < RX_QUEUE_LIMIT {
+            // No reader thread (the port could not be cloned): read here
+            let outcome = match self.serial_port.as_ref().map(|port| port.try_lock()) {
+                Some(Ok(mut port_guard)) => {
+                    let mut buffer = vec![0u8; SERIAL_READ_BUFFER_SIZE];
+                    read_port(&mut **port_guard, &mut buffer)
+                }
+                _ => ReadOutcome::Idle,
+            };
+            self.handle_read(outcome);
+        }
 
-            if self.auto_reconnect {
-                self.reconnecting = true;
-                self.error_message = Some("Connection lost. Auto-reconnecting...".to_string());
+        if self.rx_queue.is_empty() {
+            self.poll_bursts();
+            self.poll_idle_flush(std::time::Instant::now());
+            self.poll_filter_hold(std::time::Instant::now());
+            self.check_clock(std::time::Instant::now(), Local::now());
+            self.poll_decoder();
+            self.feed_boot_capture(&[]);
+            // Without a reader thread the port is only read when the UI runs
+            let wait = if self.low_latency && self.port_reader.is_none() {
+                LOW_LATENCY_READ_TIMEOUT
             } else {
-                self.error_message = Some("Connection lost. Device disconnected.".to_string());
+                Duration::from_millis(DEFAULT_REPAINT_INTERVAL_MS)
+            };
+            ctx.request_repaint_after(wait);
+        } else {
+            self.process_rx_frame();
+            ctx.request_repaint();
+        }
+    }
+
+    pub(crate) fn handle_read(&mut self, outcome: ReadOutcome) {
+        match outcome {
+            ReadOutcome::Data {
+                data,
+                received_at,
+                arrived,
+            } => {
+                self.zero_reads = 0;
+                if let Some(bridge) = &self.tcp_bridge_server {
+                    bridge.broadcast(&data);
+                }
+                if let Some(timing) = self.attach_timing.as_mut() {
+                    timing.note_data(arrived);
+                }
+                if self.low_latency {
+                    self.echo_latency.note_rx(arrived);
+                }
+                if self.xmodem.is_some() {
+                    self.last_rx_at = Some(arrived);
+                    self.feed_xmodem(&data);
+                    return;
+                }
+                self.rx_queue.push(&data, received_at, arrived, self.char_time());
+            }
+            ReadOutcome::Idle => self.zero_reads = 0,
+            ReadOutcome::Zero => {
+                if self.note_zero_read() {
+                    let reason = format!("peer closed ({} empty reads)", self.zero_reads);
+                    self.connection_lost(&reason);
+                }
+            }
+            ReadOutcome::Failed => self.connection_lost("device disconnected"),
+            ReadOutcome::Dropped(bytes) => {
+                // After what was read before the gap
+                self.drain_rx_queue();
+                self.note_event(&format!("RX overrun: {} bytes dropped while the display fell behind", bytes));
+                self.error_message = Some(format!("{} received bytes were dropped; the display fell behind", bytes));
             }
         }
+    }
+
+    /// Work through one frame's budget of queued RX data. Returns the bytes processed.
+    pub(crate) fn process_rx_frame(&mut self) -> usize {
+        let mut processed = 0;
+        let mut display_changed = false;
 
-        if let Some(data) = read_result {
+        for chunk in self.rx_queue.take_frame(self.rx_frame_budget) {
+            // Before logging, so the marker precedes the data read after the jump
+            self.check_clock(chunk.arrived, chunk.received_at);
+            let data = chunk.data;
+            processed += data.len();
+            self.last_rx_at = Some(chunk.arrived);
             self.bytes_received += data.len();
+            if self.is_live() {
+                Metrics::add(&self.metrics.bytes_received, data.len());
+            }
+            self.note_rx(data.len());
 
             if self.logging_enabled {
-                self.log_entries
-                    .push(logging::create_log_entry(Direction::Received, &data));
+                self.log_entry(logging::create_log_entry_at(
+                    Direction::Received,
+                    &data,
+                    chunk.received_at,
+                ));
             }
 
-            let should_display = if self.filter_enabled {
-                self.matches_filter(&data)
-            } else {
-                true
-            };
-
-            if should_display {
-                self.receive_buffer.extend_from_slice(&data);
-                self.update_display_buffer();
+            if self.decoder.is_none() {
+                // Decoded frames are stored one row each as they complete
+                self.db_log(DbRecord::chunk(Direction::Received, &data));
             }
 
-            if self.receive_buffer.len() > MAX_BUFFER_SIZE {
-                self.receive_buffer.drain(0..BUFFER_DRAIN_SIZE);
-                self.update_display_buffer();
+            self.feed_boot_capture(&data);
+
+            let (data, flow_events) = self.split_flow_control(data);
+            let text = self.decode_rx_text(&data);
+            let text_bytes = text.as_ref().map_or(&data[..], |text| text.as_bytes());
+            self.feed_pinned(text_bytes);
+            self.feed_variables(text_bytes);
+            self.feed_syslog(text_bytes);
+
+            self.record_burst(data.len(), chunk.arrived);
+            let new_frames = self.feed_decoder(&data, chunk.arrived);
+            self.feed_detector(&data);
+            let data = self.feed_split_log(data, chunk.received_at);
+
+            // Regex rules see whole lines, not however the reads split them
+            let mut displayed = false;
+            for piece in self.filter_pieces(data, chunk.arrived) {
+                displayed |= self.show_filtered(&piece, chunk.received_at);
             }
+            self.mark_decoded_frames(new_frames);
+            display_changed |= displayed || new_frames > 0;
+
+            self.annotate_flow_events(&flow_events);
         }
 
-        ctx.request_repaint_after(Duration::from_millis(DEFAULT_REPAINT_INTERVAL_MS));
+        // A drain renders the display again, so trim before appending
+        self.trim_receive_buffer();
+        if display_changed {
+            self.extend_display_buffer();
+        }
+        processed
+    }
+
+    fn poll_offline_replay(&mut self, ctx: &egui::Context) {
+        if self.step_offline_replay() {
+            ctx.request_repaint();
+        }
+    }
+
+    fn poll_fast_attach(&mut self) {
+        if let Some(attached) = self.fast_attach.as_ref().and_then(FastAttach::poll) {
+            self.complete_fast_attach(attached);
+        }
+    }
+
+    pub(crate) fn poll_net_connect(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &self.net_connect else {
+            return;
+        };
+        match pending.poll() {
+            Some(result) => self.finish_net_connect(result),
+            None => ctx.request_repaint_after(NET_CONNECT_POLL_INTERVAL),
+        }
     }
 
     fn poll_reconnect(&mut self, ctx: &egui::Context) {
-        if !self.reconnecting || self.connected {
+        if !self.reconnecting || self.connected || self.net_connect.is_some() {
+            return;
+        }
+
+        // The worker wakes the UI itself once the port is open. It watches a
+        // single port, so a failover list and TCP are retried on the timer instead.
+        if self.fast_attach_enabled
+            && self.connection_type == ConnectionType::Serial
+            && self.selected_port.is_some()
+            && !self.failover.active()
+        {
+            if self.fast_attach.is_none() {
+                self.arm_fast_attach(ctx);
+            }
             return;
         }
+        self.fast_attach = None;
 
         let now = std::time::Instant::now();
         let elapsed = now.duration_since(self.last_reconnect_attempt);
@@ -142,45 +354,308 @@ impl ComAnalyzerApp {
         if elapsed.as_millis() >= self.reconnect_delay_ms as u128 {
             self.last_reconnect_attempt = now;
 
-            if self.selected_port.is_some() {
-                self.connect();
+            if self.has_connect_target() {
+                self.attempt_reconnect();
+            }
+        }
 
-                if self.connected {
-                    self.reconnecting = false;
-                    let msg = format!(
-                        "[{}] Reconnected successfully\n",
-                        Local::now().format("%Y-%m-%d %H:%M:%S")
-                    );
-                    self.receive_buffer.extend_from_slice(msg.as_bytes());
-                    self.update_display_buffer();
-                    self.error_message = Some("Reconnected!".to_string());
+        ctx.request_repaint_after(Duration::from_millis(100));
+    }
+
+    fn poll_instance_requests(&mut self) {
+        let Some(server) = &self.instance_server else {
+            return;
+        };
+
+        for port in server.poll() {
+            if self.connected
+                && self.connection_type == ConnectionType::Serial
+                && self.selected_port.as_deref() == Some(port.as_str())
+            {
+                let msg = format!(
+                    "[{}] Releasing {} for another RustCOM window\n",
+                    Local::now().format("%Y-%m-%d %H:%M:%S"),
+                    port
+                );
+                self.receive_buffer.extend_from_slice(msg.as_bytes());
+                self.disconnect();
+            }
+        }
+    }
+
+    fn poll_watches(&mut self, ctx: &egui::Context) {
+        if self.watches.is_empty() {
+            return;
+        }
+        // Wake up when a held condition is due, even if no data arrives
+        if let Some(wait) = self.check_watches() {
+            ctx.request_repaint_after(wait);
+        }
+    }
+
+    fn poll_tcp_bridge(&mut self, ctx: &egui::Context) {
+        if self.run_tcp_bridge() {
+            ctx.request_repaint_after(TCP_BRIDGE_POLL_INTERVAL);
+        }
+    }
+
+    /// A write failure on the log writer is noticed while nothing else happens.
+    fn poll_log_stream(&mut self, ctx: &egui::Context) {
+        if self.check_log_stream() {
+            ctx.request_repaint_after(FLUSH_INTERVAL);
+        }
+    }
+
+    fn poll_rate_alarm(&mut self, ctx: &egui::Context) {
+        if self.check_rate_alarm(std::time::Instant::now()) {
+            ctx.request_repaint_after(RATE_ALARM_POLL_INTERVAL);
+        }
+    }
+
+    fn poll_schedule(&mut self, ctx: &egui::Context) {
+        if self.schedule.is_empty() {
+            return;
+        }
+        if let Some(wait) = self.run_schedule(std::time::Instant::now()) {
+            ctx.request_repaint_after(wait);
+        }
+    }
+
+    fn poll_tx_pending(&mut self, ctx: &egui::Context) {
+        if let Some(wait) = self.flush_tx_pending(std::time::Instant::now()) {
+            ctx.request_repaint_after(wait);
+        }
+    }
+
+    fn poll_keepalive(&mut self, ctx: &egui::Context) {
+        if let Some(wait) = self.run_keepalive(std::time::Instant::now()) {
+            ctx.request_repaint_after(wait);
+        }
+    }
+
+    fn poll_repeat(&mut self, ctx: &egui::Context) {
+        if let Some(wait) = self.run_repeat(std::time::Instant::now()) {
+            ctx.request_repaint_after(wait);
+        }
+    }
+
+    fn poll_xmodem(&mut self, ctx: &egui::Context) {
+        if let Some(wait) = self.run_xmodem(std::time::Instant::now()) {
+            ctx.request_repaint_after(wait);
+        }
+    }
+
+    fn poll_recording(&mut self, ctx: &egui::Context) {
+        let Some(recording) = self.recording.as_mut() else {
+            return;
+        };
+        recording.rate.sample(recording.status.bytes(), std::time::Instant::now());
+        // A failed write hands RX back to the display; end the recording with it
+        if recording.status.error().is_some() {
+            self.stop_recording();
+        }
+        ctx.request_repaint_after(Duration::from_millis(DEFAULT_REPAINT_INTERVAL_MS * 4));
+    }
+
+    /// End hums whose traffic has stopped.
+    fn poll_sound(&mut self, ctx: &egui::Context) {
+        if let Some(sound) = self.sound.as_mut() {
+            sound.expire(std::time::Instant::now());
+            ctx.request_repaint_after(sound::HUM_HOLD / 2);
+        }
+    }
+
+    /// Put the session name in the window title when it changes.
+    fn poll_window_title(&mut self, ctx: &egui::Context) {
+        let title = self.session_tag.window_title();
+        if title != self.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+        }
+    }
+
+    fn poll_cable_test(&mut self, ctx: &egui::Context) {
+        if let Some(run) = self.cable_test.as_mut().filter(|run| !run.finished) {
+            run.poll();
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+    }
+
+    fn poll_event_port(&mut self) {
+        let Some(port) = self.event_port.as_mut() else {
+            return;
+        };
+
+        match port.poll() {
+            Ok(lines) => {
+                for line in lines {
+                    self.inject_marker(&line);
                 }
             }
+            Err(e) => {
+                self.event_port = None;
+                self.error_message = Some(e);
+            }
         }
+    }
 
-        ctx.request_repaint_after(Duration::from_millis(100));
+    pub(crate) fn poll_file_send(&mut self, ctx: &egui::Context) {
+        if self.file_transfer.is_none() {
+            return;
+        }
+
+        if !self.connected {
+            self.abort_file_send("not connected");
+            return;
+        }
+
+        if self.monitor_only {
+            self.abort_file_send("monitor-only mode is on");
+            return;
+        }
+
+        // The file waits its turn behind held sends and within the byte rate
+        let wait = self.tx_limiter.wait(std::time::Instant::now());
+        if !self.tx_pending.is_empty() || !wait.is_zero() {
+            ctx.request_repaint_after(wait.max(Duration::from_millis(1)));
+            return;
+        }
+
+        let Some(transfer) = self.file_transfer.as_mut() else {
+            return;
+        };
+
+        let chunk = match transfer.next_chunk() {
+            Ok(chunk) => rustcom_core::hex::apply_soft_parity(chunk, self.tx_parity),
+            Err(e) => {
+                self.abort_file_send(&e);
+                return;
+            }
+        };
+
+        if !chunk.is_empty() {
+            let write_result = match &self.serial_port {
+                Some(port) => match port.lock() {
+                    Ok(mut port_guard) => port_guard.write(&chunk),
+                    Err(_) => Ok(0),
+                },
+                None => Ok(0),
+            };
+
+            match write_result {
+                Ok(count) => {
+                    transfer.advance(count);
+                    if count > 0 {
+                        self.tx_limiter.sent(count, std::time::Instant::now());
+                    }
+                    self.bytes_sent += count;
+                    Metrics::add(&self.metrics.bytes_sent, count);
+                    if count > 0 {
+                        self.note_tx(count);
+                    }
+                    if self.logging_enabled && count > 0 {
+                        self.log_entry(logging::create_log_entry(Direction::Sent, &chunk[..count]));
+                    }
+                    if count > 0 {
+                        self.record_tx(&chunk[..count]);
+                    }
+                }
+                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => {
+                    self.abort_file_send(&e.to_string());
+                    return;
+                }
+            }
+        }
+
+        if let Some(transfer) = self.file_transfer.take_if(|t| t.is_done()) {
+            let msg = format!(
+                "[{}] File sent: {} ({} bytes)\n",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                transfer.name,
+                transfer.sent
+            );
+            self.receive_buffer.extend_from_slice(msg.as_bytes());
+            self.update_display_buffer();
+        } else {
+            ctx.request_repaint();
+        }
     }
 
     fn render_top_panel(&mut self, ctx: &egui::Context) {
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.heading("RustCOM");
+                self.render_session_tag(ui);
+
+                let slot = Self::slot_name(self.active_slot);
+                let other = Self::slot_name(1 - self.active_slot);
+                self.action_button(
+                    ui,
+                    Action::ToggleConfigSlot,
+                    egui::Button::new(egui::RichText::new(format!("Config {}", slot)).strong()),
+                    &format!("Switch to Config {}", other),
+                );
+
+                ui.toggle_value(&mut self.monitor_only, "Monitor only")
+                    .on_hover_text("Never transmit: blocks sending, file send and DTR/RTS changes");
 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if self.monitor_only {
+                        egui::Frame::none()
+                            .fill(egui::Color32::from_rgb(160, 40, 40))
+                            .rounding(4.0)
+                            .inner_margin(egui::Margin::symmetric(6.0, 2.0))
+                            .show(ui, |ui| {
+                                ui.label(
+                                    egui::RichText::new("MONITOR ONLY")
+                                        .strong()
+                                        .color(egui::Color32::WHITE),
+                                );
+                            });
+                        ui.separator();
+                    }
                     ui.label(format!("RX: {} bytes", self.bytes_received));
+                    self.render_activity_led(ui, &self.rx_led, self.palette.ok, "RX activity");
                     ui.separator();
                     ui.label(format!("TX: {} bytes", self.bytes_sent));
+                    self.render_activity_led(ui, &self.tx_led, self.palette.marker, "TX activity");
+                    if !self.tx_pending.is_empty() {
+                        ui.colored_label(self.palette.warning, format!("throttled ({} waiting)", self.tx_pending.len()))
+                            .on_hover_text(format!("{} sends delayed by the TX rate limit so far", self.tx_delayed));
+                    }
+                    if self.keepalive_timer.is_running() {
+                        ui.colored_label(self.palette.ok, "keepalive").on_hover_text(format!(
+                            "Sent after {} s without TX; {} sent so far",
+                            self.keepalive.interval().as_secs(),
+                            self.keepalives_sent
+                        ));
+                    }
                 });
             });
 
-            // Dismissible error bar
+            // Dismissible error bar; the corrupt settings file goes with its own message only
+            if self
+                .corrupt_settings
+                .as_ref()
+                .is_some_and(|(_, message)| self.error_message.as_ref() != Some(message))
+            {
+                self.corrupt_settings = None;
+            }
             if let Some(error) = self.error_message.clone() {
                 ui.horizontal(|ui| {
-                    ui.colored_label(egui::Color32::from_rgb(255, 100, 100), &error);
+                    ui.colored_label(self.palette.error, &error).live();
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        if ui.small_button("x").clicked() {
+                        if ui.small_button("x").spoken("Dismiss error").clicked() {
                             self.error_message = None;
                         }
+                        if let Some((path, _)) = self.corrupt_settings.clone() {
+                            if ui.small_button("Show corrupt file").clicked() {
+                                if let Err(e) = crate::boot_log::open_in_system(&path) {
+                                    self.error_message = Some(e);
+                                }
+                            }
+                        }
                     });
                 });
             }
@@ -189,6 +664,75 @@ impl ComAnalyzerApp {
         });
     }
 
+    /// The session colour chip and name; double-click the name to rename.
+    fn render_session_tag(&mut self, ui: &mut egui::Ui) {
+        let fill = match self.session_tag.color {
+            Some([r, g, b]) => egui::Color32::from_rgb(r, g, b),
+            None => ui.visuals().widgets.inactive.bg_fill,
+        };
+        let (rect, chip) = ui.allocate_exact_size(egui::vec2(14.0, 14.0), egui::Sense::click());
+        ui.painter().rect_filled(rect, 3.0, fill);
+        let chip = chip.on_hover_text("Session colour");
+        let popup = ui.make_persistent_id("session_colors");
+        if chip.clicked() {
+            ui.memory_mut(|mem| mem.toggle_popup(popup));
+        }
+        let close = egui::PopupCloseBehavior::CloseOnClick;
+        egui::popup::popup_below_widget(ui, popup, &chip, close, |ui: &mut egui::Ui| {
+            ui.horizontal(|ui| {
+                for [r, g, b] in SESSION_COLORS {
+                    let (rect, swatch) = ui.allocate_exact_size(egui::vec2(16.0, 16.0), egui::Sense::click());
+                    ui.painter().rect_filled(rect, 3.0, egui::Color32::from_rgb(r, g, b));
+                    if swatch.clicked() {
+                        self.session_tag.color = Some([r, g, b]);
+                    }
+                }
+                if ui.small_button("None").clicked() {
+                    self.session_tag.color = None;
+                }
+            });
+        });
+
+        if self.renaming_session {
+            let edit = ui.add(
+                egui::TextEdit::singleline(&mut self.session_tag.name)
+                    .hint_text("Session name")
+                    .desired_width(140.0),
+            )
+            .spoken("Session name");
+            edit.request_focus();
+            if edit.lost_focus() {
+                self.session_tag.name = self.session_tag.name.trim().to_string();
+                self.renaming_session = false;
+            }
+        } else {
+            let name = match self.session_tag.name.as_str() {
+                "" => egui::RichText::new("Unnamed").weak(),
+                name => egui::RichText::new(name).strong(),
+            };
+            if ui
+                .add(egui::Label::new(name).sense(egui::Sense::click()))
+                .on_hover_text("Double-click to rename")
+                .double_clicked()
+            {
+                self.renaming_session = true;
+            }
+        }
+    }
+
+    /// A small LED that lights on activity and fades out, repainting while it fades.
+    fn render_activity_led(&self, ui: &mut egui::Ui, led: &ActivityLed, on: egui::Color32, hover: &str) {
+        let brightness = led.brightness(std::time::Instant::now());
+        let off = ui.visuals().widgets.inactive.bg_fill;
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+        ui.painter()
+            .circle_filled(rect.center(), 5.0, off.lerp_to_gamma(on, brightness));
+        response.on_hover_text(hover);
+        if brightness > 0.0 {
+            ui.ctx().request_repaint_after(LED_REPAINT_INTERVAL);
+        }
+    }
+
     fn render_side_panel(&mut self, ctx: &egui::Context) {
         egui::SidePanel::left("connection_panel")
             .default_width(SIDEBAR_WIDTH)
@@ -205,76 +749,171 @@ impl ComAnalyzerApp {
 
                     self.render_view_group(ui);
                     ui.add_space(5.0);
+                    self.render_protocol_group(ui);
+                    ui.add_space(5.0);
+                    self.render_statistics_group(ui);
+                    ui.add_space(5.0);
                     self.render_logging_group(ui);
                     ui.add_space(5.0);
+                    self.render_syslog_group(ui);
+                    ui.add_space(5.0);
+                    self.render_metrics_group(ui);
+                    ui.add_space(5.0);
+                    self.render_live_view_group(ui);
+                    ui.add_space(5.0);
+                    self.render_tcp_bridge_group(ui);
+                    ui.add_space(5.0);
+                    self.render_report_group(ui);
+                    ui.add_space(5.0);
+                    self.render_export_group(ui);
+                    ui.add_space(5.0);
                     self.render_filter_group(ui);
                     ui.add_space(5.0);
+                    self.render_pinned_group(ui);
+                    ui.add_space(5.0);
+                    self.render_variables_group(ui);
+                    ui.add_space(5.0);
+                    self.render_watches_group(ui);
+                    ui.add_space(5.0);
+                    self.render_send_guard_group(ui);
+                    ui.add_space(5.0);
+                    self.render_macros_group(ui);
+                    ui.add_space(5.0);
+                    self.render_schedule_group(ui);
+                    ui.add_space(5.0);
+                    self.render_rate_limit_group(ui);
+                    ui.add_space(5.0);
+                    self.render_keepalive_group(ui);
+                    ui.add_space(5.0);
+                    self.render_file_send_group(ui);
+                    ui.add_space(5.0);
+                    self.render_xmodem_group(ui);
+                    ui.add_space(5.0);
+                    self.render_event_port_group(ui);
+                    ui.add_space(5.0);
                     self.render_virtual_com_group(ui);
+                    ui.add_space(5.0);
+                    self.render_cable_test_group(ui);
                 });
             });
     }
 
+    /// Port picker, refreshed by hand or by the scanner, and the failover list.
+    fn render_serial_port_selection(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Refresh").clicked() {
+                self.available_ports = serialport::available_ports().unwrap_or_default();
+            }
+            ui.label("COM Port:");
+
+            if self.auto_scan_ports {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label(
+                        egui::RichText::new("auto")
+                            .small()
+                            .color(self.palette.ok),
+                    );
+                });
+            }
+        });
+
+        egui::ComboBox::from_id_source("port_selector")
+            .width(ui.available_width())
+            .selected_text(
+                self.selected_port
+                    .as_deref()
+                    .unwrap_or("Select port..."),
+            )
+            .show_ui(ui, |ui: &mut egui::Ui| {
+                for port in &self.available_ports {
+                    let port_name = port.port_name.clone();
+                    let port_type_str = match &port.port_type {
+                        serialport::SerialPortType::UsbPort(info) => {
+                            format!("(USB {:04x}:{:04x})", info.vid, info.pid)
+                        }
+                        serialport::SerialPortType::PciPort => "(PCI)".to_string(),
+                        serialport::SerialPortType::BluetoothPort => "(BT)".to_string(),
+                        serialport::SerialPortType::Unknown => "".to_string(),
+                    };
+                    let label = format!("{} {}", port_name, port_type_str);
+                    ui.selectable_value(&mut self.selected_port, Some(port_name), label);
+                }
+            })
+            .response
+            .spoken("Port");
+
+        ui.checkbox(&mut self.failover.enabled, "Try a list of ports")
+            .on_hover_text("Connect and auto-reconnect try these in order until one opens");
+        if self.failover.enabled {
+            self.render_failover_list(ui);
+        }
+    }
+
     fn render_connection_group(&mut self, ui: &mut egui::Ui) {
         ui.group(|ui| {
-            ui.label(egui::RichText::new("Connection").strong());
+            self.group_heading(ui, "Connection");
             ui.separator();
 
-            ui.horizontal(|ui| {
-                if ui.button("Refresh").clicked() {
-                    self.available_ports = serialport::available_ports().unwrap_or_default();
-                }
-                ui.label("COM Port:");
-
-                if self.auto_scan_ports {
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        ui.label(
-                            egui::RichText::new("auto")
-                                .small()
-                                .color(egui::Color32::GREEN),
+            ui.add_enabled_ui(!self.connected && !self.connecting(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Type:");
+                    egui::ComboBox::from_id_source("connection_type")
+                        .selected_text(self.connection_type.as_str())
+                        .show_ui(ui, |ui: &mut egui::Ui| {
+                            for kind in ConnectionType::ALL {
+                                ui.selectable_value(&mut self.connection_type, kind, kind.as_str());
+                            }
+                        })
+                        .response
+                        .spoken("Connection type")
+                        .on_hover_text(
+                            "TCP client reads a serial server such as ser2net or ESP-Link; \
+                             RFC 2217 also sets its port's baud rate, format and DTR/RTS",
                         );
-                    });
-                }
+                });
             });
 
-            egui::ComboBox::from_id_source("port_selector")
-                .width(ui.available_width())
-                .selected_text(
-                    self.selected_port
-                        .as_deref()
-                        .unwrap_or("Select port..."),
-                )
-                .show_ui(ui, |ui: &mut egui::Ui| {
-                    for port in &self.available_ports {
-                        let port_name = port.port_name.clone();
-                        let port_type_str = match &port.port_type {
-                            serialport::SerialPortType::UsbPort(info) => {
-                                format!("(USB {:04x}:{:04x})", info.vid, info.pid)
-                            }
-                            serialport::SerialPortType::PciPort => "(PCI)".to_string(),
-                            serialport::SerialPortType::BluetoothPort => "(BT)".to_string(),
-                            serialport::SerialPortType::Unknown => "".to_string(),
-                        };
-                        let label = format!("{} {}", port_name, port_type_str);
-                        ui.selectable_value(&mut self.selected_port, Some(port_name), label);
-                    }
+            if self.connection_type.is_network() {
+                ui.horizontal(|ui| {
+                    ui.label("Host:port:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.tcp_address)
+                            .hint_text("192.168.1.20:4001")
+                            .desired_width(ui.available_width()),
+                    )
+                    .spoken("TCP address");
                 });
+            } else {
+                self.render_serial_port_selection(ui);
+            }
 
             ui.add_space(5.0);
 
             ui.horizontal(|ui| {
                 ui.label("Baud:");
                 egui::ComboBox::from_id_source("baud_rate")
-                    .width(ui.available_width() - 50.0)
+                    .width(ui.available_width() - 100.0)
                     .selected_text(&self.baud_rate)
                     .show_ui(ui, |ui: &mut egui::Ui| {
-                        for rate in &[
-                            "300", "1200", "2400", "4800", "9600", "19200", "38400", "57600",
-                            "115200", "230400", "460800", "921600",
-                        ] {
-                            ui.selectable_value(&mut self.baud_rate, rate.to_string(), *rate);
+                        for rate in BAUD_RATES {
+                            ui.selectable_value(&mut self.baud_rate, rate.to_string(), rate.to_string());
                         }
-                    });
-            });
+                    })
+                    .response
+                    .spoken("Baud rate");
+                self.action_button(
+                    ui,
+                    Action::BaudDown,
+                    egui::Button::new("<").small(),
+                    "Previous baud rate, reconnecting if connected",
+                );
+                self.action_button(
+                    ui,
+                    Action::BaudUp,
+                    egui::Button::new(">").small(),
+                    "Next baud rate, reconnecting if connected",
+                );
+            });
 
             ui.horizontal(|ui| {
                 ui.label("Data:");
@@ -286,7 +925,9 @@ impl ComAnalyzerApp {
                         ui.selectable_value(&mut self.data_bits, DataBits::Six, "6");
                         ui.selectable_value(&mut self.data_bits, DataBits::Seven, "7");
                         ui.selectable_value(&mut self.data_bits, DataBits::Eight, "8");
-                    });
+                    })
+                    .response
+                    .spoken("Data bits");
 
                 ui.label("Stop:");
                 egui::ComboBox::from_id_source("stop_bits")
@@ -295,7 +936,9 @@ impl ComAnalyzerApp {
                     .show_ui(ui, |ui: &mut egui::Ui| {
                         ui.selectable_value(&mut self.stop_bits, StopBits::One, "1");
                         ui.selectable_value(&mut self.stop_bits, StopBits::Two, "2");
-                    });
+                    })
+                    .response
+                    .spoken("Stop bits");
             });
 
             ui.horizontal(|ui| {
@@ -307,7 +950,9 @@ impl ComAnalyzerApp {
                         ui.selectable_value(&mut self.parity, Parity::None, "None");
                         ui.selectable_value(&mut self.parity, Parity::Even, "Even");
                         ui.selectable_value(&mut self.parity, Parity::Odd, "Odd");
-                    });
+                    })
+                    .response
+                    .spoken("Parity");
             });
 
             ui.horizontal(|ui| {
@@ -327,19 +972,19 @@ impl ComAnalyzerApp {
                             FlowControl::Hardware,
                             "Hardware",
                         );
-                    });
+                    })
+                    .response
+                    .spoken("Flow control");
             });
 
             ui.add_space(10.0);
 
             // Connect button - fill available width
-            let button_text = if self.connected {
-                "Disconnect"
-            } else {
-                "Connect"
-            };
+            let action = self.connect_action();
             let button_color = if self.connected {
                 egui::Color32::from_rgb(200, 60, 60)
+            } else if self.connecting() {
+                egui::Color32::from_rgb(180, 140, 40)
             } else {
                 egui::Color32::from_rgb(60, 160, 60)
             };
@@ -347,17 +992,10 @@ impl ComAnalyzerApp {
             let button_width = ui.available_width();
             let layout = egui::Layout::top_down(egui::Align::Center);
             ui.allocate_ui_with_layout(egui::vec2(button_width, 32.0), layout, |ui| {
-                let button = egui::Button::new(egui::RichText::new(button_text))
+                let button = egui::Button::new(egui::RichText::new(action.name()))
                     .fill(button_color)
                     .min_size(egui::vec2(button_width, 32.0));
-
-                if ui.add(button).clicked() {
-                    if self.connected {
-                        self.disconnect();
-                    } else {
-                        self.connect();
-                    }
-                }
+                self.action_button(ui, action, button, "");
             });
 
             ui.add_space(5.0);
@@ -365,34 +1003,161 @@ impl ComAnalyzerApp {
             // Status
             ui.vertical_centered(|ui| {
                 let (status_text, status_color) = if self.connected {
-                    ("CONNECTED", egui::Color32::GREEN)
+                    ("CONNECTED", self.palette.ok)
+                } else if self.connecting() {
+                    ("CONNECTING...", self.palette.warning)
                 } else {
                     ("DISCONNECTED", egui::Color32::GRAY)
                 };
                 ui.colored_label(status_color, egui::RichText::new(status_text).strong());
             });
 
+            if let Some(timing) = &self.attach_timing {
+                let first_byte = match timing.first_byte_after {
+                    Some(after) => format!("first byte {} ms", after.as_millis()),
+                    None => "no data yet".to_string(),
+                };
+                ui.label(
+                    egui::RichText::new(format!(
+                        "Attach: opened {} ms, {} after the port appeared",
+                        timing.opened_after.as_millis(),
+                        first_byte
+                    ))
+                    .small(),
+                )
+                .on_hover_text("A first byte long after opening means the start of the boot output was captured");
+            }
+
+            if let Some(owner) = self
+                .port_owner
+                .as_ref()
+                .filter(|owner| self.selected_port.as_deref() == Some(owner.port.as_str()))
+            {
+                ui.colored_label(
+                    self.palette.warning,
+                    format!("{} is open in another RustCOM window", owner.port),
+                );
+                if ui
+                    .button("Request release")
+                    .on_hover_text("Ask the other window to disconnect so this one can take over")
+                    .clicked()
+                {
+                    self.request_port_release();
+                }
+            }
+
+            if let Some(info) = &self.port_info {
+                for difference in &info.differences {
+                    ui.colored_label(self.palette.warning, difference);
+                }
+                egui::CollapsingHeader::new("Port info")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        egui::Grid::new("port_info").num_columns(2).striped(true).show(ui, |ui| {
+                            for (name, value) in info.rows() {
+                                ui.label(name);
+                                ui.label(egui::RichText::new(value).monospace());
+                                ui.end_row();
+                            }
+                        });
+                    })
+                    .header_response
+                    .on_hover_text("What the OS and driver granted when the port was opened, read back from the port");
+            }
+
             // Advanced options (collapsible)
             ui.add_space(5.0);
             egui::CollapsingHeader::new("Advanced")
                 .default_open(false)
                 .show(ui, |ui| {
                     ui.checkbox(&mut self.auto_reconnect, "Auto-reconnect");
+                    ui.checkbox(&mut self.retry_initial_connect, "Keep trying if initial connect fails");
 
-                    if self.auto_reconnect {
-                        ui.horizontal(|ui| {
-                            ui.label("Delay:");
-                            ui.add(
-                                egui::Slider::new(&mut self.reconnect_delay_ms, 500..=10000)
-                                    .suffix("ms"),
-                            );
-                        });
+                    if self.auto_reconnect || self.retry_initial_connect {
+                        ui.checkbox(&mut self.fast_attach_enabled, "Fast attach")
+                            .on_hover_text("Open the port the moment it appears, to catch a boot banner right after power-on");
+                        if self.fast_attach_enabled {
+                            ui.checkbox(&mut self.fast_attach_dtr, "Assert DTR on attach");
+                        } else {
+                            ui.horizontal(|ui| {
+                                ui.label("Delay:");
+                                ui.add(
+                                    egui::Slider::new(&mut self.reconnect_delay_ms, 500..=10000)
+                                        .suffix("ms"),
+                                )
+                                .spoken("Reconnect delay");
+                            });
+                        }
 
                         if self.reconnecting {
-                            ui.colored_label(egui::Color32::YELLOW, "Reconnecting...");
+                            ui.colored_label(self.palette.warning, "Reconnecting...");
                         }
                     }
 
+                    ui.horizontal(|ui| {
+                        ui.label("TX parity bit:");
+                        egui::ComboBox::from_id_source("tx_parity")
+                            .selected_text(self.tx_parity.as_str())
+                            .show_ui(ui, |ui: &mut egui::Ui| {
+                                for parity in [
+                                    SoftParity::None,
+                                    SoftParity::Even,
+                                    SoftParity::Odd,
+                                    SoftParity::Mark,
+                                    SoftParity::Space,
+                                ] {
+                                    ui.selectable_value(&mut self.tx_parity, parity, parity.as_str());
+                                }
+                            })
+                            .response
+                            .spoken("TX parity bit")
+                            .on_hover_text("Compute bit 7 in software for adapters that only support 8N1");
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Write timeout:");
+                        ui.add(egui::DragValue::new(&mut self.write_timeout_ms).range(10..=60_000).suffix(" ms"))
+                            .spoken("Write timeout");
+                    })
+                    .response
+                    .on_hover_text("How long a send waits for the port to take more bytes, e.g. while flow control holds it off");
+
+                    ui.add_enabled(
+                        self.flow_control == FlowControl::Software,
+                        egui::Checkbox::new(&mut self.show_flow_events, "Show XON/XOFF as events"),
+                    )
+                    .on_hover_text("Strip XON/XOFF from the display and decoders and annotate them instead");
+
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.eof_detection, "Empty reads mean closed:")
+                            .on_hover_text("Disconnect after this many zero-byte reads in a row, e.g. when a socat PTY's peer exits");
+                        ui.add_enabled(
+                            self.eof_detection,
+                            egui::DragValue::new(&mut self.eof_zero_reads).range(1..=1000),
+                        )
+                        .spoken("Empty reads before closing");
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("RX per frame:");
+                        let mut kb = self.rx_frame_budget / 1024;
+                        if ui
+                            .add(
+                                egui::DragValue::new(&mut kb)
+                                    .range(MAX_RX_SUB_CHUNK / 1024..=1024)
+                                    .suffix(" KB"),
+                            )
+                            .spoken("RX per frame")
+                            .on_hover_text("Received data processed per screen update; the rest waits for the next one")
+                            .changed()
+                        {
+                            self.rx_frame_budget = kb * 1024;
+                        }
+                        if !self.rx_queue.is_empty() {
+                            ui.label(format!("{} KB queued", self.rx_queue.len() / 1024));
+                        }
+                    });
+
                     ui.checkbox(&mut self.auto_scan_ports, "Auto-scan for ports");
 
                     if self.auto_scan_ports {
@@ -401,167 +1166,3895 @@ impl ComAnalyzerApp {
                             ui.add(
                                 egui::Slider::new(&mut self.port_scan_interval_ms, 1000..=10000)
                                     .suffix("ms"),
-                            );
+                            )
+                            .spoken("Port scan interval");
                         });
                     }
                 });
         });
     }
 
+    /// The ordered failover candidates with controls to reorder, remove and add.
+    fn render_failover_list(&mut self, ui: &mut egui::Ui) {
+        let count = self.failover.candidates.len();
+        let mut move_up = None;
+        let mut remove = None;
+        for (index, candidate) in self.failover.candidates.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}.", index + 1));
+                ui.label(egui::RichText::new(candidate.label()).monospace());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("x").spoken("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                    if ui.add_enabled(index + 1 < count, egui::Button::new("⬇").small()).spoken("Move down").clicked() {
+                        move_up = Some(index + 1);
+                    }
+                    if ui.add_enabled(index > 0, egui::Button::new("⬆").small()).spoken("Move up").clicked() {
+                        move_up = Some(index);
+                    }
+                });
+            });
+        }
+        if let Some(index) = move_up {
+            self.failover.candidates.swap(index - 1, index);
+        }
+        if let Some(index) = remove {
+            self.failover.candidates.remove(index);
+        }
+
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.failover_input)
+                    .hint_text("COM3 or usb:0403:6001")
+                    .desired_width(ui.available_width() - 40.0),
+            )
+            .spoken("Port to add");
+            let add = ui
+                .button("Add")
+                .on_hover_text("With nothing typed, adds the selected port (by USB identity when it has one)")
+                .clicked()
+                || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+            if add {
+                if let Err(e) = self.add_failover_candidate() {
+                    self.error_message = Some(e);
+                }
+            }
+        });
+        if count == 0 {
+            ui.label(egui::RichText::new("Empty list: the selected port is used").small().weak());
+        }
+    }
+
     fn render_signals_group(&mut self, ui: &mut egui::Ui) {
         ui.group(|ui| {
-            ui.label(egui::RichText::new("Signals").strong());
+            self.group_heading(ui, "Signals");
             ui.separator();
 
             ui.horizontal(|ui| {
-                let dtr_response = ui.checkbox(&mut self.dtr_state, "DTR");
-                if dtr_response.changed() {
-                    self.set_dtr(self.dtr_state);
-                }
-                dtr_response.on_hover_text("Data Terminal Ready");
+                self.action_checkbox(ui, Action::Dtr, "DTR").on_hover_text("Data Terminal Ready");
+                self.action_checkbox(ui, Action::Rts, "RTS").on_hover_text("Request To Send");
+            });
 
-                let rts_response = ui.checkbox(&mut self.rts_state, "RTS");
-                if rts_response.changed() {
-                    self.set_rts(self.rts_state);
+            ui.horizontal(|ui| {
+                ui.label("Inject:");
+                let can_send = self.connected && !self.monitor_only;
+                for event in [FlowEvent::Xon, FlowEvent::Xoff] {
+                    let response = ui
+                        .add_enabled(can_send, egui::Button::new(event.name()).small())
+                        .on_hover_text(format!(
+                            "Send a single {} byte (0x{:02X}) now, e.g. to unstick a device",
+                            event.name(),
+                            event.byte()
+                        ));
+                    if response.clicked() {
+                        if let Err(e) = self.inject_flow_byte(event) {
+                            self.error_message = Some(e);
+                        }
+                    }
                 }
-                rts_response.on_hover_text("Request To Send");
             });
+
+            self.poll_cts(std::time::Instant::now());
+            if self.connected && self.flow_control == FlowControl::Hardware {
+                match self.cts_state {
+                    Some(true) => ui.colored_label(self.palette.ok, "TX clear: CTS high"),
+                    Some(false) => ui.colored_label(self.palette.warning, "TX held off: CTS low"),
+                    None => ui.label(egui::RichText::new("CTS not reported by the driver").weak()),
+                };
+            }
+
+            if self.show_flow_events && self.flow_control == FlowControl::Software {
+                let paused = self.flow_tracker.time_paused(std::time::Instant::now());
+                let (state, color) = if self.flow_tracker.is_paused() {
+                    ("XOFF", self.palette.warning)
+                } else {
+                    ("XON", self.palette.ok)
+                };
+                ui.horizontal(|ui| {
+                    ui.label("Flow:");
+                    ui.colored_label(color, state);
+                });
+                ui.label(format!(
+                    "Pauses: {}  In XOFF: {:.1}s",
+                    self.flow_tracker.pause_count,
+                    paused.as_secs_f32()
+                ));
+            }
         });
     }
 
+    /// Switch visuals when the theme setting or, for "System", the OS theme changes.
+    /// eframe also applies OS theme changes itself, so this compares against the
+    /// visuals actually in use.
+    pub(crate) fn apply_theme(&mut self, ctx: &egui::Context, system: Option<eframe::Theme>) {
+        let theme = theme::resolve(self.theme_setting, system);
+        let dark = theme == eframe::Theme::Dark;
+        if ctx.style().visuals.dark_mode != dark {
+            ctx.set_visuals(theme.egui_visuals());
+        }
+        self.palette = Palette::new(theme);
+    }
+
     fn render_view_group(&mut self, ui: &mut egui::Ui) {
         ui.group(|ui| {
-            ui.label(egui::RichText::new("View").strong());
+            self.group_heading(ui, "View");
             ui.separator();
 
+            ui.horizontal(|ui| {
+                ui.label("Theme:");
+                for setting in [ThemeSetting::System, ThemeSetting::Dark, ThemeSetting::Light] {
+                    ui.selectable_value(&mut self.theme_setting, setting, setting.as_str());
+                }
+            });
+
             ui.horizontal(|ui| {
                 ui.selectable_value(&mut self.view_mode, ViewMode::Ascii, "ASCII");
                 ui.selectable_value(&mut self.view_mode, ViewMode::Hex, "HEX");
                 ui.selectable_value(&mut self.view_mode, ViewMode::Both, "Both");
+                ui.selectable_value(&mut self.view_mode, ViewMode::Auto, "Auto")
+                    .on_hover_text("Each line as text, or as hex when too little of it is printable");
             });
 
+            if self.view_mode == ViewMode::Auto {
+                ui.horizontal(|ui| {
+                    ui.label("Text at:");
+                    ui.add(
+                        egui::Slider::new(
+                            &mut self.auto_view.printable_percent,
+                            auto_view::MIN_PRINTABLE_PERCENT..=100,
+                        )
+                        .suffix("% printable"),
+                    )
+                    .spoken("Auto view printable threshold");
+                });
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Gutter:");
+                for gutter in [Gutter::Off, Gutter::Offset, Gutter::Line] {
+                    ui.selectable_value(&mut self.gutter, gutter, gutter.as_str());
+                }
+            })
+            .response
+            .on_hover_text("Session byte offset (hex) or line number of each terminal line");
+
+            ui.horizontal(|ui| {
+                ui.label("Font size:");
+                ui.add(
+                    egui::DragValue::new(&mut self.terminal_font_size)
+                        .range(MIN_TERMINAL_FONT_SIZE..=MAX_TERMINAL_FONT_SIZE)
+                        .speed(0.1)
+                        .max_decimals(1),
+                )
+                .spoken("Font size");
+                if ui.small_button("Reset").clicked() {
+                    self.reset_terminal_zoom();
+                }
+            })
+            .response
+            .on_hover_text("Ctrl+wheel over the terminal zooms, Ctrl+0 resets; Shift+wheel scrolls sideways");
+
             ui.checkbox(&mut self.auto_scroll, "Auto-scroll");
+            ui.checkbox(&mut self.activity_ticks, "Tick on RX/TX activity")
+                .on_hover_text("A short system sound at the start of each burst of activity");
+            self.render_sound_settings(ui);
+
+            self.action_checkbox(ui, Action::StripAnsi, "Strip ANSI codes");
+
+            ui.checkbox(&mut self.timestamp_tx, "Timestamp TX lines");
 
-            if ui.checkbox(&mut self.strip_ansi, "Strip ANSI codes").changed() {
+            if ui
+                .checkbox(&mut self.mask_7bit, "Mask RX text to 7 bits")
+                .on_hover_text("Clear bit 7 before decoding text (7-bit links)")
+                .changed()
+            {
                 self.update_display_buffer();
             }
-        });
-    }
 
-    fn render_logging_group(&mut self, ui: &mut egui::Ui) {
-        ui.group(|ui| {
-            ui.label(egui::RichText::new("Logging").strong());
-            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .checkbox(&mut self.burst_grouping, "Group bursts")
+                    .on_hover_text("Draw a divider with size and duration after each burst of RX data")
+                    .changed()
+                {
+                    self.update_display_buffer();
+                }
+                ui.add_enabled(
+                    self.burst_grouping,
+                    egui::DragValue::new(&mut self.burst_gap_ms)
+                        .range(1..=5000)
+                        .suffix(" ms gap"),
+                )
+                .spoken("Burst gap");
+            });
 
-            ui.checkbox(&mut self.logging_enabled, "Enable logging");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.idle_flush, "End lines when idle")
+                    .on_hover_text("Treat an unterminated last line as complete for pinned values, variables and syslog once RX goes quiet");
+                ui.add_enabled(
+                    self.idle_flush,
+                    egui::DragValue::new(&mut self.idle_flush_ms)
+                        .range(10..=10_000)
+                        .suffix(" ms"),
+                )
+                .spoken("Idle time");
+            });
 
-            if self.logging_enabled {
-                ui.horizontal(|ui| {
-                    ui.label("File:");
-                    ui.add(
-                        egui::TextEdit::singleline(&mut self.log_file_path)
-                            .desired_width(ui.available_width() - 60.0),
+            ui.horizontal(|ui| {
+                ui.label("Encoding:");
+                let mut encoding = self.display_encoding;
+                egui::ComboBox::from_id_source("display_encoding")
+                    .width(80.0)
+                    .selected_text(encoding.as_str())
+                    .show_ui(ui, |ui: &mut egui::Ui| {
+                        for option in TextEncoding::ALL {
+                            ui.selectable_value(&mut encoding, option, option.as_str());
+                        }
+                    })
+                    .response
+                    .spoken("Display encoding")
+                    .on_hover_text("RX is decoded as it arrives; the terminal, hex view and exports hold the text");
+                if encoding != self.display_encoding {
+                    self.set_display_encoding(encoding);
+                }
+                ui.label("Detect:");
+                egui::ComboBox::from_id_source("encoding_detection")
+                    .width(60.0)
+                    .selected_text(self.encoding_detection.as_str())
+                    .show_ui(ui, |ui: &mut egui::Ui| {
+                        for option in EncodingDetection::ALL {
+                            ui.selectable_value(&mut self.encoding_detection, option, option.as_str());
+                        }
+                    })
+                    .response
+                    .spoken("Encoding detection")
+                    .on_hover_text(
+                        "Look for UTF-16 (a byte order mark, or NUL every other byte) at the start of each \
+                         session, and offer or apply it",
                     );
-                });
+            });
 
-                if ui.button("Save Log").clicked() {
-                    self.save_log();
+            ui.horizontal(|ui| {
+                let table_loaded = self.translation_table.is_some();
+                if ui
+                    .add_enabled(
+                        table_loaded,
+                        egui::Checkbox::new(&mut self.translation_enabled, "Translate bytes"),
+                    )
+                    .changed()
+                {
+                    self.update_display_buffer();
                 }
-            }
+
+                let (selected, mapped) = match &self.translation_table {
+                    Some(table) => (table.name.clone(), table.mapped_count()),
+                    None => ("None".to_string(), 0),
+                };
+                let mut chosen = None;
+                egui::ComboBox::from_id_source("translation_table")
+                    .width(ui.available_width())
+                    .selected_text(selected)
+                    .show_ui(ui, |ui: &mut egui::Ui| {
+                        for builtin in [BuiltinTable::Cp037, BuiltinTable::Cp437, BuiltinTable::Petscii] {
+                            if ui.selectable_label(false, builtin.as_str()).clicked() {
+                                chosen = Some(builtin);
+                            }
+                        }
+                    })
+                    .response
+                    .spoken("Translation table")
+                    .on_hover_text(format!("{} of 256 byte values mapped", mapped));
+                if let Some(builtin) = chosen {
+                    self.set_translation_table(builtin.table());
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.translation_path)
+                        .desired_width(ui.available_width() - 45.0)
+                        .hint_text("table.csv / .json"),
+                )
+                .spoken("Translation table file");
+                if ui
+                    .add_enabled(!self.translation_path.is_empty(), egui::Button::new("Load"))
+                    .on_hover_text("Lines of 'byte,text' (CSV) or an object of byte -> text (JSON)")
+                    .clicked()
+                {
+                    self.load_translation_table();
+                }
+            });
         });
     }
 
-    fn render_filter_group(&mut self, ui: &mut egui::Ui) {
+    fn render_sound_settings(&mut self, ui: &mut egui::Ui) {
+        let before = self.sound_settings.clone();
+        let settings = &mut self.sound_settings;
+        ui.checkbox(&mut settings.enabled, "Sound monitor").on_hover_text(
+            "Hear bus activity: a tick per frame or per N bytes through the audio output; \
+             a flood becomes a steady hum",
+        );
+        if settings.enabled {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("sound_theme")
+                    .width(70.0)
+                    .selected_text(settings.theme.as_str())
+                    .show_ui(ui, |ui| {
+                        for theme in SoundTheme::ALL {
+                            ui.selectable_value(&mut settings.theme, theme, theme.as_str());
+                        }
+                    })
+                    .response
+                    .spoken("Sound theme");
+                ui.add(egui::Slider::new(&mut settings.volume, 0.0..=1.0).show_value(false))
+                    .spoken("Volume")
+                    .on_hover_text("Volume");
+            });
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("sound_trigger")
+                    .width(90.0)
+                    .selected_text(settings.trigger.as_str())
+                    .show_ui(ui, |ui| {
+                        for trigger in [SoundTrigger::Frame, SoundTrigger::Bytes] {
+                            ui.selectable_value(&mut settings.trigger, trigger, trigger.as_str());
+                        }
+                    })
+                    .response
+                    .spoken("Sound trigger")
+                    .on_hover_text("Per frame ticks for each decoded frame, or each read or write without a decoder");
+                if settings.trigger == SoundTrigger::Bytes {
+                    ui.add(egui::DragValue::new(&mut settings.every_bytes).range(1..=1_000_000).prefix("N = "))
+                        .spoken("Bytes per tick");
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut settings.rx, "RX");
+                ui.checkbox(&mut settings.tx, "TX");
+                ui.checkbox(&mut settings.errors, "Errors")
+                    .on_hover_text("Malformed decoded frames, such as CRC failures");
+            });
+        }
+        if self.sound_settings != before {
+            self.apply_sound_settings();
+        }
+    }
+
+    fn render_protocol_group(&mut self, ui: &mut egui::Ui) {
         ui.group(|ui| {
-            ui.label(egui::RichText::new("Filter").strong());
+            self.group_heading(ui, "Protocol");
             ui.separator();
 
-            ui.checkbox(&mut self.filter_enabled, "Enable filter");
-
-            if self.filter_enabled {
-                ui.label("Pattern:");
-
-                let response = ui.add(
-                    egui::TextEdit::singleline(&mut self.filter_pattern)
-                        .hint_text("regex pattern..."),
-                );
+            let previous_mode = self.protocol_mode;
+            let previous_framing = self.custom_framing;
 
-                if response.changed() {
-                    self.update_filter();
+            ui.horizontal(|ui| {
+                ui.label("Mode:");
+                let combo = egui::ComboBox::from_id_source("protocol_mode")
+                    .width(ui.available_width())
+                    .selected_text(self.protocol_mode.as_str())
+                    .show_ui(ui, |ui: &mut egui::Ui| {
+                        for mode in ProtocolMode::ALL {
+                            ui.selectable_value(&mut self.protocol_mode, mode, mode.as_str());
+                        }
+                    })
+                    .response
+                    .spoken("Protocol mode");
+                if let Some(shortcut) = Action::CycleProtocol.shortcut() {
+                    combo.on_hover_text(format!("{} cycles through the modes", ui.ctx().format_shortcut(&shortcut)));
                 }
+            });
+
+            if self.protocol_mode == ProtocolMode::Custom {
+                ui.horizontal(|ui| {
+                    ui.label("Framing:");
+                    egui::ComboBox::from_id_source("custom_framing")
+                        .width(ui.available_width())
+                        .selected_text(self.custom_framing.as_str())
+                        .show_ui(ui, |ui: &mut egui::Ui| {
+                            ui.selectable_value(&mut self.custom_framing, CustomFraming::Slip, "SLIP");
+                            ui.selectable_value(&mut self.custom_framing, CustomFraming::Cobs, "COBS");
+                            ui.selectable_value(
+                                &mut self.custom_framing,
+                                CustomFraming::StxEtx,
+                                CustomFraming::StxEtx.as_str(),
+                            );
+                            ui.selectable_value(
+                                &mut self.custom_framing,
+                                CustomFraming::Rule,
+                                CustomFraming::Rule.as_str(),
+                            );
+                        })
+                        .response
+                        .spoken("Framing");
+                });
             }
-        });
-    }
 
-    fn render_virtual_com_group(&mut self, ui: &mut egui::Ui) {
-        ui.group(|ui| {
-            ui.label(egui::RichText::new("Virtual COM").strong());
-            ui.separator();
+            let previous_rule = self.framing_rule.clone();
+            if self.protocol_mode == ProtocolMode::Custom && self.custom_framing == CustomFraming::Rule {
+                self.render_framing_rule(ui);
+            }
 
-            ui.label(egui::RichText::new("Create loopback COM port pairs").small());
+            if self.protocol_mode != previous_mode
+                || self.custom_framing != previous_framing
+                || self.framing_rule != previous_rule
+            {
+                self.update_decoder();
+            }
 
-            if let Some(vport) = &self.virtual_com_port.clone() {
-                ui.colored_label(egui::Color32::GREEN, vport);
+            if self.protocol_mode == ProtocolMode::Custom && self.custom_framing == CustomFraming::StxEtx {
+                self.render_stx_compose(ui);
+            }
 
-                if ui.button("Clear").clicked() {
-                    self.virtual_com_port = None;
+            if self.protocol_mode != ProtocolMode::None {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} frames", self.decoded_frames.len()));
+                    if ui.small_button("Clear").clicked() {
+                        self.clear_decoded_frames();
+                    }
+                });
+                if self.protocol_mode == ProtocolMode::Nmea {
+                    let nmea = &self.nmea_stats;
+                    let color = if nmea.checksum_errors > 0 {
+                        self.palette.error
+                    } else {
+                        ui.visuals().text_color()
+                    };
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "Bad checksums: {} of {} sentences, {} truncated",
+                            nmea.checksum_errors, nmea.sentences, nmea.malformed
+                        ))
+                        .color(color),
+                    )
+                    .on_hover_text("Counted since the last counter reset");
                 }
-            } else {
-                if ui.button("Create/Find Pair").clicked() {
-                    self.create_virtual_com();
+
+                let mut view = self.decoded_view();
+                ui.horizontal(|ui| {
+                    ui.label("Show:");
+                    egui::ComboBox::from_id_source("decoded_view")
+                        .width(ui.available_width())
+                        .selected_text(view.as_str())
+                        .show_ui(ui, |ui: &mut egui::Ui| {
+                            for option in
+                                [DecodedView::Raw, DecodedView::Decoded, DecodedView::Interleaved]
+                            {
+                                ui.selectable_value(&mut view, option, option.as_str());
+                            }
+                        })
+                        .response
+                        .spoken("Show frames as");
+                });
+                if view != self.decoded_view() {
+                    self.set_decoded_view(view);
                 }
+            }
 
-                ui.label(
-                    egui::RichText::new("Click to create or find existing com0com port pairs")
-                        .italics()
-                        .small(),
-                );
+            if self.protocol_mode.is_modbus() {
+                self.render_slave_controls(ui);
             }
         });
     }
 
-    fn render_central_panel(&mut self, ctx: &egui::Context) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical(|ui| {
-                // Terminal header
+    /// Editors for the custom framing rule; changes apply to data received from then on.
+    fn render_framing_rule(&mut self, ui: &mut egui::Ui) {
+        let rule = &mut self.framing_rule;
+        ui.horizontal(|ui| {
+            ui.label("Frames end:");
+            egui::ComboBox::from_id_source("framing_rule_boundary")
+                .selected_text(rule.boundary.as_str())
+                .show_ui(ui, |ui: &mut egui::Ui| {
+                    for boundary in FrameBoundary::ALL {
+                        ui.selectable_value(&mut rule.boundary, boundary, boundary.as_str());
+                    }
+                })
+                .response
+                .spoken("Frames end");
+        });
+        match rule.boundary {
+            FrameBoundary::Delimiter => {
                 ui.horizontal(|ui| {
-                    ui.heading("Terminal");
-
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        if ui.button("Clear").clicked() {
-                            self.receive_buffer.clear();
-                            self.receive_buffer_display.clear();
+                    ui.label("Delimiter:");
+                    let edited = ui
+                        .add(egui::TextEdit::singleline(&mut self.framing_delimiter_text).desired_width(80.0))
+                        .spoken("Delimiter")
+                        .on_hover_text("Hex bytes, e.g. 0D 0A")
+                        .changed();
+                    match hex::parse_hex_input(&self.framing_delimiter_text) {
+                        Ok(bytes) if !bytes.is_empty() => {
+                            if edited {
+                                rule.delimiter = bytes;
+                            }
                         }
-
-                        if ui.button("Save").clicked() {
-                            self.save_buffer();
+                        Ok(_) => {
+                            ui.colored_label(self.palette.error, "empty");
                         }
-                    });
+                        Err(e) => {
+                            ui.colored_label(self.palette.error, e);
+                        }
+                    }
+                });
+            }
+            FrameBoundary::FixedLength => {
+                ui.horizontal(|ui| {
+                    ui.label("Length:");
+                    ui.add(egui::DragValue::new(&mut rule.fixed_len).range(1..=MAX_FRAME_SIZE).suffix(" bytes"))
+                        .spoken("Frame length")
+                        .on_hover_text("Whole frame, checksum included");
                 });
+            }
+            FrameBoundary::StxEtx => {
+                ui.checkbox(&mut rule.length_byte, "Length byte after STX");
+            }
+        }
+        ui.horizontal(|ui| {
+            ui.label("Checksum:");
+            egui::ComboBox::from_id_source("framing_rule_checksum")
+                .selected_text(rule.checksum.as_str())
+                .show_ui(ui, |ui: &mut egui::Ui| {
+                    for checksum in TrailingChecksum::ALL {
+                        ui.selectable_value(&mut rule.checksum, checksum, checksum.as_str());
+                    }
+                })
+                .response
+                .spoken("Checksum");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Max frame:");
+            ui.add(egui::DragValue::new(&mut rule.max_len).range(1..=MAX_FRAME_SIZE).suffix(" bytes"))
+                .spoken("Max frame")
+                .on_hover_text("Longer frames are shown as FAIL and dropped, so a missing delimiter cannot fill memory");
+        });
+    }
 
-                ui.separator();
+    /// Compose an STX/ETX frame and see its bytes, BCC included, before sending.
+    fn render_stx_compose(&mut self, ui: &mut egui::Ui) {
+        let mut send = false;
+        ui.horizontal(|ui| {
+            ui.label("Compose:");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.stx_compose)
+                    .hint_text("payload")
+                    .desired_width(ui.available_width() - 50.0),
+            )
+            .spoken("STX frame payload");
+            let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let button = ui.add_enabled(self.connected && !self.monitor_only, egui::Button::new("Send"));
+            send = button.clicked() || (enter && self.connected && !self.monitor_only);
+        });
+        let frame = self.composed_frame();
+        ui.label(egui::RichText::new(protocol::format_payload_hex(&frame)).small().monospace())
+            .on_hover_text("STX, payload and line ending, ETX, then the BCC (XOR of payload and ETX)");
+        if send {
+            if let Err(e) = self.send_composed_frame() {
+                self.error_message = Some(e);
+            }
+        }
+    }
 
-                // Receive area
-                let text_height = ui.available_height() - 60.0;
+    fn render_slave_controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Response timeout:");
+            if ui
+                .add(
+                    egui::DragValue::new(&mut self.modbus_timeout_ms)
+                        .range(10..=60_000)
+                        .suffix(" ms"),
+                )
+                .spoken("Response timeout")
+                .changed()
+            {
+                self.modbus_tracker.timeout = Duration::from_millis(self.modbus_timeout_ms);
+            }
+        });
+        if self.protocol_mode == ProtocolMode::ModbusRtu {
+            ui.horizontal(|ui| {
+                ui.label("Frame gap:");
+                let response = ui
+                    .add(
+                        egui::DragValue::new(&mut self.rtu_frame_gap_ms)
+                            .range(0..=1000)
+                            .custom_formatter(|ms, _| if ms == 0.0 { "3.5 chars".to_string() } else { format!("{} ms", ms) }),
+                    )
+                    .spoken("Frame gap")
+                    .on_hover_text("Silence that ends a frame. Raise it for USB adapters that deliver bytes in batches; 0 uses 3.5 characters at the baud rate");
+                if response.changed() {
+                    self.update_decoder();
+                }
+            });
+        }
 
-                egui::ScrollArea::vertical()
-                    .auto_shrink([false; 2])
-                    .stick_to_bottom(self.auto_scroll)
-                    .max_height(text_height)
-                    .show(ui, |ui| {
-                        ui.add(
-                            egui::TextEdit::multiline(&mut self.receive_buffer_display.as_str())
-                                .font(egui::TextStyle::Monospace)
-                                .desired_width(f32::INFINITY)
-                                .desired_rows(30),
-                        );
-                    });
+        let slaves: Vec<u8> = self.modbus_tracker.slaves.keys().copied().collect();
+        if slaves.is_empty() {
+            return;
+        }
+        ui.label("Show slaves:");
+        ui.horizontal_wrapped(|ui| {
+            if ui
+                .selectable_label(self.slave_filter.is_empty(), "All")
+                .clicked()
+            {
+                self.slave_filter.clear();
+                self.update_display_buffer();
+            }
+            for slave in slaves {
+                let text = egui::RichText::new(slave.to_string()).color(self.palette.slave(slave));
+                if ui
+                    .selectable_label(self.slave_filter.contains(&slave), text)
+                    .clicked()
+                {
+                    self.toggle_slave_filter(slave);
+                }
+            }
+        });
+    }
+
+    fn render_statistics_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Statistics");
+            ui.separator();
+
+            ui.label(format!(
+                "RX {} bytes, TX {} bytes",
+                self.bytes_received, self.bytes_sent
+            ));
+
+            let sizes = &self.burst_stats.sizes;
+            let gaps = &self.burst_stats.gaps;
+            let fmt = |value: Option<f64>| value.map(|v| format!("{:.1}", v)).unwrap_or("-".into());
+
+            egui::Grid::new("burst_stats")
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Bursts");
+                    ui.label("Size (B)");
+                    ui.label("Gap (ms)");
+                    ui.end_row();
+
+                    ui.label("Count");
+                    ui.label(sizes.count().to_string());
+                    ui.label(gaps.count().to_string());
+                    ui.end_row();
+
+                    for (name, size, gap) in [
+                        ("Min", sizes.min(), gaps.min()),
+                        ("Max", sizes.max(), gaps.max()),
+                        ("Mean", sizes.mean(), gaps.mean()),
+                        ("P95", sizes.percentile(95.0), gaps.percentile(95.0)),
+                    ] {
+                        ui.label(name);
+                        ui.label(fmt(size));
+                        ui.label(fmt(gap));
+                        ui.end_row();
+                    }
+                });
+
+            if self.protocol_mode == ProtocolMode::ModbusRtu {
+                let rtu = &self.rtu_timing_stats;
+                let color = if rtu.violations() > 0 {
+                    self.palette.marker
+                } else {
+                    ui.visuals().text_color()
+                };
+                ui.label(format!("RTU frames: {}", rtu.frames));
+                ui.label(
+                    egui::RichText::new(format!(
+                        "Short frame gaps (<3.5 char): {}\nLong char gaps (>1.5 char): {}",
+                        rtu.short_frame_gaps, rtu.long_char_gaps
+                    ))
+                    .color(color),
+                )
+                .on_hover_text(
+                    "Gaps are measured from chunk arrival times; USB adapters that batch \
+                     data can report gaps that were not on the wire",
+                );
+            }
+
+            if self.protocol_mode.is_modbus() && !self.modbus_tracker.slaves.is_empty() {
+                egui::Grid::new("slave_stats")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for heading in ["Slave", "Req", "Resp", "Exc", "T/O", "Avg ms"] {
+                            ui.label(heading);
+                        }
+                        ui.end_row();
+
+                        for (slave, stats) in &self.modbus_tracker.slaves {
+                            ui.colored_label(self.palette.slave(*slave), slave.to_string());
+                            ui.label(stats.requests.to_string());
+                            ui.label(stats.responses.to_string());
+                            ui.label(stats.exceptions.to_string());
+                            ui.label(stats.timeouts.to_string());
+                            ui.label(fmt(stats.mean_latency().map(burst::ms)));
+                            ui.end_row();
+                        }
+                    });
+            }
+
+            self.action_checkbox(ui, Action::LowLatency, "Low latency").on_hover_text(
+                "Time echoes: each read is timed against the last write, both stamped on the I/O \
+                 path, and the port is read with a 1 ms timeout. Uses more CPU.",
+            );
+            if self.low_latency {
+                let echo = &self.echo_latency.latencies;
+                let last = self.echo_latency.last.map(burst::ms);
+                ui.label(format!(
+                    "Echo latency (ms): last {}, mean {}, min {}, max {}, P95 {} ({} samples)",
+                    fmt(last),
+                    fmt(echo.mean()),
+                    fmt(echo.min()),
+                    fmt(echo.max()),
+                    fmt(echo.percentile(95.0)),
+                    echo.count()
+                ));
+            }
+
+            ui.horizontal(|ui| {
+                self.action_button(ui, Action::ResetCounters, egui::Button::new("Reset counters"), "");
+                if ui
+                    .add_enabled(
+                        self.burst_stats.record_count() > 0,
+                        egui::Button::new("Export CSV"),
+                    )
+                    .on_hover_text("Per-burst start time, size, duration and preceding gap")
+                    .clicked()
+                {
+                    self.export_burst_csv();
+                }
+            });
+
+            egui::CollapsingHeader::new("Compare sessions")
+                .default_open(false)
+                .show(ui, |ui| self.render_session_comparison(ui));
+        });
+    }
+
+    /// Saved statistics snapshots side by side with the live values, each
+    /// delta coloured by whether it is an improvement.
+    fn render_session_comparison(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui
+                .button("Save snapshot")
+                .on_hover_text("Write the current statistics to stats_<time>.json")
+                .clicked()
+            {
+                self.save_stats_snapshot();
+            }
+            ui.checkbox(&mut self.snapshot_on_disconnect, "On disconnect");
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.stats_snapshot_path)
+                    .hint_text("stats_....json")
+                    .desired_width(ui.available_width() - 50.0),
+            )
+            .spoken("Snapshot file");
+            if ui.button("Load").clicked() {
+                self.load_stats_snapshot();
+            }
+        });
+
+        if self.stats_snapshots.is_empty() {
+            return;
+        }
+
+        let live = self.stats_snapshot();
+        let names = stats_snapshot::metric_names(&live, &self.stats_snapshots);
+        let mut remove = None;
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            egui::Grid::new("session_comparison")
+                .num_columns(2 + 2 * self.stats_snapshots.len())
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label(egui::RichText::new("Live").strong());
+                    for (index, snapshot) in self.stats_snapshots.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(egui::RichText::new(&snapshot.taken_at).strong())
+                                .on_hover_text(snapshot.label());
+                            if ui.small_button("x").spoken("Remove").clicked() {
+                                remove = Some(index);
+                            }
+                        });
+                        ui.label("Δ").spoken("Change");
+                    }
+                    ui.end_row();
+
+                    for name in names {
+                        ui.label(name);
+                        let current = live.get(name);
+                        ui.label(current.map_or("-".to_string(), Metric::display));
+                        for snapshot in &self.stats_snapshots {
+                            let saved = snapshot.get(name);
+                            ui.label(saved.map_or("-".to_string(), Metric::display));
+                            match (current, saved) {
+                                (Some(current), Some(saved)) => {
+                                    let comparison = stats_snapshot::compare(current, saved);
+                                    let color = match comparison.change {
+                                        Change::Same => ui.visuals().weak_text_color(),
+                                        Change::Better => self.palette.ok,
+                                        Change::Worse => self.palette.error,
+                                        Change::Different => self.palette.warning,
+                                    };
+                                    ui.colored_label(color, comparison.display(&current.unit));
+                                }
+                                _ => {
+                                    ui.label("");
+                                }
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+        if let Some(index) = remove {
+            self.stats_snapshots.remove(index);
+        }
+    }
+
+    fn render_logging_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Logging");
+            ui.separator();
+
+            let mut enabled = self.logging_enabled;
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut enabled, "Enable logging").changed() {
+                    self.set_logging(enabled);
+                }
+                if self.log_stream.is_some() {
+                    ui.colored_label(egui::Color32::RED, "●").spoken("Logging active");
+                }
+            });
+
+            ui.add_enabled_ui(!self.logging_enabled, |ui| {
+                ui.checkbox(&mut self.log_streaming, "Stream to file as captured")
+                    .on_hover_text("Append entries to the log file as they arrive, so nothing is lost on a crash");
+            });
+
+            if self.logging_enabled || self.log_streaming {
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.add_enabled(
+                        self.log_stream.is_none(),
+                        egui::TextEdit::singleline(&mut self.log_file_path)
+                            .desired_width(ui.available_width() - 60.0),
+                    )
+                    .spoken("Log file");
+                });
+            }
+
+            if self.log_streaming {
+                ui.horizontal(|ui| {
+                    let keep = ui.checkbox(&mut self.log_keep_in_memory, "Also keep last");
+                    let count = ui
+                        .add(egui::DragValue::new(&mut self.log_memory_entries).range(100..=10_000_000))
+                        .spoken("Entries kept in memory while streaming");
+                    ui.label("entries in memory");
+                    if (keep.changed() || count.changed()) && self.log_stream.is_some() {
+                        self.log_entries.keep_last = self.log_keep_in_memory.then_some(self.log_memory_entries);
+                    }
+                });
+                if let Some(stream) = &self.log_stream {
+                    ui.label(format!("{} entries, {} KB written", stream.entries(), stream.size() / 1024));
+                }
+            } else if self.logging_enabled {
+                self.action_button(ui, Action::SaveLog, egui::Button::new("Save Log"), "");
+
+                ui.horizontal(|ui| {
+                    ui.label("Keep in memory:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.log_entries.max_entries)
+                            .range(100..=10_000_000)
+                            .suffix(" entries"),
+                    )
+                    .spoken("Entries kept in memory");
+                    let mut mb = self.log_entries.max_bytes / (1024 * 1024);
+                    if ui
+                        .add(egui::DragValue::new(&mut mb).range(1..=1024).suffix(" MB"))
+                        .spoken("Megabytes kept in memory")
+                        .changed()
+                    {
+                        self.log_entries.max_bytes = mb * 1024 * 1024;
+                    }
+                })
+                .response
+                .on_hover_text("Older entries move to a temporary file and are merged back on Save Log");
+
+                if !self.log_entries.is_empty() {
+                    let spilled = self.log_entries.spilled();
+                    ui.label(if spilled > 0 {
+                        format!("{} entries ({} on disk)", self.log_entries.len(), spilled)
+                    } else {
+                        format!("{} entries", self.log_entries.len())
+                    });
+                }
+                if let Some(error) = &self.log_entries.spill_error {
+                    ui.colored_label(self.palette.error, error);
+                }
+            }
+
+            ui.separator();
+            self.render_offline_log(ui);
+            ui.separator();
+            self.render_db_logging(ui);
+            ui.separator();
+
+            ui.checkbox(&mut self.boot_log_enabled, "Capture boot log on connect")
+                .on_hover_text("Save the first seconds of RX after every connect to its own file");
+
+            if self.boot_log_enabled {
+                ui.horizontal(|ui| {
+                    ui.label("First");
+                    ui.add(egui::DragValue::new(&mut self.boot_log_seconds).range(1..=600).suffix(" s"))
+                        .spoken("Boot log seconds");
+                    ui.label("or");
+                    ui.add(egui::DragValue::new(&mut self.boot_log_kb).range(1..=10240).suffix(" KB"))
+                        .spoken("Boot log kilobytes");
+                });
+            }
+
+            let mut open = None;
+            for path in self.boot_logs.iter().rev() {
+                ui.horizontal(|ui| {
+                    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+                    let recording = self.boot_capture.as_ref().is_some_and(|c| &c.path == path);
+                    if ui.small_button("Open").clicked() {
+                        open = Some(path.clone());
+                    }
+                    if recording {
+                        ui.colored_label(egui::Color32::RED, "●").spoken("Recording");
+                    }
+                    ui.label(egui::RichText::new(name).small());
+                });
+            }
+            if let Some(path) = open {
+                if let Err(e) = crate::boot_log::open_in_system(&path) {
+                    self.error_message = Some(e);
+                }
+            }
+
+            ui.separator();
+            self.render_split_log(ui);
+            ui.separator();
+            self.render_recorder_controls(ui);
+        });
+    }
+
+    /// Opening a saved log as an offline session, and what was opened.
+    fn render_offline_log(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Open log:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.log_import_path)
+                    .hint_text("rustcom_....log / .csv / .pcapng")
+                    .desired_width(ui.available_width() - 50.0),
+            )
+            .spoken("Log file to open");
+            let idle = !self.connected && !self.connecting();
+            if ui
+                .add_enabled(idle, egui::Button::new("Open"))
+                .on_hover_text("Replay a RustCOM text, CSV or pcapng log with its directions and times")
+                .on_disabled_hover_text("Disconnect first")
+                .clicked()
+            {
+                if let Err(e) = self.open_offline_log() {
+                    self.error_message = Some(e);
+                }
+            }
+        });
+        if let Some(session) = &self.offline_session {
+            let name = std::path::Path::new(&session.path)
+                .file_name()
+                .map_or_else(|| session.path.clone(), |name| name.to_string_lossy().into_owned());
+            let mut text = format!("Offline: {} ({}, {} entries)", name, session.format.as_str(), session.entries);
+            if session.skipped > 0 {
+                text.push_str(&format!(", {} skipped", session.skipped));
+            }
+            if let Some(replay) = &self.offline_replay {
+                text.push_str(&format!(", replaying, {} left", replay.entries.len()));
+            }
+            ui.label(egui::RichText::new(text).small())
+                .on_hover_text("Unreadable lines, rows or packets are skipped");
+        }
+    }
+
+    fn render_recorder_controls(&mut self, ui: &mut egui::Ui) {
+        let recording = self.recording.is_some();
+        ui.horizontal(|ui| {
+            ui.label("Raw recorder:");
+            if recording {
+                if ui.button("Stop").clicked() {
+                    self.stop_recording();
+                }
+            } else if ui
+                .add_enabled(self.connected && self.xmodem.is_none(), egui::Button::new("Record"))
+                .clicked()
+            {
+                if let Err(e) = self.start_recording() {
+                    self.error_message = Some(e);
+                }
+            }
+        })
+        .response
+        .on_hover_text(
+            "Write RX straight to a new file in the folder below, skipping the display, decoders and filters, \
+             for captures of any size",
+        );
+        ui.add_enabled_ui(!recording, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Folder:");
+                ui.add(egui::TextEdit::singleline(&mut self.recorder_dir).desired_width(ui.available_width()))
+                    .spoken("Recording folder");
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.recorder_gzip, "Gzip");
+                ui.checkbox(&mut self.recorder_tail, "Tail preview")
+                    .on_hover_text("Show the last 1 KB recorded");
+            });
+        });
+    }
+
+    /// Counters in place of the terminal while recording raw.
+    fn render_recording_view(&mut self, ui: &mut egui::Ui, height: f32) {
+        let Some(recording) = &self.recording else {
+            return;
+        };
+        let bytes = recording.status.bytes();
+        let elapsed = recording.started.elapsed();
+        let average = bytes as f64 / elapsed.as_secs_f64().max(1e-3);
+        let tail = recording.status.tail();
+        let mut open = None;
+        let mut stop = false;
+        ui.allocate_ui(egui::vec2(ui.available_width(), height), |ui| {
+            ui.set_min_height(height);
+            ui.horizontal(|ui| {
+                ui.colored_label(egui::Color32::RED, "●").spoken("Recording");
+                ui.label(format!("Recording raw RX to {}", recording.path.display()));
+                if ui.small_button("Open folder").clicked() {
+                    open = recording.path.parent().map(|dir| dir.to_path_buf());
+                }
+                if ui.small_button("Stop").clicked() {
+                    stop = true;
+                }
+            });
+            egui::Grid::new("recording_counters").num_columns(2).show(ui, |ui| {
+                ui.label("Recorded:");
+                ui.label(recorder::format_size(bytes));
+                ui.end_row();
+                ui.label("Throughput:");
+                ui.label(cable_test::format_rate(recording.rate.bytes_per_second));
+                ui.end_row();
+                ui.label("Average:");
+                ui.label(cable_test::format_rate(average));
+                ui.end_row();
+                ui.label("Elapsed:");
+                ui.label(format!("{} s", elapsed.as_secs()));
+                ui.end_row();
+            });
+            if let Some(tail) = tail {
+                ui.separator();
+                ui.label(format!("Last {} bytes:", TAIL_PREVIEW_BYTES.min(tail.len())));
+                let text = match self.view_mode {
+                    ViewMode::Ascii | ViewMode::Auto => String::from_utf8_lossy(&tail).to_string(),
+                    _ => hex::format_hex(&tail),
+                };
+                egui::ScrollArea::vertical()
+                    .id_source("recording_tail")
+                    .auto_shrink([false; 2])
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        ui.label(egui::RichText::new(text).monospace());
+                    });
+            }
+        });
+        if stop {
+            self.stop_recording();
+        }
+        if let Some(dir) = open {
+            if let Err(e) = crate::boot_log::open_in_system(&dir) {
+                self.error_message = Some(e);
+            }
+        }
+    }
+
+    fn render_split_log(&mut self, ui: &mut egui::Ui) {
+        let running = self.split_log.is_some();
+        ui.horizontal(|ui| {
+            ui.label("Split on:");
+            ui.add_enabled(
+                !running,
+                egui::TextEdit::singleline(&mut self.split_pattern)
+                    .hint_text("^U-Boot")
+                    .desired_width(ui.available_width() - 60.0),
+            );
+            if running {
+                if ui.button("Stop").clicked() {
+                    self.split_log = None;
+                }
+            } else if ui.button("Start").clicked() {
+                self.start_split_log();
+            }
+        })
+        .response
+        .on_hover_text(
+            "Write RX to a new file in split_logs each time a line matches this regex, \
+             e.g. a boot banner; the matching line starts the new file",
+        );
+        ui.horizontal(|ui| {
+            ui.label("File name:");
+            ui.add_enabled(
+                !running,
+                egui::TextEdit::singleline(&mut self.split_template).desired_width(ui.available_width()),
+            )
+            .on_hover_text("{n} is the boot number, {time} the time of the split");
+        });
+        ui.checkbox(&mut self.split_display, "Clear the terminal at each split");
+
+        let mut open = None;
+        if let Some(log) = &self.split_log {
+            ui.horizontal(|ui| {
+                ui.colored_label(egui::Color32::RED, "●").spoken("Recording");
+                ui.label(format!("Boot {}", log.boot));
+                let name = log.path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+                if ui.small_button("Open").clicked() {
+                    open = Some(log.path.clone());
+                }
+                ui.label(egui::RichText::new(name).small());
+            });
+        }
+        if let Some(path) = open {
+            if let Err(e) = crate::boot_log::open_in_system(&path) {
+                self.error_message = Some(e);
+            }
+        }
+    }
+
+    fn render_db_logging(&mut self, ui: &mut egui::Ui) {
+        let mut start = false;
+        let mut stop = false;
+        let mut apply = false;
+        ui.horizontal(|ui| {
+            ui.label("Database:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.db_path)
+                    .hint_text("capture.sqlite")
+                    .desired_width(ui.available_width() - 60.0),
+            );
+            if self.db_logger.is_some() {
+                stop = ui.button("Stop").clicked();
+            } else {
+                start = ui.button("Start").clicked();
+            }
+        })
+        .response
+        .on_hover_text("Write sessions, frames, extracted values and events to an SQLite file");
+
+        if let Some(db) = &self.db_logger {
+            ui.horizontal(|ui| {
+                ui.colored_label(egui::Color32::RED, "●").spoken("Recording");
+                ui.label(format!("{} rows, {} KB", db.rows_written(), db.file_size() / 1024));
+            });
+            // An edited path takes effect only when applied
+            if db.path() != std::path::Path::new(self.db_path.trim()) {
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        self.palette.warning,
+                        format!("Still writing to {}", db.path().display()),
+                    );
+                    apply = ui
+                        .button("Apply")
+                        .on_hover_text("Close the current file and continue logging in the new one")
+                        .clicked();
+                });
+            }
+            if let Some(error) = db.last_error() {
+                ui.colored_label(self.palette.error, error);
+            }
+        }
+
+        if start {
+            self.start_db_logging();
+        }
+        if stop {
+            self.stop_db_logging();
+        }
+        if apply {
+            if let Err(e) = self.apply_db_path() {
+                self.error_message = Some(e);
+            }
+        }
+    }
+
+    fn render_syslog_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Syslog")
+                .on_hover_text("Forward each received line as an RFC 5424 message to a log collector");
+            ui.separator();
+
+            let mut toggled = false;
+            let running = self.syslog_forwarder.is_some();
+            let mut enabled = running;
+            if ui.checkbox(&mut enabled, "Forward to syslog").changed() {
+                toggled = true;
+            }
+
+            let config = &mut self.syslog;
+            ui.add_enabled_ui(!running, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Host:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut config.host)
+                            .hint_text("logs.example.com")
+                            .desired_width(ui.available_width() - 130.0),
+                    )
+                    .spoken("Syslog host");
+                    ui.add(egui::DragValue::new(&mut config.port).range(1..=65535)).spoken("Syslog port");
+                    egui::ComboBox::from_id_source("syslog_transport")
+                        .width(50.0)
+                        .selected_text(config.transport.as_str())
+                        .show_ui(ui, |ui: &mut egui::Ui| {
+                            for transport in [Transport::Udp, Transport::Tcp] {
+                                ui.selectable_value(&mut config.transport, transport, transport.as_str());
+                            }
+                        })
+                        .response
+                        .spoken("Syslog transport");
+                });
+            });
+
+            egui::Grid::new("syslog_fields").num_columns(2).show(ui, |ui| {
+                ui.label("Hostname:");
+                ui.add(egui::TextEdit::singleline(&mut config.hostname).hint_text("this machine")).spoken("Hostname");
+                ui.end_row();
+                ui.label("App name:");
+                ui.text_edit_singleline(&mut config.app_name).spoken("App name");
+                ui.end_row();
+                ui.label("Facility:");
+                egui::ComboBox::from_id_source("syslog_facility")
+                    .selected_text(FACILITY_NAMES[usize::from(config.facility.min(23))])
+                    .show_ui(ui, |ui: &mut egui::Ui| {
+                        for (code, name) in FACILITY_NAMES.iter().enumerate() {
+                            ui.selectable_value(&mut config.facility, code as u8, *name);
+                        }
+                    })
+                    .response
+                    .spoken("Facility");
+                ui.end_row();
+                ui.label("Severity:");
+                severity_combo(ui, "syslog_default_severity", &mut config.default_severity);
+                ui.end_row();
+            });
+
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut config.forward_tx, "TX data");
+                ui.checkbox(&mut config.forward_events, "Event markers");
+            });
+
+            ui.label("Severity rules (first match wins):");
+            let mut remove = None;
+            for (index, rule) in config.rules.iter_mut().enumerate() {
+                ui.push_id(("syslog_rule", index), |ui| {
+                    ui.horizontal(|ui| {
+                        let response = ui.add(
+                            egui::TextEdit::singleline(&mut rule.pattern)
+                                .hint_text("ERROR")
+                                .desired_width(ui.available_width() - 130.0),
+                        )
+                        .spoken("Severity rule pattern");
+                        if response.changed() {
+                            rule.compile();
+                        }
+                        severity_combo(ui, "severity", &mut rule.severity);
+                        if ui.small_button("x").spoken("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                    if let Some(error) = &rule.error {
+                        ui.colored_label(self.palette.error, egui::RichText::new(error).small());
+                    }
+                });
+            }
+            if let Some(index) = remove {
+                config.rules.remove(index);
+            }
+            if ui.button("Add rule").clicked() {
+                config.rules.push(SeverityRule::new("", 3));
+            }
+
+            if let Some(forwarder) = &self.syslog_forwarder {
+                ui.label(format!("{}: {} sent, {} failed", forwarder.target(), forwarder.sent(), forwarder.failed()));
+                if let Some(error) = forwarder.last_error() {
+                    ui.colored_label(self.palette.error, error);
+                }
+            }
+
+            if toggled {
+                if running {
+                    self.stop_syslog();
+                } else {
+                    self.start_syslog();
+                }
+            }
+        });
+    }
+
+    fn render_metrics_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Metrics").on_hover_text(
+                "Serve byte, reconnect and frame counters and numeric pinned values and variables \
+                 in the Prometheus text format",
+            );
+            ui.separator();
+
+            let running = self.metrics_server.is_some();
+            let mut enabled = running;
+            let toggled = ui.checkbox(&mut enabled, "Serve /metrics").changed();
+            ui.horizontal(|ui| {
+                ui.label("Listen on:");
+                ui.add_enabled(
+                    !running,
+                    egui::TextEdit::singleline(&mut self.metrics_config.address).hint_text(DEFAULT_METRICS_ADDRESS),
+                )
+                .spoken("Metrics address");
+            });
+            if let Some(server) = &self.metrics_server {
+                let url = format!("http://{}/metrics", server.address());
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(&url).small());
+                    if ui.small_button("Copy").clicked() {
+                        ui.output_mut(|o| o.copied_text = url.clone());
+                    }
+                });
+            }
+
+            if toggled {
+                if running {
+                    self.stop_metrics();
+                } else {
+                    self.start_metrics();
+                }
+            }
+        });
+    }
+
+    fn render_live_view_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Live View")
+                .on_hover_text("A read-only page with the latest lines, connection status and counters, for a browser");
+            ui.separator();
+
+            let running = self.live_view_server.is_some();
+            let mut enabled = running;
+            let toggled = ui.checkbox(&mut enabled, "Serve in browser").changed();
+            ui.add_enabled_ui(!running, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Port:");
+                    ui.add(egui::DragValue::new(&mut self.live_view.port).range(1..=65535)).spoken("Live view port");
+                    ui.label("Lines:");
+                    ui.add(egui::DragValue::new(&mut self.live_view.lines).range(10..=MAX_LIVE_VIEW_LINES))
+                        .spoken("Live view lines");
+                });
+                ui.checkbox(&mut self.live_view.expose_lan, "Allow other machines")
+                    .on_hover_text("Listen on every network interface instead of this machine only; needs a token");
+                ui.horizontal(|ui| {
+                    ui.label("Token:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.live_view.token)
+                            .hint_text("none")
+                            .desired_width(120.0),
+                    )
+                    .spoken("Live view token");
+                    if ui.small_button("New").clicked() {
+                        self.live_view.token = live_view::new_token();
+                    }
+                });
+            });
+            if let Some(server) = &self.live_view_server {
+                let url = server.url();
+                ui.horizontal(|ui| {
+                    ui.hyperlink_to(egui::RichText::new(&url).small(), &url);
+                    if ui.small_button("Copy").clicked() {
+                        ui.output_mut(|o| o.copied_text = url.clone());
+                    }
+                });
+                if self.live_view.expose_lan {
+                    ui.colored_label(
+                        self.palette.warning,
+                        egui::RichText::new(format!("Reachable from the network on port {}", server.port())).small(),
+                    );
+                }
+            }
+
+            if toggled {
+                if running {
+                    self.stop_live_view();
+                } else {
+                    if self.live_view.expose_lan && self.live_view.token.trim().is_empty() {
+                        self.live_view.token = live_view::new_token();
+                    }
+                    self.start_live_view();
+                }
+            }
+        });
+    }
+
+    fn render_tcp_bridge_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "TCP Bridge").on_hover_text(
+                "Share the port over TCP while connected: port RX goes to every client, and what clients send \
+                 is written to the port and shown as TX",
+            );
+            ui.separator();
+
+            let running = self.tcp_bridge_server.is_some();
+            ui.add_enabled_ui(!running, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Port:");
+                    ui.add(egui::DragValue::new(&mut self.tcp_bridge.port).range(1..=65535)).spoken("TCP bridge port");
+                });
+                ui.checkbox(&mut self.tcp_bridge.expose_lan, "Allow other machines")
+                    .on_hover_text("Listen on every network interface instead of this machine only");
+            });
+            ui.checkbox(&mut self.tcp_bridge.read_only, "Read-only")
+                .on_hover_text("Clients only receive; what they send is dropped");
+
+            match &self.tcp_bridge_server {
+                Some(bridge) => {
+                    let clients = bridge.client_count();
+                    let port = bridge.port();
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Listening on port {}, {} client(s)", port, clients));
+                        if ui.button("Stop").clicked() {
+                            self.stop_tcp_bridge();
+                        }
+                    });
+                    if self.tcp_bridge.expose_lan {
+                        ui.colored_label(
+                            self.palette.warning,
+                            egui::RichText::new(format!("Anyone on the network can connect to port {}", port)).small(),
+                        );
+                    }
+                    if self.monitor_only && !self.tcp_bridge.read_only {
+                        ui.colored_label(
+                            self.palette.warning,
+                            egui::RichText::new("Monitor-only: client data is refused").small(),
+                        );
+                    }
+                    if self.recording.is_some() {
+                        ui.colored_label(
+                            self.palette.warning,
+                            egui::RichText::new("Raw recording: clients get no RX until it stops").small(),
+                        );
+                    }
+                }
+                None => {
+                    if ui.add_enabled(self.connected, egui::Button::new("Start")).clicked() {
+                        self.start_tcp_bridge();
+                    }
+                    if !self.connected {
+                        ui.weak("Connect to start the bridge");
+                    }
+                }
+            }
+        });
+    }
+
+    fn render_report_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Report");
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Title:");
+                ui.text_edit_singleline(&mut self.report_title);
+            });
+
+            let markers = if self.has_event_markers {
+                report::find_markers(&self.capture_text())
+            } else {
+                Vec::new()
+            };
+            ui.horizontal(|ui| {
+                ui.label("Include:");
+                egui::ComboBox::from_id_source("report_excerpt")
+                    .width(ui.available_width())
+                    .selected_text(self.report_excerpt.as_str())
+                    .show_ui(ui, |ui: &mut egui::Ui| {
+                        ui.selectable_value(&mut self.report_excerpt, Excerpt::None, Excerpt::None.as_str());
+                        ui.selectable_value(
+                            &mut self.report_excerpt,
+                            Excerpt::Everything,
+                            Excerpt::Everything.as_str(),
+                        );
+                        if markers.len() >= 2
+                            && ui
+                                .selectable_label(
+                                    matches!(self.report_excerpt, Excerpt::BetweenMarkers(..)),
+                                    "Between markers",
+                                )
+                                .clicked()
+                        {
+                            self.report_excerpt = Excerpt::BetweenMarkers(0, markers.len() - 1);
+                        }
+                    })
+                    .response
+                    .spoken("Excerpt");
+            });
+
+            if let Excerpt::BetweenMarkers(a, b) = &mut self.report_excerpt {
+                for (label, index) in [("From:", a), ("To:", b)] {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        let selected = markers
+                            .get(*index)
+                            .map(|m| m.text.clone())
+                            .unwrap_or_else(|| "(missing)".to_string());
+                        egui::ComboBox::from_id_source(label)
+                            .width(ui.available_width())
+                            .selected_text(selected)
+                            .show_ui(ui, |ui: &mut egui::Ui| {
+                                for (i, marker) in markers.iter().enumerate() {
+                                    ui.selectable_value(index, i, &marker.text);
+                                }
+                            })
+                            .response
+                            .spoken(&format!("{} marker", label.trim_end_matches(':')));
+                    });
+                }
+            }
+
+            self.action_button(
+                ui,
+                Action::GenerateReport,
+                egui::Button::new("Generate report…"),
+                "Standalone HTML file; print it from the browser for a PDF",
+            );
+        });
+    }
+
+    fn render_export_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Export Window");
+            ui.separator();
+
+            let marker_count = self.log_entries.events().len();
+            ui.horizontal(|ui| {
+                ui.label("Range:");
+                egui::ComboBox::from_id_source("export_range")
+                    .width(ui.available_width())
+                    .selected_text(self.export_range.as_str())
+                    .show_ui(ui, |ui: &mut egui::Ui| {
+                        for range in [ExportRange::LastMinutes, ExportRange::Timestamps] {
+                            ui.selectable_value(&mut self.export_range, range, range.as_str());
+                        }
+                        if marker_count >= 2
+                            && ui
+                                .selectable_label(
+                                    matches!(self.export_range, ExportRange::Markers(..)),
+                                    "Between markers",
+                                )
+                                .clicked()
+                        {
+                            self.export_range = ExportRange::Markers(0, marker_count - 1);
+                        }
+                    })
+                    .response
+                    .spoken("Export range");
+            });
+
+            match &mut self.export_range {
+                ExportRange::LastMinutes => {
+                    ui.horizontal(|ui| {
+                        ui.label("Last");
+                        ui.add(
+                            egui::DragValue::new(&mut self.export_minutes)
+                                .range(1..=24 * 60)
+                                .suffix(" min"),
+                        )
+                        .spoken("Minutes");
+                    });
+                }
+                ExportRange::Timestamps => {
+                    for (label, text) in [("From:", &mut self.export_start), ("To:", &mut self.export_end)] {
+                        ui.horizontal(|ui| {
+                            ui.label(label);
+                            ui.add(
+                                egui::TextEdit::singleline(text)
+                                    .hint_text("HH:MM:SS or YYYY-MM-DD HH:MM:SS")
+                                    .desired_width(ui.available_width()),
+                            )
+                            .spoken(&format!("{} time", label.trim_end_matches(':')));
+                        });
+                    }
+                }
+                ExportRange::Markers(a, b) => {
+                    let events = self.log_entries.events();
+                    for (label, index) in [("From:", a), ("To:", b)] {
+                        ui.horizontal(|ui| {
+                            ui.label(label);
+                            let selected = events
+                                .get(*index)
+                                .map(|(_, text)| text.clone())
+                                .unwrap_or_else(|| "(missing)".to_string());
+                            egui::ComboBox::from_id_source(("export_marker", label))
+                                .width(ui.available_width())
+                                .selected_text(selected)
+                                .show_ui(ui, |ui: &mut egui::Ui| {
+                                    for (i, (timestamp, text)) in events.iter().enumerate() {
+                                        ui.selectable_value(index, i, format!("{}  {}", timestamp, text));
+                                    }
+                                })
+                                .response
+                                .spoken(&format!("{} marker", label.trim_end_matches(':')));
+                        });
+                    }
+                }
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Format:");
+                egui::ComboBox::from_id_source("export_format")
+                    .selected_text(self.export_format.as_str())
+                    .show_ui(ui, |ui: &mut egui::Ui| {
+                        for format in [
+                            ExportFormat::Text,
+                            ExportFormat::Raw,
+                            ExportFormat::Csv,
+                            ExportFormat::Pcapng,
+                        ] {
+                            ui.selectable_value(&mut self.export_format, format, format.as_str());
+                        }
+                    })
+                    .response
+                    .spoken("Export format");
+            });
+
+            let response = ui.add_enabled(!self.log_entries.is_empty(), egui::Button::new("Export"));
+            if response.clicked() {
+                self.export_window();
+            }
+            if self.log_entries.is_empty() {
+                ui.label(egui::RichText::new("Enable logging to record data for export").small().weak());
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.doc_excerpt_path)
+                        .hint_text("excerpt.json")
+                        .desired_width(ui.available_width() - 90.0),
+                )
+                .spoken("Excerpt file");
+                if ui
+                    .button("Open excerpt")
+                    .on_hover_text("Reopen a saved annotated excerpt")
+                    .clicked()
+                {
+                    self.open_doc_excerpt();
+                }
+            });
+        });
+    }
+
+    fn render_filter_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Filter");
+            ui.separator();
+
+            ui.checkbox(&mut self.filter_enabled, "Enable filter");
+            self.render_rule_sets(ui);
+            ui.add_space(4.0);
+
+            ui.label(if self.filter_enabled {
+                "Show data matching any rule:"
+            } else {
+                "Rules (only highlighting while the filter is off):"
+            });
+
+            let mut remove = None;
+            for (index, rule) in self.filter_rules.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut rule.enabled, "").spoken("Rule enabled");
+                    let response = ui
+                        .add(
+                            egui::TextEdit::singleline(&mut rule.pattern)
+                                .hint_text("regex pattern...")
+                                .desired_width(ui.available_width() - 55.0),
+                        )
+                        .spoken("Filter pattern");
+                    if response.changed() {
+                        rule.compile();
+                    }
+                    if let Some(average) = rule.timing.average() {
+                        response.on_hover_text(format!(
+                            "Average match time {:.1} µs ({} samples)",
+                            average.as_secs_f64() * 1e6,
+                            rule.timing.samples
+                        ));
+                    }
+                    let mut no_highlight = false;
+                    match &mut rule.highlight {
+                        Some(color) => {
+                            ui.color_edit_button_srgb(color)
+                                .spoken("Highlight colour")
+                                .on_hover_text("Highlight colour (right-click to remove)")
+                                .context_menu(|ui| {
+                                    if ui.button("No highlight").clicked() {
+                                        no_highlight = true;
+                                        ui.close_menu();
+                                    }
+                                });
+                        }
+                        None => {
+                            if ui
+                                .small_button("hl")
+                                .spoken("Highlight")
+                                .on_hover_text("Highlight matching lines in the terminal")
+                                .clicked()
+                            {
+                                rule.highlight = Some([255, 160, 0]);
+                            }
+                        }
+                    }
+                    if no_highlight {
+                        rule.highlight = None;
+                    }
+                    if ui.small_button("x").spoken("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                });
+
+                if let Some(error) = &rule.error {
+                    ui.colored_label(
+                        self.palette.error,
+                        egui::RichText::new(error).small(),
+                    );
+                } else if let Some(average) = rule
+                    .timing
+                    .average()
+                    .filter(|&average| average > filter::SLOW_RULE_THRESHOLD)
+                {
+                    ui.colored_label(
+                        self.palette.warning,
+                        egui::RichText::new(format!(
+                            "Slow pattern: {:.0} µs per chunk",
+                            average.as_secs_f64() * 1e6
+                        ))
+                        .small(),
+                    );
+                }
+            }
+
+            if let Some(index) = remove {
+                self.filter_rules.remove(index);
+            }
+
+            if ui.button("Add rule").clicked() {
+                self.filter_rules.push(FilterRule::new(""));
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Hold partial lines:")
+                    .on_hover_text("Wait this long for the end of a line before filtering it as it is");
+                ui.add(
+                    egui::DragValue::new(&mut self.filter_hold_ms)
+                        .range(10..=10_000)
+                        .suffix(" ms"),
+                )
+                .spoken("Hold partial lines");
+            });
+
+            ui.add_space(4.0);
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.keep_rejected, "Keep rejected data")
+                    .on_hover_text("Hold the latest bytes the filter dropped, to check the rules");
+                let response = ui.add(
+                    egui::DragValue::new(&mut self.rejected_kb)
+                        .range(1..=MAX_REJECTED_KB)
+                        .suffix(" KB"),
+                )
+                .spoken("Rejected data kept");
+                if response.changed() {
+                    self.rejected.set_capacity(self.rejected_kb * 1024);
+                }
+            });
+            if self.keep_rejected || !self.rejected.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} bytes held", self.rejected.len()));
+                    self.action_button(ui, Action::ShowRejected, egui::Button::new("View").small(), "");
+                });
+            }
+        });
+    }
+
+    /// Named rule sets: pick one to load it into the filter, save the current
+    /// rules as a set, or exchange sets as JSON files.
+    fn render_rule_sets(&mut self, ui: &mut egui::Ui) {
+        let mut choose = None;
+        ui.horizontal(|ui| {
+            ui.label("Rule set:");
+            let selected = self
+                .selected_rule_set
+                .and_then(|index| self.rule_sets.get(index))
+                .map_or("—", |set| set.name.as_str());
+            egui::ComboBox::from_id_source("rule_set")
+                .selected_text(selected)
+                .width(ui.available_width() - 55.0)
+                .show_ui(ui, |ui| {
+                    for (index, set) in self.rule_sets.iter().enumerate() {
+                        let label = format!("{} ({} rules)", set.name, set.rules.len());
+                        if ui.selectable_label(self.selected_rule_set == Some(index), label).clicked() {
+                            choose = Some(index);
+                        }
+                    }
+                })
+                .response
+                .spoken("Rule set");
+            if ui
+                .add_enabled(self.selected_rule_set.is_some(), egui::Button::new("Delete").small())
+                .clicked()
+            {
+                self.delete_rule_set();
+            }
+        });
+        if let Some(index) = choose {
+            self.apply_rule_set(index);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.rule_set_name)
+                    .hint_text("set name")
+                    .desired_width(ui.available_width() - 110.0),
+            )
+            .spoken("Rule set name");
+            if ui.button("Save set").on_hover_text("Store the rules below under this name").clicked() {
+                self.save_rule_set();
+            }
+            if ui.button("Export").on_hover_text("Write the selected set to a JSON file").clicked() {
+                self.export_rule_set();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.rule_set_path)
+                    .hint_text("rules.json")
+                    .desired_width(ui.available_width() - 60.0),
+            )
+            .spoken("Rule set file");
+            if ui
+                .button("Import")
+                .on_hover_text("Load a rule set file, merging into a set of the same name")
+                .clicked()
+            {
+                self.import_rule_set();
+            }
+        });
+        for error in &self.rule_set_errors {
+            ui.colored_label(self.palette.error, egui::RichText::new(error).small());
+        }
+    }
+
+    fn render_pinned_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Pinned Values");
+            ui.separator();
+
+            let mut remove = None;
+            for (index, value) in self.pinned_values.iter_mut().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut value.name)
+                                .hint_text("name")
+                                .desired_width(ui.available_width() - 80.0),
+                        )
+                        .spoken("Value name");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut value.units)
+                                .hint_text("units")
+                                .desired_width(40.0),
+                        )
+                        .spoken("Units");
+                        if ui.small_button("x").spoken("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("pin_source")
+                            .selected_text(value.source.as_str())
+                            .width(80.0)
+                            .show_ui(ui, |ui| {
+                                for source in [PinSource::Regex, PinSource::Bytes] {
+                                    ui.selectable_value(&mut value.source, source, source.as_str());
+                                }
+                            })
+                            .response
+                            .spoken("Value source");
+                        match value.source {
+                            PinSource::Regex => {
+                                let response = ui.add(
+                                    egui::TextEdit::singleline(&mut value.pattern)
+                                        .hint_text("T=([\\d.]+)")
+                                        .desired_width(ui.available_width()),
+                                )
+                                .spoken("Value pattern");
+                                if response.changed() {
+                                    value.compile();
+                                }
+                            }
+                            PinSource::Bytes => {
+                                ui.label("at");
+                                ui.add(egui::DragValue::new(&mut value.offset).range(0..=1023)).spoken("Byte offset");
+                                ui.label("len");
+                                ui.add(egui::DragValue::new(&mut value.length).range(1..=64)).spoken("Byte length");
+                            }
+                        }
+                    });
+                    if value.source == PinSource::Regex {
+                        if let Some(error) = &value.error {
+                            ui.colored_label(self.palette.error, egui::RichText::new(error).small());
+                        }
+                    }
+
+                    ui.horizontal(|ui| {
+                        for (label, limit) in [("Warn", &mut value.warning), ("Crit", &mut value.critical)] {
+                            let mut enabled = limit.is_some();
+                            if ui.checkbox(&mut enabled, label).changed() {
+                                *limit = enabled.then_some(0.0);
+                            }
+                            if let Some(limit) = limit {
+                                ui.add(egui::DragValue::new(limit).speed(0.1)).spoken(&format!("{} limit", label));
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut value.below, "Low is bad")
+                            .on_hover_text("Thresholds are lower limits, e.g. for a supply voltage");
+                        ui.checkbox(&mut value.alert, "Alert")
+                            .on_hover_text("Beep and show a status message when the value turns critical");
+                    });
+                    ui.separator();
+                });
+            }
+
+            if let Some(index) = remove {
+                self.pinned_values.remove(index);
+            }
+
+            if ui.button("Add value").clicked() {
+                self.pinned_values.push(PinnedValue::new("", ""));
+            }
+        });
+    }
+
+    fn render_variables_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Variables")
+                .on_hover_text("key=value and key: value pairs from received lines, e.g. vbat=3.72 temp=41");
+            ui.separator();
+            ui.checkbox(&mut self.variables_enabled, "Extract key=value pairs");
+            if !self.variables_enabled {
+                return;
+            }
+
+            let mut toggle_plot = None;
+            let mut hide = None;
+            if self.variables.visible().next().is_none() {
+                ui.weak("No pairs seen yet");
+            } else {
+                egui::Grid::new("variables")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for heading in ["Key", "Value", "Updates", "Changed", "Plot", ""] {
+                            ui.label(egui::RichText::new(heading).small().strong());
+                        }
+                        ui.end_row();
+                        for variable in self.variables.visible() {
+                            ui.label(&variable.key);
+                            ui.monospace(&variable.value);
+                            ui.label(variable.updates.to_string());
+                            ui.label(variable.changed_at.format("%H:%M:%S").to_string());
+                            let mut plotted = variable.plotted;
+                            let plottable = !variable.history.is_empty();
+                            if ui
+                                .add_enabled(plottable, egui::Checkbox::without_text(&mut plotted))
+                                .spoken(&format!("Plot {}", variable.key))
+                                .changed()
+                            {
+                                toggle_plot = Some(variable.key.clone());
+                            }
+                            if ui.small_button("hide").spoken(&format!("Hide {}", variable.key)).clicked() {
+                                hide = Some(variable.key.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            }
+            if self.variables.dropped > 0 {
+                ui.colored_label(
+                    self.palette.warning,
+                    format!("{} pairs ignored: the table is full", self.variables.dropped),
+                );
+            }
+
+            let mut show = None;
+            if !self.variables.hidden_keys().is_empty() {
+                ui.collapsing(format!("Hidden keys ({})", self.variables.hidden_keys().len()), |ui| {
+                    for key in self.variables.hidden_keys() {
+                        ui.horizontal(|ui| {
+                            ui.label(key);
+                            if ui.small_button("show").spoken(&format!("Show {}", key)).clicked() {
+                                show = Some(key.clone());
+                            }
+                        });
+                    }
+                });
+            }
+
+            for variable in self.variables.visible().filter(|v| v.plotted && !v.history.is_empty()) {
+                ui.label(format!("{}: {}", variable.key, variable.value));
+                self.render_sparkline(ui, variable);
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Export CSV").clicked() {
+                    self.export_variables_csv();
+                }
+                if ui.button("Clear").clicked() {
+                    self.variables.clear();
+                }
+            });
+
+            if let Some(variable) = toggle_plot.and_then(|key| self.variables.get_mut(&key)) {
+                variable.plotted = !variable.plotted;
+            }
+            if let Some(key) = hide {
+                self.variables.set_hidden(&key, true);
+            }
+            if let Some(key) = show {
+                self.variables.set_hidden(&key, false);
+            }
+        });
+    }
+
+    fn render_watches_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Watches").on_hover_text(
+                "Conditions on extracted variables: <, <=, >, >=, ==, != joined with and/or, \
+                 optionally ending in \"for 5s\". Alarms are marked in the terminal.",
+            );
+            ui.separator();
+            if !self.variables_enabled && !self.watches.is_empty() {
+                ui.colored_label(self.palette.warning, "Turn on variable extraction for watches to see values");
+            }
+
+            let mut remove = None;
+            for (index, watch) in self.watches.iter_mut().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut watch.enabled, "").spoken("Watch enabled");
+                        let response = ui.add(
+                            egui::TextEdit::singleline(&mut watch.expression)
+                                .hint_text("temp > 60 for 5s")
+                                .desired_width(ui.available_width() - 75.0),
+                        )
+                        .spoken("Watch expression");
+                        if response.changed() {
+                            watch.compile();
+                        }
+                        let (status, color) = match watch.state {
+                            _ if watch.alarm => ("ALARM", self.palette.error),
+                            Some(true) => ("true", self.palette.warning),
+                            Some(false) => ("false", self.palette.ok),
+                            None => ("—", ui.visuals().weak_text_color()),
+                        };
+                        ui.add_sized([40.0, 18.0], egui::Label::new(egui::RichText::new(status).color(color)));
+                        if ui.small_button("x").spoken("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                    if let Some(error) = &watch.error {
+                        ui.colored_label(self.palette.error, egui::RichText::new(error).small());
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Hysteresis:");
+                        ui.add(egui::DragValue::new(&mut watch.hysteresis).speed(0.1).range(0.0..=f64::MAX))
+                            .spoken("Hysteresis")
+                            .on_hover_text("Once alarmed, a threshold must be passed by this much before the alarm clears");
+                    });
+                });
+            }
+            if let Some(index) = remove {
+                self.watches.remove(index);
+            }
+
+            if ui.button("Add watch").clicked() {
+                self.watches.push(Watch::new(""));
+            }
+
+            ui.separator();
+            self.render_rate_alarm(ui);
+        });
+    }
+
+    fn render_rate_alarm(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.rate_alarm.enabled, "RX rate alarm").on_hover_text(
+                "Catch a runaway device: alarm when RX stays above the rate for the time given, \
+                 and clear once it stays below the clear level as long",
+            );
+            let (status, color) = if self.rate_tracker.alarm {
+                ("ALARM", self.palette.error)
+            } else if self.rate_alarm.enabled && self.connected {
+                ("ok", self.palette.ok)
+            } else {
+                ("—", ui.visuals().weak_text_color())
+            };
+            ui.colored_label(color, status);
+            if self.rate_alarm.enabled && self.connected {
+                ui.weak(format!("{:.1} KB/s", self.rate_tracker.bytes_per_second / 1024.0));
+            }
+        });
+        let config = &mut self.rate_alarm;
+        ui.horizontal(|ui| {
+            ui.label("Above:");
+            ui.add(egui::DragValue::new(&mut config.threshold_kb).speed(1.0).range(0.1..=100_000.0).suffix(" KB/s"))
+                .spoken("Rate alarm threshold");
+            ui.label("for");
+            ui.add(egui::DragValue::new(&mut config.sustain_secs).speed(0.5).range(0.0..=3600.0).suffix(" s"))
+                .spoken("Rate alarm hold time");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Clear below:");
+            ui.add(egui::DragValue::new(&mut config.clear_percent).speed(1.0).range(0.0..=100.0).suffix(" %"))
+                .spoken("Rate alarm clear level")
+                .on_hover_text("Percentage of the threshold the rate must stay below for the alarm to clear");
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut config.start_recorder, "Start raw recorder")
+                .on_hover_text("Switch to raw recording when the alarm goes off; the recording keeps going after");
+            ui.label("RTS:");
+            let caption = |rts: Option<bool>| match rts {
+                None => "Unchanged",
+                Some(true) => "On",
+                Some(false) => "Off",
+            };
+            egui::ComboBox::from_id_source("rate_alarm_rts")
+                .width(80.0)
+                .selected_text(caption(config.rts))
+                .show_ui(ui, |ui: &mut egui::Ui| {
+                    for rts in [None, Some(true), Some(false)] {
+                        ui.selectable_value(&mut config.rts, rts, caption(rts));
+                    }
+                })
+                .response
+                .spoken("Rate alarm RTS")
+                .on_hover_text("RTS level held while the alarm is raised, e.g. to stop a device honouring flow control");
+        });
+    }
+
+    /// A small line plot of a variable's numeric history, scaled to fit.
+    fn render_sparkline(&self, ui: &mut egui::Ui, variable: &Variable) {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 48.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_stroke(rect, 2.0, ui.visuals().widgets.noninteractive.bg_stroke);
+
+        let (Some(&(first, _)), Some(&(last, _))) = (variable.history.front(), variable.history.back()) else {
+            return;
+        };
+        let (min, max) = variable
+            .history
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &(_, v)| (lo.min(v), hi.max(v)));
+        let span_ms = (last - first).num_milliseconds().max(1) as f32;
+        let range = if max > min { (max - min) as f32 } else { 1.0 };
+        let inner = rect.shrink(4.0);
+        let points: Vec<egui::Pos2> = variable
+            .history
+            .iter()
+            .map(|&(at, v)| {
+                let x = (at - first).num_milliseconds() as f32 / span_ms;
+                let y = (v - min) as f32 / range;
+                egui::pos2(inner.left() + x * inner.width(), inner.bottom() - y * inner.height())
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, self.palette.marker)));
+
+        let font = egui::TextStyle::Small.resolve(ui.style());
+        let color = ui.visuals().weak_text_color();
+        painter.text(inner.left_top(), egui::Align2::LEFT_TOP, format!("{}", max), font.clone(), color);
+        painter.text(inner.left_bottom(), egui::Align2::LEFT_BOTTOM, format!("{}", min), font, color);
+    }
+
+    fn render_macros_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Macros").on_hover_text(
+                "Canned payloads sent from the buttons above the send box or a function key. \
+                 Saved with the A/B configuration.",
+            );
+            ui.separator();
+
+            let mut edit = None;
+            let mut remove = None;
+            let mut swap = None;
+            let count = self.macros.len();
+            for (index, m) in self.macros.iter().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(index > 0, egui::Button::new("▲").small()).spoken("Move up").clicked() {
+                            swap = Some(index - 1);
+                        }
+                        if ui
+                            .add_enabled(index + 1 < count, egui::Button::new("▼").small())
+                            .spoken("Move down")
+                            .clicked()
+                        {
+                            swap = Some(index);
+                        }
+                        if let Some(key) = m.key {
+                            ui.label(egui::RichText::new(format!("F{}", key)).monospace().weak());
+                        }
+                        ui.label(m.label()).on_hover_text(&m.text);
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.small_button("x").spoken("Remove").clicked() {
+                                remove = Some(index);
+                            }
+                            if ui.small_button("Edit").clicked() {
+                                edit = Some(index);
+                            }
+                        });
+                    });
+                });
+            }
+            if let Some(index) = swap {
+                self.macros.swap(index, index + 1);
+                self.macro_edit = None;
+                self.template_prompt = None;
+            }
+            if let Some(index) = remove {
+                self.macros.remove(index);
+                self.macro_edit = None;
+                self.template_prompt = None;
+            }
+            if let Some(index) = edit {
+                self.macro_edit = Some(MacroEdit {
+                    index: Some(index),
+                    draft: self.macros[index].clone(),
+                    error: None,
+                });
+            }
+
+            let add = ui.add_enabled(count < MAX_MACROS, egui::Button::new("Add macro"));
+            if add.on_disabled_hover_text(format!("At most {} macros", MAX_MACROS)).clicked() {
+                self.macro_edit = Some(MacroEdit {
+                    index: None,
+                    draft: Macro::default(),
+                    error: None,
+                });
+            }
+        });
+    }
+
+    fn render_schedule_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Schedule").on_hover_text(
+                "Payloads sent on a timer while connected. Entries with the same interval and \
+                 different phase offsets run as a spaced sequence.",
+            );
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.schedule_running, "Run schedule");
+                if self.schedule_running && !self.connected {
+                    ui.label(egui::RichText::new("waiting for a connection").small().weak());
+                }
+            });
+
+            let mut remove = None;
+            for (index, entry) in self.schedule.iter_mut().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut entry.enabled, "").spoken("Entry enabled");
+                        ui.selectable_value(&mut entry.mode, SendMode::Ascii, "ASCII");
+                        ui.selectable_value(&mut entry.mode, SendMode::Hex, "Hex");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut entry.text)
+                                .hint_text(match entry.mode {
+                                    SendMode::Ascii => "STATUS?",
+                                    SendMode::Hex => "01 03 00 00 00 02",
+                                })
+                                .desired_width(ui.available_width() - 25.0),
+                        )
+                        .spoken("Scheduled payload");
+                        if ui.small_button("x").spoken("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Every");
+                        ui.add(
+                            egui::DragValue::new(&mut entry.interval_ms)
+                                .range(MIN_SCHEDULE_INTERVAL_MS..=u64::MAX)
+                                .suffix(" ms"),
+                        )
+                        .spoken("Interval");
+                        ui.label("phase");
+                        ui.add(egui::DragValue::new(&mut entry.phase_ms).suffix(" ms"))
+                            .spoken("Phase")
+                            .on_hover_text("Delay of the first send after the schedule starts");
+                    });
+                    match &entry.last_run {
+                        Some(run) => {
+                            let at = run.at.format("%H:%M:%S");
+                            match &run.result {
+                                Ok(bytes) => ui.colored_label(
+                                    self.palette.ok,
+                                    egui::RichText::new(format!("Last run {}: {} bytes sent", at, bytes)).small(),
+                                ),
+                                Err(e) => ui.colored_label(
+                                    self.palette.error,
+                                    egui::RichText::new(format!("Last run {}: {}", at, e)).small(),
+                                ),
+                            };
+                        }
+                        None => {
+                            ui.label(egui::RichText::new("Not run yet").small().weak());
+                        }
+                    }
+                });
+            }
+            if let Some(index) = remove {
+                self.schedule.remove(index);
+            }
+
+            if ui.button("Add entry").clicked() {
+                self.schedule
+                    .push(ScheduledSend::new("", SendMode::Ascii, DEFAULT_SCHEDULE_INTERVAL_MS));
+            }
+        });
+    }
+
+    fn render_rate_limit_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "TX Rate Limit").on_hover_text(
+                "Space out sends from every path (send box, typing, schedule, file send, XON/XOFF) \
+                 for devices that drop input sent too fast. Sends over the limit wait in order.",
+            );
+            ui.separator();
+            let limit = &mut self.tx_limiter.limit;
+            let unlimited = |n: f64, _| if n == 0.0 { "unlimited".to_string() } else { format!("{}", n) };
+            ui.horizontal(|ui| {
+                ui.label("Payloads/s:");
+                ui.add(egui::DragValue::new(&mut limit.payloads_per_sec).custom_formatter(unlimited))
+                    .spoken("Payloads per second");
+            });
+            ui.horizontal(|ui| {
+                ui.label("Bytes/s:");
+                ui.add(egui::DragValue::new(&mut limit.bytes_per_sec).custom_formatter(unlimited))
+                    .spoken("Bytes per second");
+            });
+            ui.label(format!("Delayed sends: {}", self.tx_delayed));
+        });
+    }
+
+    fn render_keepalive_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Keepalive").on_hover_text(
+                "Sent when nothing else has been transmitted for the interval, for radio links and \
+                 device watchdogs that drop a quiet session. Stops while disconnected.",
+            );
+            ui.separator();
+            let keepalive = &mut self.keepalive;
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut keepalive.enabled, "Enabled");
+                if keepalive.enabled && !self.connected {
+                    ui.label(egui::RichText::new("waiting for a connection").small().weak());
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut keepalive.mode, SendMode::Ascii, "ASCII");
+                ui.selectable_value(&mut keepalive.mode, SendMode::Hex, "Hex");
+                ui.add(
+                    egui::TextEdit::singleline(&mut keepalive.text)
+                        .hint_text(match keepalive.mode {
+                            SendMode::Ascii => "AT",
+                            SendMode::Hex => "00",
+                        })
+                        .desired_width(ui.available_width()),
+                )
+                .spoken("Keepalive payload");
+            });
+            ui.horizontal(|ui| {
+                ui.label("After");
+                ui.add(
+                    egui::DragValue::new(&mut keepalive.interval_secs)
+                        .range(MIN_KEEPALIVE_SECS..=86_400)
+                        .suffix(" s"),
+                )
+                .spoken("Keepalive interval");
+                ui.label("without TX");
+            });
+            ui.checkbox(&mut keepalive.quiet, "Leave out of TX log and counters");
+            ui.label(format!("Keepalives sent: {}", self.keepalives_sent));
+        });
+    }
+
+    fn render_send_guard_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Send Guard")
+                .on_hover_text("Sends matching a rule are shown for confirmation first");
+            ui.separator();
+
+            let locked = self.send_guard.is_locked();
+            let mut remove = None;
+            for (index, rule) in self.send_guard.rules.iter_mut().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.add_enabled_ui(!locked, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut rule.enabled, "").spoken("Rule enabled");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut rule.description)
+                                    .hint_text("description")
+                                    .desired_width(ui.available_width() - 24.0),
+                            )
+                            .spoken("Rule description");
+                            if ui.small_button("x").spoken("Remove").clicked() {
+                                remove = Some(index);
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let before = rule.kind;
+                            egui::ComboBox::from_id_source("guard_kind")
+                                .selected_text(rule.kind.as_str())
+                                .width(60.0)
+                                .show_ui(ui, |ui| {
+                                    for kind in [GuardKind::Hex, GuardKind::Regex] {
+                                        ui.selectable_value(&mut rule.kind, kind, kind.as_str());
+                                    }
+                                })
+                                .response
+                                .spoken("Pattern kind");
+                            let hint = match rule.kind {
+                                GuardKind::Hex => "A5 5A 01",
+                                GuardKind::Regex => "^ERASE",
+                            };
+                            let response = ui.add(
+                                egui::TextEdit::singleline(&mut rule.pattern)
+                                    .hint_text(hint)
+                                    .desired_width(ui.available_width()),
+                            )
+                            .spoken("Guard pattern");
+                            if response.changed() || rule.kind != before {
+                                rule.compile();
+                            }
+                        });
+                    });
+                    if let Some(error) = &rule.error {
+                        ui.colored_label(self.palette.error, egui::RichText::new(error).small());
+                    }
+                });
+            }
+
+            if let Some(index) = remove {
+                self.send_guard.rules.remove(index);
+            }
+
+            if !locked && ui.button("Add rule").clicked() {
+                self.send_guard
+                    .rules
+                    .push(GuardRule::new("", GuardKind::Hex, ""));
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.guard_lock_code)
+                        .password(true)
+                        .hint_text("code")
+                        .desired_width(80.0),
+                )
+                .spoken("Lock code");
+                let result = if locked {
+                    ui.button("Unlock")
+                        .clicked()
+                        .then(|| self.send_guard.unlock(&self.guard_lock_code))
+                } else {
+                    ui.button("Lock")
+                        .on_hover_text("Keep these rules from being edited or disabled without the code")
+                        .clicked()
+                        .then(|| self.send_guard.lock(&self.guard_lock_code))
+                };
+                match result {
+                    Some(Ok(())) => self.guard_lock_code.clear(),
+                    Some(Err(e)) => self.error_message = Some(e),
+                    None => {}
+                }
+            });
+            if locked {
+                ui.label(egui::RichText::new("Locked for this profile").small().weak());
+            }
+        });
+    }
+
+    /// Shows the exact bytes of a guarded send and waits for the go-ahead.
+    fn render_guard_confirmation(&mut self, ctx: &egui::Context) {
+        let Some(confirmation) = &self.guard_confirmation else {
+            return;
+        };
+        let hex: Vec<String> = confirmation
+            .wire_bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        let mut send = false;
+        let mut cancel = false;
+
+        egui::Window::new("Confirm send")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.colored_label(
+                    self.palette.warning,
+                    format!("This payload matches the rule \"{}\".", confirmation.rule),
+                );
+                ui.label(format!("{} bytes will be written:", confirmation.wire_bytes.len()));
+                egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    ui.add(egui::Label::new(egui::RichText::new(hex.join(" ")).monospace()).wrap());
+                });
+                ui.horizontal(|ui| {
+                    send = ui.button("Send").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if send {
+            if let Err(e) = self.confirm_guarded_send() {
+                self.error_message = Some(e);
+            }
+        } else if cancel || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.guard_confirmation = None;
+        }
+    }
+
+    /// "Go to" dialog for a byte offset or line number.
+    fn render_goto_dialog(&mut self, ctx: &egui::Context) {
+        if !self.goto_open {
+            return;
+        }
+        let mut go = false;
+        let mut open = true;
+        egui::Window::new("Go to")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 80.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.goto_by_line, false, "Byte offset");
+                    ui.selectable_value(&mut self.goto_by_line, true, "Line number");
+                });
+                let hint = if self.goto_by_line { "e.g. 1200" } else { "e.g. 0x1F400 or 128000" };
+                let response = ui.add(egui::TextEdit::singleline(&mut self.goto_input).hint_text(hint))
+                    .spoken(if self.goto_by_line { "Line number" } else { "Byte offset" });
+                response.request_focus();
+                go = ui.button("Go").clicked()
+                    || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+            });
+
+        if go {
+            if let Err(e) = self.goto() {
+                self.error_message = Some(e);
+            }
+        } else if !open || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.goto_open = false;
+        }
+    }
+
+    /// Name, payload, send mode, line ending and function key of the macro
+    /// being edited. Save checks that the payload, or a template's
+    /// placeholders, parse.
+    fn render_macro_dialog(&mut self, ctx: &egui::Context) {
+        let Some(mut edit) = self.macro_edit.take() else {
+            return;
+        };
+        let mut save = false;
+        let mut cancel = false;
+        let mut open = true;
+        let title = if edit.index.is_some() { "Edit macro" } else { "New macro" };
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 80.0])
+            .show(ctx, |ui| {
+                let draft = &mut edit.draft;
+                egui::Grid::new("macro_edit").num_columns(2).show(ui, |ui| {
+                    ui.label("Name:");
+                    ui.add(egui::TextEdit::singleline(&mut draft.name).hint_text("Reset")).spoken("Macro name");
+                    ui.end_row();
+
+                    ui.label("Mode:");
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut draft.mode, SendMode::Ascii, "ASCII");
+                        ui.selectable_value(&mut draft.mode, SendMode::Hex, "Hex");
+                    });
+                    ui.end_row();
+
+                    ui.label("Template:");
+                    ui.checkbox(&mut draft.template, "Ask for {placeholders} when sent")
+                        .on_hover_text(template::SYNTAX_HELP);
+                    ui.end_row();
+
+                    ui.label("Payload:");
+                    let hint = match (draft.mode, draft.template) {
+                        (SendMode::Ascii, false) => r"AT+RST\x00",
+                        (SendMode::Hex, false) => "01 06 00 01 00 03",
+                        (SendMode::Ascii, true) => r"SET CHAN {n:int(1..16)=1}\r",
+                        (SendMode::Hex, true) => "01 06 {reg:int(0..0xFFFF)} {value:hex}",
+                    };
+                    let payload = ui
+                        .add(
+                            egui::TextEdit::singleline(&mut draft.text)
+                                .hint_text(hint)
+                                .font(egui::TextStyle::Monospace),
+                        )
+                        .spoken("Macro payload");
+                    if draft.template {
+                        payload.on_hover_text(template::SYNTAX_HELP);
+                    } else {
+                        payload.on_hover_text(r"ASCII understands \r \n \t \0 \e \\ and \xHH");
+                    }
+                    ui.end_row();
+
+                    if draft.mode == SendMode::Ascii {
+                        ui.label("Line ending:");
+                        let shown = draft.line_ending.map_or("Send box", |ending| ending.as_str());
+                        egui::ComboBox::from_id_source("macro_line_ending")
+                            .selected_text(shown)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut draft.line_ending, None, "Send box");
+                                for ending in [LineEnding::None, LineEnding::Cr, LineEnding::Lf, LineEnding::CrLf] {
+                                    ui.selectable_value(&mut draft.line_ending, Some(ending), ending.as_str());
+                                }
+                            })
+                            .response
+                            .spoken("Line ending");
+                        ui.end_row();
+                    }
+
+                    ui.label("Key:");
+                    let shown = draft.key.map_or("None".to_string(), |key| format!("F{}", key));
+                    egui::ComboBox::from_id_source("macro_key").selected_text(shown).show_ui(ui, |ui| {
+                        ui.selectable_value(&mut draft.key, None, "None");
+                        for key in 1..=MACRO_KEY_COUNT {
+                            ui.selectable_value(&mut draft.key, Some(key), format!("F{}", key));
+                        }
+                    })
+                    .response
+                    .spoken("Function key");
+                    ui.end_row();
+                });
+
+                let preview = match draft.parse_template() {
+                    Ok(None) => draft.payload(self.line_ending).map(|payload| protocol::format_payload_hex(&payload)),
+                    Ok(Some(template)) if template.placeholders.is_empty() => Ok("No placeholders".to_string()),
+                    Ok(Some(template)) => {
+                        let asks: Vec<String> =
+                            template.placeholders.iter().map(|p| format!("{} ({})", p.name, p.hint())).collect();
+                        Ok(format!("Asks for {}", asks.join(", ")))
+                    }
+                    Err(e) => Err(e),
+                };
+                match preview {
+                    Ok(preview) => ui.label(egui::RichText::new(preview).small().monospace()),
+                    Err(e) => ui.colored_label(self.palette.error, egui::RichText::new(e).small()),
+                };
+                if let Some(e) = &edit.error {
+                    ui.colored_label(self.palette.error, e);
+                }
+                ui.horizontal(|ui| {
+                    save = ui.button("Save").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if save {
+            let check = match edit.draft.parse_template() {
+                Ok(None) => edit.draft.payload(self.line_ending).map(|_| ()),
+                Ok(Some(_)) => Ok(()),
+                Err(e) => Err(e),
+            };
+            match check {
+                Ok(()) => {
+                    let key = edit.draft.key;
+                    let index = match edit.index {
+                        Some(index) => {
+                            self.macros[index] = edit.draft;
+                            index
+                        }
+                        None => {
+                            self.macros.push(edit.draft);
+                            self.macros.len() - 1
+                        }
+                    };
+                    macros::assign_key(&mut self.macros, index, key);
+                    self.template_prompt = None;
+                    return;
+                }
+                Err(e) => edit.error = Some(e),
+            }
+        }
+        if open && !cancel && !ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.macro_edit = Some(edit);
+        }
+    }
+
+    /// A field per placeholder of the template macro being sent, checked as
+    /// it is typed, and the text it will send. Enter sends, Escape cancels.
+    fn render_template_prompt(&mut self, ctx: &egui::Context) {
+        let Some(mut prompt) = self.template_prompt.take() else {
+            return;
+        };
+        let Some(m) = self.macros.get(prompt.index) else {
+            return;
+        };
+        let mode = m.mode;
+        let mut send = false;
+        let mut cancel = false;
+        let mut open = true;
+        egui::Window::new(format!("Send {}", m.label()))
+            .id(egui::Id::new("template_prompt"))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 80.0])
+            .show(ctx, |ui| {
+                egui::Grid::new("template_values").num_columns(3).show(ui, |ui| {
+                    for (i, p) in prompt.template.placeholders.iter().enumerate() {
+                        ui.label(format!("{}:", p.name));
+                        let value = &mut prompt.values[i];
+                        let response = match &p.kind {
+                            PlaceholderKind::Choice(options) => {
+                                egui::ComboBox::from_id_source(("template_choice", i))
+                                    .selected_text(value.as_str())
+                                    .show_ui(ui, |ui| {
+                                        for option in options {
+                                            ui.selectable_value(value, option.clone(), option);
+                                        }
+                                    })
+                                    .response
+                            }
+                            _ => {
+                                let hint = p.default.clone().unwrap_or_else(|| p.hint());
+                                ui.add(
+                                    egui::TextEdit::singleline(value)
+                                        .hint_text(hint)
+                                        .desired_width(160.0)
+                                        .font(egui::TextStyle::Monospace),
+                                )
+                            }
+                        }
+                        .spoken(&p.name)
+                        .on_hover_text(p.hint());
+                        if prompt.focus && i == 0 {
+                            response.request_focus();
+                        }
+                        match p.render(value, mode) {
+                            Ok(_) => ui.label(egui::RichText::new(p.hint()).weak()),
+                            Err(e) => ui.colored_label(self.palette.error, e),
+                        };
+                        ui.end_row();
+                    }
+                });
+                prompt.focus = false;
+
+                let resolved = prompt.template.resolve(&prompt.values, mode);
+                match &resolved {
+                    Ok(text) => ui.label(egui::RichText::new(text).monospace()),
+                    Err(e) => ui.colored_label(self.palette.error, egui::RichText::new(e).small()),
+                };
+                ui.horizontal(|ui| {
+                    let ok = resolved.is_ok();
+                    send = ui.add_enabled(ok, egui::Button::new("Send")).clicked()
+                        || (ok && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if send {
+            if let Err(e) = self.send_template(prompt.index, &prompt.values) {
+                self.error_message = Some(e);
+            }
+        } else if open && !cancel && !ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.template_prompt = Some(prompt);
+        }
+    }
+
+    /// One button per macro, above the send box; a template's caption ends in
+    /// an ellipsis.
+    fn render_macro_bar(&mut self, ui: &mut egui::Ui) {
+        let mut send = None;
+        ui.horizontal_wrapped(|ui| {
+            if !self.connected || self.monitor_only {
+                ui.disable();
+            }
+            for (index, m) in self.macros.iter().enumerate() {
+                let hover = match m.key {
+                    Some(key) => format!("{} (F{})", m.text, key),
+                    None => m.text.clone(),
+                };
+                let label = if m.template { format!("{}…", m.label()) } else { m.label().to_string() };
+                if ui.button(label).on_hover_text(hover).clicked() {
+                    send = Some(index);
+                }
+            }
+        });
+        if let Some(index) = send {
+            if let Err(e) = self.run_macro(index) {
+                self.error_message = Some(e);
+            }
+        }
+    }
+
+    /// F1 to F12 send the macro bound to them, or ask for a template's values.
+    fn poll_macro_keys(&mut self, ctx: &egui::Context) {
+        for key in 1..=MACRO_KEY_COUNT {
+            let Some(index) = macros::for_key(&self.macros, key) else {
+                continue;
+            };
+            let Some(f_key) = egui::Key::from_name(&format!("F{}", key)) else {
+                continue;
+            };
+            if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, f_key)) {
+                if let Err(e) = self.run_macro(index) {
+                    self.error_message = Some(e);
+                }
+            }
+        }
+    }
+
+    /// A side panel group's title. Tab stops on it and Ctrl+F6 jumps between
+    /// them, scrolling the group into view; screen readers hear a heading.
+    fn group_heading(&mut self, ui: &mut egui::Ui, title: &str) -> egui::Response {
+        let label = egui::Label::new(egui::RichText::new(title).strong())
+            .selectable(false)
+            .sense(egui::Sense::focusable_noninteractive());
+        let heading = ui.push_id(title, |ui| ui.add(label)).inner;
+        ui.ctx()
+            .accesskit_node_builder(heading.id, |node| node.set_role(egui::accesskit::Role::Heading));
+        if heading.gained_focus() {
+            heading.scroll_to_me(None);
+        }
+        if heading.has_focus() {
+            ui.painter()
+                .rect_stroke(heading.rect.expand(2.0), 2.0, ui.visuals().selection.stroke);
+        }
+        self.focus_areas.push(heading.id);
+        heading
+    }
+
+    /// Ctrl+F6 and Ctrl+Shift+F6 move focus between the areas rendered last
+    /// frame. Shift first, as Ctrl+F6 would match it too.
+    fn poll_area_keys(&mut self, ctx: &egui::Context) {
+        let areas = std::mem::take(&mut self.focus_areas);
+        for (shortcut, forward) in [(a11y::PREVIOUS_AREA, false), (a11y::NEXT_AREA, true)] {
+            if ctx.input_mut(|i| i.consume_shortcut(&shortcut)) {
+                let focused = ctx.memory(|mem| mem.focused());
+                if let Some(id) = a11y::next_area(&areas, focused, forward) {
+                    ctx.memory_mut(|mem| mem.request_focus(id));
+                }
+            }
+        }
+    }
+
+    /// Ctrl+Shift+P: every action, narrowed as you type. Up/Down move the
+    /// highlight, Enter runs it and Escape closes.
+    fn render_command_palette(&mut self, ctx: &egui::Context) {
+        let Some(mut palette) = self.command_palette.take() else {
+            return;
+        };
+        let key = |key| ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, key));
+        let (up, down, enter, escape) = (
+            key(egui::Key::ArrowUp),
+            key(egui::Key::ArrowDown),
+            key(egui::Key::Enter),
+            key(egui::Key::Escape),
+        );
+
+        let mut run = None;
+        let mut open = true;
+        egui::Window::new("Commands")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 80.0])
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut palette.query)
+                        .hint_text("Type to search actions and macros")
+                        .desired_width(320.0),
+                )
+                .spoken("Search actions");
+                response.request_focus();
+                if response.changed() {
+                    palette.selected = 0;
+                }
+
+                let matches = actions::palette_matches(&palette.query, &self.macros);
+                if down {
+                    palette.selected += 1;
+                }
+                if up {
+                    palette.selected = palette.selected.saturating_sub(1);
+                }
+                palette.selected = palette.selected.min(matches.len().saturating_sub(1));
+                if matches.is_empty() {
+                    ui.label(egui::RichText::new("No matching action").weak());
+                }
+
+                egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                    for (row, &item) in matches.iter().enumerate() {
+                        let (enabled, text, shortcut) = match item {
+                            PaletteItem::Action(action) => {
+                                let text = match self.action_checked(action) {
+                                    Some(true) => format!("{}: on", action.name()),
+                                    Some(false) => format!("{}: off", action.name()),
+                                    None => action.name().to_string(),
+                                };
+                                let shortcut = action.shortcut().map(|shortcut| ctx.format_shortcut(&shortcut));
+                                (self.action_enabled(action), text, shortcut)
+                            }
+                            PaletteItem::Macro(index) => {
+                                let m = &self.macros[index];
+                                let shortcut = m.key.map(|key| format!("F{}", key));
+                                let enabled = self.connected && !self.monitor_only;
+                                (enabled, actions::macro_palette_name(m), shortcut)
+                            }
+                        };
+                        ui.horizontal(|ui| {
+                            let selected = row == palette.selected;
+                            let response = ui.add_enabled(enabled, egui::SelectableLabel::new(selected, text));
+                            if selected && (up || down) {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() || (selected && enter && enabled) {
+                                run = Some(item);
+                            }
+                            if let Some(shortcut) = shortcut {
+                                ui.label(egui::RichText::new(shortcut).weak());
+                            }
+                        });
+                    }
+                });
+            });
+
+        if let Some(item) = run {
+            match item {
+                PaletteItem::Action(action) => self.run_action(action),
+                PaletteItem::Macro(index) => {
+                    if let Err(e) = self.run_macro(index) {
+                        self.error_message = Some(e);
+                    }
+                }
+            }
+        } else if open && !escape {
+            self.command_palette = Some(palette);
+        }
+    }
+
+    /// A button that runs `action`, disabled while the action is, with the
+    /// action's shortcut added to the hover text. Screen readers hear the
+    /// action's name, with the hover text as its description.
+    fn action_button(&mut self, ui: &mut egui::Ui, action: Action, button: egui::Button, hover: &str) {
+        let shortcut = action.shortcut().map(|shortcut| ui.ctx().format_shortcut(&shortcut));
+        let hover = match (hover, &shortcut) {
+            ("", Some(shortcut)) => shortcut.clone(),
+            (text, Some(shortcut)) => format!("{} ({})", text, shortcut),
+            (text, None) => text.to_string(),
+        };
+        let mut response = ui.add_enabled(self.action_enabled(action), button).spoken(action.name());
+        ui.ctx().accesskit_node_builder(response.id, |node| {
+            if !hover.is_empty() {
+                node.set_description(hover.as_str());
+            }
+            if let Some(shortcut) = shortcut {
+                node.set_keyboard_shortcut(shortcut);
+            }
+        });
+        if !hover.is_empty() {
+            response = response.on_hover_text(hover);
+        }
+        if response.clicked() {
+            self.run_action(action);
+        }
+    }
+
+    /// A checkbox showing a toggle action's state; a click runs the action.
+    fn action_checkbox(&mut self, ui: &mut egui::Ui, action: Action, label: &str) -> egui::Response {
+        let mut checked = self.action_checked(action).unwrap_or(false);
+        let response = ui.add_enabled(self.action_enabled(action), egui::Checkbox::new(&mut checked, label));
+        if response.changed() {
+            self.run_action(action);
+        }
+        response
+    }
+
+    /// The data the filter dropped, newest first.
+    fn render_rejected_window(&mut self, ctx: &egui::Context) {
+        if !self.rejected_open {
+            return;
+        }
+        let mut open = true;
+        let mut save = false;
+        let mut clear = false;
+        egui::Window::new("Rejected data")
+            .open(&mut open)
+            .default_width(560.0)
+            .default_height(400.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} bytes in {} chunks", self.rejected.len(), self.rejected.chunks().count()));
+                    if self.rejected.evicted() > 0 {
+                        ui.label(
+                            egui::RichText::new(format!("({} older bytes dropped)", self.rejected.evicted())).weak(),
+                        );
+                    }
+                    save = ui.add_enabled(!self.rejected.is_empty(), egui::Button::new("Save")).clicked();
+                    clear = ui.button("Clear").clicked();
+                });
+                ui.separator();
+                egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                    for chunk in self.rejected.chunks().rev() {
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "{}  {} bytes",
+                                chunk.received_at.format("%H:%M:%S%.3f"),
+                                chunk.data.len()
+                            ))
+                            .strong(),
+                        );
+                        ui.label(egui::RichText::new(hex::format_hex(&chunk.data)).monospace());
+                    }
+                });
+            });
+
+        if save {
+            self.save_rejected();
+        }
+        if clear {
+            self.rejected.clear();
+        }
+        if !open {
+            self.rejected_open = false;
+        }
+    }
+
+    /// Field annotations and export of the excerpt picked from the hex view.
+    fn render_excerpt_dialog(&mut self, ctx: &egui::Context) {
+        let Some(excerpt) = &mut self.doc_excerpt else {
+            return;
+        };
+        let mut open = true;
+        let mut export = false;
+        egui::Window::new("Annotate & export excerpt")
+            .collapsible(false)
+            .open(&mut open)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Offset 0x{:08X}, {} bytes",
+                    excerpt.offset,
+                    excerpt.bytes.len()
+                ));
+                egui::ScrollArea::vertical().max_height(220.0).show(ui, |ui| {
+                    ui.label(excerpt_preview(excerpt, ui.visuals().text_color()));
+                });
+                ui.separator();
+
+                let count = excerpt.bytes.len();
+                let mut remove = None;
+                egui::Grid::new("excerpt_fields").num_columns(5).show(ui, |ui| {
+                    ui.label("Field");
+                    ui.label("Start");
+                    ui.label("Length");
+                    ui.label("Colour");
+                    ui.end_row();
+                    for (index, annotation) in excerpt.annotations.iter_mut().enumerate() {
+                        ui.add(egui::TextEdit::singleline(&mut annotation.name).desired_width(140.0))
+                            .spoken("Field name");
+                        ui.add(egui::DragValue::new(&mut annotation.start).range(0..=count - 1)).spoken("Field start");
+                        let max_len = count - annotation.start.min(count - 1);
+                        ui.add(egui::DragValue::new(&mut annotation.len).range(1..=max_len)).spoken("Field length");
+                        ui.color_edit_button_srgb(&mut annotation.color).spoken("Field colour");
+                        if ui.small_button("✖").spoken("Remove field").on_hover_text("Remove field").clicked() {
+                            remove = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(index) = remove {
+                    excerpt.annotations.remove(index);
+                }
+                if ui.button("Add field").clicked() {
+                    // Start where the last field ends, as fields usually follow each other
+                    let start = excerpt.annotations.last().map_or(0, |a| a.start + a.len);
+                    let name = format!("Field {}", excerpt.annotations.len() + 1);
+                    excerpt.annotate(&name, start, 1);
+                }
+                if let Err(e) = excerpt.validate() {
+                    ui.colored_label(self.palette.error, e);
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Format:");
+                    for format in [ExcerptFormat::Html, ExcerptFormat::Markdown] {
+                        ui.selectable_value(&mut self.doc_excerpt_format, format, format.as_str());
+                    }
+                    export = ui.button("Export").clicked();
+                });
+            });
+
+        if export {
+            self.error_message = Some(match self.export_doc_excerpt() {
+                Ok(filename) => format!("Saved excerpt to {} and its annotations to {}", filename, self.doc_excerpt_path),
+                Err(e) => e,
+            });
+        } else if !open {
+            self.doc_excerpt = None;
+        }
+    }
+
+    /// Integer, float and ASCII readings of the selected hex, shown beside the
+    /// selection. The area takes no input, so the terminal keeps focus.
+    fn render_value_popup(&self, ctx: &egui::Context, pos: egui::Pos2) {
+        // Not for a selection scrolled out of view
+        if !self.terminal_rect.contains(pos) {
+            return;
+        }
+        let Some(bytes) = self.terminal_selection.clone().and_then(|range| self.selection_value_bytes(range)) else {
+            return;
+        };
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        egui::Area::new(egui::Id::new("terminal_value_popup"))
+            .order(egui::Order::Tooltip)
+            .interactable(false)
+            .fixed_pos(pos + egui::vec2(0.0, 4.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(egui::RichText::new(hex.join(" ")).monospace().strong());
+                    egui::Grid::new("terminal_value_readings").num_columns(2).show(ui, |ui| {
+                        for reading in inspect::interpret(&bytes) {
+                            ui.label(egui::RichText::new(reading.label).weak());
+                            ui.label(egui::RichText::new(reading.value).monospace());
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+    }
+
+    /// Ctrl+wheel or pinch over the terminal zooms its font; Ctrl+0 there resets
+    /// it, taking the key before egui's own whole-window zoom reset sees it.
+    /// Returns the size ratio when it changed.
+    fn terminal_zoom_input(&mut self, ctx: &egui::Context) -> Option<f32> {
+        let hovered = ctx.pointer_hover_pos().is_some_and(|pos| self.terminal_rect.contains(pos));
+        if !hovered {
+            return None;
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Num0)) {
+            return self.reset_terminal_zoom();
+        }
+        let factor = ctx.input(|i| i.zoom_delta());
+        if factor != 1.0 {
+            return self.zoom_terminal(factor);
+        }
+        None
+    }
+
+    /// Offsets or line numbers beside the terminal rows that start a line.
+    /// Rows wrapped from the line above and lines that are not data are left blank.
+    fn paint_gutter(&self, ui: &egui::Ui, gutter: egui::Rect, galley_pos: egui::Pos2, galley: &egui::Galley) {
+        let font = egui::FontId::monospace(self.terminal_font_size);
+        let color = ui.visuals().weak_text_color();
+        let painter = ui.painter();
+        let mut line = 0;
+        let mut starts_line = true;
+        for row in &galley.rows {
+            if starts_line {
+                let label = match (self.display_lines.get(line).copied().flatten(), self.gutter) {
+                    (Some(start), Gutter::Offset) => format!("{:08X}", start.offset),
+                    (Some(start), Gutter::Line) => start.line.to_string(),
+                    _ => String::new(),
+                };
+                let pos = egui::pos2(gutter.right() - 6.0, galley_pos.y + row.rect.top());
+                painter.text(pos, egui::Align2::RIGHT_TOP, label, font.clone(), color);
+            }
+            if row.ends_with_newline {
+                line += 1;
+            }
+            starts_line = row.ends_with_newline;
+        }
+    }
+
+    /// Scroll to display line `target` and select it.
+    fn scroll_to_display_line(&self, ui: &egui::Ui, terminal_id: egui::Id, galley_pos: egui::Pos2, galley: &egui::Galley, target: usize) {
+        let mut line = 0;
+        let mut char_index = 0;
+        let mut found: Option<(egui::Rect, usize)> = None;
+        for row in &galley.rows {
+            let row_chars = row.glyphs.len() + usize::from(row.ends_with_newline);
+            if line == target {
+                let rect = row.rect.translate(galley_pos.to_vec2());
+                let (rect, start) = match found {
+                    Some((first, start)) => (first.union(rect), start),
+                    None => (rect, char_index),
+                };
+                found = Some((rect, start));
+            }
+            char_index += row_chars;
+            if row.ends_with_newline {
+                if line == target {
+                    break;
+                }
+                line += 1;
+            }
+        }
+        let Some((rect, start)) = found else {
+            return;
+        };
+        ui.scroll_to_rect(rect, Some(egui::Align::Center));
+        if let Some(mut state) = egui::TextEdit::load_state(ui.ctx(), terminal_id) {
+            let end = egui::text::CCursor::new(char_index.saturating_sub(1).max(start));
+            let range = egui::text::CCursorRange::two(egui::text::CCursor::new(start), end);
+            state.cursor.set_char_range(Some(range));
+            state.store(ui.ctx(), terminal_id);
+        }
+    }
+
+    /// The latest pinned values, shown above the terminal.
+    fn render_pinned_values(&self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|ui| {
+            for value in &self.pinned_values {
+                let text = egui::RichText::new(value.display()).monospace().strong();
+                let text = match value.level {
+                    PinLevel::Normal => text,
+                    PinLevel::Warning => text.color(self.palette.warning),
+                    PinLevel::Critical => text.color(self.palette.error),
+                };
+                ui.group(|ui| {
+                    ui.label(format!("{}:", value.name));
+                    ui.label(text);
+                });
+            }
+        });
+    }
+
+    fn render_file_send_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Send File");
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.file_send_path)
+                        .hint_text("path to file...")
+                        .desired_width(ui.available_width() - 50.0),
+                )
+                .spoken("File to send");
+                if ui.button("Load").clicked() {
+                    self.load_file_preview();
+                }
+            });
+
+            let Some(preview) = &self.file_preview else {
+                return;
+            };
+
+            ui.label(egui::RichText::new(format!("{} bytes on disk", preview.size)).small());
+
+            match &preview.firmware {
+                Some(Ok(image)) => {
+                    ui.label(format!(
+                        "{}: {} records, checksums OK",
+                        image.format.as_str(),
+                        image.record_count
+                    ));
+                    if let Some(header) = &image.header {
+                        ui.label(egui::RichText::new(format!("Header: {}", header)).small());
+                    }
+                    for segment in image.segments.iter().take(8) {
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "0x{:08X}-0x{:08X} ({} B)",
+                                segment.address,
+                                segment.end().saturating_sub(1),
+                                segment.data.len()
+                            ))
+                            .monospace()
+                            .small(),
+                        );
+                    }
+                    if image.segments.len() > 8 {
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "... {} more ranges",
+                                image.segments.len() - 8
+                            ))
+                            .small(),
+                        );
+                    }
+                    ui.label(format!("Data: {} bytes", image.total_bytes()));
+                    if let Some(entry) = image.entry_point {
+                        ui.label(format!("Entry point: 0x{:08X}", entry));
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.radio_value(
+                            &mut self.firmware_send_mode,
+                            FirmwareSendMode::RawText,
+                            "Raw lines",
+                        );
+                        ui.radio_value(
+                            &mut self.firmware_send_mode,
+                            FirmwareSendMode::Binary,
+                            "Binary image",
+                        );
+                    });
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(self.palette.error, e.to_string());
+                    ui.label(egui::RichText::new("Only raw sending is available").small());
+                }
+                None => {}
+            }
+
+            let sending_image = matches!(preview.firmware, Some(Ok(_)))
+                && self.firmware_send_mode == FirmwareSendMode::Binary;
+            if preview.is_binary {
+                ui.label(
+                    egui::RichText::new("Binary file: line endings are sent unchanged").small(),
+                );
+            } else if !sending_image {
+                ui.horizontal(|ui| {
+                    ui.label("Line endings:");
+                    egui::ComboBox::from_id_source("file_line_conversion")
+                        .selected_text(self.file_line_conversion.as_str())
+                        .show_ui(ui, |ui: &mut egui::Ui| {
+                            for conversion in [
+                                LineConversion::AsIs,
+                                LineConversion::Lf,
+                                LineConversion::CrLf,
+                                LineConversion::Cr,
+                            ] {
+                                ui.selectable_value(
+                                    &mut self.file_line_conversion,
+                                    conversion,
+                                    conversion.as_str(),
+                                );
+                            }
+                        })
+                        .response
+                        .spoken("Line endings");
+                });
+            }
+
+            if let Some(transfer) = &self.file_transfer {
+                ui.add(
+                    egui::ProgressBar::new(transfer.progress()).text(format!(
+                        "{} / {} bytes",
+                        transfer.sent,
+                        transfer.estimated_total()
+                    )),
+                );
+                if ui.button("Cancel").clicked() {
+                    self.abort_file_send("cancelled");
+                }
+            } else if ui
+                .add_enabled(
+                    self.connected && !self.monitor_only,
+                    egui::Button::new("Send File"),
+                )
+                .clicked()
+            {
+                if let Err(e) = self.start_file_send() {
+                    self.error_message = Some(e);
+                }
+            }
+        });
+    }
+
+    /// XMODEM send of the file loaded in Send File, or receive into a file,
+    /// with progress and a cancel button while it runs.
+    fn render_xmodem_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "XMODEM").on_hover_text(
+                "File transfer to and from bootloaders. While it runs, the terminal shows no transfer traffic \
+                 and other sends wait.",
+            );
+            ui.separator();
+
+            if let Some(run) = &self.xmodem {
+                let progress = run.transfer.progress();
+                let verb = if run.is_receive() { "Receiving into" } else { "Sending" };
+                ui.label(format!("{} {}", verb, run.name));
+                match progress.total {
+                    Some(total) if total > 0 => {
+                        let sent = progress.bytes.min(total);
+                        ui.add(
+                            egui::ProgressBar::new(sent as f32 / total as f32)
+                                .text(format!("{} / {} bytes", sent, total)),
+                        );
+                    }
+                    _ => {
+                        ui.label(format!("{} bytes", progress.bytes));
+                    }
+                }
+                let checksum = progress.checksum.map_or("waiting for the other side", |c| c.as_str());
+                ui.label(format!("{} blocks, {} retries, {}", progress.blocks, progress.retries, checksum));
+                if ui.button("Cancel").clicked() {
+                    self.cancel_xmodem();
+                }
+                return;
+            }
+
+            let can_send = self.connected && !self.monitor_only && self.file_transfer.is_none();
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.xmodem_one_k, "1K blocks")
+                    .on_hover_text("XMODEM-1K: send 1024-byte blocks when the receiver asks for CRC");
+                ui.checkbox(&mut self.xmodem_crc, "CRC").on_hover_text(
+                    "Ask the sender for CRC-16 blocks when receiving; after three tries it falls back to checksums",
+                );
+            });
+            let send = ui
+                .add_enabled(can_send && self.file_preview.is_some(), egui::Button::new("Send via XMODEM"))
+                .on_hover_text("Send the file loaded in Send File")
+                .on_disabled_hover_text("Connect and load a file in Send File");
+            if send.clicked() {
+                if let Err(e) = self.start_xmodem_send() {
+                    self.error_message = Some(e);
+                }
+            }
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.xmodem_receive_path)
+                        .hint_text("file to receive into")
+                        .desired_width(ui.available_width() - 140.0),
+                )
+                .spoken("XMODEM receive file");
+                if ui.add_enabled(can_send, egui::Button::new("Receive via XMODEM")).clicked() {
+                    if let Err(e) = self.start_xmodem_receive() {
+                        self.error_message = Some(e);
+                    }
+                }
+            });
+            if let Some(result) = &self.xmodem_result {
+                ui.label(egui::RichText::new(result).small());
+            }
+        });
+    }
+
+    fn render_event_port_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Event Markers");
+            ui.separator();
+
+            if let Some(port) = &self.event_port {
+                ui.colored_label(self.palette.marker, format!("Listening on {}", port.name));
+                if ui.button("Stop").clicked() {
+                    self.event_port = None;
+                }
+                return;
+            }
+
+            ui.label(egui::RichText::new("Lines from a second port are added as markers").small());
+
+            egui::ComboBox::from_id_source("event_port_selector")
+                .width(ui.available_width())
+                .selected_text(self.event_port_name.as_deref().unwrap_or("Select port..."))
+                .show_ui(ui, |ui: &mut egui::Ui| {
+                    for port in &self.available_ports {
+                        let name = port.port_name.clone();
+                        ui.selectable_value(&mut self.event_port_name, Some(name.clone()), name);
+                    }
+                })
+                .response
+                .spoken("Event marker port");
+
+            ui.horizontal(|ui| {
+                ui.label("Baud:");
+                ui.add(egui::TextEdit::singleline(&mut self.event_baud_rate).desired_width(70.0))
+                    .spoken("Event marker baud rate");
+                if ui.button("Listen").clicked() {
+                    self.open_event_port();
+                }
+            });
+        });
+    }
+
+    fn render_virtual_com_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Virtual COM");
+            ui.separator();
+
+            ui.label(egui::RichText::new("Create loopback COM port pairs").small());
+
+            if let Some(vport) = &self.virtual_com_port.clone() {
+                ui.colored_label(self.palette.ok, vport);
+
+                if ui.button("Clear").clicked() {
+                    self.virtual_com_port = None;
+                }
+            } else {
+                if ui.button("Create/Find Pair").clicked() {
+                    self.create_virtual_com();
+                }
+
+                ui.label(
+                    egui::RichText::new("Click to create or find existing com0com port pairs")
+                        .italics()
+                        .small(),
+                );
+            }
+        });
+    }
+
+    fn render_cable_test_group(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            self.group_heading(ui, "Cable Test");
+            ui.separator();
+
+            let running = self.cable_test.as_ref().is_some_and(|run| !run.finished);
+            ui.add_enabled_ui(!running, |ui| {
+                ui.label(
+                    egui::RichText::new("Loopback plug on the selected port, or a null-modem cable to a second port")
+                        .small(),
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Returns on:");
+                    egui::ComboBox::from_id_source("cable_test_port_b")
+                        .selected_text(self.cable_test_port_b.as_deref().unwrap_or("same port"))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.cable_test_port_b, None, "same port");
+                            for port in &self.available_ports {
+                                let name = Some(port.port_name.clone());
+                                ui.selectable_value(&mut self.cable_test_port_b, name, &port.port_name);
+                            }
+                        })
+                        .response
+                        .spoken("Returns on");
+                });
+
+                let config = &mut self.cable_test_config;
+                ui.label("Baud rates:");
+                ui.horizontal_wrapped(|ui| {
+                    for baud in cable_test::TEST_BAUD_RATES {
+                        let mut on = config.baud_rates.contains(&baud);
+                        if ui.checkbox(&mut on, baud.to_string()).changed() {
+                            config.baud_rates.retain(|&b| b != baud);
+                            if on {
+                                config.baud_rates.push(baud);
+                                config.baud_rates.sort_unstable();
+                            }
+                        }
+                    }
+                });
+                ui.horizontal_wrapped(|ui| {
+                    for pattern in TestPattern::ALL {
+                        let mut on = config.patterns.contains(&pattern);
+                        if ui.checkbox(&mut on, pattern.as_str()).changed() {
+                            config.patterns.retain(|&p| p != pattern);
+                            if on {
+                                config.patterns.push(pattern);
+                            }
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Bytes per test:");
+                    ui.add(egui::DragValue::new(&mut config.pattern_len).range(16..=65536)).spoken("Bytes per test");
+                });
+                ui.checkbox(&mut config.check_handshake, "Check RTS/CTS and DTR/DSR wiring");
+            });
+
+            ui.horizontal(|ui| {
+                if running {
+                    ui.spinner();
+                    if ui.button("Cancel").clicked() {
+                        if let Some(run) = &self.cable_test {
+                            run.cancel();
+                        }
+                    }
+                } else if ui
+                    .add_enabled(!self.connected && !self.monitor_only, egui::Button::new("Run test"))
+                    .on_disabled_hover_text(if self.monitor_only {
+                        "Monitor only is on; the test sends patterns and toggles RTS/DTR"
+                    } else {
+                        "Disconnect first; the test opens the port itself"
+                    })
+                    .clicked()
+                {
+                    self.start_cable_test();
+                }
+            });
+
+            let Some(run) = &self.cable_test else {
+                return;
+            };
+            for outcome in &run.outcomes {
+                let color = if outcome.passed { self.palette.ok } else { self.palette.error };
+                ui.colored_label(color, egui::RichText::new(outcome.line()).small().monospace());
+            }
+            if run.finished {
+                let passed = run.outcomes.iter().filter(|o| o.passed).count();
+                let all = passed == run.outcomes.len() && !run.outcomes.is_empty() && !run.is_cancelled();
+                ui.colored_label(
+                    if all { self.palette.ok } else { self.palette.error },
+                    format!("{}/{} passed", passed, run.outcomes.len()),
+                );
+                let mut export = false;
+                ui.horizontal(|ui| {
+                    if ui.button("Copy").clicked() {
+                        ui.ctx().copy_text(run.summary());
+                    }
+                    export = ui.button("Export").clicked();
+                });
+                if export {
+                    self.export_cable_test();
+                }
+            }
+        });
+    }
+
+    fn render_central_panel(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                // Terminal header
+                ui.horizontal(|ui| {
+                    ui.heading("Terminal");
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        self.action_button(ui, Action::ClearTerminal, egui::Button::new("Clear"), "");
+                        self.action_button(ui, Action::GoTo, egui::Button::new("Go to…"), "");
+                        self.action_button(ui, Action::SaveTerminal, egui::Button::new("Save"), "");
+                        if let Some((count, line)) = &self.announcement {
+                            // A fresh widget per reading, so the same line is announced again
+                            ui.push_id(count, |ui| {
+                                ui.add(egui::Label::new(egui::RichText::new(line).weak()).truncate()).live();
+                            });
+                        }
+                    });
+                });
+
+                ui.separator();
+
+                if !self.pinned_values.is_empty() {
+                    self.render_pinned_values(ui);
+                    ui.separator();
+                }
+
+                if let Some(protocol) = self.protocol_suggestion {
+                    self.render_protocol_suggestion(ui, protocol);
+                }
+                if let Some(encoding) = self.encoding_suggestion {
+                    self.render_encoding_suggestion(ui, encoding);
+                }
+
+                if self.recording.is_some() {
+                    // Raw recording: the reader thread writes RX to disk, nothing to show here
+                    let height = ui.available_height() - 60.0;
+                    self.render_recording_view(ui, height);
+                } else {
+                    // Receive area; rules edited since the last render colour it again
+                    if self.highlight_rules() != self.display_highlight_rules {
+                        self.update_display_buffer();
+                    }
+                    let show_frames = self.decoder.is_some() && self.decoded_view() == DecodedView::Raw;
+                    let text_height = if show_frames {
+                        (ui.available_height() - 60.0) * 0.6
+                    } else {
+                        ui.available_height() - 60.0
+                    };
+
+                    // Hex rows keep their columns; text wraps to the panel
+                    let wrap = matches!(self.view_mode, ViewMode::Ascii | ViewMode::Auto);
+                    let mut scroll_area = if wrap {
+                        egui::ScrollArea::vertical()
+                    } else {
+                        egui::ScrollArea::both()
+                    };
+                    if let Some(ratio) = self.terminal_zoom_input(ui.ctx()) {
+                        // Scale the offset with the text so the same rows stay in view
+                        scroll_area = scroll_area.scroll_offset(self.terminal_scroll_offset * ratio);
+                    }
+                    let font = egui::FontId::monospace(self.terminal_font_size);
+
+                    let scroll_output = scroll_area
+                        .id_source("terminal_scroll")
+                        .auto_shrink([false; 2])
+                        .stick_to_bottom(self.auto_scroll)
+                        .max_height(text_height)
+                        .show(ui, |ui| {
+                            let marker_color = self.palette.marker;
+                            let highlights = &self.display_highlights;
+                            let highlighting = !self.display_highlight_rules.is_empty();
+                            let layout_font = font.clone();
+                            let mut marker_layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                                let font = layout_font.clone();
+                                let normal = ui.visuals().text_color();
+                                let mut job = egui::text::LayoutJob::default();
+                                for (index, line) in text.split_inclusive('\n').enumerate() {
+                                    let color = if event_port::is_marker_line(line) {
+                                        marker_color
+                                    } else if let Some([r, g, b]) = highlights.get(index).copied().flatten() {
+                                        egui::Color32::from_rgb(r, g, b)
+                                    } else {
+                                        normal
+                                    };
+                                    job.append(line, 0.0, egui::TextFormat::simple(font.clone(), color));
+                                }
+                                job.wrap.max_width = if wrap { wrap_width } else { f32::INFINITY };
+                                ui.fonts(|fonts| fonts.layout_job(job))
+                            };
+
+                            let terminal_id = egui::Id::new("terminal");
+                            self.focus_areas.push(terminal_id);
+                            let goto_line = self.goto_line.take();
+                            let mut display = self.receive_buffer_display.as_str();
+                            let mut terminal = egui::TextEdit::multiline(&mut display)
+                                .id(terminal_id)
+                                .font(font.clone())
+                                .desired_width(f32::INFINITY)
+                                .desired_rows(30);
+                            if self.has_event_markers || highlighting || !wrap {
+                                terminal = terminal.layouter(&mut marker_layouter);
+                            }
+                            let output = ui
+                                .horizontal_top(|ui| {
+                                    let gutter = (self.gutter != Gutter::Off).then(|| {
+                                        let width = ui.fonts(|f| f.glyph_width(&font, '0')) * 8.0 + 10.0;
+                                        ui.allocate_exact_size(egui::vec2(width, 0.0), egui::Sense::hover()).0
+                                    });
+                                    let output = terminal.show(ui);
+                                    if let Some(gutter) = gutter {
+                                        self.paint_gutter(ui, gutter, output.galley_pos, &output.galley);
+                                    }
+                                    if let Some(target) = goto_line {
+                                        let galley = &output.galley;
+                                        self.scroll_to_display_line(ui, terminal_id, output.galley_pos, galley, target);
+                                    }
+                                    output
+                                })
+                                .inner;
+                            ui.ctx().accesskit_node_builder(terminal_id, |node| node.set_name("Terminal"));
+
+                            if let Some(range) = output.cursor_range {
+                                let chars = range.as_sorted_char_range();
+                                self.terminal_selection = (!chars.is_empty()).then_some(chars);
+                                // Wait until the drag ends so the popup does not chase the pointer
+                                if !ui.input(|i| i.pointer.any_down()) {
+                                    let end = output.galley.pos_from_cursor(&range.primary);
+                                    self.render_value_popup(ui.ctx(), output.galley_pos + end.left_bottom().to_vec2());
+                                }
+                            }
+
+                            output.response.context_menu(|ui| {
+                                let Some(range) = self.terminal_selection.clone() else {
+                                    ui.label("Select text to create a filter");
+                                    return;
+                                };
+
+                                if ui.button("Create filter from selection").clicked() {
+                                    let text = self.selection_text(range.clone());
+                                    self.add_filter_rule(filter::text_pattern(&text));
+                                    ui.close_menu();
+                                }
+
+                                if ui.button("Create hex filter from selection").clicked() {
+                                    let bytes = self.selection_bytes(range.clone());
+                                    if bytes.is_empty() {
+                                        self.error_message =
+                                            Some("Selection contains no data bytes".to_string());
+                                    } else {
+                                        self.add_filter_rule(filter::hex_pattern(&bytes));
+                                    }
+                                    ui.close_menu();
+                                }
+
+                                ui.separator();
+                                if ui.button("Copy as text payload").clicked() {
+                                    let text = self.selection_text(range.clone());
+                                    self.recent_payloads.add(&text, SendMode::Ascii, "");
+                                    ui.ctx().copy_text(text);
+                                    ui.close_menu();
+                                }
+
+                                if ui.button("Copy as hex payload").clicked() {
+                                    let bytes = self.selection_bytes(range.clone());
+                                    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+                                    let hex = hex.join(" ");
+                                    self.recent_payloads.add(&hex, SendMode::Hex, "");
+                                    ui.ctx().copy_text(hex);
+                                    ui.close_menu();
+                                }
+
+                                if matches!(self.view_mode, ViewMode::Hex | ViewMode::Both) {
+                                    ui.separator();
+                                    if ui.button("Annotate & export excerpt…").clicked() {
+                                        match self.selection_excerpt(range) {
+                                            Ok(excerpt) => self.doc_excerpt = Some(excerpt),
+                                            Err(e) => self.error_message = Some(e),
+                                        }
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        });
+                    self.terminal_rect = scroll_output.inner_rect;
+                    self.terminal_scroll_offset = scroll_output.state.offset;
+
+                    if show_frames {
+                        ui.separator();
+                        self.render_frame_pane(ui);
+                    }
+                }
 
                 ui.add_space(5.0);
                 ui.separator();
 
+                if self.confirm_stale_send {
+                    self.render_stale_send_prompt(ui);
+                }
+
+                if !self.macros.is_empty() {
+                    self.render_macro_bar(ui);
+                }
+
                 // Send area
                 ui.horizontal(|ui| {
+                    if self.monitor_only {
+                        ui.disable();
+                    }
+
+                    egui::ComboBox::from_id_source("send_discipline")
+                        .width(90.0)
+                        .selected_text(self.send_discipline.as_str())
+                        .show_ui(ui, |ui: &mut egui::Ui| {
+                            for discipline in SendDiscipline::ALL {
+                                ui.selectable_value(&mut self.send_discipline, discipline, discipline.as_str());
+                            }
+                        })
+                        .response
+                        .spoken("Send from")
+                        .on_hover_text("Send from the box, or type straight to the port per character or per line");
+
                     // Mode toggle
                     ui.selectable_value(&mut self.send_mode, SendMode::Ascii, "ASCII");
                     ui.selectable_value(&mut self.send_mode, SendMode::Hex, "Hex");
@@ -584,40 +5077,426 @@ impl ComAnalyzerApp {
                                     LineEnding::CrLf,
                                     "\\r\\n",
                                 );
-                            });
+                            })
+                            .response
+                            .spoken("Line ending");
+                        ui.checkbox(&mut self.parse_escapes, "Parse escapes").on_hover_text(
+                            "Send \\r \\n \\t \\0 \\e \\\\ and \\xHH as the bytes they stand for; \
+                             off, backslashes are sent as typed",
+                        );
                     }
 
+                    egui::ComboBox::from_id_source("send_encoding")
+                        .width(50.0)
+                        .selected_text(self.send_encoding.as_str())
+                        .show_ui(ui, |ui: &mut egui::Ui| {
+                            ui.selectable_value(&mut self.send_encoding, SendEncoding::None, "Raw");
+                            ui.selectable_value(&mut self.send_encoding, SendEncoding::Slip, "SLIP");
+                            ui.selectable_value(&mut self.send_encoding, SendEncoding::Cobs, "COBS");
+                            ui.selectable_value(&mut self.send_encoding, SendEncoding::StxEtx, "STX/ETX");
+                        })
+                        .response
+                        .spoken("Send encoding")
+                        .on_hover_text("Encode the payload before sending");
+
                     let hint = match self.send_mode {
+                        _ if self.monitor_only => "Monitor only: sending is disabled",
                         SendMode::Ascii => "Type message here...",
                         SendMode::Hex => "AA BB 0D 0A ...",
                     };
 
+                    if self.send_discipline != SendDiscipline::SendBox {
+                        self.render_typing_input(ui);
+                        return;
+                    }
+
+                    self.render_recent_payloads_menu(ui);
+                    self.render_send_history_menu(ui);
+
+                    // Enter is taken off the queue before the text field sees it,
+                    // so the field keeps focus; Shift+Enter is its return key. Up
+                    // on the first line and Down on the last recall the history.
                     let send_id = egui::Id::new("send_input");
+                    let cursor = egui::text_edit::TextEditState::load(ui.ctx(), send_id)
+                        .and_then(|state| state.cursor.char_range())
+                        .map_or(usize::MAX, |range| range.primary.index);
+                    let before: String = self.send_buffer.chars().take(cursor).collect();
+                    let on_first_line = !before.contains('\n');
+                    let on_last_line = before.len() == self.send_buffer.len()
+                        || !self.send_buffer[before.len()..].contains('\n');
+                    let mut enter_pressed = false;
+                    let mut recall = None;
+                    if ui.memory(|mem| mem.has_focus(send_id)) {
+                        ui.input_mut(|i| {
+                            i.events.retain(|event| match keyboard::send_box_key(event) {
+                                Some(SendBoxKey::Send) => {
+                                    enter_pressed = true;
+                                    false
+                                }
+                                Some(SendBoxKey::Older) if on_first_line => {
+                                    recall = Some(true);
+                                    false
+                                }
+                                Some(SendBoxKey::Newer) if on_last_line => {
+                                    recall = Some(false);
+                                    false
+                                }
+                                _ => true,
+                            })
+                        });
+                    }
+                    if let Some(older) = recall {
+                        if self.recall_send_history(older) {
+                            let mut state =
+                                egui::text_edit::TextEditState::load(ui.ctx(), send_id).unwrap_or_default();
+                            let end = egui::text::CCursor::new(self.send_buffer.chars().count());
+                            state.cursor.set_char_range(Some(egui::text::CCursorRange::one(end)));
+                            state.store(ui.ctx(), send_id);
+                        }
+                    }
                     let response = ui.add(
-                        egui::TextEdit::singleline(&mut self.send_buffer)
+                        egui::TextEdit::multiline(&mut self.send_buffer)
                             .id(send_id)
-                            .desired_width(ui.available_width() - 60.0)
+                            .desired_rows(1)
+                            .return_key(egui::KeyboardShortcut::new(egui::Modifiers::SHIFT, egui::Key::Enter))
+                            .desired_width(ui.available_width() - 230.0)
                             .hint_text(hint),
-                    );
+                    )
+                    .spoken("Send box");
+                    self.focus_areas.push(send_id);
+
+                    if response.changed() {
+                        self.send_generation = Some(self.connection_generation);
+                        self.confirm_stale_send = false;
+                        self.guard_confirmation = None;
+                    }
 
                     let send_clicked = ui.button("Send").clicked();
-                    let enter_pressed =
-                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if send_clicked {
+                        // The click took focus from the text field
+                        ui.memory_mut(|mem| mem.request_focus(send_id));
+                    }
 
                     if send_clicked || enter_pressed {
                         if self.connected && !self.send_buffer.is_empty() {
-                            match self.send_mode {
-                                SendMode::Ascii => self.send_data(),
-                                SendMode::Hex => self.send_hex_input(),
+                            if let Err(e) = self.submit_send(false) {
+                                self.error_message = Some(e);
                             }
                         } else if !self.connected {
                             self.error_message = Some("Not connected".to_string());
                         }
-                        // Always re-focus the input after send/enter
-                        ui.memory_mut(|mem| mem.request_focus(send_id));
+                    }
+
+                    let repeating = self.repeat.is_running();
+                    let label = if repeating { "Stop" } else { "Repeat" };
+                    self.action_button(
+                        ui,
+                        Action::Repeat,
+                        egui::Button::new(label).selected(repeating),
+                        "Send the box again every interval until stopped or disconnected",
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut self.repeat_interval_ms)
+                            .range(MIN_REPEAT_MS..=MAX_REPEAT_MS)
+                            .suffix(" ms"),
+                    )
+                    .spoken("Repeat interval");
+                    if repeating || self.repeat.fired > 0 {
+                        ui.label(format!("×{}", self.repeat.fired))
+                            .on_hover_text("Sends since the repeat started");
                     }
                 });
             });
         });
     }
+
+    /// Keyboard focus target for per-character and per-line typing. Click it,
+    /// then type; in per-line mode the line is edited here until Enter.
+    fn render_typing_input(&mut self, ui: &mut egui::Ui) {
+        let id = egui::Id::new("typing_input");
+        let size = egui::vec2(ui.available_width(), ui.spacing().interact_size.y);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let response = ui.interact(rect, id, egui::Sense::click());
+        self.focus_areas.push(id);
+        response.widget_info(|| {
+            let name = match self.send_discipline {
+                SendDiscipline::Line => "Type a line to send",
+                _ => "Type straight to the port",
+            };
+            egui::WidgetInfo::labeled(egui::WidgetType::TextEdit, ui.is_enabled(), name)
+        });
+        if response.clicked() {
+            response.request_focus();
+        }
+
+        let focused = response.has_focus();
+        if focused {
+            // Keep Tab, arrows and Escape for the port instead of egui navigation
+            ui.memory_mut(|mem| {
+                mem.set_focus_lock_filter(
+                    id,
+                    egui::EventFilter {
+                        tab: true,
+                        horizontal_arrows: true,
+                        vertical_arrows: true,
+                        escape: true,
+                    },
+                )
+            });
+            let events = ui.input(|i| i.events.clone());
+            for event in &events {
+                let result = match self.send_discipline {
+                    SendDiscipline::Line => match keyboard::edit_line(&mut self.line_editor, event) {
+                        LineKey::Enter if !self.monitor_only => self.send_typed_line(),
+                        LineKey::Edited => {
+                            self.send_generation = Some(self.connection_generation);
+                            self.confirm_stale_send = false;
+                            self.guard_confirmation = None;
+                            Ok(())
+                        }
+                        _ => Ok(()),
+                    },
+                    _ => {
+                        let enter = match self.line_ending {
+                            LineEnding::None => b"\r".to_vec(),
+                            ending => ending.as_bytes().to_vec(),
+                        };
+                        match keyboard::keystroke_bytes(event, &enter) {
+                            Some(_) if !self.connected => Err("Not connected".to_string()),
+                            Some(bytes) => self.send_keystroke(&bytes),
+                            None => Ok(()),
+                        }
+                    }
+                };
+                if let Err(e) = result {
+                    self.error_message = Some(e);
+                }
+            }
+        }
+
+        let visuals = ui.style().interact_selectable(&response, focused);
+        let painter = ui.painter_at(rect);
+        painter.rect(rect, 2.0, ui.visuals().extreme_bg_color, visuals.bg_stroke);
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        let text_pos = rect.left_center() + egui::vec2(4.0, 0.0);
+        match self.send_discipline {
+            SendDiscipline::Line if focused || !self.line_editor.is_empty() => {
+                let text = format!("> {}", self.line_editor.text());
+                painter.text(text_pos, egui::Align2::LEFT_CENTER, &text, font.clone(), ui.visuals().text_color());
+                if focused {
+                    // Cursor after "> " plus the characters before it
+                    let before: String = text.chars().take(self.line_editor.cursor() + 2).collect();
+                    let width = ui.fonts(|f| f.layout_no_wrap(before, font.clone(), egui::Color32::WHITE).size().x);
+                    let x = text_pos.x + width;
+                    painter.vline(x, rect.shrink(3.0).y_range(), visuals.fg_stroke);
+                }
+            }
+            _ => {
+                let hint = match (self.send_discipline, focused) {
+                    _ if self.monitor_only => "Monitor only: sending is disabled",
+                    (SendDiscipline::Character, true) => "Typing goes straight to the port (Esc, arrows and Ctrl+letter included)",
+                    (SendDiscipline::Line, _) => "Click here and type; Enter sends the line, Esc clears it",
+                    _ => "Click here to type straight to the port",
+                };
+                painter.text(text_pos, egui::Align2::LEFT_CENTER, hint, font, ui.visuals().weak_text_color());
+            }
+        }
+    }
+
+    /// Dropdown of recent payloads; clicking one puts it in the send box.
+    fn render_recent_payloads_menu(&mut self, ui: &mut egui::Ui) {
+        let mut insert = None;
+        let mut remove = None;
+        ui.add_enabled_ui(!self.recent_payloads.entries().is_empty(), |ui| {
+            ui.menu_button("Recent", |ui| {
+                for (index, entry) in self.recent_payloads.entries_mut().iter_mut().enumerate() {
+                    ui.push_id(index, |ui| {
+                        ui.horizontal(|ui| {
+                            let label = format!("[{}] {}", entry.mode.as_str(), entry.text);
+                            if ui
+                                .add(egui::Button::new(egui::RichText::new(label).monospace()).truncate())
+                                .clicked()
+                            {
+                                insert = Some(index);
+                            }
+                            ui.add(
+                                egui::TextEdit::singleline(&mut entry.description)
+                                    .hint_text("description")
+                                    .desired_width(120.0),
+                            )
+                            .spoken("Payload description");
+                            if ui.small_button("x").spoken("Remove").clicked() {
+                                remove = Some(index);
+                            }
+                        });
+                    });
+                }
+            })
+            .response
+            .on_hover_text("Recently sent or copied payloads");
+        });
+
+        if let Some(index) = insert {
+            self.insert_recent_payload(index);
+            ui.close_menu();
+        } else if let Some(index) = remove {
+            self.recent_payloads.remove(index);
+        }
+    }
+
+    /// Dropdown of the send history for the current mode, newest first.
+    fn render_send_history_menu(&mut self, ui: &mut egui::Ui) {
+        let entries = self.send_history.entries(self.send_mode);
+        let mut insert = None;
+        ui.add_enabled_ui(!entries.is_empty(), |ui| {
+            ui.menu_button("History", |ui| {
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for (index, text) in entries.iter().enumerate().rev() {
+                        let label = egui::RichText::new(text.replace('\n', "\\n")).monospace();
+                        if ui.add(egui::Button::new(label).truncate()).clicked() {
+                            insert = Some(index);
+                        }
+                    }
+                });
+            })
+            .response
+            .on_hover_text(format!("{} send box history (Up/Down in the box)", self.send_mode.as_str()));
+        });
+
+        if let Some(index) = insert {
+            self.insert_history_entry(index);
+            ui.close_menu();
+        }
+    }
+
+    fn render_stale_send_prompt(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.colored_label(
+                self.palette.warning,
+                "The connection was re-established since you typed this. Send anyway?",
+            );
+            if ui.button("Send anyway").clicked() {
+                if let Err(e) = self.submit_send(true) {
+                    self.error_message = Some(e);
+                }
+            }
+            if ui.button("Keep editing").clicked() {
+                self.confirm_stale_send = false;
+            }
+        });
+    }
+
+    fn render_protocol_suggestion(&mut self, ui: &mut egui::Ui, protocol: DetectedProtocol) {
+        egui::Frame::none()
+            .fill(ui.visuals().faint_bg_color)
+            .rounding(4.0)
+            .inner_margin(4.0)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if protocol.settings().is_some() {
+                        ui.label(format!("Looks like {} — enable decoder?", protocol.as_str()));
+                        if ui.small_button("Enable").clicked() {
+                            self.accept_protocol_suggestion();
+                        }
+                    } else {
+                        ui.label(format!("Looks like {}", protocol.as_str()));
+                    }
+                    if ui.small_button("x").on_hover_text("Don't suggest again").clicked() {
+                        self.protocol_suggestion = None;
+                        self.suggestion_dismissed = true;
+                    }
+                });
+            });
+    }
+
+    fn render_encoding_suggestion(&mut self, ui: &mut egui::Ui, encoding: TextEncoding) {
+        egui::Frame::none()
+            .fill(ui.visuals().faint_bg_color)
+            .rounding(4.0)
+            .inner_margin(4.0)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Looks like {} text — decode it?", encoding.as_str()));
+                    if ui.small_button("Decode").clicked() {
+                        self.accept_encoding_suggestion();
+                    }
+                    if ui.small_button("x").on_hover_text("Keep decoding as UTF-8").clicked() {
+                        self.encoding_suggestion = None;
+                    }
+                });
+            });
+    }
+
+    fn render_frame_pane(&mut self, ui: &mut egui::Ui) {
+        let pane_height = ui.available_height() - 60.0;
+
+        egui::ScrollArea::vertical()
+            .id_source("frame_pane")
+            .auto_shrink([false; 2])
+            .stick_to_bottom(self.auto_scroll)
+            .max_height(pane_height)
+            .show(ui, |ui| {
+                for entry in &self.decoded_frames {
+                    if !self.slave_visible(entry.slave) {
+                        continue;
+                    }
+                    let color = if entry.frame.error.is_some() {
+                        self.palette.error
+                    } else if !entry.frame.violations.is_empty() {
+                        self.palette.marker
+                    } else if entry.frame.resync.is_some() {
+                        self.palette.warning
+                    } else if let Some(slave) = entry.slave {
+                        self.palette.slave(slave)
+                    } else {
+                        ui.visuals().text_color()
+                    };
+                    ui.label(egui::RichText::new(entry.line()).monospace().color(color));
+                }
+            });
+    }
+}
+
+/// A combo box choosing a syslog severity by name.
+fn severity_combo(ui: &mut egui::Ui, id: &str, severity: &mut u8) {
+    egui::ComboBox::from_id_source(id)
+        .width(110.0)
+        .selected_text(SEVERITY_NAMES[usize::from((*severity).min(7))])
+        .show_ui(ui, |ui: &mut egui::Ui| {
+            for (code, name) in SEVERITY_NAMES.iter().enumerate() {
+                ui.selectable_value(severity, code as u8, format!("{} {}", code, name));
+            }
+        })
+        .response
+        .spoken("Severity");
+}
+
+/// The excerpt as offset/hex/ASCII rows with each field's bytes on its colour.
+fn excerpt_preview(excerpt: &doc_excerpt::Excerpt, text: egui::Color32) -> egui::text::LayoutJob {
+    let mut job = egui::text::LayoutJob::default();
+    let plain = egui::TextFormat::simple(egui::FontId::monospace(12.0), text);
+    let cell = |job: &mut egui::text::LayoutJob, index: usize, cell: &str| {
+        let mut format = plain.clone();
+        if let Some(field) = excerpt.annotation_index(index) {
+            let [r, g, b] = excerpt.annotations[field].color;
+            format.background = egui::Color32::from_rgb(r, g, b);
+            format.color = egui::Color32::BLACK;
+        }
+        job.append(cell, 0.0, format);
+    };
+    for (row, chunk) in excerpt.bytes.chunks(EXCERPT_ROW_BYTES).enumerate() {
+        let base = row * EXCERPT_ROW_BYTES;
+        job.append(&format!("{:08X}  ", excerpt.offset + base as u64), 0.0, plain.clone());
+        for (i, byte) in chunk.iter().enumerate() {
+            cell(&mut job, base + i, &format!("{:02X}", byte));
+            job.append(" ", 0.0, plain.clone());
+        }
+        job.append(&" ".repeat((EXCERPT_ROW_BYTES - chunk.len()) * 3 + 1), 0.0, plain.clone());
+        for (i, &byte) in chunk.iter().enumerate() {
+            let ch = if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' };
+            cell(&mut job, base + i, &ch.to_string());
+        }
+        job.append("\n", 0.0, plain.clone());
+    }
+    job
 }